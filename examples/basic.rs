@@ -21,6 +21,7 @@ fn main() -> io::Result<()> {
                 println!("\nAborted!");
                 break Ok(());
             }
+            Signal::Custom { .. } => {}
         }
     }
 }
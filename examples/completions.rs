@@ -90,6 +90,7 @@ fn main() -> io::Result<()> {
                 println!("\nAborted!");
                 break Ok(());
             }
+            Signal::Custom { .. } => {}
         }
     }
 }
@@ -70,6 +70,7 @@ fn main() -> io::Result<()> {
                 println!("\nAborted!");
                 break Ok(());
             }
+            Signal::Custom { .. } => {}
         }
     }
 }
@@ -218,6 +218,7 @@ fn main() -> reedline::Result<()> {
             Err(err) => {
                 println!("Error: {err:?}");
             }
+            Ok(Signal::Custom { .. }) => {}
         }
     }
 
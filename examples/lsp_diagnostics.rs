@@ -33,11 +33,7 @@ fn main() -> io::Result<()> {
         std::process::exit(1);
     };
 
-    let config = LspConfig {
-        command,
-        timeout_ms: 100,
-        uri_scheme: "repl".to_string(),
-    };
+    let config = LspConfig::new(command);
 
     // Create the diagnostics provider
     let diagnostics = LspDiagnosticsProvider::new(config);
@@ -61,6 +57,7 @@ fn main() -> io::Result<()> {
     println!();
     println!("Type nushell code to see diagnostics as underlines while typing.");
     println!("Press Ctrl+. or Alt+f to open the fix menu when on a diagnostic.");
+    println!("Press Alt+s to jump to a symbol, typing to filter them.");
     println!("Press Ctrl+C to exit.");
     println!();
 
@@ -76,13 +73,14 @@ fn main() -> io::Result<()> {
                 println!("\nGoodbye!");
                 break;
             }
+            Signal::Custom { .. } => {}
         }
     }
 
     Ok(())
 }
 
-/// Add keybinding for the diagnostic fix menu (Alt+f and Ctrl+.)
+/// Add keybindings for the diagnostic fix menu (Alt+f and Ctrl+.) and the symbol menu (Alt+s)
 fn add_diagnostic_fix_keybinding(keybindings: &mut Keybindings) {
     keybindings.add_binding(
         KeyModifiers::ALT,
@@ -94,6 +92,11 @@ fn add_diagnostic_fix_keybinding(keybindings: &mut Keybindings) {
         KeyCode::Char('.'),
         ReedlineEvent::OpenDiagnosticFixMenu,
     );
+    keybindings.add_binding(
+        KeyModifiers::ALT,
+        KeyCode::Char('s'),
+        ReedlineEvent::OpenSymbolMenu,
+    );
     // Add Tab/Shift-Tab for menu navigation
     keybindings.add_binding(KeyModifiers::NONE, KeyCode::Tab, ReedlineEvent::MenuNext);
     keybindings.add_binding(
@@ -101,4 +104,15 @@ fn add_diagnostic_fix_keybinding(keybindings: &mut Keybindings) {
         KeyCode::BackTab,
         ReedlineEvent::MenuPrevious,
     );
+    // PageUp/PageDown move a page through long menus
+    keybindings.add_binding(
+        KeyModifiers::NONE,
+        KeyCode::PageUp,
+        ReedlineEvent::MenuPagePrevious,
+    );
+    keybindings.add_binding(
+        KeyModifiers::NONE,
+        KeyCode::PageDown,
+        ReedlineEvent::MenuPageNext,
+    );
 }
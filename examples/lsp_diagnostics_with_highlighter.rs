@@ -35,11 +35,7 @@ fn main() -> io::Result<()> {
         std::process::exit(1);
     };
 
-    let config = LspConfig {
-        command,
-        timeout_ms: 100,
-        uri_scheme: "repl".to_string(),
-    };
+    let config = LspConfig::new(command);
 
     // Create the diagnostics provider
     let diagnostics = LspDiagnosticsProvider::new(config);
@@ -130,6 +126,7 @@ fn main() -> io::Result<()> {
                 println!("\nGoodbye!");
                 break;
             }
+            Signal::Custom { .. } => {}
         }
     }
 
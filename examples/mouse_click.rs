@@ -5,11 +5,14 @@ use reedline::{DefaultPrompt, MouseClickMode, Reedline, Signal};
 use std::io;
 
 fn main() -> io::Result<()> {
-    let mut line_editor = Reedline::create().with_mouse_click(MouseClickMode::EnabledWithOsc133);
+    let mut line_editor = Reedline::create()
+        .with_mouse_click(MouseClickMode::EnabledWithOsc133)
+        .with_mouse_support(true);
     let prompt = DefaultPrompt::default();
 
     println!("Mouse click-to-cursor enabled.");
     println!("Type some text, then click in the line to move the cursor.");
+    println!("Drag to select text, double-click to select a word.");
     println!("Press Enter to submit, Ctrl-D/Ctrl-C to exit.");
 
     loop {
@@ -22,6 +25,7 @@ fn main() -> io::Result<()> {
                 println!("\nAborted!");
                 break Ok(());
             }
+            Signal::Custom { .. } => {}
         }
     }
 }
@@ -0,0 +1,27 @@
+// Create a reedline object coloring brackets by how deeply they are nested.
+// cargo run --example rainbow_brackets
+//
+// brackets are colored over the highlighting of the example highlighter
+use reedline::{DefaultPrompt, ExampleHighlighter, RainbowBracketHighlighter, Reedline, Signal};
+use std::io;
+
+fn main() -> io::Result<()> {
+    let commands = vec!["ls".into(), "each".into(), "where".into()];
+    let highlighter = RainbowBracketHighlighter::new(Box::new(ExampleHighlighter::new(commands)));
+    let mut line_editor = Reedline::create().with_highlighter(Box::new(highlighter));
+    let prompt = DefaultPrompt::default();
+
+    loop {
+        let sig = line_editor.read_line(&prompt)?;
+        match sig {
+            Signal::Success(buffer) => {
+                println!("We processed: {buffer}");
+            }
+            Signal::CtrlD | Signal::CtrlC => {
+                println!("\nAborted!");
+                break Ok(());
+            }
+            Signal::Custom { .. } => {}
+        }
+    }
+}
@@ -0,0 +1,81 @@
+// Create a reedline object with completions painted in styles of their own
+// cargo run --example styled_completions
+//
+// [Tab] lists directories in blue, executables in green and deprecated
+// options dimmed, also while they are selected
+
+use nu_ansi_term::{Color, Style};
+use reedline::{
+    default_emacs_keybindings, ColumnarMenu, Completer, DefaultPrompt, Emacs, KeyCode,
+    KeyModifiers, MenuBuilder, Reedline, ReedlineEvent, ReedlineMenu, Signal, Span, Suggestion,
+};
+use std::io;
+
+/// Completes the word before the cursor from a fixed list of styled entries
+struct StyledCompleter(Vec<(&'static str, Option<Style>, &'static str)>);
+
+impl Completer for StyledCompleter {
+    fn complete(&mut self, line: &str, pos: usize) -> Vec<Suggestion> {
+        let start = line[..pos].rfind(' ').map_or(0, |i| i + 1);
+        let word = &line[start..pos];
+        self.0
+            .iter()
+            .filter(|(value, _, _)| value.starts_with(word))
+            .map(|(value, style, description)| Suggestion {
+                value: value.to_string(),
+                description: Some(description.to_string()),
+                style: *style,
+                span: Span::new(start, pos),
+                append_whitespace: true,
+                ..Default::default()
+            })
+            .collect()
+    }
+}
+
+fn main() -> io::Result<()> {
+    let directory = Some(Color::Blue.bold());
+    let executable = Some(Color::Green.normal());
+    let deprecated = Some(Style::new().dimmed().strikethrough());
+    let completer = Box::new(StyledCompleter(vec![
+        ("src/", directory, "directory"),
+        ("examples/", directory, "directory"),
+        ("build.sh", executable, "executable"),
+        ("Cargo.toml", None, "file"),
+        ("--color", None, "option"),
+        ("--colour", deprecated, "deprecated, use --color"),
+    ]));
+
+    let completion_menu = Box::new(ColumnarMenu::default().with_name("completion_menu"));
+
+    let mut keybindings = default_emacs_keybindings();
+    keybindings.add_binding(
+        KeyModifiers::NONE,
+        KeyCode::Tab,
+        ReedlineEvent::UntilFound(vec![
+            ReedlineEvent::Menu("completion_menu".to_string()),
+            ReedlineEvent::MenuNext,
+        ]),
+    );
+
+    let mut line_editor = Reedline::create()
+        .with_completer(completer)
+        .with_menu(ReedlineMenu::EngineCompleter(completion_menu))
+        .with_edit_mode(Box::new(Emacs::new(keybindings)));
+
+    let prompt = DefaultPrompt::default();
+
+    loop {
+        let sig = line_editor.read_line(&prompt)?;
+        match sig {
+            Signal::Success(buffer) => {
+                println!("We processed: {buffer}");
+            }
+            Signal::CtrlD | Signal::CtrlC => {
+                println!("\nAborted!");
+                break Ok(());
+            }
+            Signal::Custom { .. } => {}
+        }
+    }
+}
@@ -0,0 +1,59 @@
+// Suspend the line editor with Ctrl+Z and resume it with `fg`.
+// cargo run --example suspend
+//
+// Run this from an interactive shell with job control. Type something, open
+// the completion menu with Tab, then press Ctrl+Z: the shell prompt should be
+// usable normally. After `fg` the prompt, buffer and menu are redrawn.
+
+use reedline::{
+    default_emacs_keybindings, ColumnarMenu, DefaultCompleter, DefaultPrompt, Emacs, KeyCode,
+    KeyModifiers, MenuBuilder, Reedline, ReedlineEvent, ReedlineMenu, Signal,
+};
+use std::io;
+
+fn main() -> io::Result<()> {
+    let commands = vec![
+        "test".into(),
+        "hello world".into(),
+        "hello world reedline".into(),
+        "this is the reedline crate".into(),
+    ];
+    let completer = Box::new(DefaultCompleter::new_with_wordlen(commands, 2));
+    let completion_menu = Box::new(ColumnarMenu::default().with_name("completion_menu"));
+
+    let mut keybindings = default_emacs_keybindings();
+    keybindings.add_binding(
+        KeyModifiers::CONTROL,
+        KeyCode::Char('z'),
+        ReedlineEvent::Suspend,
+    );
+    keybindings.add_binding(
+        KeyModifiers::NONE,
+        KeyCode::Tab,
+        ReedlineEvent::UntilFound(vec![
+            ReedlineEvent::Menu("completion_menu".to_string()),
+            ReedlineEvent::MenuNext,
+        ]),
+    );
+
+    let mut line_editor = Reedline::create()
+        .with_completer(completer)
+        .with_menu(ReedlineMenu::EngineCompleter(completion_menu))
+        .with_edit_mode(Box::new(Emacs::new(keybindings)));
+    let prompt = DefaultPrompt::default();
+
+    println!("Press Ctrl+Z to suspend, then `fg` to resume. Ctrl-D to quit.");
+
+    loop {
+        match line_editor.read_line(&prompt)? {
+            Signal::Success(buffer) => {
+                println!("We processed: {buffer}");
+            }
+            Signal::CtrlD | Signal::CtrlC => {
+                println!("\nAborted!");
+                break Ok(());
+            }
+            Signal::Custom { .. } => {}
+        }
+    }
+}
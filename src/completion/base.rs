@@ -1,5 +1,8 @@
 use nu_ansi_term::Style;
-use std::ops::Range;
+use std::{cell::RefCell, ops::Range};
+
+use super::{CancellationToken, CompletionHandle, QuotingStyle};
+use crate::HistoryItemId;
 
 /// A span of source code, with positions in bytes
 #[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Ord, PartialOrd, Hash)]
@@ -41,15 +44,50 @@ pub trait Completer: Send {
         line: &str,
         pos: usize,
     ) -> (Vec<Suggestion>, Vec<Range<usize>>) {
-        let mut ranges = vec![];
         let suggestions = self.complete(line, pos);
-        for suggestion in &suggestions {
-            ranges.push(suggestion.span.start..suggestion.span.end);
-        }
-        ranges.dedup();
+        let ranges = base_ranges(&suggestions);
         (suggestions, ranges)
     }
 
+    /// Same as [`Completer::complete`], knowing more about where the menu
+    /// completes, such as the word at the cursor and whether it is in quotes
+    ///
+    /// The built-in menus complete with this method. Defaults to
+    /// [`Completer::complete_with_base_ranges`] with the line and position of
+    /// the context, whose base ranges the default
+    /// [`Completer::complete_streaming`] passes on to the menus.
+    fn complete_with_context(&mut self, ctx: &CompletionContext) -> Vec<Suggestion> {
+        let (suggestions, ranges) = self.complete_with_base_ranges(ctx.line, ctx.pos);
+        ctx.set_base_ranges(ranges);
+        suggestions
+    }
+
+    /// Same as [`Completer::complete_with_context`], for completers too slow
+    /// to answer before the next key press, e.g. ones reading the file system
+    ///
+    /// Return a [`CompletionHandle::streaming`] handle and send the
+    /// suggestions from another thread as they come up. The completion menus
+    /// show them as they arrive; once the line changes the suggestions are no
+    /// longer wanted and `token` is cancelled. The menus highlight the
+    /// strings the suggestions are based on by the ranges of the handle, see
+    /// [`CompletionHandle::with_base_ranges`]. Defaults to the suggestions of
+    /// [`Completer::complete_with_context`], ready right away, with the
+    /// ranges of [`Completer::complete_with_base_ranges`] unless
+    /// `complete_with_context` is overridden.
+    fn complete_streaming(
+        &mut self,
+        ctx: &CompletionContext,
+        token: CancellationToken,
+    ) -> CompletionHandle {
+        let _ = token;
+        ctx.take_base_ranges();
+        let suggestions = self.complete_with_context(ctx);
+        match ctx.take_base_ranges() {
+            Some(ranges) => CompletionHandle::ready(suggestions).with_base_ranges(ranges),
+            None => CompletionHandle::ready(suggestions),
+        }
+    }
+
     /// action that will return a partial section of available completions
     /// this command comes handy when trying to avoid to pull all the data at once
     /// from the completer
@@ -71,6 +109,102 @@ pub trait Completer: Send {
     fn total_completions(&mut self, line: &str, pos: usize) -> usize {
         self.complete(line, pos).len()
     }
+
+    /// The description of a suggestion returned without one, looked up when
+    /// a menu selects it
+    ///
+    /// For descriptions too expensive to get for every suggestion up front.
+    /// Defaults to none.
+    fn resolve_description(&mut self, suggestion: &Suggestion) -> Option<String> {
+        let _ = suggestion;
+        None
+    }
+}
+
+/// The ranges of the text the suggestions replace, in order without repeats
+/// next to each other
+pub(crate) fn base_ranges(suggestions: &[Suggestion]) -> Vec<Range<usize>> {
+    let mut ranges: Vec<_> = suggestions
+        .iter()
+        .map(|suggestion| suggestion.span.start..suggestion.span.end)
+        .collect();
+    ranges.dedup();
+    ranges
+}
+
+/// Where a menu asks for completions, passed to [`Completer::complete_with_context`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CompletionContext<'a> {
+    /// The line to complete
+    pub line: &'a str,
+    /// The cursor position in `line`, in bytes
+    pub pos: usize,
+    /// The word before the cursor, from the last whitespace outside of quotes
+    pub word_span: Span,
+    /// The text of `word_span`
+    pub word: &'a str,
+    /// The quote (`"`, `'` or `` ` ``) opened before the cursor and not closed yet
+    pub quote: Option<char>,
+    /// Whether the cursor follows a backslash escaping the next character
+    pub escaped: bool,
+    /// Whether the menu was open already and completes the edited line again,
+    /// rather than completing as it opens
+    pub refinement: bool,
+    /// Base ranges the default [`Completer::complete_with_context`] got from
+    /// [`Completer::complete_with_base_ranges`], until the default
+    /// [`Completer::complete_streaming`] takes them
+    base_ranges: RefCell<Option<Vec<Range<usize>>>>,
+}
+
+impl<'a> CompletionContext<'a> {
+    /// The context of completing `line` at `pos`
+    ///
+    /// Backslashes escape the next character outside of quotes and inside
+    /// double quotes; single quotes and backticks take everything literally.
+    pub fn new(line: &'a str, pos: usize, refinement: bool) -> Self {
+        let pos = pos.min(line.len());
+        let mut word_start = 0;
+        let mut quote = None;
+        let mut escaped = false;
+        for (index, c) in line[..pos].char_indices() {
+            if escaped {
+                escaped = false;
+            } else if let Some(open) = quote {
+                if c == open {
+                    quote = None;
+                } else {
+                    escaped = c == '\\' && open == '"';
+                }
+            } else if c == '\\' {
+                escaped = true;
+            } else if "\"'`".contains(c) {
+                quote = Some(c);
+            } else if c.is_whitespace() {
+                word_start = index + c.len_utf8();
+            }
+        }
+
+        CompletionContext {
+            line,
+            pos,
+            word_span: Span::new(word_start, pos),
+            word: &line[word_start..pos],
+            quote,
+            escaped,
+            refinement,
+            base_ranges: RefCell::new(None),
+        }
+    }
+
+    /// Pass on the base ranges of the suggestions completed in this context
+    pub(crate) fn set_base_ranges(&self, ranges: Vec<Range<usize>>) {
+        *self.base_ranges.borrow_mut() = Some(ranges);
+    }
+
+    /// Take the base ranges passed on with [`Self::set_base_ranges`]
+    pub(crate) fn take_base_ranges(&self) -> Option<Vec<Range<usize>>> {
+        self.base_ranges.borrow_mut().take()
+    }
 }
 
 /// Suggestion returned by the Completer
@@ -95,6 +229,22 @@ pub struct Suggestion {
     /// Indices of the graphemes in the suggestion that matched the typed text.
     /// Useful if using fuzzy matching.
     pub match_indices: Option<Vec<usize>>,
+    /// What the suggestion stands for, shown as an icon in front of it
+    pub kind: Option<SuggestionKind>,
+    /// When the suggested text was recorded, e.g. the start of a command from
+    /// the history
+    pub timestamp: Option<chrono::DateTime<chrono::Utc>>,
+    /// Id of the history entry the suggestion comes from, if any
+    pub history_id: Option<HistoryItemId>,
+    /// How to quote `value` when inserting it, e.g. a file name with spaces.
+    /// `value` itself is unquoted.
+    pub quoting: QuotingStyle,
+    /// Name of the group the suggestion is listed in, e.g. `files` or
+    /// `aliases`, shown as a header above the group by the columnar and IDE
+    /// menus
+    ///
+    /// Suggestions without a group are listed last, without a header.
+    pub group: Option<String>,
 }
 
 impl Suggestion {
@@ -103,3 +253,207 @@ impl Suggestion {
         self.display_override.as_ref().unwrap_or(&self.value)
     }
 }
+
+/// What a [`Suggestion`] stands for, following the completion item kinds of
+/// the language server protocol
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum SuggestionKind {
+    /// Plain text
+    Text,
+    /// Method of a type
+    Method,
+    /// Function or command
+    Function,
+    /// Constructor of a type
+    Constructor,
+    /// Field of a record or struct
+    Field,
+    /// Variable
+    Variable,
+    /// Class
+    Class,
+    /// Interface or trait
+    Interface,
+    /// Module
+    Module,
+    /// Property
+    Property,
+    /// Unit of measurement
+    Unit,
+    /// Literal value
+    Value,
+    /// Enumeration
+    Enum,
+    /// Language keyword
+    Keyword,
+    /// Template to fill in
+    Snippet,
+    /// Color
+    Color,
+    /// File
+    File,
+    /// Reference to another item
+    Reference,
+    /// Directory
+    Folder,
+    /// Variant of an enumeration
+    EnumMember,
+    /// Constant
+    Constant,
+    /// Struct
+    Struct,
+    /// Event
+    Event,
+    /// Operator
+    Operator,
+    /// Type parameter
+    TypeParameter,
+}
+
+impl SuggestionKind {
+    /// A one column wide symbol for the kind
+    pub fn icon(self) -> &'static str {
+        match self {
+            SuggestionKind::Text => "¶",
+            SuggestionKind::Method | SuggestionKind::Function => "ƒ",
+            SuggestionKind::Constructor => "⊕",
+            SuggestionKind::Field | SuggestionKind::Property => "◦",
+            SuggestionKind::Variable => "α",
+            SuggestionKind::Class => "◆",
+            SuggestionKind::Interface => "◇",
+            SuggestionKind::Module => "▣",
+            SuggestionKind::Unit => "∘",
+            SuggestionKind::Value => "≡",
+            SuggestionKind::Enum => "∈",
+            SuggestionKind::Keyword => "κ",
+            SuggestionKind::Snippet => "§",
+            SuggestionKind::Color => "●",
+            SuggestionKind::File => "□",
+            SuggestionKind::Reference => "→",
+            SuggestionKind::Folder => "▸",
+            SuggestionKind::EnumMember => "∋",
+            SuggestionKind::Constant => "π",
+            SuggestionKind::Struct => "▦",
+            SuggestionKind::Event => "ϟ",
+            SuggestionKind::Operator => "±",
+            SuggestionKind::TypeParameter => "τ",
+        }
+    }
+
+    /// A short ASCII label for the kind, for terminals lacking the icons
+    pub fn label(self) -> &'static str {
+        match self {
+            SuggestionKind::Text => "txt",
+            SuggestionKind::Method => "meth",
+            SuggestionKind::Function => "fn",
+            SuggestionKind::Constructor => "new",
+            SuggestionKind::Field => "fld",
+            SuggestionKind::Variable => "var",
+            SuggestionKind::Class => "cls",
+            SuggestionKind::Interface => "ifc",
+            SuggestionKind::Module => "mod",
+            SuggestionKind::Property => "prop",
+            SuggestionKind::Unit => "unit",
+            SuggestionKind::Value => "val",
+            SuggestionKind::Enum => "enum",
+            SuggestionKind::Keyword => "kw",
+            SuggestionKind::Snippet => "snip",
+            SuggestionKind::Color => "clr",
+            SuggestionKind::File => "file",
+            SuggestionKind::Reference => "ref",
+            SuggestionKind::Folder => "dir",
+            SuggestionKind::EnumMember => "memb",
+            SuggestionKind::Constant => "cnst",
+            SuggestionKind::Struct => "strc",
+            SuggestionKind::Event => "evt",
+            SuggestionKind::Operator => "op",
+            SuggestionKind::TypeParameter => "type",
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+    use rstest::rstest;
+
+    #[rstest]
+    #[case("ls", 2, "ls", None, false)]
+    #[case("ls -l", 5, "-l", None, false)]
+    #[case("ls ", 3, "", None, false)]
+    #[case("cd \"My Doc", 10, "\"My Doc", Some('"'), false)]
+    #[case("cd 'it\\", 7, "'it\\", Some('\''), false)]
+    #[case("cd \"a\\", 6, "\"a\\", Some('"'), true)]
+    #[case("cd \"a b\" c", 10, "c", None, false)]
+    #[case("echo a\\ b", 9, "a\\ b", None, false)]
+    #[case("echo a\\", 7, "a\\", None, true)]
+    #[case("open `x y", 6, "`", Some('`'), false)]
+    #[case("grep ä", 7, "ä", None, false)]
+    fn context_knows_the_word_and_quotes_at_the_cursor(
+        #[case] line: &str,
+        #[case] pos: usize,
+        #[case] word: &str,
+        #[case] quote: Option<char>,
+        #[case] escaped: bool,
+    ) {
+        let ctx = CompletionContext::new(line, pos, false);
+        assert_eq!((ctx.word, ctx.quote, ctx.escaped), (word, quote, escaped));
+        assert_eq!(&line[ctx.word_span.start..ctx.word_span.end], word);
+        assert_eq!(ctx.word_span.end, pos);
+    }
+
+    struct Fixed;
+
+    impl Completer for Fixed {
+        fn complete(&mut self, line: &str, pos: usize) -> Vec<Suggestion> {
+            vec![Suggestion {
+                value: format!("{line}@{pos}"),
+                ..Default::default()
+            }]
+        }
+    }
+
+    #[test]
+    fn completing_with_context_defaults_to_complete() {
+        let ctx = CompletionContext::new("ls -l", 2, true);
+        assert_eq!(Fixed.complete_with_context(&ctx)[0].value, "ls -l@2");
+    }
+
+    /// Only completes with base ranges, leaving `complete` out
+    struct RangesOnly;
+
+    impl Completer for RangesOnly {
+        fn complete(&mut self, _line: &str, _pos: usize) -> Vec<Suggestion> {
+            unreachable!("completes with base ranges")
+        }
+
+        fn complete_with_base_ranges(
+            &mut self,
+            line: &str,
+            pos: usize,
+        ) -> (Vec<Suggestion>, Vec<Range<usize>>) {
+            (
+                Fixed.complete(line, pos),
+                vec![Range { start: 0, end: pos }],
+            )
+        }
+    }
+
+    #[test]
+    fn completing_with_context_goes_through_the_base_ranges() {
+        let ctx = CompletionContext::new("ls -l", 2, true);
+        assert_eq!(RangesOnly.complete_with_context(&ctx)[0].value, "ls -l@2");
+    }
+
+    #[test]
+    fn streaming_keeps_the_base_ranges() {
+        let ctx = CompletionContext::new("ls -l", 2, true);
+        let handle = RangesOnly.complete_streaming(&ctx, CancellationToken::default());
+        assert_eq!(handle.base_ranges(), [Range { start: 0, end: 2 }]);
+
+        // Without ranges of their own they are those of the spans
+        let handle = Fixed.complete_streaming(&ctx, CancellationToken::default());
+        assert_eq!(handle.base_ranges(), [Range { start: 0, end: 0 }]);
+    }
+}
@@ -0,0 +1,379 @@
+use std::{
+    collections::VecDeque,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    time::{Duration, Instant},
+};
+
+use super::{CancellationToken, Completer, CompletionContext, CompletionHandle, Span, Suggestion};
+
+type CandidateFilter = Box<dyn Fn(&str, &Suggestion) -> bool + Send>;
+
+const DEFAULT_MAX_ENTRIES: usize = 32;
+
+/// [`Completer`] remembering the suggestions of another one, e.g. while a
+/// word is typed and erased again in the completion menu
+///
+/// Results are kept per word before the cursor, together with the text
+/// around it. Completing a word seen before returns its suggestions again.
+/// Completing a longer word than one seen before filters the suggestions of
+/// the shorter word instead of asking the wrapped completer, keeping those
+/// that start with the typed text. That is only right for completers whose
+/// suggestions for a longer word are among those of the shorter one, such as
+/// a [`DefaultCompleter`](super::DefaultCompleter) matching prefixes; a file
+/// completer listing the directory typed so far is not one of them.
+///
+/// Suggestions depending on something else than the line, such as the files
+/// on disk or the environment, go stale. Give the cached results a
+/// [`Self::with_ttl`], or call [`CacheInvalidator::invalidate`] when they
+/// change.
+///
+/// # Example
+/// ```rust
+/// use reedline::{CachedCompleter, Completer, DefaultCompleter};
+///
+/// let commands = DefaultCompleter::new_with_wordlen(vec!["cargo".into(), "cat".into()], 1);
+/// let mut completer = CachedCompleter::new(Box::new(commands));
+///
+/// assert_eq!(completer.complete("c", 1).len(), 2);
+/// // Filtered from the suggestions of "c"
+/// assert_eq!(completer.complete("ca", 2).len(), 2);
+/// assert_eq!(completer.complete("car", 3).len(), 1);
+/// ```
+pub struct CachedCompleter {
+    completer: Box<dyn Completer>,
+    /// The least recently used first
+    entries: VecDeque<Entry>,
+    max_entries: usize,
+    ttl: Option<Duration>,
+    filter: CandidateFilter,
+    invalidated: Arc<AtomicBool>,
+}
+
+/// The suggestions of a completed word
+struct Entry {
+    /// The line up to the word
+    before: String,
+    word: String,
+    /// The line after the cursor
+    after: String,
+    suggestions: Vec<Suggestion>,
+    stored: Instant,
+}
+
+impl Entry {
+    fn is_for(&self, ctx: &CompletionContext) -> bool {
+        self.before == ctx.line[..ctx.word_span.start] && self.after == ctx.line[ctx.pos..]
+    }
+
+    /// Whether the suggestions for `ctx` can be filtered from these ones
+    fn extends_to(&self, ctx: &CompletionContext) -> bool {
+        let pos = self.before.len() + self.word.len();
+        !self.word.is_empty()
+            && self.word.len() < ctx.word.len()
+            && ctx.word.starts_with(&self.word)
+            && !self.suggestions.is_empty()
+            && self.suggestions.iter().all(|s| s.span.end == pos)
+            && self.is_for(ctx)
+    }
+}
+
+impl CachedCompleter {
+    /// Remember the suggestions of `completer`, for up to 32 words
+    pub fn new(completer: Box<dyn Completer>) -> Self {
+        Self {
+            completer,
+            entries: VecDeque::new(),
+            max_entries: DEFAULT_MAX_ENTRIES,
+            ttl: None,
+            filter: Box::new(|typed, suggestion| {
+                suggestion.value.len() > typed.len() && suggestion.value.starts_with(typed)
+            }),
+            invalidated: Arc::new(AtomicBool::new(false)),
+        }
+    }
+
+    /// A builder setting how many words to remember suggestions for (default: 32)
+    ///
+    /// The least recently used are forgotten first. With 0 nothing is cached.
+    #[must_use]
+    pub fn with_max_entries(mut self, max_entries: usize) -> Self {
+        self.max_entries = max_entries;
+        self
+    }
+
+    /// A builder setting how long suggestions are reused (default: until
+    /// invalidated)
+    #[must_use]
+    pub fn with_ttl(mut self, ttl: Duration) -> Self {
+        self.ttl = Some(ttl);
+        self
+    }
+
+    /// A builder setting which suggestions of a shorter word to keep for a
+    /// longer one, given the text a suggestion replaces
+    ///
+    /// Defaults to those starting with the replaced text and longer than it,
+    /// as a [`DefaultCompleter`](super::DefaultCompleter) matching prefixes
+    /// returns them.
+    #[must_use]
+    pub fn with_filter(mut self, filter: CandidateFilter) -> Self {
+        self.filter = filter;
+        self
+    }
+
+    /// Forget all suggestions
+    pub fn invalidate(&mut self) {
+        self.entries.clear();
+    }
+
+    /// A handle forgetting the suggestions from elsewhere, e.g. once the
+    /// completer was passed to [`Reedline`](crate::Reedline)
+    pub fn invalidator(&self) -> CacheInvalidator {
+        CacheInvalidator(self.invalidated.clone())
+    }
+
+    /// Drop the entries that went stale
+    fn expire(&mut self) {
+        if self.invalidated.swap(false, Ordering::Relaxed) {
+            self.entries.clear();
+        }
+        if let Some(ttl) = self.ttl {
+            self.entries.retain(|entry| entry.stored.elapsed() < ttl);
+        }
+    }
+
+    /// The remembered suggestions for `ctx`, filtered from a shorter word if
+    /// need be
+    fn lookup(&mut self, ctx: &CompletionContext) -> Option<Vec<Suggestion>> {
+        self.expire();
+        if let Some(index) = self
+            .entries
+            .iter()
+            .position(|entry| entry.word == ctx.word && entry.is_for(ctx))
+        {
+            let entry = self.entries.remove(index)?;
+            let suggestions = entry.suggestions.clone();
+            self.entries.push_back(entry);
+            return Some(suggestions);
+        }
+
+        let shorter = self
+            .entries
+            .iter()
+            .filter(|entry| entry.extends_to(ctx))
+            .max_by_key(|entry| entry.word.len())?;
+        let suggestions: Vec<_> = shorter
+            .suggestions
+            .iter()
+            .filter_map(|suggestion| {
+                let typed = ctx.line.get(suggestion.span.start..ctx.pos)?;
+                (self.filter)(typed, suggestion).then(|| Suggestion {
+                    span: Span::new(suggestion.span.start, ctx.pos),
+                    // Matched against the shorter word
+                    match_indices: None,
+                    ..suggestion.clone()
+                })
+            })
+            .collect();
+        self.store(ctx, suggestions.clone());
+        Some(suggestions)
+    }
+
+    fn store(&mut self, ctx: &CompletionContext, suggestions: Vec<Suggestion>) {
+        if self.max_entries == 0 {
+            return;
+        }
+        if self.entries.len() >= self.max_entries {
+            self.entries.pop_front();
+        }
+        self.entries.push_back(Entry {
+            before: ctx.line[..ctx.word_span.start].to_string(),
+            word: ctx.word.to_string(),
+            after: ctx.line[ctx.pos..].to_string(),
+            suggestions,
+            stored: Instant::now(),
+        });
+    }
+}
+
+impl Completer for CachedCompleter {
+    fn complete(&mut self, line: &str, pos: usize) -> Vec<Suggestion> {
+        self.complete_with_context(&CompletionContext::new(line, pos, false))
+    }
+
+    fn complete_with_context(&mut self, ctx: &CompletionContext) -> Vec<Suggestion> {
+        if let Some(suggestions) = self.lookup(ctx) {
+            return suggestions;
+        }
+        let suggestions = self.completer.complete_with_context(ctx);
+        self.store(ctx, suggestions.clone());
+        suggestions
+    }
+
+    /// Remembered suggestions are ready right away; otherwise the wrapped
+    /// completer streams them, without caching
+    fn complete_streaming(
+        &mut self,
+        ctx: &CompletionContext,
+        token: CancellationToken,
+    ) -> CompletionHandle {
+        match self.lookup(ctx) {
+            Some(suggestions) => CompletionHandle::ready(suggestions),
+            None => self.completer.complete_streaming(ctx, token),
+        }
+    }
+
+    fn resolve_description(&mut self, suggestion: &Suggestion) -> Option<String> {
+        self.completer.resolve_description(suggestion)
+    }
+}
+
+/// Makes a [`CachedCompleter`] forget its suggestions, see
+/// [`CachedCompleter::invalidator`]
+///
+/// Clones share the state with the completer.
+#[derive(Debug, Clone)]
+pub struct CacheInvalidator(Arc<AtomicBool>);
+
+impl CacheInvalidator {
+    /// Forget the suggestions before the next completion
+    pub fn invalidate(&self) {
+        self.0.store(true, Ordering::Relaxed);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::DefaultCompleter;
+    use pretty_assertions::assert_eq;
+    use std::sync::Mutex;
+
+    /// Counts the completions reaching the wrapped completer
+    struct Counting {
+        completer: DefaultCompleter,
+        calls: Arc<Mutex<usize>>,
+    }
+
+    impl Completer for Counting {
+        fn complete(&mut self, line: &str, pos: usize) -> Vec<Suggestion> {
+            *self.calls.lock().unwrap() += 1;
+            self.completer.complete(line, pos)
+        }
+    }
+
+    fn words(words: &[&str]) -> DefaultCompleter {
+        DefaultCompleter::new_with_wordlen(words.iter().map(|w| w.to_string()).collect(), 1)
+    }
+
+    fn counted(completer: DefaultCompleter) -> (CachedCompleter, Arc<Mutex<usize>>) {
+        let calls = Arc::new(Mutex::new(0));
+        let counting = Counting {
+            completer,
+            calls: calls.clone(),
+        };
+        (CachedCompleter::new(Box::new(counting)), calls)
+    }
+
+    fn values(suggestions: &[Suggestion]) -> Vec<(&str, Span)> {
+        suggestions
+            .iter()
+            .map(|s| (s.value.as_str(), s.span))
+            .collect()
+    }
+
+    #[test]
+    fn longer_words_are_filtered_with_spans_following_the_cursor() {
+        let (mut completer, calls) = counted(words(&["cargo", "cat", "cargo test", "cd"]));
+
+        completer.complete("cargo t; ca", 11);
+        let suggestions = completer.complete("cargo t; car", 12);
+
+        assert_eq!(
+            values(&suggestions),
+            [
+                ("cargo", Span::new(9, 12)),
+                ("cargo test", Span::new(9, 12))
+            ]
+        );
+        assert_eq!(*calls.lock().unwrap(), 1);
+    }
+
+    #[test]
+    fn erasing_returns_the_suggestions_of_the_shorter_word() {
+        let (mut completer, calls) = counted(words(&["cargo", "cat", "cd"]));
+
+        let shorter = completer.complete("ca", 2);
+        completer.complete("car", 3);
+        assert_eq!(completer.complete("ca", 2), shorter);
+        assert_eq!(*calls.lock().unwrap(), 1);
+    }
+
+    #[test]
+    fn other_text_around_the_word_is_completed_again() {
+        let (mut completer, calls) = counted(words(&["cargo", "cat"]));
+
+        completer.complete("ca", 2);
+        completer.complete("ls ca", 5);
+        completer.complete("ca x", 2);
+        assert_eq!(*calls.lock().unwrap(), 3);
+    }
+
+    #[test]
+    fn invalidating_forgets_the_suggestions() {
+        let (mut completer, calls) = counted(words(&["cargo"]));
+        let invalidator = completer.invalidator();
+
+        completer.complete("ca", 2);
+        invalidator.invalidate();
+        completer.complete("car", 3);
+        completer.invalidate();
+        completer.complete("car", 3);
+        assert_eq!(*calls.lock().unwrap(), 3);
+    }
+
+    #[test]
+    fn expired_and_evicted_entries_are_completed_again() {
+        let (completer, calls) = counted(words(&["cargo"]));
+        let mut completer = completer.with_ttl(Duration::ZERO);
+        completer.complete("ca", 2);
+        completer.complete("ca", 2);
+        assert_eq!(*calls.lock().unwrap(), 2);
+
+        let (completer, calls) = counted(words(&["cargo", "cat"]));
+        let mut completer = completer.with_max_entries(1);
+        completer.complete("ca", 2);
+        completer.complete("ls c", 4);
+        completer.complete("car", 3);
+        assert_eq!(*calls.lock().unwrap(), 3);
+    }
+
+    #[test]
+    fn a_large_candidate_set_is_filtered_like_the_uncached_completer() {
+        let candidates: Vec<String> = (0..20_000)
+            .map(|n| format!("{}{n:05}", ["ab", "abc", "b"][n % 3]))
+            .collect();
+        let mut uncached = DefaultCompleter::new_with_wordlen(candidates.clone(), 1);
+        let (mut cached, calls) = counted(uncached.clone());
+        let typed = [
+            "a", "ab", "abc", "abc0", "abc00", "abc001", "abc00", "abc0", "abc", "abc1", "ab",
+            "ab0", "ab00", "ab000", "ab0000", "ab00003", "b", "b1", "b19", "b199", "b1999",
+        ];
+
+        let expected: Vec<_> = typed
+            .iter()
+            .map(|word| uncached.complete(word, word.len()))
+            .collect();
+        let results: Vec<_> = typed
+            .iter()
+            .map(|word| cached.complete(word, word.len()))
+            .collect();
+
+        assert_eq!(results, expected);
+        // Only "a" and "b" reach the wrapped completer
+        assert_eq!(*calls.lock().unwrap(), 2);
+    }
+}
@@ -0,0 +1,141 @@
+use std::ops::Range;
+
+use super::{base_ranges, Completer, CompletionContext, Suggestion};
+
+/// [`Completer`] merging the suggestions of several completers, e.g. of a
+/// [`DefaultCompleter`](super::DefaultCompleter) and a language server
+///
+/// Suggestions are listed in the order of the completers. One with the same
+/// value and span as an earlier suggestion is dropped, lending its
+/// [`kind`](Suggestion::kind) to the earlier one if that has none.
+///
+/// # Example
+/// ```rust
+/// use reedline::{ChainedCompleter, Completer, DefaultCompleter};
+///
+/// let commands = DefaultCompleter::new_with_wordlen(vec!["ls".into(), "less".into()], 1);
+/// let files = DefaultCompleter::new_with_wordlen(vec!["lib.rs".into()], 1);
+/// let mut completer = ChainedCompleter::new(vec![Box::new(commands), Box::new(files)]);
+///
+/// assert_eq!(completer.complete("l", 1).len(), 3);
+/// ```
+pub struct ChainedCompleter {
+    completers: Vec<Box<dyn Completer>>,
+}
+
+impl ChainedCompleter {
+    /// Merge the suggestions of `completers`
+    pub fn new(completers: Vec<Box<dyn Completer>>) -> Self {
+        Self { completers }
+    }
+
+    /// A builder that adds a completer after the existing ones
+    #[must_use]
+    pub fn with_completer(mut self, completer: Box<dyn Completer>) -> Self {
+        self.completers.push(completer);
+        self
+    }
+
+    /// Merge the suggestions of every completer, as given by `complete`
+    fn merge(
+        &mut self,
+        mut complete: impl FnMut(&mut dyn Completer) -> Vec<Suggestion>,
+    ) -> Vec<Suggestion> {
+        let mut merged: Vec<Suggestion> = Vec::new();
+        for completer in &mut self.completers {
+            for suggestion in complete(completer.as_mut()) {
+                match merged
+                    .iter_mut()
+                    .find(|s| s.value == suggestion.value && s.span == suggestion.span)
+                {
+                    Some(earlier) => {
+                        earlier.kind = earlier.kind.or(suggestion.kind);
+                    }
+                    None => merged.push(suggestion),
+                }
+            }
+        }
+        merged
+    }
+}
+
+impl Completer for ChainedCompleter {
+    fn complete(&mut self, line: &str, pos: usize) -> Vec<Suggestion> {
+        self.merge(|completer| completer.complete(line, pos))
+    }
+
+    /// Passes on the base ranges of every completer, those of their spans
+    /// for completers without any
+    fn complete_with_context(&mut self, ctx: &CompletionContext) -> Vec<Suggestion> {
+        let mut ranges: Vec<Range<usize>> = Vec::new();
+        let merged = self.merge(|completer| {
+            let suggestions = completer.complete_with_context(ctx);
+            let completer_ranges = ctx
+                .take_base_ranges()
+                .unwrap_or_else(|| base_ranges(&suggestions));
+            for range in completer_ranges {
+                if !ranges.contains(&range) {
+                    ranges.push(range);
+                }
+            }
+            suggestions
+        });
+        ctx.set_base_ranges(ranges);
+        merged
+    }
+
+    fn resolve_description(&mut self, suggestion: &Suggestion) -> Option<String> {
+        self.completers
+            .iter_mut()
+            .find_map(|completer| completer.resolve_description(suggestion))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Span, SuggestionKind};
+    use pretty_assertions::assert_eq;
+
+    struct Fixed(Vec<(&'static str, Option<SuggestionKind>)>);
+
+    impl Completer for Fixed {
+        fn complete(&mut self, _line: &str, pos: usize) -> Vec<Suggestion> {
+            self.0
+                .iter()
+                .map(|(value, kind)| Suggestion {
+                    value: value.to_string(),
+                    span: Span::new(0, pos),
+                    kind: *kind,
+                    ..Suggestion::default()
+                })
+                .collect()
+        }
+    }
+
+    #[test]
+    fn merges_in_order_without_duplicates() {
+        let local = Fixed(vec![("ls", None), ("let", None)]);
+        let lsp = Fixed(vec![
+            ("let", Some(SuggestionKind::Keyword)),
+            ("length", Some(SuggestionKind::Function)),
+        ]);
+        let mut completer =
+            ChainedCompleter::new(vec![Box::new(local)]).with_completer(Box::new(lsp));
+
+        let merged: Vec<_> = completer
+            .complete("l", 1)
+            .into_iter()
+            .map(|s| (s.value, s.kind))
+            .collect();
+
+        assert_eq!(
+            merged,
+            vec![
+                ("ls".to_string(), None),
+                ("let".to_string(), Some(SuggestionKind::Keyword)),
+                ("length".to_string(), Some(SuggestionKind::Function)),
+            ]
+        );
+    }
+}
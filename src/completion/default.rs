@@ -1,9 +1,26 @@
-use crate::{Completer, Span, Suggestion};
+use crate::{history::best_match, Completer, Span, Suggestion};
 use std::{
     collections::{BTreeMap, BTreeSet},
     str::Chars,
     sync::Arc,
 };
+use unicode_segmentation::UnicodeSegmentation;
+
+/// How the [`DefaultCompleter`] matches the typed text against its words
+///
+/// Matching is case-sensitive in every mode.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum MatchingMode {
+    /// Words starting with the typed text, in alphabetical order
+    #[default]
+    Prefix,
+    /// Words containing the typed text, the earliest occurrence first
+    Substring,
+    /// Words containing the characters of the typed text in order, not
+    /// necessarily next to each other. The most compact matches come first,
+    /// then those starting earliest in the word.
+    Fuzzy,
+}
 
 /// A default completer that can detect keywords
 ///
@@ -26,6 +43,7 @@ use std::{
 pub struct DefaultCompleter {
     root: CompletionNode,
     min_word_len: usize,
+    matching_mode: MatchingMode,
 }
 
 impl Default for DefaultCompleter {
@@ -34,6 +52,7 @@ impl Default for DefaultCompleter {
         Self {
             root: CompletionNode::new(inclusions),
             min_word_len: 2,
+            matching_mode: MatchingMode::default(),
         }
     }
 }
@@ -92,31 +111,22 @@ impl Completer for DefaultCompleter {
                     } else {
                         span_line = format!("{s} {span_line}");
                     }
-                    if let Some(mut extensions) = self.root.complete(span_line.chars()) {
-                        extensions.sort();
-                        completions.extend(
-                            extensions
-                                .iter()
-                                .map(|ext| {
-                                    let span = Span::new(
-                                        pos - span_line.len() - span_line_whitespaces,
-                                        pos,
-                                    );
-
-                                    Suggestion {
-                                        value: format!("{span_line}{ext}"),
-                                        description: None,
-                                        style: None,
-                                        extra: None,
-                                        span,
-                                        append_whitespace: false,
-                                        ..Default::default()
-                                    }
-                                })
-                                .filter(|t| t.value.len() > (t.span.end - t.span.start))
-                                .collect::<Vec<Suggestion>>(),
-                        );
-                    }
+                    let span = Span::new(pos - span_line.len() - span_line_whitespaces, pos);
+                    completions.extend(
+                        self.matches(&span_line)
+                            .into_iter()
+                            .map(|(value, match_indices)| Suggestion {
+                                value,
+                                description: None,
+                                style: None,
+                                extra: None,
+                                span,
+                                append_whitespace: false,
+                                match_indices,
+                                ..Default::default()
+                            })
+                            .filter(|t| t.value.len() > (t.span.end - t.span.start)),
+                    );
                 }
             }
         }
@@ -277,6 +287,85 @@ impl DefaultCompleter {
         self.min_word_len = len;
         self
     }
+
+    /// Returns how the typed text is matched against the words
+    pub fn matching_mode(&self) -> MatchingMode {
+        self.matching_mode
+    }
+
+    /// A builder that sets how the typed text is matched against the words
+    ///
+    /// Outside of [`MatchingMode::Prefix`] the suggestions carry the
+    /// `match_indices` of the matched characters, for menus to highlight them.
+    /// # Example
+    /// ```
+    /// use reedline::{Completer, DefaultCompleter, MatchingMode};
+    ///
+    /// let mut completions = DefaultCompleter::default().with_matching_mode(MatchingMode::Fuzzy);
+    /// completions.insert(vec!["checkout","cherry-pick","commit"].iter().map(|s| s.to_string()).collect());
+    /// let suggestions = completions.complete("cmt", 3);
+    /// assert_eq!(suggestions[0].value, "commit");
+    /// assert_eq!(suggestions[0].match_indices, Some(vec![0, 2, 5]));
+    /// ```
+    #[must_use]
+    pub fn with_matching_mode(mut self, matching_mode: MatchingMode) -> Self {
+        self.matching_mode = matching_mode;
+        self
+    }
+
+    /// Words matching `typed`, best first, with the grapheme indices of the
+    /// matched characters outside of prefix matching
+    fn matches(&self, typed: &str) -> Vec<(String, Option<Vec<usize>>)> {
+        if self.matching_mode == MatchingMode::Prefix {
+            let mut extensions = self.root.complete(typed.chars()).unwrap_or_default();
+            extensions.sort();
+            return extensions
+                .into_iter()
+                .map(|ext| (format!("{typed}{ext}"), None))
+                .collect();
+        }
+
+        let needle: Vec<char> = typed.chars().collect();
+        let mut scored: Vec<_> = self
+            .root
+            .collect("")
+            .into_iter()
+            .filter_map(|word| {
+                let chars: Vec<char> = word.chars().collect();
+                let positions = match self.matching_mode {
+                    MatchingMode::Substring => {
+                        let start = (0..=chars.len().checked_sub(needle.len())?)
+                            .find(|&start| chars[start..].starts_with(&needle))?;
+                        (start..start + needle.len()).collect()
+                    }
+                    _ => best_match(&needle, &chars)?,
+                };
+                let score = match (positions.first(), positions.last()) {
+                    (Some(&first), Some(&last)) => (last - first + 1 - positions.len(), first),
+                    _ => (0, 0),
+                };
+                let indices = grapheme_indices(&word, &positions);
+                Some((score, word, indices))
+            })
+            .collect();
+        scored.sort();
+        scored
+            .into_iter()
+            .map(|(_, word, indices)| (word, Some(indices)))
+            .collect()
+    }
+}
+
+/// Indices of the graphemes of `word` holding the characters at `positions`
+fn grapheme_indices(word: &str, positions: &[usize]) -> Vec<usize> {
+    let of_char: Vec<usize> = word
+        .graphemes(true)
+        .enumerate()
+        .flat_map(|(index, grapheme)| grapheme.chars().map(move |_| index))
+        .collect();
+    let mut indices: Vec<usize> = positions.iter().map(|&i| of_char[i]).collect();
+    indices.dedup();
+    indices
 }
 
 #[derive(Debug, Clone)]
@@ -365,6 +454,8 @@ impl CompletionNode {
 mod tests {
     use super::*;
     use pretty_assertions::assert_eq;
+    use rstest::rstest;
+
     #[test]
     fn default_completer_with_non_ansi() {
         let mut completions = DefaultCompleter::default();
@@ -461,4 +552,91 @@ mod tests {
             [&buffer[ranges[0].clone()], &buffer[ranges[1].clone()]]
         );
     }
+
+    fn matching(mode: MatchingMode, line: &str) -> Vec<(String, Option<Vec<usize>>)> {
+        let mut completions = DefaultCompleter::with_inclusions(&['-', '_'])
+            .set_min_word_len(1)
+            .with_matching_mode(mode);
+        completions.insert(
+            [
+                "checkout",
+                "cherry-pick",
+                "commit",
+                "config",
+                "git-commit",
+                "status",
+                "stash",
+            ]
+            .iter()
+            .map(|s| s.to_string())
+            .collect(),
+        );
+        completions
+            .complete(line, line.len())
+            .into_iter()
+            .map(|suggestion| (suggestion.value, suggestion.match_indices))
+            .collect()
+    }
+
+    fn ranked(matches: &[(String, Option<Vec<usize>>)]) -> Vec<&str> {
+        matches.iter().map(|(value, _)| value.as_str()).collect()
+    }
+
+    #[test]
+    fn prefix_matching_is_the_default() {
+        assert_eq!(
+            DefaultCompleter::default().matching_mode(),
+            MatchingMode::Prefix
+        );
+        let matches = matching(MatchingMode::Prefix, "co");
+        assert_eq!(ranked(&matches), ["commit", "config"]);
+        assert!(matches.iter().all(|(_, indices)| indices.is_none()));
+    }
+
+    #[test]
+    fn substring_matches_rank_earliest_occurrence_first() {
+        let matches = matching(MatchingMode::Substring, "it");
+        assert_eq!(ranked(&matches), ["git-commit", "commit"]);
+        assert_eq!(matches[1].1, Some(vec![4, 5]));
+
+        assert_eq!(
+            ranked(&matching(MatchingMode::Substring, "st")),
+            ["stash", "status"]
+        );
+        assert_eq!(matching(MatchingMode::Substring, "Co"), []);
+    }
+
+    #[rstest]
+    #[case("cmt", &["commit", "git-commit"])]
+    #[case("co", &["commit", "config", "git-commit", "checkout"])]
+    #[case("ck", &["checkout", "cherry-pick"])]
+    #[case("sts", &["stash", "status"])]
+    #[case("ht", &["checkout"])]
+    #[case("tp", &[])]
+    #[case("CO", &[])]
+    fn fuzzy_matches_rank_compact_then_early_matches_first(
+        #[case] typed: &str,
+        #[case] expected: &[&str],
+    ) {
+        assert_eq!(ranked(&matching(MatchingMode::Fuzzy, typed)), expected);
+    }
+
+    #[test]
+    fn fuzzy_matches_report_the_matched_graphemes() {
+        assert_eq!(
+            matching(MatchingMode::Fuzzy, "cmt")[0],
+            ("commit".to_string(), Some(vec![0, 2, 5]))
+        );
+
+        // The accent combines with the `e` into one grapheme
+        let mut completions =
+            DefaultCompleter::with_inclusions(&['\u{301}']).with_matching_mode(MatchingMode::Fuzzy);
+        completions.insert(vec![
+            "cafe\u{301}s".to_string(),
+            "ｎｕｓｈｅｌｌ".to_string(),
+        ]);
+        let mut suggestion = |line: &str| completions.complete(line, line.len()).remove(0);
+        assert_eq!(suggestion("es").match_indices, Some(vec![3, 4]));
+        assert_eq!(suggestion("ｎｈｌ").match_indices, Some(vec![0, 3, 5]));
+    }
 }
@@ -1,8 +1,10 @@
-use std::{collections::HashSet, ops::Deref};
+use std::{collections::HashSet, time::Duration};
+
+use unicode_segmentation::UnicodeSegmentation;
 
 use crate::{
     history::SearchQuery, menu_functions::parse_selection_char, Completer, History, HistoryItem,
-    Result, Span, Suggestion,
+    Span, Suggestion,
 };
 
 const SELECTION_CHAR: char = '!';
@@ -15,35 +17,70 @@ pub(crate) struct HistoryCompleter<'menu>(&'menu dyn History);
 // updating the menu and that must happen in the same thread
 unsafe impl Send for HistoryCompleter<'_> {}
 
-fn search_unique(
-    completer: &HistoryCompleter,
+/// How many entries to read from the history at once
+const PAGE_SIZE: i64 = 256;
+
+/// The distinct command lines containing the text of `line`, most recent first
+///
+/// The history is read a page at a time, so only the entries up to the last
+/// one taken are loaded. A failing search ends the results.
+fn search_unique<'menu>(
+    completer: &HistoryCompleter<'menu>,
     line: &str,
-) -> Result<impl Iterator<Item = HistoryItem>> {
-    let parsed = parse_selection_char(line, SELECTION_CHAR);
-    let values = completer.0.search(SearchQuery::all_that_contain_rev(
-        parsed.remainder.to_string(),
-    ))?;
+) -> impl Iterator<Item = HistoryItem> + 'menu {
+    let history = completer.0;
+    let contains = parse_selection_char(line, SELECTION_CHAR)
+        .remainder
+        .to_string();
+    let mut start_id = None;
+    let mut exhausted = false;
+    let mut page = Vec::new().into_iter();
+    let pages = std::iter::from_fn(move || loop {
+        if let Some(item) = page.next() {
+            return Some(item);
+        }
+        if exhausted {
+            return None;
+        }
+        let items = history
+            .search(SearchQuery {
+                start_id,
+                limit: Some(PAGE_SIZE),
+                ..SearchQuery::all_that_contain_rev(contains.clone())
+            })
+            .ok()?;
+        start_id = items.last().and_then(|item| item.id);
+        exhausted = (items.len() as i64) < PAGE_SIZE || start_id.is_none();
+        page = items.into_iter();
+    });
 
     let mut seen_matching_command_lines = HashSet::new();
-    Ok(values
-        .into_iter()
-        .filter(move |value| seen_matching_command_lines.insert(value.command_line.clone())))
+    pages.filter(move |value| seen_matching_command_lines.insert(value.command_line.clone()))
 }
 
 impl Completer for HistoryCompleter<'_> {
     fn complete(&mut self, line: &str, pos: usize) -> Vec<Suggestion> {
-        match search_unique(self, line) {
-            Err(_) => vec![],
-            Ok(search_results) => search_results
-                .map(|value| self.create_suggestion(line, pos, value.command_line.deref()))
-                .collect(),
-        }
+        search_unique(self, line)
+            .map(|value| self.create_suggestion(line, pos, &value))
+            .collect()
     }
 
-    // TODO: Implement `fn partial_complete()`
+    fn partial_complete(
+        &mut self,
+        line: &str,
+        pos: usize,
+        start: usize,
+        offset: usize,
+    ) -> Vec<Suggestion> {
+        search_unique(self, line)
+            .skip(start)
+            .take(offset)
+            .map(|value| self.create_suggestion(line, pos, &value))
+            .collect()
+    }
 
     fn total_completions(&mut self, line: &str, _pos: usize) -> usize {
-        search_unique(self, line).map(|i| i.count()).unwrap_or(0)
+        search_unique(self, line).count()
     }
 }
 
@@ -52,24 +89,72 @@ impl<'menu> HistoryCompleter<'menu> {
         Self(history)
     }
 
-    fn create_suggestion(&self, line: &str, pos: usize, value: &str) -> Suggestion {
+    fn create_suggestion(&self, line: &str, pos: usize, item: &HistoryItem) -> Suggestion {
         let span = Span {
             start: pos - line.len(),
             end: pos,
         };
+        let query = parse_selection_char(line, SELECTION_CHAR).remainder;
 
         Suggestion {
-            value: value.to_string(),
-            description: None,
+            value: item.command_line.clone(),
+            description: annotation(item),
+            match_indices: Some(match_indices(&item.command_line, query)),
             style: None,
             extra: None,
             span,
             append_whitespace: false,
+            timestamp: item.start_timestamp,
+            history_id: item.id,
             ..Default::default()
         }
     }
 }
 
+/// Indices of the graphemes of `command_line` inside any occurrence of `query`
+fn match_indices(command_line: &str, query: &str) -> Vec<usize> {
+    if query.is_empty() {
+        return Vec::new();
+    }
+    let matches: Vec<_> = command_line
+        .match_indices(query)
+        .map(|(start, matched)| start..start + matched.len())
+        .collect();
+    command_line
+        .grapheme_indices(true)
+        .enumerate()
+        .filter(|(_, (offset, _))| matches.iter().any(|range| range.contains(offset)))
+        .map(|(index, _)| index)
+        .collect()
+}
+
+/// Compact outcome of a command, e.g. `✓ 1.2s` or `✗ 15ms`, if it was recorded
+fn annotation(item: &HistoryItem) -> Option<String> {
+    let status = item
+        .exit_status
+        .map(|status| if status == 0 { "✓" } else { "✗" });
+    let duration = item.duration.map(format_duration);
+    match (status, duration) {
+        (Some(status), Some(duration)) => Some(format!("{status} {duration}")),
+        (Some(status), None) => Some(status.to_string()),
+        (None, Some(duration)) => Some(duration),
+        (None, None) => None,
+    }
+}
+
+/// Format `duration` with the unit that keeps it short: `15ms`, `1.2s` or `3m07s`
+fn format_duration(duration: Duration) -> String {
+    let millis = duration.as_millis();
+    if millis < 1000 {
+        format!("{millis}ms")
+    } else if millis < 60_000 {
+        format!("{:.1}s", duration.as_secs_f64())
+    } else {
+        let secs = duration.as_secs();
+        format!("{}m{:02}s", secs / 60, secs % 60)
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use rstest::rstest;
@@ -134,6 +219,73 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn partial_completions_page_through_unique_matches() -> Result<()> {
+        // Enough repeated entries to span several pages read from the history
+        let mut history = FileBackedHistory::new(2000)?;
+        for i in 0..2000 {
+            history.save(new_history_item(&format!("make target_{}", i % 700)))?;
+        }
+        let input = "make";
+        let mut sut = HistoryCompleter::new(&history);
+
+        let all = sut.complete(input, input.len());
+        assert_eq!(all.len(), 700);
+        assert_eq!(sut.total_completions(input, input.len()), 700);
+        for (page, expected) in all.chunks(64).enumerate() {
+            let actual = sut.partial_complete(input, input.len(), page * 64, 64);
+            assert_eq!(actual, expected);
+        }
+        assert_eq!(sut.partial_complete(input, input.len(), 700, 64), vec![]);
+        Ok(())
+    }
+
+    #[rstest]
+    #[case(Some(0), Some(Duration::from_millis(15)), Some("✓ 15ms"))]
+    #[case(Some(1), Some(Duration::from_millis(1250)), Some("✗ 1.2s"))]
+    #[case(Some(0), Some(Duration::from_secs(187)), Some("✓ 3m07s"))]
+    #[case(Some(127), None, Some("✗"))]
+    #[case(None, Some(Duration::from_millis(999)), Some("999ms"))]
+    #[case(None, None, None)]
+    fn suggestions_are_annotated_with_the_outcome(
+        #[case] exit_status: Option<i64>,
+        #[case] duration: Option<Duration>,
+        #[case] expected: Option<&str>,
+    ) {
+        let item = HistoryItem {
+            exit_status,
+            duration,
+            ..new_history_item("make")
+        };
+        let history = FileBackedHistory::default();
+        let suggestion = HistoryCompleter::new(&history).create_suggestion("", 0, &item);
+
+        assert_eq!(suggestion.description.as_deref(), expected);
+    }
+
+    #[rstest]
+    #[case("git status", "git", vec![0, 1, 2])]
+    #[case("git status", "stat", vec![4, 5, 6, 7])]
+    #[case("git status", "us", vec![8, 9])]
+    #[case("ls; ls -l", "ls", vec![0, 1, 4, 5])]
+    #[case("echo ä; ls", "ls", vec![8, 9])]
+    #[case("git status", "", vec![])]
+    #[case("git status", "!1", vec![])]
+    fn suggestions_mark_the_matched_text(
+        #[case] command_line: &str,
+        #[case] line: &str,
+        #[case] expected: Vec<usize>,
+    ) {
+        let history = FileBackedHistory::default();
+        let suggestion = HistoryCompleter::new(&history).create_suggestion(
+            line,
+            line.len(),
+            &new_history_item(command_line),
+        );
+
+        assert_eq!(suggestion.match_indices, Some(expected));
+    }
+
     #[rstest]
     #[case(vec![], "any", vec![])]
     #[case(vec!["old match","recent match","between","recent match"], "match", vec!["recent match","old match"])]
@@ -1,6 +1,18 @@
 mod base;
+mod cached;
+mod chained;
 mod default;
 pub(crate) mod history;
+mod post_process;
+mod quoting;
+mod streaming;
 
-pub use base::{Completer, Span, Suggestion};
-pub use default::DefaultCompleter;
+pub(crate) use base::base_ranges;
+pub use base::{Completer, CompletionContext, Span, Suggestion, SuggestionKind};
+pub use cached::{CacheInvalidator, CachedCompleter};
+pub use chained::ChainedCompleter;
+pub use default::{DefaultCompleter, MatchingMode};
+pub use post_process::{SuggestionOrder, SuggestionPostProcessor};
+pub(crate) use quoting::quote_in_line;
+pub use quoting::{quote_for_shell, QuotingStyle};
+pub use streaming::{CancellationToken, CompletionHandle, CompletionSink};
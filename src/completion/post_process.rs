@@ -0,0 +1,426 @@
+use std::{
+    cmp::Ordering,
+    collections::{hash_map::Entry, HashMap, HashSet},
+};
+
+use super::{CancellationToken, Completer, CompletionContext, CompletionHandle, Span, Suggestion};
+
+/// In which order a [`SuggestionPostProcessor`] lists suggestions
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum SuggestionOrder {
+    /// Keep the order of the completer
+    #[default]
+    Completer,
+    /// Sort by value
+    Alphabetical,
+    /// Best match first, judged by how close together the
+    /// [`match_indices`](Suggestion::match_indices) are and how early they
+    /// start. Suggestions without them follow in completer order.
+    MatchScore,
+}
+
+/// Sorts and deduplicates the suggestions of a completer before any menu
+/// shows them, the same way for every menu
+///
+/// When sorting, a suggestion whose value is exactly the typed text stays in
+/// front. By default the suggestions are passed on as they are.
+///
+/// # Example
+/// ```rust
+/// use reedline::{Reedline, SuggestionOrder, SuggestionPostProcessor};
+///
+/// let line_editor = Reedline::create().with_suggestion_post_processor(
+///     SuggestionPostProcessor::default()
+///         .with_order(SuggestionOrder::Alphabetical)
+///         .with_case_folding(true)
+///         .with_deduplication(true),
+/// );
+/// ```
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct SuggestionPostProcessor {
+    order: SuggestionOrder,
+    case_folding: bool,
+    deduplication: bool,
+}
+
+impl SuggestionPostProcessor {
+    /// A builder setting the order of the suggestions
+    #[must_use]
+    pub fn with_order(mut self, order: SuggestionOrder) -> Self {
+        self.order = order;
+        self
+    }
+
+    /// A builder choosing whether alphabetical sorting ignores case
+    #[must_use]
+    pub fn with_case_folding(mut self, case_folding: bool) -> Self {
+        self.case_folding = case_folding;
+        self
+    }
+
+    /// A builder choosing whether suggestions with the same value and span
+    /// are listed once
+    ///
+    /// The one kept is the first with the longest description, in the place
+    /// of the first of them.
+    #[must_use]
+    pub fn with_deduplication(mut self, deduplication: bool) -> Self {
+        self.deduplication = deduplication;
+        self
+    }
+
+    /// Sort and deduplicate `suggestions` completing `line`
+    pub fn process(&self, line: &str, mut suggestions: Vec<Suggestion>) -> Vec<Suggestion> {
+        if self.deduplication {
+            suggestions = deduplicate(suggestions);
+        }
+        match self.order {
+            SuggestionOrder::Completer => {}
+            SuggestionOrder::Alphabetical => suggestions.sort_by(|a, b| {
+                exact_first(line, a, b).then_with(|| {
+                    if self.case_folding {
+                        let folded = a.value.to_lowercase().cmp(&b.value.to_lowercase());
+                        folded.then_with(|| a.value.cmp(&b.value))
+                    } else {
+                        a.value.cmp(&b.value)
+                    }
+                })
+            }),
+            SuggestionOrder::MatchScore => suggestions.sort_by(|a, b| {
+                exact_first(line, a, b).then_with(|| match (match_score(a), match_score(b)) {
+                    (Some(a), Some(b)) => a.cmp(&b),
+                    (Some(_), None) => Ordering::Less,
+                    (None, Some(_)) => Ordering::Greater,
+                    (None, None) => Ordering::Equal,
+                })
+            }),
+        }
+        suggestions
+    }
+
+    /// Whether processing can change the suggestions
+    fn changes_suggestions(&self) -> bool {
+        self.deduplication || self.order != SuggestionOrder::Completer
+    }
+
+    /// `completer` with its suggestions processed
+    pub(crate) fn wrap<'a>(&'a self, completer: &'a mut dyn Completer) -> PostProcessed<'a> {
+        PostProcessed {
+            completer,
+            processor: self,
+        }
+    }
+}
+
+/// What makes suggestions duplicates of each other
+fn duplicate_key(suggestion: &Suggestion) -> (String, Span) {
+    (suggestion.value.clone(), suggestion.span)
+}
+
+/// Suggestions with distinct values and spans, each the first with the
+/// longest description among its duplicates
+fn deduplicate(suggestions: Vec<Suggestion>) -> Vec<Suggestion> {
+    let description_len = |s: &Suggestion| s.description.as_ref().map(String::len);
+    let mut unique: Vec<Suggestion> = Vec::with_capacity(suggestions.len());
+    let mut positions = HashMap::with_capacity(suggestions.len());
+    for suggestion in suggestions {
+        match positions.entry(duplicate_key(&suggestion)) {
+            Entry::Occupied(position) => {
+                let earlier = &mut unique[*position.get()];
+                if description_len(&suggestion) > description_len(earlier) {
+                    *earlier = suggestion;
+                }
+            }
+            Entry::Vacant(position) => {
+                position.insert(unique.len());
+                unique.push(suggestion);
+            }
+        }
+    }
+    unique
+}
+
+/// Puts a suggestion whose value is exactly the text it replaces first
+fn exact_first(line: &str, a: &Suggestion, b: &Suggestion) -> Ordering {
+    let is_exact = |s: &Suggestion| line.get(s.span.start..s.span.end) == Some(s.value.as_str());
+    is_exact(b).cmp(&is_exact(a))
+}
+
+/// How far apart the matched characters are and where they start, lower
+/// being better
+fn match_score(suggestion: &Suggestion) -> Option<(usize, usize)> {
+    let indices = suggestion.match_indices.as_ref()?;
+    match (indices.iter().min(), indices.iter().max()) {
+        (Some(&first), Some(&last)) => Some((last - first + 1 - indices.len(), first)),
+        _ => Some((0, 0)),
+    }
+}
+
+/// A completer whose suggestions go through a [`SuggestionPostProcessor`]
+///
+/// Suggestions arriving from a completer running in the background are
+/// processed batch by batch, leaving out duplicates of earlier batches. Pages
+/// of [`Completer::partial_complete`] are taken from all suggestions
+/// processed, unless processing leaves them as they are.
+pub(crate) struct PostProcessed<'a> {
+    completer: &'a mut dyn Completer,
+    processor: &'a SuggestionPostProcessor,
+}
+
+impl Completer for PostProcessed<'_> {
+    fn complete(&mut self, line: &str, pos: usize) -> Vec<Suggestion> {
+        let suggestions = self.completer.complete(line, pos);
+        self.processor.process(line, suggestions)
+    }
+
+    fn complete_with_context(&mut self, ctx: &CompletionContext) -> Vec<Suggestion> {
+        let suggestions = self.completer.complete_with_context(ctx);
+        self.processor.process(ctx.line, suggestions)
+    }
+
+    fn complete_streaming(
+        &mut self,
+        ctx: &CompletionContext,
+        token: CancellationToken,
+    ) -> CompletionHandle {
+        let mut handle = self.completer.complete_streaming(ctx, token);
+        if !self.processor.changes_suggestions() {
+            return handle;
+        }
+        if !handle.is_finished() {
+            let processor = self.processor.clone();
+            let line = ctx.line.to_string();
+            let mut earlier = HashSet::new();
+            return handle.process_batches(move |batch| {
+                let batch = processor.process(&line, batch);
+                if !processor.deduplication {
+                    return batch;
+                }
+                batch
+                    .into_iter()
+                    .filter(|suggestion| earlier.insert(duplicate_key(suggestion)))
+                    .collect()
+            });
+        }
+        let suggestions = handle.take_arrived();
+        CompletionHandle::ready(self.processor.process(ctx.line, suggestions))
+            .with_base_ranges(handle.base_ranges().to_vec())
+    }
+
+    fn partial_complete(
+        &mut self,
+        line: &str,
+        pos: usize,
+        start: usize,
+        offset: usize,
+    ) -> Vec<Suggestion> {
+        if !self.processor.changes_suggestions() {
+            return self.completer.partial_complete(line, pos, start, offset);
+        }
+        self.complete(line, pos)
+            .into_iter()
+            .skip(start)
+            .take(offset)
+            .collect()
+    }
+
+    fn total_completions(&mut self, line: &str, pos: usize) -> usize {
+        if !self.processor.deduplication {
+            return self.completer.total_completions(line, pos);
+        }
+        self.complete(line, pos).len()
+    }
+
+    fn resolve_description(&mut self, suggestion: &Suggestion) -> Option<String> {
+        self.completer.resolve_description(suggestion)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Span;
+    use pretty_assertions::assert_eq;
+    use rstest::rstest;
+
+    fn suggestion(value: &str, description: Option<&str>, indices: Option<&[usize]>) -> Suggestion {
+        Suggestion {
+            value: value.into(),
+            description: description.map(Into::into),
+            match_indices: indices.map(<[usize]>::to_vec),
+            span: Span::new(4, 6),
+            ..Default::default()
+        }
+    }
+
+    /// What a merge of two sources might return for `git ch`
+    fn messy() -> Vec<Suggestion> {
+        vec![
+            suggestion("cherry-pick", None, Some(&[0, 1])),
+            suggestion("ch", None, None),
+            suggestion("Checkout", Some("switch"), Some(&[0, 5])),
+            suggestion("branch", None, Some(&[4, 5])),
+            suggestion("cherry-pick", Some("apply commits"), Some(&[0, 1])),
+            suggestion("Checkout", Some("switch branches"), Some(&[0, 5])),
+            suggestion("changelog", None, Some(&[0, 1])),
+        ]
+    }
+
+    #[rstest]
+    #[case::untouched(
+        SuggestionOrder::Completer, false, false,
+        &["cherry-pick", "ch", "Checkout", "branch", "cherry-pick", "Checkout", "changelog"]
+    )]
+    #[case::deduplicated(
+        SuggestionOrder::Completer, false, true,
+        &["cherry-pick", "ch", "Checkout", "branch", "changelog"]
+    )]
+    #[case::alphabetical(
+        SuggestionOrder::Alphabetical, false, true,
+        &["ch", "Checkout", "branch", "changelog", "cherry-pick"]
+    )]
+    #[case::alphabetical_folded(
+        SuggestionOrder::Alphabetical, true, true,
+        &["ch", "branch", "changelog", "Checkout", "cherry-pick"]
+    )]
+    #[case::match_score(
+        SuggestionOrder::MatchScore, false, true,
+        &["ch", "cherry-pick", "changelog", "branch", "Checkout"]
+    )]
+    #[case::match_score_with_duplicates(
+        SuggestionOrder::MatchScore, false, false,
+        &["ch", "cherry-pick", "cherry-pick", "changelog", "branch", "Checkout", "Checkout"]
+    )]
+    fn messy_suggestions_are_ordered(
+        #[case] order: SuggestionOrder,
+        #[case] case_folding: bool,
+        #[case] deduplication: bool,
+        #[case] expected: &[&str],
+    ) {
+        let processor = SuggestionPostProcessor::default()
+            .with_order(order)
+            .with_case_folding(case_folding)
+            .with_deduplication(deduplication);
+
+        let processed = processor.process("git ch", messy());
+
+        let values: Vec<_> = processed.iter().map(|s| s.value.as_str()).collect();
+        assert_eq!(values, expected);
+    }
+
+    #[test]
+    fn duplicates_keep_the_richest_description() {
+        let processor = SuggestionPostProcessor::default().with_deduplication(true);
+
+        let processed = processor.process("git ch", messy());
+
+        let descriptions: Vec<_> = processed
+            .iter()
+            .map(|s| (s.value.as_str(), s.description.as_deref()))
+            .collect();
+        assert_eq!(
+            descriptions,
+            [
+                ("cherry-pick", Some("apply commits")),
+                ("ch", None),
+                ("Checkout", Some("switch branches")),
+                ("branch", None),
+                ("changelog", None),
+            ]
+        );
+    }
+
+    #[test]
+    fn same_values_replacing_other_text_are_kept() {
+        let processor = SuggestionPostProcessor::default().with_deduplication(true);
+        let elsewhere = Suggestion {
+            span: Span::new(0, 3),
+            ..suggestion("Checkout", None, None)
+        };
+
+        let processed = processor.process("git ch", [messy(), vec![elsewhere]].concat());
+
+        let spans: Vec<_> = processed
+            .iter()
+            .map(|s| (s.value.as_str(), s.span.start))
+            .collect();
+        assert_eq!(
+            spans,
+            [
+                ("cherry-pick", 4),
+                ("ch", 4),
+                ("Checkout", 4),
+                ("branch", 4),
+                ("changelog", 4),
+                ("Checkout", 0),
+            ]
+        );
+    }
+
+    /// Completes with the messy suggestions, streaming them in two batches
+    /// when `streaming`
+    struct Messy {
+        streaming: bool,
+    }
+
+    impl Completer for Messy {
+        fn complete(&mut self, _line: &str, _pos: usize) -> Vec<Suggestion> {
+            messy()
+        }
+
+        fn complete_streaming(
+            &mut self,
+            ctx: &CompletionContext,
+            _token: CancellationToken,
+        ) -> CompletionHandle {
+            if !self.streaming {
+                return CompletionHandle::ready(self.complete(ctx.line, ctx.pos));
+            }
+            let (sink, handle) = CompletionHandle::streaming();
+            let mut suggestions = messy();
+            let second = suggestions.split_off(4);
+            sink.send(suggestions);
+            sink.send(second);
+            handle
+        }
+    }
+
+    fn values(suggestions: &[Suggestion]) -> Vec<&str> {
+        suggestions.iter().map(|s| s.value.as_str()).collect()
+    }
+
+    #[test]
+    fn pages_are_taken_from_the_processed_suggestions() {
+        let processor = SuggestionPostProcessor::default()
+            .with_order(SuggestionOrder::Alphabetical)
+            .with_deduplication(true);
+        let mut completer = Messy { streaming: false };
+        let mut processed = processor.wrap(&mut completer);
+
+        assert_eq!(
+            values(&processed.partial_complete("git ch", 6, 1, 2)),
+            ["Checkout", "branch"]
+        );
+        assert_eq!(processed.total_completions("git ch", 6), 5);
+    }
+
+    #[test]
+    fn streamed_batches_are_processed_as_they_arrive() {
+        let processor = SuggestionPostProcessor::default()
+            .with_order(SuggestionOrder::Alphabetical)
+            .with_deduplication(true);
+        let mut completer = Messy { streaming: true };
+        let mut processed = processor.wrap(&mut completer);
+
+        let mut handle = processed.complete_streaming(
+            &CompletionContext::new("git ch", 6, false),
+            CancellationToken::new(),
+        );
+
+        assert_eq!(
+            values(&handle.take_arrived()),
+            ["ch", "Checkout", "branch", "cherry-pick", "changelog"]
+        );
+        assert!(handle.is_finished());
+    }
+}
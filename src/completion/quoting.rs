@@ -0,0 +1,163 @@
+use std::borrow::Cow;
+
+use super::CompletionContext;
+
+/// How the value of a [`Suggestion`](super::Suggestion) is quoted when a menu
+/// inserts it, e.g. for file names with spaces
+///
+/// Values without characters special to a shell are inserted as they are in
+/// every style. When the user already opened a quote before the suggestion,
+/// the value is escaped for that quote and the quote is closed instead.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum QuotingStyle {
+    /// Insert the value as it is
+    #[default]
+    Verbatim,
+    /// Put a backslash in front of each special character: `My\ Documents`
+    Backslash,
+    /// Wrap the value in single quotes: `'My Documents'`
+    SingleQuotes,
+    /// Wrap the value in double quotes: `"My Documents"`
+    DoubleQuotes,
+}
+
+/// Characters a shell gives a meaning to, besides whitespace
+const SPECIAL: &str = "'\"`\\$&|;<>()[]{}*?!#~";
+
+fn is_special(c: char) -> bool {
+    c.is_whitespace() || SPECIAL.contains(c)
+}
+
+/// Quote `value` in `style` so a shell reads it back as one word
+///
+/// # Example
+/// ```rust
+/// use reedline::{quote_for_shell, QuotingStyle};
+///
+/// assert_eq!(quote_for_shell("My Documents", QuotingStyle::Backslash), r"My\ Documents");
+/// assert_eq!(quote_for_shell("it's", QuotingStyle::SingleQuotes), r"'it'\''s'");
+/// assert_eq!(quote_for_shell("$HOME", QuotingStyle::DoubleQuotes), r#""\$HOME""#);
+/// assert_eq!(quote_for_shell("notes.txt", QuotingStyle::DoubleQuotes), "notes.txt");
+/// ```
+pub fn quote_for_shell(value: &str, style: QuotingStyle) -> Cow<'_, str> {
+    if style == QuotingStyle::Verbatim || !value.chars().any(is_special) {
+        return Cow::Borrowed(value);
+    }
+    match style {
+        QuotingStyle::Verbatim => Cow::Borrowed(value),
+        QuotingStyle::Backslash => {
+            let mut quoted = String::with_capacity(value.len() + 4);
+            for c in value.chars() {
+                if is_special(c) {
+                    quoted.push('\\');
+                }
+                quoted.push(c);
+            }
+            Cow::Owned(quoted)
+        }
+        QuotingStyle::SingleQuotes => Cow::Owned(format!("'{}'", escape_in_quotes(value, '\''))),
+        QuotingStyle::DoubleQuotes => Cow::Owned(format!("\"{}\"", escape_in_quotes(value, '"'))),
+    }
+}
+
+/// Escape `value` to be read literally between two `quote`s
+fn escape_in_quotes(value: &str, quote: char) -> String {
+    match quote {
+        // Single quotes can't be escaped inside single quotes: close the
+        // quote, add an escaped one and open it again
+        '\'' => value.replace('\'', r"'\''"),
+        '"' => {
+            let mut escaped = String::with_capacity(value.len());
+            for c in value.chars() {
+                if matches!(c, '"' | '\\' | '$' | '`') {
+                    escaped.push('\\');
+                }
+                escaped.push(c);
+            }
+            escaped
+        }
+        _ => value.to_string(),
+    }
+}
+
+/// The text replacing `line[start..end]` to insert `value` quoted in `style`
+///
+/// Inside a quote opened before `start`, the value is escaped for it and the
+/// quote closed. A replaced text starting with a quote keeps that quote.
+pub(crate) fn quote_in_line(
+    line: &str,
+    start: usize,
+    end: usize,
+    value: &str,
+    style: QuotingStyle,
+) -> String {
+    if style == QuotingStyle::Verbatim {
+        return value.to_string();
+    }
+    if let Some(quote) = CompletionContext::new(line, start, false).quote {
+        return format!("{}{quote}", escape_in_quotes(value, quote));
+    }
+    match line[start..end].chars().next() {
+        Some(quote @ ('\'' | '"' | '`')) => {
+            format!("{quote}{}{quote}", escape_in_quotes(value, quote))
+        }
+        _ => quote_for_shell(value, style).into_owned(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+    use rstest::rstest;
+
+    #[rstest]
+    #[case::plain("notes.txt", r"notes.txt", r"notes.txt", r"notes.txt")]
+    #[case::unicode(
+        "café ñandú.md",
+        r"café\ ñandú.md",
+        r"'café ñandú.md'",
+        r#""café ñandú.md""#
+    )]
+    #[case::spaces(
+        "My Documents",
+        r"My\ Documents",
+        r"'My Documents'",
+        r#""My Documents""#
+    )]
+    #[case::single_quote("it's", r"it\'s", r"'it'\''s'", r#""it's""#)]
+    #[case::double_quote(r#"say "hi""#, r#"say\ \"hi\""#, r#"'say "hi"'"#, r#""say \"hi\"""#)]
+    #[case::dollar("$HOME", r"\$HOME", r"'$HOME'", r#""\$HOME""#)]
+    #[case::backslash(r"a\b", r"a\\b", r"'a\b'", r#""a\\b""#)]
+    fn values_are_quoted_in_each_style(
+        #[case] value: &str,
+        #[case] backslash: &str,
+        #[case] single: &str,
+        #[case] double: &str,
+    ) {
+        assert_eq!(quote_for_shell(value, QuotingStyle::Verbatim), value);
+        assert_eq!(quote_for_shell(value, QuotingStyle::Backslash), backslash);
+        assert_eq!(quote_for_shell(value, QuotingStyle::SingleQuotes), single);
+        assert_eq!(quote_for_shell(value, QuotingStyle::DoubleQuotes), double);
+    }
+
+    #[rstest]
+    #[case::unquoted("cat My", 4, 6, "My Documents", "'My Documents'")]
+    #[case::inside_single_quote("cat 'My", 5, 7, "My Documents", "My Documents'")]
+    #[case::inside_double_quote("cat \"$H", 5, 7, "$HOME", "\\$HOME\"")]
+    #[case::inside_a_longer_quote("cat 'My Documents/no", 18, 20, "it's", r"it'\''s'")]
+    #[case::span_with_the_quote("cat \"My", 4, 7, "My Documents", "\"My Documents\"")]
+    #[case::span_with_a_closed_quote("cat 'a b' My", 10, 12, "My Documents", "'My Documents'")]
+    fn quotes_opened_in_the_line_are_closed(
+        #[case] line: &str,
+        #[case] start: usize,
+        #[case] end: usize,
+        #[case] value: &str,
+        #[case] expected: &str,
+    ) {
+        assert_eq!(
+            quote_in_line(line, start, end, value, QuotingStyle::SingleQuotes),
+            expected
+        );
+    }
+}
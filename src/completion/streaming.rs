@@ -0,0 +1,247 @@
+use std::{
+    fmt,
+    ops::Range,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        mpsc::{self, Receiver, Sender, TryRecvError},
+        Arc,
+    },
+};
+
+use super::{base_ranges, Suggestion};
+
+/// Tells a completer running in the background that its suggestions are no
+/// longer wanted, e.g. because the line changed
+///
+/// Clones share the state: cancelling one cancels all of them.
+#[derive(Debug, Clone, Default)]
+pub struct CancellationToken(Arc<AtomicBool>);
+
+impl CancellationToken {
+    /// A token that is not cancelled yet
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Ask the completer to stop
+    pub fn cancel(&self) {
+        self.0.store(true, Ordering::Relaxed);
+    }
+
+    /// Whether the suggestions are no longer wanted
+    pub fn is_cancelled(&self) -> bool {
+        self.0.load(Ordering::Relaxed)
+    }
+}
+
+/// Suggestions of a completion, available right away or arriving in batches
+/// from a completer running in the background
+///
+/// Returned by [`Completer::complete_streaming`](super::Completer::complete_streaming).
+#[derive(Debug)]
+pub struct CompletionHandle {
+    /// Suggestions that arrived and were not taken yet
+    arrived: Vec<Suggestion>,
+    /// Batches still to come, `None` once all arrived
+    arriving: Option<Receiver<Vec<Suggestion>>>,
+    /// Ranges of the strings the suggestions arrived so far are based on
+    base_ranges: Vec<Range<usize>>,
+    /// Applied to each batch as it arrives
+    process_batch: Option<BatchProcessor>,
+}
+
+/// Processes the batches of suggestions streamed to a [`CompletionHandle`]
+struct BatchProcessor(Box<dyn FnMut(Vec<Suggestion>) -> Vec<Suggestion> + Send>);
+
+impl fmt::Debug for BatchProcessor {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("BatchProcessor")
+    }
+}
+
+impl CompletionHandle {
+    /// A finished completion with all of its `suggestions`
+    pub fn ready(suggestions: Vec<Suggestion>) -> Self {
+        CompletionHandle {
+            base_ranges: base_ranges(&suggestions),
+            arrived: suggestions,
+            arriving: None,
+            process_batch: None,
+        }
+    }
+
+    /// Use `ranges` as the ranges of the strings the suggestions are based
+    /// on, such as the ones of [`Completer::complete_with_base_ranges`](super::Completer::complete_with_base_ranges)
+    ///
+    /// Without them, they are the spans of the suggestions. Suggestions
+    /// streamed later add the ranges of their spans.
+    #[must_use]
+    pub fn with_base_ranges(mut self, ranges: Vec<Range<usize>>) -> Self {
+        self.base_ranges = ranges;
+        self
+    }
+
+    /// A completion whose suggestions are sent to the returned sink
+    ///
+    /// The completion is finished once the sink is dropped.
+    pub fn streaming() -> (CompletionSink, Self) {
+        let (sender, receiver) = mpsc::channel();
+        let handle = CompletionHandle {
+            arrived: Vec::new(),
+            arriving: Some(receiver),
+            base_ranges: Vec::new(),
+            process_batch: None,
+        };
+        (CompletionSink(sender), handle)
+    }
+
+    /// Take the suggestions that arrived since the last call, without waiting
+    /// for more
+    pub fn take_arrived(&mut self) -> Vec<Suggestion> {
+        if let Some(arriving) = &self.arriving {
+            loop {
+                match arriving.try_recv() {
+                    Ok(batch) => {
+                        let batch = match &mut self.process_batch {
+                            Some(BatchProcessor(process)) => process(batch),
+                            None => batch,
+                        };
+                        for range in base_ranges(&batch) {
+                            if !self.base_ranges.contains(&range) {
+                                self.base_ranges.push(range);
+                            }
+                        }
+                        self.arrived.extend(batch);
+                    }
+                    Err(TryRecvError::Empty) => break,
+                    Err(TryRecvError::Disconnected) => {
+                        self.arriving = None;
+                        break;
+                    }
+                }
+            }
+        }
+        std::mem::take(&mut self.arrived)
+    }
+
+    /// Whether all suggestions arrived
+    ///
+    /// Suggestions that arrived may still have to be taken.
+    pub fn is_finished(&self) -> bool {
+        self.arriving.is_none()
+    }
+
+    /// The handle with `process` applied to each batch of suggestions still
+    /// to arrive
+    pub(crate) fn process_batches(
+        mut self,
+        process: impl FnMut(Vec<Suggestion>) -> Vec<Suggestion> + Send + 'static,
+    ) -> Self {
+        self.process_batch = Some(BatchProcessor(Box::new(process)));
+        self
+    }
+
+    /// The ranges of the strings the suggestions arrived so far are based on,
+    /// taken or not
+    pub fn base_ranges(&self) -> &[Range<usize>] {
+        &self.base_ranges
+    }
+}
+
+/// Where a completer running in the background sends its suggestions, see
+/// [`CompletionHandle::streaming`]
+#[derive(Debug)]
+pub struct CompletionSink(Sender<Vec<Suggestion>>);
+
+impl CompletionSink {
+    /// Send a batch of suggestions, following the ones sent before
+    ///
+    /// Returns `false` if the handle was dropped, i.e. nobody waits for them
+    /// anymore.
+    pub fn send(&self, suggestions: Vec<Suggestion>) -> bool {
+        self.0.send(suggestions).is_ok()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Span;
+    use pretty_assertions::assert_eq;
+
+    fn values(suggestions: Vec<Suggestion>) -> Vec<String> {
+        suggestions.into_iter().map(|s| s.value).collect()
+    }
+
+    fn suggestion(value: &str) -> Suggestion {
+        Suggestion {
+            value: value.to_string(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn ready_handles_are_finished() {
+        let mut handle = CompletionHandle::ready(vec![suggestion("ls")]);
+        assert!(handle.is_finished());
+        assert_eq!(values(handle.take_arrived()), ["ls"]);
+        assert_eq!(handle.take_arrived(), []);
+    }
+
+    #[test]
+    fn streamed_batches_arrive_in_order_until_the_sink_is_dropped() {
+        let (sink, mut handle) = CompletionHandle::streaming();
+        assert_eq!(handle.take_arrived(), []);
+        assert!(!handle.is_finished());
+
+        assert!(sink.send(vec![suggestion("ls"), suggestion("less")]));
+        assert!(sink.send(vec![suggestion("lsof")]));
+        assert_eq!(values(handle.take_arrived()), ["ls", "less", "lsof"]);
+        assert!(!handle.is_finished());
+
+        sink.send(vec![suggestion("ln")]);
+        drop(sink);
+        assert_eq!(values(handle.take_arrived()), ["ln"]);
+        assert!(handle.is_finished());
+    }
+
+    #[test]
+    fn base_ranges_are_the_given_ones_or_those_of_the_spans() {
+        let spanned = |value: &str, start, end| Suggestion {
+            span: Span::new(start, end),
+            ..suggestion(value)
+        };
+        let ready = CompletionHandle::ready(vec![
+            spanned("ls", 0, 1),
+            spanned("less", 0, 1),
+            spanned("cd", 3, 4),
+        ]);
+        assert_eq!(ready.base_ranges(), [0..1, 3..4]);
+        let given =
+            CompletionHandle::ready(vec![spanned("ls", 0, 1)]).with_base_ranges(vec![0..2, 3..5]);
+        assert_eq!(given.base_ranges(), [0..2, 3..5]);
+
+        let (sink, mut handle) = CompletionHandle::streaming();
+        sink.send(vec![spanned("ls", 0, 1)]);
+        handle.take_arrived();
+        sink.send(vec![spanned("cd", 3, 4), spanned("ln", 0, 1)]);
+        handle.take_arrived();
+        assert_eq!(handle.base_ranges(), [0..1, 3..4]);
+    }
+
+    #[test]
+    fn sending_fails_once_nobody_waits() {
+        let (sink, handle) = CompletionHandle::streaming();
+        drop(handle);
+        assert!(!sink.send(vec![suggestion("ls")]));
+    }
+
+    #[test]
+    fn cancelling_a_clone_cancels_the_token() {
+        let token = CancellationToken::new();
+        let clone = token.clone();
+        assert!(!token.is_cancelled());
+        clone.cancel();
+        assert!(token.is_cancelled());
+    }
+}
@@ -15,6 +15,17 @@ pub trait Clipboard: Send {
     fn len(&mut self) -> usize {
         self.get().0.len()
     }
+
+    /// Set the clipboard of the system to `content`, returning whether it
+    /// could
+    ///
+    /// Clipboards local to the application can't, nor can the system
+    /// clipboard without e.g. a display server to hold it.
+    #[allow(dead_code)]
+    fn set_system(&mut self, content: &str) -> bool {
+        let _ = content;
+        false
+    }
 }
 
 /// Determines how the content in the clipboard should be inserted
@@ -100,6 +111,12 @@ mod system_clipboard {
             self.mode = mode;
         }
 
+        fn set_system(&mut self, content: &str) -> bool {
+            self.local_copy = content.to_owned();
+            self.mode = ClipboardMode::Normal;
+            self.cb.set_text(content).is_ok()
+        }
+
         fn get(&mut self) -> (String, ClipboardMode) {
             let system_content = self.cb.get_text().unwrap_or_default();
             if system_content == self.local_copy {
@@ -136,6 +153,13 @@ mod tests {
         cb.set(&previous_state, ClipboardMode::Normal);
     }
 
+    #[test]
+    fn local_clipboard_does_not_reach_the_system() {
+        let mut cb = get_local_clipboard();
+
+        assert!(!cb.set_system("test"));
+    }
+
     #[cfg(feature = "system_clipboard")]
     #[test]
     fn reads_back_system() {
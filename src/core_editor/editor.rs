@@ -21,6 +21,7 @@ pub struct Editor {
     selection_anchor: Option<usize>,
     selection_mode: Option<PromptEditMode>,
     edit_mode: PromptEditMode,
+    overwrite: bool,
 }
 
 impl Default for Editor {
@@ -35,6 +36,7 @@ impl Default for Editor {
             selection_anchor: None,
             selection_mode: None,
             edit_mode: PromptEditMode::Default,
+            overwrite: false,
         }
     }
 }
@@ -45,6 +47,11 @@ impl Editor {
         &self.line_buffer
     }
 
+    /// Whether typed characters replace the ones under the cursor
+    pub const fn is_overwrite(&self) -> bool {
+        self.overwrite
+    }
+
     /// Set the current [`LineBuffer`].
     /// [`UndoBehavior`] specifies how this change should be reflected on the undo stack.
     pub(crate) fn set_line_buffer(&mut self, line_buffer: LineBuffer, undo_behavior: UndoBehavior) {
@@ -184,6 +191,7 @@ impl Editor {
                 }
             }
             EditCommand::SwapCursorAndAnchor => self.swap_cursor_and_anchor(),
+            EditCommand::ToggleOverwrite => self.overwrite = !self.overwrite,
             #[cfg(feature = "system_clipboard")]
             EditCommand::CutSelectionSystem => self.cut_selection_to_system(),
             #[cfg(feature = "system_clipboard")]
@@ -651,6 +659,14 @@ impl Editor {
         }
     }
 
+    /// Sets the system clipboard to `text`, leaving the buffer as is
+    ///
+    /// Returns `false` if the system clipboard is unavailable or refused it.
+    #[cfg(all(feature = "system_clipboard", feature = "lsp_diagnostics"))]
+    pub(crate) fn copy_to_system_clipboard(&mut self, text: &str) -> bool {
+        self.system_clipboard.set_system(text)
+    }
+
     #[cfg(feature = "system_clipboard")]
     fn copy_selection_to_system(&mut self) {
         if let Some((start, end)) = self.get_selection() {
@@ -756,7 +772,13 @@ impl Editor {
     }
 
     fn insert_char(&mut self, c: char) {
+        // Typing over a selection replaces it, whether overwriting or not
+        let overwrite = self.overwrite && self.get_selection().is_none();
         self.delete_selection();
+        // Overwriting stops at the end of the line instead of joining lines
+        if overwrite && !matches!(self.line_buffer.grapheme_right(), "\n" | "\r\n") {
+            self.line_buffer.delete_right_grapheme();
+        }
         self.line_buffer.insert_char(c);
     }
 
@@ -1138,6 +1160,74 @@ mod test {
         editor
     }
 
+    fn type_chars(editor: &mut Editor, chars: &str) {
+        for c in chars.chars() {
+            editor.run_edit_command(&EditCommand::InsertChar(c));
+        }
+    }
+
+    #[rstest]
+    #[case("héllo", 1, "ab", "hablo")]
+    #[case("a👍🏽b", 1, "x", "axb")]
+    #[case("日本語", 3, "ab", "日ab")]
+    #[case("ab", 1, "xyz", "axyz")]
+    #[case("ab\ncd", 1, "xyz", "axyz\ncd")]
+    fn overwrite_replaces_graphemes(
+        #[case] input: &str,
+        #[case] position: usize,
+        #[case] typed: &str,
+        #[case] expected: &str,
+    ) {
+        let mut editor = editor_with(input);
+        editor.line_buffer.set_insertion_point(position);
+        editor.run_edit_command(&EditCommand::ToggleOverwrite);
+
+        type_chars(&mut editor, typed);
+
+        assert_eq!(editor.get_buffer(), expected);
+        assert_eq!(editor.insertion_point(), position + typed.len());
+    }
+
+    #[test]
+    fn toggling_overwrite_off_inserts_again() {
+        let mut editor = editor_with("héllo");
+        editor.line_buffer.set_insertion_point(1);
+
+        editor.run_edit_command(&EditCommand::ToggleOverwrite);
+        assert!(editor.is_overwrite());
+        type_chars(&mut editor, "e");
+        editor.run_edit_command(&EditCommand::ToggleOverwrite);
+        assert!(!editor.is_overwrite());
+        type_chars(&mut editor, "e");
+
+        assert_eq!(editor.get_buffer(), "heello");
+    }
+
+    #[test]
+    fn overwrite_over_selection_only_replaces_selection() {
+        let mut editor = editor_with("abcdef");
+        editor.run_edit_command(&EditCommand::ToggleOverwrite);
+        editor.run_edit_command(&EditCommand::MoveToStart { select: false });
+        editor.run_edit_command(&EditCommand::MoveRight { select: true });
+        editor.run_edit_command(&EditCommand::MoveRight { select: true });
+
+        type_chars(&mut editor, "x");
+
+        assert_eq!(editor.get_buffer(), "xcdef");
+    }
+
+    #[test]
+    fn overwrite_is_undone_per_typed_run() {
+        let mut editor = editor_with("héllo");
+        editor.line_buffer.set_insertion_point(1);
+        editor.run_edit_command(&EditCommand::ToggleOverwrite);
+        type_chars(&mut editor, "ab");
+
+        editor.run_edit_command(&EditCommand::Undo);
+
+        assert_eq!(editor.get_buffer(), "héllo");
+    }
+
     #[rstest]
     #[case("abc def ghi", 11, "abc def ")]
     #[case("abc def-ghi", 11, "abc def-")]
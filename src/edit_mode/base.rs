@@ -1,6 +1,6 @@
 use crate::{
     enums::{EventStatus, ReedlineEvent, ReedlineRawEvent},
-    PromptEditMode,
+    Keybindings, PromptEditMode,
 };
 
 /// Define the style of parsing for the edit events
@@ -18,4 +18,25 @@ pub trait EditMode: Send {
     fn handle_mode_specific_event(&mut self, _event: ReedlineEvent) -> EventStatus {
         EventStatus::Inapplicable
     }
+
+    /// The keybinding tables in use, each labelled with the mode it applies to
+    ///
+    /// Edit modes with a single table return one entry; Vi returns its
+    /// `vi_normal` and `vi_insert` tables. Defaults to no tables.
+    fn keybindings(&self) -> Vec<(&'static str, &Keybindings)> {
+        Vec::new()
+    }
+
+    /// The keybinding tables in use, to add bindings to the configured mode
+    ///
+    /// Same tables as [`EditMode::keybindings`]. Defaults to no tables.
+    fn keybindings_mut(&mut self) -> Vec<&mut Keybindings> {
+        Vec::new()
+    }
+
+    /// Whether characters typed with AltGr that have no `Ctrl+Alt` binding are accepted
+    ///
+    /// Called by [`Reedline::with_altgr_insertion`](crate::Reedline::with_altgr_insertion)
+    /// and whenever the edit mode is replaced. Defaults to ignoring the setting.
+    fn set_altgr_insertion(&mut self, _enabled: bool) {}
 }
@@ -2,7 +2,7 @@ use crate::{
     edit_mode::{
         keybindings::{
             add_common_control_bindings, add_common_edit_bindings, add_common_navigation_bindings,
-            add_common_selection_bindings, edit_bind, Keybindings,
+            add_common_selection_bindings, edit_bind, insertable_char, Keybindings,
         },
         EditMode,
     },
@@ -115,12 +115,14 @@ pub fn default_emacs_keybindings() -> Keybindings {
 /// This parses the incoming Events like a emacs style-editor
 pub struct Emacs {
     keybindings: Keybindings,
+    altgr_insertion: bool,
 }
 
 impl Default for Emacs {
     fn default() -> Self {
         Emacs {
             keybindings: default_emacs_keybindings(),
+            altgr_insertion: true,
         }
     }
 }
@@ -140,31 +142,17 @@ impl EditMode for Emacs {
                     // Mixed modifiers are used by non american keyboards that have extra
                     // keys like 'alt gr'. Keep this in mind if in the future there are
                     // cases where an event is not being captured
-                    let c = match modifier {
+                    let binding_char = match modifier {
                         KeyModifiers::NONE => c,
                         _ => c.to_ascii_lowercase(),
                     };
 
                     self.keybindings
-                        .find_binding(modifier, KeyCode::Char(c))
+                        .find_binding(modifier, KeyCode::Char(binding_char))
                         .unwrap_or_else(|| {
-                            if modifier == KeyModifiers::NONE
-                                || modifier == KeyModifiers::SHIFT
-                                || modifier == KeyModifiers::CONTROL | KeyModifiers::ALT
-                                || modifier
-                                    == KeyModifiers::CONTROL
-                                        | KeyModifiers::ALT
-                                        | KeyModifiers::SHIFT
-                            {
-                                ReedlineEvent::Edit(vec![EditCommand::InsertChar(
-                                    if modifier == KeyModifiers::SHIFT {
-                                        c.to_ascii_uppercase()
-                                    } else {
-                                        c
-                                    },
-                                )])
-                            } else {
-                                ReedlineEvent::None
+                            match insertable_char(modifier, c, self.altgr_insertion) {
+                                Some(c) => ReedlineEvent::Edit(vec![EditCommand::InsertChar(c)]),
+                                None => ReedlineEvent::None,
                             }
                         })
                 }
@@ -184,6 +172,20 @@ impl EditMode for Emacs {
                 row,
                 button: button.into(),
             },
+            Event::Mouse(MouseEvent {
+                kind: MouseEventKind::Drag(crossterm::event::MouseButton::Left),
+                column,
+                row,
+                modifiers: KeyModifiers::NONE,
+            }) => ReedlineEvent::MouseDrag { column, row },
+            Event::Mouse(MouseEvent {
+                kind: MouseEventKind::ScrollUp,
+                ..
+            }) => ReedlineEvent::MouseScrollUp,
+            Event::Mouse(MouseEvent {
+                kind: MouseEventKind::ScrollDown,
+                ..
+            }) => ReedlineEvent::MouseScrollDown,
             Event::Mouse(_) => ReedlineEvent::None,
             Event::Resize(width, height) => ReedlineEvent::Resize(width, height),
             Event::FocusGained => ReedlineEvent::None,
@@ -197,18 +199,34 @@ impl EditMode for Emacs {
     fn edit_mode(&self) -> PromptEditMode {
         PromptEditMode::Emacs
     }
+
+    fn keybindings(&self) -> Vec<(&'static str, &Keybindings)> {
+        vec![("emacs", &self.keybindings)]
+    }
+
+    fn keybindings_mut(&mut self) -> Vec<&mut Keybindings> {
+        vec![&mut self.keybindings]
+    }
+
+    fn set_altgr_insertion(&mut self, enabled: bool) {
+        self.altgr_insertion = enabled;
+    }
 }
 
 impl Emacs {
     /// Emacs style input parsing constructor if you want to use custom keybindings
     pub const fn new(keybindings: Keybindings) -> Self {
-        Emacs { keybindings }
+        Emacs {
+            keybindings,
+            altgr_insertion: true,
+        }
     }
 }
 
 #[cfg(test)]
 mod test {
     use super::*;
+    use crossterm::event::{KeyEventKind, KeyEventState};
     use pretty_assertions::assert_eq;
 
     #[test]
@@ -309,6 +327,148 @@ mod test {
         );
     }
 
+    fn altgr_event(c: char, shift: bool) -> ReedlineRawEvent {
+        let mut modifiers = KeyModifiers::CONTROL | KeyModifiers::ALT;
+        if shift {
+            modifiers |= KeyModifiers::SHIFT;
+        }
+        ReedlineRawEvent::try_from(Event::Key(KeyEvent::new(KeyCode::Char(c), modifiers))).unwrap()
+    }
+
+    #[test]
+    fn altgr_characters_are_inserted() {
+        let mut emacs = Emacs::default();
+
+        for c in ['@', '{', '~', '€'] {
+            assert_eq!(
+                emacs.parse_event(altgr_event(c, false)),
+                ReedlineEvent::Edit(vec![EditCommand::InsertChar(c)])
+            );
+        }
+    }
+
+    #[test]
+    fn shifted_altgr_character_keeps_its_case() {
+        let mut emacs = Emacs::default();
+
+        assert_eq!(
+            emacs.parse_event(altgr_event('Ω', true)),
+            ReedlineEvent::Edit(vec![EditCommand::InsertChar('Ω')])
+        );
+        assert_eq!(
+            emacs.parse_event(altgr_event('Q', true)),
+            ReedlineEvent::Edit(vec![EditCommand::InsertChar('Q')])
+        );
+    }
+
+    #[test]
+    fn explicit_ctrl_alt_binding_wins_over_altgr_insertion() {
+        let mut keybindings = default_emacs_keybindings();
+        keybindings.add_binding(
+            KeyModifiers::CONTROL | KeyModifiers::ALT,
+            KeyCode::Char('q'),
+            ReedlineEvent::ClearScreen,
+        );
+        let mut emacs = Emacs::new(keybindings);
+
+        assert_eq!(
+            emacs.parse_event(altgr_event('q', false)),
+            ReedlineEvent::ClearScreen
+        );
+    }
+
+    #[test]
+    fn altgr_insertion_can_be_disabled() {
+        let mut emacs = Emacs::default();
+        emacs.set_altgr_insertion(false);
+
+        assert_eq!(
+            emacs.parse_event(altgr_event('@', false)),
+            ReedlineEvent::None
+        );
+    }
+
+    #[test]
+    fn dead_key_control_characters_are_dropped() {
+        let mut emacs = Emacs::default();
+
+        assert_eq!(
+            emacs.parse_event(altgr_event('\0', false)),
+            ReedlineEvent::None
+        );
+        let nul = ReedlineRawEvent::try_from(Event::Key(KeyEvent::new(
+            KeyCode::Char('\0'),
+            KeyModifiers::NONE,
+        )))
+        .unwrap();
+        assert_eq!(emacs.parse_event(nul), ReedlineEvent::None);
+    }
+
+    #[test]
+    fn altgr_repeat_with_kitty_state_is_inserted() {
+        let mut emacs = Emacs::default();
+        let event = ReedlineRawEvent::try_from(Event::Key(KeyEvent::new_with_kind_and_state(
+            KeyCode::Char('{'),
+            KeyModifiers::CONTROL | KeyModifiers::ALT,
+            KeyEventKind::Repeat,
+            KeyEventState::NUM_LOCK,
+        )))
+        .unwrap();
+
+        assert_eq!(
+            emacs.parse_event(event),
+            ReedlineEvent::Edit(vec![EditCommand::InsertChar('{')])
+        );
+    }
+
+    #[test]
+    fn insert_key_toggles_overwrite() {
+        let mut emacs = Emacs::default();
+        let insert = ReedlineRawEvent::try_from(Event::Key(KeyEvent::new(
+            KeyCode::Insert,
+            KeyModifiers::NONE,
+        )))
+        .unwrap();
+
+        assert_eq!(
+            emacs.parse_event(insert),
+            ReedlineEvent::Edit(vec![EditCommand::ToggleOverwrite])
+        );
+    }
+
+    #[test]
+    fn keypad_keys_map_to_main_cluster_events() {
+        let mut emacs = Emacs::default();
+        let keypad = |code| {
+            ReedlineRawEvent::try_from(Event::Key(KeyEvent::new_with_kind_and_state(
+                code,
+                KeyModifiers::NONE,
+                KeyEventKind::Press,
+                KeyEventState::KEYPAD,
+            )))
+            .unwrap()
+        };
+
+        assert_eq!(
+            emacs.parse_event(keypad(KeyCode::Enter)),
+            ReedlineEvent::Enter
+        );
+        assert_eq!(
+            emacs.parse_event(keypad(KeyCode::Home)),
+            emacs.parse_event(
+                ReedlineRawEvent::try_from(Event::Key(KeyEvent::new(
+                    KeyCode::Home,
+                    KeyModifiers::NONE
+                )))
+                .unwrap()
+            )
+        );
+        assert_eq!(
+            emacs.parse_event(keypad(KeyCode::KeypadBegin)),
+            ReedlineEvent::None
+        );
+    }
+
     #[test]
     fn kill_line() {
         let mut emacs = Emacs::default();
@@ -2,7 +2,7 @@ use {
     crate::{enums::ReedlineEvent, EditCommand},
     crossterm::event::{KeyCode, KeyModifiers},
     serde::{Deserialize, Serialize},
-    std::collections::HashMap,
+    std::{cmp::Ordering, collections::HashMap},
 };
 
 #[derive(Serialize, Deserialize, Clone, PartialEq, Eq, Hash, Debug)]
@@ -81,6 +81,88 @@ impl Keybindings {
     pub fn get_keybindings(&self) -> &HashMap<KeyCombination, ReedlineEvent> {
         &self.bindings
     }
+
+    /// Iterate over all bindings, ordered by modifier and then key code
+    pub fn iter(&self) -> impl Iterator<Item = (KeyModifiers, KeyCode, &ReedlineEvent)> {
+        let mut bindings: Vec<_> = self
+            .bindings
+            .iter()
+            .map(|(combo, event)| (combo.modifier, combo.key_code, event))
+            .collect();
+        bindings.sort_by(|a, b| compare_combination(a.0, a.1, b.0, b.1));
+        bindings.into_iter()
+    }
+
+    /// Find the key combinations that trigger `event`
+    ///
+    /// Bindings match when their event is `event` or runs it as part of a
+    /// [`ReedlineEvent::UntilFound`], [`ReedlineEvent::Multiple`] or a multi-command
+    /// [`ReedlineEvent::Edit`]. The result is ordered like [`Keybindings::iter`].
+    pub fn find(&self, event: &ReedlineEvent) -> Vec<KeyCombination> {
+        self.iter()
+            .filter(|(_, _, bound)| bound.contains(event))
+            .map(|(modifier, key_code, _)| KeyCombination { modifier, key_code })
+            .collect()
+    }
+
+    /// List the key combinations bound to different events here and in `other`
+    ///
+    /// Each entry holds the combination, the event bound here and the one bound
+    /// in `other`, e.g. a user binding and the default it shadows.
+    pub fn conflicts_with<'a>(
+        &'a self,
+        other: &'a Keybindings,
+    ) -> Vec<(KeyCombination, &'a ReedlineEvent, &'a ReedlineEvent)> {
+        self.iter()
+            .filter_map(|(modifier, key_code, ours)| {
+                let theirs = other.bindings.get(&KeyCombination { modifier, key_code })?;
+                (ours != theirs).then_some((KeyCombination { modifier, key_code }, ours, theirs))
+            })
+            .collect()
+    }
+}
+
+fn compare_combination(
+    modifier: KeyModifiers,
+    key_code: KeyCode,
+    other_modifier: KeyModifiers,
+    other_key_code: KeyCode,
+) -> Ordering {
+    modifier.bits().cmp(&other_modifier.bits()).then_with(|| {
+        key_code
+            .partial_cmp(&other_key_code)
+            .unwrap_or(Ordering::Equal)
+    })
+}
+
+/// Whether `modifier` is how an AltGr chord arrives.
+///
+/// Terminals on Windows report AltGr as `Ctrl+Alt`, with `Shift` added for
+/// the shifted layer of the key.
+pub(crate) fn is_altgr(modifier: KeyModifiers) -> bool {
+    modifier == KeyModifiers::CONTROL | KeyModifiers::ALT
+        || modifier == KeyModifiers::CONTROL | KeyModifiers::ALT | KeyModifiers::SHIFT
+}
+
+/// The character to insert for an unbound key press, if any.
+///
+/// AltGr chords insert the character exactly as the layout produced it (e.g.
+/// `@` or `{` on German layouts) when `altgr_insertion` is set. Control
+/// characters, which dead keys can produce on their own, are never inserted.
+pub(crate) fn insertable_char(
+    modifier: KeyModifiers,
+    c: char,
+    altgr_insertion: bool,
+) -> Option<char> {
+    if c.is_control() {
+        return None;
+    }
+    match modifier {
+        KeyModifiers::NONE => Some(c),
+        KeyModifiers::SHIFT => Some(c.to_ascii_uppercase()),
+        modifier if altgr_insertion && is_altgr(modifier) => Some(c),
+        _ => None,
+    }
 }
 
 pub fn edit_bind(command: EditCommand) -> ReedlineEvent {
@@ -90,6 +172,7 @@ pub fn edit_bind(command: EditCommand) -> ReedlineEvent {
 /// Add the basic special keybindings
 ///
 /// `Ctrl-C`, `Ctrl-D`, `Ctrl-O`, `Ctrl-R`
+/// + `Alt-R` to change the scope of the history search
 /// + `Esc`
 /// + `Ctrl-O` to open the external editor
 pub fn add_common_control_bindings(kb: &mut Keybindings) {
@@ -101,6 +184,7 @@ pub fn add_common_control_bindings(kb: &mut Keybindings) {
     kb.add_binding(KM::CONTROL, KC::Char('d'), ReedlineEvent::CtrlD);
     kb.add_binding(KM::CONTROL, KC::Char('l'), ReedlineEvent::ClearScreen);
     kb.add_binding(KM::CONTROL, KC::Char('r'), ReedlineEvent::SearchHistory);
+    kb.add_binding(KM::ALT, KC::Char('r'), ReedlineEvent::CycleHistoryScope);
     kb.add_binding(KM::CONTROL, KC::Char('o'), ReedlineEvent::OpenEditor);
 }
 /// Add the arrow navigation and its `Ctrl` variants
@@ -120,6 +204,9 @@ pub fn add_common_navigation_bindings(kb: &mut Keybindings) {
         KC::Down,
         ReedlineEvent::UntilFound(vec![ReedlineEvent::MenuDown, ReedlineEvent::Down]),
     );
+    // Scroll the description of the selected menu entry
+    kb.add_binding(KM::ALT, KC::Up, ReedlineEvent::MenuDescriptionUp);
+    kb.add_binding(KM::ALT, KC::Down, ReedlineEvent::MenuDescriptionDown);
     kb.add_binding(
         KM::NONE,
         KC::Left,
@@ -209,8 +296,15 @@ pub fn add_common_edit_bindings(kb: &mut Keybindings) {
     use KeyModifiers as KM;
     kb.add_binding(KM::NONE, KC::Backspace, edit_bind(EC::Backspace));
     kb.add_binding(KM::NONE, KC::Delete, edit_bind(EC::Delete));
+    kb.add_binding(KM::NONE, KC::Insert, edit_bind(EC::ToggleOverwrite));
     kb.add_binding(KM::CONTROL, KC::Backspace, edit_bind(EC::BackspaceWord));
     kb.add_binding(KM::CONTROL, KC::Delete, edit_bind(EC::DeleteWord));
+    kb.add_binding(
+        KM::SHIFT,
+        KC::Delete,
+        ReedlineEvent::UntilFound(vec![ReedlineEvent::MenuDeleteEntry, edit_bind(EC::Delete)]),
+    );
+    kb.add_binding(KM::ALT, KC::Char('e'), ReedlineEvent::MenuEditEntry);
     // Base commands should not affect cut buffer
     kb.add_binding(KM::CONTROL, KC::Char('h'), edit_bind(EC::Backspace));
     kb.add_binding(KM::CONTROL, KC::Char('w'), edit_bind(EC::BackspaceWord));
@@ -232,7 +326,14 @@ pub fn add_common_edit_bindings(kb: &mut Keybindings) {
         KC::Char('v'),
         edit_bind(EC::PasteSystem),
     );
-    kb.add_binding(KM::ALT, KC::Enter, edit_bind(EC::InsertNewline));
+    kb.add_binding(
+        KM::ALT,
+        KC::Enter,
+        ReedlineEvent::UntilFound(vec![
+            ReedlineEvent::MenuAcceptAndContinue,
+            edit_bind(EC::InsertNewline),
+        ]),
+    );
     kb.add_binding(KM::SHIFT, KC::Enter, edit_bind(EC::InsertNewline));
     kb.add_binding(KM::CONTROL, KC::Char('j'), ReedlineEvent::Enter);
 }
@@ -288,3 +389,130 @@ pub fn add_common_selection_bindings(kb: &mut Keybindings) {
         edit_bind(EC::SelectAll),
     );
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::default_emacs_keybindings;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn find_looks_inside_nested_events() {
+        let keybindings = default_emacs_keybindings();
+
+        // Ctrl-F is UntilFound[HistoryHintComplete, MenuRight, Right]
+        let found = keybindings.find(&ReedlineEvent::MenuRight);
+        assert!(found.contains(&KeyCombination {
+            modifier: KeyModifiers::CONTROL,
+            key_code: KeyCode::Char('f'),
+        }));
+
+        // Alt-Right runs MoveWordRight inside an UntilFound
+        let found = keybindings.find(&edit_bind(EditCommand::MoveWordRight { select: false }));
+        assert!(found.contains(&KeyCombination {
+            modifier: KeyModifiers::ALT,
+            key_code: KeyCode::Right,
+        }));
+        assert!(found.contains(&KeyCombination {
+            modifier: KeyModifiers::ALT,
+            key_code: KeyCode::Char('f'),
+        }));
+    }
+
+    #[test]
+    fn find_matches_nested_multiple_and_edit_lists() {
+        let mut keybindings = Keybindings::new();
+        keybindings.add_binding(
+            KeyModifiers::NONE,
+            KeyCode::F(2),
+            ReedlineEvent::UntilFound(vec![
+                ReedlineEvent::MenuNext,
+                ReedlineEvent::Multiple(vec![ReedlineEvent::Edit(vec![
+                    EditCommand::MoveToStart { select: false },
+                    EditCommand::InsertChar('#'),
+                ])]),
+            ]),
+        );
+
+        assert_eq!(
+            keybindings.find(&edit_bind(EditCommand::InsertChar('#'))),
+            vec![KeyCombination {
+                modifier: KeyModifiers::NONE,
+                key_code: KeyCode::F(2),
+            }]
+        );
+        assert!(keybindings.find(&ReedlineEvent::Enter).is_empty());
+    }
+
+    #[test]
+    fn iter_is_ordered_by_modifier_then_key() {
+        let mut keybindings = Keybindings::new();
+        keybindings.add_binding(
+            KeyModifiers::CONTROL,
+            KeyCode::Char('b'),
+            ReedlineEvent::Left,
+        );
+        keybindings.add_binding(KeyModifiers::NONE, KeyCode::Char('z'), ReedlineEvent::Up);
+        keybindings.add_binding(
+            KeyModifiers::CONTROL,
+            KeyCode::Char('a'),
+            ReedlineEvent::Right,
+        );
+
+        let keys: Vec<_> = keybindings.iter().map(|(m, k, _)| (m, k)).collect();
+
+        assert_eq!(
+            keys,
+            vec![
+                (KeyModifiers::NONE, KeyCode::Char('z')),
+                (KeyModifiers::CONTROL, KeyCode::Char('a')),
+                (KeyModifiers::CONTROL, KeyCode::Char('b')),
+            ]
+        );
+    }
+
+    #[test]
+    fn conflicts_list_shadowed_bindings_only() {
+        let defaults = default_emacs_keybindings();
+        let mut user = Keybindings::new();
+        user.add_binding(
+            KeyModifiers::CONTROL,
+            KeyCode::Char('l'),
+            ReedlineEvent::Enter,
+        );
+        user.add_binding(
+            KeyModifiers::CONTROL,
+            KeyCode::Char('c'),
+            ReedlineEvent::CtrlC,
+        );
+        user.add_binding(KeyModifiers::NONE, KeyCode::F(5), ReedlineEvent::Repaint);
+
+        let conflicts = user.conflicts_with(&defaults);
+
+        assert_eq!(
+            conflicts,
+            vec![(
+                KeyCombination {
+                    modifier: KeyModifiers::CONTROL,
+                    key_code: KeyCode::Char('l'),
+                },
+                &ReedlineEvent::Enter,
+                &ReedlineEvent::ClearScreen,
+            )]
+        );
+    }
+
+    #[test]
+    fn describe_expands_nested_events() {
+        let event = ReedlineEvent::UntilFound(vec![
+            ReedlineEvent::Menu("completion_menu".into()),
+            ReedlineEvent::Edit(vec![EditCommand::InsertChar('x')]),
+        ]);
+
+        assert_eq!(
+            event.describe(),
+            "UntilFound(Menu(completion_menu) | Edit(InsertChar('x')))"
+        );
+        assert_eq!(ReedlineEvent::Enter.describe(), "Enter");
+    }
+}
@@ -2,10 +2,14 @@ mod base;
 mod cursors;
 mod emacs;
 mod keybindings;
+mod reedline_keybindings;
 mod vi;
 
 pub use base::EditMode;
 pub use cursors::CursorConfig;
 pub use emacs::{default_emacs_keybindings, Emacs};
 pub use keybindings::Keybindings;
+pub use reedline_keybindings::{
+    add_menu_keybindings, KeybindingsMode, ReedlineKeybindings, COMPLETION_MENU,
+};
 pub use vi::{default_vi_insert_keybindings, default_vi_normal_keybindings, Vi};
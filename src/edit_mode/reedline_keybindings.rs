@@ -0,0 +1,223 @@
+use {
+    super::{
+        default_emacs_keybindings, default_vi_insert_keybindings, default_vi_normal_keybindings,
+        EditMode, Emacs, Keybindings, Vi,
+    },
+    crate::enums::ReedlineEvent,
+    crossterm::event::{KeyCode, KeyModifiers},
+    serde::{Deserialize, Serialize},
+};
+
+/// Name of the completion menu opened by the default menu keybindings
+pub const COMPLETION_MENU: &str = "completion_menu";
+
+/// Selects the edit mode built by [`ReedlineKeybindings::into_edit_mode`]
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum KeybindingsMode {
+    /// [`Emacs`] using the Emacs table
+    #[default]
+    Emacs,
+    /// [`Vi`] using the Vi insert and normal tables
+    Vi,
+}
+
+/// Keybinding tables for every edit mode
+///
+/// Holds the Emacs table together with the Vi insert and normal tables, so a
+/// binding can be added to all of them at once with
+/// [`ReedlineKeybindings::bind_all`] or to a single one through its field.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct ReedlineKeybindings {
+    /// Keybindings of the Emacs edit mode
+    pub emacs: Keybindings,
+    /// Keybindings of the Vi insert mode
+    pub vi_insert: Keybindings,
+    /// Keybindings of the Vi normal mode
+    pub vi_normal: Keybindings,
+}
+
+impl Default for ReedlineKeybindings {
+    fn default() -> Self {
+        let mut keybindings = Self {
+            emacs: default_emacs_keybindings(),
+            vi_insert: default_vi_insert_keybindings(),
+            vi_normal: default_vi_normal_keybindings(),
+        };
+        for table in keybindings.tables_mut() {
+            add_menu_keybindings(table);
+        }
+        keybindings
+    }
+}
+
+impl ReedlineKeybindings {
+    /// Empty tables for every mode
+    pub fn empty() -> Self {
+        Self {
+            emacs: Keybindings::empty(),
+            vi_insert: Keybindings::empty(),
+            vi_normal: Keybindings::empty(),
+        }
+    }
+
+    /// Use an existing Emacs table, with the default Vi tables
+    pub fn from_emacs(emacs: Keybindings) -> Self {
+        Self {
+            emacs,
+            ..Self::default()
+        }
+    }
+
+    /// Use existing Vi insert and normal tables, with the default Emacs table
+    pub fn from_vi(vi_insert: Keybindings, vi_normal: Keybindings) -> Self {
+        Self {
+            vi_insert,
+            vi_normal,
+            ..Self::default()
+        }
+    }
+
+    /// Add a keybinding to the Emacs, Vi insert and Vi normal tables
+    ///
+    /// # Panics
+    ///
+    /// If `event` is an empty [`ReedlineEvent::UntilFound`]
+    pub fn bind_all(&mut self, modifier: KeyModifiers, key_code: KeyCode, event: ReedlineEvent) {
+        for table in self.tables_mut() {
+            table.add_binding(modifier, key_code, event.clone());
+        }
+    }
+
+    /// Remove a keybinding from the Emacs, Vi insert and Vi normal tables
+    pub fn unbind_all(&mut self, modifier: KeyModifiers, key_code: KeyCode) {
+        for table in self.tables_mut() {
+            table.remove_binding(modifier, key_code);
+        }
+    }
+
+    /// The tables used by `mode`, each labelled like [`EditMode::keybindings`]
+    pub fn tables(&self, mode: KeybindingsMode) -> Vec<(&'static str, &Keybindings)> {
+        match mode {
+            KeybindingsMode::Emacs => vec![("emacs", &self.emacs)],
+            KeybindingsMode::Vi => vec![
+                ("vi_normal", &self.vi_normal),
+                ("vi_insert", &self.vi_insert),
+            ],
+        }
+    }
+
+    /// Build the edit mode selected by `mode` from these tables
+    pub fn into_edit_mode(self, mode: KeybindingsMode) -> Box<dyn EditMode> {
+        match mode {
+            KeybindingsMode::Emacs => Box::new(Emacs::new(self.emacs)),
+            KeybindingsMode::Vi => Box::new(Vi::new(self.vi_insert, self.vi_normal)),
+        }
+    }
+
+    fn tables_mut(&mut self) -> [&mut Keybindings; 3] {
+        [&mut self.emacs, &mut self.vi_insert, &mut self.vi_normal]
+    }
+}
+
+/// Add the default menu keybindings
+///
+/// - `Tab` opens the [`COMPLETION_MENU`] or moves to the next entry
+/// - `Shift+Tab` moves to the previous menu entry
+/// - `Esc` closes the active menu
+pub fn add_menu_keybindings(kb: &mut Keybindings) {
+    use KeyCode as KC;
+    use KeyModifiers as KM;
+
+    kb.add_binding(
+        KM::NONE,
+        KC::Tab,
+        ReedlineEvent::UntilFound(vec![
+            ReedlineEvent::Menu(COMPLETION_MENU.to_string()),
+            ReedlineEvent::MenuNext,
+        ]),
+    );
+    kb.add_binding(KM::SHIFT, KC::BackTab, ReedlineEvent::MenuPrevious);
+    kb.add_binding(KM::NONE, KC::Esc, ReedlineEvent::Esc);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn defaults_bind_menu_keys_in_every_table() {
+        let keybindings = ReedlineKeybindings::default();
+
+        for table in [
+            &keybindings.emacs,
+            &keybindings.vi_insert,
+            &keybindings.vi_normal,
+        ] {
+            assert_eq!(
+                table.find_binding(KeyModifiers::NONE, KeyCode::Tab),
+                Some(ReedlineEvent::UntilFound(vec![
+                    ReedlineEvent::Menu(COMPLETION_MENU.to_string()),
+                    ReedlineEvent::MenuNext,
+                ]))
+            );
+            assert_eq!(
+                table.find_binding(KeyModifiers::NONE, KeyCode::Esc),
+                Some(ReedlineEvent::Esc)
+            );
+        }
+    }
+
+    #[test]
+    fn bind_all_then_override_one_mode() {
+        let mut keybindings = ReedlineKeybindings::empty();
+        keybindings.bind_all(KeyModifiers::NONE, KeyCode::F(1), ReedlineEvent::Repaint);
+        keybindings
+            .vi_normal
+            .add_binding(KeyModifiers::NONE, KeyCode::F(1), ReedlineEvent::Enter);
+
+        assert_eq!(
+            keybindings
+                .emacs
+                .find_binding(KeyModifiers::NONE, KeyCode::F(1)),
+            Some(ReedlineEvent::Repaint)
+        );
+        assert_eq!(
+            keybindings
+                .vi_insert
+                .find_binding(KeyModifiers::NONE, KeyCode::F(1)),
+            Some(ReedlineEvent::Repaint)
+        );
+        assert_eq!(
+            keybindings
+                .vi_normal
+                .find_binding(KeyModifiers::NONE, KeyCode::F(1)),
+            Some(ReedlineEvent::Enter)
+        );
+    }
+
+    #[test]
+    fn into_edit_mode_uses_the_selected_tables() {
+        let mut keybindings = ReedlineKeybindings::empty();
+        keybindings
+            .vi_insert
+            .add_binding(KeyModifiers::NONE, KeyCode::F(2), ReedlineEvent::Enter);
+
+        let edit_mode = keybindings.clone().into_edit_mode(KeybindingsMode::Vi);
+        let labels: Vec<_> = edit_mode
+            .keybindings()
+            .into_iter()
+            .map(|(label, _)| label)
+            .collect();
+        assert_eq!(labels, vec!["vi_normal", "vi_insert"]);
+        assert_eq!(
+            edit_mode.keybindings()[1]
+                .1
+                .find_binding(KeyModifiers::NONE, KeyCode::F(2)),
+            Some(ReedlineEvent::Enter)
+        );
+
+        let edit_mode = keybindings.into_edit_mode(KeybindingsMode::Emacs);
+        assert_eq!(edit_mode.keybindings()[0].0, "emacs");
+    }
+}
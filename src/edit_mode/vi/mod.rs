@@ -12,7 +12,10 @@ use self::motion::ViCharSearch;
 
 use super::EditMode;
 use crate::{
-    edit_mode::{keybindings::Keybindings, vi::parser::parse},
+    edit_mode::{
+        keybindings::{insertable_char, is_altgr, Keybindings},
+        vi::parser::parse,
+    },
     enums::{EditCommand, EventStatus, ReedlineEvent, ReedlineRawEvent},
     PromptEditMode, PromptViMode,
 };
@@ -46,6 +49,7 @@ pub struct Vi {
     previous: Option<ReedlineEvent>,
     // last f, F, t, T motion for ; and ,
     last_char_search: Option<ViCharSearch>,
+    altgr_insertion: bool,
 }
 
 impl Default for Vi {
@@ -57,6 +61,7 @@ impl Default for Vi {
             mode: ViMode::Insert,
             previous: None,
             last_char_search: None,
+            altgr_insertion: true,
         }
     }
 }
@@ -84,6 +89,7 @@ impl EditMode for Vi {
                     ReedlineEvent::Multiple(vec![ReedlineEvent::Esc, ReedlineEvent::Repaint])
                 }
                 (ViMode::Normal | ViMode::Visual, modifier, KeyCode::Char(c)) => {
+                    let typed = c;
                     let c = c.to_ascii_lowercase();
 
                     if let Some(event) = self
@@ -91,11 +97,14 @@ impl EditMode for Vi {
                         .find_binding(modifiers, KeyCode::Char(c))
                     {
                         event
-                    } else if modifier == KeyModifiers::NONE || modifier == KeyModifiers::SHIFT {
-                        self.cache.push(if modifier == KeyModifiers::SHIFT {
-                            c.to_ascii_uppercase()
-                        } else {
-                            c
+                    } else if modifier == KeyModifiers::NONE
+                        || modifier == KeyModifiers::SHIFT
+                        || (self.altgr_insertion && is_altgr(modifier) && !typed.is_control())
+                    {
+                        self.cache.push(match modifier {
+                            KeyModifiers::NONE => c,
+                            KeyModifiers::SHIFT => c.to_ascii_uppercase(),
+                            _ => typed,
                         });
 
                         let res = parse(&mut self.cache.iter().peekable());
@@ -126,31 +135,17 @@ impl EditMode for Vi {
                     // Mixed modifiers are used by non american keyboards that have extra
                     // keys like 'alt gr'. Keep this in mind if in the future there are
                     // cases where an event is not being captured
-                    let c = match modifier {
+                    let binding_char = match modifier {
                         KeyModifiers::NONE => c,
                         _ => c.to_ascii_lowercase(),
                     };
 
                     self.insert_keybindings
-                        .find_binding(modifier, KeyCode::Char(c))
+                        .find_binding(modifier, KeyCode::Char(binding_char))
                         .unwrap_or_else(|| {
-                            if modifier == KeyModifiers::NONE
-                                || modifier == KeyModifiers::SHIFT
-                                || modifier == KeyModifiers::CONTROL | KeyModifiers::ALT
-                                || modifier
-                                    == KeyModifiers::CONTROL
-                                        | KeyModifiers::ALT
-                                        | KeyModifiers::SHIFT
-                            {
-                                ReedlineEvent::Edit(vec![EditCommand::InsertChar(
-                                    if modifier == KeyModifiers::SHIFT {
-                                        c.to_ascii_uppercase()
-                                    } else {
-                                        c
-                                    },
-                                )])
-                            } else {
-                                ReedlineEvent::None
+                            match insertable_char(modifier, c, self.altgr_insertion) {
+                                Some(c) => ReedlineEvent::Edit(vec![EditCommand::InsertChar(c)]),
+                                None => ReedlineEvent::None,
                             }
                         })
                 }
@@ -194,6 +189,20 @@ impl EditMode for Vi {
                 row,
                 button: button.into(),
             },
+            Event::Mouse(MouseEvent {
+                kind: MouseEventKind::Drag(crossterm::event::MouseButton::Left),
+                column,
+                row,
+                modifiers: KeyModifiers::NONE,
+            }) => ReedlineEvent::MouseDrag { column, row },
+            Event::Mouse(MouseEvent {
+                kind: MouseEventKind::ScrollUp,
+                ..
+            }) => ReedlineEvent::MouseScrollUp,
+            Event::Mouse(MouseEvent {
+                kind: MouseEventKind::ScrollDown,
+                ..
+            }) => ReedlineEvent::MouseScrollDown,
             Event::Mouse(_) => ReedlineEvent::None,
             Event::Resize(width, height) => ReedlineEvent::Resize(width, height),
             Event::FocusGained => ReedlineEvent::None,
@@ -223,6 +232,21 @@ impl EditMode for Vi {
             _ => EventStatus::Inapplicable,
         }
     }
+
+    fn keybindings(&self) -> Vec<(&'static str, &Keybindings)> {
+        vec![
+            ("vi_normal", &self.normal_keybindings),
+            ("vi_insert", &self.insert_keybindings),
+        ]
+    }
+
+    fn keybindings_mut(&mut self) -> Vec<&mut Keybindings> {
+        vec![&mut self.normal_keybindings, &mut self.insert_keybindings]
+    }
+
+    fn set_altgr_insertion(&mut self, enabled: bool) {
+        self.altgr_insertion = enabled;
+    }
 }
 
 #[cfg(test)]
@@ -245,6 +269,71 @@ mod test {
         assert!(matches!(vi.mode, ViMode::Normal));
     }
 
+    fn altgr_event(c: char) -> ReedlineRawEvent {
+        ReedlineRawEvent::try_from(Event::Key(KeyEvent::new(
+            KeyCode::Char(c),
+            KeyModifiers::CONTROL | KeyModifiers::ALT,
+        )))
+        .unwrap()
+    }
+
+    #[test]
+    fn altgr_character_is_inserted_in_insert_mode() {
+        let mut vi = Vi::default();
+
+        assert_eq!(
+            vi.parse_event(altgr_event('@')),
+            ReedlineEvent::Edit(vec![EditCommand::InsertChar('@')])
+        );
+    }
+
+    #[test]
+    fn altgr_character_completes_normal_mode_motion() {
+        let mut vi = Vi {
+            mode: ViMode::Normal,
+            ..Default::default()
+        };
+        let f = ReedlineRawEvent::try_from(Event::Key(KeyEvent::new(
+            KeyCode::Char('f'),
+            KeyModifiers::NONE,
+        )))
+        .unwrap();
+
+        assert_eq!(vi.parse_event(f), ReedlineEvent::None);
+        assert_eq!(
+            vi.parse_event(altgr_event('{')),
+            ReedlineEvent::Multiple(vec![ReedlineEvent::Edit(vec![
+                EditCommand::MoveRightUntil {
+                    c: '{',
+                    select: false
+                }
+            ])])
+        );
+    }
+
+    #[test]
+    fn altgr_character_is_dropped_when_disabled() {
+        let mut vi = Vi::default();
+        vi.set_altgr_insertion(false);
+
+        assert_eq!(vi.parse_event(altgr_event('@')), ReedlineEvent::None);
+    }
+
+    #[test]
+    fn keybindings_expose_normal_and_insert_tables() {
+        let vi = Vi::default();
+
+        let tables = vi.keybindings();
+
+        let names: Vec<_> = tables.iter().map(|(name, _)| *name).collect();
+        assert_eq!(names, vec!["vi_normal", "vi_insert"]);
+        assert!(tables[1]
+            .1
+            .find(&ReedlineEvent::Edit(vec![EditCommand::Backspace]))
+            .iter()
+            .any(|combo| combo.key_code == KeyCode::Backspace));
+    }
+
     #[test]
     fn keybinding_without_modifier_test() {
         let mut keybindings = default_vi_normal_keybindings();
@@ -3,13 +3,13 @@ use std::path::PathBuf;
 use itertools::Itertools;
 use nu_ansi_term::{Color, Style};
 
-#[cfg(feature = "lsp_diagnostics")]
-use crate::lsp::LspDiagnosticsProvider;
-use crate::{enums::ReedlineRawEvent, CursorConfig};
 #[cfg(feature = "bashisms")]
+use crate::menu_functions::{parse_selection_char, ParseAction};
+use crate::{enums::ReedlineRawEvent, CursorConfig};
+#[cfg(feature = "lsp_diagnostics")]
 use crate::{
-    history::SearchFilter,
-    menu_functions::{parse_selection_char, ParseAction},
+    lsp::{merge_lsp_keybindings, LspConfig, LspDiagnosticsProvider, LSP_COMPLETION_MENU},
+    ColumnarMenu, MenuBuilder,
 };
 #[cfg(feature = "external_printer")]
 use {
@@ -19,23 +19,27 @@ use {
 };
 use {
     crate::{
-        completion::{Completer, DefaultCompleter},
+        completion::{Completer, DefaultCompleter, SuggestionPostProcessor},
         core_editor::Editor,
-        edit_mode::{EditMode, Emacs},
+        edit_mode::{EditMode, Emacs, Keybindings, KeybindingsMode, ReedlineKeybindings},
         enums::{EventStatus, ReedlineEvent},
-        highlighter::SimpleMatchHighlighter,
-        hinter::Hinter,
+        highlighter::{highlight_matching_bracket, SimpleMatchHighlighter},
+        hinter::{HintTokenBoundary, Hinter},
         history::{
-            FileBackedHistory, History, HistoryCursor, HistoryItem, HistoryItemId,
-            HistoryNavigationQuery, HistorySessionId, SearchDirection, SearchQuery,
+            expand_history, CommandLineSearch, FileBackedHistory, History, HistoryCursor,
+            HistoryDeduplication, HistoryEvent, HistoryItem, HistoryItemId, HistoryMatches,
+            HistoryNavigationQuery, HistoryNavigationScope, HistorySearchMode, HistorySessionId,
+            SearchDirection, SearchFilter, SearchQuery,
         },
-        painting::{Painter, PainterSuspendedState, PromptLines, RenderSnapshot},
+        painting::{Painter, PainterSuspendedState, PromptLines, RenderSnapshot, StyledText},
         prompt::{PromptEditMode, PromptHistorySearchStatus},
         result::{ReedlineError, ReedlineErrorVariants},
         terminal_extensions::{
             bracketed_paste::BracketedPasteGuard,
             kitty::KittyProtocolGuard,
+            mouse_capture::MouseCaptureGuard,
             semantic_prompt::{Osc133ClickEventsMarkers, SemanticPromptMarkers},
+            suspend::TerminalModes,
         },
         utils::text_manipulation,
         EditCommand, ExampleHighlighter, Highlighter, LineBuffer, Menu, MenuEvent, MouseButton,
@@ -49,7 +53,12 @@ use {
         terminal, QueueableCommand,
     },
     std::{
-        fs::File, io, io::Result, io::Write, process::Command, time::Duration, time::SystemTime,
+        fs::File,
+        io,
+        io::Result,
+        io::Write,
+        process::Command,
+        time::{Duration, Instant, SystemTime},
     },
 };
 
@@ -69,6 +78,15 @@ const EVENTS_THRESHOLD: usize = 10;
 /// idle callback).
 const DEFAULT_POLL_INTERVAL: Duration = Duration::from_millis(100);
 
+/// Default pause in typing before a completion trigger opens the menu
+const DEFAULT_COMPLETION_TRIGGER_DELAY: Duration = Duration::from_millis(150);
+
+/// Maximum time between two clicks on the same spot to count as a double-click.
+const DOUBLE_CLICK_INTERVAL: Duration = Duration::from_millis(500);
+
+/// Predicate keeping submitted lines out of the history
+type HistoryExclusion = Box<dyn Fn(&str) -> bool + Send>;
+
 /// Determines if inputs should be used to extend the regular line buffer,
 /// traverse the history in the standard prompt or edit the search string in the
 /// reverse search
@@ -105,6 +123,21 @@ impl MouseClickMode {
     }
 }
 
+/// What opening a completion menu does first, see
+/// [`Reedline::with_completion_behavior`]
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum CompletionBehavior {
+    /// Open the menu right away.
+    #[default]
+    MenuImmediately,
+    /// Insert the start all suggestions share, like bash, and open the menu
+    /// only once there is nothing more to insert, e.g. on the next press.
+    /// A single suggestion is completed outright.
+    InsertCommonPrefixThenMenu,
+    /// Complete a single suggestion outright, otherwise open the menu.
+    InsertSingleOtherwiseMenu,
+}
+
 /// Line editor engine
 ///
 /// ## Example usage
@@ -134,8 +167,39 @@ pub struct Reedline {
     // none if history doesn't support this
     history_last_run_id: Option<HistoryItemId>,
     history_exclusion_prefix: Option<String>,
+    history_exclusion: Option<HistoryExclusion>,
     history_excluded_item: Option<HistoryItem>,
     history_cursor_on_excluded: bool,
+    // the typed line while navigating by the text before the cursor
+    history_prefix_original: Option<LineBuffer>,
+    history_search_mode: HistorySearchMode,
+    history_navigation_scope: HistoryNavigationScope,
+    // the scope of the running reverse search, switched by `CycleHistoryScope`
+    history_search_scope: HistoryNavigationScope,
+    // where the reverse search is among its matches, and the last match it
+    // found, still shown while the search fails
+    history_search_position: Option<(usize, usize)>,
+    history_search_last_match: String,
+    // the matches of the reverse search with the query and scope they were
+    // found for, so stepping through them doesn't read them all again
+    history_search_matches: Option<(
+        HistoryNavigationQuery,
+        HistoryNavigationScope,
+        HistoryMatches,
+    )>,
+    // why the matches of the reverse search couldn't be counted
+    history_search_error: Option<String>,
+    // the history menu entry waiting for a second delete to confirm it
+    history_menu_deletion: Option<String>,
+    // the history menu entry being fixed, replaced when the line is submitted
+    history_edited_entry: Option<HistoryItemId>,
+    // none to use the backend's own duplicate handling
+    history_deduplication: Option<HistoryDeduplication>,
+    history_expansion: bool,
+    // why the last submitted line couldn't be expanded, shown until the next edit
+    history_expansion_error: Option<String>,
+    // why the validator didn't let Enter submit the line, shown until the next edit
+    validation_error: Option<String>,
     input_mode: InputMode,
 
     // State of the painter after a `ReedlineEvent::ExecuteHostCommand` was requested, used after
@@ -153,11 +217,25 @@ pub struct Reedline {
 
     // Edit Mode: Vi, Emacs
     edit_mode: Box<dyn EditMode>,
+    altgr_insertion: bool,
 
     // Provides the tab completions
     completer: Box<dyn Completer>,
     quick_completions: bool,
     partial_completions: bool,
+    completion_behavior: CompletionBehavior,
+    suggestion_post_processor: SuggestionPostProcessor,
+
+    // Text before the cursor that opens the completion menu once typed
+    completion_triggers: Vec<String>,
+    // How long typing has to pause before a trigger opens the menu
+    completion_trigger_delay: Duration,
+    // Trigger typed last and when, opening the menu once typing pauses
+    pending_completion_trigger: Option<(String, Instant)>,
+    // Trigger that opened the menu last
+    fired_completion_trigger: Option<String>,
+    // Trigger whose menu was closed with Esc, not opening it again
+    dismissed_completion_trigger: Option<String>,
 
     // Highlight the edit buffer
     highlighter: Box<dyn Highlighter>,
@@ -165,16 +243,41 @@ pub struct Reedline {
     // Style used for visual selection
     visual_selection_style: Style,
 
+    // Style of the search text inside the result of the history search, and
+    // of the match the cursor lands on, the match style in bold by default
+    history_search_match_style: Style,
+    history_search_current_match_style: Option<Style>,
+
+    // Highlight the bracket at the cursor and its partner
+    bracket_matching: bool,
+    matching_bracket_style: Style,
+    unmatched_bracket_style: Style,
+
     // Showcase hints based on various strategies (history, language-completion, spellcheck, etc)
     hinter: Option<Box<dyn Hinter>>,
     hide_hints: bool,
 
+    // Where accepting a hint token by token stops
+    hint_token_boundary: HintTokenBoundary,
+
+    // Style of the part of the hint the next accepted token inserts
+    hint_next_token_style: Option<Style>,
+
     // Use ansi coloring or not
     use_ansi_coloring: bool,
 
     // Whether to enable mouse click-to-cursor functionality
     mouse_click_mode: MouseClickMode,
 
+    // Manage optional terminal mouse capture (clicks, drags and wheel)
+    mouse_capture: MouseCaptureGuard,
+
+    // Time and buffer offset of the last left click, used to detect double-clicks
+    last_mouse_click: Option<(Instant, usize)>,
+
+    // Number of menu entries or history items to move per mouse wheel notch
+    mouse_scroll_lines: usize,
+
     // Current working directory as defined by the application. If set, it will
     // override the actual working directory of the process.
     cwd: Option<String>,
@@ -211,6 +314,13 @@ pub struct Reedline {
     idle_callback: Option<Box<dyn FnMut() + Send>>,
     #[cfg(feature = "lsp_diagnostics")]
     lsp_diagnostics: Option<LspDiagnosticsProvider>,
+    // Whether `with_lsp` added its keybindings, added to later edit modes too
+    #[cfg(feature = "lsp_diagnostics")]
+    lsp_keybindings: bool,
+    // Buffer offsets of the color swatches drawn by the last paint, which
+    // clicks are mapped around
+    #[cfg(feature = "lsp_diagnostics")]
+    painted_swatches: Vec<usize>,
 }
 
 struct BufferEditor {
@@ -260,24 +370,57 @@ impl Reedline {
             history_session_id: hist_session_id,
             history_last_run_id: None,
             history_exclusion_prefix: None,
+            history_exclusion: None,
             history_excluded_item: None,
             history_cursor_on_excluded: false,
+            history_prefix_original: None,
+            history_search_mode: HistorySearchMode::default(),
+            history_navigation_scope: HistoryNavigationScope::default(),
+            history_search_scope: HistoryNavigationScope::default(),
+            history_search_position: None,
+            history_search_last_match: String::new(),
+            history_search_matches: None,
+            history_search_error: None,
+            history_menu_deletion: None,
+            history_edited_entry: None,
+            history_deduplication: None,
+            history_expansion: false,
+            history_expansion_error: None,
+            validation_error: None,
             input_mode: InputMode::Regular,
             suspended_state: None,
             last_render_snapshot: None,
             painter,
             transient_prompt: None,
             edit_mode,
+            altgr_insertion: true,
             completer,
             quick_completions: false,
             partial_completions: false,
+            completion_behavior: CompletionBehavior::default(),
+            suggestion_post_processor: SuggestionPostProcessor::default(),
+            completion_triggers: Vec::new(),
+            completion_trigger_delay: DEFAULT_COMPLETION_TRIGGER_DELAY,
+            pending_completion_trigger: None,
+            fired_completion_trigger: None,
+            dismissed_completion_trigger: None,
             highlighter: buffer_highlighter,
             visual_selection_style,
+            history_search_match_style: Style::new().fg(Color::Green),
+            history_search_current_match_style: None,
+            bracket_matching: false,
+            matching_bracket_style: Style::new().bold(),
+            unmatched_bracket_style: Style::new().fg(Color::Red).bold(),
             hinter,
             hide_hints: false,
+            hint_token_boundary: HintTokenBoundary::default(),
+            hint_next_token_style: None,
             validator,
             use_ansi_coloring: true,
             mouse_click_mode: MouseClickMode::default(),
+            mouse_capture: MouseCaptureGuard::default(),
+            last_mouse_click: None,
+            mouse_scroll_lines: 1,
             cwd: None,
             menus: Vec::new(),
             buffer_editor: None,
@@ -292,6 +435,10 @@ impl Reedline {
             idle_callback: None,
             #[cfg(feature = "lsp_diagnostics")]
             lsp_diagnostics: None,
+            #[cfg(feature = "lsp_diagnostics")]
+            lsp_keybindings: false,
+            #[cfg(feature = "lsp_diagnostics")]
+            painted_swatches: Vec::new(),
         }
     }
 
@@ -367,6 +514,29 @@ impl Reedline {
         self
     }
 
+    /// A builder to choose where accepting a hint token by token stops,
+    /// with [`ReedlineEvent::HistoryHintWordComplete`] and
+    /// [`ReedlineEvent::HistoryHintTokenComplete`]
+    ///
+    /// The default, [`HintTokenBoundary::Words`], leaves the splitting to the
+    /// [`Hinter`].
+    #[must_use]
+    pub fn with_hint_token_boundary(mut self, boundary: HintTokenBoundary) -> Self {
+        self.hint_token_boundary = boundary;
+        self
+    }
+
+    /// A builder to show the part of the hint that
+    /// [`ReedlineEvent::HistoryHintWordComplete`] inserts in `style`, over
+    /// the style of the hint
+    ///
+    /// Only applies to hinters returning a [`Hinter::styled_hint`].
+    #[must_use]
+    pub fn with_hint_next_token_style(mut self, style: Style) -> Self {
+        self.hint_next_token_style = Some(style);
+        self
+    }
+
     /// Remove current [`Hinter`]
     #[must_use]
     pub fn disable_hints(mut self) -> Self {
@@ -413,6 +583,61 @@ impl Reedline {
         self
     }
 
+    /// A builder setting what opening a completion menu does first
+    ///
+    /// Other than [`CompletionBehavior::MenuImmediately`], this takes over from
+    /// quick and partial completions when the menu opens. Menus that can't
+    /// quick complete, such as the history menu, always open right away.
+    #[must_use]
+    pub fn with_completion_behavior(mut self, behavior: CompletionBehavior) -> Self {
+        self.completion_behavior = behavior;
+        self
+    }
+
+    /// A builder setting how the suggestions of completers are sorted and
+    /// deduplicated before any menu shows them
+    #[must_use]
+    pub fn with_suggestion_post_processor(
+        mut self,
+        post_processor: SuggestionPostProcessor,
+    ) -> Self {
+        self.suggestion_post_processor = post_processor;
+        self
+    }
+
+    /// A builder setting text that opens the completion menu once typed, such
+    /// as `/`, `.` or `--`
+    ///
+    /// When the text before the cursor ends in a trigger after typing, the
+    /// first menu that isn't a history menu is opened as by its Tab binding.
+    /// Pasted text never opens it, and neither does a trigger while a menu is
+    /// already open. After closing the menu with Esc, only a different
+    /// trigger opens it again.
+    ///
+    /// # Example
+    /// ```rust
+    /// use reedline::Reedline;
+    ///
+    /// let line_editor = Reedline::create()
+    ///     .with_completion_triggers(vec!["/".into(), ".".into(), "--".into()]);
+    /// ```
+    #[must_use]
+    pub fn with_completion_triggers(mut self, triggers: Vec<String>) -> Self {
+        self.completion_triggers = triggers;
+        self
+    }
+
+    /// A builder setting how long typing has to pause before a completion
+    /// trigger opens the menu, 150ms by default
+    ///
+    /// Waiting keeps the menu from flickering in and out during fast typing.
+    /// See [`Reedline::with_completion_triggers`].
+    #[must_use]
+    pub fn with_completion_trigger_delay(mut self, delay: Duration) -> Self {
+        self.completion_trigger_delay = delay;
+        self
+    }
+
     /// A builder which enables or disables the use of ansi coloring in the prompt
     /// and in the command line syntax highlighting.
     #[must_use]
@@ -438,6 +663,33 @@ impl Reedline {
         self
     }
 
+    /// Toggle whether reedline captures the mouse while reading a line.
+    ///
+    /// With mouse support enabled, clicking in the buffer moves the cursor,
+    /// dragging selects text and double-clicking selects the word under the pointer.
+    /// Scrolling the wheel moves through an active menu or, without a menu,
+    /// navigates history like the arrow keys.
+    ///
+    /// Capturing the mouse disables the terminal's native text selection while
+    /// [`Reedline::read_line`] is active, so this is disabled by default.
+    #[must_use]
+    pub fn with_mouse_support(mut self, enable: bool) -> Self {
+        self.mouse_capture.set(enable);
+        if enable && !self.mouse_click_mode.is_enabled() {
+            self.mouse_click_mode = MouseClickMode::Enabled;
+        }
+        self
+    }
+
+    /// Set how many menu entries or history items a single mouse wheel notch moves.
+    ///
+    /// Only has an effect with [`Reedline::with_mouse_support`] enabled. Defaults to 1.
+    #[must_use]
+    pub fn with_mouse_scroll_lines(mut self, lines: usize) -> Self {
+        self.mouse_scroll_lines = lines.max(1);
+        self
+    }
+
     /// Update current working directory.
     #[must_use]
     pub fn with_cwd(mut self, cwd: Option<String>) -> Self {
@@ -445,6 +697,13 @@ impl Reedline {
         self
     }
 
+    /// Update the current working directory, e.g. before each [`Reedline::read_line`]
+    ///
+    /// Without it the working directory of the process is used.
+    pub fn set_cwd(&mut self, cwd: Option<String>) {
+        self.cwd = cwd;
+    }
+
     /// A builder that configures the highlighter for your instance of the Reedline engine
     /// # Example
     /// ```rust
@@ -474,6 +733,51 @@ impl Reedline {
         self
     }
 
+    /// A builder that turns highlighting the bracket at the cursor and its
+    /// partner on or off (the default)
+    ///
+    /// The bracket on the cursor, or else the one right before it, is
+    /// matched, skipping brackets in quotes. The styles are layered over
+    /// those of the [`Highlighter`].
+    #[must_use]
+    pub fn with_bracket_matching(mut self, bracket_matching: bool) -> Self {
+        self.bracket_matching = bracket_matching;
+        self
+    }
+
+    /// A builder that configures the style of the bracket at the cursor and
+    /// its partner, bold by default
+    #[must_use]
+    pub fn with_matching_bracket_style(mut self, style: Style) -> Self {
+        self.matching_bracket_style = style;
+        self
+    }
+
+    /// A builder that configures the style of a bracket at the cursor without
+    /// a partner, bold red by default
+    #[must_use]
+    pub fn with_unmatched_bracket_style(mut self, style: Style) -> Self {
+        self.unmatched_bracket_style = style;
+        self
+    }
+
+    /// A builder that configures the style of the searched text inside the
+    /// result of the history search, green by default
+    #[must_use]
+    pub fn with_history_search_match_style(mut self, style: Style) -> Self {
+        self.history_search_match_style = style;
+        self
+    }
+
+    /// A builder that configures the style layered over the last match of the
+    /// searched text, where the cursor lands when the result is taken, the
+    /// match style in bold by default
+    #[must_use]
+    pub fn with_history_search_current_match_style(mut self, style: Style) -> Self {
+        self.history_search_current_match_style = Some(style);
+        self
+    }
+
     /// A builder which configures the history for your instance of the Reedline engine
     /// # Example
     /// ```rust,no_run
@@ -515,6 +819,92 @@ impl Reedline {
         self
     }
 
+    /// A builder which keeps lines for which `exclude` returns `true` out of the history
+    ///
+    /// Applies in addition to [`Reedline::with_history_exclusion_prefix`].
+    /// Excluded lines are never passed to the [`History`] backend, but the
+    /// last one can still be recalled with `Up` until the next line is submitted.
+    ///
+    /// # Example
+    /// ```rust
+    /// // Keep anything mentioning a password and single character commands out of the history
+    /// use reedline::Reedline;
+    ///
+    /// let mut line_editor = Reedline::create().with_history_exclusion(Box::new(|line| {
+    ///     line.contains("password") || line.trim().chars().count() == 1
+    /// }));
+    /// ```
+    #[must_use]
+    pub fn with_history_exclusion(mut self, exclude: Box<dyn Fn(&str) -> bool + Send>) -> Self {
+        self.history_exclusion = Some(exclude);
+        self
+    }
+
+    /// A builder that sets how the interactive reverse history search (`Ctrl+R`)
+    /// matches the typed text
+    ///
+    /// Defaults to [`HistorySearchMode::Substring`]. With
+    /// [`HistorySearchMode::Fuzzy`] the best match is shown first and the
+    /// matched characters are highlighted.
+    #[must_use]
+    pub fn with_history_search_mode(mut self, mode: HistorySearchMode) -> Self {
+        self.history_search_mode = mode;
+        self
+    }
+
+    /// A builder that restricts the history navigation to the commands in `scope`
+    ///
+    /// Applies to the up/down navigation and the prefix search, and is the
+    /// scope the reverse history search (`Ctrl+R`) starts in, which
+    /// [`ReedlineEvent::CycleHistoryScope`] switches. Defaults to
+    /// [`HistoryNavigationScope::Global`]. A [`History`] that doesn't record
+    /// sessions or working directories falls back to it. Restrict the hints
+    /// with [`DefaultHinter::with_scope`](crate::DefaultHinter::with_scope).
+    #[must_use]
+    pub fn with_history_navigation_scope(mut self, scope: HistoryNavigationScope) -> Self {
+        self.history_navigation_scope = scope;
+        self
+    }
+
+    /// A builder that sets how submitted lines already in the history are saved
+    ///
+    /// Without it the [`History`] backend decides: [`FileBackedHistory`]
+    /// ignores consecutive duplicates, `SqliteBackedHistory` keeps all.
+    ///
+    /// # Example
+    /// ```rust
+    /// // Keep only the most recent copy of every command line
+    /// use reedline::{HistoryDeduplication, Reedline};
+    ///
+    /// let mut line_editor =
+    ///     Reedline::create().with_history_deduplication(HistoryDeduplication::EraseOlder);
+    /// ```
+    #[must_use]
+    pub fn with_history_deduplication(mut self, deduplication: HistoryDeduplication) -> Self {
+        self.history_deduplication = Some(deduplication);
+        self
+    }
+
+    /// A builder that enables csh-style history expansion of submitted lines
+    ///
+    /// `!!` stands for the last command, `!n` for the `n`-th command of the
+    /// history, `!-n` for the `n`-th most recent one and `!prefix` for the most
+    /// recent one starting with `prefix`. `!$` is the last word of the last
+    /// command and `!*` all but its first word. A line starting with
+    /// `^old^new` runs the last command with the first `old` replaced by `new`.
+    ///
+    /// Nothing is expanded in single quotes or after a backslash. The expanded
+    /// line is painted before it is returned and saved in the history. If a
+    /// reference can't be found, the line isn't submitted and an error is shown
+    /// below it until the next edit.
+    ///
+    /// Takes precedence over the expansion of the `bashisms` feature.
+    #[must_use]
+    pub fn with_history_expansion(mut self, enabled: bool) -> Self {
+        self.history_expansion = enabled;
+        self
+    }
+
     /// A builder that configures the validator for your instance of the Reedline engine
     /// # Example
     /// ```rust
@@ -595,10 +985,58 @@ impl Reedline {
     /// A builder which configures the edit mode for your instance of the Reedline engine
     #[must_use]
     pub fn with_edit_mode(mut self, edit_mode: Box<dyn EditMode>) -> Self {
-        self.edit_mode = edit_mode;
+        self.set_edit_mode(edit_mode);
+        self
+    }
+
+    /// A builder which configures the edit mode selected by `mode` from a set
+    /// of keybinding tables
+    ///
+    /// [`KeybindingsMode::Emacs`] uses the Emacs table and
+    /// [`KeybindingsMode::Vi`] the Vi insert and normal tables.
+    ///
+    /// # Example
+    /// ```rust
+    /// use reedline::{KeyCode, KeyModifiers, KeybindingsMode, Reedline, ReedlineEvent, ReedlineKeybindings};
+    ///
+    /// let mut keybindings = ReedlineKeybindings::default();
+    /// keybindings.bind_all(KeyModifiers::NONE, KeyCode::F(5), ReedlineEvent::ClearScreen);
+    ///
+    /// let line_editor = Reedline::create().with_keybindings(keybindings, KeybindingsMode::Vi);
+    /// ```
+    #[must_use]
+    pub fn with_keybindings(
+        mut self,
+        keybindings: ReedlineKeybindings,
+        mode: KeybindingsMode,
+    ) -> Self {
+        self.set_edit_mode(keybindings.into_edit_mode(mode));
+        self
+    }
+
+    /// A builder which sets whether characters typed with AltGr that have no
+    /// `Ctrl+Alt` binding are inserted (default: `true`)
+    ///
+    /// Terminals on Windows report AltGr as `Ctrl+Alt`, so with this disabled
+    /// characters like `@` or `{` on German and Nordic layouts are dropped, in
+    /// Vi normal mode also as motion arguments (e.g. `f{`). The setting applies
+    /// to every edit mode, including ones configured later.
+    #[must_use]
+    pub fn with_altgr_insertion(mut self, enabled: bool) -> Self {
+        self.altgr_insertion = enabled;
+        self.edit_mode.set_altgr_insertion(enabled);
         self
     }
 
+    fn set_edit_mode(&mut self, mut edit_mode: Box<dyn EditMode>) {
+        edit_mode.set_altgr_insertion(self.altgr_insertion);
+        #[cfg(feature = "lsp_diagnostics")]
+        if self.lsp_keybindings {
+            merge_lsp_keybindings(edit_mode.as_mut());
+        }
+        self.edit_mode = edit_mode;
+    }
+
     /// A builder that appends a menu to the engine
     #[must_use]
     pub fn with_menu(mut self, menu: ReedlineMenu) -> Self {
@@ -634,6 +1072,13 @@ impl Reedline {
         self
     }
 
+    /// The keybinding tables of the current edit mode, labelled by mode
+    ///
+    /// See [`EditMode::keybindings`] for the labels.
+    pub fn keybindings(&self) -> Vec<(&'static str, &Keybindings)> {
+        self.edit_mode.keybindings()
+    }
+
     /// Returns the corresponding expected prompt style for the given edit mode
     pub fn prompt_edit_mode(&self) -> PromptEditMode {
         self.edit_mode.edit_mode()
@@ -697,6 +1142,9 @@ impl Reedline {
     }
 
     /// Update the underlying [`History`] to/from disk
+    ///
+    /// Call it between commands to make the entries of other sessions sharing
+    /// the history available to Up-arrow navigation.
     pub fn sync_history(&mut self) -> std::io::Result<()> {
         // TODO: check for interactions in the non-submitting events
         self.history.sync()
@@ -735,15 +1183,77 @@ impl Reedline {
         terminal::enable_raw_mode()?;
         self.bracketed_paste.enter();
         self.kitty_protocol.enter();
+        self.mouse_capture.enter();
 
         let result = self.read_line_helper(prompt);
 
         self.bracketed_paste.exit();
         self.kitty_protocol.exit();
+        self.mouse_capture.exit();
         terminal::disable_raw_mode()?;
         result
     }
 
+    /// Leave the terminal modes set up by [`Reedline::read_line`], returning
+    /// which of them were active.
+    fn leave_terminal_modes(&mut self) -> io::Result<TerminalModes> {
+        let modes = TerminalModes {
+            raw_mode: terminal::is_raw_mode_enabled()?,
+            bracketed_paste: self.bracketed_paste.is_active(),
+            kitty_protocol: self.kitty_protocol.is_active(),
+            mouse_capture: self.mouse_capture.is_active(),
+        };
+
+        self.bracketed_paste.exit();
+        self.kitty_protocol.exit();
+        self.mouse_capture.exit();
+        if modes.raw_mode {
+            terminal::disable_raw_mode()?;
+        }
+        Ok(modes)
+    }
+
+    /// Re-enter the terminal modes returned by [`Reedline::leave_terminal_modes`].
+    fn restore_terminal_modes(&mut self, modes: TerminalModes) -> io::Result<()> {
+        if modes.raw_mode {
+            terminal::enable_raw_mode()?;
+        }
+        if modes.bracketed_paste {
+            self.bracketed_paste.enter();
+        }
+        if modes.kitty_protocol {
+            self.kitty_protocol.enter();
+        }
+        if modes.mouse_capture {
+            self.mouse_capture.enter();
+        }
+        Ok(())
+    }
+
+    /// Stop the process for job control and redraw everything once resumed.
+    #[cfg(unix)]
+    fn suspend_process(&mut self) -> io::Result<()> {
+        // Leave the cursor below the buffer so the shell's job message
+        // doesn't overwrite it.
+        self.painter.move_cursor_to_end()?;
+        let modes = self.leave_terminal_modes()?;
+        let stopped = crate::terminal_extensions::suspend::stop_process_group();
+        // Execution continues here after SIGCONT. Restore the terminal even if
+        // stopping failed so the editor stays usable.
+        self.restore_terminal_modes(modes)?;
+
+        // The screen may have changed arbitrarily while stopped, so start a
+        // fresh prompt at the current cursor position.
+        self.last_render_snapshot = None;
+        self.painter.initialize_prompt_position(None)?;
+        stopped
+    }
+
+    #[cfg(not(unix))]
+    fn suspend_process(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+
     /// Returns the current insertion point of the input buffer.
     pub fn current_insertion_point(&self) -> usize {
         self.editor.insertion_point()
@@ -783,6 +1293,8 @@ impl Reedline {
             // Last editor was suspended to run a ExecuteHostCommand event,
             // we are resuming operation now.
             self.suspended_state = None;
+        } else {
+            self.history_edited_entry = None;
         }
         self.hide_hints = false;
 
@@ -829,13 +1341,37 @@ impl Reedline {
 
             let mut events: Vec<Event> = vec![];
 
+            // The progress of a running LSP command fix is shown until the
+            // server answers, so keep polling to repaint then
+            #[cfg(feature = "lsp_diagnostics")]
+            let lsp_command_running = self
+                .lsp_diagnostics
+                .as_ref()
+                .map_or(false, |provider| provider.running_command().is_some());
+
+            // Requests keyed on the cursor position are sent once it rested,
+            // and their answers painted as they arrive, so keep polling until then
+            #[cfg(feature = "lsp_diagnostics")]
+            let lsp_cursor_requests_pending = self
+                .lsp_diagnostics
+                .as_ref()
+                .map_or(false, LspDiagnosticsProvider::cursor_requests_pending);
+
+            // Values of a completer running in the background are shown as
+            // they arrive, so keep polling until all did
+            let menu_completing = self
+                .menus
+                .iter()
+                .any(|menu| menu.is_active() && menu.is_completing());
+
             if !self.immediately_accept {
                 // Determine if we need to poll (non-blocking) or can block on input.
                 // We need polling if external_printer or idle_callback is configured,
-                // using the shared poll_interval for the timeout.
+                // or while a menu is completing, using the shared poll_interval
+                // for the timeout.
                 let needs_polling = {
                     #[allow(unused_mut)]
-                    let mut result = false;
+                    let mut result = menu_completing || self.pending_completion_trigger.is_some();
                     #[cfg(feature = "external_printer")]
                     if self.external_printer.is_some() {
                         result = true;
@@ -844,11 +1380,31 @@ impl Reedline {
                     if self.idle_callback.is_some() {
                         result = true;
                     }
+                    #[cfg(feature = "lsp_diagnostics")]
+                    if lsp_command_running || lsp_cursor_requests_pending {
+                        result = true;
+                    }
                     result
                 };
 
                 if needs_polling {
-                    if event::poll(self.poll_interval)? {
+                    // Wake up in time to open the menu of a typed trigger
+                    let timeout = self.pending_completion_trigger.as_ref().map_or(
+                        self.poll_interval,
+                        |(_, typed)| {
+                            (*typed + self.completion_trigger_delay)
+                                .saturating_duration_since(Instant::now())
+                                .min(self.poll_interval)
+                        },
+                    );
+                    // Wake up in time to send the requests of a resting cursor
+                    #[cfg(feature = "lsp_diagnostics")]
+                    let timeout = self
+                        .lsp_diagnostics
+                        .as_ref()
+                        .and_then(LspDiagnosticsProvider::cursor_requests_due_in)
+                        .map_or(timeout, |due_in| timeout.min(due_in));
+                    if event::poll(timeout)? {
                         events.push(crossterm::event::read()?);
                     }
                 } else {
@@ -873,6 +1429,30 @@ impl Reedline {
                 }
             }
 
+            #[cfg(feature = "lsp_diagnostics")]
+            if lsp_command_running
+                && self
+                    .lsp_diagnostics
+                    .as_mut()
+                    .map_or(false, LspDiagnosticsProvider::check_wake)
+            {
+                self.repaint(prompt)?;
+            }
+
+            #[cfg(feature = "lsp_diagnostics")]
+            if lsp_cursor_requests_pending
+                && events.is_empty()
+                && self
+                    .lsp_diagnostics
+                    .as_mut()
+                    .map_or(false, LspDiagnosticsProvider::poll_cursor_requests)
+            {
+                self.repaint(prompt)?;
+            }
+
+            let values_arrived =
+                menu_completing && self.active_menu().map_or(false, |menu| menu.poll_values());
+
             // Convert `Event` into `ReedlineEvent`. Also, fuse consecutive
             // `ReedlineEvent::EditCommand` into one. Also, if there're multiple
             // `ReedlineEvent::Resize`, only keep the last one.
@@ -905,7 +1485,7 @@ impl Reedline {
             }
 
             // Handle reedline events.
-            let mut need_repaint = false;
+            let mut need_repaint = values_arrived;
             for event in reedline_events {
                 match self.handle_event(prompt, event)? {
                     EventStatus::Exits(signal) => {
@@ -926,6 +1506,9 @@ impl Reedline {
                     }
                 }
             }
+            if self.open_triggered_menu(prompt)? {
+                need_repaint = true;
+            }
             if need_repaint {
                 self.repaint(prompt)?;
             }
@@ -933,8 +1516,20 @@ impl Reedline {
     }
 
     fn handle_event(&mut self, prompt: &dyn Prompt, event: ReedlineEvent) -> Result<EventStatus> {
+        if !matches!(event, ReedlineEvent::MenuDeleteEntry) {
+            self.history_menu_deletion = None;
+        }
+        // Anything but typing a trigger again keeps the menu of a trigger closed
+        let pending_trigger = self.pending_completion_trigger.take();
+        if matches!(event, ReedlineEvent::Esc) {
+            self.dismissed_completion_trigger = pending_trigger
+                .map(|(trigger, _)| trigger)
+                .or_else(|| self.fired_completion_trigger.take());
+        }
         if self.input_mode == InputMode::HistorySearch {
-            self.handle_history_search_event(event)
+            let status = self.handle_history_search_event(event)?;
+            self.update_history_search_match();
+            Ok(status)
         } else {
             self.handle_editor_event(prompt, event)
         }
@@ -974,6 +1569,10 @@ impl Reedline {
                 self.painter.clear_screen()?;
                 Ok(EventStatus::Handled)
             }
+            ReedlineEvent::Suspend => {
+                self.suspend_process()?;
+                Ok(EventStatus::Handled)
+            }
             ReedlineEvent::ClearScrollback => {
                 self.painter.clear_scrollback()?;
                 Ok(EventStatus::Handled)
@@ -995,6 +1594,10 @@ impl Reedline {
                 self.suspended_state = Some(self.painter.state_before_suspension());
                 Ok(EventStatus::Exits(Signal::Success(host_command)))
             }
+            ReedlineEvent::ReturnSignal { code, keep_buffer } => {
+                self.input_mode = InputMode::Regular;
+                Ok(self.return_signal(code, keep_buffer))
+            }
             ReedlineEvent::Edit(commands) => {
                 self.run_history_commands(&commands);
                 Ok(EventStatus::Handled)
@@ -1018,13 +1621,37 @@ impl Reedline {
                 // A handled Event causes a repaint
                 Ok(EventStatus::Handled)
             }
-            ReedlineEvent::PreviousHistory | ReedlineEvent::Up | ReedlineEvent::SearchHistory => {
+            ReedlineEvent::PreviousHistory
+            | ReedlineEvent::Up
+            | ReedlineEvent::SearchHistory
+            | ReedlineEvent::HistoryPrefixSearchBackward => {
                 self.history_cursor
                     .back(self.history.as_ref())
                     .expect("todo: error handling");
                 Ok(EventStatus::Handled)
             }
-            ReedlineEvent::NextHistory | ReedlineEvent::Down => {
+            ReedlineEvent::MouseScrollUp | ReedlineEvent::MouseScrollDown
+                if !self.mouse_capture.enabled() =>
+            {
+                Ok(EventStatus::Inapplicable)
+            }
+            ReedlineEvent::MouseScrollUp => {
+                let event = ReedlineEvent::PreviousHistory;
+                for _ in 0..self.mouse_scroll_lines {
+                    self.handle_history_search_event(event.clone())?;
+                }
+                Ok(EventStatus::Handled)
+            }
+            ReedlineEvent::MouseScrollDown => {
+                let event = ReedlineEvent::NextHistory;
+                for _ in 0..self.mouse_scroll_lines {
+                    self.handle_history_search_event(event.clone())?;
+                }
+                Ok(EventStatus::Handled)
+            }
+            ReedlineEvent::NextHistory
+            | ReedlineEvent::Down
+            | ReedlineEvent::HistoryPrefixSearchForward => {
                 self.history_cursor
                     .forward(self.history.as_ref())
                     .expect("todo: error handling");
@@ -1036,6 +1663,10 @@ impl Reedline {
                 }
                 Ok(EventStatus::Handled)
             }
+            ReedlineEvent::CycleHistoryScope => {
+                self.cycle_history_search_scope();
+                Ok(EventStatus::Handled)
+            }
             ReedlineEvent::Esc => {
                 self.input_mode = InputMode::Regular;
                 Ok(EventStatus::Handled)
@@ -1046,6 +1677,7 @@ impl Reedline {
             | ReedlineEvent::Multiple(_)
             | ReedlineEvent::None
             | ReedlineEvent::HistoryHintWordComplete
+            | ReedlineEvent::HistoryHintTokenComplete
             | ReedlineEvent::OpenEditor
             | ReedlineEvent::Menu(_)
             | ReedlineEvent::MenuNext
@@ -1056,9 +1688,18 @@ impl Reedline {
             | ReedlineEvent::MenuRight
             | ReedlineEvent::MenuPageNext
             | ReedlineEvent::MenuPagePrevious
+            | ReedlineEvent::MenuDescriptionUp
+            | ReedlineEvent::MenuDescriptionDown
+            | ReedlineEvent::MenuDeleteEntry
+            | ReedlineEvent::MenuEditEntry
+            | ReedlineEvent::MenuAcceptAndContinue
+            | ReedlineEvent::MouseDrag { .. }
             | ReedlineEvent::ViChangeMode(_) => Ok(EventStatus::Inapplicable),
             #[cfg(feature = "lsp_diagnostics")]
-            ReedlineEvent::OpenDiagnosticFixMenu => Ok(EventStatus::Inapplicable),
+            ReedlineEvent::OpenDiagnosticFixMenu
+            | ReedlineEvent::OpenSymbolMenu
+            | ReedlineEvent::ToggleDiagnostics
+            | ReedlineEvent::CopyDiagnostic => Ok(EventStatus::Inapplicable),
         }
     }
 
@@ -1073,11 +1714,42 @@ impl Reedline {
                     if let Some(menu) = self.menus.iter_mut().find(|menu| menu.name() == name) {
                         menu.menu_event(MenuEvent::Activate(self.quick_completions));
 
+                        if self.completion_behavior != CompletionBehavior::MenuImmediately
+                            && menu.can_quick_complete()
+                        {
+                            menu.update_values(
+                                &mut self.editor,
+                                self.completer.as_mut(),
+                                self.history.as_ref(),
+                                &self.suggestion_post_processor,
+                            );
+                            match menu.get_values().len() {
+                                0 => menu.menu_event(MenuEvent::Deactivate),
+                                1 => return self.handle_editor_event(prompt, ReedlineEvent::Enter),
+                                _ => {
+                                    if self.completion_behavior
+                                        == CompletionBehavior::InsertCommonPrefixThenMenu
+                                        && menu.can_partially_complete(
+                                            true,
+                                            &mut self.editor,
+                                            self.completer.as_mut(),
+                                            self.history.as_ref(),
+                                            &self.suggestion_post_processor,
+                                        )
+                                    {
+                                        menu.menu_event(MenuEvent::Deactivate);
+                                    }
+                                }
+                            }
+                            return Ok(EventStatus::Handled);
+                        }
+
                         if self.quick_completions && menu.can_quick_complete() {
                             menu.update_values(
                                 &mut self.editor,
                                 self.completer.as_mut(),
                                 self.history.as_ref(),
+                                &self.suggestion_post_processor,
                             );
 
                             if menu.get_values().len() == 1 {
@@ -1091,6 +1763,7 @@ impl Reedline {
                                 &mut self.editor,
                                 self.completer.as_mut(),
                                 self.history.as_ref(),
+                                &self.suggestion_post_processor,
                             )
                         {
                             return Ok(EventStatus::Handled);
@@ -1112,6 +1785,7 @@ impl Reedline {
                                 &mut self.editor,
                                 self.completer.as_mut(),
                                 self.history.as_ref(),
+                                &self.suggestion_post_processor,
                             );
                         }
                         menu.menu_event(MenuEvent::NextElement);
@@ -1170,23 +1844,79 @@ impl Reedline {
                         Ok(EventStatus::Handled)
                     })
             }
-            ReedlineEvent::HistoryHintComplete => {
-                if let Some(hinter) = self.hinter.as_mut() {
-                    let current_hint = hinter.complete_hint();
-                    if self.hints_active()
-                        && self.editor.is_cursor_at_buffer_end()
-                        && !current_hint.is_empty()
-                        && self.active_menu().is_none()
-                    {
-                        self.run_edit_commands(&[EditCommand::InsertString(current_hint)]);
-                        return Ok(EventStatus::Handled);
-                    }
-                }
-                Ok(EventStatus::Inapplicable)
+            ReedlineEvent::MenuDescriptionUp => {
+                self.active_menu()
+                    .map_or(Ok(EventStatus::Inapplicable), |menu| {
+                        menu.menu_event(MenuEvent::ScrollDescriptionUp);
+                        Ok(EventStatus::Handled)
+                    })
             }
-            ReedlineEvent::HistoryHintWordComplete => {
+            ReedlineEvent::MenuDescriptionDown => {
+                self.active_menu()
+                    .map_or(Ok(EventStatus::Inapplicable), |menu| {
+                        menu.menu_event(MenuEvent::ScrollDescriptionDown);
+                        Ok(EventStatus::Handled)
+                    })
+            }
+            ReedlineEvent::MenuDeleteEntry => Ok(self.delete_history_menu_entry()),
+            ReedlineEvent::MenuEditEntry => {
+                let Some(menu) = self
+                    .menus
+                    .iter_mut()
+                    .find(|menu| menu.is_active() && matches!(menu, ReedlineMenu::HistoryMenu(_)))
+                else {
+                    return Ok(EventStatus::Inapplicable);
+                };
+                let entry = menu
+                    .selected_value()
+                    .and_then(|suggestion| suggestion.history_id);
+                menu.replace_in_buffer(&mut self.editor);
+                menu.menu_event(MenuEvent::Deactivate);
+                self.history_edited_entry = entry;
+                Ok(EventStatus::Handled)
+            }
+            ReedlineEvent::MenuAcceptAndContinue => {
+                let Some(menu) = self.menus.iter_mut().find(|menu| menu.is_active()) else {
+                    return Ok(EventStatus::Inapplicable);
+                };
+                menu.replace_in_buffer(&mut self.editor);
+                menu.menu_event(MenuEvent::Deactivate);
+                if menu.can_continue() {
+                    menu.menu_event(MenuEvent::Activate(true));
+                    menu.update_values(
+                        &mut self.editor,
+                        self.completer.as_mut(),
+                        self.history.as_ref(),
+                        &self.suggestion_post_processor,
+                    );
+                    if menu.get_values().is_empty() {
+                        menu.menu_event(MenuEvent::Deactivate);
+                    }
+                }
+                Ok(EventStatus::Handled)
+            }
+            ReedlineEvent::HistoryHintComplete => {
                 if let Some(hinter) = self.hinter.as_mut() {
-                    let current_hint_part = hinter.next_hint_token();
+                    let current_hint = hinter.complete_hint();
+                    if self.hints_active()
+                        && self.editor.is_cursor_at_buffer_end()
+                        && !current_hint.is_empty()
+                        && self.active_menu().is_none()
+                    {
+                        self.run_edit_commands(&[EditCommand::InsertString(current_hint)]);
+                        return Ok(EventStatus::Handled);
+                    }
+                }
+                Ok(EventStatus::Inapplicable)
+            }
+            ReedlineEvent::HistoryHintWordComplete | ReedlineEvent::HistoryHintTokenComplete => {
+                let under_cursor = event == ReedlineEvent::HistoryHintTokenComplete;
+                if let Some(hinter) = self.hinter.as_ref() {
+                    let current_hint_part = self.hint_token_boundary.next_part(
+                        hinter.as_ref(),
+                        self.editor.get_buffer(),
+                        under_cursor,
+                    );
                     if self.hints_active()
                         && self.editor.is_cursor_at_buffer_end()
                         && !current_hint_part.is_empty()
@@ -1199,6 +1929,9 @@ impl Reedline {
                 Ok(EventStatus::Inapplicable)
             }
             ReedlineEvent::Esc => {
+                if self.active_menu().map_or(false, |menu| menu.clear_filter()) {
+                    return Ok(EventStatus::Handled);
+                }
                 self.deactivate_menus();
                 self.editor.clear_selection();
                 Ok(EventStatus::Handled)
@@ -1223,6 +1956,10 @@ impl Reedline {
                 self.painter.clear_screen()?;
                 Ok(EventStatus::Handled)
             }
+            ReedlineEvent::Suspend => {
+                self.suspend_process()?;
+                Ok(EventStatus::Handled)
+            }
             ReedlineEvent::ClearScrollback => {
                 self.deactivate_menus();
                 self.painter.clear_scrollback()?;
@@ -1253,6 +1990,11 @@ impl Reedline {
                     Some(ValidationResult::Incomplete) => {
                         self.run_edit_commands(&[EditCommand::InsertNewline]);
 
+                        Ok(EventStatus::Handled)
+                    }
+                    Some(ValidationResult::Invalid { message }) => {
+                        self.validation_error = Some(message);
+
                         Ok(EventStatus::Handled)
                     }
                 }
@@ -1280,6 +2022,11 @@ impl Reedline {
                     Some(ValidationResult::Incomplete) => {
                         self.run_edit_commands(&[EditCommand::InsertNewline]);
 
+                        Ok(EventStatus::Handled)
+                    }
+                    Some(ValidationResult::Invalid { message }) => {
+                        self.validation_error = Some(message);
+
                         Ok(EventStatus::Handled)
                     }
                 }
@@ -1289,8 +2036,19 @@ impl Reedline {
                 self.suspended_state = Some(self.painter.state_before_suspension());
                 Ok(EventStatus::Exits(Signal::Success(host_command)))
             }
+            ReedlineEvent::ReturnSignal { code, keep_buffer } => {
+                self.deactivate_menus();
+                Ok(self.return_signal(code, keep_buffer))
+            }
             ReedlineEvent::Edit(commands) => {
+                if self
+                    .active_menu()
+                    .map_or(false, |menu| menu.edit_filter(&commands))
+                {
+                    return Ok(EventStatus::Handled);
+                }
                 self.run_edit_commands(&commands);
+                self.note_completion_trigger(&commands);
                 if let Some(menu) = self.menus.iter_mut().find(|men| men.is_active()) {
                     if self.quick_completions && menu.can_quick_complete() {
                         match commands.first() {
@@ -1305,6 +2063,7 @@ impl Reedline {
                                     &mut self.editor,
                                     self.completer.as_mut(),
                                     self.history.as_ref(),
+                                    &self.suggestion_post_processor,
                                 );
                                 if let Some(&EditCommand::Complete) = commands.first() {
                                     if menu.get_values().len() == 1 {
@@ -1316,6 +2075,7 @@ impl Reedline {
                                             &mut self.editor,
                                             self.completer.as_mut(),
                                             self.history.as_ref(),
+                                            &self.suggestion_post_processor,
                                         )
                                     {
                                         return Ok(EventStatus::Handled);
@@ -1333,6 +2093,7 @@ impl Reedline {
                 Ok(EventStatus::Handled)
             }
             ReedlineEvent::OpenEditor => self.open_editor().map(|_| EventStatus::Handled),
+            ReedlineEvent::CycleHistoryScope => Ok(EventStatus::Inapplicable),
             ReedlineEvent::Resize(width, height) => {
                 self.last_render_snapshot = None;
                 self.painter.handle_resize(width, height);
@@ -1370,6 +2131,20 @@ impl Reedline {
                 self.enter_history_search();
                 Ok(EventStatus::Handled)
             }
+            ReedlineEvent::HistoryPrefixSearchBackward
+            | ReedlineEvent::HistoryPrefixSearchForward
+                if self.menus.iter().any(|menu| menu.is_active()) =>
+            {
+                Ok(EventStatus::Inapplicable)
+            }
+            ReedlineEvent::HistoryPrefixSearchBackward => {
+                self.history_prefix_search(SearchDirection::Backward);
+                Ok(EventStatus::Handled)
+            }
+            ReedlineEvent::HistoryPrefixSearchForward => {
+                self.history_prefix_search(SearchDirection::Forward);
+                Ok(EventStatus::Handled)
+            }
             ReedlineEvent::Multiple(events) => {
                 let mut latest_signal = EventStatus::Inapplicable;
                 for event in events {
@@ -1417,6 +2192,14 @@ impl Reedline {
                 }
                 Ok(EventStatus::Handled)
             }
+            ReedlineEvent::MouseDrag { column, row } => {
+                if self.mouse_capture.enabled() {
+                    self.handle_mouse_drag(column, row);
+                }
+                Ok(EventStatus::Handled)
+            }
+            ReedlineEvent::MouseScrollUp => Ok(self.handle_mouse_scroll(true)),
+            ReedlineEvent::MouseScrollDown => Ok(self.handle_mouse_scroll(false)),
 
             #[cfg(feature = "lsp_diagnostics")]
             ReedlineEvent::OpenDiagnosticFixMenu => {
@@ -1426,34 +2209,299 @@ impl Reedline {
                     Ok(EventStatus::Inapplicable)
                 }
             }
+            #[cfg(feature = "lsp_diagnostics")]
+            ReedlineEvent::OpenSymbolMenu => {
+                if self.open_symbol_menu() {
+                    Ok(EventStatus::Handled)
+                } else {
+                    Ok(EventStatus::Inapplicable)
+                }
+            }
+            #[cfg(feature = "lsp_diagnostics")]
+            ReedlineEvent::ToggleDiagnostics => {
+                if let Some(ref mut provider) = self.lsp_diagnostics {
+                    let enabled = !provider.is_enabled();
+                    provider.set_enabled(enabled);
+                    if !enabled {
+                        self.menus
+                            .retain(|m| m.name() != crate::lsp::DIAGNOSTIC_FIX_MENU);
+                    }
+                    Ok(EventStatus::Handled)
+                } else {
+                    Ok(EventStatus::Inapplicable)
+                }
+            }
+            #[cfg(feature = "lsp_diagnostics")]
+            ReedlineEvent::CopyDiagnostic => {
+                if let Some(text) = self.diagnostic_at_cursor().map(crate::lsp::clipboard_text) {
+                    #[cfg(feature = "system_clipboard")]
+                    let copied = self.editor.copy_to_system_clipboard(&text);
+                    #[cfg(not(feature = "system_clipboard"))]
+                    let copied = false;
+                    // OSC 52 is only the fallback, as not every terminal allows it
+                    if !copied {
+                        self.painter.copy_to_clipboard(&text)?;
+                    }
+                    Ok(EventStatus::Handled)
+                } else {
+                    Ok(EventStatus::Inapplicable)
+                }
+            }
             ReedlineEvent::None => Ok(EventStatus::Inapplicable),
         }
     }
 
+    /// Offset in the buffer painted at `column` and `row` by the last paint
+    fn buffer_offset_at(&self, column: u16, row: u16) -> Option<usize> {
+        let snapshot = self.last_render_snapshot.as_ref()?;
+        let offset = self
+            .painter
+            .screen_to_buffer_offset(snapshot, column, row)?;
+        #[cfg(feature = "lsp_diagnostics")]
+        let offset = crate::lsp::offset_without_swatches(&self.painted_swatches, offset);
+        Some(offset)
+    }
+
     fn handle_mouse_click(&mut self, column: u16, row: u16) -> Result<()> {
-        let snapshot = match &self.last_render_snapshot {
-            Some(snapshot) => snapshot,
-            None => return Ok(()),
-        };
-        if self.input_mode != InputMode::Regular || self.menus.iter().any(|m| m.is_active()) {
+        if self.input_mode != InputMode::Regular {
+            return Ok(());
+        }
+        if self.menus.iter().any(|m| m.is_active()) {
+            self.handle_menu_click(column, row);
             return Ok(());
         }
         let buffer = self.editor.get_buffer();
-        if let Some(offset) = self.painter.screen_to_buffer_offset(snapshot, column, row) {
+        if let Some(offset) = self.buffer_offset_at(column, row) {
             if buffer.is_char_boundary(offset) {
+                self.editor.clear_selection();
                 self.editor.edit_buffer(
                     |buf| buf.set_insertion_point(offset),
                     UndoBehavior::MoveCursor,
                 );
+
+                let now = Instant::now();
+                let is_double_click = matches!(
+                    self.last_mouse_click,
+                    Some((time, last))
+                        if last == offset && now.duration_since(time) <= DOUBLE_CLICK_INTERVAL
+                );
+                if is_double_click {
+                    self.select_word_at_cursor();
+                    self.last_mouse_click = None;
+                } else {
+                    self.last_mouse_click = Some((now, offset));
+                }
             }
         }
         Ok(())
     }
 
+    /// Let the active menu turn a click inside it into selecting or applying an entry.
+    fn handle_menu_click(&mut self, column: u16, row: u16) {
+        if !self.mouse_capture.enabled() {
+            return;
+        }
+        let Some(menu_start_row) = self
+            .last_render_snapshot
+            .as_ref()
+            .and_then(|snapshot| snapshot.menu_start_row)
+        else {
+            return;
+        };
+        let Some(menu_row) = row.checked_sub(menu_start_row) else {
+            return;
+        };
+        let Some(menu) = self.menus.iter_mut().find(|menu| menu.is_active()) else {
+            return;
+        };
+        match menu.handle_mouse(menu_row, column) {
+            Some(MenuEvent::Accept) => {
+                menu.replace_in_buffer(&mut self.editor);
+                menu.menu_event(MenuEvent::Deactivate);
+            }
+            Some(event) => menu.menu_event(event),
+            None => {}
+        }
+    }
+
+    /// Extend the selection from the last click position to the dragged-to position.
+    fn handle_mouse_drag(&mut self, column: u16, row: u16) {
+        if self.input_mode != InputMode::Regular || self.menus.iter().any(|m| m.is_active()) {
+            return;
+        }
+        if let Some(offset) = self.buffer_offset_at(column, row) {
+            if self.editor.get_buffer().is_char_boundary(offset) {
+                self.last_mouse_click = None;
+                self.editor.run_edit_command(&EditCommand::MoveToPosition {
+                    position: offset,
+                    select: true,
+                });
+            }
+        }
+    }
+
+    /// Move through the active menu, or through history when no menu is open.
+    fn handle_mouse_scroll(&mut self, up: bool) -> EventStatus {
+        if !self.mouse_capture.enabled() {
+            return EventStatus::Inapplicable;
+        }
+        let lines = self.mouse_scroll_lines;
+        if let Some(menu) = self.menus.iter_mut().find(|menu| menu.is_active()) {
+            let event = if up {
+                MenuEvent::PreviousElement
+            } else {
+                MenuEvent::NextElement
+            };
+            // Menus only keep the latest event until the next repaint, so apply
+            // all but the last notch step right away.
+            for _ in 1..lines {
+                menu.menu_event(event.clone());
+                menu.update_working_details(
+                    &mut self.editor,
+                    self.completer.as_mut(),
+                    self.history.as_ref(),
+                    &self.suggestion_post_processor,
+                    &self.painter,
+                );
+            }
+            menu.menu_event(event);
+            return EventStatus::Handled;
+        }
+        for _ in 0..lines {
+            if up {
+                self.previous_history();
+            } else {
+                self.next_history();
+            }
+        }
+        EventStatus::Handled
+    }
+
+    /// Select the word under the cursor, leaving the cursor at its end.
+    fn select_word_at_cursor(&mut self) {
+        if self.editor.line_buffer().on_whitespace() {
+            return;
+        }
+        let range = self.editor.line_buffer().current_word_range();
+        for command in [
+            EditCommand::MoveToPosition {
+                position: range.start,
+                select: false,
+            },
+            EditCommand::MoveToPosition {
+                position: range.end,
+                select: true,
+            },
+        ] {
+            self.editor.run_edit_command(&command);
+        }
+    }
+
+    /// Ask to confirm deleting the entry selected in the history menu, or
+    /// delete it if it was asked for already
+    fn delete_history_menu_entry(&mut self) -> EventStatus {
+        let Some(menu) = self
+            .menus
+            .iter_mut()
+            .find(|menu| menu.is_active() && matches!(menu, ReedlineMenu::HistoryMenu(_)))
+        else {
+            return EventStatus::Inapplicable;
+        };
+        let Some(command_line) = menu.selected_value().map(|suggestion| suggestion.value) else {
+            return EventStatus::Handled;
+        };
+        if self.history_menu_deletion.as_ref() != Some(&command_line) {
+            let first_line = command_line.lines().next().unwrap_or_default();
+            menu.menu_event(MenuEvent::Notice(format!(
+                "Delete \"{first_line}\" from the history? Press again to confirm"
+            )));
+            self.history_menu_deletion = Some(command_line);
+            return EventStatus::Handled;
+        }
+
+        self.history_menu_deletion = None;
+        match delete_from_history(self.history.as_mut(), &command_line) {
+            Ok(()) => menu.menu_event(MenuEvent::Refresh),
+            Err(ReedlineError(ReedlineErrorVariants::HistoryFeatureUnsupported { .. })) => menu
+                .menu_event(MenuEvent::Notice(
+                    "Deleting entries is not supported by this history".to_string(),
+                )),
+            Err(err) => menu.menu_event(MenuEvent::Notice(format!(
+                "Couldn't delete the entry: {err}"
+            ))),
+        }
+        EventStatus::Handled
+    }
+
     fn active_menu(&mut self) -> Option<&mut ReedlineMenu> {
         self.menus.iter_mut().find(|menu| menu.is_active())
     }
 
+    /// Exit `read_line` with [`Signal::Custom`], clearing the buffer unless it should be kept.
+    fn return_signal(&mut self, code: u32, keep_buffer: bool) -> EventStatus {
+        self.last_render_snapshot = None;
+        let partial_buffer = self.editor.get_buffer().to_string();
+        if !keep_buffer {
+            self.run_edit_commands(&[EditCommand::Clear]);
+            self.editor.reset_undo_stack();
+        }
+        EventStatus::Exits(Signal::Custom {
+            code,
+            partial_buffer,
+        })
+    }
+
+    /// Remember a trigger typed by `commands`, to open the completion menu
+    /// once typing pauses.
+    fn note_completion_trigger(&mut self, commands: &[EditCommand]) {
+        if self.completion_triggers.is_empty()
+            || !is_typed(commands)
+            || self.active_menu().is_some()
+        {
+            return;
+        }
+        let before_cursor = &self.editor.get_buffer()[..self.editor.insertion_point()];
+        let Some(trigger) = trigger_at_end(&self.completion_triggers, before_cursor) else {
+            return;
+        };
+        if self.dismissed_completion_trigger.as_deref() == Some(trigger) {
+            return;
+        }
+        let trigger = trigger.to_string();
+        self.dismissed_completion_trigger = None;
+        self.pending_completion_trigger = Some((trigger, Instant::now()));
+    }
+
+    /// Open the completion menu for a trigger typed before typing paused.
+    ///
+    /// Returns whether the menu was opened.
+    fn open_triggered_menu(&mut self, prompt: &dyn Prompt) -> io::Result<bool> {
+        let paused = self
+            .pending_completion_trigger
+            .as_ref()
+            .map_or(false, |(_, typed)| {
+                typed.elapsed() >= self.completion_trigger_delay
+            });
+        if !paused || self.input_mode != InputMode::Regular || self.active_menu().is_some() {
+            return Ok(false);
+        }
+        let Some((trigger, _)) = self.pending_completion_trigger.take() else {
+            return Ok(false);
+        };
+        let Some(name) = self
+            .menus
+            .iter()
+            .find(|menu| !matches!(menu, ReedlineMenu::HistoryMenu(_)))
+            .map(|menu| menu.name().to_string())
+        else {
+            return Ok(false);
+        };
+
+        self.fired_completion_trigger = Some(trigger);
+        self.handle_editor_event(prompt, ReedlineEvent::Menu(name))
+            .map(|status| matches!(status, EventStatus::Handled))
+    }
+
     fn deactivate_menus(&mut self) {
         self.menus
             .iter_mut()
@@ -1464,9 +2512,10 @@ impl Reedline {
         self.history_cursor_on_excluded = false;
         if self.input_mode != InputMode::HistoryTraversal {
             self.input_mode = InputMode::HistoryTraversal;
-            self.history_cursor = HistoryCursor::new(
+            self.history_prefix_original = None;
+            self.history_cursor = self.history_cursor(
                 self.get_history_navigation_based_on_line_buffer(),
-                self.get_history_session_id(),
+                self.history_navigation_scope,
             );
 
             if self.history_excluded_item.is_some() {
@@ -1489,9 +2538,10 @@ impl Reedline {
     fn next_history(&mut self) {
         if self.input_mode != InputMode::HistoryTraversal {
             self.input_mode = InputMode::HistoryTraversal;
-            self.history_cursor = HistoryCursor::new(
+            self.history_prefix_original = None;
+            self.history_cursor = self.history_cursor(
                 self.get_history_navigation_based_on_line_buffer(),
-                self.get_history_session_id(),
+                self.history_navigation_scope,
             );
         }
 
@@ -1520,6 +2570,52 @@ impl Reedline {
             .update_undo_state(UndoBehavior::HistoryNavigation)
     }
 
+    /// Step through the history entries starting with the text before the cursor
+    ///
+    /// The search text is taken once, when the navigation starts. The cursor
+    /// stays after it on every entry, and moving forward past the newest entry
+    /// restores the line as it was typed.
+    fn history_prefix_search(&mut self, direction: SearchDirection) {
+        if self.input_mode != InputMode::HistoryTraversal || self.history_prefix_original.is_none()
+        {
+            let original = self.editor.line_buffer().clone();
+            let prefix = original.get_buffer()[..original.insertion_point()].to_string();
+            self.input_mode = InputMode::HistoryTraversal;
+            self.history_cursor_on_excluded = false;
+            self.history_cursor = self.history_cursor(
+                HistoryNavigationQuery::PrefixSearch(prefix),
+                self.history_navigation_scope,
+            );
+            self.history_prefix_original = Some(original);
+        }
+        let Some(original) = self.history_prefix_original.clone() else {
+            return;
+        };
+
+        match direction {
+            SearchDirection::Backward => self.history_cursor.back(self.history.as_ref()),
+            SearchDirection::Forward => self.history_cursor.forward(self.history.as_ref()),
+        }
+        .expect("todo: error handling");
+
+        match self.history_cursor.string_at_cursor() {
+            Some(command_line) => {
+                let mut line_buffer = LineBuffer::from(command_line.as_str());
+                line_buffer.set_insertion_point(original.insertion_point());
+                self.editor
+                    .set_line_buffer(line_buffer, UndoBehavior::HistoryNavigation);
+            }
+            None => {
+                self.editor
+                    .set_line_buffer(original, UndoBehavior::HistoryNavigation);
+                self.input_mode = InputMode::Regular;
+                self.history_prefix_original = None;
+            }
+        }
+        self.editor
+            .update_undo_state(UndoBehavior::HistoryNavigation);
+    }
+
     /// Enable the search and navigation through the history from the line buffer prompt
     ///
     /// Enables either prefix search with output in the line buffer or simple traversal
@@ -1545,13 +2641,87 @@ impl Reedline {
     ///
     /// This mode uses a separate prompt and handles keybindings slightly differently!
     fn enter_history_search(&mut self) {
-        self.history_cursor = HistoryCursor::new(
-            HistoryNavigationQuery::SubstringSearch("".to_string()),
-            self.get_history_session_id(),
+        self.history_search_scope = self.history_navigation_scope;
+        self.history_cursor = self.history_cursor(
+            self.history_search_mode.navigation_query(String::new()),
+            self.history_search_scope,
         );
+        self.history_search_position = None;
+        self.history_search_last_match.clear();
+        self.history_search_matches = None;
+        self.history_search_error = None;
         self.input_mode = InputMode::HistorySearch;
     }
 
+    /// Remember where the reverse search is among its matches and the match
+    /// it shows, keeping the last match while the search fails
+    fn update_history_search_match(&mut self) {
+        if self.input_mode != InputMode::HistorySearch {
+            return;
+        }
+        let query = self.history_cursor.get_navigation();
+        let scope = self.history_cursor.scope();
+        let counted = matches!(
+            &self.history_search_matches,
+            Some((counted_query, counted_scope, _))
+                if *counted_query == query && *counted_scope == scope
+        );
+        if !counted {
+            self.history_search_matches = None;
+            self.history_search_error = None;
+            // Nothing to count at the initial point
+            if self.history_cursor.string_at_cursor().is_some() {
+                match self.history_cursor.matches(self.history.as_ref()) {
+                    Ok(matches) => self.history_search_matches = Some((query, scope, matches)),
+                    Err(err) => {
+                        self.history_search_error =
+                            Some(format!("Couldn't count the matches: {err}"));
+                    }
+                }
+            }
+        }
+        self.history_search_position = self
+            .history_search_matches
+            .as_ref()
+            .and_then(|(_, _, matches)| self.history_cursor.position_among(matches));
+        if let Some(found) = self.history_cursor.string_at_cursor() {
+            self.history_search_last_match = found;
+        }
+    }
+
+    /// Switch the reverse history search to the next scope, searching again
+    /// for the same text
+    fn cycle_history_search_scope(&mut self) {
+        self.history_search_scope = self.history_search_scope.next();
+        self.history_cursor = self.history_cursor(
+            self.history_cursor.get_navigation(),
+            self.history_search_scope,
+        );
+        self.history_cursor
+            .back(self.history.as_ref())
+            .expect("todo: error handling");
+    }
+
+    /// A cursor navigating the history by `query` within `scope`
+    fn history_cursor(
+        &self,
+        query: HistoryNavigationQuery,
+        scope: HistoryNavigationScope,
+    ) -> HistoryCursor {
+        HistoryCursor::new(query, self.get_history_session_id())
+            .with_scope(scope, Some(self.working_directory()))
+    }
+
+    /// The working directory set by the application, or else the one of the process
+    fn working_directory(&self) -> String {
+        self.cwd.clone().unwrap_or_else(|| {
+            std::env::current_dir()
+                .unwrap_or_default()
+                .to_string_lossy()
+                .to_string()
+        })
+    }
+
     /// Dispatches the applicable [`EditCommand`] actions for editing the history search string.
     ///
     /// Only modifies internal state, does not perform regular output!
@@ -1560,18 +2730,12 @@ impl Reedline {
             match command {
                 EditCommand::InsertChar(c) => {
                     let navigation = self.history_cursor.get_navigation();
-                    if let HistoryNavigationQuery::SubstringSearch(mut substring) = navigation {
-                        substring.push(*c);
-                        self.history_cursor = HistoryCursor::new(
-                            HistoryNavigationQuery::SubstringSearch(substring),
-                            self.get_history_session_id(),
-                        );
-                    } else {
-                        self.history_cursor = HistoryCursor::new(
-                            HistoryNavigationQuery::SubstringSearch(String::from(*c)),
-                            self.get_history_session_id(),
-                        );
-                    }
+                    let mut search = navigation.search_text().unwrap_or_default().to_string();
+                    search.push(*c);
+                    self.history_cursor = self.history_cursor(
+                        self.history_search_mode.navigation_query(search),
+                        self.history_search_scope,
+                    );
                     self.history_cursor
                         .back(self.history.as_mut())
                         .expect("todo: error handling");
@@ -1579,12 +2743,13 @@ impl Reedline {
                 EditCommand::Backspace => {
                     let navigation = self.history_cursor.get_navigation();
 
-                    if let HistoryNavigationQuery::SubstringSearch(substring) = navigation {
-                        let new_substring = text_manipulation::remove_last_grapheme(&substring);
+                    if let Some(search) = navigation.search_text() {
+                        let new_search = text_manipulation::remove_last_grapheme(search);
 
-                        self.history_cursor = HistoryCursor::new(
-                            HistoryNavigationQuery::SubstringSearch(new_substring.to_string()),
-                            self.get_history_session_id(),
+                        self.history_cursor = self.history_cursor(
+                            self.history_search_mode
+                                .navigation_query(new_search.to_string()),
+                            self.history_search_scope,
                         );
                         self.history_cursor
                             .back(self.history.as_mut())
@@ -1622,21 +2787,24 @@ impl Reedline {
                         .set_line_buffer(original, UndoBehavior::HistoryNavigation);
                 }
             }
-            HistoryNavigationQuery::PrefixSearch(prefix) => {
-                if let Some(prefix_result) = self.history_cursor.string_at_cursor() {
+            HistoryNavigationQuery::PrefixSearch(query)
+            | HistoryNavigationQuery::SubstringSearch(query)
+            | HistoryNavigationQuery::FuzzySearch(query) => {
+                if let Some(search_result) = self.history_cursor.string_at_cursor() {
                     self.editor
-                        .set_buffer(prefix_result, UndoBehavior::HistoryNavigation);
+                        .set_buffer(search_result, UndoBehavior::HistoryNavigation);
                 } else {
                     self.editor
-                        .set_buffer(prefix, UndoBehavior::HistoryNavigation);
+                        .set_buffer(query, UndoBehavior::HistoryNavigation);
                 }
             }
-            HistoryNavigationQuery::SubstringSearch(_) => todo!(),
         }
     }
 
     /// Executes [`EditCommand`] actions by modifying the internal state appropriately. Does not output itself.
     pub fn run_edit_commands(&mut self, commands: &[EditCommand]) {
+        self.history_expansion_error = None;
+        self.validation_error = None;
         if self.input_mode == InputMode::HistoryTraversal {
             if matches!(
                 self.history_cursor.get_navigation(),
@@ -1662,6 +2830,13 @@ impl Reedline {
         for command in commands {
             self.editor.run_edit_command(command);
         }
+
+        #[cfg(feature = "lsp_diagnostics")]
+        match commands.last() {
+            Some(EditCommand::InsertChar(c)) => self.format_on_type(*c),
+            Some(EditCommand::InsertNewline) => self.format_on_type('\n'),
+            _ => {}
+        }
     }
 
     fn up_command(&mut self) {
@@ -1702,6 +2877,9 @@ impl Reedline {
     #[cfg(feature = "bashisms")]
     /// Parses the ! command to replace entries from the history
     fn parse_bang_command(&mut self) -> Option<ReedlineEvent> {
+        if self.history_expansion {
+            return None;
+        }
         let buffer = self.editor.get_buffer();
         let parsed = parse_selection_char(buffer, '!');
         let parsed_prefix = parsed.prefix.unwrap_or_default().to_string();
@@ -1726,6 +2904,7 @@ impl Reedline {
                         start_id: None,
                         end_id: None,
                         limit: Some(1), // fetch the latest one entries
+                        offset: None,
                         filter: SearchFilter::anything(self.get_history_session_id()),
                     })
                     .unwrap_or_else(|_| Vec::new())
@@ -1746,6 +2925,7 @@ impl Reedline {
                         start_id: None,
                         end_id: None,
                         limit: Some(index as i64), // fetch the latest n entries
+                        offset: None,
                         filter: SearchFilter::anything(self.get_history_session_id()),
                     })
                     .unwrap_or_else(|_| Vec::new())
@@ -1762,12 +2942,7 @@ impl Reedline {
                         .history
                         .search(SearchQuery::last_with_prefix_and_cwd(
                             parsed.prefix.unwrap().to_string(),
-                            self.cwd.clone().unwrap_or_else(|| {
-                                std::env::current_dir()
-                                    .unwrap_or_default()
-                                    .to_string_lossy()
-                                    .to_string()
-                            }),
+                            self.working_directory(),
                             self.get_history_session_id(),
                         ))
                         .unwrap_or_else(|_| Vec::new())
@@ -1809,6 +2984,7 @@ impl Reedline {
                         start_id: None,
                         end_id: None,
                         limit: Some((index + 1) as i64), // fetch the oldest n entries
+                        offset: None,
                         filter: SearchFilter::anything(self.get_history_session_id()),
                     })
                     .unwrap_or_else(|_| Vec::new())
@@ -1847,6 +3023,39 @@ impl Reedline {
         }
     }
 
+    /// The command line of a history entry referred to by a history expansion
+    fn history_event(&self, event: HistoryEvent) -> Option<String> {
+        let session = self.get_history_session_id();
+        let (query, index) = match event {
+            HistoryEvent::Relative(n) => (
+                SearchQuery {
+                    limit: Some(n as i64),
+                    offset: None,
+                    ..SearchQuery::everything(SearchDirection::Backward, session)
+                },
+                n.checked_sub(1)?,
+            ),
+            HistoryEvent::Absolute(n) => (
+                SearchQuery {
+                    limit: Some(n as i64),
+                    offset: None,
+                    ..SearchQuery::everything(SearchDirection::Forward, session)
+                },
+                n.checked_sub(1)?,
+            ),
+            HistoryEvent::Prefix(prefix) => (
+                SearchQuery::last_with_prefix(prefix.to_string(), session),
+                0,
+            ),
+        };
+        self.history
+            .search(query)
+            .ok()?
+            .into_iter()
+            .nth(index)
+            .map(|item| item.command_line)
+    }
+
     fn open_editor(&mut self) -> Result<()> {
         match &mut self.buffer_editor {
             Some(BufferEditor {
@@ -1880,27 +3089,37 @@ impl Reedline {
     fn history_search_paint(&mut self, prompt: &dyn Prompt) -> Result<()> {
         let navigation = self.history_cursor.get_navigation();
 
-        if let HistoryNavigationQuery::SubstringSearch(substring) = navigation {
-            let status =
-                if !substring.is_empty() && self.history_cursor.string_at_cursor().is_none() {
-                    PromptHistorySearchStatus::Failing
-                } else {
-                    PromptHistorySearchStatus::Passing
-                };
+        if let Some(search) = navigation.search_text() {
+            let found = self.history_cursor.string_at_cursor();
+            let status = if !search.is_empty() && found.is_none() {
+                PromptHistorySearchStatus::Failing
+            } else {
+                PromptHistorySearchStatus::Passing
+            };
 
-            let prompt_history_search = PromptHistorySearch::new(status, substring.clone());
+            let mut prompt_history_search = PromptHistorySearch::new(status, search.to_string())
+                .with_scope(self.history_cursor.scope());
+            if let (Some((index, total)), Some(_)) = (self.history_search_position, &found) {
+                prompt_history_search = prompt_history_search.with_position(index, total);
+            }
 
-            let res_string = self.history_cursor.string_at_cursor().unwrap_or_default();
+            // A failing search keeps showing the last match
+            let res_string = found.unwrap_or_else(|| self.history_search_last_match.clone());
 
             // Highlight matches
             let res_string = if self.use_ansi_coloring {
-                let match_highlighter = SimpleMatchHighlighter::new(substring);
-                let styled = match_highlighter.highlight(&res_string, 0);
-                styled.render_simple()
+                self.history_search_highlight(&navigation, search, &res_string)
+                    .render_simple()
             } else {
                 res_string
             };
 
+            let search_error = match &self.history_search_error {
+                Some(error) if self.use_ansi_coloring => Color::Red.paint(error).to_string(),
+                Some(error) => error.clone(),
+                None => String::new(),
+            };
+
             let lines = PromptLines::new(
                 prompt,
                 self.prompt_edit_mode(),
@@ -1908,7 +3127,7 @@ impl Reedline {
                 &res_string,
                 "",
                 "",
-                "",
+                &search_error,
             );
 
             self.painter.repaint_buffer(
@@ -1925,10 +3144,49 @@ impl Reedline {
         Ok(())
     }
 
+    /// The result of the history search with every match of `search` styled,
+    /// the one the cursor lands on in the stronger current match style
+    fn history_search_highlight(
+        &self,
+        navigation: &HistoryNavigationQuery,
+        search: &str,
+        res_string: &str,
+    ) -> StyledText {
+        let current_match_style = self
+            .history_search_current_match_style
+            .unwrap_or_else(|| self.history_search_match_style.bold());
+        SimpleMatchHighlighter::new(search.to_string())
+            .with_match_style(self.history_search_match_style)
+            .with_current_match_style(current_match_style)
+            .with_fuzzy_matching(matches!(navigation, HistoryNavigationQuery::FuzzySearch(_)))
+            .highlight(res_string, 0)
+    }
+
+    /// The text shown below the prompt, the errors about the line in red
+    /// before the diagnostics
+    fn with_line_errors(&self, diagnostic_display: String) -> String {
+        self.history_expansion_error
+            .iter()
+            .chain(&self.validation_error)
+            .map(|error| {
+                if self.use_ansi_coloring {
+                    Color::Red.paint(error).to_string()
+                } else {
+                    error.clone()
+                }
+            })
+            .chain((!diagnostic_display.is_empty()).then_some(diagnostic_display))
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+
     /// Triggers a full repaint including the prompt parts
     ///
     /// Includes the highlighting and hinting calls.
     fn buffer_paint(&mut self, prompt: &dyn Prompt) -> Result<()> {
+        #[cfg(feature = "lsp_diagnostics")]
+        self.apply_lsp_workspace_edits();
+
         let cursor_position_in_buffer = self.editor.insertion_point();
         let buffer_to_paint = self.editor.get_buffer();
 
@@ -1936,6 +3194,7 @@ impl Reedline {
         #[cfg(feature = "lsp_diagnostics")]
         if let Some(ref mut provider) = self.lsp_diagnostics {
             provider.update_content(buffer_to_paint);
+            provider.update_cursor(buffer_to_paint, cursor_position_in_buffer);
         }
 
         let mut styled_text = self
@@ -1946,26 +3205,64 @@ impl Reedline {
             styled_text.style_range(from, to, self.visual_selection_style);
         }
 
+        if self.bracket_matching {
+            highlight_matching_bracket(
+                &mut styled_text,
+                buffer_to_paint,
+                cursor_position_in_buffer,
+                self.matching_bracket_style,
+                self.unmatched_bracket_style,
+            );
+        }
+
+        #[cfg(feature = "lsp_diagnostics")]
+        if let Some(ref provider) = self.lsp_diagnostics {
+            if self.use_ansi_coloring && provider.is_enabled() {
+                crate::lsp::highlight_occurrences(&mut styled_text, provider);
+            }
+        }
+
+        // Draw color swatches in front of color literals reported by the LSP server
+        #[cfg(feature = "lsp_diagnostics")]
+        let render_insertion_point = {
+            self.painted_swatches = match self.lsp_diagnostics {
+                Some(ref mut provider) if self.use_ansi_coloring && provider.is_enabled() => {
+                    crate::lsp::insert_color_swatches(
+                        &mut styled_text,
+                        buffer_to_paint,
+                        provider.colors(),
+                    )
+                }
+                _ => Vec::new(),
+            };
+            crate::lsp::offset_with_swatches(&self.painted_swatches, cursor_position_in_buffer)
+        };
+        #[cfg(not(feature = "lsp_diagnostics"))]
+        let render_insertion_point = cursor_position_in_buffer;
+
         let (before_cursor, after_cursor) = styled_text.render_around_insertion_point(
-            cursor_position_in_buffer,
+            render_insertion_point,
             prompt,
             self.use_ansi_coloring,
             self.painter.semantic_markers(),
         );
 
         let hint: String = if self.hints_active() {
+            let cwd = self.working_directory();
             self.hinter.as_mut().map_or_else(String::new, |hinter| {
-                hinter.handle(
+                let hint = hinter.handle(
                     buffer_to_paint,
                     cursor_position_in_buffer,
                     self.history.as_ref(),
                     self.use_ansi_coloring,
-                    &self.cwd.clone().unwrap_or_else(|| {
-                        std::env::current_dir()
-                            .unwrap_or_default()
-                            .to_string_lossy()
-                            .to_string()
-                    }),
+                    &cwd,
+                );
+                self.hint_token_boundary.render_hint(
+                    hinter.as_ref(),
+                    hint,
+                    buffer_to_paint,
+                    self.hint_next_token_style,
+                    self.use_ansi_coloring,
                 )
             })
         } else {
@@ -1980,12 +3277,15 @@ impl Reedline {
         let diagnostic_display = {
             let prompt_edit_mode = self.prompt_edit_mode();
             let use_ansi_coloring = self.use_ansi_coloring;
+            let screen_width = self.painter.screen_width() as usize;
             if let Some(ref mut provider) = self.lsp_diagnostics {
                 crate::lsp::format_diagnostics_for_prompt(
                     provider,
                     buffer_to_paint,
+                    cursor_position_in_buffer,
                     prompt,
                     prompt_edit_mode,
+                    screen_width,
                     use_ansi_coloring,
                 )
             } else {
@@ -1995,6 +3295,8 @@ impl Reedline {
         #[cfg(not(feature = "lsp_diagnostics"))]
         let diagnostic_display = String::new();
 
+        let diagnostic_display = self.with_line_errors(diagnostic_display);
+
         let mut lines = PromptLines::new(
             prompt,
             self.prompt_edit_mode(),
@@ -2004,6 +3306,10 @@ impl Reedline {
             &hint,
             &diagnostic_display,
         );
+        if self.editor.is_overwrite() {
+            lines.prompt_indicator =
+                prompt.render_prompt_overwrite_indicator(self.prompt_edit_mode());
+        }
 
         // Updating the working details of the active menu
         for menu in self.menus.iter_mut() {
@@ -2017,6 +3323,7 @@ impl Reedline {
                     &mut self.editor,
                     self.completer.as_mut(),
                     self.history.as_ref(),
+                    &self.suggestion_post_processor,
                     &self.painter,
                 );
             }
@@ -2036,8 +3343,9 @@ impl Reedline {
 
         if self.mouse_click_mode.is_enabled() {
             if let Some(layout) = &self.painter.last_layout {
-                let buffer = self.editor.get_buffer();
-                let (raw_before, raw_after) = buffer.split_at(cursor_position_in_buffer);
+                // The text as painted, clicks are mapped back around swatches
+                let painted = styled_text.raw_string();
+                let (raw_before, raw_after) = painted.split_at(render_insertion_point);
                 self.last_render_snapshot = Some(
                     self.painter
                         .render_snapshot(&lines, menu, raw_before, raw_after, layout),
@@ -2123,24 +3431,146 @@ impl Reedline {
         self
     }
 
-    /// Open the diagnostic fix menu with available fixes at the cursor position.
+    /// Turn on LSP support from a single [`LspConfig`].
     ///
-    /// This requests code actions from the LSP server for diagnostics at the
-    /// cursor position and displays them in a menu.
+    /// Spawns one [`LspDiagnosticsProvider`] and installs inline diagnostics, an
+    /// [`LspCompleter`](crate::LspCompleter) shown in a [`ColumnarMenu`](crate::ColumnarMenu),
+    /// and the bindings of [`add_lsp_keybindings`](crate::add_lsp_keybindings).
+    /// Diagnostics and completions share the same server connection.
     ///
-    /// Returns `true` if the menu was opened, `false` if there were no fixes.
+    /// The bindings are added to the edit mode, whether it is configured before
+    /// or after this call. Keys the edit mode binds already keep their binding.
+    ///
+    /// ## Required feature:
+    /// `lsp_diagnostics`
+    ///
+    /// # Example
+    /// ```no_run
+    /// use reedline::{LspConfig, Reedline};
+    ///
+    /// let config = LspConfig::new("nu-lint --lsp");
+    /// let line_editor = Reedline::create().with_lsp(config);
+    /// ```
     #[cfg(feature = "lsp_diagnostics")]
-    fn open_diagnostic_fix_menu(&mut self) -> bool {
-        let Some(ref mut provider) = self.lsp_diagnostics else {
-            return false;
+    #[must_use]
+    pub fn with_lsp(mut self, config: LspConfig) -> Self {
+        let provider = LspDiagnosticsProvider::new(config);
+        self.completer = Box::new(provider.completer());
+        self.lsp_diagnostics = Some(provider);
+
+        if !self
+            .menus
+            .iter()
+            .any(|menu| menu.name() == LSP_COMPLETION_MENU)
+        {
+            let menu = ColumnarMenu::default().with_name(LSP_COMPLETION_MENU);
+            self.menus
+                .push(ReedlineMenu::EngineCompleter(Box::new(menu)));
+        }
+
+        self.lsp_keybindings = true;
+        merge_lsp_keybindings(self.edit_mode.as_mut());
+        self
+    }
+
+    /// Whether LSP diagnostics are currently displayed.
+    ///
+    /// Returns `false` when no diagnostics provider is configured. Diagnostics
+    /// can be toggled at runtime with [`ReedlineEvent::ToggleDiagnostics`].
+    ///
+    /// ## Required feature:
+    /// `lsp_diagnostics`
+    #[cfg(feature = "lsp_diagnostics")]
+    pub fn diagnostics_enabled(&self) -> bool {
+        self.lsp_diagnostics
+            .as_ref()
+            .map_or(false, LspDiagnosticsProvider::is_enabled)
+    }
+
+    /// Whether the menu with the fixes for the diagnostic at the cursor is
+    /// open.
+    ///
+    /// A host handling keys itself can use it to leave keys like Tab to the
+    /// menu only while it's open.
+    ///
+    /// ## Required feature:
+    /// `lsp_diagnostics`
+    #[cfg(feature = "lsp_diagnostics")]
+    pub fn diagnostic_fix_menu_active(&self) -> bool {
+        self.menus
+            .iter()
+            .any(|menu| menu.name() == crate::lsp::DIAGNOSTIC_FIX_MENU && menu.is_active())
+    }
+
+    /// Why the LSP server can't be started, e.g. `nu-lint: No such file or
+    /// directory`.
+    ///
+    /// Returns `None` when no diagnostics provider is configured or nothing
+    /// went wrong. See [`LspDiagnosticsProvider::last_error`].
+    ///
+    /// ## Required feature:
+    /// `lsp_diagnostics`
+    #[cfg(feature = "lsp_diagnostics")]
+    pub fn lsp_error(&mut self) -> Option<&str> {
+        self.lsp_diagnostics
+            .as_mut()
+            .and_then(LspDiagnosticsProvider::last_error)
+    }
+
+    /// The LSP diagnostic under the cursor, the most severe one where several
+    /// overlap, e.g. to show it in a status bar.
+    ///
+    /// Returns `None` when no diagnostics provider is configured. See
+    /// [`LspDiagnosticsProvider::diagnostic_at`].
+    ///
+    /// ## Required feature:
+    /// `lsp_diagnostics`
+    #[cfg(feature = "lsp_diagnostics")]
+    pub fn diagnostic_at_cursor(&mut self) -> Option<&lsp_types::Diagnostic> {
+        let content = self.editor.get_buffer();
+        let pos = self.editor.insertion_point();
+        self.lsp_diagnostics.as_mut()?.diagnostic_at(content, pos)
+    }
+
+    /// Show only the LSP diagnostics from one of `sources`, or from all
+    /// sources for `None`.
+    ///
+    /// Does nothing when no diagnostics provider is configured. See
+    /// [`LspDiagnosticsProvider::set_source_filter`].
+    ///
+    /// ## Required feature:
+    /// `lsp_diagnostics`
+    #[cfg(feature = "lsp_diagnostics")]
+    pub fn set_diagnostics_source_filter(
+        &mut self,
+        sources: Option<std::collections::HashSet<String>>,
+    ) {
+        if let Some(provider) = &mut self.lsp_diagnostics {
+            provider.set_source_filter(sources);
+        }
+    }
+
+    /// Open the diagnostic fix menu with available fixes at the cursor position.
+    ///
+    /// This requests code actions from the LSP server for diagnostics at the
+    /// cursor position and displays them in a menu.
+    ///
+    /// Returns `true` if the menu was opened, `false` if there were no fixes.
+    #[cfg(feature = "lsp_diagnostics")]
+    fn open_diagnostic_fix_menu(&mut self) -> bool {
+        let Some(ref mut provider) = self.lsp_diagnostics else {
+            return false;
         };
+        if !provider.is_enabled() {
+            return false;
+        }
 
         let cursor_pos = self.editor.insertion_point();
         let content = self.editor.get_buffer();
 
         // Remove any existing diagnostic fix menu
-        let menu_name = "diagnostic_fix_menu";
-        self.menus.retain(|m| m.name() != menu_name);
+        self.menus
+            .retain(|m| m.name() != crate::lsp::DIAGNOSTIC_FIX_MENU);
 
         // Create the menu using the integration helper, passing highlighter for pre-highlighting
         if let Some(menu) = crate::lsp::create_diagnostic_fix_menu(
@@ -2148,6 +3578,8 @@ impl Reedline {
             cursor_pos,
             content,
             Some(self.highlighter.as_ref()),
+            // Leave half of the screen to the prompt and the lines above it
+            (self.painter.screen_height() / 2).max(1),
         ) {
             self.menus.push(menu);
             true
@@ -2156,6 +3588,77 @@ impl Reedline {
         }
     }
 
+    /// Open the symbol menu with the symbols of the document and the workspace.
+    ///
+    /// Returns `true` if the menu was opened, `false` if there were no symbols.
+    #[cfg(feature = "lsp_diagnostics")]
+    fn open_symbol_menu(&mut self) -> bool {
+        let Some(ref mut provider) = self.lsp_diagnostics else {
+            return false;
+        };
+
+        self.menus.retain(|m| m.name() != crate::lsp::SYMBOL_MENU);
+
+        match crate::lsp::create_symbol_menu(provider, self.editor.get_buffer()) {
+            Some(menu) => {
+                self.menus.push(menu);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Apply the LSP server's on-type formatting for the character just typed.
+    ///
+    /// The edits get their own undo point, so undo restores the text as typed.
+    #[cfg(feature = "lsp_diagnostics")]
+    fn format_on_type(&mut self, ch: char) {
+        let Some(ref mut provider) = self.lsp_diagnostics else {
+            return;
+        };
+
+        let content = self.editor.get_buffer();
+        let edits = provider.format_on_type(content, self.editor.insertion_point(), ch);
+        if edits.is_empty() {
+            return;
+        }
+
+        let (buffer, cursor) =
+            crate::lsp::apply_text_edits(content, self.editor.insertion_point(), &edits);
+        if buffer == content {
+            return;
+        }
+        let mut line_buffer = self.editor.line_buffer().clone();
+        line_buffer.set_buffer(buffer);
+        line_buffer.set_insertion_point(cursor);
+        self.editor
+            .set_line_buffer(line_buffer, UndoBehavior::CreateUndoPoint);
+    }
+
+    /// Apply the edits the LSP server made via `workspace/applyEdit`.
+    ///
+    /// Each batch gets its own undo point. Edits for content other than the
+    /// current buffer are dropped.
+    #[cfg(feature = "lsp_diagnostics")]
+    fn apply_lsp_workspace_edits(&mut self) {
+        let Some(ref mut provider) = self.lsp_diagnostics else {
+            return;
+        };
+
+        for (content, edits) in provider.take_workspace_edits() {
+            if *content != *self.editor.get_buffer() {
+                continue;
+            }
+            let (buffer, cursor) =
+                crate::lsp::apply_text_edits(&content, self.editor.insertion_point(), &edits);
+            let mut line_buffer = self.editor.line_buffer().clone();
+            line_buffer.set_buffer(buffer);
+            line_buffer.set_insertion_point(cursor);
+            self.editor
+                .set_line_buffer(line_buffer, UndoBehavior::CreateUndoPoint);
+        }
+    }
+
     #[cfg(feature = "external_printer")]
     fn external_messages(external_printer: &ExternalPrinter<String>) -> Result<Vec<String>> {
         let mut messages = Vec::new();
@@ -2180,8 +3683,54 @@ impl Reedline {
         Ok(messages)
     }
 
+    /// Whether `line` is kept out of the history by the exclusion prefix or predicate
+    fn is_excluded_from_history(&self, line: &str) -> bool {
+        self.history_exclusion_prefix
+            .as_ref()
+            .map_or(false, |prefix| line.starts_with(prefix))
+            || self
+                .history_exclusion
+                .as_ref()
+                .map_or(false, |exclude| exclude(line))
+    }
+
+    /// Save a submitted line, or only remember it for recall if it is excluded
+    fn record_in_history(&mut self, line: &str) {
+        let mut entry = HistoryItem::from_command_line(line);
+        entry.session_id = self.get_history_session_id();
+
+        if self.is_excluded_from_history(line) {
+            entry.id = Some(Self::FILTERED_ITEM_ID);
+            self.history_last_run_id = entry.id;
+            self.history_excluded_item = Some(entry);
+        } else {
+            entry = match self.history_deduplication {
+                Some(deduplication) => self.history.save_deduplicated(entry, deduplication),
+                None => self.history.save(entry),
+            }
+            .expect("todo: error handling");
+            self.history_last_run_id = entry.id;
+            self.history_excluded_item = None;
+        }
+    }
+
     fn submit_buffer(&mut self, prompt: &dyn Prompt) -> io::Result<EventStatus> {
-        let buffer = self.editor.get_buffer().to_string();
+        self.validation_error = None;
+        let mut buffer = self.editor.get_buffer().to_string();
+        if self.history_expansion {
+            match expand_history(&buffer, |event| self.history_event(event)) {
+                Ok(Some(expanded)) => {
+                    self.editor
+                        .set_buffer(expanded.clone(), UndoBehavior::CreateUndoPoint);
+                    buffer = expanded;
+                }
+                Ok(None) => {}
+                Err(err) => {
+                    self.history_expansion_error = Some(err.to_string());
+                    return Ok(EventStatus::Handled);
+                }
+            }
+        }
         self.hide_hints = true;
         // Additional repaint to show the content without hints etc.
         if let Some(transient_prompt) = self.transient_prompt.take() {
@@ -2190,25 +3739,19 @@ impl Reedline {
         } else {
             self.repaint(prompt)?;
         }
-        if !buffer.is_empty() {
-            let mut entry = HistoryItem::from_command_line(&buffer);
-            entry.session_id = self.get_history_session_id();
-
-            if self
-                .history_exclusion_prefix
-                .as_ref()
-                .map(|prefix| buffer.starts_with(prefix))
-                .unwrap_or(false)
-            {
-                entry.id = Some(Self::FILTERED_ITEM_ID);
-                self.history_last_run_id = entry.id;
-                self.history_excluded_item = Some(entry);
-            } else {
-                entry = self.history.save(entry).expect("todo: error handling");
-                self.history_last_run_id = entry.id;
-                self.history_excluded_item = None;
+        if let Some(id) = self.history_edited_entry.take() {
+            // Backends that can't delete keep the entry next to the fixed one
+            let fixed = self
+                .history
+                .load(id)
+                .map_or(false, |entry| entry.command_line != buffer);
+            if fixed {
+                let _ = self.history.delete(id);
             }
         }
+        if !buffer.is_empty() {
+            self.record_in_history(&buffer);
+        }
         self.run_edit_commands(&[EditCommand::Clear]);
         self.editor.reset_undo_stack();
 
@@ -2216,11 +3759,55 @@ impl Reedline {
     }
 }
 
+/// Delete every entry of `history` with `command_line`
+fn delete_from_history(history: &mut dyn History, command_line: &str) -> crate::Result<()> {
+    let entries = history.search(SearchQuery {
+        filter: SearchFilter::from_text_search(
+            CommandLineSearch::Exact(command_line.to_string()),
+            None,
+        ),
+        ..SearchQuery::everything(SearchDirection::Forward, None)
+    })?;
+    for id in entries.into_iter().filter_map(|entry| entry.id) {
+        history.delete(id)?;
+    }
+    Ok(())
+}
+
+/// Whether `commands` come from typing, rather than from a paste or a binding
+///
+/// Pasting without bracketed paste inserts more characters at once than
+/// anyone types between two reads.
+fn is_typed(commands: &[EditCommand]) -> bool {
+    !commands.is_empty()
+        && commands.len() <= EVENTS_THRESHOLD
+        && commands
+            .iter()
+            .all(|command| matches!(command, EditCommand::InsertChar(_)))
+}
+
+/// The longest of `triggers` that `text` ends in
+fn trigger_at_end<'t>(triggers: &'t [String], text: &str) -> Option<&'t str> {
+    triggers
+        .iter()
+        .filter(|trigger| !trigger.is_empty() && text.ends_with(trigger.as_str()))
+        .max_by_key(|trigger| trigger.len())
+        .map(String::as_str)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
     use crate::terminal_extensions::semantic_prompt::PromptKind;
-    use crate::DefaultPrompt;
+    use crate::{
+        result::{ReedlineError, ReedlineErrorVariants},
+        ColumnarMenu, DefaultPrompt, ListMenu, MatchingMode, MenuBuilder, Result as ReedlineResult,
+        Span, Suggestion, Vi,
+    };
+    use std::sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    };
 
     #[test]
     fn test_cursor_position_after_multiline_history_navigation() {
@@ -2269,6 +3856,128 @@ mod tests {
         assert_eq!(reedline.current_buffer_contents(), multiline_command);
     }
 
+    #[test]
+    fn altgr_key_events_insert_characters_into_buffer() {
+        let mut reedline = Reedline::create();
+        let prompt = DefaultPrompt::default();
+        let altgr = KeyModifiers::CONTROL | KeyModifiers::ALT;
+        let keys = [
+            (KeyCode::Char('a'), KeyModifiers::NONE),
+            (KeyCode::Char('@'), altgr),
+            (KeyCode::Char('{'), altgr),
+            (KeyCode::Char('~'), altgr | KeyModifiers::SHIFT),
+        ];
+
+        for (code, modifiers) in keys {
+            let raw = ReedlineRawEvent::try_from(Event::Key(KeyEvent::new(code, modifiers)))
+                .expect("press events are accepted");
+            let event = reedline.edit_mode.parse_event(raw);
+            reedline.handle_event(&prompt, event).unwrap();
+        }
+
+        assert_eq!(reedline.current_buffer_contents(), "a@{~");
+    }
+
+    #[rstest::rstest]
+    #[case(Box::<Emacs>::default())]
+    #[case(Box::<Vi>::default())]
+    fn disabled_altgr_insertion_applies_to_later_edit_modes(#[case] edit_mode: Box<dyn EditMode>) {
+        let mut reedline = Reedline::create()
+            .with_altgr_insertion(false)
+            .with_edit_mode(edit_mode);
+        let raw = ReedlineRawEvent::try_from(Event::Key(KeyEvent::new(
+            KeyCode::Char('@'),
+            KeyModifiers::CONTROL | KeyModifiers::ALT,
+        )))
+        .expect("press events are accepted");
+
+        assert_eq!(reedline.edit_mode.parse_event(raw), ReedlineEvent::None);
+    }
+
+    #[test]
+    fn insert_key_switches_to_overwrite_and_back() {
+        let mut reedline = Reedline::create();
+        reedline
+            .editor
+            .set_buffer("héllo".to_string(), UndoBehavior::CreateUndoPoint);
+        reedline.run_edit_commands(&[EditCommand::MoveToStart { select: false }]);
+        let prompt = DefaultPrompt::default();
+        let keys = [
+            KeyCode::Right,
+            KeyCode::Insert,
+            KeyCode::Char('a'),
+            KeyCode::Insert,
+            KeyCode::Char('b'),
+        ];
+
+        for code in keys {
+            let raw =
+                ReedlineRawEvent::try_from(Event::Key(KeyEvent::new(code, KeyModifiers::NONE)))
+                    .expect("press events are accepted");
+            let event = reedline.edit_mode.parse_event(raw);
+            reedline.handle_event(&prompt, event).unwrap();
+        }
+
+        assert_eq!(reedline.current_buffer_contents(), "habllo");
+        assert!(!reedline.editor.is_overwrite());
+    }
+
+    fn return_signal_reedline(buffer: &str) -> Reedline {
+        let mut reedline = Reedline::create();
+        reedline
+            .editor
+            .set_buffer(buffer.to_string(), UndoBehavior::CreateUndoPoint);
+        reedline
+    }
+
+    #[test]
+    fn return_signal_keeps_buffer_for_next_read_line() {
+        let mut reedline = return_signal_reedline("partial input");
+        let prompt = DefaultPrompt::default();
+
+        let status = reedline
+            .handle_event(
+                &prompt,
+                ReedlineEvent::ReturnSignal {
+                    code: 7,
+                    keep_buffer: true,
+                },
+            )
+            .unwrap();
+
+        assert!(matches!(
+            status,
+            EventStatus::Exits(Signal::Custom { code: 7, ref partial_buffer })
+                if partial_buffer == "partial input"
+        ));
+        assert_eq!(reedline.current_buffer_contents(), "partial input");
+        assert_eq!(reedline.history.count_all().unwrap(), 0);
+    }
+
+    #[test]
+    fn return_signal_clears_buffer_when_not_kept() {
+        let mut reedline = return_signal_reedline("partial input");
+        let prompt = DefaultPrompt::default();
+
+        let status = reedline
+            .handle_event(
+                &prompt,
+                ReedlineEvent::ReturnSignal {
+                    code: 1,
+                    keep_buffer: false,
+                },
+            )
+            .unwrap();
+
+        assert!(matches!(
+            status,
+            EventStatus::Exits(Signal::Custom { code: 1, ref partial_buffer })
+                if partial_buffer == "partial input"
+        ));
+        assert_eq!(reedline.current_buffer_contents(), "");
+        assert_eq!(reedline.history.count_all().unwrap(), 0);
+    }
+
     #[test]
     fn thread_safe() {
         fn f<S: Send>(_: S) {}
@@ -2350,6 +4059,1561 @@ mod tests {
         assert_eq!(reedline.current_insertion_point(), 0);
     }
 
+    fn single_line_snapshot(buffer: &str) -> RenderSnapshot {
+        RenderSnapshot {
+            screen_width: 40,
+            screen_height: 10,
+            prompt_start_row: 0,
+            prompt_height: 1,
+            large_buffer: false,
+            prompt_str_left: "".to_string(),
+            prompt_indicator: "".to_string(),
+            before_cursor: buffer.to_string(),
+            after_cursor: "".to_string(),
+            first_buffer_col: 0,
+            menu_active: false,
+            menu_start_row: None,
+            large_buffer_extra_rows_after_prompt: None,
+            large_buffer_offset: None,
+            right_prompt: None,
+        }
+    }
+
+    fn mouse_support_reedline(buffer: &str) -> Reedline {
+        let mut reedline = Reedline::create().with_mouse_support(true);
+        reedline
+            .editor
+            .set_buffer(buffer.to_string(), UndoBehavior::CreateUndoPoint);
+        reedline.last_render_snapshot = Some(single_line_snapshot(buffer));
+        reedline
+    }
+
+    fn left_click(column: u16) -> ReedlineEvent {
+        ReedlineEvent::Mouse {
+            column,
+            row: 0,
+            button: MouseButton::Left,
+        }
+    }
+
+    #[test]
+    fn mouse_drag_selects_text() {
+        let mut reedline = mouse_support_reedline("hello world");
+        let prompt = DefaultPrompt::default();
+
+        reedline.handle_event(&prompt, left_click(6)).unwrap();
+        reedline
+            .handle_event(&prompt, ReedlineEvent::MouseDrag { column: 9, row: 0 })
+            .unwrap();
+
+        assert_eq!(reedline.editor.get_selection(), Some((6, 9)));
+        assert_eq!(reedline.current_insertion_point(), 9);
+    }
+
+    #[test]
+    fn mouse_drag_backwards_selects_text() {
+        let mut reedline = mouse_support_reedline("hello world");
+        let prompt = DefaultPrompt::default();
+
+        reedline.handle_event(&prompt, left_click(5)).unwrap();
+        reedline
+            .handle_event(&prompt, ReedlineEvent::MouseDrag { column: 1, row: 0 })
+            .unwrap();
+
+        assert_eq!(reedline.editor.get_selection(), Some((1, 5)));
+    }
+
+    #[test]
+    fn mouse_click_clears_selection() {
+        let mut reedline = mouse_support_reedline("hello world");
+        let prompt = DefaultPrompt::default();
+
+        reedline.handle_event(&prompt, left_click(0)).unwrap();
+        reedline
+            .handle_event(&prompt, ReedlineEvent::MouseDrag { column: 4, row: 0 })
+            .unwrap();
+        reedline.handle_event(&prompt, left_click(8)).unwrap();
+
+        assert_eq!(reedline.editor.get_selection(), None);
+        assert_eq!(reedline.current_insertion_point(), 8);
+    }
+
+    #[test]
+    fn mouse_double_click_selects_word() {
+        let mut reedline = mouse_support_reedline("hello world");
+        let prompt = DefaultPrompt::default();
+
+        reedline.handle_event(&prompt, left_click(8)).unwrap();
+        reedline.handle_event(&prompt, left_click(8)).unwrap();
+
+        assert_eq!(reedline.editor.get_selection(), Some((6, 11)));
+    }
+
+    #[test]
+    fn mouse_drag_ignored_without_mouse_support() {
+        let mut reedline = Reedline::create().with_mouse_click(MouseClickMode::Enabled);
+        let prompt = DefaultPrompt::default();
+        reedline
+            .editor
+            .set_buffer("hello".to_string(), UndoBehavior::CreateUndoPoint);
+        reedline.last_render_snapshot = Some(single_line_snapshot("hello"));
+
+        reedline.handle_event(&prompt, left_click(0)).unwrap();
+        reedline
+            .handle_event(&prompt, ReedlineEvent::MouseDrag { column: 3, row: 0 })
+            .unwrap();
+
+        assert_eq!(reedline.editor.get_selection(), None);
+    }
+
+    fn completion_menu_reedline(scroll_lines: usize) -> Reedline {
+        let completer =
+            DefaultCompleter::new(vec!["apple".into(), "apricot".into(), "avocado".into()]);
+        let mut reedline = Reedline::create()
+            .with_mouse_support(true)
+            .with_mouse_scroll_lines(scroll_lines)
+            .with_completer(Box::new(completer))
+            .with_menu(ReedlineMenu::EngineCompleter(Box::new(
+                ColumnarMenu::default().with_name("completion_menu"),
+            )));
+        reedline
+            .editor
+            .set_buffer("a".to_string(), UndoBehavior::CreateUndoPoint);
+        reedline.painter.handle_resize(80, 24);
+        reedline
+    }
+
+    /// Apply pending menu events the way a repaint would.
+    fn update_active_menu(reedline: &mut Reedline) {
+        if let Some(menu) = reedline.menus.iter_mut().find(|menu| menu.is_active()) {
+            menu.update_working_details(
+                &mut reedline.editor,
+                reedline.completer.as_mut(),
+                reedline.history.as_ref(),
+                &reedline.suggestion_post_processor,
+                &reedline.painter,
+            );
+        }
+    }
+
+    fn scroll_and_accept(reedline: &mut Reedline, events: &[ReedlineEvent]) -> String {
+        let prompt = DefaultPrompt::default();
+        reedline
+            .handle_event(&prompt, ReedlineEvent::Menu("completion_menu".into()))
+            .unwrap();
+        update_active_menu(reedline);
+        for event in events {
+            reedline.handle_event(&prompt, event.clone()).unwrap();
+            update_active_menu(reedline);
+        }
+        reedline
+            .handle_event(&prompt, ReedlineEvent::Enter)
+            .unwrap();
+        reedline.current_buffer_contents().to_string()
+    }
+
+    #[test]
+    fn mouse_scroll_moves_menu_selection() {
+        let mut reedline = completion_menu_reedline(1);
+        let buffer = scroll_and_accept(
+            &mut reedline,
+            &[
+                ReedlineEvent::MouseScrollDown,
+                ReedlineEvent::MouseScrollDown,
+                ReedlineEvent::MouseScrollUp,
+            ],
+        );
+        assert_eq!(buffer, "apricot");
+    }
+
+    #[test]
+    fn mouse_scroll_moves_configured_lines_per_notch() {
+        let mut reedline = completion_menu_reedline(2);
+        let buffer = scroll_and_accept(&mut reedline, &[ReedlineEvent::MouseScrollDown]);
+        assert_eq!(buffer, "avocado");
+    }
+
+    /// Completes the words starting with the buffer, regardless of case
+    struct CaseInsensitiveCompleter(&'static [&'static str]);
+
+    impl Completer for CaseInsensitiveCompleter {
+        fn complete(&mut self, line: &str, pos: usize) -> Vec<Suggestion> {
+            let typed = line[..pos].to_lowercase();
+            self.0
+                .iter()
+                .filter(|word| word.to_lowercase().starts_with(&typed))
+                .map(|word| Suggestion {
+                    value: word.to_string(),
+                    span: Span::new(0, pos),
+                    append_whitespace: true,
+                    ..Suggestion::default()
+                })
+                .collect()
+        }
+    }
+
+    /// Completes the last word of the line from a list of file names
+    struct FileCompleter;
+
+    impl Completer for FileCompleter {
+        fn complete(&mut self, line: &str, pos: usize) -> Vec<Suggestion> {
+            let start = line[..pos].rfind(' ').map_or(0, |space| space + 1);
+            ["a.txt", "b.txt", "c.txt"]
+                .iter()
+                .filter(|file| file.starts_with(&line[start..pos]))
+                .map(|file| Suggestion {
+                    value: file.to_string(),
+                    span: Span::new(start, pos),
+                    append_whitespace: true,
+                    ..Suggestion::default()
+                })
+                .collect()
+        }
+    }
+
+    #[rstest::rstest]
+    #[case::continued(
+        ReedlineMenu::EngineCompleter(Box::new(
+            ColumnarMenu::default().with_name("completion_menu"),
+        )),
+        "ls b.txt a.txt ",
+        true
+    )]
+    #[case::single_shot(
+        ReedlineMenu::EngineCompleter(Box::new(
+            ListMenu::default()
+                .with_name("completion_menu")
+                .with_only_buffer_difference(false),
+        )),
+        "ls b.txt ",
+        false
+    )]
+    fn accepting_and_continuing_completes_one_argument_after_another(
+        #[case] menu: ReedlineMenu,
+        #[case] buffer: &str,
+        #[case] menu_active: bool,
+    ) {
+        let mut reedline = Reedline::create()
+            .with_completer(Box::new(FileCompleter))
+            .with_menu(menu);
+        reedline
+            .editor
+            .set_buffer("ls ".to_string(), UndoBehavior::CreateUndoPoint);
+        reedline.painter.handle_resize(80, 24);
+        let prompt = DefaultPrompt::default();
+
+        for event in [
+            ReedlineEvent::Menu("completion_menu".into()),
+            ReedlineEvent::MenuNext,
+            ReedlineEvent::MenuAcceptAndContinue,
+            ReedlineEvent::MenuAcceptAndContinue,
+        ] {
+            reedline.handle_event(&prompt, event).unwrap();
+            update_active_menu(&mut reedline);
+        }
+
+        let active = reedline.active_menu().is_some();
+        assert_eq!(
+            (reedline.current_buffer_contents(), active),
+            (buffer, menu_active)
+        );
+    }
+
+    #[rstest::rstest]
+    #[case::menu_right_away(
+        CompletionBehavior::MenuImmediately,
+        "ca",
+        &[("ca", true), ("ca", true)]
+    )]
+    #[case::menu_right_away_for_one(CompletionBehavior::MenuImmediately, "cl", &[("cl", true)])]
+    #[case::prefix_then_menu(
+        CompletionBehavior::InsertCommonPrefixThenMenu,
+        "ca",
+        &[("cargo", false), ("cargo", true), ("cargo", true)]
+    )]
+    #[case::prefix_in_the_casing_of_all(
+        CompletionBehavior::InsertCommonPrefixThenMenu,
+        "cargo.",
+        &[("Cargo.", false), ("Cargo.", true)]
+    )]
+    #[case::prefix_then_single(
+        CompletionBehavior::InsertCommonPrefixThenMenu,
+        "cl",
+        &[("clean ", false), ("clean ", false)]
+    )]
+    #[case::prefix_of_nothing(CompletionBehavior::InsertCommonPrefixThenMenu, "x", &[("x", false)])]
+    #[case::single_otherwise_menu(
+        CompletionBehavior::InsertSingleOtherwiseMenu,
+        "ca",
+        &[("ca", true), ("ca", true)]
+    )]
+    #[case::single_completed(
+        CompletionBehavior::InsertSingleOtherwiseMenu,
+        "cargo.t",
+        &[("Cargo.toml ", false)]
+    )]
+    fn tab_presses_follow_the_completion_behavior(
+        #[case] behavior: CompletionBehavior,
+        #[case] buffer: &str,
+        #[case] presses: &[(&str, bool)],
+    ) {
+        let completer =
+            CaseInsensitiveCompleter(&["Cargo.toml", "Cargo.lock", "cargo-clippy", "clean"]);
+        let mut reedline = Reedline::create()
+            .with_completer(Box::new(completer))
+            .with_completion_behavior(behavior)
+            .with_menu(ReedlineMenu::EngineCompleter(Box::new(
+                ColumnarMenu::default().with_name("completion_menu"),
+            )));
+        reedline
+            .editor
+            .set_buffer(buffer.to_string(), UndoBehavior::CreateUndoPoint);
+        reedline.painter.handle_resize(80, 24);
+        let prompt = DefaultPrompt::default();
+        let tab = ReedlineEvent::UntilFound(vec![
+            ReedlineEvent::Menu("completion_menu".into()),
+            ReedlineEvent::MenuNext,
+        ]);
+
+        for (press, expected) in presses.iter().enumerate() {
+            reedline.handle_event(&prompt, tab.clone()).unwrap();
+            update_active_menu(&mut reedline);
+            let menu_active = reedline.active_menu().is_some();
+            let state = (reedline.current_buffer_contents(), menu_active);
+            assert_eq!(state, *expected, "after press {}", press + 1);
+        }
+    }
+
+    #[rstest::rstest]
+    #[case::single("cd /", Some("/"))]
+    #[case::multi_character("ls --", Some("--"))]
+    #[case::longest("cd ./", Some("./"))]
+    #[case::shorter("cd .", Some("."))]
+    #[case::partial("ls -", None)]
+    #[case::not_at_end("cd /tmp", None)]
+    #[case::empty("", None)]
+    fn triggers_match_at_the_end(#[case] text: &str, #[case] expected: Option<&str>) {
+        let triggers = ["/", ".", "--", "./", ""].map(String::from);
+
+        assert_eq!(trigger_at_end(&triggers, text), expected);
+    }
+
+    fn triggered_reedline() -> Reedline {
+        let mut reedline = Reedline::create()
+            .with_completer(Box::new(CaseInsensitiveCompleter(&["/tmp", "--all"])))
+            .with_completion_triggers(vec!["/".into(), "--".into()])
+            .with_completion_trigger_delay(Duration::ZERO)
+            .with_menu(ReedlineMenu::HistoryMenu(Box::new(
+                ListMenu::default().with_name("history_menu"),
+            )))
+            .with_menu(ReedlineMenu::EngineCompleter(Box::new(
+                ColumnarMenu::default().with_name("completion_menu"),
+            )));
+        reedline.painter.handle_resize(80, 24);
+        reedline
+    }
+
+    /// Type `text` one character per read, returning whether the completion
+    /// menu is open after each
+    fn type_and_pause(reedline: &mut Reedline, text: &str) -> Vec<bool> {
+        let prompt = DefaultPrompt::default();
+        text.chars()
+            .map(|c| {
+                let typed = ReedlineEvent::Edit(vec![EditCommand::InsertChar(c)]);
+                reedline.handle_event(&prompt, typed).unwrap();
+                reedline.open_triggered_menu(&prompt).unwrap();
+                reedline
+                    .active_menu()
+                    .map_or(false, |menu| menu.name() == "completion_menu")
+            })
+            .collect()
+    }
+
+    #[test]
+    fn typed_triggers_open_the_completion_menu() {
+        let mut reedline = triggered_reedline();
+
+        assert_eq!(
+            type_and_pause(&mut reedline, "cd /"),
+            [false, false, false, true]
+        );
+
+        let mut reedline = triggered_reedline();
+        assert_eq!(
+            type_and_pause(&mut reedline, "ls -a --"),
+            [false, false, false, false, false, false, false, true]
+        );
+    }
+
+    #[test]
+    fn typing_on_before_the_pause_opens_no_menu() {
+        let mut reedline =
+            triggered_reedline().with_completion_trigger_delay(Duration::from_secs(60));
+
+        assert_eq!(type_and_pause(&mut reedline, "cd /"), [false; 4]);
+        assert!(reedline.pending_completion_trigger.is_some());
+        type_and_pause(&mut reedline, "t");
+        assert!(reedline.pending_completion_trigger.is_none());
+    }
+
+    #[rstest::rstest]
+    #[case::bracketed(vec![EditCommand::InsertString("cd /".into())])]
+    #[case::unbracketed("cd ~/projects/".chars().map(EditCommand::InsertChar).collect())]
+    fn pasted_triggers_open_no_menu(#[case] paste: Vec<EditCommand>) {
+        let mut reedline = triggered_reedline();
+        let prompt = DefaultPrompt::default();
+
+        reedline
+            .handle_event(&prompt, ReedlineEvent::Edit(paste))
+            .unwrap();
+
+        assert!(!reedline.open_triggered_menu(&prompt).unwrap());
+        assert!(reedline.active_menu().is_none());
+    }
+
+    #[test]
+    fn esc_keeps_the_trigger_closed_until_another_fires() {
+        let mut reedline = triggered_reedline();
+        let prompt = DefaultPrompt::default();
+        assert_eq!(type_and_pause(&mut reedline, "/"), [true]);
+
+        reedline.handle_event(&prompt, ReedlineEvent::Esc).unwrap();
+        assert!(reedline.active_menu().is_none());
+        assert_eq!(type_and_pause(&mut reedline, "tmp/"), [false; 4]);
+
+        assert_eq!(type_and_pause(&mut reedline, " --"), [false, false, true]);
+        reedline.handle_event(&prompt, ReedlineEvent::Esc).unwrap();
+        assert_eq!(type_and_pause(&mut reedline, "/"), [true]);
+    }
+
+    fn filtering_reedline(matching: MatchingMode) -> Reedline {
+        let mut reedline = Reedline::create().with_menu(ReedlineMenu::HistoryMenu(Box::new(
+            ListMenu::default()
+                .with_name("filter_menu")
+                .with_type_to_filter(Some(matching)),
+        )));
+        // Listed newest first
+        for command in ["branch", "commit", "cherry-pick", "checkout"] {
+            reedline
+                .history
+                .save(HistoryItem::from_command_line(command))
+                .unwrap();
+        }
+        reedline.painter.handle_resize(80, 24);
+        reedline
+            .editor
+            .set_buffer("git ".to_string(), UndoBehavior::CreateUndoPoint);
+        let prompt = DefaultPrompt::default();
+        reedline
+            .handle_event(&prompt, ReedlineEvent::Menu("filter_menu".into()))
+            .unwrap();
+        update_active_menu(&mut reedline);
+        reedline
+    }
+
+    /// Send `commands` as one edit and the values the active menu shows then
+    fn edit_and_list(reedline: &mut Reedline, commands: Vec<EditCommand>) -> Vec<String> {
+        let prompt = DefaultPrompt::default();
+        reedline
+            .handle_event(&prompt, ReedlineEvent::Edit(commands))
+            .unwrap();
+        update_active_menu(reedline);
+        let menu = reedline.active_menu().unwrap();
+        menu.get_values().iter().map(|s| s.value.clone()).collect()
+    }
+
+    #[rstest::rstest]
+    #[case::substring(MatchingMode::Substring, "ch", &["checkout", "cherry-pick", "branch"])]
+    #[case::prefix(MatchingMode::Prefix, "ch", &["checkout", "cherry-pick"])]
+    #[case::fuzzy(MatchingMode::Fuzzy, "cmt", &["commit"])]
+    fn typed_keys_filter_the_list_menu(
+        #[case] matching: MatchingMode,
+        #[case] typed: &str,
+        #[case] expected: &[&str],
+    ) {
+        let mut reedline = filtering_reedline(matching);
+
+        let typed = typed.chars().map(EditCommand::InsertChar).collect();
+        assert_eq!(edit_and_list(&mut reedline, typed), expected);
+
+        assert_eq!(reedline.current_buffer_contents(), "git ");
+        let status = reedline.active_menu().unwrap().menu_string(10, false);
+        assert!(status.contains("Filter: "), "{status}");
+    }
+
+    #[test]
+    fn backspace_edits_the_filter_of_the_list_menu() {
+        let mut reedline = filtering_reedline(MatchingMode::Substring);
+        let type_char = |c| vec![EditCommand::InsertChar(c)];
+
+        assert_eq!(edit_and_list(&mut reedline, type_char('o')).len(), 2);
+        assert_eq!(edit_and_list(&mut reedline, type_char('u')), ["checkout"]);
+        assert_eq!(
+            edit_and_list(&mut reedline, vec![EditCommand::Backspace]),
+            ["checkout", "commit"]
+        );
+        assert_eq!(
+            edit_and_list(&mut reedline, vec![EditCommand::Backspace]).len(),
+            4
+        );
+
+        assert_eq!(reedline.current_buffer_contents(), "git ");
+    }
+
+    #[test]
+    fn esc_clears_the_filter_before_closing_the_list_menu() {
+        let mut reedline = filtering_reedline(MatchingMode::Substring);
+        let prompt = DefaultPrompt::default();
+        edit_and_list(&mut reedline, vec![EditCommand::InsertChar('m')]);
+
+        reedline.handle_event(&prompt, ReedlineEvent::Esc).unwrap();
+        update_active_menu(&mut reedline);
+        let menu = reedline.active_menu().expect("the menu stays open");
+        assert_eq!(menu.get_values().len(), 4);
+
+        reedline.handle_event(&prompt, ReedlineEvent::Esc).unwrap();
+        assert!(reedline.active_menu().is_none());
+        assert_eq!(reedline.current_buffer_contents(), "git ");
+    }
+
+    #[test]
+    fn resize_recomputes_active_menu_layout() {
+        let mut reedline = completion_menu_reedline(1);
+        let prompt = DefaultPrompt::default();
+        reedline
+            .handle_event(&prompt, ReedlineEvent::Menu("completion_menu".into()))
+            .unwrap();
+        update_active_menu(&mut reedline);
+
+        // The three values share a row only once the screen is wide enough
+        for (width, height, rows) in [(1, 1, 3), (0, 0, 3), (3, 2, 3), (80, 24, 1)] {
+            reedline
+                .handle_event(&prompt, ReedlineEvent::Resize(width, height))
+                .unwrap();
+            update_active_menu(&mut reedline);
+            let menu = reedline.active_menu().expect("menu stays active");
+            assert_eq!(menu.menu_required_lines(width.max(1)), rows);
+        }
+    }
+
+    #[test]
+    fn mouse_scroll_navigates_history_without_menu() {
+        let mut reedline = Reedline::create().with_mouse_support(true);
+        let prompt = DefaultPrompt::default();
+        for command in ["first", "second"] {
+            reedline
+                .history
+                .save(HistoryItem::from_command_line(command))
+                .unwrap();
+        }
+
+        reedline
+            .handle_event(&prompt, ReedlineEvent::MouseScrollUp)
+            .unwrap();
+        assert_eq!(reedline.current_buffer_contents(), "second");
+        reedline
+            .handle_event(&prompt, ReedlineEvent::MouseScrollUp)
+            .unwrap();
+        assert_eq!(reedline.current_buffer_contents(), "first");
+        reedline
+            .handle_event(&prompt, ReedlineEvent::MouseScrollDown)
+            .unwrap();
+        assert_eq!(reedline.current_buffer_contents(), "second");
+    }
+
+    #[test]
+    fn mouse_scroll_ignored_without_mouse_support() {
+        let mut reedline = Reedline::create();
+        let prompt = DefaultPrompt::default();
+        reedline
+            .history
+            .save(HistoryItem::from_command_line("first"))
+            .unwrap();
+
+        let status = reedline
+            .handle_event(&prompt, ReedlineEvent::MouseScrollUp)
+            .unwrap();
+
+        assert!(matches!(status, EventStatus::Inapplicable));
+        assert_eq!(reedline.current_buffer_contents(), "");
+    }
+
+    #[cfg(feature = "lsp_diagnostics")]
+    fn fix_menu_reedline(mouse_support: bool) -> Reedline {
+        let fix = |title: &str, new_text: &str| -> lsp_types::CodeAction {
+            serde_json::from_value(serde_json::json!({
+                "title": title,
+                "edit": { "changes": { "repl:/session/repl": [{
+                    "range": {
+                        "start": { "line": 0, "character": 0 },
+                        "end": { "line": 0, "character": 2 },
+                    },
+                    "newText": new_text,
+                }]}},
+            }))
+            .unwrap()
+        };
+        let mut menu = crate::DiagnosticFixMenu::default();
+        menu.set_fixes(
+            vec![fix("List", "ls"), fix("Print", "echo"), fix("Where", "pwd")],
+            "sl",
+            0,
+            None,
+        );
+        let mut menu = ReedlineMenu::EngineCompleter(Box::new(menu));
+        menu.menu_event(MenuEvent::Activate(false));
+
+        let mut reedline = mouse_support_reedline("sl").with_mouse_support(mouse_support);
+        reedline.menus.push(menu);
+        let snapshot = reedline.last_render_snapshot.as_mut().unwrap();
+        snapshot.menu_active = true;
+        snapshot.menu_start_row = Some(1);
+        reedline
+    }
+
+    #[cfg(feature = "lsp_diagnostics")]
+    fn click_row(reedline: &mut Reedline, row: u16) {
+        let click = ReedlineEvent::Mouse {
+            column: 3,
+            row,
+            button: MouseButton::Left,
+        };
+        reedline
+            .handle_event(&DefaultPrompt::default(), click)
+            .unwrap();
+    }
+
+    #[cfg(feature = "lsp_diagnostics")]
+    #[test]
+    fn clicking_a_fix_selects_it_and_clicking_again_applies_it() {
+        let mut reedline = fix_menu_reedline(true);
+
+        click_row(&mut reedline, 3);
+        assert!(reedline.active_menu().is_some());
+        assert_eq!(reedline.current_buffer_contents(), "sl");
+
+        click_row(&mut reedline, 3);
+        assert!(reedline.active_menu().is_none());
+        assert_eq!(reedline.current_buffer_contents(), "pwd");
+    }
+
+    #[cfg(feature = "lsp_diagnostics")]
+    #[test]
+    fn scrolling_moves_the_fix_selection() {
+        let mut reedline = fix_menu_reedline(true);
+        let prompt = DefaultPrompt::default();
+
+        reedline
+            .handle_event(&prompt, ReedlineEvent::MouseScrollDown)
+            .unwrap();
+        click_row(&mut reedline, 2);
+
+        assert_eq!(reedline.current_buffer_contents(), "echo");
+    }
+
+    #[cfg(feature = "lsp_diagnostics")]
+    #[test]
+    fn paging_through_fixes_stops_at_the_ends() {
+        let mut reedline = fix_menu_reedline(false);
+        let prompt = DefaultPrompt::default();
+
+        for event in [ReedlineEvent::MenuPageNext, ReedlineEvent::MenuPageNext] {
+            reedline.handle_event(&prompt, event).unwrap();
+        }
+        reedline
+            .handle_event(&prompt, ReedlineEvent::Enter)
+            .unwrap();
+
+        assert_eq!(reedline.current_buffer_contents(), "pwd");
+    }
+
+    #[cfg(feature = "lsp_diagnostics")]
+    #[test]
+    fn fix_menu_clicks_ignored_without_mouse_support() {
+        let mut reedline = fix_menu_reedline(false);
+
+        click_row(&mut reedline, 1);
+        click_row(&mut reedline, 1);
+
+        assert!(reedline.active_menu().is_some());
+        assert_eq!(reedline.current_buffer_contents(), "sl");
+    }
+
+    #[test]
+    fn excluded_lines_are_recallable_but_not_saved() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("history.txt");
+        let history = Box::new(FileBackedHistory::with_file(10, path.clone()).unwrap());
+        let mut reedline = Reedline::create()
+            .with_history(history)
+            .with_history_exclusion(Box::new(|line| line.contains("password")));
+        let prompt = DefaultPrompt::default();
+
+        reedline.record_in_history("ls");
+        reedline.record_in_history("login --password hunter2");
+
+        reedline.handle_event(&prompt, ReedlineEvent::Up).unwrap();
+        assert_eq!(
+            reedline.current_buffer_contents(),
+            "login --password hunter2"
+        );
+        reedline.handle_event(&prompt, ReedlineEvent::Up).unwrap();
+        assert_eq!(reedline.current_buffer_contents(), "ls");
+
+        let saved: Vec<_> = reedline
+            .history
+            .search(SearchQuery::everything(SearchDirection::Forward, None))
+            .unwrap()
+            .into_iter()
+            .map(|item| item.command_line)
+            .collect();
+        assert_eq!(saved, vec!["ls"]);
+
+        reedline.sync_history().unwrap();
+        let next_session = FileBackedHistory::with_file(10, path).unwrap();
+        let saved: Vec<_> = next_session
+            .search(SearchQuery::everything(SearchDirection::Forward, None))
+            .unwrap()
+            .into_iter()
+            .map(|item| item.command_line)
+            .collect();
+        assert_eq!(saved, vec!["ls"]);
+    }
+
+    #[test]
+    fn exclusion_prefix_and_predicate_combine() {
+        let reedline = Reedline::create()
+            .with_history_exclusion_prefix(Some(' '.into()))
+            .with_history_exclusion(Box::new(|line| line.trim().chars().count() == 1));
+
+        assert!(reedline.is_excluded_from_history(" secret"));
+        assert!(reedline.is_excluded_from_history("l"));
+        assert!(!reedline.is_excluded_from_history("ls"));
+    }
+
+    fn history_expansion_reedline(line: &str) -> Reedline {
+        let mut reedline = Reedline::create().with_history_expansion(true);
+        reedline.painter.handle_resize(80, 24);
+        reedline.record_in_history("ls -l");
+        reedline.record_in_history("apt install vim");
+        reedline
+            .editor
+            .set_buffer(line.to_string(), UndoBehavior::CreateUndoPoint);
+        reedline
+    }
+
+    #[test]
+    fn history_expansion_submits_and_saves_the_expanded_line() {
+        let mut reedline = history_expansion_reedline("sudo !! && ls !$");
+        let prompt = DefaultPrompt::default();
+
+        let status = reedline
+            .handle_event(&prompt, ReedlineEvent::Submit)
+            .unwrap();
+
+        assert!(matches!(
+            status,
+            EventStatus::Exits(Signal::Success(line)) if line == "sudo apt install vim && ls vim"
+        ));
+        let latest = reedline
+            .history
+            .search(SearchQuery::everything(SearchDirection::Backward, None))
+            .unwrap();
+        assert_eq!(latest[0].command_line, "sudo apt install vim && ls vim");
+    }
+
+    #[test]
+    fn history_expansion_failure_keeps_the_line_and_shows_an_error() {
+        let mut reedline = history_expansion_reedline("echo !42");
+        let prompt = DefaultPrompt::default();
+
+        let status = reedline
+            .handle_event(&prompt, ReedlineEvent::Submit)
+            .unwrap();
+
+        assert!(matches!(status, EventStatus::Handled));
+        assert_eq!(reedline.current_buffer_contents(), "echo !42");
+        assert_eq!(
+            reedline.history_expansion_error.as_deref(),
+            Some("!42: event not found")
+        );
+
+        reedline.run_edit_commands(&[EditCommand::Backspace]);
+        assert_eq!(reedline.history_expansion_error, None);
+    }
+
+    #[test]
+    fn invalid_lines_show_why_below_the_prompt_until_the_next_edit() {
+        let mut reedline = Reedline::create().with_validator(Box::new(crate::DefaultValidator));
+        reedline.painter.handle_resize(80, 24);
+        let prompt = DefaultPrompt::default();
+        reedline.run_edit_commands(&[EditCommand::InsertString("echo [1, 2)".into())]);
+
+        let status = reedline
+            .handle_event(&prompt, ReedlineEvent::Enter)
+            .unwrap();
+
+        assert!(matches!(status, EventStatus::Handled));
+        assert_eq!(reedline.current_buffer_contents(), "echo [1, 2)");
+        assert_eq!(
+            reedline.with_line_errors("diagnostics".to_string()),
+            "\u{1b}[31mUnexpected `)`, `[` is not closed yet\u{1b}[0m\ndiagnostics"
+        );
+
+        reedline.run_edit_commands(&[EditCommand::Backspace]);
+        assert_eq!(reedline.validation_error, None);
+        assert_eq!(reedline.with_line_errors(String::new()), "");
+
+        reedline.run_edit_commands(&[EditCommand::InsertChar(']')]);
+        let status = reedline
+            .handle_event(&prompt, ReedlineEvent::Enter)
+            .unwrap();
+        assert!(matches!(
+            status,
+            EventStatus::Exits(Signal::Success(line)) if line == "echo [1, 2]"
+        ));
+    }
+
+    #[test]
+    fn incomplete_lines_still_get_a_newline() {
+        let mut reedline = Reedline::create().with_validator(Box::new(crate::DefaultValidator));
+        let prompt = DefaultPrompt::default();
+        reedline.run_edit_commands(&[EditCommand::InsertString("echo [1, 2".into())]);
+
+        reedline
+            .handle_event(&prompt, ReedlineEvent::Enter)
+            .unwrap();
+
+        assert_eq!(reedline.current_buffer_contents(), "echo [1, 2\n");
+        assert_eq!(reedline.validation_error, None);
+    }
+
+    #[test]
+    fn history_expansion_is_off_by_default() {
+        let mut reedline = Reedline::create();
+        reedline.painter.handle_resize(80, 24);
+        reedline.record_in_history("ls");
+        reedline
+            .editor
+            .set_buffer("echo '!!' !!".to_string(), UndoBehavior::CreateUndoPoint);
+
+        let status = reedline
+            .handle_event(&DefaultPrompt::default(), ReedlineEvent::Submit)
+            .unwrap();
+
+        assert!(matches!(
+            status,
+            EventStatus::Exits(Signal::Success(line)) if line == "echo '!!' !!"
+        ));
+    }
+
+    fn prefix_search_reedline(line: &str, cursor: usize) -> Reedline {
+        let mut reedline = Reedline::create();
+        for command in ["git status", "ls", "git push", "gitk", "git push"] {
+            reedline
+                .history
+                .save(HistoryItem::from_command_line(command))
+                .unwrap();
+        }
+        let mut line_buffer = LineBuffer::from(line);
+        line_buffer.set_insertion_point(cursor);
+        reedline
+            .editor
+            .set_line_buffer(line_buffer, UndoBehavior::CreateUndoPoint);
+        reedline
+    }
+
+    #[test]
+    fn prefix_search_filters_by_the_text_before_the_cursor() {
+        let mut reedline = prefix_search_reedline("git sx", 4);
+        let prompt = DefaultPrompt::default();
+        let mut visit = |event: ReedlineEvent| {
+            reedline.handle_event(&prompt, event).unwrap();
+            (
+                reedline.current_buffer_contents().to_string(),
+                reedline.current_insertion_point(),
+            )
+        };
+
+        let backward = [
+            visit(ReedlineEvent::HistoryPrefixSearchBackward),
+            visit(ReedlineEvent::HistoryPrefixSearchBackward),
+            visit(ReedlineEvent::HistoryPrefixSearchBackward),
+        ];
+        assert_eq!(
+            backward,
+            [
+                ("git push".to_string(), 4),
+                ("git status".to_string(), 4),
+                ("git status".to_string(), 4),
+            ]
+        );
+
+        let forward = [
+            visit(ReedlineEvent::HistoryPrefixSearchForward),
+            visit(ReedlineEvent::HistoryPrefixSearchForward),
+        ];
+        assert_eq!(
+            forward,
+            [("git push".to_string(), 4), ("git sx".to_string(), 4)]
+        );
+    }
+
+    #[test]
+    fn prefix_search_starts_over_after_editing() {
+        let mut reedline = prefix_search_reedline("git", 3);
+        let prompt = DefaultPrompt::default();
+
+        reedline
+            .handle_event(&prompt, ReedlineEvent::HistoryPrefixSearchBackward)
+            .unwrap();
+        assert_eq!(reedline.current_buffer_contents(), "git push");
+        reedline
+            .handle_event(
+                &prompt,
+                ReedlineEvent::Edit(vec![EditCommand::InsertChar('k')]),
+            )
+            .unwrap();
+        assert_eq!(reedline.current_buffer_contents(), "gitk push");
+
+        // The edited line is the new starting point
+        reedline
+            .handle_event(&prompt, ReedlineEvent::HistoryPrefixSearchBackward)
+            .unwrap();
+        assert_eq!(reedline.current_buffer_contents(), "gitk");
+        reedline
+            .handle_event(&prompt, ReedlineEvent::HistoryPrefixSearchForward)
+            .unwrap();
+        assert_eq!(reedline.current_buffer_contents(), "gitk push");
+        assert_eq!(reedline.current_insertion_point(), 4);
+    }
+
+    #[test]
+    fn history_search_uses_the_configured_mode() {
+        let mut reedline = Reedline::create().with_history_search_mode(HistorySearchMode::Fuzzy);
+        let prompt = DefaultPrompt::default();
+        for command in ["git commit -m", "ls"] {
+            reedline
+                .history
+                .save(HistoryItem::from_command_line(command))
+                .unwrap();
+        }
+
+        reedline
+            .handle_event(&prompt, ReedlineEvent::SearchHistory)
+            .unwrap();
+        for c in "gcm".chars() {
+            reedline
+                .handle_event(
+                    &prompt,
+                    ReedlineEvent::Edit(vec![EditCommand::InsertChar(c)]),
+                )
+                .unwrap();
+        }
+        reedline
+            .handle_event(&prompt, ReedlineEvent::Enter)
+            .unwrap();
+
+        assert_eq!(reedline.current_buffer_contents(), "git commit -m");
+    }
+
+    #[cfg(any(feature = "sqlite", feature = "sqlite-dynlib"))]
+    #[test]
+    fn history_navigation_and_search_keep_to_the_scope() {
+        let mut history = crate::SqliteBackedHistory::in_memory().unwrap();
+        for (command, cwd) in [
+            ("cargo test", "/app"),
+            ("ls", "/app"),
+            ("cargo run", "/home"),
+        ] {
+            history
+                .save(HistoryItem {
+                    cwd: Some(cwd.to_string()),
+                    ..HistoryItem::from_command_line(command)
+                })
+                .unwrap();
+        }
+        let mut reedline = Reedline::create()
+            .with_history(Box::new(history))
+            .with_history_navigation_scope(HistoryNavigationScope::Directory { exact: true });
+        reedline.set_cwd(Some("/app".to_string()));
+        let prompt = DefaultPrompt::default();
+
+        reedline.handle_event(&prompt, ReedlineEvent::Up).unwrap();
+        assert_eq!(reedline.current_buffer_contents(), "ls");
+        reedline.handle_event(&prompt, ReedlineEvent::Up).unwrap();
+        assert_eq!(reedline.current_buffer_contents(), "cargo test");
+        reedline.handle_event(&prompt, ReedlineEvent::Up).unwrap();
+        assert_eq!(reedline.current_buffer_contents(), "cargo test");
+
+        reedline
+            .handle_event(&prompt, ReedlineEvent::SearchHistory)
+            .unwrap();
+        reedline
+            .handle_event(
+                &prompt,
+                ReedlineEvent::Edit(vec![EditCommand::InsertChar('c')]),
+            )
+            .unwrap();
+        assert_eq!(
+            reedline.history_cursor.string_at_cursor().as_deref(),
+            Some("cargo test")
+        );
+        // Directory { exact: true } goes back to Global
+        reedline
+            .handle_event(&prompt, ReedlineEvent::CycleHistoryScope)
+            .unwrap();
+        assert_eq!(
+            reedline.history_cursor.scope(),
+            HistoryNavigationScope::Global
+        );
+        assert_eq!(
+            reedline.history_cursor.string_at_cursor().as_deref(),
+            Some("cargo run")
+        );
+    }
+
+    fn history_menu_reedline(history: Box<dyn History>, commands: &[&str]) -> Reedline {
+        let mut reedline =
+            Reedline::create()
+                .with_history(history)
+                .with_menu(ReedlineMenu::HistoryMenu(Box::new(
+                    crate::ListMenu::default().with_name("history_menu"),
+                )));
+        for command in commands {
+            reedline
+                .history
+                .save(HistoryItem::from_command_line(*command))
+                .unwrap();
+        }
+        reedline.painter.handle_resize(80, 24);
+        reedline
+            .handle_event(
+                &DefaultPrompt::default(),
+                ReedlineEvent::Menu("history_menu".into()),
+            )
+            .unwrap();
+        update_active_menu(&mut reedline);
+        reedline
+    }
+
+    fn send_to_menu(reedline: &mut Reedline, event: ReedlineEvent) -> Option<String> {
+        reedline
+            .handle_event(&DefaultPrompt::default(), event)
+            .unwrap();
+        update_active_menu(reedline);
+        reedline
+            .active_menu()
+            .and_then(|menu| menu.selected_value())
+            .map(|suggestion| suggestion.value)
+    }
+
+    fn entries_of(reedline: &Reedline, command_line: &str) -> i64 {
+        reedline
+            .history
+            .count(SearchQuery {
+                filter: SearchFilter::from_text_search(
+                    CommandLineSearch::Exact(command_line.to_string()),
+                    None,
+                ),
+                ..SearchQuery::everything(SearchDirection::Forward, None)
+            })
+            .unwrap()
+    }
+
+    #[cfg(any(feature = "sqlite", feature = "sqlite-dynlib"))]
+    #[test]
+    fn history_menu_deletes_the_selected_entry_once_confirmed() {
+        let secret = "curl -u admin:hunter2 example.com";
+        let mut reedline = history_menu_reedline(
+            Box::new(crate::SqliteBackedHistory::in_memory().unwrap()),
+            &[secret, "ls", "pwd", secret],
+        );
+        let menu_text =
+            |reedline: &mut Reedline| reedline.active_menu().unwrap().menu_string(10, false);
+
+        assert_eq!(
+            send_to_menu(&mut reedline, ReedlineEvent::MenuDeleteEntry).as_deref(),
+            Some(secret)
+        );
+        assert!(menu_text(&mut reedline).contains("Press again to confirm"));
+        assert_eq!(entries_of(&reedline, secret), 2);
+
+        // Every entry with the command line goes, the next one is selected
+        assert_eq!(
+            send_to_menu(&mut reedline, ReedlineEvent::MenuDeleteEntry).as_deref(),
+            Some("pwd")
+        );
+        assert_eq!(entries_of(&reedline, secret), 0);
+        assert!(menu_text(&mut reedline).contains("total: 2"));
+
+        // Moving in between asks again
+        send_to_menu(&mut reedline, ReedlineEvent::MenuDeleteEntry);
+        send_to_menu(&mut reedline, ReedlineEvent::MenuDown);
+        assert_eq!(
+            send_to_menu(&mut reedline, ReedlineEvent::MenuDeleteEntry).as_deref(),
+            Some("ls")
+        );
+        assert_eq!(entries_of(&reedline, "pwd"), 1);
+        assert_eq!(entries_of(&reedline, "ls"), 1);
+
+        // Deleting the last entry selects the one before it
+        assert_eq!(
+            send_to_menu(&mut reedline, ReedlineEvent::MenuDeleteEntry).as_deref(),
+            Some("pwd")
+        );
+        assert_eq!(entries_of(&reedline, "ls"), 0);
+    }
+
+    #[cfg(any(feature = "sqlite", feature = "sqlite-dynlib"))]
+    #[test]
+    fn editing_a_history_menu_entry_replaces_it_when_submitted() {
+        let mut reedline = history_menu_reedline(
+            Box::new(crate::SqliteBackedHistory::in_memory().unwrap()),
+            &["gti status", "ls", "gti status"],
+        );
+        let prompt = DefaultPrompt::default();
+
+        send_to_menu(&mut reedline, ReedlineEvent::MenuEditEntry);
+        assert!(reedline.active_menu().is_none());
+        assert_eq!(reedline.current_buffer_contents(), "gti status");
+        assert_eq!(entries_of(&reedline, "gti status"), 2);
+
+        reedline.run_edit_commands(&[EditCommand::MoveToStart { select: false }]);
+        reedline.run_edit_commands(&[EditCommand::DeleteWord]);
+        reedline.run_edit_commands(&[EditCommand::InsertString("git".into())]);
+        reedline
+            .handle_event(&prompt, ReedlineEvent::Enter)
+            .unwrap();
+
+        // Only the edited entry is replaced, not the others with its text
+        assert_eq!(entries_of(&reedline, "gti status"), 1);
+        assert_eq!(entries_of(&reedline, "git status"), 1);
+        assert_eq!(entries_of(&reedline, "ls"), 1);
+    }
+
+    #[test]
+    fn history_menu_says_when_deleting_is_not_supported() {
+        let mut reedline =
+            history_menu_reedline(Box::new(FileBackedHistory::new(10).unwrap()), &["ls"]);
+
+        send_to_menu(&mut reedline, ReedlineEvent::MenuDeleteEntry);
+        send_to_menu(&mut reedline, ReedlineEvent::MenuDeleteEntry);
+
+        let menu_text = reedline.active_menu().unwrap().menu_string(10, false);
+        assert!(menu_text.contains("Deleting entries is not supported by this history"));
+        assert_eq!(entries_of(&reedline, "ls"), 1);
+    }
+
+    #[test]
+    fn reverse_search_counts_the_matches_it_steps_through() {
+        let mut history = FileBackedHistory::new(100).unwrap();
+        for command in [
+            "git status",
+            "ls",
+            "git status",
+            "cd",
+            "git status",
+            "git commit",
+            "git push",
+        ] {
+            history
+                .save(HistoryItem::from_command_line(command))
+                .unwrap();
+        }
+        let mut reedline = Reedline::create().with_history(Box::new(history));
+        let prompt = DefaultPrompt::default();
+        let search = |reedline: &mut Reedline, event: ReedlineEvent| {
+            reedline.handle_event(&prompt, event).unwrap();
+            (
+                reedline.history_search_position,
+                reedline.history_search_last_match.clone(),
+            )
+        };
+        let type_char = |c| ReedlineEvent::Edit(vec![EditCommand::InsertChar(c)]);
+
+        search(&mut reedline, ReedlineEvent::SearchHistory);
+        search(&mut reedline, type_char('g'));
+        search(&mut reedline, type_char('i'));
+        // The `git status` entries count once, as the search skips repeats
+        assert_eq!(
+            search(&mut reedline, type_char('t')),
+            (Some((1, 3)), "git push".to_string())
+        );
+        search(&mut reedline, ReedlineEvent::SearchHistory);
+        assert_eq!(
+            search(&mut reedline, ReedlineEvent::SearchHistory),
+            (Some((3, 3)), "git status".to_string())
+        );
+        assert_eq!(
+            search(&mut reedline, ReedlineEvent::SearchHistory),
+            (Some((3, 3)), "git status".to_string())
+        );
+        assert_eq!(
+            search(&mut reedline, ReedlineEvent::NextHistory),
+            (Some((2, 3)), "git commit".to_string())
+        );
+
+        search(&mut reedline, type_char(' '));
+        assert_eq!(
+            search(&mut reedline, type_char('s')),
+            (Some((1, 1)), "git status".to_string())
+        );
+        // A failing search has no position and keeps the last match
+        assert_eq!(
+            search(&mut reedline, type_char('x')),
+            (None, "git status".to_string())
+        );
+        assert_eq!(
+            search(
+                &mut reedline,
+                ReedlineEvent::Edit(vec![EditCommand::Backspace])
+            ),
+            (Some((1, 1)), "git status".to_string())
+        );
+
+        let indicator = prompt.render_prompt_history_search_indicator(
+            PromptHistorySearch::new(PromptHistorySearchStatus::Passing, "git".to_string())
+                .with_position(2, 3),
+        );
+        assert_eq!(indicator, "(reverse-search [2/3]: git) ");
+    }
+
+    /// A history reading its entries a page at a time, which may fail, and
+    /// counting how many pages were read
+    struct PagedHistory {
+        history: FileBackedHistory,
+        pages_read: Arc<AtomicUsize>,
+        pages_fail: bool,
+    }
+
+    impl PagedHistory {
+        fn with_entries(entries: &[&str], pages_fail: bool) -> (Self, Arc<AtomicUsize>) {
+            let mut history = FileBackedHistory::new(100).unwrap();
+            for entry in entries {
+                history
+                    .save(HistoryItem::from_command_line(*entry))
+                    .unwrap();
+            }
+            let pages_read = Arc::new(AtomicUsize::new(0));
+            let paged = PagedHistory {
+                history,
+                pages_read: Arc::clone(&pages_read),
+                pages_fail,
+            };
+            (paged, pages_read)
+        }
+    }
+
+    impl History for PagedHistory {
+        fn save(&mut self, h: HistoryItem) -> ReedlineResult<HistoryItem> {
+            self.history.save(h)
+        }
+        fn load(&self, id: HistoryItemId) -> ReedlineResult<HistoryItem> {
+            self.history.load(id)
+        }
+        fn count(&self, query: SearchQuery) -> ReedlineResult<i64> {
+            self.history.count(query)
+        }
+        fn search(&self, query: SearchQuery) -> ReedlineResult<Vec<HistoryItem>> {
+            // Stepping to the next match reads one entry
+            if query.limit != Some(1) {
+                self.pages_read.fetch_add(1, Ordering::SeqCst);
+                if self.pages_fail {
+                    return Err(ReedlineError(ReedlineErrorVariants::OtherHistoryError(
+                        "database is locked",
+                    )));
+                }
+            }
+            self.history.search(query)
+        }
+        fn update(
+            &mut self,
+            id: HistoryItemId,
+            updater: &dyn Fn(HistoryItem) -> HistoryItem,
+        ) -> ReedlineResult<()> {
+            self.history.update(id, updater)
+        }
+        fn clear(&mut self) -> ReedlineResult<()> {
+            self.history.clear()
+        }
+        fn delete(&mut self, h: HistoryItemId) -> ReedlineResult<()> {
+            self.history.delete(h)
+        }
+        fn sync(&mut self) -> std::io::Result<()> {
+            self.history.sync()
+        }
+        fn session(&self) -> Option<HistorySessionId> {
+            self.history.session()
+        }
+    }
+
+    #[test]
+    fn reverse_search_counts_the_matches_once_per_query() {
+        let (history, pages_read) =
+            PagedHistory::with_entries(&["git status", "ls", "git commit", "git push"], false);
+        let mut reedline = Reedline::create().with_history(Box::new(history));
+        let prompt = DefaultPrompt::default();
+        for event in [
+            ReedlineEvent::SearchHistory,
+            ReedlineEvent::Edit(vec![EditCommand::InsertChar('g')]),
+        ] {
+            reedline.handle_event(&prompt, event).unwrap();
+        }
+        assert_eq!(pages_read.load(Ordering::SeqCst), 1);
+
+        for _ in 0..2 {
+            reedline
+                .handle_event(&prompt, ReedlineEvent::SearchHistory)
+                .unwrap();
+        }
+        assert_eq!(reedline.history_search_position, Some((3, 3)));
+        assert_eq!(pages_read.load(Ordering::SeqCst), 1);
+
+        reedline
+            .handle_event(
+                &prompt,
+                ReedlineEvent::Edit(vec![EditCommand::InsertChar('i')]),
+            )
+            .unwrap();
+        assert_eq!(pages_read.load(Ordering::SeqCst), 2);
+    }
+
+    #[test]
+    fn reverse_search_says_when_the_matches_cant_be_counted() {
+        let (history, _) = PagedHistory::with_entries(&["git status"], true);
+        let mut reedline = Reedline::create().with_history(Box::new(history));
+        let prompt = DefaultPrompt::default();
+        for event in [
+            ReedlineEvent::SearchHistory,
+            ReedlineEvent::Edit(vec![EditCommand::InsertChar('g')]),
+        ] {
+            reedline.handle_event(&prompt, event).unwrap();
+        }
+
+        assert_eq!(reedline.history_search_position, None);
+        assert_eq!(reedline.history_search_last_match, "git status");
+        assert_eq!(
+            reedline.history_search_error.as_deref(),
+            Some("Couldn't count the matches: error in Reedline history: database is locked")
+        );
+    }
+
+    #[rstest::rstest]
+    #[case::substring(HistoryNavigationQuery::SubstringSearch("stat".to_string()), "git status")]
+    #[case::fuzzy(HistoryNavigationQuery::FuzzySearch("gst".to_string()), "git status")]
+    #[case::no_match(HistoryNavigationQuery::FuzzySearch("xyz".to_string()), "xyz")]
+    fn search_results_are_taken_into_the_buffer(
+        #[case] navigation: HistoryNavigationQuery,
+        #[case] expected: &str,
+    ) {
+        let mut history = FileBackedHistory::new(100).unwrap();
+        for command in ["git status", "ls"] {
+            history
+                .save(HistoryItem::from_command_line(command))
+                .unwrap();
+        }
+        let mut reedline = Reedline::create().with_history(Box::new(history));
+        reedline.history_cursor =
+            reedline.history_cursor(navigation, HistoryNavigationScope::default());
+        reedline
+            .history_cursor
+            .back(reedline.history.as_ref())
+            .unwrap();
+
+        reedline.update_buffer_from_history();
+
+        assert_eq!(reedline.current_buffer_contents(), expected);
+        assert_eq!(reedline.current_insertion_point(), expected.len());
+    }
+
+    #[test]
+    fn reverse_search_highlights_every_match_of_the_query() {
+        let mut history = FileBackedHistory::new(100).unwrap();
+        history
+            .save(HistoryItem::from_command_line("git add . && git commit"))
+            .unwrap();
+        let mut reedline = Reedline::create()
+            .with_history(Box::new(history))
+            .with_history_search_current_match_style(Style::new().fg(Color::Green).underline());
+        let prompt = DefaultPrompt::default();
+        let search = |reedline: &mut Reedline, event: ReedlineEvent| {
+            reedline.handle_event(&prompt, event).unwrap();
+            let navigation = reedline.history_cursor.get_navigation();
+            navigation.search_text().map(|search| {
+                reedline
+                    .history_search_highlight(
+                        &navigation,
+                        search,
+                        &reedline.history_search_last_match,
+                    )
+                    .render_simple()
+            })
+        };
+        let type_char = |c| ReedlineEvent::Edit(vec![EditCommand::InsertChar(c)]);
+
+        search(&mut reedline, ReedlineEvent::SearchHistory);
+        assert_eq!(
+            search(&mut reedline, type_char('g')),
+            Some("\u{1b}[32mg\u{1b}[0mit add . && \u{1b}[4;32mg\u{1b}[0mit commit".to_string())
+        );
+        assert_eq!(
+            search(&mut reedline, type_char('i')),
+            Some("\u{1b}[32mgi\u{1b}[0mt add . && \u{1b}[4;32mgi\u{1b}[0mt commit".to_string())
+        );
+        assert_eq!(
+            search(&mut reedline, type_char('t')),
+            Some("\u{1b}[32mgit\u{1b}[0m add . && \u{1b}[4;32mgit\u{1b}[0m commit".to_string())
+        );
+
+        // Once the search ends, the buffer is painted without the matches
+        search(&mut reedline, ReedlineEvent::Enter);
+        assert!(reedline.input_mode == InputMode::Regular);
+        assert_eq!(
+            reedline.current_buffer_contents(),
+            "git add . && git commit"
+        );
+    }
+
+    #[cfg(feature = "lsp_diagnostics")]
+    fn missing_server() -> LspConfig {
+        LspConfig::new("reedline-test-missing-server")
+    }
+
+    #[cfg(feature = "lsp_diagnostics")]
+    #[rstest::rstest]
+    #[case::before(true)]
+    #[case::after(false)]
+    fn lsp_bindings_are_added_to_the_configured_edit_mode(#[case] vi_before_lsp: bool) {
+        let mut keybindings = crate::default_vi_insert_keybindings();
+        keybindings.add_binding(KeyModifiers::NONE, KeyCode::Tab, ReedlineEvent::ClearScreen);
+        let vi = Box::new(crate::Vi::new(
+            keybindings,
+            crate::default_vi_normal_keybindings(),
+        ));
+        let mut reedline = if vi_before_lsp {
+            Reedline::create()
+                .with_edit_mode(vi)
+                .with_lsp(missing_server())
+        } else {
+            Reedline::create()
+                .with_lsp(missing_server())
+                .with_edit_mode(vi)
+        };
+
+        assert!(matches!(reedline.prompt_edit_mode(), PromptEditMode::Vi(_)));
+        let tables = reedline.edit_mode.keybindings_mut();
+        for table in &tables {
+            assert_eq!(
+                table.find_binding(KeyModifiers::CONTROL, KeyCode::Char('.')),
+                Some(ReedlineEvent::OpenDiagnosticFixMenu)
+            );
+        }
+        assert_eq!(
+            tables[1].find_binding(KeyModifiers::NONE, KeyCode::Tab),
+            Some(ReedlineEvent::ClearScreen)
+        );
+    }
+
+    #[cfg(feature = "lsp_diagnostics")]
+    #[test]
+    fn toggle_diagnostics_flips_display_state() {
+        let provider = LspDiagnosticsProvider::new(missing_server());
+        let mut reedline = Reedline::create().with_lsp_diagnostics(provider);
+        let prompt = DefaultPrompt::default();
+        assert!(reedline.diagnostics_enabled());
+
+        reedline
+            .handle_event(&prompt, ReedlineEvent::ToggleDiagnostics)
+            .unwrap();
+        assert!(!reedline.diagnostics_enabled());
+        assert!(!reedline.open_diagnostic_fix_menu());
+
+        reedline
+            .handle_event(&prompt, ReedlineEvent::ToggleDiagnostics)
+            .unwrap();
+        assert!(reedline.diagnostics_enabled());
+    }
+
+    #[cfg(feature = "lsp_diagnostics")]
+    #[test]
+    fn fix_menu_reports_whether_it_is_open() {
+        let provider = LspDiagnosticsProvider::new(missing_server());
+        let mut reedline = Reedline::create().with_lsp_diagnostics(provider);
+        let prompt = DefaultPrompt::default();
+        assert!(!reedline.diagnostic_fix_menu_active());
+
+        let mut fix_menu = crate::DiagnosticFixMenu::default();
+        fix_menu.set_fixes(
+            vec![lsp_types::CodeAction {
+                title: "Remove unused variable".to_string(),
+                data: Some(serde_json::json!(7)),
+                ..Default::default()
+            }],
+            "let x = 1",
+            0,
+            None,
+        );
+        let mut menu = ReedlineMenu::EngineCompleter(Box::new(fix_menu));
+        menu.menu_event(MenuEvent::Activate(false));
+        reedline.menus.push(menu);
+        assert!(reedline.diagnostic_fix_menu_active());
+
+        reedline.handle_event(&prompt, ReedlineEvent::Esc).unwrap();
+        assert!(!reedline.diagnostic_fix_menu_active());
+    }
+
+    #[cfg(feature = "lsp_diagnostics")]
+    #[test]
+    fn copying_without_a_diagnostic_does_nothing() {
+        let provider = LspDiagnosticsProvider::new(missing_server());
+        let mut reedline = Reedline::create().with_lsp_diagnostics(provider);
+        let prompt = DefaultPrompt::default();
+        reedline.run_edit_commands(&[EditCommand::InsertString("let x = 1".into())]);
+
+        assert!(matches!(
+            reedline
+                .handle_event(&prompt, ReedlineEvent::CopyDiagnostic)
+                .unwrap(),
+            EventStatus::Inapplicable
+        ));
+        assert_eq!(reedline.current_buffer_contents(), "let x = 1");
+    }
+
+    #[test]
+    fn leave_terminal_modes_records_active_guards() {
+        let mut reedline = Reedline::create()
+            .use_bracketed_paste(false)
+            .with_mouse_support(true);
+        reedline.mouse_capture.enter();
+
+        let modes = reedline.leave_terminal_modes().unwrap();
+
+        assert_eq!(
+            modes,
+            TerminalModes {
+                mouse_capture: true,
+                ..TerminalModes::default()
+            }
+        );
+        assert!(!reedline.mouse_capture.is_active());
+
+        reedline.restore_terminal_modes(modes).unwrap();
+        assert!(reedline.mouse_capture.is_active());
+        assert!(!reedline.bracketed_paste.is_active());
+    }
+
+    #[test]
+    fn restore_terminal_modes_skips_inactive_guards() {
+        let mut reedline = Reedline::create().with_mouse_support(true);
+
+        let modes = reedline.leave_terminal_modes().unwrap();
+        assert_eq!(modes, TerminalModes::default());
+
+        reedline.restore_terminal_modes(modes).unwrap();
+        assert!(!reedline.mouse_capture.is_active());
+    }
+
     #[test]
     fn mouse_click_osc133_sets_semantic_markers() {
         let reedline = Reedline::create().with_mouse_click(MouseClickMode::EnabledWithOsc133);
@@ -34,6 +34,15 @@ pub enum Signal {
     CtrlC, // Interrupt current editing
     /// Abort with `Ctrl+D` signalling `EOF` or abort of a whole interactive session
     CtrlD, // End terminal session
+    /// Host defined action bound with [`ReedlineEvent::ReturnSignal`]
+    ///
+    /// Nothing is added to the history.
+    Custom {
+        /// Code given in the keybinding
+        code: u32,
+        /// Buffer contents at the time of the keypress
+        partial_buffer: String,
+    },
 }
 
 /// Scope of text object operation ("i" inner or "a" around)
@@ -416,6 +425,9 @@ pub enum EditCommand {
     /// Swap the positions of the cursor and anchor
     SwapCursorAndAnchor,
 
+    /// Toggle overwrite mode, in which typed characters replace the ones under the cursor
+    ToggleOverwrite,
+
     /// Cut selection to system clipboard
     #[cfg(feature = "system_clipboard")]
     CutSelectionSystem,
@@ -585,6 +597,7 @@ impl Display for EditCommand {
             EditCommand::CopyLeftUntil(_) => write!(f, "CopyLeftUntil Value: <char>"),
             EditCommand::CopyLeftBefore(_) => write!(f, "CopyLeftBefore Value: <char>"),
             EditCommand::SwapCursorAndAnchor => write!(f, "SwapCursorAndAnchor"),
+            EditCommand::ToggleOverwrite => write!(f, "ToggleOverwrite"),
             #[cfg(feature = "system_clipboard")]
             EditCommand::CutSelectionSystem => write!(f, "CutSelectionSystem"),
             #[cfg(feature = "system_clipboard")]
@@ -709,6 +722,7 @@ impl EditCommand {
             | EditCommand::CopyInsidePair { .. }
             | EditCommand::CopyAroundPair { .. }
             | EditCommand::CopyTextObject { .. } => EditType::NoOp,
+            EditCommand::ToggleOverwrite => EditType::NoOp,
         }
     }
 }
@@ -792,6 +806,11 @@ pub enum ReedlineEvent {
     /// Complete a single token/word of the history hint
     HistoryHintWordComplete,
 
+    /// Complete the history hint to the end of the token under the cursor,
+    /// split as set with
+    /// [`Reedline::with_hint_token_boundary`](crate::Reedline::with_hint_token_boundary)
+    HistoryHintTokenComplete,
+
     /// Handle EndOfLine event
     ///
     /// Expected Behavior:
@@ -818,6 +837,12 @@ pub enum ReedlineEvent {
     /// Sets the prompt back to the first line
     ClearScrollback,
 
+    /// Suspend the process (job control), restoring the terminal while stopped
+    ///
+    /// Sends `SIGTSTP` to the process group and repaints once resumed.
+    /// No-op on non-unix platforms.
+    Suspend,
+
     /// Handle enter event
     Enter,
 
@@ -840,6 +865,20 @@ pub enum ReedlineEvent {
         button: MouseButton,
     },
 
+    /// Mouse drag with the left button held, with screen coordinates
+    MouseDrag {
+        /// Column (x) position, 0-indexed from left
+        column: u16,
+        /// Row (y) position, 0-indexed from top
+        row: u16,
+    },
+
+    /// Mouse wheel scrolled up by one notch
+    MouseScrollUp,
+
+    /// Mouse wheel scrolled down by one notch
+    MouseScrollDown,
+
     /// trigger terminal resize
     Resize(u16, u16),
 
@@ -870,6 +909,27 @@ pub enum ReedlineEvent {
     /// Search the history for a string
     SearchHistory,
 
+    /// Switch the history search to the next [`HistoryNavigationScope`],
+    /// from the widest to the narrowest and back
+    ///
+    /// Inapplicable outside the history search.
+    ///
+    /// [`HistoryNavigationScope`]: crate::HistoryNavigationScope
+    CycleHistoryScope,
+
+    /// Navigate to the previous historic buffer starting with the text before
+    /// the cursor, keeping the cursor where it is
+    ///
+    /// Inapplicable while a menu is active, so it can be chained after
+    /// [`ReedlineEvent::MenuUp`] in a [`ReedlineEvent::UntilFound`].
+    HistoryPrefixSearchBackward,
+
+    /// Navigate to the next historic buffer starting with the text before the
+    /// cursor, or back to the typed line past the newest one
+    ///
+    /// Inapplicable while a menu is active.
+    HistoryPrefixSearchForward,
+
     /// In vi mode multiple reedline events can be chained while parsing the
     /// command or movement characters
     Multiple(Vec<ReedlineEvent>),
@@ -904,9 +964,50 @@ pub enum ReedlineEvent {
     /// Move to the previous history page
     MenuPagePrevious,
 
+    /// Scroll the description of the selected menu entry up, keeping the
+    /// selection
+    MenuDescriptionUp,
+
+    /// Scroll the description of the selected menu entry down, keeping the
+    /// selection
+    MenuDescriptionDown,
+
+    /// Remove the entry selected in the history menu from the history, after
+    /// asking to confirm by sending the event again
+    ///
+    /// Every entry with the same command line is removed. Inapplicable
+    /// unless a history menu is active.
+    MenuDeleteEntry,
+
+    /// Load the entry selected in the history menu into the line buffer to
+    /// fix it, closing the menu
+    ///
+    /// Submitting the changed line replaces the entry in the history.
+    /// Inapplicable unless a history menu is active.
+    MenuEditEntry,
+
+    /// Apply the selected menu entry and keep the menu open with the values
+    /// for the line as it is now, e.g. to complete several file arguments
+    ///
+    /// Menus that can't continue, like the diagnostic fix menu, are closed
+    /// as with [`ReedlineEvent::Enter`]. Inapplicable unless a menu is active.
+    MenuAcceptAndContinue,
+
     /// Way to bind the execution of a whole command (directly returning from [`crate::Reedline::read_line()`]) to a keybinding
     ExecuteHostCommand(String),
 
+    /// Return [`Signal::Custom`] with the given code from [`crate::Reedline::read_line()`]
+    ///
+    /// With `keep_buffer` the unfinished buffer is kept so the next `read_line`
+    /// continues editing it, otherwise the buffer is cleared. The buffer is never
+    /// added to the history.
+    ReturnSignal {
+        /// Code identifying the host action
+        code: u32,
+        /// Keep the buffer for the next `read_line` call
+        keep_buffer: bool,
+    },
+
     /// Open text editor
     OpenEditor,
 
@@ -917,6 +1018,24 @@ pub enum ReedlineEvent {
     /// Shows available quick fixes at cursor position
     #[cfg(feature = "lsp_diagnostics")]
     OpenDiagnosticFixMenu,
+
+    /// Open the symbol menu (requires lsp_diagnostics feature)
+    /// Typing filters the symbols, selecting one moves the cursor to it
+    #[cfg(feature = "lsp_diagnostics")]
+    OpenSymbolMenu,
+
+    /// Show or hide LSP diagnostics (requires lsp_diagnostics feature)
+    /// The server connection stays alive while diagnostics are hidden
+    #[cfg(feature = "lsp_diagnostics")]
+    ToggleDiagnostics,
+
+    /// Copy the message of the diagnostic under the cursor, and its code, to
+    /// the clipboard (requires lsp_diagnostics feature)
+    /// Uses the system clipboard with the system_clipboard feature, falling
+    /// back to the clipboard of the terminal through OSC 52 when the system
+    /// clipboard is unavailable
+    #[cfg(feature = "lsp_diagnostics")]
+    CopyDiagnostic,
 }
 
 impl Display for ReedlineEvent {
@@ -925,10 +1044,12 @@ impl Display for ReedlineEvent {
             ReedlineEvent::None => write!(f, "None"),
             ReedlineEvent::HistoryHintComplete => write!(f, "HistoryHintComplete"),
             ReedlineEvent::HistoryHintWordComplete => write!(f, "HistoryHintWordComplete"),
+            ReedlineEvent::HistoryHintTokenComplete => write!(f, "HistoryHintTokenComplete"),
             ReedlineEvent::CtrlD => write!(f, "CtrlD"),
             ReedlineEvent::CtrlC => write!(f, "CtrlC"),
             ReedlineEvent::ClearScreen => write!(f, "ClearScreen"),
             ReedlineEvent::ClearScrollback => write!(f, "ClearScrollback"),
+            ReedlineEvent::Suspend => write!(f, "Suspend"),
             ReedlineEvent::Enter => write!(f, "Enter"),
             ReedlineEvent::Submit => write!(f, "Submit"),
             ReedlineEvent::SubmitOrNewline => write!(f, "SubmitOrNewline"),
@@ -938,6 +1059,9 @@ impl Display for ReedlineEvent {
                 row,
                 button,
             } => write!(f, "Mouse({}, {}, {:?})", column, row, button),
+            ReedlineEvent::MouseDrag { column, row } => write!(f, "MouseDrag({}, {})", column, row),
+            ReedlineEvent::MouseScrollUp => write!(f, "MouseScrollUp"),
+            ReedlineEvent::MouseScrollDown => write!(f, "MouseScrollDown"),
             ReedlineEvent::Resize(_, _) => write!(f, "Resize <int> <int>"),
             ReedlineEvent::Edit(_) => write!(
                 f,
@@ -951,6 +1075,9 @@ impl Display for ReedlineEvent {
             ReedlineEvent::Left => write!(f, "Left"),
             ReedlineEvent::NextHistory => write!(f, "NextHistory"),
             ReedlineEvent::SearchHistory => write!(f, "SearchHistory"),
+            ReedlineEvent::CycleHistoryScope => write!(f, "CycleHistoryScope"),
+            ReedlineEvent::HistoryPrefixSearchBackward => write!(f, "HistoryPrefixSearchBackward"),
+            ReedlineEvent::HistoryPrefixSearchForward => write!(f, "HistoryPrefixSearchForward"),
             ReedlineEvent::Multiple(_) => write!(f, "Multiple[ {{ ReedLineEvents, }} ]"),
             ReedlineEvent::UntilFound(_) => write!(f, "UntilFound [ {{ ReedLineEvents, }} ]"),
             ReedlineEvent::Menu(_) => write!(f, "Menu Name: <string>"),
@@ -962,11 +1089,78 @@ impl Display for ReedlineEvent {
             ReedlineEvent::MenuRight => write!(f, "MenuRight"),
             ReedlineEvent::MenuPageNext => write!(f, "MenuPageNext"),
             ReedlineEvent::MenuPagePrevious => write!(f, "MenuPagePrevious"),
+            ReedlineEvent::MenuDescriptionUp => write!(f, "MenuDescriptionUp"),
+            ReedlineEvent::MenuDescriptionDown => write!(f, "MenuDescriptionDown"),
+            ReedlineEvent::MenuDeleteEntry => write!(f, "MenuDeleteEntry"),
+            ReedlineEvent::MenuEditEntry => write!(f, "MenuEditEntry"),
+            ReedlineEvent::MenuAcceptAndContinue => write!(f, "MenuAcceptAndContinue"),
             ReedlineEvent::ExecuteHostCommand(_) => write!(f, "ExecuteHostCommand"),
+            ReedlineEvent::ReturnSignal { .. } => write!(f, "ReturnSignal"),
             ReedlineEvent::OpenEditor => write!(f, "OpenEditor"),
             ReedlineEvent::ViChangeMode(_) => write!(f, "ViChangeMode mode: <string>"),
             #[cfg(feature = "lsp_diagnostics")]
             ReedlineEvent::OpenDiagnosticFixMenu => write!(f, "OpenDiagnosticFixMenu"),
+            #[cfg(feature = "lsp_diagnostics")]
+            ReedlineEvent::OpenSymbolMenu => write!(f, "OpenSymbolMenu"),
+            #[cfg(feature = "lsp_diagnostics")]
+            ReedlineEvent::ToggleDiagnostics => write!(f, "ToggleDiagnostics"),
+            #[cfg(feature = "lsp_diagnostics")]
+            ReedlineEvent::CopyDiagnostic => write!(f, "CopyDiagnostic"),
+        }
+    }
+}
+
+impl ReedlineEvent {
+    /// Human-readable description of the event, including nested events and edit commands
+    ///
+    /// Unlike the [`Display`] implementation, which lists the event kinds for
+    /// configuration, this spells out the contents, e.g. for a `bindkey`-style listing.
+    pub fn describe(&self) -> String {
+        fn describe_all(events: &[ReedlineEvent], separator: &str) -> String {
+            events
+                .iter()
+                .map(ReedlineEvent::describe)
+                .collect::<Vec<_>>()
+                .join(separator)
+        }
+
+        match self {
+            ReedlineEvent::Edit(commands) => {
+                let commands: Vec<_> = commands.iter().map(|c| format!("{c:?}")).collect();
+                format!("Edit({})", commands.join(", "))
+            }
+            ReedlineEvent::Multiple(events) => format!("Multiple({})", describe_all(events, ", ")),
+            ReedlineEvent::UntilFound(events) => {
+                format!("UntilFound({})", describe_all(events, " | "))
+            }
+            ReedlineEvent::Menu(name) => format!("Menu({name})"),
+            ReedlineEvent::ExecuteHostCommand(command) => {
+                format!("ExecuteHostCommand({command:?})")
+            }
+            ReedlineEvent::ViChangeMode(mode) => format!("ViChangeMode({mode})"),
+            ReedlineEvent::Resize(width, height) => format!("Resize({width}, {height})"),
+            ReedlineEvent::ReturnSignal { code, keep_buffer } => {
+                format!("ReturnSignal(code: {code}, keep_buffer: {keep_buffer})")
+            }
+            event => event.to_string(),
+        }
+    }
+
+    /// Whether this event is `other` or runs it as part of a nested event
+    ///
+    /// A single edit command also matches edits that include it among others.
+    pub(crate) fn contains(&self, other: &ReedlineEvent) -> bool {
+        if self == other {
+            return true;
+        }
+        match (self, other) {
+            (ReedlineEvent::Multiple(events) | ReedlineEvent::UntilFound(events), _) => {
+                events.iter().any(|event| event.contains(other))
+            }
+            (ReedlineEvent::Edit(commands), ReedlineEvent::Edit(wanted)) if wanted.len() == 1 => {
+                commands.contains(&wanted[0])
+            }
+            _ => false,
         }
     }
 }
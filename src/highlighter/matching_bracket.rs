@@ -0,0 +1,241 @@
+use nu_ansi_term::Style;
+
+use crate::{StyleModifier, StyledText};
+
+/// A bracket outside of quotes, as found by [`scan_brackets`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Bracket {
+    /// An opening bracket inside `depth` others
+    Open { offset: usize, depth: usize },
+    /// A closing bracket and the offset of its opener, `None` if it has none,
+    /// inside `depth` other brackets
+    Close {
+        offset: usize,
+        opener: Option<usize>,
+        depth: usize,
+    },
+    /// An opening bracket that is never closed, reported after the others
+    Unclosed { offset: usize },
+}
+
+/// Visit the brackets `()[]{}` of `buffer` in order, skipping those in
+/// quotes or escaped with `\`
+///
+/// A closing bracket only closes an opener of its kind, the last one still
+/// open. Only the open brackets are kept while scanning.
+pub(crate) fn scan_brackets(buffer: &str, mut visit: impl FnMut(Bracket)) {
+    let mut open = Vec::new();
+    let mut quote = None;
+    let mut escaped = false;
+    for (offset, c) in buffer.char_indices() {
+        if escaped {
+            escaped = false;
+            continue;
+        }
+        match (quote, c) {
+            (Some('\''), '\'') => quote = None,
+            (Some('\''), _) => {}
+            (Some(_), '\\') | (None, '\\') => escaped = true,
+            (Some(q), c) if q == c => quote = None,
+            (Some(_), _) => {}
+            (None, '\'' | '"' | '`') => quote = Some(c),
+            (None, '(' | '[' | '{') => {
+                visit(Bracket::Open {
+                    offset,
+                    depth: open.len(),
+                });
+                open.push((c, offset));
+            }
+            (None, ')' | ']' | '}') => {
+                let opener = match open.last() {
+                    Some(&(opener, opener_offset)) if partner_of(opener) == c => {
+                        open.pop();
+                        Some(opener_offset)
+                    }
+                    _ => None,
+                };
+                visit(Bracket::Close {
+                    offset,
+                    opener,
+                    depth: open.len(),
+                });
+            }
+            (None, _) => {}
+        }
+    }
+    for (_, offset) in open {
+        visit(Bracket::Unclosed { offset });
+    }
+}
+
+fn partner_of(opener: char) -> char {
+    match opener {
+        '(' => ')',
+        '[' => ']',
+        _ => '}',
+    }
+}
+
+/// The bracket on or right before `cursor` in `buffer` and the offset of its
+/// partner, `None` if it has none
+///
+/// The bracket on the cursor wins over the one before it. Brackets in quotes
+/// or escaped with `\` are skipped, both as the bracket at the cursor and
+/// while looking for its partner.
+pub(crate) fn bracket_at_cursor(buffer: &str, cursor: usize) -> Option<(usize, Option<usize>)> {
+    let on_cursor = cursor;
+    let before_cursor = buffer[..cursor.min(buffer.len())]
+        .char_indices()
+        .next_back()
+        .map(|(offset, _)| offset);
+
+    // Partner of the bracket on and before the cursor, once seen outside quotes
+    let mut found_on: Option<Option<usize>> = None;
+    let mut found_before: Option<Option<usize>> = None;
+    let mut record = |bracket: usize, partner: Option<usize>| {
+        if bracket == on_cursor {
+            found_on = Some(partner);
+        } else if Some(bracket) == before_cursor {
+            found_before = Some(partner);
+        }
+    };
+
+    scan_brackets(buffer, |bracket| match bracket {
+        Bracket::Open { .. } => {}
+        Bracket::Close {
+            offset,
+            opener: Some(opener),
+            ..
+        } => {
+            record(opener, Some(offset));
+            record(offset, Some(opener));
+        }
+        Bracket::Close { offset, .. } | Bracket::Unclosed { offset } => record(offset, None),
+    });
+
+    found_on
+        .map(|partner| (on_cursor, partner))
+        .or_else(|| found_before.map(|partner| (before_cursor.unwrap_or_default(), partner)))
+}
+
+/// Style the bracket at the cursor and its partner in `matched`, or the
+/// bracket alone in `unmatched` if it has no partner
+pub(crate) fn highlight_matching_bracket(
+    styled_text: &mut StyledText,
+    buffer: &str,
+    cursor: usize,
+    matched: Style,
+    unmatched: Style,
+) {
+    match bracket_at_cursor(buffer, cursor) {
+        Some((bracket, Some(partner))) => {
+            for offset in [bracket, partner] {
+                styled_text
+                    .apply_style_over_range(offset..offset + 1, StyleModifier::from(matched));
+            }
+        }
+        Some((bracket, None)) => {
+            styled_text
+                .apply_style_over_range(bracket..bracket + 1, StyleModifier::from(unmatched));
+        }
+        None => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use nu_ansi_term::Color;
+    use pretty_assertions::assert_eq;
+    use rstest::rstest;
+
+    #[rstest]
+    #[case::on_an_opener("ls (pwd)", 3, Some((3, Some(7))))]
+    #[case::after_a_closer("ls (pwd)", 8, Some((7, Some(3))))]
+    #[case::on_wins_over_before("f()[0]", 3, Some((3, Some(5))))]
+    #[case::before_if_not_on("f()[0]", 2, Some((2, Some(1))))]
+    #[case::nested("{ [(a) (b)] }", 2, Some((2, Some(10))))]
+    #[case::inner_of_nested("{ [(a) (b)] }", 7, Some((7, Some(9))))]
+    #[case::unmatched_opener("echo (ls", 5, Some((5, None)))]
+    #[case::unmatched_closer("echo ls)", 8, Some((7, None)))]
+    #[case::mismatched_kind("(a]", 2, Some((2, None)))]
+    #[case::no_bracket("ls -la", 3, None)]
+    #[case::empty("", 0, None)]
+    #[case::after_multibyte("é(x)", 2, Some((2, Some(4))))]
+    fn bracket_and_partner(
+        #[case] buffer: &str,
+        #[case] cursor: usize,
+        #[case] expected: Option<(usize, Option<usize>)>,
+    ) {
+        assert_eq!(bracket_at_cursor(buffer, cursor), expected);
+    }
+
+    #[rstest]
+    #[case::double_quotes(r#"(echo ")")"#, 0, Some((0, Some(9))))]
+    #[case::single_quotes("(echo ')')", 0, Some((0, Some(9))))]
+    #[case::backticks("(echo `)`)", 0, Some((0, Some(9))))]
+    #[case::escaped(r"(echo \))", 0, Some((0, Some(8))))]
+    #[case::escaped_quote(r#"(echo "\")")"#, 0, Some((0, Some(11))))]
+    #[case::backslash_in_single_quotes(r"('\' )", 0, Some((0, Some(5))))]
+    #[case::cursor_in_quotes(r#"echo "(x)""#, 6, None)]
+    fn brackets_in_quotes_are_ignored(
+        #[case] buffer: &str,
+        #[case] cursor: usize,
+        #[case] expected: Option<(usize, Option<usize>)>,
+    ) {
+        assert_eq!(bracket_at_cursor(buffer, cursor), expected);
+    }
+
+    fn styled(buffer: &str) -> StyledText {
+        let mut styled_text = StyledText::new();
+        styled_text.push((Style::new().fg(Color::Green), buffer.to_string()));
+        styled_text
+    }
+
+    #[test]
+    fn both_brackets_of_a_pair_are_styled() {
+        let green = Style::new().fg(Color::Green);
+        let mut styled_text = styled("ls (pwd)");
+
+        highlight_matching_bracket(
+            &mut styled_text,
+            "ls (pwd)",
+            3,
+            Style::new().bold(),
+            Style::new().on(Color::Red),
+        );
+
+        assert_eq!(
+            styled_text.buffer,
+            [
+                (green, "ls ".to_string()),
+                (green.bold(), "(".to_string()),
+                (green, "pwd".to_string()),
+                (green.bold(), ")".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn only_an_unmatched_bracket_is_styled_as_an_error() {
+        let green = Style::new().fg(Color::Green);
+        let mut styled_text = styled("echo (ls");
+
+        highlight_matching_bracket(
+            &mut styled_text,
+            "echo (ls",
+            5,
+            Style::new().bold(),
+            Style::new().on(Color::Red),
+        );
+
+        assert_eq!(
+            styled_text.buffer,
+            [
+                (green, "echo ".to_string()),
+                (green.on(Color::Red), "(".to_string()),
+                (green, "ls".to_string()),
+            ]
+        );
+    }
+}
@@ -1,9 +1,15 @@
 mod example;
+mod matching_bracket;
+mod rainbow;
+mod rule_based;
 mod simple_match;
 
 use crate::StyledText;
 
 pub use example::ExampleHighlighter;
+pub(crate) use matching_bracket::{highlight_matching_bracket, scan_brackets, Bracket};
+pub use rainbow::RainbowBracketHighlighter;
+pub use rule_based::{HighlightPattern, HighlightRule, RuleBasedHighlighter};
 pub use simple_match::SimpleMatchHighlighter;
 /// The syntax highlighting trait. Implementers of this trait will take in the current string and then
 /// return a `StyledText` object, which represents the contents of the original line as styled strings
@@ -0,0 +1,169 @@
+use nu_ansi_term::{Color, Style};
+
+use super::matching_bracket::{scan_brackets, Bracket};
+use crate::{Highlighter, StyleModifier, StyledText};
+
+/// A highlighter coloring brackets by how deeply they are nested, over the
+/// highlighting of another highlighter
+///
+/// Both brackets of a pair get the color of their depth from a palette that
+/// starts over once it runs out. Brackets in quotes are left as the inner
+/// highlighter styled them, closing brackets without an opener get an error
+/// style.
+///
+/// ```rust
+/// use reedline::{ExampleHighlighter, RainbowBracketHighlighter, Reedline};
+///
+/// let highlighter =
+///     RainbowBracketHighlighter::new(Box::new(ExampleHighlighter::new(vec!["ls".into()])));
+/// let line_editor = Reedline::create().with_highlighter(Box::new(highlighter));
+/// ```
+pub struct RainbowBracketHighlighter {
+    inner: Box<dyn Highlighter>,
+    palette: Vec<Color>,
+    unmatched_style: Style,
+}
+
+impl RainbowBracketHighlighter {
+    /// Color the brackets over the highlighting of `inner`
+    pub fn new(inner: Box<dyn Highlighter>) -> Self {
+        RainbowBracketHighlighter {
+            inner,
+            palette: vec![Color::Yellow, Color::Magenta, Color::Cyan],
+            unmatched_style: Style::new().fg(Color::White).on(Color::Red),
+        }
+    }
+
+    /// A builder that sets the colors of the depths, the first one for the
+    /// outermost brackets
+    ///
+    /// With no colors, only the closing brackets without an opener are
+    /// styled.
+    #[must_use]
+    pub fn with_palette(mut self, palette: Vec<Color>) -> Self {
+        self.palette = palette;
+        self
+    }
+
+    /// A builder that sets the style of closing brackets without an opener,
+    /// layered over the style of the inner highlighter
+    #[must_use]
+    pub fn with_unmatched_style(mut self, style: Style) -> Self {
+        self.unmatched_style = style;
+        self
+    }
+
+    fn depth_color(&self, depth: usize) -> Option<Color> {
+        if self.palette.is_empty() {
+            None
+        } else {
+            Some(self.palette[depth % self.palette.len()])
+        }
+    }
+}
+
+impl Highlighter for RainbowBracketHighlighter {
+    fn highlight(&self, line: &str, cursor: usize) -> StyledText {
+        let mut styled_text = self.inner.highlight(line, cursor);
+        scan_brackets(line, |bracket| {
+            let (offset, modifier) = match bracket {
+                Bracket::Open { offset, depth }
+                | Bracket::Close {
+                    offset,
+                    opener: Some(_),
+                    depth,
+                } => match self.depth_color(depth) {
+                    Some(color) => (offset, StyleModifier::new().with_foreground(color)),
+                    None => return,
+                },
+                Bracket::Close { offset, .. } => {
+                    (offset, StyleModifier::from(self.unmatched_style))
+                }
+                Bracket::Unclosed { .. } => return,
+            };
+            styled_text.apply_style_over_range(offset..offset + 1, modifier);
+        });
+        styled_text
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+    use rstest::rstest;
+
+    /// Highlights the whole line in bold
+    struct BoldHighlighter;
+
+    impl Highlighter for BoldHighlighter {
+        fn highlight(&self, line: &str, _cursor: usize) -> StyledText {
+            let mut styled_text = StyledText::new();
+            styled_text.push((Style::new().bold(), line.to_string()));
+            styled_text
+        }
+    }
+
+    /// The line with each bracket replaced by the index of its color in the
+    /// palette, `!` for the error style
+    fn depths(line: &str) -> String {
+        let palette = vec![Color::Fixed(0), Color::Fixed(1), Color::Fixed(2)];
+        let highlighter = RainbowBracketHighlighter::new(Box::new(BoldHighlighter))
+            .with_palette(palette)
+            .with_unmatched_style(Style::new().on(Color::Red));
+        let styled_text = highlighter.highlight(line, 0);
+
+        assert_eq!(styled_text.raw_string(), line);
+        styled_text
+            .buffer
+            .iter()
+            .flat_map(|(style, text)| {
+                let marker = match (style.foreground, style.background) {
+                    (_, Some(Color::Red)) => Some('!'),
+                    (Some(Color::Fixed(index)), _) => Some(char::from(b'0' + index)),
+                    _ => None,
+                };
+                text.chars().map(move |c| marker.unwrap_or(c))
+            })
+            .collect()
+    }
+
+    #[rstest]
+    #[case::flat("ls (pwd)", "ls 0pwd0")]
+    #[case::nested("{ [(a) (b)] }", "0 12a2 2b21 0")]
+    #[case::palette_starts_over("((((x))))", "0120x0210")]
+    #[case::mixed_kinds("f([{}])", "f012210")]
+    #[case::siblings("(a)(b)", "0a00b0")]
+    #[case::unclosed_opener("(a (b)", "0a 1b1")]
+    #[case::unmatched_closer("a) (b)", "a! 0b0")]
+    #[case::mismatched_closer("(a]", "0a!")]
+    #[case::closer_after_mismatch("(a])", "0a!0")]
+    fn brackets_are_colored_by_depth(#[case] line: &str, #[case] expected: &str) {
+        assert_eq!(depths(line), expected);
+    }
+
+    #[rstest]
+    #[case::double_quotes(r#"(echo "(")"#, r#"0echo "("0"#)]
+    #[case::single_quotes("(echo ')')", "0echo ')'0")]
+    #[case::escaped_double_quote(r#"(echo "\")")"#, r#"0echo "\")"0"#)]
+    #[case::backslash_ends_single_quotes(r"(a '\' (b))", r"0a '\' 1b10")]
+    #[case::escaped_bracket(r"(echo \))", r"0echo \)0")]
+    #[case::unclosed_quote(r#"(echo ")"#, r#"0echo ")"#)]
+    fn brackets_in_quotes_keep_their_style(#[case] line: &str, #[case] expected: &str) {
+        assert_eq!(depths(line), expected);
+    }
+
+    #[test]
+    fn colors_are_layered_over_the_inner_style() {
+        let highlighter = RainbowBracketHighlighter::new(Box::new(BoldHighlighter));
+
+        assert_eq!(
+            highlighter.highlight("(x)", 0).buffer,
+            [
+                (Style::new().bold().fg(Color::Yellow), "(".to_string()),
+                (Style::new().bold(), "x".to_string()),
+                (Style::new().bold().fg(Color::Yellow), ")".to_string()),
+            ]
+        );
+    }
+}
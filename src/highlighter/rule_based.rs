@@ -0,0 +1,481 @@
+use std::ops::Range;
+
+use nu_ansi_term::{Color, Style};
+use serde::{Deserialize, Deserializer};
+
+use crate::{Highlighter, StyledText};
+
+/// What a [`HighlightRule`] matches in the line
+///
+/// Words are the whitespace separated tokens of the line, a quoted string or
+/// a character escaped with `\` staying in its word.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum HighlightPattern {
+    /// Every occurrence of the text
+    Literal(String),
+    /// Words equal to the text
+    Word(String),
+    /// Words starting with the text, e.g. `-` for flags
+    Prefix(String),
+    /// Strings in `"` or `'`, up to the end of the line if they aren't closed
+    Quoted,
+    /// Words that are numbers, like `10` or `2.5`
+    Number,
+    /// The first word of the line
+    FirstWord,
+    /// Every match of a regular expression
+    #[cfg(feature = "regex")]
+    Regex(#[serde(deserialize_with = "deserialize_regex")] regex::Regex),
+}
+
+impl HighlightPattern {
+    /// Byte ranges of the matches in `line`, in order
+    fn matches(&self, line: &str) -> Vec<Range<usize>> {
+        match self {
+            HighlightPattern::Literal(text) if text.is_empty() => Vec::new(),
+            HighlightPattern::Literal(text) => line
+                .match_indices(text.as_str())
+                .map(|(start, found)| start..start + found.len())
+                .collect(),
+            HighlightPattern::Word(text) => words(line)
+                .into_iter()
+                .filter(|word| line[word.clone()] == *text)
+                .collect(),
+            HighlightPattern::Prefix(text) => words(line)
+                .into_iter()
+                .filter(|word| line[word.clone()].starts_with(text.as_str()))
+                .collect(),
+            HighlightPattern::Quoted => quoted(line),
+            HighlightPattern::Number => words(line)
+                .into_iter()
+                .filter(|word| is_number(&line[word.clone()]))
+                .collect(),
+            HighlightPattern::FirstWord => words(line).into_iter().take(1).collect(),
+            #[cfg(feature = "regex")]
+            HighlightPattern::Regex(regex) => {
+                regex.find_iter(line).map(|found| found.range()).collect()
+            }
+        }
+    }
+}
+
+#[cfg(feature = "regex")]
+fn deserialize_regex<'de, D: Deserializer<'de>>(deserializer: D) -> Result<regex::Regex, D::Error> {
+    let pattern = String::deserialize(deserializer)?;
+    regex::Regex::new(&pattern).map_err(serde::de::Error::custom)
+}
+
+/// Text matching a pattern and the style it is shown in
+#[derive(Debug, Clone, Deserialize)]
+pub struct HighlightRule {
+    /// What the rule matches
+    pub pattern: HighlightPattern,
+    /// The style of the matched text
+    #[serde(deserialize_with = "deserialize_style")]
+    pub style: Style,
+}
+
+impl HighlightRule {
+    /// Show the matches of `pattern` in `style`
+    pub fn new(pattern: HighlightPattern, style: Style) -> Self {
+        Self { pattern, style }
+    }
+}
+
+/// A [`Style`] as written in a config file, the attributes left out being
+/// off
+#[derive(Default, Deserialize)]
+#[serde(default)]
+struct StyleConfig {
+    foreground: Option<Color>,
+    background: Option<Color>,
+    is_bold: bool,
+    is_dimmed: bool,
+    is_italic: bool,
+    is_underline: bool,
+    is_blink: bool,
+    is_reverse: bool,
+    is_hidden: bool,
+    is_strikethrough: bool,
+}
+
+fn deserialize_style<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Style, D::Error> {
+    let config = StyleConfig::deserialize(deserializer)?;
+    Ok(Style {
+        foreground: config.foreground,
+        background: config.background,
+        is_bold: config.is_bold,
+        is_dimmed: config.is_dimmed,
+        is_italic: config.is_italic,
+        is_underline: config.is_underline,
+        is_blink: config.is_blink,
+        is_reverse: config.is_reverse,
+        is_hidden: config.is_hidden,
+        is_strikethrough: config.is_strikethrough,
+        ..Style::default()
+    })
+}
+
+/// A highlighter declared as an ordered list of rules instead of code
+///
+/// Earlier rules take precedence: a match overlapping text an earlier rule
+/// already styled is left out as a whole. The text no rule matches is shown
+/// in the default style.
+///
+/// The rules can be read from a config file:
+///
+/// ```rust
+/// use reedline::RuleBasedHighlighter;
+///
+/// let highlighter: RuleBasedHighlighter = serde_json::from_str(
+///     r#"{
+///         "rules": [
+///             { "pattern": "quoted", "style": { "foreground": "Green" } },
+///             { "pattern": { "prefix": "-" }, "style": { "foreground": "Cyan" } }
+///         ]
+///     }"#,
+/// )
+/// .unwrap();
+/// ```
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct RuleBasedHighlighter {
+    rules: Vec<HighlightRule>,
+    #[serde(default, deserialize_with = "deserialize_style")]
+    default_style: Style,
+}
+
+impl RuleBasedHighlighter {
+    /// Highlight with `rules`, the first one taking precedence
+    pub fn new(rules: Vec<HighlightRule>) -> Self {
+        Self {
+            rules,
+            default_style: Style::default(),
+        }
+    }
+
+    /// Rules for a shell: strings are green, flags cyan, numbers magenta and
+    /// the command, the first word, is bold
+    pub fn shell() -> Self {
+        Self::new(vec![
+            HighlightRule::new(HighlightPattern::Quoted, Style::new().fg(Color::Green)),
+            HighlightRule::new(HighlightPattern::FirstWord, Style::new().bold()),
+            HighlightRule::new(
+                HighlightPattern::Prefix("-".to_string()),
+                Style::new().fg(Color::Cyan),
+            ),
+            HighlightRule::new(HighlightPattern::Number, Style::new().fg(Color::Magenta)),
+        ])
+    }
+
+    /// A builder that adds a rule, taking precedence after the existing ones
+    #[must_use]
+    pub fn with_rule(mut self, rule: HighlightRule) -> Self {
+        self.rules.push(rule);
+        self
+    }
+
+    /// A builder that sets the style of the text no rule matches
+    #[must_use]
+    pub fn with_default_style(mut self, style: Style) -> Self {
+        self.default_style = style;
+        self
+    }
+}
+
+impl Highlighter for RuleBasedHighlighter {
+    fn highlight(&self, line: &str, _cursor: usize) -> StyledText {
+        // The styled ranges, ordered by where they start
+        let mut styled: Vec<(Range<usize>, Style)> = Vec::new();
+        for rule in &self.rules {
+            for found in rule.pattern.matches(line) {
+                if found.is_empty() {
+                    continue;
+                }
+                let index = styled.partition_point(|(range, _)| range.start < found.start);
+                let overlaps_before = index > 0 && styled[index - 1].0.end > found.start;
+                let overlaps_after = styled
+                    .get(index)
+                    .map_or(false, |(range, _)| range.start < found.end);
+                if !overlaps_before && !overlaps_after {
+                    styled.insert(index, (found, rule.style));
+                }
+            }
+        }
+
+        let mut styled_text = StyledText::new();
+        let mut end = 0;
+        for (range, style) in styled {
+            if range.start > end {
+                styled_text.push((self.default_style, line[end..range.start].to_string()));
+            }
+            end = range.end;
+            styled_text.push((style, line[range].to_string()));
+        }
+        if end < line.len() || line.is_empty() {
+            styled_text.push((self.default_style, line[end..].to_string()));
+        }
+        styled_text
+    }
+}
+
+/// Byte ranges of the whitespace separated words of `line`, keeping quoted
+/// strings and escaped characters in their word
+fn words(line: &str) -> Vec<Range<usize>> {
+    let mut words = Vec::new();
+    let mut start = None;
+    let mut quote = None;
+    let mut escaped = false;
+    for (i, c) in line.char_indices() {
+        if escaped {
+            escaped = false;
+        } else if let Some(open) = quote {
+            match c {
+                '\\' if open == '"' => escaped = true,
+                c if c == open => quote = None,
+                _ => {}
+            }
+        } else if c.is_whitespace() {
+            if let Some(start) = start.take() {
+                words.push(start..i);
+            }
+        } else {
+            start.get_or_insert(i);
+            match c {
+                '\\' => escaped = true,
+                '\'' | '"' => quote = Some(c),
+                _ => {}
+            }
+        }
+    }
+    if let Some(start) = start {
+        words.push(start..line.len());
+    }
+    words
+}
+
+/// Byte ranges of the strings in `"` or `'` in `line`, including the quotes
+fn quoted(line: &str) -> Vec<Range<usize>> {
+    let mut strings = Vec::new();
+    let mut open: Option<(char, usize)> = None;
+    let mut escaped = false;
+    for (i, c) in line.char_indices() {
+        if escaped {
+            escaped = false;
+            continue;
+        }
+        match (open, c) {
+            (Some(('"', _)) | None, '\\') => escaped = true,
+            (Some((quote, start)), c) if c == quote => {
+                strings.push(start..i + c.len_utf8());
+                open = None;
+            }
+            (None, '\'' | '"') => open = Some((c, i)),
+            _ => {}
+        }
+    }
+    if let Some((_, start)) = open {
+        strings.push(start..line.len());
+    }
+    strings
+}
+
+/// Whether `word` is digits with at most one `.` between them
+fn is_number(word: &str) -> bool {
+    let mut parts = word.splitn(2, '.');
+    let all_digits = |part: &str| !part.is_empty() && part.chars().all(|c| c.is_ascii_digit());
+    parts.next().map_or(false, all_digits) && parts.next().map_or(true, all_digits)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+    use rstest::rstest;
+
+    fn bold() -> Style {
+        Style::new().bold()
+    }
+
+    fn fg(color: Color) -> Style {
+        Style::new().fg(color)
+    }
+
+    fn highlight(highlighter: &RuleBasedHighlighter, line: &str) -> Vec<(Style, String)> {
+        let styled_text = highlighter.highlight(line, 0);
+        assert_eq!(styled_text.raw_string(), line);
+        styled_text.buffer
+    }
+
+    #[test]
+    fn shell_rules_style_strings_flags_numbers_and_the_command() {
+        assert_eq!(
+            highlight(
+                &RuleBasedHighlighter::shell(),
+                "grep -n 10 \"a b\" --color=auto"
+            ),
+            [
+                (bold(), "grep".to_string()),
+                (Style::default(), " ".to_string()),
+                (fg(Color::Cyan), "-n".to_string()),
+                (Style::default(), " ".to_string()),
+                (fg(Color::Magenta), "10".to_string()),
+                (Style::default(), " ".to_string()),
+                (fg(Color::Green), "\"a b\"".to_string()),
+                (Style::default(), " ".to_string()),
+                (fg(Color::Cyan), "--color=auto".to_string()),
+            ]
+        );
+    }
+
+    #[rstest]
+    #[case::earlier_rule_wins(&["ls", "ls"], "ls", &[(0, "ls")])]
+    #[case::later_rule_styles_the_rest(&["ls", "l"], "ls l", &[(0, "ls"), (-1, " "), (1, "l")])]
+    #[case::overlapping_match_is_left_out(&["a b", "b c"], "a b c", &[(0, "a b"), (-1, " c")])]
+    #[case::contained_match_is_left_out(&["abc", "b"], "abc b", &[(0, "abc"), (-1, " "), (1, "b")])]
+    #[case::enclosing_match_is_left_out(&["b", "abc"], "abc", &[(-1, "a"), (0, "b"), (-1, "c")])]
+    #[case::adjacent_matches_are_kept(&["ab", "cd"], "abcd", &[(0, "ab"), (1, "cd")])]
+    fn earlier_rules_take_precedence(
+        #[case] literals: &[&str],
+        #[case] line: &str,
+        #[case] expected: &[(i8, &str)],
+    ) {
+        let palette = [Color::Red, Color::Blue];
+        let highlighter = RuleBasedHighlighter::new(
+            literals
+                .iter()
+                .zip(palette)
+                .map(|(literal, color)| {
+                    HighlightRule::new(HighlightPattern::Literal(literal.to_string()), fg(color))
+                })
+                .collect(),
+        )
+        .with_default_style(Style::new().dimmed());
+
+        let expected: Vec<_> = expected
+            .iter()
+            .map(|&(rule, text)| {
+                let style =
+                    usize::try_from(rule).map_or(Style::new().dimmed(), |rule| fg(palette[rule]));
+                (style, text.to_string())
+            })
+            .collect();
+        assert_eq!(highlight(&highlighter, line), expected);
+    }
+
+    #[rstest]
+    #[case::quoted_flag("echo \"-x\" -y", &[("\"-x\"", Color::Green), ("-y", Color::Cyan)])]
+    #[case::flag_with_quoted_value("ls --name=\"a b\"", &[("\"a b\"", Color::Green)])]
+    #[case::unclosed_quote("echo 'a -b", &[("'a -b", Color::Green)])]
+    #[case::escaped_quote(r#"echo \"-x 1"#, &[("1", Color::Magenta)])]
+    #[case::escaped_quote_in_string(r#"echo "a\"b" 1"#, &[(r#""a\"b""#, Color::Green), ("1", Color::Magenta)])]
+    #[case::not_a_number("head 1.2.3 .5 7.", &[])]
+    fn strings_take_precedence_over_words(#[case] line: &str, #[case] expected: &[(&str, Color)]) {
+        let colored: Vec<_> = highlight(&RuleBasedHighlighter::shell(), line)
+            .into_iter()
+            .filter_map(|(style, text)| style.foreground.map(|color| (text, color)))
+            .collect();
+        let expected: Vec<_> = expected
+            .iter()
+            .map(|&(text, color)| (text.to_string(), color))
+            .collect();
+        assert_eq!(colored, expected);
+    }
+
+    #[test]
+    fn multibyte_text_is_split_on_char_boundaries() {
+        let highlighter = RuleBasedHighlighter::shell()
+            .with_rule(HighlightRule::new(
+                HighlightPattern::Literal("ü".to_string()),
+                fg(Color::Red),
+            ))
+            .with_rule(HighlightRule::new(
+                HighlightPattern::Word("日本".to_string()),
+                fg(Color::Blue),
+            ));
+
+        assert_eq!(
+            highlight(&highlighter, "éché -ñ 'ö ü' ü 日本 2"),
+            [
+                (bold(), "éché".to_string()),
+                (Style::default(), " ".to_string()),
+                (fg(Color::Cyan), "-ñ".to_string()),
+                (Style::default(), " ".to_string()),
+                (fg(Color::Green), "'ö ü'".to_string()),
+                (Style::default(), " ".to_string()),
+                (fg(Color::Red), "ü".to_string()),
+                (Style::default(), " ".to_string()),
+                (fg(Color::Blue), "日本".to_string()),
+                (Style::default(), " ".to_string()),
+                (fg(Color::Magenta), "2".to_string()),
+            ]
+        );
+    }
+
+    #[rstest]
+    #[case::empty("")]
+    #[case::only_whitespace("   ")]
+    fn lines_without_matches_keep_the_default_style(#[case] line: &str) {
+        let highlighter = RuleBasedHighlighter::shell().with_default_style(Style::new().dimmed());
+
+        assert_eq!(
+            highlight(&highlighter, line),
+            [(Style::new().dimmed(), line.to_string())]
+        );
+    }
+
+    #[test]
+    fn rules_are_read_from_config() {
+        let highlighter: RuleBasedHighlighter = serde_json::from_str(
+            r#"{
+                "rules": [
+                    { "pattern": { "word": "sudo" }, "style": { "foreground": "Red", "is_bold": true } },
+                    { "pattern": "first_word", "style": { "is_underline": true } },
+                    { "pattern": { "literal": "|" }, "style": { "foreground": { "Fixed": 208 } } }
+                ],
+                "default_style": { "foreground": "LightGray" }
+            }"#,
+        )
+        .unwrap();
+
+        assert_eq!(
+            highlight(&highlighter, "sudo ls | wc"),
+            [
+                (fg(Color::Red).bold(), "sudo".to_string()),
+                (fg(Color::LightGray), " ls ".to_string()),
+                (fg(Color::Fixed(208)), "|".to_string()),
+                (fg(Color::LightGray), " wc".to_string()),
+            ]
+        );
+    }
+
+    #[cfg(feature = "regex")]
+    #[test]
+    fn regex_rules_match_anywhere() {
+        let highlighter: RuleBasedHighlighter = serde_json::from_str(
+            r#"{ "rules": [{ "pattern": { "regex": "\\$\\w+" }, "style": { "foreground": "Purple" } }] }"#,
+        )
+        .unwrap();
+
+        assert_eq!(
+            highlight(&highlighter, "echo $HOME/$ü"),
+            [
+                (Style::default(), "echo ".to_string()),
+                (fg(Color::Purple), "$HOME".to_string()),
+                (Style::default(), "/".to_string()),
+                (fg(Color::Purple), "$ü".to_string()),
+            ]
+        );
+    }
+
+    #[cfg(feature = "regex")]
+    #[test]
+    fn invalid_regexes_are_config_errors() {
+        let error = serde_json::from_str::<RuleBasedHighlighter>(
+            r#"{ "rules": [{ "pattern": { "regex": "(" }, "style": {} }] }"#,
+        )
+        .unwrap_err();
+
+        assert!(error.to_string().contains("regex parse error"));
+    }
+}
@@ -1,6 +1,8 @@
 use crate::highlighter::Highlighter;
-use crate::StyledText;
+use crate::history::fuzzy_match_indices;
+use crate::{StyleModifier, StyledText};
 use nu_ansi_term::{Color, Style};
+use std::ops::Range;
 
 /// Highlight all matches for a given search string in a line
 ///
@@ -8,10 +10,19 @@ use nu_ansi_term::{Color, Style};
 ///
 /// - non-matching text: Default style
 /// - matching text: Green foreground color
+///
+/// With fuzzy matching the characters of the most compact in-order match of
+/// the search string are highlighted instead.
+///
+/// The match styles are layered over the neutral style. With a current match
+/// style, the last match, the one closest to the end of the line where the
+/// cursor lands, is layered over once more in it.
 pub struct SimpleMatchHighlighter {
     neutral_style: Style,
     match_style: Style,
+    current_match_style: Option<Style>,
     query: String,
+    fuzzy: bool,
 }
 
 impl Default for SimpleMatchHighlighter {
@@ -19,7 +30,9 @@ impl Default for SimpleMatchHighlighter {
         Self {
             neutral_style: Style::default(),
             match_style: Style::new().fg(Color::Green),
+            current_match_style: None,
             query: String::default(),
+            fuzzy: false,
         }
     }
 }
@@ -27,20 +40,36 @@ impl Default for SimpleMatchHighlighter {
 impl Highlighter for SimpleMatchHighlighter {
     fn highlight(&self, line: &str, _cursor: usize) -> StyledText {
         let mut styled_text = StyledText::new();
+        styled_text.push((self.neutral_style, line.to_owned()));
         if self.query.is_empty() {
-            styled_text.push((self.neutral_style, line.to_owned()));
-        } else {
-            let mut next_idx: usize = 0;
+            return styled_text;
+        }
 
-            for (idx, mat) in line.match_indices(&self.query) {
-                if idx != next_idx {
-                    styled_text.push((self.neutral_style, line[next_idx..idx].to_owned()));
-                }
-                styled_text.push((self.match_style, mat.to_owned()));
-                next_idx = idx + mat.len();
+        // Each match as the ranges it styles, a fuzzy match is a single one
+        let matches: Vec<Vec<Range<usize>>> = if self.fuzzy {
+            let ranges: Vec<_> = fuzzy_match_indices(&self.query, line)
+                .unwrap_or_default()
+                .into_iter()
+                .map(|idx| idx..idx + line[idx..].chars().next().map_or(0, char::len_utf8))
+                .collect();
+            if ranges.is_empty() {
+                Vec::new()
+            } else {
+                vec![ranges]
             }
-            if next_idx != line.len() {
-                styled_text.push((self.neutral_style, line[next_idx..].to_owned()));
+        } else {
+            line.match_indices(&self.query)
+                .map(|(idx, mat)| std::iter::once(idx..idx + mat.len()).collect())
+                .collect()
+        };
+
+        for range in matches.iter().flatten() {
+            styled_text
+                .apply_style_over_range(range.clone(), StyleModifier::from(self.match_style));
+        }
+        if let (Some(style), Some(current)) = (self.current_match_style, matches.last()) {
+            for range in current {
+                styled_text.apply_style_over_range(range.clone(), StyleModifier::from(style));
             }
         }
         styled_text
@@ -63,6 +92,13 @@ impl SimpleMatchHighlighter {
         self
     }
 
+    /// Highlight the characters of the query in order instead of exact matches
+    #[must_use]
+    pub fn with_fuzzy_matching(mut self, fuzzy: bool) -> Self {
+        self.fuzzy = fuzzy;
+        self
+    }
+
     /// Set style for the matches found
     #[must_use]
     pub fn with_match_style(mut self, match_style: Style) -> Self {
@@ -70,6 +106,14 @@ impl SimpleMatchHighlighter {
         self
     }
 
+    /// Set the style layered over the last match, the one the cursor lands on
+    /// when the line is taken
+    #[must_use]
+    pub fn with_current_match_style(mut self, current_match_style: Style) -> Self {
+        self.current_match_style = Some(current_match_style);
+        self
+    }
+
     /// Set style for the text that does not match the query
     #[must_use]
     pub fn with_neutral_style(mut self, neutral_style: Style) -> Self {
@@ -77,3 +121,137 @@ impl SimpleMatchHighlighter {
         self
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::painting::{Painter, RenderSnapshot};
+    use pretty_assertions::assert_eq;
+    use rstest::rstest;
+
+    #[test]
+    fn fuzzy_highlights_matched_characters() {
+        let highlighter = SimpleMatchHighlighter::new("gcö".to_string()).with_fuzzy_matching(true);
+        let match_style = Style::new().fg(Color::Green);
+
+        let styled = highlighter.highlight("git cöm", 0);
+
+        assert_eq!(
+            styled.buffer,
+            vec![
+                (match_style, "g".to_string()),
+                (Style::default(), "it ".to_string()),
+                (match_style, "c".to_string()),
+                (match_style, "ö".to_string()),
+                (Style::default(), "m".to_string()),
+            ]
+        );
+    }
+
+    /// Marks the text in the match style with `[]`, in the current match
+    /// style with `{}`
+    fn marked(styled: &StyledText) -> String {
+        styled
+            .buffer
+            .iter()
+            .map(|(style, text)| match (style.is_bold, style.foreground) {
+                (true, _) => format!("{{{text}}}"),
+                (false, Some(Color::Green)) => format!("[{text}]"),
+                _ => text.clone(),
+            })
+            .collect()
+    }
+
+    #[rstest]
+    #[case::every_match("git add . && git commit", "git", "[git] add . && {git} commit")]
+    #[case::single_match("ls -la", "la", "ls -{la}")]
+    #[case::adjacent_matches("aaaa", "aa", "[aa]{aa}")]
+    #[case::no_match("ls -la", "cd", "ls -la")]
+    #[case::multibyte("é cd é", "é", "[é] cd {é}")]
+    #[case::fuzzy_is_one_match("git cöm", "gcö", "{g}it {c}{ö}m")]
+    fn the_last_match_is_current(#[case] line: &str, #[case] query: &str, #[case] expected: &str) {
+        let highlighter = SimpleMatchHighlighter::new(query.to_string())
+            .with_fuzzy_matching(query == "gcö")
+            .with_current_match_style(Style::new().bold());
+
+        assert_eq!(marked(&highlighter.highlight(line, 0)), expected);
+    }
+
+    #[test]
+    fn matches_are_layered_over_the_neutral_style() {
+        let neutral = Style::new().on(Color::Black);
+        let highlighter = SimpleMatchHighlighter::new("git".to_string())
+            .with_neutral_style(neutral)
+            .with_current_match_style(Style::new().bold().underline());
+
+        assert_eq!(
+            highlighter.highlight("git && git", 0).buffer,
+            [
+                (neutral.fg(Color::Green), "git".to_string()),
+                (neutral, " && ".to_string()),
+                (
+                    neutral.fg(Color::Green).bold().underline(),
+                    "git".to_string()
+                ),
+            ]
+        );
+    }
+
+    /// The rendered rows of `styled` as the painter wraps it on a screen
+    /// `width` columns wide, after an empty prompt
+    fn soft_wrapped(styled: &StyledText, width: u16) -> Vec<String> {
+        let line = styled.raw_string();
+        let snapshot = RenderSnapshot {
+            screen_width: width,
+            screen_height: 10,
+            prompt_start_row: 0,
+            prompt_height: 1,
+            large_buffer: false,
+            prompt_str_left: "".to_string(),
+            prompt_indicator: "".to_string(),
+            before_cursor: line.clone(),
+            after_cursor: "".to_string(),
+            first_buffer_col: 0,
+            menu_active: false,
+            menu_start_row: None,
+            large_buffer_extra_rows_after_prompt: None,
+            large_buffer_offset: None,
+            right_prompt: None,
+        };
+        let painter = Painter::new(std::io::BufWriter::new(std::io::stderr()));
+
+        let mut row_starts: Vec<usize> = (0..snapshot.screen_height)
+            .map_while(|row| painter.screen_to_buffer_offset(&snapshot, 0, row))
+            .collect();
+        row_starts.push(line.len());
+
+        row_starts
+            .windows(2)
+            .map(|row| {
+                let mut rest = styled.clone();
+                let parts = rest.split_range(row[0]..row[1]);
+                StyledText {
+                    buffer: rest.buffer.drain(parts).collect(),
+                }
+                .render_simple()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn matches_split_across_a_soft_wrap_keep_their_style() {
+        let highlighter = SimpleMatchHighlighter::new("commit".to_string())
+            .with_current_match_style(Style::new().fg(Color::Green).bold());
+        let styled = highlighter.highlight("git commit && git commit --amend", 0);
+
+        assert_eq!(
+            soft_wrapped(&styled, 8),
+            [
+                "git \u{1b}[32mcomm\u{1b}[0m",
+                "\u{1b}[32mit\u{1b}[0m && gi",
+                "t \u{1b}[1;32mcommit\u{1b}[0m",
+                " --amend",
+            ]
+        );
+    }
+}
@@ -0,0 +1,307 @@
+use nu_ansi_term::Style;
+use unicode_segmentation::UnicodeSegmentation;
+
+use super::is_whitespace_str;
+use crate::Hinter;
+
+/// Where accepting a hint token by token stops, see
+/// [`Reedline::with_hint_token_boundary`](crate::Reedline::with_hint_token_boundary)
+///
+/// The line is split with the hint after the buffer, so a quote opened in
+/// the buffer ends in the hint.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum HintTokenBoundary {
+    /// Words, as the hinter splits them with
+    /// [`Hinter::next_hint_token`](crate::Hinter::next_hint_token)
+    #[default]
+    Words,
+    /// Shell tokens separated by whitespace, a quoted string or a space
+    /// escaped with `\` staying in its token
+    ShellTokens,
+    /// Shell tokens, paths among them split after each `/`
+    PathSegments,
+}
+
+impl HintTokenBoundary {
+    /// Byte ranges of the tokens of `line`
+    fn tokens(self, line: &str) -> Vec<(usize, usize)> {
+        match self {
+            HintTokenBoundary::Words => line
+                .split_word_bound_indices()
+                .filter(|(_, word)| !is_whitespace_str(word))
+                .map(|(start, word)| (start, start + word.len()))
+                .collect(),
+            HintTokenBoundary::ShellTokens => shell_tokens(line, false),
+            HintTokenBoundary::PathSegments => shell_tokens(line, true),
+        }
+    }
+
+    /// The start of `hint` up to the end of the next token of `buffer`
+    /// followed by `hint`, the rest of the token under the cursor if the
+    /// buffer ends inside one
+    ///
+    /// With `under_cursor`, only the rest of the token under the cursor is
+    /// taken, nothing if the buffer doesn't end inside a token.
+    pub(crate) fn hint_part(self, buffer: &str, hint: &str, under_cursor: bool) -> String {
+        let cursor = buffer.len();
+        let line = format!("{buffer}{hint}");
+        self.tokens(&line)
+            .into_iter()
+            .find(|&(start, end)| end > cursor && (!under_cursor || start < cursor))
+            .map_or_else(String::new, |(_, end)| line[cursor..end].to_string())
+    }
+
+    /// The part of the current hint of `hinter` the next accepted token
+    /// inserts after `buffer`
+    ///
+    /// With [`HintTokenBoundary::Words`], the hinter splits the hint itself.
+    pub(crate) fn next_part(self, hinter: &dyn Hinter, buffer: &str, under_cursor: bool) -> String {
+        if self == HintTokenBoundary::Words && !under_cursor {
+            hinter.next_hint_token()
+        } else {
+            self.hint_part(buffer, &hinter.complete_hint(), under_cursor)
+        }
+    }
+
+    /// The hint to show after `buffer`, the part the next accepted token
+    /// inserts in `next_token_style`
+    ///
+    /// `hint` as formatted by the hinter is kept without ANSI coloring or if
+    /// the hinter has no [`Hinter::styled_hint`].
+    pub(crate) fn render_hint(
+        self,
+        hinter: &dyn Hinter,
+        hint: String,
+        buffer: &str,
+        next_token_style: Option<Style>,
+        use_ansi_coloring: bool,
+    ) -> String {
+        match hinter.styled_hint() {
+            Some(mut styled_hint) if use_ansi_coloring => {
+                if let Some(style) = next_token_style {
+                    let next_part = self.next_part(hinter, buffer, false);
+                    styled_hint.style_range(0, next_part.len(), style);
+                }
+                styled_hint.render_simple()
+            }
+            _ => hint,
+        }
+    }
+}
+
+/// Byte ranges of the whitespace separated tokens of `line`, keeping quoted
+/// strings and escaped characters in their token
+///
+/// With `split_paths`, a token also ends after each `/` outside of quotes.
+fn shell_tokens(line: &str, split_paths: bool) -> Vec<(usize, usize)> {
+    let mut tokens = Vec::new();
+    let mut start = None;
+    let mut quote = None;
+    let mut escaped = false;
+    for (i, c) in line.char_indices() {
+        if escaped {
+            escaped = false;
+            continue;
+        }
+        if let Some(open) = quote {
+            match c {
+                '\\' if open == '"' => escaped = true,
+                c if c == open => quote = None,
+                _ => {}
+            }
+            continue;
+        }
+        if c.is_whitespace() {
+            if let Some(start) = start.take() {
+                tokens.push((start, i));
+            }
+            continue;
+        }
+        let token_start = *start.get_or_insert(i);
+        match c {
+            '\\' => escaped = true,
+            '\'' | '"' => quote = Some(c),
+            '/' if split_paths => {
+                tokens.push((token_start, i + 1));
+                start = None;
+            }
+            _ => {}
+        }
+    }
+    if let Some(start) = start {
+        tokens.push((start, line.len()));
+    }
+    tokens
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{DefaultHinter, FileBackedHistory, History, HistoryItem};
+    use nu_ansi_term::Color;
+    use pretty_assertions::assert_eq;
+    use rstest::rstest;
+
+    #[rstest]
+    #[case::words(HintTokenBoundary::Words, "git commit -m \"", "fix: thing\"", "fix")]
+    #[case::whole_quote(
+        HintTokenBoundary::ShellTokens,
+        "git commit -m ",
+        "\"fix: thing\" --amend",
+        "\"fix: thing\""
+    )]
+    #[case::rest_of_quote(
+        HintTokenBoundary::ShellTokens,
+        "git commit -m \"fi",
+        "x: thing\" --amend",
+        "x: thing\""
+    )]
+    #[case::single_quotes(HintTokenBoundary::ShellTokens, "echo ", "'a b' c", "'a b'")]
+    #[case::escaped_quote(HintTokenBoundary::ShellTokens, "echo \"a \\\"", "b c\" d", "b c\"")]
+    #[case::escaped_space(
+        HintTokenBoundary::ShellTokens,
+        "cd ",
+        "My\\ Documents/notes -l",
+        "My\\ Documents/notes"
+    )]
+    #[case::first_segment(HintTokenBoundary::PathSegments, "cd ", "~/My\\ Documents/", "~/")]
+    #[case::escaped_segment(
+        HintTokenBoundary::PathSegments,
+        "cd ~/",
+        "My\\ Documents/notes",
+        "My\\ Documents/"
+    )]
+    #[case::quoted_path(
+        HintTokenBoundary::PathSegments,
+        "cd ",
+        "\"~/My Documents/notes\"",
+        "\"~/My Documents/notes\""
+    )]
+    fn next_token_of_the_hint(
+        #[case] boundary: HintTokenBoundary,
+        #[case] buffer: &str,
+        #[case] hint: &str,
+        #[case] expected: &str,
+    ) {
+        assert_eq!(boundary.hint_part(buffer, hint, false), expected);
+    }
+
+    #[rstest]
+    #[case::inside(HintTokenBoundary::ShellTokens, "ls -l", "a src", "a")]
+    #[case::inside_quote(HintTokenBoundary::ShellTokens, "echo \"a", " b\" c", " b\"")]
+    #[case::after(HintTokenBoundary::ShellTokens, "ls ", "-la src", "")]
+    #[case::end_of_token(HintTokenBoundary::ShellTokens, "ls", " -la", "")]
+    #[case::segment(
+        HintTokenBoundary::PathSegments,
+        "cd ~/pro",
+        "jects/reedline",
+        "jects/"
+    )]
+    fn rest_of_the_token_under_the_cursor(
+        #[case] boundary: HintTokenBoundary,
+        #[case] buffer: &str,
+        #[case] hint: &str,
+        #[case] expected: &str,
+    ) {
+        assert_eq!(boundary.hint_part(buffer, hint, true), expected);
+    }
+
+    #[test]
+    fn long_paths_are_accepted_segment_by_segment() {
+        let line = "vim ~/projects/reedline/src/menu/columnar_menu.rs";
+        let mut buffer = "vim ".to_string();
+        let mut parts = Vec::new();
+        while buffer.len() < line.len() {
+            let part =
+                HintTokenBoundary::PathSegments.hint_part(&buffer, &line[buffer.len()..], false);
+            buffer.push_str(&part);
+            parts.push(part);
+        }
+
+        assert_eq!(
+            parts,
+            [
+                "~/",
+                "projects/",
+                "reedline/",
+                "src/",
+                "menu/",
+                "columnar_menu.rs"
+            ]
+        );
+    }
+
+    fn hinter_for(buffer: &str, command_line: &str) -> (DefaultHinter, String) {
+        let mut history = FileBackedHistory::default();
+        history
+            .save(HistoryItem::from_command_line(command_line))
+            .unwrap();
+        let mut hinter = DefaultHinter::default().with_style(Style::new().fg(Color::DarkGray));
+        let hint = hinter.handle(buffer, buffer.len(), &history, true, "/");
+        (hinter, hint)
+    }
+
+    #[rstest]
+    #[case::words(
+        HintTokenBoundary::Words,
+        "\u{1b}[97m commit\u{1b}[0m\u{1b}[90m --amend\u{1b}[0m"
+    )]
+    #[case::shell_tokens(
+        HintTokenBoundary::ShellTokens,
+        "\u{1b}[97m commit\u{1b}[0m\u{1b}[90m --amend\u{1b}[0m"
+    )]
+    fn next_token_of_the_hint_is_styled(
+        #[case] boundary: HintTokenBoundary,
+        #[case] expected: &str,
+    ) {
+        let (hinter, hint) = hinter_for("git", "git commit --amend");
+
+        assert_eq!(
+            boundary.render_hint(
+                &hinter,
+                hint,
+                "git",
+                Some(Style::new().fg(Color::LightGray)),
+                true
+            ),
+            expected
+        );
+    }
+
+    #[test]
+    fn rest_of_the_token_under_the_cursor_is_styled() {
+        let (hinter, hint) = hinter_for("git com", "git commit --amend");
+
+        assert_eq!(
+            HintTokenBoundary::ShellTokens.render_hint(
+                &hinter,
+                hint,
+                "git com",
+                Some(Style::new().fg(Color::LightGray)),
+                true
+            ),
+            "\u{1b}[97mmit\u{1b}[0m\u{1b}[90m --amend\u{1b}[0m"
+        );
+    }
+
+    #[test]
+    fn hint_is_kept_without_a_next_token_style_or_coloring() {
+        let (hinter, hint) = hinter_for("git", "git commit --amend");
+        assert_eq!(hint, "\u{1b}[90m commit --amend\u{1b}[0m");
+
+        assert_eq!(
+            HintTokenBoundary::Words.render_hint(&hinter, hint.clone(), "git", None, true),
+            hint
+        );
+        assert_eq!(
+            HintTokenBoundary::Words.render_hint(
+                &hinter,
+                " commit --amend".to_string(),
+                "git",
+                Some(Style::new().fg(Color::LightGray)),
+                false
+            ),
+            " commit --amend"
+        );
+    }
+}
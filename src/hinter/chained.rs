@@ -0,0 +1,223 @@
+use crate::{Hinter, History, StyledText};
+
+/// A hinter asking several hinters in turn, showing the first hint found
+///
+/// Accepting the hint, fully or token by token, goes to the hinter that
+/// produced it. A typical chain prefers the commands of the current session,
+/// then those run in the current directory, then the whole history:
+///
+/// ```rust
+/// use reedline::{ChainedHinter, DefaultHinter};
+///
+/// let hinter = ChainedHinter::default()
+///     .with_hinter(Box::new(DefaultHinter::session_scoped()))
+///     .with_hinter(Box::new(DefaultHinter::cwd_scoped()))
+///     .with_hinter(Box::new(DefaultHinter::default()));
+/// ```
+#[derive(Default)]
+pub struct ChainedHinter {
+    hinters: Vec<Box<dyn Hinter>>,
+    /// Index of the hinter that produced the current hint
+    active: Option<usize>,
+}
+
+impl ChainedHinter {
+    /// Create a chain asking `hinters` in order
+    pub fn new(hinters: Vec<Box<dyn Hinter>>) -> Self {
+        ChainedHinter {
+            hinters,
+            active: None,
+        }
+    }
+
+    /// A builder that adds `hinter` at the end of the chain
+    #[must_use]
+    pub fn with_hinter(mut self, hinter: Box<dyn Hinter>) -> Self {
+        self.hinters.push(hinter);
+        self
+    }
+
+    fn active_hinter(&self) -> Option<&dyn Hinter> {
+        self.active.map(|index| self.hinters[index].as_ref())
+    }
+}
+
+impl Hinter for ChainedHinter {
+    fn handle(
+        &mut self,
+        line: &str,
+        pos: usize,
+        history: &dyn History,
+        use_ansi_coloring: bool,
+        cwd: &str,
+    ) -> String {
+        self.active = None;
+        for (index, hinter) in self.hinters.iter_mut().enumerate() {
+            let hint = hinter.handle(line, pos, history, use_ansi_coloring, cwd);
+            if !hinter.complete_hint().is_empty() {
+                self.active = Some(index);
+                return hint;
+            }
+        }
+        String::new()
+    }
+
+    fn complete_hint(&self) -> String {
+        self.active_hinter()
+            .map_or_else(String::new, Hinter::complete_hint)
+    }
+
+    fn next_hint_token(&self) -> String {
+        self.active_hinter()
+            .map_or_else(String::new, Hinter::next_hint_token)
+    }
+
+    fn styled_hint(&self) -> Option<StyledText> {
+        self.active_hinter().and_then(Hinter::styled_hint)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{FileBackedHistory, HistoryItem};
+    use pretty_assertions::assert_eq;
+
+    /// Hints `hint` for lines starting with `prefix`, accepting its words one by one
+    struct FixedHinter {
+        prefix: &'static str,
+        hint: &'static str,
+        current: String,
+    }
+
+    impl FixedHinter {
+        fn boxed(prefix: &'static str, hint: &'static str) -> Box<dyn Hinter> {
+            Box::new(FixedHinter {
+                prefix,
+                hint,
+                current: String::new(),
+            })
+        }
+    }
+
+    impl Hinter for FixedHinter {
+        fn handle(&mut self, line: &str, _: usize, _: &dyn History, _: bool, _: &str) -> String {
+            self.current = if line.starts_with(self.prefix) {
+                self.hint.to_string()
+            } else {
+                String::new()
+            };
+            format!("<{}>", self.current)
+        }
+
+        fn complete_hint(&self) -> String {
+            self.current.clone()
+        }
+
+        fn next_hint_token(&self) -> String {
+            self.current
+                .split_inclusive(' ')
+                .next()
+                .unwrap_or_default()
+                .to_string()
+        }
+    }
+
+    #[test]
+    fn the_first_hinter_with_a_hint_wins() {
+        let history = FileBackedHistory::default();
+        let mut hinter = ChainedHinter::new(vec![
+            FixedHinter::boxed("git", " status --short"),
+            FixedHinter::boxed("g", "rep -r todo"),
+        ]);
+
+        assert_eq!(
+            hinter.handle("git", 3, &history, false, "/"),
+            "< status --short>"
+        );
+        assert_eq!(hinter.complete_hint(), " status --short");
+        assert_eq!(hinter.next_hint_token(), " ");
+
+        assert_eq!(hinter.handle("g", 1, &history, false, "/"), "<rep -r todo>");
+        assert_eq!(hinter.complete_hint(), "rep -r todo");
+        assert_eq!(hinter.next_hint_token(), "rep ");
+    }
+
+    #[test]
+    fn no_hint_when_no_hinter_has_one() {
+        let history = FileBackedHistory::default();
+        let mut hinter = ChainedHinter::default().with_hinter(FixedHinter::boxed("g", "it"));
+
+        hinter.handle("g", 1, &history, false, "/");
+        assert_eq!(hinter.handle("ls", 2, &history, false, "/"), "");
+        assert_eq!(hinter.complete_hint(), "");
+        assert_eq!(hinter.next_hint_token(), "");
+        assert_eq!(
+            ChainedHinter::default().handle("ls", 2, &history, false, "/"),
+            ""
+        );
+    }
+
+    #[test]
+    fn history_hinters_fall_back_to_the_next_one() {
+        let mut history = FileBackedHistory::default();
+        history
+            .save(HistoryItem::from_command_line("cargo build"))
+            .unwrap();
+        let mut hinter = ChainedHinter::default()
+            .with_hinter(FixedHinter::boxed("cargo t", "est"))
+            .with_hinter(Box::new(crate::DefaultHinter::default()));
+
+        assert_eq!(hinter.handle("cargo b", 7, &history, false, "/"), "uild");
+        assert_eq!(hinter.handle("cargo t", 7, &history, false, "/"), "<est>");
+    }
+
+    #[cfg(any(feature = "sqlite", feature = "sqlite-dynlib"))]
+    #[test]
+    fn scoped_history_hinters_prefer_the_session_then_the_directory() {
+        use crate::{DefaultHinter, HistorySessionId, SqliteBackedHistory};
+
+        let dir = tempfile::tempdir().unwrap();
+        let mut history = SqliteBackedHistory::with_file(
+            dir.path().join("history.sqlite3"),
+            Some(HistorySessionId::new(2)),
+            None,
+        )
+        .unwrap();
+        for (command_line, session, cwd) in [
+            ("cargo test --doc", 1, "/work/app"),
+            ("cargo build --release", 1, "/home"),
+            ("cargo run", 1, "/work/app"),
+            ("cargo check", 2, "/home"),
+            ("git push", 1, "/home"),
+        ] {
+            history
+                .save(HistoryItem {
+                    session_id: Some(HistorySessionId::new(session)),
+                    cwd: Some(cwd.to_string()),
+                    ..HistoryItem::from_command_line(command_line)
+                })
+                .unwrap();
+        }
+        let mut hinter = ChainedHinter::default()
+            .with_hinter(Box::new(DefaultHinter::session_scoped()))
+            .with_hinter(Box::new(DefaultHinter::cwd_scoped()))
+            .with_hinter(Box::new(DefaultHinter::default()));
+
+        let mut hint = |line: &str| {
+            hinter.handle(line, line.len(), &history, false, "/work/app");
+            (hinter.complete_hint(), hinter.next_hint_token())
+        };
+        assert_eq!(hint("cargo "), ("check".to_string(), "check".to_string()));
+        assert_eq!(hint("cargo r"), ("un".to_string(), "un".to_string()));
+        assert_eq!(
+            hint("cargo t"),
+            ("est --doc".to_string(), "est".to_string())
+        );
+        assert_eq!(
+            hint("cargo b"),
+            ("uild --release".to_string(), "uild".to_string())
+        );
+        assert_eq!(hint("git "), ("push".to_string(), "push".to_string()));
+    }
+}
@@ -2,7 +2,7 @@ use crate::{
     hinter::get_first_token,
     history::SearchQuery,
     result::{ReedlineError, ReedlineErrorVariants::HistoryFeatureUnsupported},
-    Hinter, History,
+    Hinter, History, StyledText,
 };
 use nu_ansi_term::{Color, Style};
 
@@ -82,6 +82,12 @@ impl Hinter for CwdAwareHinter {
     fn next_hint_token(&self) -> String {
         get_first_token(&self.current_hint)
     }
+
+    fn styled_hint(&self) -> Option<StyledText> {
+        Some(StyledText {
+            buffer: vec![(self.style, self.current_hint.clone())],
+        })
+    }
 }
 
 impl Default for CwdAwareHinter {
@@ -1,4 +1,9 @@
-use crate::{hinter::get_first_token, history::SearchQuery, Hinter, History};
+use crate::{
+    hinter::get_first_token,
+    history::{CommandLineSearch, SearchFilter, SearchQuery},
+    result::{ReedlineError, ReedlineErrorVariants::HistoryFeatureUnsupported},
+    Hinter, History, HistoryNavigationScope, StyledText,
+};
 use nu_ansi_term::{Color, Style};
 
 /// A hinter that uses the completions or the history to show a hint to the user
@@ -6,6 +11,7 @@ pub struct DefaultHinter {
     style: Style,
     current_hint: String,
     min_chars: usize,
+    scope: HistoryNavigationScope,
 }
 
 impl Hinter for DefaultHinter {
@@ -15,14 +21,27 @@ impl Hinter for DefaultHinter {
         #[allow(unused_variables)] pos: usize,
         history: &dyn History,
         use_ansi_coloring: bool,
-        _cwd: &str,
+        cwd: &str,
     ) -> String {
         self.current_hint = if line.chars().count() >= self.min_chars {
+            let filter = SearchFilter::from_text_search(
+                CommandLineSearch::Prefix(line.to_string()),
+                history.session(),
+            );
             history
-                .search(SearchQuery::last_with_prefix(
-                    line.to_string(),
-                    history.session(),
+                .search(SearchQuery::last_with_search(
+                    self.scope.restrict(filter, Some(cwd)),
                 ))
+                .or_else(|err| {
+                    if let ReedlineError(HistoryFeatureUnsupported { .. }) = err {
+                        history.search(SearchQuery::last_with_prefix(
+                            line.to_string(),
+                            history.session(),
+                        ))
+                    } else {
+                        Err(err)
+                    }
+                })
                 .expect("todo: error handling")
                 .first()
                 .map_or_else(String::new, |entry| {
@@ -50,6 +69,12 @@ impl Hinter for DefaultHinter {
     fn next_hint_token(&self) -> String {
         get_first_token(&self.current_hint)
     }
+
+    fn styled_hint(&self) -> Option<StyledText> {
+        Some(StyledText {
+            buffer: vec![(self.style, self.current_hint.clone())],
+        })
+    }
 }
 
 impl Default for DefaultHinter {
@@ -58,11 +83,29 @@ impl Default for DefaultHinter {
             style: Style::new().fg(Color::LightGray),
             current_hint: String::new(),
             min_chars: 1,
+            scope: HistoryNavigationScope::Global,
         }
     }
 }
 
 impl DefaultHinter {
+    /// A hinter suggesting only the commands of the current session
+    ///
+    /// Without a session, or with a [`History`] that doesn't record it, all
+    /// commands are suggested. Chain it with wider hinters in a
+    /// [`ChainedHinter`](crate::ChainedHinter) to fall back to them.
+    pub fn session_scoped() -> Self {
+        DefaultHinter::default().with_scope(HistoryNavigationScope::Session)
+    }
+
+    /// A hinter suggesting only the commands run in the current working directory
+    ///
+    /// With a [`History`] that doesn't record the directory, all commands are
+    /// suggested.
+    pub fn cwd_scoped() -> Self {
+        DefaultHinter::default().with_scope(HistoryNavigationScope::Directory { exact: true })
+    }
+
     /// A builder that sets the style applied to the hint as part of the buffer
     #[must_use]
     pub fn with_style(mut self, style: Style) -> Self {
@@ -76,4 +119,13 @@ impl DefaultHinter {
         self.min_chars = min_chars;
         self
     }
+
+    /// A builder that restricts the hints to the commands in `scope`
+    ///
+    /// Falls back to all commands with a [`History`] that can't filter by the scope.
+    #[must_use]
+    pub fn with_scope(mut self, scope: HistoryNavigationScope) -> Self {
+        self.scope = scope;
+        self
+    }
 }
@@ -1,5 +1,9 @@
+mod boundary;
+mod chained;
 mod cwd_aware;
 mod default;
+pub use boundary::HintTokenBoundary;
+pub use chained::ChainedHinter;
 pub use cwd_aware::CwdAwareHinter;
 pub use default::DefaultHinter;
 
@@ -27,7 +31,7 @@ pub fn get_first_token(string: &str) -> String {
     result
 }
 
-use crate::History;
+use crate::{History, StyledText};
 /// A trait that's responsible for returning the hint for the current line and position
 /// Hints are often shown in-line as part of the buffer, showing the user text they can accept or ignore
 pub trait Hinter: Send {
@@ -49,4 +53,12 @@ pub trait Hinter: Send {
     /// Return the first semantic token of the hint
     /// for incremental completion
     fn next_hint_token(&self) -> String;
+
+    /// Return the current hint with its styling, letting the engine style
+    /// the part the next accepted token inserts differently
+    ///
+    /// By default, the hint is shown as formatted by [`Hinter::handle`].
+    fn styled_hint(&self) -> Option<StyledText> {
+        None
+    }
 }
@@ -1,6 +1,14 @@
+#[cfg(feature = "history_json")]
+use super::interchange::{self, MigrationReport};
 use super::HistoryItemId;
-use crate::{core_editor::LineBuffer, HistoryItem, HistorySessionId, Result};
+use crate::{
+    core_editor::LineBuffer, HistoryItem, HistorySessionId, ReedlineError, ReedlineErrorVariants,
+    Result,
+};
 use chrono::Utc;
+#[cfg(feature = "history_json")]
+use std::io::{BufRead, Write};
+use std::time::Duration;
 
 /// Browsing modes for a [`History`]
 #[derive(Debug, Clone, PartialEq, Eq)]
@@ -11,8 +19,131 @@ pub enum HistoryNavigationQuery {
     PrefixSearch(String),
     /// Full exact search for all entries containing a string.
     SubstringSearch(String),
+    /// Search for entries containing the characters of a string in order, best match first.
+    FuzzySearch(String),
     // Suffix Search
-    // Fuzzy Search
+}
+
+impl HistoryNavigationQuery {
+    /// The text searched for, if this is a search
+    pub(crate) fn search_text(&self) -> Option<&str> {
+        match self {
+            HistoryNavigationQuery::Normal(_) => None,
+            HistoryNavigationQuery::PrefixSearch(text)
+            | HistoryNavigationQuery::SubstringSearch(text)
+            | HistoryNavigationQuery::FuzzySearch(text) => Some(text),
+        }
+    }
+}
+
+/// How the interactive reverse history search matches the typed text
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum HistorySearchMode {
+    /// Entries containing the text
+    #[default]
+    Substring,
+    /// Entries starting with the text
+    Prefix,
+    /// Entries containing the characters of the text in order (e.g. `gcm`
+    /// finds `git commit -m`), ranked by how compact the match is and then
+    /// by recency
+    Fuzzy,
+}
+
+impl HistorySearchMode {
+    /// The [`CommandLineSearch`] matching `text` in this mode
+    pub fn command_line_search(self, text: String) -> CommandLineSearch {
+        match self {
+            HistorySearchMode::Substring => CommandLineSearch::Substring(text),
+            HistorySearchMode::Prefix => CommandLineSearch::Prefix(text),
+            HistorySearchMode::Fuzzy => CommandLineSearch::Fuzzy(text),
+        }
+    }
+
+    /// The [`HistoryNavigationQuery`] matching `text` in this mode
+    pub fn navigation_query(self, text: String) -> HistoryNavigationQuery {
+        match self {
+            HistorySearchMode::Substring => HistoryNavigationQuery::SubstringSearch(text),
+            HistorySearchMode::Prefix => HistoryNavigationQuery::PrefixSearch(text),
+            HistorySearchMode::Fuzzy => HistoryNavigationQuery::FuzzySearch(text),
+        }
+    }
+}
+
+/// Which previous commands history navigation and hints draw from
+///
+/// Narrower scopes need the session or working directory recorded in the
+/// [`HistoryItem`]s. With a [`History`] that doesn't record them the scope
+/// falls back to [`HistoryNavigationScope::Global`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum HistoryNavigationScope {
+    /// All commands, leaving out those other sessions ran since this one started
+    #[default]
+    Global,
+    /// Only the commands of the current session
+    Session,
+    /// Only the commands run in the current working directory, or with
+    /// `exact: false` in a directory starting with it
+    Directory {
+        /// Leave out the commands run in subdirectories
+        exact: bool,
+    },
+}
+
+/// The separators ending a directory in a `cwd`, so that Unix and Windows
+/// paths both match a [`SearchFilter::cwd_prefix`]
+pub(crate) const PATH_SEPARATORS: [char; 2] = ['/', '\\'];
+
+impl HistoryNavigationScope {
+    /// Restrict `filter` to the commands in this scope
+    ///
+    /// [`HistoryNavigationScope::Session`] keeps the session of `filter`, and
+    /// restricts nothing without one, as does
+    /// [`HistoryNavigationScope::Directory`] without a `cwd`.
+    pub fn restrict(self, filter: SearchFilter, cwd: Option<&str>) -> SearchFilter {
+        match (self, cwd) {
+            (HistoryNavigationScope::Session, _) => SearchFilter {
+                session_exact: filter.session,
+                ..filter
+            },
+            (HistoryNavigationScope::Directory { exact: true }, Some(cwd)) => {
+                filter.with_cwd_exact(cwd)
+            }
+            (HistoryNavigationScope::Directory { exact: false }, Some(cwd)) => {
+                // A trailing separator would leave out the directory itself
+                filter.with_cwd_prefix(cwd.trim_end_matches(PATH_SEPARATORS))
+            }
+            (HistoryNavigationScope::Global | HistoryNavigationScope::Directory { .. }, _) => {
+                filter
+            }
+        }
+    }
+
+    /// The scope to switch to when cycling through them, from the widest to
+    /// the narrowest
+    #[must_use]
+    pub fn next(self) -> Self {
+        match self {
+            HistoryNavigationScope::Global => HistoryNavigationScope::Session,
+            HistoryNavigationScope::Session => HistoryNavigationScope::Directory { exact: false },
+            HistoryNavigationScope::Directory { exact: false } => {
+                HistoryNavigationScope::Directory { exact: true }
+            }
+            HistoryNavigationScope::Directory { exact: true } => HistoryNavigationScope::Global,
+        }
+    }
+}
+
+/// How [`History::save_deduplicated`] treats a command line that is already in the history
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HistoryDeduplication {
+    /// Save every entry, repeated or not
+    KeepAll,
+    /// Skip an entry identical to the most recent one, like bash's `ignoredups`
+    IgnoreConsecutive,
+    /// Move a repeated entry to the front, erasing its older copies, like
+    /// zsh's `HIST_IGNORE_ALL_DUPS`
+    EraseOlder,
 }
 
 /// Ways to search for a particular command line in the [`History`]
@@ -26,6 +157,12 @@ pub enum CommandLineSearch {
     ///
     /// Useful to gather statistics
     Exact(String),
+    /// Command line contains the characters of the string in order
+    ///
+    /// Results are ranked best match first regardless of the
+    /// [`SearchDirection`]: more compact matches first, then more recent ones.
+    /// A `limit` applies to the ranked results.
+    Fuzzy(String),
 }
 
 /// Defines how to traverse the history when executing a [`SearchQuery`]
@@ -47,12 +184,17 @@ pub struct SearchFilter {
     pub hostname: Option<String>,
     /// Exact filter for the working directory
     pub cwd_exact: Option<String>,
-    /// Prefix filter for the working directory
+    /// Filter for the working directory and the directories below it
     pub cwd_prefix: Option<String>,
     /// Filter whether the command completed
     pub exit_successful: Option<bool>,
+    /// Filter for commands that ran at least this long
+    pub duration_at_least: Option<Duration>,
     /// Filter on the session id
     pub session: Option<HistorySessionId>,
+    /// Exact filter on the session id, leaving out the commands run before
+    /// the session started as well
+    pub session_exact: Option<HistorySessionId>,
 }
 
 impl SearchFilter {
@@ -87,9 +229,43 @@ impl SearchFilter {
             cwd_exact: None,
             cwd_prefix: None,
             exit_successful: None,
+            duration_at_least: None,
             session,
+            session_exact: None,
         }
     }
+
+    /// Builder keeping only commands that exited successfully, or only the failed ones
+    #[must_use]
+    pub fn with_exit_successful(mut self, exit_successful: bool) -> Self {
+        self.exit_successful = Some(exit_successful);
+        self
+    }
+
+    /// Builder keeping only commands run in the directory `cwd`
+    #[must_use]
+    pub fn with_cwd_exact(mut self, cwd: impl Into<String>) -> Self {
+        self.cwd_exact = Some(cwd.into());
+        self
+    }
+
+    /// Builder keeping only commands run in the directory `prefix` or below it
+    ///
+    /// `/work/app` matches `/work/app` and `/work/app/src`, but not the
+    /// sibling `/work/application`. With a trailing `/`, as in `/work/app/`,
+    /// only the directories below it match.
+    #[must_use]
+    pub fn with_cwd_prefix(mut self, prefix: impl Into<String>) -> Self {
+        self.cwd_prefix = Some(prefix.into());
+        self
+    }
+
+    /// Builder keeping only commands that ran at least `duration`
+    #[must_use]
+    pub fn with_duration_at_least(mut self, duration: Duration) -> Self {
+        self.duration_at_least = Some(duration);
+        self
+    }
 }
 
 /// Query for search in the potentially rich [`History`]
@@ -106,6 +282,9 @@ pub struct SearchQuery {
     pub end_id: Option<HistoryItemId>,
     /// How many results to get
     pub limit: Option<i64>,
+    /// How many results to skip before the first one to get, after ranking
+    /// fuzzy matches
+    pub offset: Option<i64>,
     /// Additional filters defined with [`SearchFilter`]
     pub filter: SearchFilter,
 }
@@ -121,6 +300,7 @@ impl SearchQuery {
             start_id: None,
             end_id: None,
             limit: None,
+            offset: None,
             filter: SearchFilter::from_text_search(CommandLineSearch::Substring(contains), None),
         }
     }
@@ -134,6 +314,7 @@ impl SearchQuery {
             start_id: None,
             end_id: None,
             limit: Some(1),
+            offset: None,
             filter,
         }
     }
@@ -168,11 +349,46 @@ impl SearchQuery {
             start_id: None,
             end_id: None,
             limit: None,
+            offset: None,
             filter: SearchFilter::anything(session),
         }
     }
 }
 
+/// The default implementation of [`History::save_deduplicated`]
+pub(crate) fn save_deduplicated_by_search<H: History + ?Sized>(
+    history: &mut H,
+    h: HistoryItem,
+    deduplication: HistoryDeduplication,
+) -> Result<HistoryItem> {
+    match deduplication {
+        HistoryDeduplication::KeepAll => {}
+        HistoryDeduplication::IgnoreConsecutive => {
+            let last = history
+                .search(SearchQuery::last_with_search(SearchFilter::anything(
+                    history.session(),
+                )))?
+                .pop();
+            if let Some(last) = last.filter(|last| last.command_line == h.command_line) {
+                return Ok(last);
+            }
+        }
+        HistoryDeduplication::EraseOlder => {
+            let older = history.search(SearchQuery {
+                filter: SearchFilter::from_text_search(
+                    CommandLineSearch::Exact(h.command_line.clone()),
+                    None,
+                ),
+                ..SearchQuery::everything(SearchDirection::Backward, None)
+            })?;
+            for id in older.into_iter().filter_map(|item| item.id) {
+                history.delete(id)?;
+            }
+        }
+    }
+    history.save(h)
+}
+
 /// Represents a history file or database
 /// Data could be stored e.g. in a plain text file, in a `JSONL` file, in a `SQLite` database
 pub trait History: Send {
@@ -180,6 +396,18 @@ pub trait History: Send {
     /// if given id is None, a new id is created and set in the return value
     /// if given id is Some, the existing entry is updated
     fn save(&mut self, h: HistoryItem) -> Result<HistoryItem>;
+    /// save a new history item, handling copies of its command line according to `deduplication`
+    ///
+    /// Returns the saved item, or the existing item standing in for it.
+    /// The default implementation builds on [`History::search`],
+    /// [`History::delete`] and [`History::save`].
+    fn save_deduplicated(
+        &mut self,
+        h: HistoryItem,
+        deduplication: HistoryDeduplication,
+    ) -> Result<HistoryItem> {
+        save_deduplicated_by_search(self, h, deduplication)
+    }
     /// load a history item by its id
     fn load(&self, id: HistoryItemId) -> Result<HistoryItem>;
 
@@ -198,11 +426,54 @@ pub trait History: Send {
         id: HistoryItemId,
         updater: &dyn Fn(HistoryItem) -> HistoryItem,
     ) -> Result<()>;
+    /// update the most recent item of this session atomically
+    ///
+    /// Lets a host record e.g. the [`HistoryItem::exit_status`] and
+    /// [`HistoryItem::duration`] of a command after running it. The default
+    /// implementation builds on [`History::search`] and [`History::update`].
+    fn update_last_item(&mut self, updater: &dyn Fn(HistoryItem) -> HistoryItem) -> Result<()> {
+        let last = self
+            .search(SearchQuery::last_with_search(SearchFilter::anything(
+                self.session(),
+            )))?
+            .pop();
+        match last.and_then(|item| item.id) {
+            Some(id) => self.update(id, updater),
+            None => Err(ReedlineError(ReedlineErrorVariants::OtherHistoryError(
+                "No item to update",
+            ))),
+        }
+    }
+    /// write all items to `writer`, oldest first, in the JSON lines format
+    /// described in [`migrate`](crate::migrate)
+    ///
+    /// Returns the number of items written. The default implementation builds
+    /// on [`History::search`]. Requires the `history_json` feature.
+    #[cfg(feature = "history_json")]
+    fn export_to(&self, writer: &mut dyn Write) -> Result<usize> {
+        interchange::export_to(self, writer)
+    }
+    /// save the items read from `reader` in the JSON lines format described in
+    /// [`migrate`](crate::migrate), handling repeated command lines according
+    /// to `deduplication`
+    ///
+    /// Malformed lines and items that can't be saved are reported and skipped.
+    /// The default implementation builds on [`History::save_deduplicated`].
+    /// Requires the `history_json` feature.
+    #[cfg(feature = "history_json")]
+    fn import_from(
+        &mut self,
+        reader: &mut dyn BufRead,
+        deduplication: HistoryDeduplication,
+    ) -> Result<MigrationReport> {
+        interchange::import_from(self, reader, deduplication)
+    }
     /// delete all history items
     fn clear(&mut self) -> Result<()>;
     /// remove an item from this history
     fn delete(&mut self, h: HistoryItemId) -> Result<()>;
-    /// ensure that this history is written to disk
+    /// ensure that this history is written to disk and sees the entries other
+    /// sessions sharing its storage wrote
     fn sync(&mut self) -> std::io::Result<()>;
     /// get the history session id
     fn session(&self) -> Option<HistorySessionId>;
@@ -277,6 +548,97 @@ mod test {
         Ok(())
     }
 
+    #[cfg(any(feature = "sqlite", feature = "sqlite-dynlib"))]
+    #[test]
+    fn update_last_item_records_the_outcome() -> Result<()> {
+        let tmp = tempfile::tempdir().unwrap();
+        let db = tmp.path().join("history.sqlite3");
+        {
+            let mut history = crate::SqliteBackedHistory::with_file(db.clone(), None, None)?;
+            history.save(HistoryItem::from_command_line("cargo build"))?;
+            history.save(HistoryItem::from_command_line("cargo test"))?;
+            history.update_last_item(&|item| HistoryItem {
+                cwd: Some("/home/me/reedline".to_string()),
+                duration: Some(Duration::from_millis(2500)),
+                exit_status: Some(101),
+                ..item
+            })?;
+        }
+
+        let history = crate::SqliteBackedHistory::with_file(db, None, None)?;
+        let last = history
+            .search(SearchQuery::last_with_search(SearchFilter::anything(None)))?
+            .remove(0);
+        assert_eq!(last.command_line, "cargo test");
+        assert_eq!(last.cwd.as_deref(), Some("/home/me/reedline"));
+        assert_eq!(last.duration, Some(Duration::from_millis(2500)));
+        assert_eq!(last.exit_status, Some(101));
+        let first = history.load(HistoryItemId::new(1))?;
+        assert_eq!(first.exit_status, None);
+        Ok(())
+    }
+
+    #[cfg(any(feature = "sqlite", feature = "sqlite-dynlib"))]
+    #[test]
+    fn search_by_outcome_cwd_and_duration() -> Result<()> {
+        let mut history = create_filled_example_history()?;
+        history.update(HistoryItemId::new(11), &|item| HistoryItem {
+            duration: Some(Duration::from_secs(30)),
+            ..item
+        })?;
+        let search = |filter: SearchFilter| {
+            history.search(SearchQuery {
+                filter,
+                ..SearchQuery::everything(SearchDirection::Backward, None)
+            })
+        };
+
+        let failed = search(SearchFilter::anything(None).with_exit_successful(false))?;
+        search_returned(&*history, failed, vec![2])?;
+
+        let slow =
+            search(SearchFilter::anything(None).with_duration_at_least(Duration::from_secs(2)))?;
+        search_returned(&*history, slow, vec![11])?;
+
+        let in_downloads = search(
+            SearchFilter::anything(None)
+                .with_cwd_prefix("/home/me/Downloads/")
+                .with_exit_successful(true),
+        )?;
+        search_returned(&*history, in_downloads, vec![7, 6, 5])?;
+
+        // The prefix is matched literally, not as a pattern
+        let pattern = search(SearchFilter::anything(None).with_cwd_prefix("/home/me/D_wnloads"))?;
+        search_returned(&*history, pattern, vec![])?;
+        Ok(())
+    }
+
+    #[cfg(any(feature = "sqlite", feature = "sqlite-dynlib"))]
+    #[test]
+    fn cwd_prefix_leaves_out_sibling_directories() -> Result<()> {
+        let mut history = create_filled_example_history()?;
+        history.save(create_item(1, "/home/me/Downloads-old", "ls", 0))?; // 13
+        history.save(create_item(1, r"C:\work\app\src", "cargo test", 0))?; // 14
+        history.save(create_item(1, r"C:\work\application", "ls", 0))?; // 15
+        let search = |prefix: &str| {
+            history.search(SearchQuery {
+                filter: SearchFilter::anything(None).with_cwd_prefix(prefix),
+                ..SearchQuery::everything(SearchDirection::Backward, None)
+            })
+        };
+
+        search_returned(
+            &*history,
+            search("/home/me/Downloads")?,
+            vec![7, 6, 5, 4, 3, 2],
+        )?;
+        search_returned(&*history, search("/home/me/Downloads/fo")?, vec![])?;
+        search_returned(&*history, search("/home/me/Downloads-old")?, vec![13])?;
+        search_returned(&*history, search(r"C:\work\app")?, vec![14])?;
+        search_returned(&*history, search(r"C:\work\app\")?, vec![14])?;
+        Ok(())
+    }
+
     fn search_returned(
         history: &dyn History,
         res: Vec<HistoryItem>,
@@ -316,6 +678,7 @@ mod test {
         let history = create_filled_example_history()?;
         let res = history.search(SearchQuery {
             limit: Some(1),
+            offset: None,
             ..SearchQuery::everything(SearchDirection::Forward, None)
         })?;
         search_returned(&*history, res, vec![if IS_FILE_BASED { 0 } else { 1 }])?;
@@ -378,6 +741,7 @@ mod test {
                 None,
             ),
             limit: Some(2),
+            offset: None,
             ..SearchQuery::everything(SearchDirection::Forward, None)
         })?;
         search_returned(&*history, res, vec![1, 4])?;
@@ -1,4 +1,9 @@
-use crate::{History, HistoryNavigationQuery, HistorySessionId};
+use std::collections::{HashMap, HashSet};
+
+use crate::{
+    result::{ReedlineError, ReedlineErrorVariants},
+    History, HistoryItemId, HistoryNavigationQuery, HistoryNavigationScope, HistorySessionId,
+};
 
 use super::base::CommandLineSearch;
 use super::base::SearchDirection;
@@ -7,6 +12,9 @@ use super::HistoryItem;
 use super::SearchQuery;
 use crate::Result;
 
+/// How many entries to read from the history at once when counting matches
+const PAGE_SIZE: i64 = 1000;
+
 /// Interface of a stateful navigation via [`HistoryNavigationQuery`].
 #[derive(Debug)]
 pub struct HistoryCursor {
@@ -14,6 +22,18 @@ pub struct HistoryCursor {
     current: Option<HistoryItem>,
     skip_dupes: bool,
     session: Option<HistorySessionId>,
+    scope: HistoryNavigationScope,
+    cwd: Option<String>,
+}
+
+/// The position of each match a [`HistoryCursor`] steps through, counting
+/// from 1, and the number of matches
+///
+/// Finding them reads every match, so it is done once per query.
+#[derive(Debug, Default)]
+pub(crate) struct HistoryMatches {
+    positions: HashMap<HistoryItemId, usize>,
+    total: usize,
 }
 
 impl HistoryCursor {
@@ -23,9 +43,28 @@ impl HistoryCursor {
             current: None,
             skip_dupes: true,
             session,
+            scope: HistoryNavigationScope::Global,
+            cwd: None,
         }
     }
 
+    /// A builder restricting the navigation to the commands in `scope`, with
+    /// `cwd` as the current working directory
+    ///
+    /// If the [`History`] can't filter by the scope the navigation silently
+    /// covers all commands instead.
+    #[must_use]
+    pub fn with_scope(mut self, scope: HistoryNavigationScope, cwd: Option<String>) -> Self {
+        self.scope = scope;
+        self.cwd = cwd;
+        self
+    }
+
+    /// The scope the navigation is restricted to
+    pub fn scope(&self) -> HistoryNavigationScope {
+        self.scope
+    }
+
     /// This moves the cursor backwards respecting the navigation query that is set
     /// - Results in a no-op if the cursor is at the initial point
     pub fn back(&mut self, history: &dyn History) -> Result<()> {
@@ -38,7 +77,8 @@ impl HistoryCursor {
         self.navigate_in_direction(history, SearchDirection::Forward)
     }
 
-    fn get_search_filter(&self) -> SearchFilter {
+    /// The filter for the entries matching the query within the scope
+    fn matching_filter(&self) -> SearchFilter {
         let filter = match self.query.clone() {
             HistoryNavigationQuery::Normal(_) => SearchFilter::anything(self.session),
             HistoryNavigationQuery::PrefixSearch(prefix) => {
@@ -48,7 +88,15 @@ impl HistoryCursor {
                 CommandLineSearch::Substring(substring),
                 self.session,
             ),
+            HistoryNavigationQuery::FuzzySearch(needle) => {
+                SearchFilter::from_text_search(CommandLineSearch::Fuzzy(needle), self.session)
+            }
         };
+        self.scope.restrict(filter, self.cwd.as_deref())
+    }
+
+    fn get_search_filter(&self) -> SearchFilter {
+        let filter = self.matching_filter();
         if let (true, Some(current)) = (self.skip_dupes, &self.current) {
             SearchFilter {
                 not_command_line: Some(current.command_line.clone()),
@@ -58,6 +106,25 @@ impl HistoryCursor {
             filter
         }
     }
+
+    /// Run the query built by `query`, falling back to the global scope for
+    /// good if the history can't filter by the scope
+    fn search_in_scope(
+        &mut self,
+        history: &dyn History,
+        query: impl Fn(&Self) -> SearchQuery,
+    ) -> Result<Vec<HistoryItem>> {
+        match history.search(query(self)) {
+            Err(ReedlineError(ReedlineErrorVariants::HistoryFeatureUnsupported { .. }))
+                if self.scope != HistoryNavigationScope::Global =>
+            {
+                self.scope = HistoryNavigationScope::Global;
+                history.search(query(self))
+            }
+            result => result,
+        }
+    }
+
     fn navigate_in_direction(
         &mut self,
         history: &dyn History,
@@ -67,15 +134,19 @@ impl HistoryCursor {
             // if searching forward but we don't have a starting point, assume we are at the end
             return Ok(());
         }
+        if let HistoryNavigationQuery::FuzzySearch(_) = &self.query {
+            return self.navigate_ranked(history, direction);
+        }
         let start_id = self.current.as_ref().and_then(|e| e.id);
-        let mut next = history.search(SearchQuery {
+        let mut next = self.search_in_scope(history, |cursor| SearchQuery {
             start_id,
             end_id: None,
             start_time: None,
             end_time: None,
             direction,
             limit: Some(1),
-            filter: self.get_search_filter(),
+            offset: None,
+            filter: cursor.get_search_filter(),
         })?;
         if next.len() == 1 {
             self.current = Some(next.swap_remove(0));
@@ -86,6 +157,103 @@ impl HistoryCursor {
         Ok(())
     }
 
+    /// Step through the fuzzy matches in ranked order instead of by id
+    ///
+    /// Going back moves to the next worse match, going forward to the next
+    /// better one and past the best match to the initial point.
+    fn navigate_ranked(&mut self, history: &dyn History, direction: SearchDirection) -> Result<()> {
+        let ranked = self.search_in_scope(history, |cursor| SearchQuery {
+            filter: cursor.matching_filter(),
+            ..SearchQuery::everything(SearchDirection::Backward, None)
+        })?;
+        let mut ranked = self.unique_ranked(ranked);
+
+        let position = self
+            .current
+            .as_ref()
+            .and_then(|current| ranked.iter().position(|item| item.id == current.id));
+        let next = match (direction, position) {
+            (SearchDirection::Backward, None) => Some(0),
+            (SearchDirection::Backward, Some(position)) => Some(position + 1),
+            (SearchDirection::Forward, Some(position)) => position.checked_sub(1),
+            (SearchDirection::Forward, None) => None,
+        };
+        match next {
+            Some(next) if next < ranked.len() => self.current = Some(ranked.swap_remove(next)),
+            // no worse match: stay on the last one
+            Some(_) => {}
+            None => self.current = None,
+        }
+        Ok(())
+    }
+
+    /// The fuzzy matches in ranked order, each command line only once if
+    /// duplicates are skipped
+    fn unique_ranked(&self, mut ranked: Vec<HistoryItem>) -> Vec<HistoryItem> {
+        if self.skip_dupes {
+            let mut seen = HashSet::new();
+            ranked.retain(|item| seen.insert(item.command_line.clone()));
+        }
+        ranked
+    }
+
+    /// The position of the entry at the cursor among `matches`, found by
+    /// [`HistoryCursor::matches`] for the same query, counting from 1, and the
+    /// number of matches
+    ///
+    /// Like the navigation, this counts a run of the same command line as
+    /// one match when duplicates are skipped. Returns `None` at the initial
+    /// point.
+    pub(crate) fn position_among(&self, matches: &HistoryMatches) -> Option<(usize, usize)> {
+        let id = self.current.as_ref()?.id?;
+        let position = matches.positions.get(&id)?;
+        Some((*position, matches.total))
+    }
+
+    /// Every match the cursor steps through with its position
+    pub(crate) fn matches(&self, history: &dyn History) -> Result<HistoryMatches> {
+        if let HistoryNavigationQuery::FuzzySearch(_) = &self.query {
+            let ranked = self.unique_ranked(history.search(SearchQuery {
+                filter: self.matching_filter(),
+                ..SearchQuery::everything(SearchDirection::Backward, None)
+            })?);
+            return Ok(HistoryMatches {
+                total: ranked.len(),
+                positions: ranked
+                    .into_iter()
+                    .enumerate()
+                    .filter_map(|(index, item)| Some((item.id?, index + 1)))
+                    .collect(),
+            });
+        }
+
+        let mut matches = HistoryMatches::default();
+        let mut previous: Option<String> = None;
+        let mut start_id = None;
+        loop {
+            let page = history.search(SearchQuery {
+                start_id,
+                limit: Some(PAGE_SIZE),
+                filter: self.matching_filter(),
+                ..SearchQuery::everything(SearchDirection::Backward, None)
+            })?;
+            for item in &page {
+                if !self.skip_dupes || previous.as_ref() != Some(&item.command_line) {
+                    matches.total += 1;
+                    previous = Some(item.command_line.clone());
+                }
+                if let Some(id) = item.id {
+                    matches.positions.insert(id, matches.total);
+                }
+            }
+            start_id = page.last().and_then(|item| item.id);
+            if (page.len() as i64) < PAGE_SIZE || start_id.is_none() {
+                break;
+            }
+        }
+        Ok(matches)
+    }
+
     /// Returns the string (if present) at the cursor
     pub fn string_at_cursor(&self) -> Option<String> {
         self.current.as_ref().map(|e| e.command_line.to_string())
@@ -360,6 +528,88 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn fuzzy_search_steps_through_ranked_matches() -> Result<()> {
+        let (mut hist, _) = create_history();
+        add_text_entries(
+            hist.as_mut(),
+            &["git commit -m", "gcm", "ls", "git checkout main", "gcm"],
+        );
+
+        let mut cursor =
+            HistoryCursor::new(HistoryNavigationQuery::FuzzySearch("gcm".to_string()), None);
+        cursor.back(&*hist)?;
+        assert_eq!(cursor.string_at_cursor(), Some("gcm".to_string()));
+        cursor.back(&*hist)?;
+        assert_eq!(cursor.string_at_cursor(), Some("git commit -m".to_string()));
+        cursor.back(&*hist)?;
+        assert_eq!(
+            cursor.string_at_cursor(),
+            Some("git checkout main".to_string())
+        );
+        cursor.back(&*hist)?;
+        assert_eq!(
+            cursor.string_at_cursor(),
+            Some("git checkout main".to_string())
+        );
+        cursor.forward(&*hist)?;
+        assert_eq!(cursor.string_at_cursor(), Some("git commit -m".to_string()));
+        cursor.forward(&*hist)?;
+        cursor.forward(&*hist)?;
+        assert_eq!(cursor.string_at_cursor(), None);
+        Ok(())
+    }
+
+    #[test]
+    fn fuzzy_search_limits_ranked_results() -> Result<()> {
+        let (mut hist, _) = create_history();
+        add_text_entries(hist.as_mut(), &["a-b", "ab", "a_b", "xyz", "a.b"]);
+
+        let ranked: Vec<_> = hist
+            .search(SearchQuery {
+                limit: Some(3),
+                filter: SearchFilter::from_text_search(
+                    CommandLineSearch::Fuzzy("ab".to_string()),
+                    None,
+                ),
+                ..SearchQuery::everything(SearchDirection::Forward, None)
+            })?
+            .into_iter()
+            .map(|item| item.command_line)
+            .collect();
+
+        assert_eq!(ranked, vec!["ab", "a.b", "a_b"]);
+        Ok(())
+    }
+
+    #[test]
+    fn fuzzy_search_matches_non_ascii_and_special_characters() -> Result<()> {
+        let (mut hist, _) = create_history();
+        add_text_entries(
+            hist.as_mut(),
+            &["echo 日本語", "ls *.rs", "ls a.rs", "cd Ä"],
+        );
+
+        let matches = |needle: &str| -> Result<Vec<String>> {
+            Ok(hist
+                .search(SearchQuery {
+                    filter: SearchFilter::from_text_search(
+                        CommandLineSearch::Fuzzy(needle.to_string()),
+                        None,
+                    ),
+                    ..SearchQuery::everything(SearchDirection::Backward, None)
+                })?
+                .into_iter()
+                .map(|item| item.command_line)
+                .collect())
+        };
+
+        assert_eq!(matches("日語")?, vec!["echo 日本語"]);
+        assert_eq!(matches("l*s")?, vec!["ls *.rs"]);
+        assert_eq!(matches("cä")?, Vec::<String>::new());
+        Ok(())
+    }
+
     #[test]
     fn substring_search_with_empty_value_returns_none() -> Result<()> {
         let (mut hist, _) = create_history();
@@ -474,6 +724,222 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn deduplication_survives_save_and_load() -> Result<()> {
+        use tempfile::tempdir;
+
+        // Saved entries, and the entries seen navigating back through them,
+        // which skips repeats of the shown entry
+        let cases = [
+            (
+                HistoryDeduplication::KeepAll,
+                vec!["a", "b", "a", "a", "c", "b"],
+                vec!["a", "b", "a", "c", "b"],
+            ),
+            (
+                HistoryDeduplication::IgnoreConsecutive,
+                vec!["a", "b", "a", "c", "b"],
+                vec!["a", "b", "a", "c", "b"],
+            ),
+            (
+                HistoryDeduplication::EraseOlder,
+                vec!["a", "c", "b"],
+                vec!["a", "c", "b"],
+            ),
+        ];
+
+        for (deduplication, expected, expected_navigation) in cases {
+            let tmp = tempdir().unwrap();
+            let histfile = tmp.path().join(".history");
+
+            {
+                let (mut hist, _) = create_history_at(10, &histfile);
+                add_text_entries(hist.as_mut(), &["a", "b"]);
+            }
+
+            {
+                let (mut hist, _) = create_history_at(10, &histfile);
+                for entry in ["a", "a", "c", "b"] {
+                    hist.save_deduplicated(HistoryItem::from_command_line(entry), deduplication)?;
+                }
+                assert_eq!(expected, get_all_entry_texts(hist.as_ref()));
+            }
+
+            let (reading_hist, mut cursor) = create_history_at(10, &histfile);
+            assert_eq!(expected, get_all_entry_texts(reading_hist.as_ref()));
+
+            let mut navigated = Vec::new();
+            for _ in 0..expected_navigation.len() {
+                cursor.back(reading_hist.as_ref())?;
+                navigated.insert(0, cursor.string_at_cursor().unwrap());
+            }
+            assert_eq!(expected_navigation, navigated);
+
+            tmp.close().unwrap();
+        }
+        Ok(())
+    }
+
+    #[cfg(any(feature = "sqlite", feature = "sqlite-dynlib"))]
+    #[test]
+    fn sqlite_deduplication_updates_existing_rows() -> Result<()> {
+        let mut hist = SqliteBackedHistory::in_memory()?;
+        add_text_entries(&mut hist, &["a", "b", "a", "c"]);
+
+        let saved = hist.save_deduplicated(
+            HistoryItem::from_command_line("c"),
+            HistoryDeduplication::IgnoreConsecutive,
+        )?;
+        assert_eq!(saved.id, Some(HistoryItemId::new(4)));
+        assert_eq!(get_all_entry_texts(&hist), vec!["a", "b", "a", "c"]);
+
+        let saved = hist.save_deduplicated(
+            HistoryItem::from_command_line("a"),
+            HistoryDeduplication::EraseOlder,
+        )?;
+        assert_eq!(saved.id, Some(HistoryItemId::new(5)));
+        assert_eq!(get_all_entry_texts(&hist), vec!["b", "c", "a"]);
+
+        // New rows still come after the moved one
+        let saved = hist.save(HistoryItem::from_command_line("d"))?;
+        assert!(saved.id > Some(HistoryItemId::new(5)));
+        assert_eq!(get_all_entry_texts(&hist), vec!["b", "c", "a", "d"]);
+        Ok(())
+    }
+
+    #[cfg(any(feature = "sqlite", feature = "sqlite-dynlib"))]
+    #[test]
+    fn sqlite_ignoring_consecutive_repeats_keeps_older_copies() -> Result<()> {
+        let mut hist = SqliteBackedHistory::in_memory()?;
+        for entry in ["a", "b", "a", "a"] {
+            hist.save_deduplicated(
+                HistoryItem::from_command_line(entry),
+                HistoryDeduplication::IgnoreConsecutive,
+            )?;
+        }
+        assert_eq!(get_all_entry_texts(&hist), vec!["a", "b", "a"]);
+        Ok(())
+    }
+
+    #[cfg(any(feature = "sqlite", feature = "sqlite-dynlib"))]
+    fn create_scoped_history() -> Result<SqliteBackedHistory> {
+        let mut hist = SqliteBackedHistory::in_memory()?;
+        for (command_line, session, cwd) in [
+            ("make", 1, "/work/app"),
+            ("cargo test", 2, "/work/app"),
+            ("ls", 2, "/home"),
+            ("cargo build", 2, "/work/app/src"),
+            ("vim main.rs", 1, "/work/app/src"),
+            ("cargo run", 1, "/work/app"),
+        ] {
+            hist.save(HistoryItem {
+                session_id: Some(HistorySessionId::new(session)),
+                cwd: Some(cwd.to_string()),
+                ..HistoryItem::from_command_line(command_line)
+            })?;
+        }
+        Ok(hist)
+    }
+
+    #[cfg(any(feature = "sqlite", feature = "sqlite-dynlib"))]
+    #[rstest::rstest]
+    #[case::global(
+        HistoryNavigationScope::Global,
+        &["cargo run", "vim main.rs", "cargo build", "ls", "cargo test", "make"]
+    )]
+    #[case::session(HistoryNavigationScope::Session, &["cargo build", "ls", "cargo test"])]
+    #[case::directory(
+        HistoryNavigationScope::Directory { exact: true },
+        &["cargo run", "cargo test", "make"]
+    )]
+    #[case::directory_tree(
+        HistoryNavigationScope::Directory { exact: false },
+        &["cargo run", "vim main.rs", "cargo build", "cargo test", "make"]
+    )]
+    fn scoped_navigation_goes_back_through_the_commands_in_scope(
+        #[case] scope: HistoryNavigationScope,
+        #[case] expected: &[&str],
+    ) -> Result<()> {
+        let hist = create_scoped_history()?;
+        let mut cursor = HistoryCursor::new(
+            HistoryNavigationQuery::Normal(LineBuffer::default()),
+            Some(HistorySessionId::new(2)),
+        )
+        .with_scope(scope, Some("/work/app".to_string()));
+
+        let mut navigated = Vec::new();
+        for _ in 0..=expected.len() {
+            cursor.back(&hist)?;
+            navigated.push(cursor.string_at_cursor().unwrap());
+        }
+        // stays on the oldest command in scope
+        assert_eq!(navigated.pop().as_deref(), expected.last().copied());
+        assert_eq!(navigated, expected);
+
+        cursor.forward(&hist)?;
+        assert_eq!(
+            cursor.string_at_cursor().as_deref(),
+            Some(expected[expected.len() - 2])
+        );
+        Ok(())
+    }
+
+    #[cfg(any(feature = "sqlite", feature = "sqlite-dynlib"))]
+    #[rstest::rstest]
+    #[case::without_slash("/work/app")]
+    #[case::with_slash("/work/app/")]
+    fn directory_tree_scope_leaves_out_sibling_directories(#[case] cwd: &str) -> Result<()> {
+        let mut hist = create_scoped_history()?;
+        hist.save(HistoryItem {
+            cwd: Some("/work/application".to_string()),
+            ..HistoryItem::from_command_line("npm test")
+        })?;
+        let mut cursor =
+            HistoryCursor::new(HistoryNavigationQuery::Normal(LineBuffer::default()), None)
+                .with_scope(
+                    HistoryNavigationScope::Directory { exact: false },
+                    Some(cwd.to_string()),
+                );
+        cursor.back(&hist)?;
+        assert_eq!(cursor.string_at_cursor(), Some("cargo run".to_string()));
+        Ok(())
+    }
+
+    #[cfg(any(feature = "sqlite", feature = "sqlite-dynlib"))]
+    #[test]
+    fn scoped_prefix_search_only_finds_commands_in_scope() -> Result<()> {
+        let hist = create_scoped_history()?;
+        let mut cursor =
+            HistoryCursor::new(HistoryNavigationQuery::PrefixSearch("cargo".into()), None)
+                .with_scope(
+                    HistoryNavigationScope::Directory { exact: true },
+                    Some("/work/app/src".to_string()),
+                );
+        cursor.back(&hist)?;
+        assert_eq!(cursor.string_at_cursor(), Some("cargo build".to_string()));
+        cursor.back(&hist)?;
+        assert_eq!(cursor.string_at_cursor(), Some("cargo build".to_string()));
+        Ok(())
+    }
+
+    #[test]
+    fn scoped_navigation_falls_back_to_all_commands() -> Result<()> {
+        let mut hist = FileBackedHistory::default();
+        add_text_entries(&mut hist, &["a", "b"]);
+        let mut cursor =
+            HistoryCursor::new(HistoryNavigationQuery::Normal(LineBuffer::default()), None)
+                .with_scope(
+                    HistoryNavigationScope::Directory { exact: false },
+                    Some("/work".to_string()),
+                );
+        cursor.back(&hist)?;
+        assert_eq!(cursor.string_at_cursor(), Some("b".to_string()));
+        assert_eq!(cursor.scope(), HistoryNavigationScope::Global);
+        cursor.back(&hist)?;
+        assert_eq!(cursor.string_at_cursor(), Some("a".to_string()));
+        Ok(())
+    }
+
     #[test]
     fn truncates_too_large_file() -> Result<()> {
         use tempfile::tempdir;
@@ -509,6 +975,89 @@ mod tests {
         Ok(())
     }
 
+    /// Walk a history of 100k entries with the cursor and page through
+    /// searches of it, checking every page against the entries saved
+    fn check_large_history(hist: &dyn History, entries: &[String]) -> Result<()> {
+        const PAGE: usize = 1000;
+
+        let mut cursor =
+            HistoryCursor::new(HistoryNavigationQuery::Normal(LineBuffer::default()), None);
+        for expected in entries.iter().rev().take(3) {
+            cursor.back(hist)?;
+            assert_eq!(cursor.string_at_cursor().as_ref(), Some(expected));
+        }
+        let mut cursor = HistoryCursor::new(
+            HistoryNavigationQuery::PrefixSearch("echo 4".to_string()),
+            None,
+        );
+        cursor.back(hist)?;
+        assert_eq!(cursor.string_at_cursor().as_deref(), Some("echo 49999"));
+        cursor.back(hist)?;
+        assert_eq!(cursor.string_at_cursor().as_deref(), Some("echo 49998"));
+
+        for contains in ["", "77"] {
+            let expected: Vec<_> = entries
+                .iter()
+                .rev()
+                .filter(|entry| entry.contains(contains))
+                .collect();
+            for (page, expected) in expected.chunks(PAGE).enumerate() {
+                let query = || SearchQuery {
+                    limit: Some(PAGE as i64),
+                    offset: Some((page * PAGE) as i64),
+                    ..SearchQuery::all_that_contain_rev(contains.to_string())
+                };
+                let actual = hist.search(query())?;
+                let actual: Vec<_> = actual.iter().map(|item| &item.command_line).collect();
+                assert_eq!(actual, expected, "page {page} of {contains:?}");
+                assert_eq!(hist.count(query())?, expected.len() as i64);
+            }
+        }
+        Ok(())
+    }
+
+    fn large_history_entries() -> Vec<String> {
+        (0..100_000)
+            .map(|i| {
+                if i % 2 == 0 {
+                    format!("cargo test --test case_{i}")
+                } else {
+                    format!("echo {}", i / 2)
+                }
+            })
+            .collect()
+    }
+
+    #[test]
+    fn large_file_history_is_read_page_by_page() -> Result<()> {
+        use tempfile::tempdir;
+
+        let tmp = tempdir().unwrap();
+        let histfile = tmp.path().join(".history");
+        let entries = large_history_entries();
+
+        {
+            let (mut writing_hist, _) = create_history_at(entries.len(), &histfile);
+            add_text_entries(writing_hist.as_mut(), &entries);
+            check_large_history(writing_hist.as_ref(), &entries)?;
+        }
+
+        let (reading_hist, _) = create_history_at(entries.len(), &histfile);
+        check_large_history(reading_hist.as_ref(), &entries)?;
+
+        tmp.close().unwrap();
+        Ok(())
+    }
+
+    #[cfg(any(feature = "sqlite", feature = "sqlite-dynlib"))]
+    #[test]
+    fn large_sqlite_history_is_read_page_by_page() -> Result<()> {
+        let entries = large_history_entries();
+        let mut hist = SqliteBackedHistory::in_memory()?;
+        add_text_entries(&mut hist, &entries);
+        check_large_history(&hist, &entries)
+    }
+
     #[test]
     fn concurrent_histories_do_not_erase_each_other() -> Result<()> {
         use tempfile::tempdir;
@@ -604,4 +1153,100 @@ mod tests {
         tmp.close().unwrap();
         Ok(())
     }
+
+    /// Entries each helper process of the cross-process tests writes
+    const ENTRIES_PER_PROCESS: usize = 25;
+
+    /// Open the history a helper process of the cross-process tests writes to
+    fn open_shared_history(backend: &str, path: &Path) -> Box<dyn History> {
+        match backend {
+            #[cfg(any(feature = "sqlite", feature = "sqlite-dynlib"))]
+            "sqlite" => {
+                Box::new(SqliteBackedHistory::with_file(path.to_owned(), None, None).unwrap())
+            }
+            _ => Box::new(
+                FileBackedHistory::with_file(1000, path.to_owned())
+                    .unwrap()
+                    .with_sync_on_save(true),
+            ),
+        }
+    }
+
+    /// Body of the helper processes spawned by the cross-process tests
+    ///
+    /// Does nothing unless run by one of them.
+    #[test]
+    fn shared_history_writer() {
+        let (Ok(backend), Ok(path), Ok(prefix)) = (
+            std::env::var("REEDLINE_TEST_HISTORY_BACKEND"),
+            std::env::var("REEDLINE_TEST_HISTORY_PATH"),
+            std::env::var("REEDLINE_TEST_HISTORY_PREFIX"),
+        ) else {
+            return;
+        };
+        let mut hist = open_shared_history(&backend, Path::new(&path));
+        for i in 0..ENTRIES_PER_PROCESS {
+            hist.save(HistoryItem::from_command_line(format!("{prefix} {i}")))
+                .unwrap();
+        }
+    }
+
+    /// Write to the history at `path` from several processes at once and check
+    /// that a session open the whole time sees every entry after syncing
+    fn check_concurrent_processes(backend: &str, path: &Path) {
+        let num_processes = 4;
+        let mut hist = open_shared_history(backend, path);
+
+        let writers: Vec<_> = (0..num_processes)
+            .map(|p| {
+                std::process::Command::new(std::env::current_exe().unwrap())
+                    .args([
+                        "--exact",
+                        "history::cursor::tests::shared_history_writer",
+                        "--test-threads=1",
+                    ])
+                    .env("REEDLINE_TEST_HISTORY_BACKEND", backend)
+                    .env("REEDLINE_TEST_HISTORY_PATH", path)
+                    .env("REEDLINE_TEST_HISTORY_PREFIX", format!("process {p}"))
+                    .stdout(std::process::Stdio::null())
+                    .spawn()
+                    .unwrap()
+            })
+            .collect();
+        let own_entries: Vec<_> = (0..ENTRIES_PER_PROCESS)
+            .map(|i| format!("parent {i}"))
+            .collect();
+        add_text_entries(hist.as_mut(), &own_entries);
+        for mut writer in writers {
+            assert!(writer.wait().unwrap().success());
+        }
+        hist.sync().unwrap();
+
+        let actual = get_all_entry_texts(hist.as_ref());
+        assert_eq!(actual.len(), (num_processes + 1) * ENTRIES_PER_PROCESS);
+        for p in 0..num_processes {
+            let written: Vec<_> = actual
+                .iter()
+                .filter(|e| e.starts_with(&format!("process {p} ")))
+                .cloned()
+                .collect();
+            let expected: Vec<_> = (0..ENTRIES_PER_PROCESS)
+                .map(|i| format!("process {p} {i}"))
+                .collect();
+            assert_eq!(written, expected);
+        }
+    }
+
+    #[test]
+    fn concurrent_processes_do_not_lose_entries() {
+        let tmp = tempfile::tempdir().unwrap();
+        check_concurrent_processes("file", &tmp.path().join(".history"));
+    }
+
+    #[cfg(any(feature = "sqlite", feature = "sqlite-dynlib"))]
+    #[test]
+    fn concurrent_processes_do_not_lose_sqlite_entries() {
+        let tmp = tempfile::tempdir().unwrap();
+        check_concurrent_processes("sqlite", &tmp.path().join("history.sqlite3"));
+    }
 }
@@ -0,0 +1,141 @@
+use std::collections::VecDeque;
+
+/// Command lines stored back to back in one string, with the offset each one
+/// starts at
+///
+/// Much smaller and faster to build than a string per entry for histories of
+/// hundreds of thousands of entries. Dropping the oldest entry only forgets
+/// its offset; the text is compacted once the dropped part outweighs the rest.
+#[derive(Debug, Default)]
+pub(super) struct Entries {
+    text: String,
+    /// Start of each entry in `text`, an entry ending where the next one starts
+    starts: VecDeque<usize>,
+}
+
+impl Entries {
+    /// Entries with room for `bytes` of text
+    pub fn with_capacity(bytes: usize) -> Self {
+        Entries {
+            text: String::with_capacity(bytes),
+            starts: VecDeque::new(),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.starts.len()
+    }
+
+    pub fn get(&self, index: usize) -> Option<&str> {
+        (index < self.len()).then(|| &self.text[self.range(index)])
+    }
+
+    pub fn back(&self) -> Option<&str> {
+        self.get(self.len().checked_sub(1)?)
+    }
+
+    /// The entries, oldest first
+    pub fn iter(&self) -> impl DoubleEndedIterator<Item = &str> + ExactSizeIterator {
+        (0..self.len()).map(|index| &self.text[self.range(index)])
+    }
+
+    pub fn push_back(&mut self, entry: &str) {
+        self.starts.push_back(self.text.len());
+        self.text.push_str(entry);
+    }
+
+    /// Drop the `count` oldest entries
+    pub fn drop_front(&mut self, count: usize) {
+        self.starts.drain(..count.min(self.len()));
+        match self.starts.front().copied() {
+            None => self.text.clear(),
+            Some(first) if first > self.text.len() / 2 => {
+                self.text.drain(..first);
+                self.starts.iter_mut().for_each(|start| *start -= first);
+            }
+            Some(_) => {}
+        }
+    }
+
+    pub fn pop_front(&mut self) {
+        self.drop_front(1);
+    }
+
+    /// Keep only the entries for which `keep` is true, in order
+    pub fn retain(&mut self, mut keep: impl FnMut(&str) -> bool) {
+        let mut kept = Entries::with_capacity(self.text.len());
+        kept.extend(self.iter().filter(|entry| keep(entry)));
+        *self = kept;
+    }
+
+    pub fn clear(&mut self) {
+        self.text.clear();
+        self.starts.clear();
+    }
+
+    fn range(&self, index: usize) -> std::ops::Range<usize> {
+        let end = self
+            .starts
+            .get(index + 1)
+            .copied()
+            .unwrap_or(self.text.len());
+        self.starts[index]..end
+    }
+}
+
+impl<T: AsRef<str>> Extend<T> for Entries {
+    fn extend<I: IntoIterator<Item = T>>(&mut self, entries: I) {
+        for entry in entries {
+            self.push_back(entry.as_ref());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    fn entries(lines: &[&str]) -> Entries {
+        let mut entries = Entries::default();
+        entries.extend(lines);
+        entries
+    }
+
+    fn collect(entries: &Entries) -> Vec<&str> {
+        entries.iter().collect()
+    }
+
+    #[test]
+    fn entries_are_kept_apart() {
+        let entries = entries(&["ls", "", "echo a\nb"]);
+        assert_eq!(entries.len(), 3);
+        assert_eq!(entries.get(1), Some(""));
+        assert_eq!(entries.get(3), None);
+        assert_eq!(entries.back(), Some("echo a\nb"));
+        assert_eq!(
+            entries.iter().rev().collect::<Vec<_>>(),
+            ["echo a\nb", "", "ls"]
+        );
+    }
+
+    #[test]
+    fn dropping_old_entries_compacts_the_text() {
+        let mut entries = entries(&["aaaa", "bb", "c"]);
+        entries.pop_front();
+        assert_eq!(collect(&entries), ["bb", "c"]);
+        assert_eq!(entries.text, "bbc");
+
+        entries.push_back("dd");
+        entries.drop_front(5);
+        assert_eq!(entries.len(), 0);
+        assert_eq!(entries.text, "");
+    }
+
+    #[test]
+    fn retain_keeps_the_order() {
+        let mut entries = entries(&["a", "b", "a", "c"]);
+        entries.retain(|entry| entry != "a");
+        assert_eq!(collect(&entries), ["b", "c"]);
+    }
+}
@@ -0,0 +1,274 @@
+//! csh-style history expansion of a submitted line: `!!`, `!n`, `!-n`,
+//! `!prefix`, `!$`, `!*` and the `^old^new` quick substitution.
+
+use std::fmt::{self, Display};
+
+use itertools::Itertools;
+
+/// A previous command referred to by an expansion
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum HistoryEvent<'line> {
+    /// The `n`-th most recent command, 1 being the last one
+    Relative(usize),
+    /// The `n`-th command of the history, counting from 1
+    Absolute(usize),
+    /// The most recent command starting with the text
+    Prefix(&'line str),
+}
+
+/// Why a line couldn't be expanded
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) enum ExpansionError {
+    /// No command matches the reference, e.g. `!42` in a shorter history
+    EventNotFound(String),
+    /// The text to replace by `^old^new` isn't part of the last command
+    SubstitutionFailed(String),
+}
+
+impl Display for ExpansionError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ExpansionError::EventNotFound(reference) => write!(f, "{reference}: event not found"),
+            ExpansionError::SubstitutionFailed(substitution) => {
+                write!(f, "{substitution}: substitution failed")
+            }
+        }
+    }
+}
+
+/// What follows a `!`
+enum Designator<'line> {
+    /// The whole command
+    Event(HistoryEvent<'line>),
+    /// `!$`: the last word of the last command
+    LastWord,
+    /// `!*`: all words but the first of the last command
+    Arguments,
+}
+
+/// Expand the history references in `line`, looking the commands up with
+/// `lookup`
+///
+/// Returns `None` if there is nothing to expand. References in single quotes
+/// are left alone, as is a `!` preceded by a backslash, which is dropped.
+pub(crate) fn expand_history(
+    line: &str,
+    mut lookup: impl FnMut(HistoryEvent) -> Option<String>,
+) -> Result<Option<String>, ExpansionError> {
+    if let Some(substitution) = line.strip_prefix('^') {
+        return quick_substitution(line, substitution, lookup).map(Some);
+    }
+
+    let mut expanded = String::with_capacity(line.len());
+    let mut changed = false;
+    let mut in_single_quotes = false;
+    let mut in_double_quotes = false;
+    let mut chars = line.char_indices().peekable();
+    while let Some((index, c)) = chars.next() {
+        match c {
+            '\'' if !in_double_quotes => in_single_quotes = !in_single_quotes,
+            '"' if !in_single_quotes => in_double_quotes = !in_double_quotes,
+            '\\' if !in_single_quotes => match chars.next() {
+                Some((_, '!')) => {
+                    expanded.push('!');
+                    changed = true;
+                    continue;
+                }
+                // Keep escaped quotes from opening or closing a quoted part
+                Some((_, escaped)) => {
+                    expanded.push('\\');
+                    expanded.push(escaped);
+                    continue;
+                }
+                None => {}
+            },
+            '!' if !in_single_quotes => {
+                let rest = &line[index + 1..];
+                if let Some((designator, len)) = parse_designator(rest, in_double_quotes) {
+                    let reference = &line[index..index + 1 + len];
+                    let text = resolve(designator, &mut lookup)
+                        .ok_or_else(|| ExpansionError::EventNotFound(reference.to_string()))?;
+                    expanded.push_str(&text);
+                    changed = true;
+                    while chars.next_if(|(i, _)| *i < index + 1 + len).is_some() {}
+                    continue;
+                }
+            }
+            _ => {}
+        }
+        expanded.push(c);
+    }
+
+    Ok(changed.then_some(expanded))
+}
+
+/// The designator at the start of `rest`, the text after a `!`, and its length
+///
+/// A `!` followed by whitespace, `=` or `(`, or at the end of the line, stays
+/// as it is.
+fn parse_designator(rest: &str, in_double_quotes: bool) -> Option<(Designator<'_>, usize)> {
+    let digits = |text: &str| {
+        text.find(|c: char| !c.is_ascii_digit())
+            .unwrap_or(text.len())
+    };
+    match rest.chars().next()? {
+        '!' => Some((Designator::Event(HistoryEvent::Relative(1)), 1)),
+        '$' => Some((Designator::LastWord, 1)),
+        '*' => Some((Designator::Arguments, 1)),
+        '-' if digits(&rest[1..]) > 0 => {
+            let len = digits(&rest[1..]);
+            let n = rest[1..=len].parse().unwrap_or(0);
+            Some((Designator::Event(HistoryEvent::Relative(n)), len + 1))
+        }
+        c if c.is_ascii_digit() => {
+            let len = digits(rest);
+            let n = rest[..len].parse().unwrap_or(0);
+            Some((Designator::Event(HistoryEvent::Absolute(n)), len))
+        }
+        c if c.is_whitespace() || c == '=' || c == '(' => None,
+        '"' if in_double_quotes => None,
+        _ => {
+            let len = rest
+                .find(|c: char| c.is_whitespace() || c == '\'' || c == '"')
+                .unwrap_or(rest.len());
+            Some((Designator::Event(HistoryEvent::Prefix(&rest[..len])), len))
+        }
+    }
+}
+
+fn resolve(
+    designator: Designator,
+    lookup: &mut impl FnMut(HistoryEvent) -> Option<String>,
+) -> Option<String> {
+    match designator {
+        Designator::Event(HistoryEvent::Relative(0) | HistoryEvent::Absolute(0)) => None,
+        Designator::Event(event) => lookup(event),
+        Designator::LastWord => lookup(HistoryEvent::Relative(1))
+            .and_then(|command| command.split_whitespace().last().map(str::to_string)),
+        Designator::Arguments => lookup(HistoryEvent::Relative(1))
+            .map(|command| command.split_whitespace().skip(1).join(" ")),
+    }
+}
+
+/// `^old^new^rest`: the last command with the first `old` replaced by `new`,
+/// followed by `rest`
+fn quick_substitution(
+    line: &str,
+    substitution: &str,
+    mut lookup: impl FnMut(HistoryEvent) -> Option<String>,
+) -> Result<String, ExpansionError> {
+    let mut parts = substitution.splitn(3, '^');
+    let old = parts.next().unwrap_or_default();
+    let new = parts.next().unwrap_or_default();
+    let rest = parts.next().unwrap_or_default();
+
+    let last = lookup(HistoryEvent::Relative(1))
+        .ok_or_else(|| ExpansionError::EventNotFound(line.to_string()))?;
+    if old.is_empty() || !last.contains(old) {
+        return Err(ExpansionError::SubstitutionFailed(line.to_string()));
+    }
+    Ok(last.replacen(old, new, 1) + rest)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+    use rstest::rstest;
+
+    const HISTORY: [&str; 4] = [
+        "ls -l /tmp",
+        "git commit -m 'fix'",
+        "cargo build --release",
+        "echo a b c",
+    ];
+
+    fn lookup(event: HistoryEvent) -> Option<String> {
+        let command = match event {
+            HistoryEvent::Relative(n) => HISTORY.len().checked_sub(n).map(|i| HISTORY[i]),
+            HistoryEvent::Absolute(n) => HISTORY.get(n - 1).copied(),
+            HistoryEvent::Prefix(prefix) => HISTORY
+                .iter()
+                .rev()
+                .find(|command| command.starts_with(prefix))
+                .copied(),
+        };
+        command.map(str::to_string)
+    }
+
+    #[rstest]
+    #[case::last_command("sudo !!", "sudo echo a b c")]
+    #[case::last_command_twice("!! && !!", "echo a b c && echo a b c")]
+    #[case::last_word("cat !$", "cat c")]
+    #[case::arguments("printf !*", "printf a b c")]
+    #[case::absolute("!1", "ls -l /tmp")]
+    #[case::relative("!-2", "cargo build --release")]
+    #[case::relative_last("!-1 | less", "echo a b c | less")]
+    #[case::prefix("!git", "git commit -m 'fix'")]
+    #[case::prefix_with_arguments("!ca --verbose", "cargo build --release --verbose")]
+    #[case::in_double_quotes("echo \"!!\"", "echo \"echo a b c\"")]
+    #[case::escaped("echo \\!!", "echo !!")]
+    #[case::escaped_quote("it\\'s !$", "it\\'s c")]
+    #[case::quick_substitution("^echo^printf", "printf a b c")]
+    #[case::quick_substitution_first_only("^ ^_", "echo_a b c")]
+    #[case::quick_substitution_with_rest("^b^x^ d", "echo a x c d")]
+    #[case::quick_substitution_deleting("^ a^", "echo b c")]
+    fn expands(#[case] line: &str, #[case] expected: &str) {
+        assert_eq!(expand_history(line, lookup), Ok(Some(expected.to_string())));
+    }
+
+    #[rstest]
+    #[case::plain("ls -l")]
+    #[case::in_single_quotes("echo '!! !$'")]
+    #[case::single_quotes_in_double_quotes_close_nothing("echo \"'\" '!!'")]
+    #[case::before_space("echo ! done")]
+    #[case::at_the_end("echo hi!")]
+    #[case::before_equals("a != b")]
+    #[case::before_parenthesis("!(x)")]
+    #[case::closing_double_quote("echo \"hi!\"")]
+    #[case::caret_inside("echo a^b^c")]
+    fn leaves_alone(#[case] line: &str) {
+        assert_eq!(expand_history(line, lookup), Ok(None));
+    }
+
+    #[rstest]
+    #[case::absolute_out_of_range("!42", ExpansionError::EventNotFound("!42".into()))]
+    #[case::absolute_zero("echo !0", ExpansionError::EventNotFound("!0".into()))]
+    #[case::relative_zero("!-0", ExpansionError::EventNotFound("!-0".into()))]
+    #[case::relative_out_of_range("!-9", ExpansionError::EventNotFound("!-9".into()))]
+    #[case::unknown_prefix("!nope x", ExpansionError::EventNotFound("!nope".into()))]
+    #[case::huge_number(
+        "!99999999999999999999999",
+        ExpansionError::EventNotFound("!99999999999999999999999".into())
+    )]
+    #[case::missing_text("^zzz^y", ExpansionError::SubstitutionFailed("^zzz^y".into()))]
+    #[case::empty_old_text("^^y", ExpansionError::SubstitutionFailed("^^y".into()))]
+    fn fails(#[case] line: &str, #[case] error: ExpansionError) {
+        assert_eq!(expand_history(line, lookup), Err(error));
+    }
+
+    #[test]
+    fn fails_on_an_empty_history() {
+        let empty = |_: HistoryEvent| None;
+        assert_eq!(
+            expand_history("sudo !!", empty),
+            Err(ExpansionError::EventNotFound("!!".into()))
+        );
+        assert_eq!(
+            expand_history("^a^b", empty),
+            Err(ExpansionError::EventNotFound("^a^b".into()))
+        );
+    }
+
+    #[test]
+    fn error_messages_name_the_reference() {
+        assert_eq!(
+            ExpansionError::EventNotFound("!42".into()).to_string(),
+            "!42: event not found"
+        );
+        assert_eq!(
+            ExpansionError::SubstitutionFailed("^a^b".into()).to_string(),
+            "^a^b: substitution failed"
+        );
+    }
+}
@@ -1,15 +1,18 @@
 use super::{
-    base::CommandLineSearch, History, HistoryItem, HistoryItemId, SearchDirection, SearchQuery,
+    base::CommandLineSearch, entries::Entries, fuzzy::rank_fuzzy_matches, History,
+    HistoryDeduplication, HistoryItem, HistoryItemId, SearchDirection, SearchQuery,
 };
 use crate::{
     result::{ReedlineError, ReedlineErrorVariants},
     HistorySessionId, Result,
 };
 
+use itertools::Either;
 use std::{
-    collections::VecDeque,
+    borrow::Cow,
+    collections::HashSet,
     fs::OpenOptions,
-    io::{BufRead, BufReader, BufWriter, Seek, SeekFrom, Write},
+    io::{BufWriter, Read, Seek, SeekFrom, Write},
     ops::{Deref, DerefMut},
     path::PathBuf,
 };
@@ -24,13 +27,29 @@ pub const NEWLINE_ESCAPE: &str = "<\\n>";
 /// Similar to bash's behavior without HISTTIMEFORMAT.
 /// (See <https://www.gnu.org/software/bash/manual/html_node/Bash-History-Facilities.html>)
 /// If the history is associated to a file all new changes within a given history capacity will be written to disk when History is dropped.
+///
+/// Several sessions can share a file: [`History::sync`] takes an advisory lock
+/// on it, merges the entries other sessions wrote in the meantime, and appends
+/// the new ones. With [`FileBackedHistory::with_sync_on_save`] every entry is
+/// written right away.
+///
+/// The entries are kept in one block of text rather than a string each, and
+/// searches are paged with [`SearchQuery::offset`] and [`SearchQuery::limit`].
+/// The whole file is still read on every sync though: other sessions rewrite
+/// it in place when they truncate it, so entries can't be read lazily by their
+/// offset in the file. Use `SqliteBackedHistory` for histories so large that
+/// reading them at startup is too slow.
 #[derive(Debug)]
 pub struct FileBackedHistory {
     capacity: usize,
-    entries: VecDeque<String>,
+    entries: Entries,
     file: Option<PathBuf>,
     len_on_disk: usize, // Keep track what was previously written to disk
     session: Option<HistorySessionId>,
+    // Entries saved with `HistoryDeduplication::EraseOlder`, whose older copies
+    // in the file are dropped on the next sync
+    erased_from_file: HashSet<String>,
+    sync_on_save: bool,
 }
 
 impl Default for FileBackedHistory {
@@ -53,8 +72,21 @@ fn encode_entry(s: &str) -> String {
     s.replace('\n', NEWLINE_ESCAPE)
 }
 
-fn decode_entry(s: &str) -> String {
-    s.replace(NEWLINE_ESCAPE, "\n")
+/// The results to skip and the most to get for `query`
+fn offset_and_limit(query: &SearchQuery) -> (usize, usize) {
+    let offset = query.offset.map_or(0, |offset| offset.max(0) as usize);
+    let limit = query
+        .limit
+        .map_or(usize::MAX, |limit| limit.max(0) as usize);
+    (offset, limit)
+}
+
+fn decode_entry(s: &str) -> Cow<'_, str> {
+    if s.contains(NEWLINE_ESCAPE) {
+        Cow::Owned(s.replace(NEWLINE_ESCAPE, "\n"))
+    } else {
+        Cow::Borrowed(s)
+    }
 }
 
 impl History for FileBackedHistory {
@@ -62,22 +94,57 @@ impl History for FileBackedHistory {
     fn save(&mut self, h: HistoryItem) -> Result<HistoryItem> {
         let entry = h.command_line;
         // Don't append if the preceding value is identical or the string empty
-        let entry_id =
-            if (self.entries.back() != Some(&entry)) && !entry.is_empty() && self.capacity > 0 {
-                if self.entries.len() == self.capacity {
-                    // History is "full", so we delete the oldest entry first,
-                    // before adding a new one.
-                    self.entries.pop_front();
-                    self.len_on_disk = self.len_on_disk.saturating_sub(1);
-                }
-                self.entries.push_back(entry.to_string());
-                Some(HistoryItemId::new((self.entries.len() - 1) as i64))
-            } else {
-                None
-            };
+        let entry_id = if (self.entries.back() != Some(entry.as_str()))
+            && !entry.is_empty()
+            && self.capacity > 0
+        {
+            Some(self.append_entry(entry.clone())?)
+        } else {
+            None
+        };
         Ok(FileBackedHistory::construct_entry(entry_id, entry))
     }
 
+    fn save_deduplicated(
+        &mut self,
+        h: HistoryItem,
+        deduplication: HistoryDeduplication,
+    ) -> Result<HistoryItem> {
+        let entry = h.command_line;
+        if entry.is_empty() || self.capacity == 0 {
+            return Ok(FileBackedHistory::construct_entry(None, entry));
+        }
+
+        match deduplication {
+            HistoryDeduplication::KeepAll => {}
+            HistoryDeduplication::IgnoreConsecutive => {
+                if self.entries.back() == Some(entry.as_str()) {
+                    let last_id = HistoryItemId::new((self.entries.len() - 1) as i64);
+                    return Ok(FileBackedHistory::construct_entry(Some(last_id), entry));
+                }
+            }
+            HistoryDeduplication::EraseOlder => {
+                let mut index = 0;
+                let mut erased_on_disk = 0;
+                self.entries.retain(|existing| {
+                    let keep = existing != entry;
+                    if !keep && index < self.len_on_disk {
+                        erased_on_disk += 1;
+                    }
+                    index += 1;
+                    keep
+                });
+                self.len_on_disk -= erased_on_disk;
+                if self.file.is_some() {
+                    self.erased_from_file.insert(entry.clone());
+                }
+            }
+        }
+
+        let entry_id = self.append_entry(entry.clone())?;
+        Ok(FileBackedHistory::construct_entry(Some(entry_id), entry))
+    }
+
     fn load(&self, id: HistoryItemId) -> Result<super::HistoryItem> {
         Ok(FileBackedHistory::construct_entry(
             Some(id),
@@ -86,92 +153,32 @@ impl History for FileBackedHistory {
                 .ok_or(ReedlineError(ReedlineErrorVariants::OtherHistoryError(
                     "Item does not exist",
                 )))?
-                .clone(),
+                .to_string(),
         ))
     }
 
     fn count(&self, query: SearchQuery) -> Result<i64> {
-        // todo: this could be done cheaper
-        Ok(self.search(query)?.len() as i64)
+        if let Some(CommandLineSearch::Fuzzy(_)) = &query.filter.command_line {
+            return Ok(self.search(query)?.len() as i64);
+        }
+        let (offset, limit) = offset_and_limit(&query);
+        Ok(self.matching(&query)?.skip(offset).take(limit).count() as i64)
     }
 
     fn search(&self, query: SearchQuery) -> Result<Vec<HistoryItem>> {
-        if query.start_time.is_some() || query.end_time.is_some() {
-            return Err(ReedlineError(
-                ReedlineErrorVariants::HistoryFeatureUnsupported {
-                    history: "FileBackedHistory",
-                    feature: "filtering by time",
-                },
-            ));
-        }
-
-        if query.filter.hostname.is_some()
-            || query.filter.cwd_exact.is_some()
-            || query.filter.cwd_prefix.is_some()
-            || query.filter.exit_successful.is_some()
-        {
-            return Err(ReedlineError(
-                ReedlineErrorVariants::HistoryFeatureUnsupported {
-                    history: "FileBackedHistory",
-                    feature: "filtering by extra info",
-                },
-            ));
-        }
-        let (min_id, max_id) = {
-            let start = query.start_id.map(|e| e.0);
-            let end = query.end_id.map(|e| e.0);
-            if let SearchDirection::Backward = query.direction {
-                (end, start)
-            } else {
-                (start, end)
-            }
-        };
-        // add one to make it inclusive
-        let min_id = min_id.map(|e| e + 1).unwrap_or(0);
-        // subtract one to make it inclusive
-        let max_id = max_id
-            .map(|e| e - 1)
-            .unwrap_or(self.entries.len() as i64 - 1);
-        if max_id < 0 || min_id > self.entries.len() as i64 - 1 {
-            return Ok(vec![]);
-        }
-        let intrinsic_limit = max_id - min_id + 1;
-        let limit = if let Some(given_limit) = query.limit {
-            std::cmp::min(intrinsic_limit, given_limit) as usize
-        } else {
-            intrinsic_limit as usize
-        };
-        let filter = |(idx, cmd): (usize, &String)| {
-            if !match &query.filter.command_line {
-                Some(CommandLineSearch::Prefix(p)) => cmd.starts_with(p),
-                Some(CommandLineSearch::Substring(p)) => cmd.contains(p),
-                Some(CommandLineSearch::Exact(p)) => cmd == p,
-                None => true,
-            } {
-                return None;
-            }
-            if let Some(str) = &query.filter.not_command_line {
-                if cmd == str {
-                    return None;
-                }
-            }
-            Some(FileBackedHistory::construct_entry(
+        let (offset, limit) = offset_and_limit(&query);
+        let to_item = |(idx, cmd): (usize, &str)| {
+            FileBackedHistory::construct_entry(
                 Some(HistoryItemId::new(idx as i64)),
-                cmd.to_string(), // todo: this copy might be a perf bottleneck
-            ))
+                cmd.to_string(),
+            )
         };
-
-        let iter = self
-            .entries
-            .iter()
-            .enumerate()
-            .skip(min_id as usize)
-            .take(intrinsic_limit as usize);
-        if let SearchDirection::Backward = query.direction {
-            Ok(iter.rev().filter_map(filter).take(limit).collect())
-        } else {
-            Ok(iter.filter_map(filter).take(limit).collect())
+        let matching = self.matching(&query)?;
+        if let Some(CommandLineSearch::Fuzzy(needle)) = &query.filter.command_line {
+            let ranked = rank_fuzzy_matches(needle, matching.map(to_item).collect());
+            return Ok(ranked.into_iter().skip(offset).take(limit).collect());
         }
+        Ok(matching.skip(offset).take(limit).map(to_item).collect())
     }
 
     fn update(
@@ -190,6 +197,7 @@ impl History for FileBackedHistory {
     fn clear(&mut self) -> Result<()> {
         self.entries.clear();
         self.len_on_disk = 0;
+        self.erased_from_file.clear();
 
         if let Some(file) = &self.file {
             if let Err(err) = std::fs::remove_file(file) {
@@ -211,11 +219,16 @@ impl History for FileBackedHistory {
 
     /// Writes unwritten history contents to disk.
     ///
-    /// If file would exceed `capacity` truncates the oldest entries.
+    /// If file would exceed `capacity` truncates the oldest entries. Older copies
+    /// of entries saved with [`HistoryDeduplication::EraseOlder`] are removed
+    /// from the file.
+    ///
+    /// Reads the whole file to merge the entries of other sessions, which may
+    /// have rewritten it since the last sync.
     fn sync(&mut self) -> std::io::Result<()> {
         if let Some(fname) = &self.file {
             // The unwritten entries
-            let own_entries = self.entries.range(self.len_on_disk..);
+            let own_entries = self.entries.iter().skip(self.len_on_disk);
 
             if let Some(base_dir) = fname.parent() {
                 std::fs::create_dir_all(base_dir)?;
@@ -231,20 +244,22 @@ impl History for FileBackedHistory {
             );
             let mut writer_guard = f_lock.write()?;
             let (mut foreign_entries, truncate) = {
-                let reader = BufReader::new(writer_guard.deref());
-                let mut from_file = reader
-                    .lines()
-                    .map(|o| o.map(|i| decode_entry(&i)))
-                    .collect::<std::io::Result<VecDeque<_>>>()?;
+                let mut text = String::new();
+                writer_guard.deref().read_to_string(&mut text)?;
+                let mut from_file = Entries::with_capacity(text.len());
+                from_file.extend(text.lines().map(decode_entry));
+                let len_in_file = from_file.len();
+                if !self.erased_from_file.is_empty() {
+                    from_file.retain(|line| !self.erased_from_file.contains(line));
+                }
+                let erased = from_file.len() != len_in_file;
                 if from_file.len() + own_entries.len() > self.capacity {
-                    (
-                        from_file.split_off(
-                            from_file.len() - (self.capacity.saturating_sub(own_entries.len())),
-                        ),
-                        true,
-                    )
+                    from_file.drop_front(
+                        from_file.len() - (self.capacity.saturating_sub(own_entries.len())),
+                    );
+                    (from_file, true)
                 } else {
-                    (from_file, false)
+                    (from_file, erased)
                 }
             };
 
@@ -253,7 +268,7 @@ impl History for FileBackedHistory {
                 if truncate {
                     writer.rewind()?;
 
-                    for line in &foreign_entries {
+                    for line in foreign_entries.iter() {
                         writer.write_all(encode_entry(line).as_bytes())?;
                         writer.write_all("\n".as_bytes())?;
                     }
@@ -272,11 +287,11 @@ impl History for FileBackedHistory {
                 file.set_len(file_len)?;
             }
 
-            let own_entries = self.entries.drain(self.len_on_disk..);
-            foreign_entries.extend(own_entries);
+            foreign_entries.extend(self.entries.iter().skip(self.len_on_disk));
             self.entries = foreign_entries;
 
             self.len_on_disk = self.entries.len();
+            self.erased_from_file.clear();
         }
         Ok(())
     }
@@ -298,10 +313,12 @@ impl FileBackedHistory {
 
         Ok(FileBackedHistory {
             capacity,
-            entries: VecDeque::new(),
+            entries: Entries::default(),
             file: None,
             len_on_disk: 0,
             session: None,
+            erased_from_file: HashSet::new(),
+            sync_on_save: false,
         })
     }
 
@@ -323,6 +340,32 @@ impl FileBackedHistory {
         Ok(hist)
     }
 
+    /// Builder writing every saved entry to the file right away
+    ///
+    /// Entries of other sessions sharing the file are picked up on every save,
+    /// and a crashing session loses none of its entries.
+    #[must_use]
+    pub fn with_sync_on_save(mut self, sync_on_save: bool) -> Self {
+        self.sync_on_save = sync_on_save;
+        self
+    }
+
+    /// Append `entry`, dropping the oldest entry if the history is full
+    fn append_entry(&mut self, entry: String) -> Result<HistoryItemId> {
+        if self.entries.len() == self.capacity {
+            // History is "full", so we delete the oldest entry first,
+            // before adding a new one.
+            self.entries.pop_front();
+            self.len_on_disk = self.len_on_disk.saturating_sub(1);
+        }
+        self.entries.push_back(&entry);
+        if self.sync_on_save {
+            // Our entries come after the merged ones, so the new entry stays last
+            self.sync()?;
+        }
+        Ok(HistoryItemId::new((self.entries.len() - 1) as i64))
+    }
+
     // this history doesn't store any info except command line
     fn construct_entry(id: Option<HistoryItemId>, command_line: String) -> HistoryItem {
         HistoryItem {
@@ -337,6 +380,81 @@ impl FileBackedHistory {
             more_info: None,
         }
     }
+
+    /// The entries in the id range of `query` whose command line matches, in
+    /// its direction
+    ///
+    /// Fuzzy searches are left to rank, limit and offset by the caller.
+    fn matching<'a>(
+        &'a self,
+        query: &'a SearchQuery,
+    ) -> Result<impl Iterator<Item = (usize, &'a str)> + 'a> {
+        if query.start_time.is_some() || query.end_time.is_some() {
+            return Err(ReedlineError(
+                ReedlineErrorVariants::HistoryFeatureUnsupported {
+                    history: "FileBackedHistory",
+                    feature: "filtering by time",
+                },
+            ));
+        }
+
+        if query.filter.hostname.is_some()
+            || query.filter.cwd_exact.is_some()
+            || query.filter.cwd_prefix.is_some()
+            || query.filter.exit_successful.is_some()
+            || query.filter.duration_at_least.is_some()
+            || query.filter.session_exact.is_some()
+        {
+            return Err(ReedlineError(
+                ReedlineErrorVariants::HistoryFeatureUnsupported {
+                    history: "FileBackedHistory",
+                    feature: "filtering by extra info",
+                },
+            ));
+        }
+        let (min_id, max_id) = {
+            let start = query.start_id.map(|e| e.0);
+            let end = query.end_id.map(|e| e.0);
+            if let SearchDirection::Backward = query.direction {
+                (end, start)
+            } else {
+                (start, end)
+            }
+        };
+        // add one to make it inclusive
+        let min_id = min_id.map(|e| e + 1).unwrap_or(0).max(0);
+        // subtract one to make it inclusive
+        let max_id = max_id
+            .map(|e| e - 1)
+            .unwrap_or(self.entries.len() as i64 - 1);
+        let in_range = if max_id < min_id || min_id > self.entries.len() as i64 - 1 {
+            0
+        } else {
+            (max_id - min_id + 1) as usize
+        };
+        let filter = move |(_, cmd): &(usize, &str)| {
+            let matches = match &query.filter.command_line {
+                Some(CommandLineSearch::Prefix(p)) => cmd.starts_with(p),
+                Some(CommandLineSearch::Substring(p)) => cmd.contains(p),
+                Some(CommandLineSearch::Exact(p)) => cmd == p,
+                // Ranked and filtered by the caller
+                Some(CommandLineSearch::Fuzzy(_)) | None => true,
+            };
+            matches && query.filter.not_command_line.as_deref() != Some(*cmd)
+        };
+
+        let iter = self
+            .entries
+            .iter()
+            .enumerate()
+            .skip(min_id as usize)
+            .take(in_range);
+        Ok(if let SearchDirection::Backward = query.direction {
+            Either::Left(iter.rev().filter(filter))
+        } else {
+            Either::Right(iter.filter(filter))
+        })
+    }
 }
 
 impl Drop for FileBackedHistory {
@@ -0,0 +1,155 @@
+//! Fuzzy matching of history entries: the characters of the search text have
+//! to appear in order, but not necessarily next to each other.
+
+use std::cmp::Reverse;
+
+use super::HistoryItem;
+
+/// Character positions of the most compact match of `needle` in `haystack`
+pub(crate) fn best_match(needle: &[char], haystack: &[char]) -> Option<Vec<usize>> {
+    let Some((&first, rest)) = needle.split_first() else {
+        return Some(Vec::new());
+    };
+    let mut best: Option<Vec<usize>> = None;
+
+    for start in (0..haystack.len()).filter(|&i| haystack[i] == first) {
+        // Greedily matching the rest gives the earliest end for this start
+        let mut positions = vec![start];
+        let mut remaining = rest.iter().peekable();
+        for (i, c) in haystack.iter().enumerate().skip(start + 1) {
+            match remaining.peek() {
+                Some(&&next) if next == *c => {
+                    positions.push(i);
+                    remaining.next();
+                }
+                Some(_) => {}
+                None => break,
+            }
+        }
+        if remaining.peek().is_some() {
+            // Later starts can't match either
+            break;
+        }
+
+        let span = |positions: &[usize]| positions[positions.len() - 1] - positions[0];
+        if best
+            .as_ref()
+            .map_or(true, |best| span(&positions) < span(best))
+        {
+            best = Some(positions);
+        }
+    }
+    best
+}
+
+/// Byte offsets of the characters of `needle` in the most compact fuzzy match
+/// in `haystack`, or `None` if `haystack` doesn't contain them in order
+///
+/// Matching is case-sensitive, like the other [`CommandLineSearch`](super::CommandLineSearch) modes.
+pub(crate) fn fuzzy_match_indices(needle: &str, haystack: &str) -> Option<Vec<usize>> {
+    let needle: Vec<char> = needle.chars().collect();
+    let (offsets, chars): (Vec<usize>, Vec<char>) = haystack.char_indices().unzip();
+    let positions = best_match(&needle, &chars)?;
+    Some(positions.into_iter().map(|i| offsets[i]).collect())
+}
+
+/// Number of characters between the matched ones in the most compact fuzzy
+/// match of `needle` in `haystack`
+fn fuzzy_gaps(needle: &[char], haystack: &str) -> Option<usize> {
+    let chars: Vec<char> = haystack.chars().collect();
+    let positions = best_match(needle, &chars)?;
+    Some(match (positions.first(), positions.last()) {
+        (Some(first), Some(last)) => last - first + 1 - positions.len(),
+        _ => 0,
+    })
+}
+
+/// Keep the entries fuzzy matching `needle`, best match first
+///
+/// Entries are ranked by the compactness of their match; among equally
+/// compact matches the most recent entry (highest id) comes first, and entries
+/// without an id keep their relative order.
+pub(crate) fn rank_fuzzy_matches(needle: &str, items: Vec<HistoryItem>) -> Vec<HistoryItem> {
+    let needle: Vec<char> = needle.chars().collect();
+    let mut scored: Vec<_> = items
+        .into_iter()
+        .filter_map(|item| Some((fuzzy_gaps(&needle, &item.command_line)?, item)))
+        .collect();
+    scored.sort_by_key(|(gaps, item)| (*gaps, Reverse(item.id.map(|id| id.0))));
+    scored.into_iter().map(|(_, item)| item).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::HistoryItemId;
+    use pretty_assertions::assert_eq;
+
+    fn item(id: i64, command_line: &str) -> HistoryItem {
+        HistoryItem {
+            id: Some(HistoryItemId::new(id)),
+            ..HistoryItem::from_command_line(command_line)
+        }
+    }
+
+    #[test]
+    fn matches_characters_in_order() {
+        assert_eq!(
+            fuzzy_match_indices("gcm", "git commit -m"),
+            Some(vec![0, 4, 6])
+        );
+        assert_eq!(fuzzy_match_indices("mcg", "git commit -m"), None);
+        assert_eq!(fuzzy_match_indices("", "anything"), Some(vec![]));
+    }
+
+    #[test]
+    fn prefers_the_most_compact_match() {
+        // The greedy match starting at the first `a` spans the whole line
+        assert_eq!(fuzzy_match_indices("ab", "a----xab"), Some(vec![6, 7]));
+    }
+
+    #[test]
+    fn matches_non_ascii_by_character() {
+        assert_eq!(fuzzy_match_indices("äöü", "cd ä/ö/ü"), Some(vec![3, 6, 9]));
+        assert_eq!(fuzzy_match_indices("日本", "echo 日x本"), Some(vec![5, 9]));
+        assert_eq!(fuzzy_match_indices("Ä", "ä"), None);
+    }
+
+    #[test]
+    fn ranks_compact_matches_first_then_recent() {
+        let items = vec![
+            item(0, "git commit -m"),
+            item(1, "gcm"),
+            item(2, "ls"),
+            item(3, "git checkout main"),
+            item(4, "g c m"),
+        ];
+
+        let ranked: Vec<_> = rank_fuzzy_matches("gcm", items)
+            .into_iter()
+            .map(|item| item.command_line)
+            .collect();
+
+        assert_eq!(
+            ranked,
+            vec!["gcm", "g c m", "git commit -m", "git checkout main"]
+        );
+    }
+
+    #[test]
+    fn ranking_is_stable_for_equal_scores() {
+        let items = vec![item(0, "a-b"), item(1, "a_b"), item(2, "a.b")];
+
+        let ranked: Vec<_> = rank_fuzzy_matches("ab", items.clone())
+            .into_iter()
+            .map(|item| item.command_line)
+            .collect();
+        let reversed: Vec<_> = rank_fuzzy_matches("ab", items.into_iter().rev().collect())
+            .into_iter()
+            .map(|item| item.command_line)
+            .collect();
+
+        assert_eq!(ranked, vec!["a.b", "a_b", "a-b"]);
+        assert_eq!(ranked, reversed);
+    }
+}
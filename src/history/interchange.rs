@@ -0,0 +1,286 @@
+//! Moving entries between [`History`] backends.
+
+use super::{History, HistoryDeduplication, HistoryItem, SearchDirection, SearchQuery};
+use crate::{ReedlineError, Result};
+#[cfg(feature = "history_json")]
+use std::io::{BufRead, Write};
+
+/// Items fetched from a source history per search
+const PAGE_SIZE: i64 = 1000;
+
+/// Outcome of [`migrate`] or [`History::import_from`]
+#[derive(Debug, Default)]
+pub struct MigrationReport {
+    /// Items read from the source
+    pub read: usize,
+    /// Items handed to the target without error
+    ///
+    /// The target may still have dropped some of them, e.g. as duplicates.
+    pub saved: usize,
+    /// Items that couldn't be read or saved: their position in the source,
+    /// counting from 1, and the error
+    pub failures: Vec<(usize, ReedlineError)>,
+}
+
+impl MigrationReport {
+    fn save(
+        &mut self,
+        target: &mut (impl History + ?Sized),
+        mut item: HistoryItem,
+        deduplication: HistoryDeduplication,
+    ) {
+        self.read += 1;
+        item.id = None;
+        match target.save_deduplicated(item, deduplication) {
+            Ok(_) => self.saved += 1,
+            Err(err) => self.failures.push((self.read, err)),
+        }
+    }
+}
+
+/// Copy all items of `from` to `to`, oldest first
+///
+/// With the `history_json` feature, [`History::export_to`] and
+/// [`History::import_from`] do the same through a file in JSON lines format: one [`HistoryItem`] per line, oldest first, in
+/// its serde representation:
+///
+/// ```text
+/// {"id":1,"start_timestamp":"2024-05-01T09:30:00Z","command_line":"ls -l","session_id":42,"hostname":"foohost","cwd":"/home/me","duration":{"secs":1,"nanos":500000000},"exit_status":0,"more_info":null}
+/// ```
+///
+/// Every field but `command_line` may be `null` or left out. The `id` is only
+/// informative: the importing history assigns its own ids.
+///
+/// Repeated command lines are handled according to `deduplication`. Timestamps,
+/// session ids and the other metadata are kept as far as `to` stores them.
+/// Failing items are reported and skipped; if reading `from` fails, the
+/// migration stops there. Call [`History::sync`] on `to` to write it out.
+pub fn migrate(
+    from: &dyn History,
+    to: &mut dyn History,
+    deduplication: HistoryDeduplication,
+) -> MigrationReport {
+    let mut report = MigrationReport::default();
+    if let Err(err) = for_each_item(from, |item| {
+        report.save(to, item, deduplication);
+        Ok(())
+    }) {
+        report.failures.push((report.read + 1, err));
+    }
+    report
+}
+
+#[cfg(feature = "history_json")]
+pub(super) fn export_to(
+    history: &(impl History + ?Sized),
+    writer: &mut dyn Write,
+) -> Result<usize> {
+    let mut written = 0;
+    for_each_item(history, |item| {
+        serde_json::to_writer(&mut *writer, &item).map_err(std::io::Error::from)?;
+        writer.write_all(b"\n")?;
+        written += 1;
+        Ok(())
+    })?;
+    writer.flush()?;
+    Ok(written)
+}
+
+#[cfg(feature = "history_json")]
+pub(super) fn import_from(
+    history: &mut (impl History + ?Sized),
+    reader: &mut dyn BufRead,
+    deduplication: HistoryDeduplication,
+) -> Result<MigrationReport> {
+    let mut report = MigrationReport::default();
+    for line in reader.lines() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        match serde_json::from_str(&line) {
+            Ok(item) => report.save(history, item, deduplication),
+            Err(err) => {
+                report.read += 1;
+                let err = std::io::Error::from(err).into();
+                report.failures.push((report.read, err));
+            }
+        }
+    }
+    Ok(report)
+}
+
+/// Call `f` on all items of `history`, oldest first, a page at a time
+fn for_each_item(
+    history: &(impl History + ?Sized),
+    mut f: impl FnMut(HistoryItem) -> Result<()>,
+) -> Result<()> {
+    let mut start_id = None;
+    loop {
+        let page = history.search(SearchQuery {
+            start_id,
+            limit: Some(PAGE_SIZE),
+            ..SearchQuery::everything(SearchDirection::Forward, None)
+        })?;
+        let is_full = page.len() as i64 == PAGE_SIZE;
+        start_id = page.last().and_then(|item| item.id);
+        for item in page {
+            f(item)?;
+        }
+        if !is_full || start_id.is_none() {
+            return Ok(());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{FileBackedHistory, HISTORY_SIZE};
+    use pretty_assertions::assert_eq;
+
+    fn command_lines(history: &dyn History) -> Vec<String> {
+        history
+            .search(SearchQuery::everything(SearchDirection::Forward, None))
+            .unwrap()
+            .into_iter()
+            .map(|item| item.command_line)
+            .collect()
+    }
+
+    fn file_history(commands: &[impl AsRef<str>]) -> FileBackedHistory {
+        let mut history = FileBackedHistory::new(commands.len().max(HISTORY_SIZE)).unwrap();
+        for command in commands {
+            history
+                .save(HistoryItem::from_command_line(command.as_ref()))
+                .unwrap();
+        }
+        history
+    }
+
+    #[cfg(feature = "history_json")]
+    #[test]
+    fn export_writes_one_line_per_item_oldest_first() -> Result<()> {
+        let history = file_history(&["ls", "echo 'a\nb'"]);
+        let mut exported = Vec::new();
+
+        assert_eq!(history.export_to(&mut exported)?, 2);
+
+        let lines: Vec<HistoryItem> = String::from_utf8(exported)
+            .unwrap()
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        assert_eq!(lines[0].command_line, "ls");
+        assert_eq!(lines[1].command_line, "echo 'a\nb'");
+        Ok(())
+    }
+
+    #[cfg(feature = "history_json")]
+    #[test]
+    fn import_reports_malformed_lines_and_keeps_going() -> Result<()> {
+        let input =
+            "{\"command_line\":\"ls\"}\nnot json\n\n{\"command_line\":\"pwd\",\"cwd\":\"/\"}\n";
+        let mut history = FileBackedHistory::default();
+
+        let report = history.import_from(&mut input.as_bytes(), HistoryDeduplication::KeepAll)?;
+
+        assert_eq!((report.read, report.saved), (3, 2));
+        assert_eq!(
+            report.failures.iter().map(|(n, _)| *n).collect::<Vec<_>>(),
+            vec![2]
+        );
+        assert_eq!(command_lines(&history), vec!["ls", "pwd"]);
+        Ok(())
+    }
+
+    #[test]
+    fn migrate_pages_through_large_histories() {
+        let commands: Vec<String> = (0..2500).map(|i| format!("echo {i}")).collect();
+        let from = file_history(&commands);
+        let mut to = FileBackedHistory::new(3000).unwrap();
+
+        let report = migrate(&from, &mut to, HistoryDeduplication::KeepAll);
+
+        assert_eq!((report.read, report.saved), (2500, 2500));
+        assert!(report.failures.is_empty());
+        assert_eq!(command_lines(&to), commands);
+    }
+
+    #[cfg(any(feature = "sqlite", feature = "sqlite-dynlib"))]
+    mod sqlite {
+        use super::*;
+        use crate::SqliteBackedHistory;
+        use pretty_assertions::assert_eq;
+
+        #[test]
+        fn file_to_sqlite_to_file_keeps_the_command_lines() {
+            let file = file_history(&["cd ~", "ls", "cat\nmultiline", "ls -l"]);
+            let mut sqlite = SqliteBackedHistory::in_memory().unwrap();
+            let mut back = FileBackedHistory::default();
+
+            let to_sqlite = migrate(&file, &mut sqlite, HistoryDeduplication::KeepAll);
+            let to_file = migrate(&sqlite, &mut back, HistoryDeduplication::KeepAll);
+
+            assert_eq!((to_sqlite.saved, to_file.saved), (4, 4));
+            assert_eq!(command_lines(&back), command_lines(&file));
+        }
+
+        #[cfg(feature = "history_json")]
+        #[test]
+        fn sqlite_round_trip_keeps_the_metadata() -> Result<()> {
+            use crate::{HistoryItemId, HistorySessionId};
+            use chrono::{TimeZone, Utc};
+            use std::time::Duration;
+
+            let rich_item = |i: i64, command_line: &str| HistoryItem {
+                id: None,
+                start_timestamp: Some(Utc.timestamp_millis_opt(1_700_000_000_000 + i).unwrap()),
+                command_line: command_line.to_string(),
+                session_id: Some(HistorySessionId::new(7)),
+                hostname: Some("foohost".to_string()),
+                cwd: Some(format!("/home/me/{i}")),
+                duration: Some(Duration::from_millis(100 * i as u64)),
+                exit_status: Some(i % 2),
+                more_info: None,
+            };
+            let mut from = SqliteBackedHistory::in_memory()?;
+            for (i, command) in ["cargo build", "cargo test", "git push"].iter().enumerate() {
+                from.save(rich_item(i as i64, command))?;
+            }
+            let mut exported = Vec::new();
+            from.export_to(&mut exported)?;
+            let mut to = SqliteBackedHistory::in_memory()?;
+            to.save(HistoryItem::from_command_line("already here"))?;
+
+            let report = to.import_from(&mut &exported[..], HistoryDeduplication::KeepAll)?;
+
+            assert_eq!(report.saved, 3);
+            let imported: Vec<_> = to
+                .search(SearchQuery::everything(SearchDirection::Forward, None))?
+                .into_iter()
+                .skip(1)
+                .map(|item| HistoryItem { id: None, ..item })
+                .collect();
+            let expected: Vec<_> = ["cargo build", "cargo test", "git push"]
+                .iter()
+                .enumerate()
+                .map(|(i, command)| rich_item(i as i64, command))
+                .collect();
+            assert_eq!(imported, expected);
+            assert_eq!(to.load(HistoryItemId::new(2))?.command_line, "cargo build");
+            Ok(())
+        }
+
+        #[test]
+        fn migrate_deduplicates_per_the_given_policy() {
+            let file = file_history(&["ls", "cd /", "ls", "pwd", "ls"]);
+            let mut sqlite = SqliteBackedHistory::in_memory().unwrap();
+
+            let report = migrate(&file, &mut sqlite, HistoryDeduplication::EraseOlder);
+
+            assert_eq!((report.read, report.saved), (5, 5));
+            assert_eq!(command_lines(&sqlite), vec!["cd /", "pwd", "ls"]);
+        }
+    }
+}
@@ -1,18 +1,32 @@
 mod base;
 mod cursor;
+mod entries;
+mod expansion;
 mod file_backed;
+mod fuzzy;
+mod interchange;
 mod item;
 #[cfg(any(feature = "sqlite", feature = "sqlite-dynlib"))]
 mod sqlite_backed;
+#[cfg(all(
+    any(feature = "sqlite", feature = "sqlite-dynlib"),
+    feature = "sqlite-encryption"
+))]
+mod sqlite_encryption;
 #[cfg(any(feature = "sqlite", feature = "sqlite-dynlib"))]
 pub use sqlite_backed::SqliteBackedHistory;
 
 pub use base::{
-    CommandLineSearch, History, HistoryNavigationQuery, SearchDirection, SearchFilter, SearchQuery,
+    CommandLineSearch, History, HistoryDeduplication, HistoryNavigationQuery,
+    HistoryNavigationScope, HistorySearchMode, SearchDirection, SearchFilter, SearchQuery,
 };
 pub use cursor::HistoryCursor;
+pub(crate) use cursor::HistoryMatches;
+pub use interchange::{migrate, MigrationReport};
 pub use item::{
     HistoryItem, HistoryItemExtraInfo, HistoryItemId, HistorySessionId, IgnoreAllExtraInfo,
 };
 
+pub(crate) use expansion::{expand_history, HistoryEvent};
 pub use file_backed::{FileBackedHistory, HISTORY_SIZE};
+pub(crate) use fuzzy::{best_match, fuzzy_match_indices};
@@ -1,26 +1,42 @@
+#[cfg(feature = "sqlite-encryption")]
+use super::sqlite_encryption::{encryption_error, matches_sealed_filters, RowCipher};
 use super::{
-    base::{CommandLineSearch, SearchDirection, SearchQuery},
-    History, HistoryItem, HistoryItemId, HistorySessionId,
+    base::{
+        save_deduplicated_by_search, CommandLineSearch, SearchDirection, SearchQuery,
+        PATH_SEPARATORS,
+    },
+    fuzzy::rank_fuzzy_matches,
+    History, HistoryDeduplication, HistoryItem, HistoryItemId, HistorySessionId,
 };
 use crate::{
     result::{ReedlineError, ReedlineErrorVariants},
     Result,
 };
 use chrono::{TimeZone, Utc};
-use rusqlite::{named_params, params, Connection, ToSql};
+use rusqlite::{named_params, params, Connection, OptionalExtension, ToSql};
 use std::{path::PathBuf, time::Duration};
 const SQLITE_APPLICATION_ID: i32 = 1151497937;
+/// How long a save waits for another session holding the database lock
+const BUSY_TIMEOUT: Duration = Duration::from_secs(5);
 
 /// A history that stores the values to an SQLite database.
 /// In addition to storing the command, the history can store an additional arbitrary HistoryEntryContext,
 /// to add information such as a timestamp, running directory, result...
 ///
+/// With [`SqliteBackedHistory::with_encryption`] the entries are encrypted
+/// at rest.
+///
 /// ## Required feature:
 /// `sqlite` or `sqlite-dynlib`
 pub struct SqliteBackedHistory {
     db: rusqlite::Connection,
     session: Option<HistorySessionId>,
     session_timestamp: Option<chrono::DateTime<Utc>>,
+    /// Whether the entries are encrypted, in which case only a history given
+    /// the key reads or adds to them
+    encrypted: bool,
+    #[cfg(feature = "sqlite-encryption")]
+    cipher: Option<RowCipher>,
 }
 
 fn deserialize_history_item(row: &rusqlite::Row) -> rusqlite::Result<HistoryItem> {
@@ -58,14 +74,21 @@ fn deserialize_history_item(row: &rusqlite::Row) -> rusqlite::Result<HistoryItem
     })
 }
 
+/// The item of a row and its encrypted columns, if it has them
+fn deserialize_row(row: &rusqlite::Row) -> rusqlite::Result<(HistoryItem, Option<Vec<u8>>)> {
+    Ok((deserialize_history_item(row)?, row.get("sealed")?))
+}
+
 impl History for SqliteBackedHistory {
     fn save(&mut self, mut entry: HistoryItem) -> Result<HistoryItem> {
+        self.ensure_unlocked()?;
+        let (stored, sealed) = self.stored_columns(&entry);
         let ret: i64 = self
             .db
             .prepare(
                 "insert into history
-                               (id,  start_timestamp,  command_line,  session_id,  hostname,  cwd,  duration_ms,  exit_status,  more_info)
-                        values (:id, :start_timestamp, :command_line, :session_id, :hostname, :cwd, :duration_ms, :exit_status, :more_info)
+                               (id,  start_timestamp,  command_line,  session_id,  hostname,  cwd,  duration_ms,  exit_status,  more_info,  sealed)
+                        values (:id, :start_timestamp, :command_line, :session_id, :hostname, :cwd, :duration_ms, :exit_status, :more_info, :sealed)
                     on conflict (history.id) do update set
                         start_timestamp = excluded.start_timestamp,
                         command_line = excluded.command_line,
@@ -74,15 +97,95 @@ impl History for SqliteBackedHistory {
                         cwd = excluded.cwd,
                         duration_ms = excluded.duration_ms,
                         exit_status = excluded.exit_status,
-                        more_info = excluded.more_info
+                        more_info = excluded.more_info,
+                        sealed = excluded.sealed
                     returning id",
             )
             .map_err(map_sqlite_err)?
             .query_row(
                 named_params! {
-                    ":id": entry.id.map(|id| id.0),
+                    ":id": stored.id.map(|id| id.0),
+                    ":start_timestamp": stored.start_timestamp.map(|e| e.timestamp_millis()),
+                    ":command_line": stored.command_line,
+                    ":session_id": stored.session_id.map(|e| e.0),
+                    ":hostname": stored.hostname,
+                    ":cwd": stored.cwd,
+                    ":duration_ms": stored.duration.map(|e| e.as_millis() as i64),
+                    ":exit_status": stored.exit_status,
+                    ":more_info": stored.more_info.as_ref().map(|e| serde_json::to_string(e).unwrap()),
+                    ":sealed": sealed,
+                },
+                |row| row.get(0),
+            )
+            .map_err(map_sqlite_err)?;
+        entry.id = Some(HistoryItemId::new(ret));
+        Ok(entry)
+    }
+
+    /// Repeated entries update the timestamp and context of the existing row
+    /// instead of inserting a new one. With [`HistoryDeduplication::EraseOlder`]
+    /// the row also moves to the front and its older copies are deleted.
+    ///
+    /// Encrypted entries can't be looked up by their command line, so they are
+    /// deduplicated like the default implementation does.
+    fn save_deduplicated(
+        &mut self,
+        mut entry: HistoryItem,
+        deduplication: HistoryDeduplication,
+    ) -> Result<HistoryItem> {
+        if self.encrypted {
+            return save_deduplicated_by_search(self, entry, deduplication);
+        }
+        let existing_sql = match deduplication {
+            HistoryDeduplication::KeepAll => return self.save(entry),
+            HistoryDeduplication::IgnoreConsecutive => {
+                "select id from history
+                    where id = (select max(id) from history) and command_line = :command_line"
+            }
+            HistoryDeduplication::EraseOlder => {
+                "select max(id) from history where command_line = :command_line"
+            }
+        };
+        let existing: Option<i64> = self
+            .db
+            .query_row(
+                existing_sql,
+                named_params! { ":command_line": entry.command_line },
+                |row| row.get(0),
+            )
+            .optional()
+            .map_err(map_sqlite_err)?
+            .flatten();
+        let Some(existing) = existing else {
+            return self.save(entry);
+        };
+
+        // in theory this should run in a transaction
+        if deduplication == HistoryDeduplication::EraseOlder {
+            self.db
+                .execute(
+                    "delete from history where command_line = :command_line and id != :id",
+                    named_params! { ":command_line": entry.command_line, ":id": existing },
+                )
+                .map_err(map_sqlite_err)?;
+        }
+        let id: i64 = self
+            .db
+            .query_row(
+                "update history set
+                        id = (select case max(id) when :id then :id else max(id) + 1 end from history),
+                        start_timestamp = :start_timestamp,
+                        session_id = :session_id,
+                        hostname = :hostname,
+                        cwd = :cwd,
+                        duration_ms = :duration_ms,
+                        exit_status = :exit_status,
+                        more_info = :more_info
+                    where id = :id
+                    returning id",
+                named_params! {
+                    ":id": existing,
                     ":start_timestamp": entry.start_timestamp.map(|e| e.timestamp_millis()),
-                    ":command_line": entry.command_line,
                     ":session_id": entry.session_id.map(|e| e.0),
                     ":hostname": entry.hostname,
                     ":cwd": entry.cwd,
@@ -93,26 +196,33 @@ impl History for SqliteBackedHistory {
                 |row| row.get(0),
             )
             .map_err(map_sqlite_err)?;
-        entry.id = Some(HistoryItemId::new(ret));
+        entry.id = Some(HistoryItemId::new(id));
         Ok(entry)
     }
 
     fn load(&self, id: HistoryItemId) -> Result<HistoryItem> {
-        let entry = self
+        self.ensure_unlocked()?;
+        let row = self
             .db
             .prepare("select * from history where id = :id")
             .map_err(map_sqlite_err)?
-            .query_row(named_params! { ":id": id.0 }, deserialize_history_item)
+            .query_row(named_params! { ":id": id.0 }, deserialize_row)
             .map_err(map_sqlite_err)?;
-        Ok(entry)
+        self.open_row(row)
     }
 
     fn count(&self, query: SearchQuery) -> Result<i64> {
-        let (query, params) = self.construct_query(&query, "coalesce(count(*), 0)");
+        self.ensure_unlocked()?;
+        if self.encrypted {
+            return Ok(self.search(query)?.len() as i64);
+        }
+        // Fuzzy matching only orders the same entries, so the limit and offset
+        // can be applied right away
+        let (query, params) = self.construct_query(&query, "id", true);
         let params_borrow: Vec<(&str, &dyn ToSql)> = params.iter().map(|e| (e.0, &*e.1)).collect();
         let result: i64 = self
             .db
-            .prepare(&query)
+            .prepare(&format!("SELECT count(*) FROM ({query})"))
             .unwrap()
             .query_row(&params_borrow[..], |r| r.get(0))
             .map_err(map_sqlite_err)?;
@@ -120,16 +230,40 @@ impl History for SqliteBackedHistory {
     }
 
     fn search(&self, query: SearchQuery) -> Result<Vec<HistoryItem>> {
-        let (query, params) = self.construct_query(&query, "*");
+        self.ensure_unlocked()?;
+        let is_fuzzy = matches!(query.filter.command_line, Some(CommandLineSearch::Fuzzy(_)));
+        // Fuzzy results are paged after ranking them
+        let (sql, params) = self.construct_query(&query, "*", !is_fuzzy);
         let params_borrow: Vec<(&str, &dyn ToSql)> = params.iter().map(|e| (e.0, &*e.1)).collect();
-        let results: Vec<HistoryItem> = self
+        let results = self
             .db
-            .prepare(&query)
+            .prepare(&sql)
             .unwrap()
-            .query_map(&params_borrow[..], deserialize_history_item)
+            .query_map(&params_borrow[..], deserialize_row)
             .map_err(map_sqlite_err)?
-            .collect::<rusqlite::Result<Vec<HistoryItem>>>()
-            .map_err(map_sqlite_err)?;
+            .map(|row| self.open_row(row.map_err(map_sqlite_err)?))
+            .collect::<Result<Vec<HistoryItem>>>()?;
+        // SQL only filtered and paged encrypted entries by their plaintext columns
+        #[cfg(feature = "sqlite-encryption")]
+        let results: Vec<_> = if self.encrypted {
+            results
+                .into_iter()
+                .filter(|item| matches_sealed_filters(&query.filter, item))
+                .collect()
+        } else {
+            results
+        };
+        let offset = query.offset.map_or(0, |offset| offset.max(0) as usize);
+        let limit = query
+            .limit
+            .map_or(usize::MAX, |limit| limit.max(0) as usize);
+        if let Some(CommandLineSearch::Fuzzy(needle)) = &query.filter.command_line {
+            let ranked = rank_fuzzy_matches(needle, results);
+            return Ok(ranked.into_iter().skip(offset).take(limit).collect());
+        }
+        if self.encrypted {
+            return Ok(results.into_iter().skip(offset).take(limit).collect());
+        }
         Ok(results)
     }
 
@@ -172,8 +306,11 @@ impl History for SqliteBackedHistory {
     }
 
     fn sync(&mut self) -> std::io::Result<()> {
-        // no-op (todo?)
-        Ok(())
+        // Entries are written on save and queries see those of other sessions,
+        // so only fold the write-ahead log back into the database
+        self.db
+            .query_row("PRAGMA wal_checkpoint(PASSIVE)", params![], |_| Ok(()))
+            .map_err(|e| std::io::Error::new(std::io::ErrorKind::Other, e))
     }
 
     fn session(&self) -> Option<HistorySessionId> {
@@ -187,6 +324,30 @@ fn map_sqlite_err(err: rusqlite::Error) -> ReedlineError {
     )))
 }
 
+fn locked_error() -> ReedlineError {
+    ReedlineError(ReedlineErrorVariants::HistoryDatabaseError(
+        "The history is encrypted and needs its key, see `SqliteBackedHistory::with_encryption`"
+            .to_string(),
+    ))
+}
+
+/// `GLOB` pattern matching the characters of `needle` in order
+fn fuzzy_glob_pattern(needle: &str) -> String {
+    let mut pattern = String::from("*");
+    for c in needle.chars() {
+        match c {
+            '*' | '?' | '[' => {
+                pattern.push('[');
+                pattern.push(c);
+                pattern.push(']');
+            }
+            c => pattern.push(c),
+        }
+        pattern.push('*');
+    }
+    pattern
+}
+
 type BoxedNamedParams<'a> = Vec<(&'static str, Box<dyn ToSql + 'a>)>;
 
 impl SqliteBackedHistory {
@@ -233,6 +394,8 @@ impl SqliteBackedHistory {
             .map_err(map_sqlite_err)?;
         db.pragma_update(None, "application_id", SQLITE_APPLICATION_ID)
             .map_err(map_sqlite_err)?;
+        // Wait for other sessions writing to the same database instead of failing
+        db.busy_timeout(BUSY_TIMEOUT).map_err(map_sqlite_err)?;
         let db_version: i32 = db
             .query_row(
                 "SELECT user_version FROM pragma_user_version",
@@ -256,28 +419,164 @@ impl SqliteBackedHistory {
             cwd text,
             duration_ms integer,
             exit_status integer,
-            more_info text
+            more_info text,
+            sealed blob -- the other columns, encrypted
         ) strict;
         create index if not exists idx_history_time on history(start_timestamp);
         create index if not exists idx_history_cwd on history(cwd); -- suboptimal for many hosts
         create index if not exists idx_history_exit_status on history(exit_status);
         create index if not exists idx_history_cmd on history(command_line);
-        create index if not exists idx_history_cmd on history(session_id);
+        create index if not exists idx_history_session on history(session_id);
+        create index if not exists idx_history_duration on history(duration_ms);
         -- todo: better indexes
         ",
         )
         .map_err(map_sqlite_err)?;
+        let has_sealed_column: bool = db
+            .query_row(
+                "select count(*) > 0 from pragma_table_info('history') where name = 'sealed'",
+                params![],
+                |r| r.get(0),
+            )
+            .map_err(map_sqlite_err)?;
+        if !has_sealed_column {
+            db.execute("alter table history add column sealed blob", params![])
+                .map_err(map_sqlite_err)?;
+        }
+        // An encrypted database holds a value sealed with its key, and takes
+        // no plaintext entries from any session
+        db.execute_batch(
+            "
+        create table if not exists history_key (check_value blob not null) strict;
+        create trigger if not exists history_insert_encrypted
+            before insert on history
+            when new.sealed is null and exists (select 1 from history_key)
+            begin select raise(abort, 'the history is encrypted'); end;
+        create trigger if not exists history_update_encrypted
+            before update on history
+            when new.sealed is null and exists (select 1 from history_key)
+            begin select raise(abort, 'the history is encrypted'); end;
+        ",
+        )
+        .map_err(map_sqlite_err)?;
+        let encrypted: bool = db
+            .query_row(
+                "select exists (select 1 from history_key)",
+                params![],
+                |r| r.get(0),
+            )
+            .map_err(map_sqlite_err)?;
         Ok(SqliteBackedHistory {
             db,
             session,
             session_timestamp,
+            encrypted,
+            #[cfg(feature = "sqlite-encryption")]
+            cipher: None,
         })
     }
 
+    /// Builder encrypting the command line and the details of each entry with
+    /// `key`, a 32 byte key for ChaCha20-Poly1305
+    ///
+    /// The id, start time and session of the entries stay readable to order
+    /// and filter them by, the other filters of a search are applied after
+    /// decrypting the entries. An empty database is encrypted from here on.
+    /// Fails if the database was encrypted with another key, or if it has
+    /// plaintext entries, as the two don't mix. Without the key an encrypted
+    /// database can't be read or added to.
+    ///
+    /// ## Required feature:
+    /// `sqlite-encryption`
+    #[cfg(feature = "sqlite-encryption")]
+    pub fn with_encryption(mut self, key: &[u8]) -> Result<Self> {
+        let cipher = RowCipher::new(key)?;
+        let check_value: Option<Vec<u8>> = self
+            .db
+            .query_row("select check_value from history_key", params![], |r| {
+                r.get(0)
+            })
+            .optional()
+            .map_err(map_sqlite_err)?;
+        if let Some(check_value) = &check_value {
+            if !cipher.fits(check_value) {
+                return Err(encryption_error("The key doesn't decrypt this history"));
+            }
+        }
+        let plaintext: bool = self
+            .db
+            .query_row(
+                "select exists (select 1 from history where sealed is null)",
+                params![],
+                |r| r.get(0),
+            )
+            .map_err(map_sqlite_err)?;
+        if plaintext {
+            return Err(encryption_error(
+                "The history has plaintext entries, which can't be mixed with encrypted ones",
+            ));
+        }
+        if check_value.is_none() {
+            self.db
+                .execute(
+                    "insert into history_key (check_value) values (?)",
+                    params![cipher.check_value()],
+                )
+                .map_err(map_sqlite_err)?;
+        }
+        self.encrypted = true;
+        self.cipher = Some(cipher);
+        Ok(self)
+    }
+
+    /// Fail if the history is encrypted and not given the key
+    fn ensure_unlocked(&self) -> Result<()> {
+        #[cfg(feature = "sqlite-encryption")]
+        let unlocked = !self.encrypted || self.cipher.is_some();
+        #[cfg(not(feature = "sqlite-encryption"))]
+        let unlocked = !self.encrypted;
+        if unlocked {
+            Ok(())
+        } else {
+            Err(locked_error())
+        }
+    }
+
+    /// The columns of `entry` stored in plaintext, and the others encrypted
+    /// if the history is
+    fn stored_columns(&self, entry: &HistoryItem) -> (HistoryItem, Option<Vec<u8>>) {
+        #[cfg(feature = "sqlite-encryption")]
+        if let Some(cipher) = &self.cipher {
+            let plaintext = HistoryItem {
+                id: entry.id,
+                start_timestamp: entry.start_timestamp,
+                session_id: entry.session_id,
+                ..HistoryItem::from_command_line("")
+            };
+            return (plaintext, Some(cipher.seal(entry)));
+        }
+        (entry.clone(), None)
+    }
+
+    /// The item of a row read with [`deserialize_row`], decrypted if need be
+    fn open_row(&self, (item, sealed): (HistoryItem, Option<Vec<u8>>)) -> Result<HistoryItem> {
+        match sealed {
+            None => Ok(item),
+            #[cfg(feature = "sqlite-encryption")]
+            Some(sealed) => match &self.cipher {
+                Some(cipher) => cipher.open(item, &sealed),
+                None => Err(locked_error()),
+            },
+            #[cfg(not(feature = "sqlite-encryption"))]
+            Some(_) => Err(locked_error()),
+        }
+    }
+
     fn construct_query<'a>(
         &self,
         query: &'a SearchQuery,
         select_expression: &str,
+        paged: bool,
     ) -> (String, BoxedNamedParams<'a>) {
         // TODO: this whole function could be done with less allocs
         let (is_asc, asc) = match query.direction {
@@ -318,52 +617,83 @@ impl SqliteBackedHistory {
             });
             params.push((":end_id", Box::new(end.0)));
         }
-        let limit = match query.limit {
-            Some(l) => {
+        let limit = match (query.limit, query.offset) {
+            // Encrypted entries are paged after filtering them in `search`
+            _ if !paged || self.encrypted => "",
+            (None, None) => "",
+            (Some(l), None) => {
                 params.push((":limit", Box::new(l)));
                 "limit :limit"
             }
-            None => "",
+            // SQLite only takes an offset after a limit, a negative one meaning none
+            (l, Some(o)) => {
+                params.push((":limit", Box::new(l.unwrap_or(-1))));
+                params.push((":offset", Box::new(o)));
+                "limit :limit offset :offset"
+            }
         };
-        if let Some(command_line) = &query.filter.command_line {
-            match command_line {
-                CommandLineSearch::Exact(e) => {
-                    wheres.push("command_line == :command_line");
-                    params.push((":command_line", Box::new(e)));
-                }
-                CommandLineSearch::Prefix(prefix) => {
-                    wheres.push("instr(command_line, :command_line) == 1");
-                    params.push((":command_line", Box::new(prefix)));
-                }
-                CommandLineSearch::Substring(cont) => {
-                    wheres.push("instr(command_line, :command_line) >= 1");
-                    params.push((":command_line", Box::new(cont)));
-                }
-            };
-        }
+        // The encrypted columns are filtered in `search`
+        if !self.encrypted {
+            if let Some(command_line) = &query.filter.command_line {
+                match command_line {
+                    CommandLineSearch::Exact(e) => {
+                        wheres.push("command_line == :command_line");
+                        params.push((":command_line", Box::new(e)));
+                    }
+                    CommandLineSearch::Prefix(prefix) => {
+                        wheres.push("instr(command_line, :command_line) == 1");
+                        params.push((":command_line", Box::new(prefix)));
+                    }
+                    CommandLineSearch::Substring(cont) => {
+                        wheres.push("instr(command_line, :command_line) >= 1");
+                        params.push((":command_line", Box::new(cont)));
+                    }
+                    CommandLineSearch::Fuzzy(needle) => {
+                        // Decompose into a case-sensitive `*a*b*c*` pattern, which
+                        // selects exactly the entries containing the characters in order
+                        wheres.push("command_line glob :command_line");
+                        params.push((":command_line", Box::new(fuzzy_glob_pattern(needle))));
+                    }
+                };
+            }
 
-        if let Some(str) = &query.filter.not_command_line {
-            wheres.push("command_line != :not_cmd");
-            params.push((":not_cmd", Box::new(str)));
-        }
-        if let Some(hostname) = &query.filter.hostname {
-            wheres.push("hostname = :hostname");
-            params.push((":hostname", Box::new(hostname)));
-        }
-        if let Some(cwd_exact) = &query.filter.cwd_exact {
-            wheres.push("cwd = :cwd");
-            params.push((":cwd", Box::new(cwd_exact)));
-        }
-        if let Some(cwd_prefix) = &query.filter.cwd_prefix {
-            wheres.push("cwd like :cwd_like");
-            let cwd_like = format!("{cwd_prefix}%");
-            params.push((":cwd_like", Box::new(cwd_like)));
-        }
-        if let Some(exit_successful) = query.filter.exit_successful {
-            if exit_successful {
-                wheres.push("exit_status = 0");
-            } else {
-                wheres.push("exit_status != 0");
+            if let Some(str) = &query.filter.not_command_line {
+                wheres.push("command_line != :not_cmd");
+                params.push((":not_cmd", Box::new(str)));
+            }
+            if let Some(hostname) = &query.filter.hostname {
+                wheres.push("hostname = :hostname");
+                params.push((":hostname", Box::new(hostname)));
+            }
+            if let Some(cwd_exact) = &query.filter.cwd_exact {
+                wheres.push("cwd = :cwd");
+                params.push((":cwd", Box::new(cwd_exact)));
+            }
+            if let Some(cwd_prefix) = &query.filter.cwd_prefix {
+                // `like` would treat `%` and `_` in the path as wildcards
+                let (below, below_windows) = if cwd_prefix.ends_with(PATH_SEPARATORS) {
+                    (cwd_prefix.clone(), cwd_prefix.clone())
+                } else {
+                    (format!("{cwd_prefix}/"), format!("{cwd_prefix}\\"))
+                };
+                wheres.push(
+                    "(cwd = :cwd_dir or instr(cwd, :cwd_prefix) == 1 \
+                     or instr(cwd, :cwd_prefix_windows) == 1)",
+                );
+                params.push((":cwd_dir", Box::new(cwd_prefix)));
+                params.push((":cwd_prefix", Box::new(below)));
+                params.push((":cwd_prefix_windows", Box::new(below_windows)));
+            }
+            if let Some(exit_successful) = query.filter.exit_successful {
+                if exit_successful {
+                    wheres.push("exit_status = 0");
+                } else {
+                    wheres.push("exit_status != 0");
+                }
+            }
+            if let Some(duration) = query.filter.duration_at_least {
+                wheres.push("duration_ms >= :duration_ms");
+                params.push((":duration_ms", Box::new(duration.as_millis() as i64)));
             }
         }
         if let (Some(session_id), Some(session_timestamp)) =
@@ -379,6 +709,10 @@ impl SqliteBackedHistory {
                 Box::new(session_timestamp.timestamp_millis()),
             ));
         }
+        if let Some(session_exact) = query.filter.session_exact {
+            wheres.push("session_id = :session_exact");
+            params.push((":session_exact", Box::new(session_exact)));
+        }
         let mut wheres = wheres.join(" and ");
         if wheres.is_empty() {
             wheres = "true".to_string();
@@ -0,0 +1,314 @@
+use super::{
+    base::{CommandLineSearch, PATH_SEPARATORS},
+    HistoryItem, SearchFilter,
+};
+use crate::{
+    result::{ReedlineError, ReedlineErrorVariants},
+    Result,
+};
+use chacha20poly1305::{
+    aead::{Aead, AeadCore, KeyInit, OsRng},
+    ChaCha20Poly1305, Nonce,
+};
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+
+/// Length of the nonce in front of each sealed value
+const NONCE_LEN: usize = 12;
+/// What the check value of a database holds, telling a wrong key apart
+const CHECK_PLAINTEXT: &[u8] = b"reedline history";
+
+/// The columns of a history row that are encrypted
+#[derive(Serialize, Deserialize)]
+struct SealedColumns {
+    command_line: String,
+    hostname: Option<String>,
+    cwd: Option<String>,
+    duration_ms: Option<u64>,
+    exit_status: Option<i64>,
+    more_info: Option<String>,
+}
+
+/// Encrypts the columns of history rows with ChaCha20-Poly1305, using a random
+/// nonce for each row
+pub(super) struct RowCipher(ChaCha20Poly1305);
+
+impl RowCipher {
+    pub fn new(key: &[u8]) -> Result<Self> {
+        ChaCha20Poly1305::new_from_slice(key)
+            .map(RowCipher)
+            .map_err(|_| encryption_error("The encryption key must be 32 bytes long"))
+    }
+
+    /// A value sealed with the key, stored to check the key on opening
+    pub fn check_value(&self) -> Vec<u8> {
+        self.seal_bytes(CHECK_PLAINTEXT)
+    }
+
+    /// Whether `check_value` was sealed with the same key
+    pub fn fits(&self, check_value: &[u8]) -> bool {
+        self.open_bytes(check_value).as_deref() == Some(CHECK_PLAINTEXT)
+    }
+
+    /// The encrypted columns of `item`
+    pub fn seal(&self, item: &HistoryItem) -> Vec<u8> {
+        let columns = SealedColumns {
+            command_line: item.command_line.clone(),
+            hostname: item.hostname.clone(),
+            cwd: item.cwd.clone(),
+            duration_ms: item.duration.map(|duration| duration.as_millis() as u64),
+            exit_status: item.exit_status,
+            more_info: item
+                .more_info
+                .as_ref()
+                .map(|more_info| serde_json::to_string(more_info).unwrap()),
+        };
+        self.seal_bytes(&serde_json::to_vec(&columns).unwrap())
+    }
+
+    /// `item` with the columns decrypted from `sealed`
+    pub fn open(&self, item: HistoryItem, sealed: &[u8]) -> Result<HistoryItem> {
+        let columns: SealedColumns = self
+            .open_bytes(sealed)
+            .and_then(|plaintext| serde_json::from_slice(&plaintext).ok())
+            .ok_or_else(|| encryption_error("An entry of the history can't be decrypted"))?;
+        let more_info = columns
+            .more_info
+            .map(|more_info| serde_json::from_str(&more_info))
+            .transpose()
+            .map_err(|e| encryption_error(&format!("could not deserialize more_info: {e}")))?;
+        Ok(HistoryItem {
+            command_line: columns.command_line,
+            hostname: columns.hostname,
+            cwd: columns.cwd,
+            duration: columns.duration_ms.map(Duration::from_millis),
+            exit_status: columns.exit_status,
+            more_info,
+            ..item
+        })
+    }
+
+    fn seal_bytes(&self, plaintext: &[u8]) -> Vec<u8> {
+        let nonce = ChaCha20Poly1305::generate_nonce(&mut OsRng);
+        let ciphertext = self
+            .0
+            .encrypt(&nonce, plaintext)
+            .expect("sealing into a vector doesn't fail");
+        [nonce.as_slice(), &ciphertext].concat()
+    }
+
+    fn open_bytes(&self, sealed: &[u8]) -> Option<Vec<u8>> {
+        if sealed.len() < NONCE_LEN {
+            return None;
+        }
+        let (nonce, ciphertext) = sealed.split_at(NONCE_LEN);
+        self.0.decrypt(Nonce::from_slice(nonce), ciphertext).ok()
+    }
+}
+
+pub(super) fn encryption_error(message: &str) -> ReedlineError {
+    ReedlineError(ReedlineErrorVariants::HistoryDatabaseError(
+        message.to_string(),
+    ))
+}
+
+/// Whether `item` passes the parts of `filter` on encrypted columns, which
+/// SQL can't apply
+///
+/// Fuzzy searches pass, they are ranked and filtered by the caller.
+pub(super) fn matches_sealed_filters(filter: &SearchFilter, item: &HistoryItem) -> bool {
+    let command_line = item.command_line.as_str();
+    let text_matches = match &filter.command_line {
+        Some(CommandLineSearch::Exact(exact)) => command_line == exact,
+        Some(CommandLineSearch::Prefix(prefix)) => command_line.starts_with(prefix.as_str()),
+        Some(CommandLineSearch::Substring(substring)) => command_line.contains(substring.as_str()),
+        Some(CommandLineSearch::Fuzzy(_)) | None => true,
+    };
+    let in_directory = |prefix: &String| {
+        item.cwd.as_deref().map_or(false, |cwd| {
+            let below = cwd.strip_prefix(prefix.as_str()).map_or(false, |rest| {
+                prefix.ends_with(PATH_SEPARATORS) || rest.starts_with(PATH_SEPARATORS)
+            });
+            cwd == prefix || below
+        })
+    };
+    text_matches
+        && filter.not_command_line.as_deref() != Some(command_line)
+        && filter
+            .hostname
+            .as_ref()
+            .map_or(true, |hostname| item.hostname.as_ref() == Some(hostname))
+        && filter
+            .cwd_exact
+            .as_ref()
+            .map_or(true, |cwd| item.cwd.as_ref() == Some(cwd))
+        && filter.cwd_prefix.as_ref().map_or(true, in_directory)
+        && filter.exit_successful.map_or(true, |successful| {
+            item.exit_status
+                .map_or(false, |status| (status == 0) == successful)
+        })
+        && filter.duration_at_least.map_or(true, |at_least| {
+            item.duration.map_or(false, |d| d >= at_least)
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::{HistoryItemId, SearchDirection, SearchQuery};
+    use super::*;
+    use crate::{History, HistorySessionId, SqliteBackedHistory};
+    use pretty_assertions::assert_eq;
+    use std::path::Path;
+
+    const KEY: &[u8; 32] = b"an example key of thirty-2 bytes";
+
+    fn open(path: &Path) -> SqliteBackedHistory {
+        SqliteBackedHistory::with_file(path.to_path_buf(), None, None).unwrap()
+    }
+
+    fn everything() -> SearchQuery {
+        SearchQuery::everything(SearchDirection::Backward, None)
+    }
+
+    fn command_lines(history: &dyn History, query: SearchQuery) -> Vec<String> {
+        history
+            .search(query)
+            .unwrap()
+            .into_iter()
+            .map(|item| item.command_line)
+            .collect()
+    }
+
+    #[test]
+    fn entries_round_trip_through_the_encrypted_database() {
+        let tmp = tempfile::tempdir().unwrap();
+        let db = tmp.path().join("history.sqlite3");
+        let saved = HistoryItem {
+            session_id: Some(HistorySessionId::new(7)),
+            hostname: Some("laptop".to_string()),
+            cwd: Some("/work/app".to_string()),
+            duration: Some(Duration::from_millis(1500)),
+            exit_status: Some(1),
+            ..HistoryItem::from_command_line("export TOKEN=hunter2")
+        };
+        {
+            let mut history = open(&db).with_encryption(KEY).unwrap();
+            history.save(saved.clone()).unwrap();
+            history
+                .save(HistoryItem {
+                    cwd: Some("/work/application".to_string()),
+                    exit_status: Some(0),
+                    ..HistoryItem::from_command_line("cargo test")
+                })
+                .unwrap();
+            history
+                .save(HistoryItem::from_command_line("cargo build"))
+                .unwrap();
+        }
+
+        let bytes = std::fs::read(&db).unwrap();
+        assert!(!bytes.windows(7).any(|window| window == b"hunter2"));
+
+        let history = open(&db).with_encryption(KEY).unwrap();
+        let loaded = history.load(HistoryItemId::new(1)).unwrap();
+        assert_eq!(
+            loaded,
+            HistoryItem {
+                id: loaded.id,
+                ..saved
+            }
+        );
+
+        let search = |filter: SearchFilter| {
+            command_lines(
+                &history,
+                SearchQuery {
+                    filter,
+                    ..everything()
+                },
+            )
+        };
+        assert_eq!(
+            search(SearchFilter::anything(None)),
+            ["cargo build", "cargo test", "export TOKEN=hunter2"]
+        );
+        assert_eq!(
+            search(SearchFilter::from_text_search(
+                CommandLineSearch::Substring("cargo".into()),
+                None
+            )),
+            ["cargo build", "cargo test"]
+        );
+        assert_eq!(
+            search(SearchFilter::from_text_search(
+                CommandLineSearch::Fuzzy("cgb".into()),
+                None
+            )),
+            ["cargo build"]
+        );
+        assert_eq!(
+            search(SearchFilter::anything(None).with_exit_successful(false)),
+            ["export TOKEN=hunter2"]
+        );
+        assert_eq!(
+            search(SearchFilter::anything(None).with_cwd_prefix("/work/app")),
+            ["export TOKEN=hunter2"]
+        );
+
+        let paged = || SearchQuery {
+            limit: Some(1),
+            offset: Some(1),
+            ..everything()
+        };
+        assert_eq!(command_lines(&history, paged()), ["cargo test"]);
+        assert_eq!(history.count(paged()).unwrap(), 1);
+    }
+
+    #[test]
+    fn a_wrong_key_is_refused() {
+        let tmp = tempfile::tempdir().unwrap();
+        let db = tmp.path().join("history.sqlite3");
+        {
+            let mut history = open(&db).with_encryption(KEY).unwrap();
+            history.save(HistoryItem::from_command_line("ls")).unwrap();
+        }
+
+        let err = open(&db)
+            .with_encryption(b"another key with thirty-two byte")
+            .err()
+            .unwrap();
+        assert_eq!(
+            err.to_string(),
+            "error within history database: The key doesn't decrypt this history"
+        );
+        assert!(open(&db).with_encryption(b"short").is_err());
+    }
+
+    #[test]
+    fn plaintext_and_encrypted_entries_are_not_mixed() {
+        let tmp = tempfile::tempdir().unwrap();
+        let plaintext_db = tmp.path().join("plaintext.sqlite3");
+        {
+            let mut history = open(&plaintext_db);
+            history.save(HistoryItem::from_command_line("ls")).unwrap();
+        }
+        assert!(open(&plaintext_db).with_encryption(KEY).is_err());
+        // Refusing left the database as it was
+        assert_eq!(command_lines(&open(&plaintext_db), everything()), ["ls"]);
+
+        let encrypted_db = tmp.path().join("encrypted.sqlite3");
+        {
+            let mut history = open(&encrypted_db).with_encryption(KEY).unwrap();
+            history.save(HistoryItem::from_command_line("ls")).unwrap();
+        }
+        let mut without_key = open(&encrypted_db);
+        assert!(without_key
+            .save(HistoryItem::from_command_line("cd"))
+            .is_err());
+        assert!(without_key.search(everything()).is_err());
+        drop(without_key);
+
+        let history = open(&encrypted_db).with_encryption(KEY).unwrap();
+        assert_eq!(command_lines(&history, everything()), ["ls"]);
+    }
+}
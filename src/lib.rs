@@ -190,7 +190,10 @@
 //! - `bashisms`: Enable support for special text sequences that recall components from the history. e.g. `!!` and `!$`. For use in shells like `bash` or [`nushell`](https://nushell.sh).
 //! - `sqlite`: Provides the `SqliteBackedHistory` to store richer information in the history. Statically links the required sqlite version.
 //! - `sqlite-dynlib`: Alternative to the feature `sqlite`. Will not statically link. Requires `sqlite >= 3.38` to link dynamically!
+//! - `sqlite-encryption`: Together with `sqlite` or `sqlite-dynlib`, lets `SqliteBackedHistory::with_encryption` encrypt the history at rest.
+//! - `history_json`: `History::export_to` and `History::import_from`, to move the history to and from files in JSON lines format.
 //! - `external_printer`: **Experimental:** Thread-safe `ExternalPrinter` handle to print lines from concurrently running threads.
+//! - `regex`: Rules of the `RuleBasedHighlighter` matching regular expressions.
 //!
 //! ## Are we prompt yet? (Development status)
 //!
@@ -245,10 +248,10 @@ pub use enums::{
 };
 
 mod painting;
-pub use painting::{Painter, StyledText};
+pub use painting::{MergePolicy, Painter, StyleModifier, StyledText};
 
 mod engine;
-pub use engine::{MouseClickMode, Reedline};
+pub use engine::{CompletionBehavior, MouseClickMode, Reedline};
 
 mod result;
 pub use result::{ReedlineError, ReedlineErrorVariants, Result};
@@ -257,8 +260,9 @@ mod history;
 #[cfg(any(feature = "sqlite", feature = "sqlite-dynlib"))]
 pub use history::SqliteBackedHistory;
 pub use history::{
-    CommandLineSearch, FileBackedHistory, History, HistoryItem, HistoryItemExtraInfo,
-    HistoryItemId, HistoryNavigationQuery, HistorySessionId, IgnoreAllExtraInfo, SearchDirection,
+    migrate, CommandLineSearch, FileBackedHistory, History, HistoryDeduplication, HistoryItem,
+    HistoryItemExtraInfo, HistoryItemId, HistoryNavigationQuery, HistoryNavigationScope,
+    HistorySearchMode, HistorySessionId, IgnoreAllExtraInfo, MigrationReport, SearchDirection,
     SearchFilter, SearchQuery, HISTORY_SIZE,
 };
 
@@ -270,19 +274,27 @@ pub use prompt::{
 
 mod edit_mode;
 pub use edit_mode::{
-    default_emacs_keybindings, default_vi_insert_keybindings, default_vi_normal_keybindings,
-    CursorConfig, EditMode, Emacs, Keybindings, Vi,
+    add_menu_keybindings, default_emacs_keybindings, default_vi_insert_keybindings,
+    default_vi_normal_keybindings, CursorConfig, EditMode, Emacs, Keybindings, KeybindingsMode,
+    ReedlineKeybindings, Vi, COMPLETION_MENU,
 };
 
 mod highlighter;
-pub use highlighter::{ExampleHighlighter, Highlighter, SimpleMatchHighlighter};
+pub use highlighter::{
+    ExampleHighlighter, HighlightPattern, HighlightRule, Highlighter, RainbowBracketHighlighter,
+    RuleBasedHighlighter, SimpleMatchHighlighter,
+};
 
 mod completion;
-pub use completion::{Completer, DefaultCompleter, Span, Suggestion};
+pub use completion::{
+    quote_for_shell, CacheInvalidator, CachedCompleter, CancellationToken, ChainedCompleter,
+    Completer, CompletionContext, CompletionHandle, CompletionSink, DefaultCompleter, MatchingMode,
+    QuotingStyle, Span, Suggestion, SuggestionKind, SuggestionOrder, SuggestionPostProcessor,
+};
 
 mod hinter;
-pub use hinter::CwdAwareHinter;
-pub use hinter::{DefaultHinter, Hinter};
+pub use hinter::{ChainedHinter, CwdAwareHinter};
+pub use hinter::{DefaultHinter, HintTokenBoundary, Hinter};
 
 mod validator;
 pub use validator::{DefaultValidator, ValidationResult, Validator};
@@ -291,17 +303,19 @@ pub use validator::{DefaultValidator, ValidationResult, Validator};
 mod lsp;
 #[cfg(feature = "lsp_diagnostics")]
 pub use lsp::{
-    CodeAction, Diagnostic, DiagnosticSeverity, LspConfig, LspDiagnosticsProvider,
-    Span as DiagnosticSpan, TextEdit,
+    add_lsp_keybindings, CodeAction, ColorInformation, Diagnostic, DiagnosticSeverity,
+    LspCompleter, LspConfig, LspDiagnosticsProvider, LspSymbol, Span as DiagnosticSpan, SymbolKind,
+    SymbolLocation, TextEdit,
 };
 
 mod menu;
-#[cfg(feature = "lsp_diagnostics")]
-pub use menu::DiagnosticFixMenu;
 pub use menu::{
-    menu_functions, ColumnarMenu, DescriptionMenu, DescriptionMode, IdeMenu, ListMenu, Menu,
-    MenuBuilder, MenuEvent, MenuSettings, MenuTextStyle, ReedlineMenu, TraversalDirection,
+    menu_functions, ColumnarMenu, DescriptionMenu, DescriptionMode, EllipsisPosition, IdeMenu,
+    ListMenu, Menu, MenuBorder, MenuBuilder, MenuEvent, MenuSettings, MenuTextStyle, ReedlineMenu,
+    ScrollIndicator, TraversalDirection,
 };
+#[cfg(feature = "lsp_diagnostics")]
+pub use menu::{DiagnosticFixMenu, SymbolMenu, TextEditInfo};
 
 mod terminal_extensions;
 pub use terminal_extensions::kitty_protocol_available;
@@ -4,7 +4,7 @@
 
 use super::diagnostic::Span;
 use lsp_types::{
-    CodeAction, CodeActionContext, CodeActionParams, CodeActionResponse, Range,
+    CodeAction, CodeActionContext, CodeActionKind, CodeActionParams, CodeActionResponse, Range,
     TextDocumentIdentifier,
 };
 use serde_json::Value;
@@ -12,11 +12,13 @@ use serde_json::Value;
 /// Request code actions from the LSP server for a given span.
 ///
 /// Returns the raw LSP code actions. Conversion to byte spans happens
-/// in the diagnostic fix menu when needed.
+/// in the diagnostic fix menu when needed. With `only`, the server is asked
+/// for actions of those kinds only.
 pub(super) fn request_code_actions<F>(
     uri: &str,
     content: &str,
     span: Span,
+    only: Option<Vec<CodeActionKind>>,
     timeout_ms: u64,
     request_fn: F,
 ) -> Vec<CodeAction>
@@ -32,7 +34,7 @@ where
         range: span_to_range(content, span),
         context: CodeActionContext {
             diagnostics: Vec::new(),
-            only: None,
+            only,
             trigger_kind: None,
         },
         work_done_progress_params: Default::default(),
@@ -45,6 +47,45 @@ where
         .unwrap_or_default()
 }
 
+/// The kinds to ask for in `CodeActionContext.only`: those of `requested`
+/// the server can produce according to its `declared` kinds.
+///
+/// `Some(None)` asks for all kinds, when none are requested or the server
+/// didn't declare its kinds. `None` means the server can't produce any of
+/// the requested kinds, so there is nothing to ask for: some servers answer
+/// `only` values they can't satisfy with nothing at all.
+pub(super) fn only_kinds(
+    requested: &[CodeActionKind],
+    declared: Option<&[CodeActionKind]>,
+) -> Option<Option<Vec<CodeActionKind>>> {
+    if requested.is_empty() {
+        return Some(None);
+    }
+    let Some(declared) = declared else {
+        return Some(Some(requested.to_vec()));
+    };
+    let kinds: Vec<CodeActionKind> = requested
+        .iter()
+        .filter(|kind| {
+            declared
+                .iter()
+                .any(|declared| kinds_overlap(kind, declared))
+        })
+        .cloned()
+        .collect();
+    (!kinds.is_empty()).then_some(Some(kinds))
+}
+
+/// Whether one kind is the other or a sub kind of it, like `refactor` and
+/// `refactor.extract`
+fn kinds_overlap(a: &CodeActionKind, b: &CodeActionKind) -> bool {
+    let is_sub_kind = |kind: &str, parent: &str| {
+        kind.strip_prefix(parent)
+            .map_or(false, |rest| rest.is_empty() || rest.starts_with('.'))
+    };
+    is_sub_kind(a.as_str(), b.as_str()) || is_sub_kind(b.as_str(), a.as_str())
+}
+
 /// Filter LSP response to only include actual code actions (not commands).
 fn filter_code_actions(response: CodeActionResponse) -> Vec<CodeAction> {
     response
@@ -64,8 +105,9 @@ fn span_to_range(content: &str, span: Span) -> Range {
     }
 }
 
-/// Convert a byte offset to an LSP Position.
-fn offset_to_position(content: &str, offset: usize) -> lsp_types::Position {
+/// Convert a byte offset to an LSP Position, counting UTF-16 code units as
+/// the protocol does.
+pub(super) fn offset_to_position(content: &str, offset: usize) -> lsp_types::Position {
     let (line, character) = content
         .char_indices()
         .take_while(|(i, _)| *i < offset)
@@ -73,9 +115,55 @@ fn offset_to_position(content: &str, offset: usize) -> lsp_types::Position {
             if c == '\n' {
                 (line + 1, 0)
             } else {
-                (line, col + 1)
+                (line, col + c.len_utf16() as u32)
             }
         });
 
     lsp_types::Position { line, character }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+    use rstest::rstest;
+
+    fn kinds(kinds: &[&str]) -> Vec<CodeActionKind> {
+        kinds
+            .iter()
+            .map(|kind| CodeActionKind::from(kind.to_string()))
+            .collect()
+    }
+
+    #[rstest]
+    #[case::nothing_requested(&[], Some(&["quickfix"][..]), Some(None))]
+    #[case::nothing_declared(&["refactor"], None, Some(Some(&["refactor"][..])))]
+    #[case::declared(
+        &["quickfix", "refactor"],
+        Some(&["quickfix"][..]),
+        Some(Some(&["quickfix"][..]))
+    )]
+    #[case::declared_sub_kind(
+        &["refactor"],
+        Some(&["refactor.extract"][..]),
+        Some(Some(&["refactor"][..]))
+    )]
+    #[case::requested_sub_kind(
+        &["refactor.inline"],
+        Some(&["refactor"][..]),
+        Some(Some(&["refactor.inline"][..]))
+    )]
+    #[case::only_shared_prefix(&["source.fixAll"], Some(&["source.fix"][..]), None)]
+    #[case::none_declared(&["refactor"], Some(&["quickfix"][..]), None)]
+    fn requested_kinds_are_intersected_with_the_declared_ones(
+        #[case] requested: &[&str],
+        #[case] declared: Option<&[&str]>,
+        #[case] expected: Option<Option<&[&str]>>,
+    ) {
+        let declared = declared.map(kinds);
+        assert_eq!(
+            only_kinds(&kinds(requested), declared.as_deref()),
+            expected.map(|only| only.map(kinds))
+        );
+    }
+}
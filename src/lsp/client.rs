@@ -4,64 +4,674 @@
 //! so the main editor thread is never blocked by slow LSP responses.
 
 use std::{
+    collections::{HashMap, HashSet},
+    io,
+    path::PathBuf,
+    process::Child,
+    sync::{Arc, Mutex, RwLock},
     thread,
     time::{Duration, Instant},
 };
 
-use crossbeam::channel::{bounded, Receiver, Sender};
-use lsp_types::{CodeAction, Diagnostic};
+use crossbeam::channel::{bounded, Receiver, Sender, TryRecvError};
+use lsp_types::{
+    CodeAction, CodeActionKind, ColorInformation, CompletionItem, Diagnostic, DiagnosticSeverity,
+    TextEdit, WorkspaceFolder,
+};
 
-use super::{diagnostic::Span, worker::LspWorker};
+use super::{
+    completion::LspCompleter,
+    diagnostic::{range_to_span, severity_rank, Span},
+    occurrences::CursorDebounce,
+    symbols::LspSymbol,
+    worker::{spawn_server, LspWorker, RequestError},
+};
+use crate::menu::{extract_text_edits, resource_operation, text_edit_info, TextEditInfo};
 
 /// LSP server configuration.
+///
+/// [`Default`] leaves the command empty: it has to be set before the
+/// configuration is used, as a provider started without one never reaches a
+/// server. [`LspConfig::new`] takes it and keeps the defaults otherwise.
+///
+/// # Example
+/// ```rust
+/// use reedline::LspConfig;
+///
+/// let config = LspConfig {
+///     command: "nu-lint --lsp".to_string(),
+///     timeout_ms: 250,
+///     ..LspConfig::default()
+/// };
+/// assert_eq!(config.uri_scheme, "repl");
+/// ```
 #[derive(Debug, Clone)]
 pub struct LspConfig {
     /// Full command to start the LSP server (e.g., "nu-lint --lsp")
     pub command: String,
-    /// Response timeout in milliseconds
+    /// Response timeout in milliseconds (default: 100)
     pub timeout_ms: u64,
+    /// How long to wait for the server to execute a command, e.g. a fix, in
+    /// milliseconds (default: 10000)
+    ///
+    /// Commands can take seconds, so they get a timeout of their own. Until
+    /// the server answers, `Applying <title>…` is shown below the prompt.
+    pub command_timeout_ms: u64,
     /// URI scheme (default: "repl")
     pub uri_scheme: String,
+    /// Path of the edited document in its URI (default: `/session/repl`)
+    ///
+    /// Servers that pick the language or settings by file name can be given
+    /// e.g. `/session/repl.nu`.
+    pub document_path: String,
+    /// Reformat the buffer when a server-declared trigger character is typed
+    pub format_on_type: bool,
+    /// Capacity of the command and response queues between the editor and
+    /// the worker thread (default: 32, at least 1)
+    ///
+    /// Commands and responses that don't fit are dropped. Content updates
+    /// never are: only the newest pending content is kept and older pending
+    /// updates are replaced by it.
+    pub channel_capacity: usize,
+    /// Folders of the workspace, advertised to the server when it starts and
+    /// returned when it asks for them (default: none)
+    ///
+    /// Lets servers resolve modules relative to a project. Relative paths are
+    /// taken from the current directory.
+    pub workspace_folders: Vec<PathBuf>,
+    /// Show the diagnostics ordered by severity, errors first, and then by
+    /// position (default: true)
+    ///
+    /// Without it they are shown in the order the server sent them.
+    pub sort_diagnostics: bool,
+    /// Show only the diagnostics on the line of the cursor (default: false)
+    ///
+    /// Cuts the noise in tall multi-line buffers. The fix menu and the
+    /// diagnostic navigation still reach every diagnostic.
+    pub current_line_only: bool,
+    /// Highlight the other occurrences of the symbol under the cursor, as
+    /// reported by the server (default: false)
+    pub highlight_occurrences: bool,
+    /// How long the cursor has to rest before requests keyed on its
+    /// position, like the occurrences to highlight, are sent, in
+    /// milliseconds (default: 150)
+    ///
+    /// Moving the cursor starts the wait over, so holding down an arrow key
+    /// sends no requests until it is released. Slow servers keep up better
+    /// with longer waits, at the cost of the highlights showing up later.
+    pub cursor_debounce_ms: u64,
+    /// How long the worker thread waits for commands before it looks for
+    /// work of its own, in milliseconds (default: 50, at least 1)
+    ///
+    /// On each tick the worker catches up on work no command woke it for,
+    /// such as content updates that did not fit the queue. Shorter ticks make
+    /// such results, e.g. diagnostics of background analysis, show up sooner
+    /// but wake the CPU more often, which costs battery; longer ticks save
+    /// power but let them show up later.
+    pub idle_poll_ms: u64,
+    /// Kinds of code actions to ask the server for, e.g. only quickfixes
+    /// (default: none, asking for all kinds)
+    ///
+    /// Kinds the server didn't declare in its capabilities are left out, and
+    /// no code actions are asked for if none of them are left.
+    pub code_action_kinds: Vec<CodeActionKind>,
+    /// Directory to start the server in (default: none, the current
+    /// directory)
+    ///
+    /// Lets servers find the configuration of a project and resolve relative
+    /// paths from its root.
+    pub current_dir: Option<PathBuf>,
 }
 
-// Channel capacity for commands and responses
-const CHANNEL_CAPACITY: usize = 32;
+impl Default for LspConfig {
+    fn default() -> Self {
+        Self {
+            command: String::new(),
+            timeout_ms: DEFAULT_TIMEOUT_MS,
+            command_timeout_ms: DEFAULT_COMMAND_TIMEOUT_MS,
+            uri_scheme: DEFAULT_URI_SCHEME.to_string(),
+            document_path: DEFAULT_DOCUMENT_PATH.to_string(),
+            format_on_type: false,
+            channel_capacity: DEFAULT_CHANNEL_CAPACITY,
+            workspace_folders: Vec::new(),
+            sort_diagnostics: true,
+            current_line_only: false,
+            highlight_occurrences: false,
+            cursor_debounce_ms: DEFAULT_CURSOR_DEBOUNCE_MS,
+            idle_poll_ms: DEFAULT_IDLE_POLL_MS,
+            code_action_kinds: Vec::new(),
+            current_dir: None,
+        }
+    }
+}
+
+impl LspConfig {
+    /// Configuration starting `command` (e.g. `"nu-lint --lsp"`), with defaults otherwise
+    pub fn new(command: impl Into<String>) -> Self {
+        Self {
+            command: command.into(),
+            ..Self::default()
+        }
+    }
+
+    /// Builder setting whether to format on typed trigger characters, see
+    /// [`Self::format_on_type`]
+    #[must_use]
+    pub fn with_format_on_type(mut self, enabled: bool) -> Self {
+        self.format_on_type = enabled;
+        self
+    }
+
+    /// Builder setting the queue capacity, see [`Self::channel_capacity`]
+    #[must_use]
+    pub fn with_channel_capacity(mut self, capacity: usize) -> Self {
+        self.channel_capacity = capacity;
+        self
+    }
+
+    /// Builder setting how long to wait for executed commands, see
+    /// [`Self::command_timeout_ms`]
+    #[must_use]
+    pub fn with_command_timeout_ms(mut self, command_timeout_ms: u64) -> Self {
+        self.command_timeout_ms = command_timeout_ms;
+        self
+    }
+
+    /// Builder setting the idle tick of the worker, see [`Self::idle_poll_ms`]
+    #[must_use]
+    pub fn with_idle_poll_ms(mut self, idle_poll_ms: u64) -> Self {
+        self.idle_poll_ms = idle_poll_ms;
+        self
+    }
+
+    /// Builder setting the workspace folders, see [`Self::workspace_folders`]
+    #[must_use]
+    pub fn with_workspace_folders(mut self, folders: Vec<PathBuf>) -> Self {
+        self.workspace_folders = folders;
+        self
+    }
+
+    /// Builder setting whether to order the diagnostics, see [`Self::sort_diagnostics`]
+    #[must_use]
+    pub fn with_sort_diagnostics(mut self, sort: bool) -> Self {
+        self.sort_diagnostics = sort;
+        self
+    }
+
+    /// Builder setting whether to show only the diagnostics on the line of
+    /// the cursor, see [`Self::current_line_only`]
+    #[must_use]
+    pub fn with_current_line_only(mut self, current_line_only: bool) -> Self {
+        self.current_line_only = current_line_only;
+        self
+    }
+
+    /// Builder setting whether to highlight the occurrences of the symbol
+    /// under the cursor, see [`Self::highlight_occurrences`]
+    #[must_use]
+    pub fn with_highlight_occurrences(mut self, highlight_occurrences: bool) -> Self {
+        self.highlight_occurrences = highlight_occurrences;
+        self
+    }
+
+    /// Builder setting how long the cursor has to rest before requests keyed
+    /// on its position are sent, see [`Self::cursor_debounce_ms`]
+    #[must_use]
+    pub fn with_cursor_debounce_ms(mut self, cursor_debounce_ms: u64) -> Self {
+        self.cursor_debounce_ms = cursor_debounce_ms;
+        self
+    }
+
+    /// Builder setting the kinds of code actions to ask for, see
+    /// [`Self::code_action_kinds`]
+    #[must_use]
+    pub fn with_code_action_kinds(mut self, kinds: Vec<CodeActionKind>) -> Self {
+        self.code_action_kinds = kinds;
+        self
+    }
+
+    /// Builder setting the directory to start the server in, see
+    /// [`Self::current_dir`]
+    #[must_use]
+    pub fn with_current_dir(mut self, dir: impl Into<PathBuf>) -> Self {
+        self.current_dir = Some(dir.into());
+        self
+    }
+
+    /// Builder setting the path of the edited document, see [`Self::document_path`]
+    #[must_use]
+    pub fn with_document_path(mut self, path: impl Into<String>) -> Self {
+        self.document_path = path.into();
+        self
+    }
+
+    /// URI of the edited document, e.g. `repl:/session/repl`
+    pub fn document_uri(&self) -> String {
+        self.uri_for(&self.document_path)
+    }
+
+    /// The workspace folders as sent to the server, leaving out those without
+    /// a file URI
+    pub(super) fn lsp_workspace_folders(&self) -> Vec<WorkspaceFolder> {
+        let current_dir = std::env::current_dir().unwrap_or_default();
+        self.workspace_folders
+            .iter()
+            .filter_map(|path| {
+                let path = current_dir.join(path);
+                let uri = lsp_types::Url::from_directory_path(&path).ok()?;
+                let name = path.file_name().map_or_else(
+                    || path.to_string_lossy().into_owned(),
+                    |name| name.to_string_lossy().into_owned(),
+                );
+                Some(WorkspaceFolder { uri, name })
+            })
+            .collect()
+    }
+
+    /// URI of the document at `path`, normalized like the server reports it
+    fn uri_for(&self, path: &str) -> String {
+        let uri = format!("{}:{}", self.uri_scheme, path);
+        uri.parse::<lsp_types::Url>()
+            .map_or(uri, |url| url.to_string())
+    }
+}
+
+// How long dropping a provider waits for the shutdown handshake of the worker
+const SHUTDOWN_TIMEOUT: Duration = Duration::from_millis(200);
+
+// Default time to wait for a response of the server
+const DEFAULT_TIMEOUT_MS: u64 = 100;
+
+// Default time to wait for the server to execute a command
+const DEFAULT_COMMAND_TIMEOUT_MS: u64 = 10_000;
+
+const DEFAULT_URI_SCHEME: &str = "repl";
+
+// Default channel capacity for commands and responses
+const DEFAULT_CHANNEL_CAPACITY: usize = 32;
+
+// Default time the worker waits for commands before looking for work of its own
+const DEFAULT_IDLE_POLL_MS: u64 = 50;
+
+const DEFAULT_DOCUMENT_PATH: &str = "/session/repl";
+
+// Default time the cursor has to rest before requests keyed on its position are sent
+const DEFAULT_CURSOR_DEBOUNCE_MS: u64 = 150;
 
 /// Commands sent from main thread to worker.
+///
+/// Buffer content is shared with the worker rather than copied per command.
 pub(super) enum LspCommand {
-    UpdateContent(String),
+    /// Wakes the worker to sync the content in [`PendingContent`]
+    UpdateContent,
     RequestCodeActions {
-        content: String,
+        content: Arc<str>,
         span: Span,
     },
     ExecuteCommand {
         command: String,
         arguments: Vec<serde_json::Value>,
     },
+    /// Answered with code actions converting the color to other notations
+    RequestColorPresentations {
+        content: Arc<str>,
+        color: ColorInformation,
+    },
+    /// Completion requests carry their own reply channel so the completer
+    /// doesn't compete with the provider for responses.
+    RequestCompletion {
+        content: Arc<str>,
+        offset: usize,
+        reply: Sender<Vec<CompletionItem>>,
+    },
+    /// Answered on `reply` with the item as completed by `completionItem/resolve`
+    ResolveCompletion {
+        item: Box<CompletionItem>,
+        reply: Sender<CompletionItem>,
+    },
+    /// Answered on `reply` with the action as completed by `codeAction/resolve`
+    ResolveCodeAction {
+        action: Box<CodeAction>,
+        reply: Sender<CodeAction>,
+    },
+    /// Answered on `reply` with the symbols of the document and the workspace
+    RequestSymbols {
+        content: Arc<str>,
+        reply: Sender<Vec<LspSymbol>>,
+    },
+    /// Answered on `reply` with the occurrences of the symbol at `offset`
+    RequestOccurrences {
+        content: Arc<str>,
+        offset: usize,
+        reply: Sender<Vec<Span>>,
+    },
+    /// Answered on `reply` with the edits to apply after typing `ch`
+    RequestOnTypeFormatting {
+        content: Arc<str>,
+        offset: usize,
+        ch: char,
+        reply: Sender<Vec<TextEdit>>,
+    },
     Shutdown,
 }
 
 /// Responses sent from worker to main thread.
 pub(super) enum LspResponse {
-    Diagnostics(Vec<Diagnostic>),
+    Diagnostics {
+        uri: String,
+        diagnostics: Vec<Diagnostic>,
+    },
     CodeActions(Vec<CodeAction>),
-    CommandExecuted(bool),
+    /// The result the server answered `workspace/executeCommand` with,
+    /// `Some(Value::Null)` for a command without one and `None` if it failed
+    /// or didn't answer in time
+    CommandExecuted(Result<serde_json::Value, RequestError>),
+    Colors(Vec<ColorInformation>),
+    /// Characters the server wants on-type formatting requests for
+    OnTypeTriggers(Vec<String>),
+    /// Edits to the main document accepted from `workspace/applyEdit`, with
+    /// the content they apply to
+    WorkspaceEdit {
+        content: Arc<str>,
+        edits: Vec<TextEdit>,
+    },
+    /// The server can't be started, e.g. `nu-lint: No such file or directory`
+    Error(String),
+}
+
+/// Content waiting to be synced by the worker, by document URI.
+///
+/// Holds only the newest update per document, so a backed up worker skips
+/// straight to the latest content instead of the queue dropping it.
+#[derive(Clone, Default)]
+pub(super) struct PendingContent(Arc<Mutex<Vec<DocumentContent>>>);
+
+/// Content of a document together with its URI
+pub(super) type DocumentContent = (String, Arc<str>);
+
+impl PendingContent {
+    /// Store `content` for `uri`, replacing older pending content of that document.
+    ///
+    /// Returns `true` if nothing was pending, i.e. the worker needs a wake-up.
+    fn replace(&self, uri: String, content: Arc<str>) -> bool {
+        let Ok(mut pending) = self.0.lock() else {
+            return false;
+        };
+        let was_empty = pending.is_empty();
+        match pending
+            .iter_mut()
+            .find(|(pending_uri, _)| *pending_uri == uri)
+        {
+            Some((_, pending_content)) => *pending_content = content,
+            None => pending.push((uri, content)),
+        }
+        was_empty
+    }
+
+    /// Take the pending content of all documents, in the order first updated.
+    pub(super) fn take(&self) -> Vec<DocumentContent> {
+        self.0
+            .lock()
+            .map(|mut pending| std::mem::take(&mut *pending))
+            .unwrap_or_default()
+    }
+}
+
+/// The server process, shared between the worker and the provider.
+///
+/// Lets the provider kill the server when the worker is stuck waiting for it.
+#[derive(Clone, Default)]
+pub(super) struct ServerProcess(Arc<Mutex<Option<Child>>>);
+
+impl ServerProcess {
+    /// Keep `child` as the server process, killing the one before.
+    pub(super) fn set(&self, child: Child) {
+        self.kill();
+        if let Ok(mut process) = self.0.lock() {
+            *process = Some(child);
+        }
+    }
+
+    /// Kill the process, if any, and wait for it to exit.
+    pub(super) fn kill(&self) {
+        let child = self.0.lock().ok().and_then(|mut process| process.take());
+        if let Some(mut child) = child {
+            let _ = child.kill();
+            let _ = child.wait();
+        }
+    }
+}
+
+/// The worker thread of a provider and the server process it talks to
+struct WorkerThread {
+    thread: thread::JoinHandle<()>,
+    process: ServerProcess,
+}
+
+impl WorkerThread {
+    /// Wait for the worker to exit after [`LspCommand::Shutdown`].
+    ///
+    /// The server is killed if the worker doesn't exit in time, e.g. because
+    /// the server doesn't answer the shutdown handshake or the command was
+    /// dropped. A worker that still doesn't exit is left behind.
+    fn join(self) {
+        let exited = |timeout: Duration| {
+            let start = Instant::now();
+            while !self.thread.is_finished() && start.elapsed() < timeout {
+                thread::sleep(Duration::from_millis(5));
+            }
+            self.thread.is_finished()
+        };
+        if !exited(SHUTDOWN_TIMEOUT) {
+            self.process.kill();
+            if !exited(Duration::from_millis(50)) {
+                return;
+            }
+        }
+        let _ = self.thread.join();
+        // Reaps the server of a worker that exited without shutting it down,
+        // e.g. after a panic
+        self.process.kill();
+    }
+}
+
+/// Drop the diagnostics equal in range, severity, code and message to an
+/// earlier one.
+fn dedup_diagnostics(diagnostics: &mut Vec<Diagnostic>) {
+    let same = |a: &Diagnostic, b: &Diagnostic| {
+        a.range == b.range && a.severity == b.severity && a.code == b.code && a.message == b.message
+    };
+    let mut unique: Vec<Diagnostic> = Vec::with_capacity(diagnostics.len());
+    for diagnostic in diagnostics.drain(..) {
+        if !unique.iter().any(|kept| same(kept, &diagnostic)) {
+            unique.push(diagnostic);
+        }
+    }
+    *diagnostics = unique;
 }
 
 /// Handle for sending LSP commands from outside the provider.
 ///
 /// Used by `DiagnosticFixMenu` to execute command-based code actions.
+/// All senders share one channel slot, so they follow the provider across
+/// [`LspDiagnosticsProvider::restart`].
 #[derive(Clone)]
 pub struct LspCommandSender {
-    tx: Sender<LspCommand>,
+    tx: Arc<RwLock<Sender<LspCommand>>>,
+    /// Title of the command started with [`Self::execute_titled_command`]
+    /// that the server has not answered yet
+    running: Arc<Mutex<Option<String>>>,
+    /// Title of the last fix that could not be applied, shown until the line
+    /// changes
+    failed: Arc<Mutex<Option<String>>>,
+    /// How long to wait for the answer to a blocking request
+    timeout_ms: u64,
 }
 
 impl LspCommandSender {
     /// Execute an LSP command (fire-and-forget, non-blocking).
     pub fn execute_command(&self, command: String, arguments: Vec<serde_json::Value>) {
-        let _ = self
-            .tx
-            .try_send(LspCommand::ExecuteCommand { command, arguments });
+        self.send(LspCommand::ExecuteCommand { command, arguments });
+    }
+
+    /// Execute an LSP command (non-blocking), showing `Applying <title>…`
+    /// below the prompt until the server answers.
+    pub fn execute_titled_command(
+        &self,
+        title: String,
+        command: String,
+        arguments: Vec<serde_json::Value>,
+    ) {
+        if self.send(LspCommand::ExecuteCommand { command, arguments }) {
+            self.set_running(Some(title));
+        } else {
+            self.report_failure(title);
+        }
+    }
+
+    /// Show `Could not apply <title>` below the prompt until the line changes.
+    pub(crate) fn report_failure(&self, title: String) {
+        if let Ok(mut failed) = self.failed.lock() {
+            *failed = Some(title);
+        }
+    }
+
+    /// Title of the last fix that could not be applied, if any.
+    fn failed(&self) -> Option<String> {
+        self.failed.lock().ok()?.clone()
+    }
+
+    /// The server answered the running command or didn't in time, reporting
+    /// it as failed only if the server answered with an error.
+    ///
+    /// A server that didn't answer in time may still apply the command.
+    fn finish(&self, result: &Result<serde_json::Value, RequestError>) {
+        let title = self
+            .running
+            .lock()
+            .ok()
+            .and_then(|mut running| running.take());
+        if let (Err(RequestError::Failed), Some(title)) = (result, title) {
+            self.report_failure(title);
+        }
+    }
+
+    /// Title of the command being executed, if any.
+    fn running(&self) -> Option<String> {
+        self.running.lock().ok()?.clone()
+    }
+
+    fn set_running(&self, title: Option<String>) {
+        if let Ok(mut running) = self.running.lock() {
+            *running = title;
+        }
+    }
+
+    fn clear_failed(&self) {
+        if let Ok(mut failed) = self.failed.lock() {
+            *failed = None;
+        }
+    }
+
+    /// Send a command to the current worker without blocking.
+    ///
+    /// Returns `false` if the worker's queue is full or it has exited.
+    pub(super) fn send(&self, command: LspCommand) -> bool {
+        match self.tx.read() {
+            Ok(tx) => tx.try_send(command).is_ok(),
+            Err(_) => false,
+        }
+    }
+
+    /// Point every clone of this sender at a new worker.
+    fn replace(&self, tx: Sender<LspCommand>) {
+        if let Ok(mut current) = self.tx.write() {
+            *current = tx;
+        }
+    }
+
+    /// Request completion items at a byte offset (blocks up to `timeout_ms`).
+    pub(super) fn completions(
+        &self,
+        content: &str,
+        offset: usize,
+        timeout_ms: u64,
+    ) -> Vec<CompletionItem> {
+        let (reply, rx) = bounded(1);
+        let sent = self.send(LspCommand::RequestCompletion {
+            content: content.into(),
+            offset,
+            reply,
+        });
+        if !sent {
+            return Vec::new();
+        }
+        rx.recv_timeout(Duration::from_millis(timeout_ms))
+            .unwrap_or_default()
+    }
+
+    /// Request the details of a completion item left out of the completion
+    /// response, such as its documentation (blocks up to `timeout_ms`).
+    pub(super) fn resolve_completion(
+        &self,
+        item: CompletionItem,
+        timeout_ms: u64,
+    ) -> Option<CompletionItem> {
+        let (reply, rx) = bounded(1);
+        let sent = self.send(LspCommand::ResolveCompletion {
+            item: Box::new(item),
+            reply,
+        });
+        if !sent {
+            return None;
+        }
+        rx.recv_timeout(Duration::from_millis(timeout_ms)).ok()
+    }
+
+    /// Request the edit of a code action the server left out, sending the
+    /// action back with its `data` (blocks up to the configured timeout).
+    pub(crate) fn resolve_code_action(&self, action: CodeAction) -> Option<CodeAction> {
+        let (reply, rx) = bounded(1);
+        let sent = self.send(LspCommand::ResolveCodeAction {
+            action: Box::new(action),
+            reply,
+        });
+        if !sent {
+            return None;
+        }
+        rx.recv_timeout(Duration::from_millis(self.timeout_ms)).ok()
+    }
+
+    /// Request the symbols of the document and the workspace (blocks up to `timeout_ms`).
+    fn symbols(&self, content: Arc<str>, timeout_ms: u64) -> Vec<LspSymbol> {
+        let (reply, rx) = bounded(1);
+        if !self.send(LspCommand::RequestSymbols { content, reply }) {
+            return Vec::new();
+        }
+        rx.recv_timeout(Duration::from_millis(timeout_ms))
+            .unwrap_or_default()
+    }
+
+    /// Request on-type formatting edits after `ch` was typed (blocks up to `timeout_ms`).
+    fn on_type_formatting(
+        &self,
+        content: Arc<str>,
+        offset: usize,
+        ch: char,
+        timeout_ms: u64,
+    ) -> Vec<TextEdit> {
+        let (reply, rx) = bounded(1);
+        let sent = self.send(LspCommand::RequestOnTypeFormatting {
+            content,
+            offset,
+            ch,
+            reply,
+        });
+        if !sent {
+            return Vec::new();
+        }
+        rx.recv_timeout(Duration::from_millis(timeout_ms))
+            .unwrap_or_default()
     }
 }
 
@@ -69,57 +679,275 @@ impl LspCommandSender {
 ///
 /// Provides a non-blocking interface to LSP diagnostics.
 /// All communication with the LSP server happens in a background thread.
+///
+/// Besides the edited document at [`LspConfig::document_path`], further
+/// documents can be kept open on the server with
+/// [`LspDiagnosticsProvider::update_document`].
 pub struct LspDiagnosticsProvider {
-    command_tx: Sender<LspCommand>,
+    config: LspConfig,
+    /// URI of the edited document
+    uri: String,
+    commands: LspCommandSender,
     response_rx: Receiver<LspResponse>,
     wake_rx: Receiver<()>,
+    pending_content: PendingContent,
+    worker: Option<WorkerThread>,
     diagnostics: Vec<Diagnostic>,
-    last_content_hash: u64,
+    colors: Vec<ColorInformation>,
+    on_type_triggers: Vec<String>,
+    /// Last content sent to the worker
+    content: Arc<str>,
+    /// Last content sent for other documents, by path
+    document_contents: HashMap<String, Arc<str>>,
+    /// Diagnostics of other documents, by path
+    document_diagnostics: HashMap<String, Vec<Diagnostic>>,
+    /// Diagnostics as published, before the source filter, by document URI
+    published_diagnostics: HashMap<String, Vec<Diagnostic>>,
+    /// Sources whose diagnostics are shown, all of them if `None`
+    source_filter: Option<HashSet<String>>,
+    /// Edits from `workspace/applyEdit` not yet applied to the buffer
+    workspace_edits: Vec<(Arc<str>, Vec<TextEdit>)>,
+    /// Holds back occurrence requests while the cursor moves
+    cursor_debounce: CursorDebounce,
+    /// Occurrences of the symbol under the cursor
+    occurrences: Vec<Span>,
+    /// Where the answer to the occurrence request sent last arrives
+    occurrences_rx: Option<Receiver<Vec<Span>>>,
+    /// Why the server can't be started, if it can't
+    last_error: Option<String>,
+    enabled: bool,
 }
 
 impl LspDiagnosticsProvider {
     /// Create new provider and spawn worker thread.
+    ///
+    /// The server process itself is started lazily on the first update.
     #[must_use]
     pub fn new(config: LspConfig) -> Self {
-        let (command_tx, command_rx) = bounded(CHANNEL_CAPACITY);
-        let (response_tx, response_rx) = bounded(CHANNEL_CAPACITY);
-        let (wake_tx, wake_rx) = bounded(1);
-
-        let worker = LspWorker {
-            uri: format!("{}:/session/repl", config.uri_scheme),
-            config,
-            conn: None,
-            version: 0,
-            command_rx,
-            response_tx,
-            wake_tx,
-        };
-
-        thread::spawn(move || worker.run());
+        let pending_content = PendingContent::default();
+        let (command_tx, response_rx, wake_rx, worker) =
+            spawn_worker(config.clone(), None, pending_content.clone());
 
         Self {
-            command_tx,
+            uri: config.document_uri(),
+            cursor_debounce: CursorDebounce::new(Duration::from_millis(config.cursor_debounce_ms)),
+            occurrences: Vec::new(),
+            occurrences_rx: None,
+            commands: LspCommandSender {
+                tx: Arc::new(RwLock::new(command_tx)),
+                running: Arc::default(),
+                failed: Arc::default(),
+                timeout_ms: config.timeout_ms,
+            },
+            config,
             response_rx,
             wake_rx,
+            pending_content,
+            worker: Some(worker),
             diagnostics: Vec::new(),
-            last_content_hash: 0,
+            colors: Vec::new(),
+            on_type_triggers: Vec::new(),
+            content: Arc::from(""),
+            document_contents: HashMap::new(),
+            document_diagnostics: HashMap::new(),
+            published_diagnostics: HashMap::new(),
+            source_filter: None,
+            workspace_edits: Vec::new(),
+            last_error: None,
+            enabled: true,
         }
     }
 
+    /// Restart the LSP server, optionally switching to a new configuration.
+    ///
+    /// The new server process is spawned before the old worker is shut down,
+    /// so on error the provider keeps using the previous server. On success the
+    /// diagnostics are cleared and the content of all documents is re-sent to
+    /// the new server. Completers and menus created from this provider keep working.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the server process could not be spawned.
+    pub fn restart(&mut self, new_config: Option<LspConfig>) -> io::Result<()> {
+        let config = new_config.unwrap_or_else(|| self.config.clone());
+        let child = spawn_server(&config.command, config.current_dir.as_deref())?;
+
+        self.commands.send(LspCommand::Shutdown);
+        if let Some(worker) = self.worker.take() {
+            worker.join();
+        }
+        let pending_content = PendingContent::default();
+        let (command_tx, response_rx, wake_rx, worker) =
+            spawn_worker(config.clone(), Some(child), pending_content.clone());
+        self.commands.replace(command_tx);
+        self.worker = Some(worker);
+        self.response_rx = response_rx;
+        self.wake_rx = wake_rx;
+        self.pending_content = pending_content;
+        self.uri = config.document_uri();
+        self.cursor_debounce =
+            CursorDebounce::new(Duration::from_millis(config.cursor_debounce_ms));
+        self.occurrences.clear();
+        self.occurrences_rx = None;
+        self.config = config;
+        self.diagnostics.clear();
+        self.colors.clear();
+        self.on_type_triggers.clear();
+        self.document_diagnostics.clear();
+        self.published_diagnostics.clear();
+        self.commands.set_running(None);
+        self.last_error = None;
+
+        let content = std::mem::replace(&mut self.content, Arc::from(""));
+        self.update_content(&content);
+        for (path, content) in std::mem::take(&mut self.document_contents) {
+            self.update_document(&path, &content);
+        }
+        Ok(())
+    }
+
     /// Update content (non-blocking). Sends to worker if content changed.
+    ///
+    /// Only the newest content waits for the worker: an update that arrives
+    /// before the worker synced the previous one replaces it, so the worker
+    /// always ends up with the latest content even when its queue is full.
     pub fn update_content(&mut self, content: &str) {
+        if *content != *self.content {
+            self.commands.clear_failed();
+        }
         if content.is_empty() {
             self.diagnostics.clear();
+            self.published_diagnostics.remove(&self.uri);
+            self.colors.clear();
+            self.content = Arc::from("");
+            return;
+        }
+
+        // Only send if content changed to avoid flooding the worker. The wake-up
+        // may be dropped on a full queue; the worker then picks the pending
+        // content up on its next poll.
+        if *content != *self.content {
+            let content: Arc<str> = content.into();
+            self.queue_content(self.uri.clone(), Arc::clone(&content));
+            self.content = content;
+        }
+    }
+
+    /// Note the cursor at `cursor` in `content` (non-blocking).
+    ///
+    /// With [`LspConfig::highlight_occurrences`], the occurrences of the
+    /// symbol under the cursor are asked for by [`Self::poll_cursor_requests`]
+    /// once the cursor rested there for [`LspConfig::cursor_debounce_ms`].
+    /// Moving the cursor drops the occurrences of the old position.
+    pub fn update_cursor(&mut self, content: &str, cursor: usize) {
+        if !self.config.highlight_occurrences {
+            return;
+        }
+        let moved = if content.is_empty() {
+            self.cursor_debounce.clear();
+            true
+        } else {
+            let content = self.shared_content(content);
+            self.cursor_debounce
+                .move_to(&content, cursor, Instant::now())
+        };
+        if moved {
+            self.occurrences.clear();
+            self.occurrences_rx = None;
+        }
+    }
+
+    /// Whether requests keyed on the cursor position wait to be sent or
+    /// answered, so [`Self::poll_cursor_requests`] has to be called again.
+    pub fn cursor_requests_pending(&self) -> bool {
+        self.occurrences_rx.is_some() || self.cursor_requests_due_in().is_some()
+    }
+
+    /// How long until the cursor rested long enough for its requests to be
+    /// sent, `None` if none wait to be.
+    pub fn cursor_requests_due_in(&self) -> Option<Duration> {
+        self.cursor_debounce.due_in(Instant::now())
+    }
+
+    /// Send the requests keyed on the cursor position once the cursor rested
+    /// long enough, and pick up their answers (non-blocking).
+    ///
+    /// Returns `true` if new occurrences arrived to be painted.
+    pub fn poll_cursor_requests(&mut self) -> bool {
+        if let Some((content, offset)) = self.cursor_debounce.take_due(Instant::now()) {
+            let (reply, rx) = bounded(1);
+            let sent = self.commands.send(LspCommand::RequestOccurrences {
+                content,
+                offset,
+                reply,
+            });
+            self.occurrences_rx = sent.then_some(rx);
+        }
+        let Some(rx) = &self.occurrences_rx else {
+            return false;
+        };
+        match rx.try_recv() {
+            Ok(occurrences) => {
+                self.occurrences_rx = None;
+                self.occurrences = occurrences;
+                true
+            }
+            Err(TryRecvError::Empty) => false,
+            Err(TryRecvError::Disconnected) => {
+                self.occurrences_rx = None;
+                false
+            }
+        }
+    }
+
+    /// The occurrences of the symbol under the cursor, empty until the
+    /// server answered for the current position.
+    pub fn occurrences(&self) -> &[Span] {
+        &self.occurrences
+    }
+
+    /// Update the content of the document at `path` (non-blocking).
+    ///
+    /// Opens the document on the server on first use. `path` is used like
+    /// [`LspConfig::document_path`]; updating the edited document itself is
+    /// the same as [`Self::update_content`]. Empty content drops the
+    /// document's diagnostics.
+    pub fn update_document(&mut self, path: &str, content: &str) {
+        if path == self.config.document_path {
+            return self.update_content(content);
+        }
+        if content.is_empty() {
+            self.document_contents.remove(path);
+            self.document_diagnostics.remove(path);
+            self.published_diagnostics
+                .remove(&self.config.uri_for(path));
             return;
         }
 
-        // Only send if content changed to avoid flooding the worker
-        let hash = hash_str(content);
-        if hash != self.last_content_hash {
-            self.last_content_hash = hash;
-            let _ = self
-                .command_tx
-                .try_send(LspCommand::UpdateContent(content.to_string()));
+        let changed = self
+            .document_contents
+            .get(path)
+            .map_or(true, |sent| **sent != *content);
+        if changed {
+            let content: Arc<str> = content.into();
+            self.queue_content(self.config.uri_for(path), Arc::clone(&content));
+            self.document_contents.insert(path.to_string(), content);
+        }
+    }
+
+    /// Hand `content` to the worker, waking it if nothing was pending yet.
+    fn queue_content(&self, uri: String, content: Arc<str>) {
+        if self.pending_content.replace(uri, content) {
+            self.commands.send(LspCommand::UpdateContent);
+        }
+    }
+
+    /// Share the last sent content when `content` matches it, copying otherwise.
+    fn shared_content(&self, content: &str) -> Arc<str> {
+        if *content == *self.content {
+            Arc::clone(&self.content)
+        } else {
+            content.into()
         }
     }
 
@@ -129,20 +957,234 @@ impl LspDiagnosticsProvider {
         &self.diagnostics
     }
 
+    /// The diagnostic under `pos` in `content`, polling for new responses first.
+    ///
+    /// A diagnostic is under every position from its start to its end. Where
+    /// several are, the most severe one is returned, the first of them on a tie.
+    pub fn diagnostic_at(&mut self, content: &str, pos: usize) -> Option<&Diagnostic> {
+        self.diagnostics()
+            .iter()
+            .filter(|d| {
+                let span = range_to_span(content, &d.range);
+                span.start <= pos && pos <= span.end
+            })
+            .min_by_key(|d| severity_rank(d.severity.unwrap_or(DiagnosticSeverity::WARNING)))
+    }
+
+    /// Get the diagnostics of the document at `path`, polling for new responses first.
+    ///
+    /// Empty for documents never passed to [`Self::update_document`].
+    pub fn document_diagnostics(&mut self, path: &str) -> &[Diagnostic] {
+        if path == self.config.document_path {
+            return self.diagnostics();
+        }
+        self.poll_responses();
+        self.document_diagnostics
+            .get(path)
+            .map_or(&[], Vec::as_slice)
+    }
+
+    /// Store diagnostics published for the document at `uri`.
+    ///
+    /// Repeats of a diagnostic, with the same range, severity, code and
+    /// message, are dropped, keeping the first one.
+    fn store_diagnostics(&mut self, uri: &str, mut diagnostics: Vec<Diagnostic>) {
+        dedup_diagnostics(&mut diagnostics);
+        let shown = self.filter_sources(&diagnostics);
+        if uri == self.uri {
+            self.diagnostics = shown;
+        } else {
+            let path = self
+                .document_contents
+                .keys()
+                .find(|path| self.config.uri_for(path) == uri);
+            let Some(path) = path else {
+                return;
+            };
+            self.document_diagnostics.insert(path.clone(), shown);
+        }
+        self.published_diagnostics
+            .insert(uri.to_string(), diagnostics);
+    }
+
+    /// The diagnostics passing the source filter
+    fn filter_sources(&self, diagnostics: &[Diagnostic]) -> Vec<Diagnostic> {
+        let Some(sources) = &self.source_filter else {
+            return diagnostics.to_vec();
+        };
+        diagnostics
+            .iter()
+            .filter(|diagnostic| {
+                diagnostic
+                    .source
+                    .as_ref()
+                    .map_or(false, |source| sources.contains(source))
+            })
+            .cloned()
+            .collect()
+    }
+
+    /// Sources whose diagnostics are shown, all of them if `None`.
+    pub fn source_filter(&self) -> Option<&HashSet<String>> {
+        self.source_filter.as_ref()
+    }
+
+    /// Show only the diagnostics whose `source` is one of `sources`, or all
+    /// diagnostics for `None`.
+    ///
+    /// Diagnostics without a source are hidden while a filter is set. Takes
+    /// effect right away for the diagnostics already published, without
+    /// asking the server again.
+    pub fn set_source_filter(&mut self, sources: Option<HashSet<String>>) {
+        self.source_filter = sources;
+        for (uri, diagnostics) in std::mem::take(&mut self.published_diagnostics) {
+            self.store_diagnostics(&uri, diagnostics);
+        }
+    }
+
+    /// Why the server can't be started, polling for new responses first.
+    ///
+    /// Set once starting the server failed because its binary is missing or
+    /// not executable, e.g. `nu-lint: No such file or directory`. The server
+    /// isn't tried again until [`Self::restart`].
+    pub fn last_error(&mut self) -> Option<&str> {
+        self.poll_responses();
+        self.last_error.as_deref()
+    }
+
+    /// Get the color literals reported by the server, polling for new responses first.
+    pub fn colors(&mut self) -> &[ColorInformation] {
+        self.poll_responses();
+        &self.colors
+    }
+
+    /// Whether to order the diagnostics by severity and position when showing them.
+    pub(super) fn sorts_diagnostics(&self) -> bool {
+        self.config.sort_diagnostics
+    }
+
+    /// Whether to show only the diagnostics on the line of the cursor.
+    pub(super) fn shows_current_line_only(&self) -> bool {
+        self.config.current_line_only
+    }
+
+    /// Whether diagnostics are currently displayed.
+    pub fn is_enabled(&self) -> bool {
+        self.enabled
+    }
+
+    /// Show or hide diagnostics.
+    ///
+    /// The worker keeps syncing content while hidden, so re-enabling shows
+    /// up-to-date diagnostics immediately.
+    pub fn set_enabled(&mut self, enabled: bool) {
+        self.enabled = enabled;
+    }
+
+    /// Get formatting edits for `ch`, just typed with the cursor at `offset`.
+    ///
+    /// Returns no edits unless [`LspConfig::format_on_type`] is set and the
+    /// server declared `ch` as an on-type formatting trigger. Blocks up to the
+    /// configured timeout while the server answers.
+    pub fn format_on_type(&mut self, content: &str, offset: usize, ch: char) -> Vec<TextEdit> {
+        if !self.config.format_on_type {
+            return Vec::new();
+        }
+        self.poll_responses();
+        let is_trigger = self
+            .on_type_triggers
+            .iter()
+            .any(|trigger| trigger.chars().eq(std::iter::once(ch)));
+        if !is_trigger {
+            return Vec::new();
+        }
+        self.commands.on_type_formatting(
+            self.shared_content(content),
+            offset,
+            ch,
+            self.config.timeout_ms,
+        )
+    }
+
+    /// Get the symbols of the edited document, followed by those of the
+    /// rest of the workspace.
+    ///
+    /// Symbols are requested once for the document and once for the
+    /// workspace, so this blocks up to twice the configured timeout while the
+    /// server answers.
+    pub fn symbols(&mut self, content: &str) -> Vec<LspSymbol> {
+        self.commands
+            .symbols(self.shared_content(content), 2 * self.config.timeout_ms)
+    }
+
     /// Get code actions for a given span.
     pub fn code_actions(&mut self, content: &str, span: Span) -> Vec<CodeAction> {
-        let _ = self.command_tx.try_send(LspCommand::RequestCodeActions {
-            content: content.to_string(),
+        self.commands.send(LspCommand::RequestCodeActions {
+            content: self.shared_content(content),
             span,
         });
+        self.wait_for_code_actions()
+    }
+
+    /// The edits of `action` to `content`, with their byte spans and the text
+    /// they replace, e.g. to preview a fix outside of the fix menu.
+    ///
+    /// Edits the server left out are asked for with `codeAction/resolve`
+    /// first (blocks up to the configured timeout). Empty for actions that
+    /// only run a command or that create, rename or delete files.
+    pub fn resolve_edits(&self, action: &CodeAction, content: &str) -> Vec<TextEditInfo> {
+        let resolved;
+        let action = if action.edit.is_none() && action.data.is_some() {
+            match self.commands.resolve_code_action(action.clone()) {
+                Some(action) => {
+                    resolved = action;
+                    &resolved
+                }
+                None => return Vec::new(),
+            }
+        } else {
+            action
+        };
+        if resource_operation(action).is_some() {
+            return Vec::new();
+        }
+        extract_text_edits(action, Some(&self.uri))
+            .unwrap_or_default()
+            .into_iter()
+            .map(|edit| text_edit_info(content, edit, None))
+            .collect()
+    }
 
-        // Brief wait for response
+    /// Get fixes converting a color literal to the server's other notations.
+    pub fn color_presentations(
+        &mut self,
+        content: &str,
+        color: &ColorInformation,
+    ) -> Vec<CodeAction> {
+        self.commands.send(LspCommand::RequestColorPresentations {
+            content: self.shared_content(content),
+            color: color.clone(),
+        });
+        self.wait_for_code_actions()
+    }
+
+    /// Briefly wait for a code actions response.
+    fn wait_for_code_actions(&mut self) -> Vec<CodeAction> {
         let start = Instant::now();
         while start.elapsed() < Duration::from_millis(100) {
             match self.response_rx.recv_timeout(Duration::from_millis(10)) {
                 Ok(LspResponse::CodeActions(actions)) => return actions,
-                Ok(LspResponse::Diagnostics(diags)) => self.diagnostics = diags,
-                Ok(LspResponse::CommandExecuted(_)) => {}
+                Ok(LspResponse::Diagnostics { uri, diagnostics }) => {
+                    self.store_diagnostics(&uri, diagnostics);
+                }
+                Ok(LspResponse::Colors(colors)) => self.colors = colors,
+                Ok(LspResponse::OnTypeTriggers(triggers)) => self.on_type_triggers = triggers,
+                Ok(LspResponse::WorkspaceEdit { content, edits }) => {
+                    self.workspace_edits.push((content, edits));
+                    self.commands.set_running(None);
+                }
+                Ok(LspResponse::CommandExecuted(result)) => self.commands.finish(&result),
+                Ok(LspResponse::Error(error)) => self.last_error = Some(error),
                 Err(_) => {}
             }
         }
@@ -151,36 +1193,101 @@ impl LspDiagnosticsProvider {
 
     /// Execute an LSP command on the server.
     ///
-    /// Returns `true` if the command was executed successfully.
+    /// Returns `true` if the command was executed successfully, see
+    /// [`Self::execute_command_with_result`] for the data the server returns.
     pub fn execute_command(&mut self, command: &str, arguments: Vec<serde_json::Value>) -> bool {
-        let _ = self.command_tx.try_send(LspCommand::ExecuteCommand {
+        self.execute_command_with_result(command, arguments)
+            .is_some()
+    }
+
+    /// Execute an LSP command on the server, returning the result it answered
+    /// with, e.g. a computed value.
+    ///
+    /// Returns `Some(Value::Null)` if the command succeeded without a result,
+    /// and `None` if it failed or the server didn't answer in time.
+    pub fn execute_command_with_result(
+        &mut self,
+        command: &str,
+        arguments: Vec<serde_json::Value>,
+    ) -> Option<serde_json::Value> {
+        self.commands.send(LspCommand::ExecuteCommand {
             command: command.to_string(),
             arguments,
         });
 
-        // Wait for response
+        // The worker answers once the server did or the command timed out,
+        // leaving it a request's timeout to pass that on
+        let timeout = self.config.command_timeout_ms + self.config.timeout_ms;
         let start = Instant::now();
-        while start.elapsed() < Duration::from_millis(500) {
+        while start.elapsed() < Duration::from_millis(timeout) {
             match self.response_rx.recv_timeout(Duration::from_millis(10)) {
-                Ok(LspResponse::CommandExecuted(success)) => return success,
-                Ok(LspResponse::Diagnostics(diags)) => self.diagnostics = diags,
+                Ok(LspResponse::CommandExecuted(result)) => {
+                    self.commands.finish(&result);
+                    return result.ok();
+                }
+                Ok(LspResponse::Diagnostics { uri, diagnostics }) => {
+                    self.store_diagnostics(&uri, diagnostics);
+                }
+                Ok(LspResponse::Colors(colors)) => self.colors = colors,
+                Ok(LspResponse::OnTypeTriggers(triggers)) => self.on_type_triggers = triggers,
+                Ok(LspResponse::WorkspaceEdit { content, edits }) => {
+                    self.workspace_edits.push((content, edits));
+                    self.commands.set_running(None);
+                }
                 Ok(LspResponse::CodeActions(_)) => {}
+                Ok(LspResponse::Error(error)) => self.last_error = Some(error),
                 Err(_) => {}
             }
         }
-        false
+        None
     }
 
     /// Poll for responses from worker (non-blocking).
     fn poll_responses(&mut self) {
         while let Ok(response) = self.response_rx.try_recv() {
             match response {
-                LspResponse::Diagnostics(diags) => self.diagnostics = diags,
-                LspResponse::CodeActions(_) | LspResponse::CommandExecuted(_) => {}
+                LspResponse::Diagnostics { uri, diagnostics } => {
+                    self.store_diagnostics(&uri, diagnostics);
+                }
+                LspResponse::Colors(colors) => self.colors = colors,
+                LspResponse::OnTypeTriggers(triggers) => self.on_type_triggers = triggers,
+                LspResponse::WorkspaceEdit { content, edits } => {
+                    self.workspace_edits.push((content, edits));
+                    self.commands.set_running(None);
+                }
+                LspResponse::CommandExecuted(result) => self.commands.finish(&result),
+                LspResponse::CodeActions(_) => {}
+                LspResponse::Error(error) => self.last_error = Some(error),
             }
         }
     }
 
+    /// Take the edits the server applied to the main document via
+    /// `workspace/applyEdit`, each with the content it applies to.
+    pub(crate) fn take_workspace_edits(&mut self) -> Vec<(Arc<str>, Vec<TextEdit>)> {
+        self.poll_responses();
+        std::mem::take(&mut self.workspace_edits)
+    }
+
+    /// URI the server knows the line being edited by.
+    pub(crate) fn document_uri(&self) -> &str {
+        &self.uri
+    }
+
+    /// Title of the command fix being executed, shown until the server answers.
+    pub fn running_command(&self) -> Option<String> {
+        self.commands.running()
+    }
+
+    /// Title of the last fix that could not be applied, shown until the line
+    /// changes.
+    ///
+    /// A fix fails when its edits don't fit the line anymore or the server
+    /// reports its command as failed.
+    pub fn failed_fix(&self) -> Option<String> {
+        self.commands.failed()
+    }
+
     /// Check if worker has signaled new diagnostics are available.
     /// If so, polls responses and returns true.
     pub fn check_wake(&mut self) -> bool {
@@ -194,25 +1301,661 @@ impl LspDiagnosticsProvider {
 
     /// Get a command sender for executing LSP commands from menus.
     pub fn command_sender(&self) -> LspCommandSender {
-        LspCommandSender {
-            tx: self.command_tx.clone(),
-        }
+        self.commands.clone()
+    }
+
+    /// Create a [`Completer`](crate::Completer) backed by the same LSP server.
+    ///
+    /// The completer shares this provider's worker thread and server process.
+    pub fn completer(&self) -> LspCompleter {
+        LspCompleter::new(self.command_sender(), self.config.timeout_ms)
     }
 }
 
 impl Drop for LspDiagnosticsProvider {
     fn drop(&mut self) {
-        let _ = self.command_tx.try_send(LspCommand::Shutdown);
-        // Worker will exit when channel disconnects
+        self.commands.send(LspCommand::Shutdown);
+        if let Some(worker) = self.worker.take() {
+            worker.join();
+        }
     }
 }
 
-fn hash_str(s: &str) -> u64 {
-    use std::{
-        collections::hash_map::DefaultHasher,
-        hash::{Hash, Hasher},
+/// Spawn a worker thread for `config`, returning its command, response and
+/// wake channels and the thread itself.
+///
+/// An already spawned server process can be passed in; otherwise the worker
+/// starts one on first use. The wake channel holds a single signal, as one
+/// pending wake-up already makes the provider poll all responses.
+fn spawn_worker(
+    config: LspConfig,
+    child: Option<Child>,
+    pending_content: PendingContent,
+) -> (
+    Sender<LspCommand>,
+    Receiver<LspResponse>,
+    Receiver<()>,
+    WorkerThread,
+) {
+    let capacity = config.channel_capacity.max(1);
+    let (command_tx, command_rx) = bounded(capacity);
+    let (response_tx, response_rx) = bounded(capacity);
+    let (wake_tx, wake_rx) = bounded(1);
+    let process = ServerProcess::default();
+
+    let worker = LspWorker {
+        uri: config.document_uri(),
+        config,
+        conn: None,
+        spawned: child,
+        process: process.clone(),
+        unstartable: false,
+        documents: HashMap::new(),
+        pending_content,
+        command_rx,
+        response_tx,
+        wake_tx,
     };
-    let mut h = DefaultHasher::new();
-    s.hash(&mut h);
-    h.finish()
+
+    let thread = thread::spawn(move || worker.run());
+
+    (
+        command_tx,
+        response_rx,
+        wake_rx,
+        WorkerThread { thread, process },
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lsp::{diagnostic::format_diagnostic_messages, format_diagnostics_for_prompt};
+    use lsp_types::{DiagnosticSeverity, Position, Range};
+
+    fn config(command: &str) -> LspConfig {
+        LspConfig::new(command)
+    }
+
+    #[test]
+    fn restart_reports_spawn_failure_and_keeps_config() {
+        let mut provider = LspDiagnosticsProvider::new(config("reedline-test-old-server"));
+
+        let result = provider.restart(Some(config("reedline-test-missing-server")));
+
+        assert!(result.is_err());
+        assert_eq!(provider.config.command, "reedline-test-old-server");
+    }
+
+    #[test]
+    fn restart_rejects_empty_command() {
+        let mut provider = LspDiagnosticsProvider::new(config("reedline-test-old-server"));
+
+        let err = provider.restart(Some(config("  "))).unwrap_err();
+
+        assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+    }
+
+    #[test]
+    fn format_on_type_only_fires_for_declared_triggers() {
+        let mut provider = LspDiagnosticsProvider::new(LspConfig {
+            format_on_type: true,
+            ..config("reedline-test-missing-server")
+        });
+        provider.on_type_triggers = vec!["}".to_string()];
+
+        // Not a trigger: answered immediately without asking the worker
+        let start = Instant::now();
+        assert!(provider.format_on_type("if x {", 6, '{').is_empty());
+        assert!(start.elapsed() < Duration::from_millis(100));
+
+        // A trigger, but the server cannot be started
+        assert!(provider.format_on_type("if x { }", 8, '}').is_empty());
+    }
+
+    #[test]
+    fn format_on_type_is_opt_in() {
+        let mut provider = LspDiagnosticsProvider::new(config("reedline-test-missing-server"));
+        provider.on_type_triggers = vec!["}".to_string()];
+
+        assert!(provider.format_on_type("if x { }", 8, '}').is_empty());
+    }
+
+    const URI: &str = "repl:/session/repl";
+
+    fn pending(provider: &LspDiagnosticsProvider) -> Vec<(String, String)> {
+        provider
+            .pending_content
+            .take()
+            .into_iter()
+            .map(|(uri, content)| (uri, content.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn update_content_sends_only_changed_content() {
+        let mut provider = LspDiagnosticsProvider::new(config("reedline-test-missing-server"));
+        let (tx, rx) = bounded(DEFAULT_CHANNEL_CAPACITY);
+        provider.commands.replace(tx);
+        provider.pending_content = PendingContent::default();
+
+        provider.update_content("let x = 1");
+        assert_eq!(pending(&provider), vec![(URI.into(), "let x = 1".into())]);
+        provider.update_content("let x = 1");
+        assert!(pending(&provider).is_empty());
+        provider.update_content("");
+        provider.update_content("let x = 1");
+        assert_eq!(pending(&provider), vec![(URI.into(), "let x = 1".into())]);
+
+        let wake_ups = rx
+            .try_iter()
+            .filter(|command| matches!(command, LspCommand::UpdateContent))
+            .count();
+        assert_eq!(wake_ups, 2);
+    }
+
+    #[test]
+    fn occurrences_are_asked_for_once_the_cursor_rests() {
+        let mut provider = LspDiagnosticsProvider::new(
+            config("reedline-test-missing-server")
+                .with_highlight_occurrences(true)
+                .with_cursor_debounce_ms(50),
+        );
+        let (tx, rx) = bounded(DEFAULT_CHANNEL_CAPACITY);
+        provider.commands.replace(tx);
+        let occurrence_requests = || {
+            rx.try_iter()
+                .filter_map(|command| match command {
+                    LspCommand::RequestOccurrences { offset, reply, .. } => {
+                        let _ = reply.send(vec![Span::new(offset, offset + 1)]);
+                        Some(offset)
+                    }
+                    _ => None,
+                })
+                .collect::<Vec<_>>()
+        };
+
+        for cursor in 0..5 {
+            provider.update_cursor("echo hello", cursor);
+            assert!(!provider.poll_cursor_requests());
+        }
+        assert!(provider.cursor_requests_pending());
+        assert!(occurrence_requests().is_empty());
+
+        thread::sleep(Duration::from_millis(60));
+        provider.update_cursor("echo hello", 4);
+        assert!(!provider.poll_cursor_requests());
+        assert_eq!(occurrence_requests(), [4]);
+        assert!(provider.poll_cursor_requests());
+        assert_eq!(provider.occurrences(), [Span::new(4, 5)]);
+        assert!(!provider.cursor_requests_pending());
+
+        provider.update_cursor("echo hello", 5);
+        assert!(provider.occurrences().is_empty());
+    }
+
+    #[test]
+    fn occurrences_are_opt_in() {
+        let mut provider = LspDiagnosticsProvider::new(
+            config("reedline-test-missing-server").with_cursor_debounce_ms(0),
+        );
+        provider.update_cursor("echo hello", 4);
+        assert!(!provider.cursor_requests_pending());
+    }
+
+    #[test]
+    fn update_content_keeps_the_newest_when_the_queue_is_full() {
+        let mut provider = LspDiagnosticsProvider::new(config("reedline-test-missing-server"));
+        let (tx, rx) = bounded(1);
+        tx.send(LspCommand::Shutdown).unwrap();
+        provider.commands.replace(tx);
+        provider.pending_content = PendingContent::default();
+
+        provider.update_content("let x = 1");
+        provider.update_content("let x = 12");
+        provider.update_content("let x = 123");
+
+        assert_eq!(pending(&provider), vec![(URI.into(), "let x = 123".into())]);
+        assert!(matches!(rx.try_recv(), Ok(LspCommand::Shutdown)));
+        assert!(rx.try_recv().is_err());
+    }
+
+    #[test]
+    fn document_uri_uses_the_configured_path() {
+        assert_eq!(config("server").document_uri(), URI);
+        assert_eq!(
+            LspConfig {
+                uri_scheme: "file".to_string(),
+                ..config("server").with_document_path("/tmp/my repl.nu")
+            }
+            .document_uri(),
+            "file:///tmp/my%20repl.nu"
+        );
+    }
+
+    #[test]
+    fn update_document_queues_each_document_once() {
+        let mut provider = LspDiagnosticsProvider::new(config("reedline-test-missing-server"));
+        let (tx, rx) = bounded(DEFAULT_CHANNEL_CAPACITY);
+        provider.commands.replace(tx);
+        provider.pending_content = PendingContent::default();
+
+        provider.update_document("/lib.nu", "def f [] {}");
+        provider.update_content("f");
+        provider.update_document("/lib.nu", "def f [] { 1 }");
+        provider.update_document("/session/repl", "f 1");
+
+        assert_eq!(
+            pending(&provider),
+            vec![
+                ("repl:/lib.nu".into(), "def f [] { 1 }".into()),
+                (URI.into(), "f 1".into()),
+            ]
+        );
+        assert_eq!(rx.try_iter().count(), 1);
+
+        provider.store_diagnostics("repl:/lib.nu", vec![Diagnostic::default()]);
+        provider.store_diagnostics("repl:/unknown.nu", vec![Diagnostic::default()]);
+        assert_eq!(provider.document_diagnostics("/lib.nu").len(), 1);
+        assert!(provider.document_diagnostics("/unknown.nu").is_empty());
+        assert!(provider.diagnostics().is_empty());
+
+        provider.update_document("/lib.nu", "");
+        assert!(provider.document_diagnostics("/lib.nu").is_empty());
+    }
+
+    #[test]
+    fn source_filter_hides_other_sources_until_cleared() {
+        let mut provider = LspDiagnosticsProvider::new(config("reedline-test-missing-server"));
+        let (tx, _rx) = bounded(DEFAULT_CHANNEL_CAPACITY);
+        provider.commands.replace(tx);
+        let from = |source: Option<&str>| Diagnostic {
+            source: source.map(str::to_string),
+            message: format!("from {source:?}"),
+            ..Diagnostic::default()
+        };
+        let sources = |diagnostics: &[Diagnostic]| -> Vec<Option<String>> {
+            diagnostics.iter().map(|d| d.source.clone()).collect()
+        };
+
+        provider.update_document("/lib.nu", "def f [] { 1 }");
+        provider.store_diagnostics(
+            URI,
+            vec![from(Some("nu-lint")), from(Some("types")), from(None)],
+        );
+        provider.store_diagnostics("repl:/lib.nu", vec![from(Some("types"))]);
+
+        provider.set_source_filter(Some(HashSet::from(["nu-lint".to_string()])));
+        assert_eq!(
+            sources(provider.diagnostics()),
+            [Some("nu-lint".to_string())]
+        );
+        assert!(provider.document_diagnostics("/lib.nu").is_empty());
+
+        // Diagnostics published while filtering are filtered too
+        provider.store_diagnostics(URI, vec![from(Some("types")), from(Some("nu-lint"))]);
+        assert_eq!(
+            sources(provider.diagnostics()),
+            [Some("nu-lint".to_string())]
+        );
+
+        provider.set_source_filter(None);
+        assert_eq!(
+            sources(provider.diagnostics()),
+            [Some("types".to_string()), Some("nu-lint".to_string())]
+        );
+        assert_eq!(provider.document_diagnostics("/lib.nu").len(), 1);
+    }
+
+    #[test]
+    fn repeated_diagnostics_are_rendered_once() {
+        let mut provider = LspDiagnosticsProvider::new(config("reedline-test-missing-server"));
+        let diagnostic = |start: u32, message: &str| Diagnostic {
+            range: Range::new(Position::new(0, start), Position::new(0, start + 2)),
+            severity: Some(DiagnosticSeverity::WARNING),
+            message: message.to_string(),
+            ..Diagnostic::default()
+        };
+        let error = Diagnostic {
+            severity: Some(DiagnosticSeverity::ERROR),
+            ..diagnostic(0, "unused")
+        };
+
+        provider.store_diagnostics(
+            URI,
+            vec![
+                diagnostic(0, "unused"),
+                diagnostic(3, "shadowed"),
+                diagnostic(0, "unused"),
+                error.clone(),
+                diagnostic(3, "shadowed"),
+            ],
+        );
+
+        assert_eq!(
+            provider.diagnostics(),
+            [diagnostic(0, "unused"), diagnostic(3, "shadowed"), error]
+        );
+        let rendered = format_diagnostic_messages(provider.diagnostics(), "ls ab", 0, false, true);
+        assert_eq!(rendered.matches("shadowed").count(), 1);
+    }
+
+    #[test]
+    fn diagnostic_at_prefers_the_most_severe_overlapping_one() {
+        let mut provider = LspDiagnosticsProvider::new(config("reedline-test-missing-server"));
+        let diagnostic = |start: u32, end: u32, severity, message: &str| Diagnostic {
+            range: Range::new(Position::new(0, start), Position::new(0, end)),
+            severity,
+            message: message.to_string(),
+            ..Diagnostic::default()
+        };
+        provider.store_diagnostics(
+            URI,
+            vec![
+                diagnostic(0, 5, None, "unknown severity"),
+                diagnostic(0, 5, Some(DiagnosticSeverity::HINT), "hint"),
+                diagnostic(3, 8, Some(DiagnosticSeverity::ERROR), "error"),
+                diagnostic(12, 14, Some(DiagnosticSeverity::WARNING), "warning"),
+            ],
+        );
+        let content = "let abc = 1; ab";
+
+        let at = |provider: &mut LspDiagnosticsProvider, pos| {
+            provider
+                .diagnostic_at(content, pos)
+                .map(|d| d.message.clone())
+        };
+        assert_eq!(at(&mut provider, 1).as_deref(), Some("unknown severity"));
+        assert_eq!(at(&mut provider, 4).as_deref(), Some("error"));
+        assert_eq!(at(&mut provider, 8).as_deref(), Some("error"));
+        assert_eq!(at(&mut provider, 10), None);
+        assert_eq!(at(&mut provider, 14).as_deref(), Some("warning"));
+    }
+
+    #[test]
+    fn resolve_edits_gives_byte_spans_and_replaced_text() {
+        let provider = LspDiagnosticsProvider::new(config("reedline-test-missing-server"));
+        let edit = |new_text: &str| {
+            serde_json::json!({
+                "range": {
+                    "start": { "line": 0, "character": 3 },
+                    "end": { "line": 0, "character": 5 },
+                },
+                "newText": new_text,
+            })
+        };
+        let action =
+            |value: serde_json::Value| -> CodeAction { serde_json::from_value(value).unwrap() };
+        let rename = action(serde_json::json!({
+            "title": "Rename to abc",
+            "edit": { "changes": { URI: [edit("abc")] } },
+        }));
+        let extract = action(serde_json::json!({
+            "title": "Extract to module",
+            "edit": { "documentChanges": [
+                { "kind": "create", "uri": "file:///tmp/module.nu" },
+                { "textDocument": { "uri": URI, "version": null }, "edits": [edit("m")] },
+            ]},
+        }));
+        let lazy = action(serde_json::json!({ "title": "Remove", "data": 7 }));
+
+        let edits = provider.resolve_edits(&rename, "ls ab | length");
+
+        let edits: Vec<_> = edits
+            .iter()
+            .map(|edit| (edit.span, edit.original.as_str(), edit.replacement.as_str()))
+            .collect();
+        assert_eq!(edits, [(Span::new(3, 5), "ab", "abc")]);
+        assert!(provider.resolve_edits(&extract, "ls ab").is_empty());
+        // Without a server to resolve it, there is nothing to preview
+        assert!(provider.resolve_edits(&lazy, "ls ab").is_empty());
+    }
+
+    #[test]
+    fn current_line_only_shows_the_diagnostics_of_the_cursor_line() {
+        let buffer = "let a = 1\nlet b = (\n  2)\nlet c = 3";
+        let diagnostic = |start: u32, end: u32, message: &str| Diagnostic {
+            range: Range::new(Position::new(start, 4), Position::new(end, 5)),
+            message: message.to_string(),
+            ..Diagnostic::default()
+        };
+        let diagnostics = vec![
+            diagnostic(0, 0, "unused a"),
+            diagnostic(1, 2, "unused b"),
+            diagnostic(3, 3, "unused c"),
+        ];
+        let shown = |current_line_only: bool, cursor: usize| {
+            let config =
+                config("reedline-test-missing-server").with_current_line_only(current_line_only);
+            let mut provider = LspDiagnosticsProvider::new(config);
+            provider.store_diagnostics(URI, diagnostics.clone());
+            let rendered = format_diagnostics_for_prompt(
+                &mut provider,
+                buffer,
+                cursor,
+                &crate::DefaultPrompt::default(),
+                crate::PromptEditMode::Default,
+                80,
+                false,
+            );
+            diagnostics
+                .iter()
+                .filter(|d| rendered.contains(&d.message))
+                .map(|d| d.message.as_str())
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(shown(false, 0), ["unused a", "unused b", "unused c"]);
+        assert_eq!(shown(true, 2), ["unused a"]);
+        assert_eq!(shown(true, buffer.find("2)").unwrap()), ["unused b"]);
+        assert_eq!(shown(true, buffer.len()), ["unused c"]);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn workspace_folders_are_absolute_directory_uris() {
+        let current_dir = std::env::current_dir().unwrap();
+        let folders = config("server")
+            .with_workspace_folders(vec!["/work/app".into(), "modules".into(), "/".into()])
+            .lsp_workspace_folders();
+
+        let folders: Vec<_> = folders
+            .into_iter()
+            .map(|folder| (folder.uri.to_string(), folder.name))
+            .collect();
+        assert_eq!(
+            folders,
+            [
+                ("file:///work/app/".to_string(), "app".to_string()),
+                (
+                    lsp_types::Url::from_directory_path(current_dir.join("modules"))
+                        .unwrap()
+                        .to_string(),
+                    "modules".to_string()
+                ),
+                ("file:///".to_string(), "/".to_string()),
+            ]
+        );
+        assert!(config("server").lsp_workspace_folders().is_empty());
+    }
+
+    #[test]
+    fn titled_command_runs_until_the_server_answers() {
+        let mut provider = LspDiagnosticsProvider::new(config("reedline-test-missing-server"));
+        let (tx, _rx) = bounded(DEFAULT_CHANNEL_CAPACITY);
+        provider.commands.replace(tx);
+        let (response_tx, response_rx) = bounded(1);
+        provider.response_rx = response_rx;
+
+        provider.command_sender().execute_titled_command(
+            "Fix all".to_string(),
+            "fix.all".to_string(),
+            Vec::new(),
+        );
+        assert_eq!(provider.running_command().as_deref(), Some("Fix all"));
+
+        response_tx
+            .send(LspResponse::CommandExecuted(Err(RequestError::Failed)))
+            .unwrap();
+        provider.diagnostics();
+        assert_eq!(provider.running_command(), None);
+        assert_eq!(provider.failed_fix().as_deref(), Some("Fix all"));
+
+        // Shown until the line changes
+        provider.update_content("");
+        assert_eq!(provider.failed_fix().as_deref(), Some("Fix all"));
+        provider.update_content("ls");
+        assert_eq!(provider.failed_fix(), None);
+    }
+
+    #[test]
+    fn executed_commands_return_the_result_of_the_server() {
+        let mut provider = LspDiagnosticsProvider::new(config("reedline-test-missing-server"));
+        let (tx, _rx) = bounded(DEFAULT_CHANNEL_CAPACITY);
+        provider.commands.replace(tx);
+        let (response_tx, response_rx) = bounded(2);
+        provider.response_rx = response_rx;
+        let result = serde_json::json!({ "value": 42, "unit": "ms" });
+
+        response_tx
+            .send(LspResponse::CommandExecuted(Ok(result.clone())))
+            .unwrap();
+        assert_eq!(
+            provider.execute_command_with_result("measure", Vec::new()),
+            Some(result.clone())
+        );
+
+        // Boolean callers only see whether the command succeeded
+        response_tx
+            .send(LspResponse::CommandExecuted(Ok(result)))
+            .unwrap();
+        assert!(provider.execute_command("measure", Vec::new()));
+        response_tx
+            .send(LspResponse::CommandExecuted(Err(RequestError::Failed)))
+            .unwrap();
+        assert!(!provider.execute_command("measure", Vec::new()));
+    }
+
+    #[test]
+    fn timed_out_commands_are_not_reported_as_failed() {
+        let mut provider = LspDiagnosticsProvider::new(config("reedline-test-missing-server"));
+        let (tx, _rx) = bounded(DEFAULT_CHANNEL_CAPACITY);
+        provider.commands.replace(tx);
+        let (response_tx, response_rx) = bounded(1);
+        provider.response_rx = response_rx;
+
+        provider.command_sender().execute_titled_command(
+            "Fix all".to_string(),
+            "fix.all".to_string(),
+            Vec::new(),
+        );
+        response_tx
+            .send(LspResponse::CommandExecuted(Err(RequestError::TimedOut)))
+            .unwrap();
+        provider.diagnostics();
+
+        assert_eq!(provider.running_command(), None);
+        assert_eq!(provider.failed_fix(), None);
+    }
+
+    #[test]
+    fn commands_answered_with_null_succeed() {
+        let mut provider = LspDiagnosticsProvider::new(config("reedline-test-missing-server"));
+        let (tx, _rx) = bounded(DEFAULT_CHANNEL_CAPACITY);
+        provider.commands.replace(tx);
+        let (response_tx, response_rx) = bounded(1);
+        provider.response_rx = response_rx;
+
+        response_tx
+            .send(LspResponse::CommandExecuted(Ok(serde_json::Value::Null)))
+            .unwrap();
+        assert!(provider.execute_command("fix.all", Vec::new()));
+
+        provider.command_sender().execute_titled_command(
+            "Fix all".to_string(),
+            "fix.all".to_string(),
+            Vec::new(),
+        );
+        response_tx
+            .send(LspResponse::CommandExecuted(Ok(serde_json::Value::Null)))
+            .unwrap();
+        provider.diagnostics();
+        assert_eq!(provider.running_command(), None);
+        assert_eq!(provider.failed_fix(), None);
+    }
+
+    #[test]
+    fn zero_channel_capacity_still_queues_commands() {
+        let provider = LspDiagnosticsProvider::new(
+            config("reedline-test-missing-server").with_channel_capacity(0),
+        );
+
+        assert!(provider.command_sender().send(LspCommand::ExecuteCommand {
+            command: "test".to_string(),
+            arguments: Vec::new(),
+        }));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn missing_server_sets_the_last_error_until_restart() {
+        let mut provider = LspDiagnosticsProvider::new(config("reedline-test-missing-server"));
+        assert_eq!(provider.last_error(), None);
+
+        provider.update_content("ls");
+        let start = Instant::now();
+        while provider.last_error().is_none() && start.elapsed() < Duration::from_secs(2) {
+            thread::sleep(Duration::from_millis(5));
+        }
+        assert_eq!(
+            provider.last_error(),
+            Some("reedline-test-missing-server: No such file or directory")
+        );
+
+        provider.last_error = Some("stale".to_string());
+        provider.restart(Some(config("cat"))).unwrap();
+        assert_eq!(provider.last_error(), None);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn dropping_kills_a_server_stuck_in_the_handshake() {
+        // Never answers `initialize`, leaving the worker waiting for it
+        let mut provider = LspDiagnosticsProvider::new(config("sleep 30"));
+        provider.update_content("ls");
+        let process = provider.worker.as_ref().unwrap().process.clone();
+        let pid = |process: &ServerProcess| process.0.lock().unwrap().as_ref().map(Child::id);
+        let start = Instant::now();
+        let server = loop {
+            if let Some(server) = pid(&process) {
+                break server;
+            }
+            assert!(
+                start.elapsed() < Duration::from_secs(2),
+                "server not started"
+            );
+            thread::sleep(Duration::from_millis(5));
+        };
+
+        let start = Instant::now();
+        drop(provider);
+
+        assert!(start.elapsed() < Duration::from_secs(1));
+        assert_eq!(pid(&process), None);
+        // Killed and reaped, so there is no such process anymore
+        assert_ne!(unsafe { libc::kill(server as libc::pid_t, 0) }, 0);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn restart_switches_config_and_clears_diagnostics() {
+        let mut provider = LspDiagnosticsProvider::new(config("reedline-test-old-server"));
+        provider.diagnostics.push(Diagnostic::default());
+
+        provider.restart(Some(config("cat"))).unwrap();
+
+        assert_eq!(provider.config.command, "cat");
+        assert!(provider.diagnostics.is_empty());
+        assert!(provider.content.is_empty());
+    }
 }
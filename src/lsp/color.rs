@@ -0,0 +1,304 @@
+//! Document color support for LSP integration.
+//!
+//! Fetches color literals (`textDocument/documentColor`) so a swatch can be
+//! drawn in front of each one, and offers the server's alternative notations
+//! (`textDocument/colorPresentation`) as fixes.
+
+use std::collections::HashMap;
+
+use lsp_types::{
+    Color, ColorInformation, ColorPresentation, ColorPresentationParams, DocumentColorParams,
+    TextDocumentIdentifier, TextEdit, WorkspaceEdit,
+};
+use nu_ansi_term::{Color as AnsiColor, Style};
+use serde_json::Value;
+
+use super::diagnostic::{range_to_span, CodeAction};
+use crate::StyledText;
+
+/// Block drawn in front of a color literal.
+const SWATCH: &str = "■ ";
+
+/// Request the color literals in the document.
+pub(super) fn request_document_colors<F>(
+    uri: &str,
+    timeout_ms: u64,
+    request_fn: F,
+) -> Vec<ColorInformation>
+where
+    F: FnOnce(&str, &DocumentColorParams, u64) -> Option<Value>,
+{
+    let Some(uri) = uri.parse().ok() else {
+        return Vec::new();
+    };
+
+    let params = DocumentColorParams {
+        text_document: TextDocumentIdentifier { uri },
+        work_done_progress_params: Default::default(),
+        partial_result_params: Default::default(),
+    };
+
+    request_fn("textDocument/documentColor", &params, timeout_ms)
+        .and_then(|v| serde_json::from_value(v).ok())
+        .unwrap_or_default()
+}
+
+/// Request alternative notations for a color literal.
+pub(super) fn request_color_presentations<F>(
+    uri: &str,
+    color: &ColorInformation,
+    timeout_ms: u64,
+    request_fn: F,
+) -> Vec<ColorPresentation>
+where
+    F: FnOnce(&str, &ColorPresentationParams, u64) -> Option<Value>,
+{
+    let Some(uri) = uri.parse().ok() else {
+        return Vec::new();
+    };
+
+    let params = ColorPresentationParams {
+        text_document: TextDocumentIdentifier { uri },
+        color: color.color,
+        range: color.range,
+        work_done_progress_params: Default::default(),
+        partial_result_params: Default::default(),
+    };
+
+    request_fn("textDocument/colorPresentation", &params, timeout_ms)
+        .and_then(|v| serde_json::from_value(v).ok())
+        .unwrap_or_default()
+}
+
+/// Turn color presentations into code actions for the diagnostic fix menu.
+///
+/// Presentations without an explicit edit replace the color literal with their label.
+/// Presentations that would not change the text are skipped.
+pub(super) fn presentations_to_actions(
+    uri: &str,
+    content: &str,
+    color: &ColorInformation,
+    presentations: Vec<ColorPresentation>,
+) -> Vec<CodeAction> {
+    let Some(uri) = uri.parse::<lsp_types::Url>().ok() else {
+        return Vec::new();
+    };
+    let span = range_to_span(content, &color.range);
+    let current = content.get(span.start..span.end).unwrap_or_default();
+
+    presentations
+        .into_iter()
+        .filter_map(|presentation| {
+            let edit = presentation.text_edit.unwrap_or_else(|| TextEdit {
+                range: color.range,
+                new_text: presentation.label.clone(),
+            });
+            if edit.new_text == current {
+                return None;
+            }
+            let mut edits = vec![edit];
+            edits.extend(presentation.additional_text_edits.unwrap_or_default());
+
+            Some(CodeAction {
+                title: format!("Convert to {}", presentation.label),
+                edit: Some(WorkspaceEdit {
+                    changes: Some(HashMap::from([(uri.clone(), edits)])),
+                    ..Default::default()
+                }),
+                ..Default::default()
+            })
+        })
+        .collect()
+}
+
+/// Insert a colored swatch in front of every color literal.
+///
+/// Returns the buffer offsets the swatches were inserted at, for mapping
+/// between the buffer and the rendered text with
+/// [`offset_with_swatches`] and [`offset_without_swatches`].
+pub(crate) fn insert_color_swatches(
+    styled_text: &mut StyledText,
+    content: &str,
+    colors: &[ColorInformation],
+) -> Vec<usize> {
+    let mut swatches: Vec<(usize, Style)> = colors
+        .iter()
+        .map(|info| {
+            let offset = range_to_span(content, &info.range).start;
+            (offset, swatch_style(info.color))
+        })
+        .filter(|(offset, _)| *offset < content.len())
+        .collect();
+    swatches.sort_by_key(|(offset, _)| *offset);
+    swatches.dedup_by_key(|(offset, _)| *offset);
+    let offsets = swatches.iter().map(|(offset, _)| *offset).collect();
+
+    let mut pending = swatches.into_iter().peekable();
+    let mut buffer = Vec::with_capacity(styled_text.buffer.len());
+    let mut start = 0;
+    for (style, mut text) in std::mem::take(&mut styled_text.buffer) {
+        let end = start + text.len();
+        let mut piece_start = start;
+        while let Some((offset, swatch)) = pending.next_if(|(offset, _)| *offset < end) {
+            let rest = text.split_off(offset.saturating_sub(piece_start).min(text.len()));
+            if !text.is_empty() {
+                buffer.push((style, text));
+            }
+            buffer.push((swatch, SWATCH.to_string()));
+            text = rest;
+            piece_start = offset.max(piece_start);
+        }
+        if !text.is_empty() {
+            buffer.push((style, text));
+        }
+        start = end;
+    }
+    styled_text.buffer = buffer;
+
+    offsets
+}
+
+/// Offset in the rendered text of `offset` in the buffer, after the swatches
+/// inserted at or before it
+pub(crate) fn offset_with_swatches(swatches: &[usize], offset: usize) -> usize {
+    offset + swatches.iter().filter(|swatch| **swatch <= offset).count() * SWATCH.len()
+}
+
+/// Offset in the buffer of `offset` in the rendered text, e.g. of a click. A
+/// swatch maps to the color literal behind it
+pub(crate) fn offset_without_swatches(swatches: &[usize], offset: usize) -> usize {
+    let mut shift = 0;
+    for &swatch in swatches {
+        let rendered = swatch + shift;
+        if offset < rendered {
+            break;
+        }
+        if offset < rendered + SWATCH.len() {
+            return swatch;
+        }
+        shift += SWATCH.len();
+    }
+    offset - shift
+}
+
+fn swatch_style(color: Color) -> Style {
+    let channel = |value: f32| (value.clamp(0.0, 1.0) * 255.0).round() as u8;
+    Style::new().fg(AnsiColor::Rgb(
+        channel(color.red),
+        channel(color.green),
+        channel(color.blue),
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use lsp_types::{Position, Range};
+    use pretty_assertions::assert_eq;
+
+    fn color_at(start: u32, end: u32) -> ColorInformation {
+        ColorInformation {
+            range: Range {
+                start: Position {
+                    line: 0,
+                    character: start,
+                },
+                end: Position {
+                    line: 0,
+                    character: end,
+                },
+            },
+            color: Color {
+                red: 1.0,
+                green: 0.0,
+                blue: 0.0,
+                alpha: 1.0,
+            },
+        }
+    }
+
+    fn styled(parts: &[&str]) -> StyledText {
+        let mut text = StyledText::new();
+        for part in parts {
+            text.push((Style::new(), part.to_string()));
+        }
+        text
+    }
+
+    #[test]
+    fn swatches_are_inserted_before_color_literals() {
+        let content = "fg #ff0000 bg #00ff00";
+        let mut text = styled(&["fg ", "#ff0000 bg #00ff00"]);
+
+        let swatches =
+            insert_color_swatches(&mut text, content, &[color_at(3, 10), color_at(14, 21)]);
+
+        assert_eq!(text.raw_string(), "fg ■ #ff0000 bg ■ #00ff00");
+        assert_eq!(swatches, [3, 14]);
+        assert_eq!(
+            offset_with_swatches(&swatches, content.len()),
+            content.len() + 2 * SWATCH.len()
+        );
+        let swatch = &text.buffer[1];
+        assert_eq!(swatch.0.foreground, Some(AnsiColor::Rgb(255, 0, 0)));
+    }
+
+    #[test]
+    fn cursor_before_literal_is_not_shifted() {
+        let content = "fg #ff0000";
+        let mut text = styled(&[content]);
+
+        let swatches = insert_color_swatches(&mut text, content, &[color_at(3, 10)]);
+
+        assert_eq!(offset_with_swatches(&swatches, 2), 2);
+        assert_eq!(text.raw_string(), "fg ■ #ff0000");
+    }
+
+    #[test]
+    fn rendered_offsets_map_back_to_the_buffer() {
+        // "fg ■ #ff0000 bg ■ #00ff00" rendered from "fg #ff0000 bg #00ff00"
+        let swatches = [3, 14];
+        assert_eq!(offset_without_swatches(&swatches, 2), 2);
+        // The swatch and the literal behind it
+        assert_eq!(offset_without_swatches(&swatches, 3), 3);
+        assert_eq!(offset_without_swatches(&swatches, 6), 3);
+        assert_eq!(offset_without_swatches(&swatches, 7), 3);
+        assert_eq!(offset_without_swatches(&swatches, 8), 4);
+        // After the second swatch
+        assert_eq!(offset_without_swatches(&swatches, 26), 18);
+        for offset in [0, 3, 10, 14, 21] {
+            let rendered = offset_with_swatches(&swatches, offset);
+            assert_eq!(offset_without_swatches(&swatches, rendered), offset);
+        }
+    }
+
+    #[test]
+    fn presentations_become_replacement_actions() {
+        let content = "fg #ff0000";
+        let color = color_at(3, 10);
+        let presentations = vec![
+            ColorPresentation {
+                label: "#ff0000".into(),
+                ..Default::default()
+            },
+            ColorPresentation {
+                label: "rgb(255, 0, 0)".into(),
+                ..Default::default()
+            },
+        ];
+
+        let actions =
+            presentations_to_actions("repl:/session/repl", content, &color, presentations);
+
+        assert_eq!(actions.len(), 1);
+        assert_eq!(actions[0].title, "Convert to rgb(255, 0, 0)");
+        let edits = actions[0]
+            .edit
+            .as_ref()
+            .and_then(|edit| edit.changes.as_ref())
+            .and_then(|changes| changes.values().next())
+            .expect("action has edits");
+        assert_eq!(edits[0].new_text, "rgb(255, 0, 0)");
+        assert_eq!(edits[0].range, color.range);
+    }
+}
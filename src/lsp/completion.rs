@@ -0,0 +1,304 @@
+//! Completion support for LSP integration.
+//!
+//! Converts LSP completion items into reedline [`Suggestion`]s so the LSP
+//! server can drive the regular completion menus.
+
+use itertools::Itertools;
+use lsp_types::{
+    CompletionItem, CompletionItemKind, CompletionTextEdit, Documentation, MarkupKind,
+};
+
+use super::{
+    client::LspCommandSender,
+    diagnostic::{self, range_to_span},
+};
+use crate::{Completer, Span, Suggestion, SuggestionKind};
+
+/// [`Completer`] that asks the LSP server for completions.
+///
+/// Obtained from [`LspDiagnosticsProvider::completer`](super::LspDiagnosticsProvider::completer),
+/// so completions share the provider's worker thread and server process.
+///
+/// Items the server sends without detail or documentation are resolved with
+/// `completionItem/resolve` once a menu selects them.
+#[derive(Clone)]
+pub struct LspCompleter {
+    sender: LspCommandSender,
+    timeout_ms: u64,
+    /// Items of the last completion without a description, not resolved yet
+    unresolved: Vec<CompletionItem>,
+}
+
+impl LspCompleter {
+    pub(super) fn new(sender: LspCommandSender, timeout_ms: u64) -> Self {
+        Self {
+            sender,
+            timeout_ms,
+            unresolved: Vec::new(),
+        }
+    }
+}
+
+impl Completer for LspCompleter {
+    fn complete(&mut self, line: &str, pos: usize) -> Vec<Suggestion> {
+        if line.is_empty() {
+            return Vec::new();
+        }
+
+        let pos = pos.min(line.len());
+        let items = self.sender.completions(line, pos, self.timeout_ms);
+        self.unresolved = items
+            .iter()
+            .filter(|item| item.detail.is_none() && item.documentation.is_none())
+            .cloned()
+            .collect();
+        items
+            .into_iter()
+            .map(|item| item_to_suggestion(item, line, pos))
+            .collect()
+    }
+
+    fn resolve_description(&mut self, suggestion: &Suggestion) -> Option<String> {
+        // Each item is resolved at most once, even if the server fails
+        let index = self
+            .unresolved
+            .iter()
+            .position(|item| item.label == suggestion.display_value())?;
+        let item = self.unresolved.swap_remove(index);
+        let item = self.sender.resolve_completion(item, self.timeout_ms)?;
+        item_description(item.detail, item.documentation)
+    }
+}
+
+/// Convert an LSP completion item into a [`Suggestion`].
+///
+/// The replacement span comes from the item's text edit when present,
+/// otherwise it covers the word before the cursor.
+fn item_to_suggestion(item: CompletionItem, line: &str, pos: usize) -> Suggestion {
+    let (value, span) = match item.text_edit {
+        Some(CompletionTextEdit::Edit(edit)) => (edit.new_text, range_to_span(line, &edit.range)),
+        Some(CompletionTextEdit::InsertAndReplace(edit)) => {
+            (edit.new_text, range_to_span(line, &edit.replace))
+        }
+        None => (
+            item.insert_text.unwrap_or_else(|| item.label.clone()),
+            diagnostic::Span::new(word_start(line, pos), pos),
+        ),
+    };
+
+    let display_override = (item.label != value).then_some(item.label);
+
+    Suggestion {
+        value,
+        display_override,
+        description: item_description(item.detail, item.documentation),
+        span: Span::new(span.start.min(span.end), span.end),
+        kind: item.kind.and_then(suggestion_kind),
+        ..Suggestion::default()
+    }
+}
+
+/// The description of an item: its detail, e.g. a signature, above its
+/// documentation
+fn item_description(
+    detail: Option<String>,
+    documentation: Option<Documentation>,
+) -> Option<String> {
+    let documentation = documentation.map(|documentation| match documentation {
+        Documentation::String(text) => text,
+        Documentation::MarkupContent(content) => match content.kind {
+            MarkupKind::PlainText => content.value,
+            MarkupKind::Markdown => markdown_to_plain_text(&content.value),
+        },
+    });
+    let parts = [detail, documentation]
+        .into_iter()
+        .flatten()
+        .map(|part| part.trim().to_string())
+        .filter(|part| !part.is_empty())
+        .dedup()
+        .collect::<Vec<_>>();
+    (!parts.is_empty()).then(|| parts.join("\n"))
+}
+
+/// Markdown without the markup the menus can't show: code fences, heading
+/// markers, bold markers and the backticks of inline code
+fn markdown_to_plain_text(markdown: &str) -> String {
+    markdown
+        .lines()
+        .filter(|line| !line.trim_start().starts_with("```"))
+        .map(|line| {
+            let line = match line.trim_start_matches('#') {
+                heading if heading.len() < line.len() => heading.trim_start(),
+                _ => line,
+            };
+            line.replace("**", "").replace('`', "")
+        })
+        .join("\n")
+}
+
+/// The [`SuggestionKind`] of an LSP completion item kind, if it is a known one
+fn suggestion_kind(kind: CompletionItemKind) -> Option<SuggestionKind> {
+    Some(match kind {
+        CompletionItemKind::TEXT => SuggestionKind::Text,
+        CompletionItemKind::METHOD => SuggestionKind::Method,
+        CompletionItemKind::FUNCTION => SuggestionKind::Function,
+        CompletionItemKind::CONSTRUCTOR => SuggestionKind::Constructor,
+        CompletionItemKind::FIELD => SuggestionKind::Field,
+        CompletionItemKind::VARIABLE => SuggestionKind::Variable,
+        CompletionItemKind::CLASS => SuggestionKind::Class,
+        CompletionItemKind::INTERFACE => SuggestionKind::Interface,
+        CompletionItemKind::MODULE => SuggestionKind::Module,
+        CompletionItemKind::PROPERTY => SuggestionKind::Property,
+        CompletionItemKind::UNIT => SuggestionKind::Unit,
+        CompletionItemKind::VALUE => SuggestionKind::Value,
+        CompletionItemKind::ENUM => SuggestionKind::Enum,
+        CompletionItemKind::KEYWORD => SuggestionKind::Keyword,
+        CompletionItemKind::SNIPPET => SuggestionKind::Snippet,
+        CompletionItemKind::COLOR => SuggestionKind::Color,
+        CompletionItemKind::FILE => SuggestionKind::File,
+        CompletionItemKind::REFERENCE => SuggestionKind::Reference,
+        CompletionItemKind::FOLDER => SuggestionKind::Folder,
+        CompletionItemKind::ENUM_MEMBER => SuggestionKind::EnumMember,
+        CompletionItemKind::CONSTANT => SuggestionKind::Constant,
+        CompletionItemKind::STRUCT => SuggestionKind::Struct,
+        CompletionItemKind::EVENT => SuggestionKind::Event,
+        CompletionItemKind::OPERATOR => SuggestionKind::Operator,
+        CompletionItemKind::TYPE_PARAMETER => SuggestionKind::TypeParameter,
+        _ => return None,
+    })
+}
+
+/// Find the byte offset where the word ending at `pos` starts.
+fn word_start(line: &str, pos: usize) -> usize {
+    line[..pos]
+        .char_indices()
+        .rev()
+        .take_while(|(_, c)| !c.is_whitespace() && !"()[]{}|;\"'`".contains(*c))
+        .last()
+        .map_or(pos, |(idx, _)| idx)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use lsp_types::{MarkupContent, Position, Range, TextEdit};
+    use pretty_assertions::assert_eq;
+    use rstest::rstest;
+
+    #[test]
+    fn item_without_edit_replaces_word_before_cursor() {
+        let line = "echo str";
+        let item = CompletionItem {
+            label: "str join".into(),
+            detail: Some("Concatenate strings".into()),
+            ..Default::default()
+        };
+        let suggestion = item_to_suggestion(item, line, line.len());
+        assert_eq!(suggestion.value, "str join");
+        assert_eq!(suggestion.span, Span::new(5, 8));
+        assert_eq!(suggestion.display_override, None);
+        assert_eq!(
+            suggestion.description.as_deref(),
+            Some("Concatenate strings")
+        );
+    }
+
+    #[test]
+    fn item_with_text_edit_uses_edit_range() {
+        let line = "ls | whe";
+        let item = CompletionItem {
+            label: "where".into(),
+            text_edit: Some(CompletionTextEdit::Edit(TextEdit {
+                range: Range {
+                    start: Position {
+                        line: 0,
+                        character: 5,
+                    },
+                    end: Position {
+                        line: 0,
+                        character: 8,
+                    },
+                },
+                new_text: "where ".into(),
+            })),
+            ..Default::default()
+        };
+        let suggestion = item_to_suggestion(item, line, line.len());
+        assert_eq!(suggestion.value, "where ");
+        assert_eq!(suggestion.span, Span::new(5, 8));
+        assert_eq!(suggestion.display_override.as_deref(), Some("where"));
+    }
+
+    #[test]
+    fn item_kind_becomes_the_suggestion_kind() {
+        let item = |kind| CompletionItem {
+            label: "length".into(),
+            kind,
+            ..Default::default()
+        };
+        let kind = |item| item_to_suggestion(item, "len", 3).kind;
+
+        assert_eq!(
+            kind(item(Some(CompletionItemKind::FUNCTION))),
+            Some(SuggestionKind::Function)
+        );
+        assert_eq!(
+            kind(item(Some(CompletionItemKind::ENUM_MEMBER))),
+            Some(SuggestionKind::EnumMember)
+        );
+        assert_eq!(kind(item(None)), None);
+    }
+
+    fn markup(kind: MarkupKind, value: &str) -> Option<Documentation> {
+        Some(Documentation::MarkupContent(MarkupContent {
+            kind,
+            value: value.into(),
+        }))
+    }
+
+    #[rstest]
+    #[case::nothing(None, None, None)]
+    #[case::detail_only(Some("int -> string"), None, Some("int -> string"))]
+    #[case::plain_string(
+        None,
+        Some(Documentation::String("Convert to text".into())),
+        Some("Convert to text")
+    )]
+    #[case::detail_above_documentation(
+        Some("int -> string"),
+        markup(MarkupKind::PlainText, "Convert to `text`\n"),
+        Some("int -> string\nConvert to `text`")
+    )]
+    #[case::markdown(
+        None,
+        markup(
+            MarkupKind::Markdown,
+            "# into string\n**Convert** to `text`\n```nu\n1 | into string\n```"
+        ),
+        Some("into string\nConvert to text\n1 | into string")
+    )]
+    #[case::documentation_repeating_the_detail(
+        Some("into string"),
+        Some(Documentation::String("into string".into())),
+        Some("into string")
+    )]
+    #[case::blank_documentation(None, Some(Documentation::String(" ".into())), None)]
+    fn description_combines_detail_and_documentation(
+        #[case] detail: Option<&str>,
+        #[case] documentation: Option<Documentation>,
+        #[case] expected: Option<&str>,
+    ) {
+        assert_eq!(
+            item_description(detail.map(str::to_string), documentation).as_deref(),
+            expected
+        );
+    }
+
+    #[test]
+    fn word_start_stops_at_delimiters() {
+        assert_eq!(word_start("(foo", 4), 1);
+        assert_eq!(word_start("a b", 3), 2);
+        assert_eq!(word_start("a ", 2), 2);
+    }
+}
@@ -3,10 +3,13 @@
 //! Re-exports LSP types and provides helper functions for styling and
 //! converting between LSP positions and byte offsets.
 
+use lsp_types::NumberOrString;
 use nu_ansi_term::{Color, Style};
 
 // Re-export LSP types for public use
-pub use lsp_types::{CodeAction, Diagnostic, DiagnosticSeverity, Range, TextEdit};
+pub use lsp_types::{
+    CodeAction, ColorInformation, Diagnostic, DiagnosticSeverity, Range, TextEdit,
+};
 
 /// Get a dimmed style for diagnostic messages displayed below the prompt.
 ///
@@ -62,6 +65,8 @@ fn byte_offset_to_column(s: &str, byte_offset: usize) -> usize {
 }
 
 /// Convert an LSP Range to a byte Span.
+///
+/// Characters are counted in UTF-16 code units, as the protocol does.
 pub fn range_to_span(content: &str, range: &Range) -> Span {
     Span::new(
         position_to_offset(content, &range.start),
@@ -69,7 +74,7 @@ pub fn range_to_span(content: &str, range: &Range) -> Span {
     )
 }
 
-/// Convert an LSP Position to a byte offset.
+/// Convert an LSP Position, counting UTF-16 code units, to a byte offset.
 fn position_to_offset(content: &str, pos: &lsp_types::Position) -> usize {
     let target_line = pos.line as usize;
     content
@@ -81,15 +86,32 @@ fn position_to_offset(content: &str, pos: &lsp_types::Position) -> usize {
             Some((i, line, current_offset))
         })
         .find(|(i, _, _)| *i == target_line)
-        .map(|(_, line, offset)| offset + (pos.character as usize).min(line.len()))
+        .map(|(_, line, offset)| offset + utf16_column_to_byte(line, pos.character))
         .unwrap_or(content.len())
 }
 
+/// The byte offset in `line` of the character `column` UTF-16 code units in,
+/// or of the end of the line if it is shorter
+///
+/// A column inside a character, e.g. between the halves of a surrogate
+/// pair, is rounded up to the next one.
+fn utf16_column_to_byte(line: &str, column: u32) -> usize {
+    let mut units = 0;
+    for (offset, c) in line.char_indices() {
+        if units >= column as usize {
+            return offset;
+        }
+        units += c.len_utf16();
+    }
+    line.len()
+}
+
 /// Format diagnostic messages for display below the prompt.
 ///
-/// Renders diagnostics with vertical connecting lines and handlebars spanning the diagnostic:
+/// Renders diagnostics with vertical connecting lines and handlebars spanning the diagnostic,
+/// followed by the dimmed code of the rule that reported them, if any:
 /// ```text
-/// ╎ ╰────╯ Unnecessary '^' prefix on external command 'head'
+/// ╎ ╰────╯ Unnecessary '^' prefix on external command 'head' [unnecessary_caret]
 /// ╰ Use 'first N' to get the first N items
 /// ```
 ///
@@ -98,16 +120,18 @@ fn position_to_offset(content: &str, pos: &lsp_types::Position) -> usize {
 /// * `buffer` - The text buffer content (for converting ranges to columns)
 /// * `prompt_width` - The visual width of the prompt (for alignment)
 /// * `use_ansi_coloring` - Whether to apply ANSI color codes
+/// * `sort` - Whether to show errors first, then warnings and so on, each by
+///   position, rather than in the order of `diagnostics`
 pub fn format_diagnostic_messages(
     diagnostics: &[Diagnostic],
     buffer: &str,
     prompt_width: usize,
     use_ansi_coloring: bool,
+    sort: bool,
 ) -> String {
     use itertools::Itertools;
 
-    // Convert and sort diagnostics by start column
-    let diag_infos: Vec<DiagRenderInfo> = diagnostics
+    let mut diag_infos: Vec<DiagRenderInfo> = diagnostics
         .iter()
         .map(|d| {
             let span = range_to_span(buffer, &d.range);
@@ -116,10 +140,13 @@ pub fn format_diagnostic_messages(
                 end_col: prompt_width + span.end_column(buffer),
                 severity: d.severity.unwrap_or(DiagnosticSeverity::WARNING),
                 message: d.message.clone(),
+                code: code_text(d),
             }
         })
-        .sorted_by_key(|d| d.start_col)
         .collect();
+    if sort {
+        diag_infos.sort_by_key(|d| (severity_rank(d.severity), d.start_col));
+    }
 
     diag_infos
         .iter()
@@ -130,6 +157,7 @@ pub fn format_diagnostic_messages(
                 diag.end_col,
                 diag.severity,
                 &diag.message,
+                diag.code.as_deref(),
                 &diag_infos[i + 1..],
                 use_ansi_coloring,
             )
@@ -137,12 +165,41 @@ pub fn format_diagnostic_messages(
         .join("\n")
 }
 
+/// The code of the rule that reported `diagnostic`, a number or a name
+fn code_text(diagnostic: &Diagnostic) -> Option<String> {
+    diagnostic.code.as_ref().map(|code| match code {
+        NumberOrString::Number(number) => number.to_string(),
+        NumberOrString::String(string) => string.clone(),
+    })
+}
+
+/// The message of `diagnostic` as copied to the clipboard, followed by its
+/// code if any
+pub(crate) fn clipboard_text(diagnostic: &Diagnostic) -> String {
+    match code_text(diagnostic) {
+        Some(code) => format!("{} [{code}]", diagnostic.message),
+        None => diagnostic.message.clone(),
+    }
+}
+
+/// Position of `severity` in the rendered block, errors coming first
+pub(super) fn severity_rank(severity: DiagnosticSeverity) -> u8 {
+    match severity {
+        DiagnosticSeverity::ERROR => 0,
+        DiagnosticSeverity::WARNING => 1,
+        DiagnosticSeverity::INFORMATION => 2,
+        DiagnosticSeverity::HINT => 3,
+        _ => 4,
+    }
+}
+
 /// Pre-computed diagnostic info for rendering.
 struct DiagRenderInfo {
     start_col: usize,
     end_col: usize,
     severity: DiagnosticSeverity,
     message: String,
+    code: Option<String>,
 }
 
 /// Format a single diagnostic line with vertical connectors for future diagnostics.
@@ -151,6 +208,7 @@ fn format_diagnostic_line(
     end_col: usize,
     severity: DiagnosticSeverity,
     message: &str,
+    code: Option<&str>,
     future_diags: &[DiagRenderInfo],
     use_ansi_coloring: bool,
 ) -> String {
@@ -167,7 +225,16 @@ fn format_diagnostic_line(
         severity,
         use_ansi_coloring,
     );
-    let styled_message = style_text(message, severity, use_ansi_coloring);
+    let mut styled_message = style_text(message, severity, use_ansi_coloring);
+    if let Some(code) = code {
+        let code = format!("[{code}]");
+        styled_message.push(' ');
+        if use_ansi_coloring {
+            styled_message.push_str(&Style::new().dimmed().paint(code).to_string());
+        } else {
+            styled_message.push_str(&code);
+        }
+    }
 
     // Merge vertical connectors into the line
     let prefix = merge_connectors_with_padding(&vertical_connectors, connector_width);
@@ -181,9 +248,13 @@ fn build_vertical_connectors(
     future_diags: &[DiagRenderInfo],
     use_ansi_coloring: bool,
 ) -> Vec<(usize, String)> {
+    use itertools::Itertools;
+
     future_diags
         .iter()
         .filter(|d| d.start_col < current_col)
+        .sorted_by_key(|d| d.start_col)
+        .dedup_by(|a, b| a.start_col == b.start_col)
         .map(|d| {
             let connector = style_text("╎", d.severity, use_ansi_coloring);
             (d.start_col, connector)
@@ -245,6 +316,7 @@ mod tests {
     use super::*;
     use lsp_types::Position;
     use pretty_assertions::assert_eq;
+    use rstest::rstest;
 
     // User expectation: diagnostic underline appears under the correct text
 
@@ -337,6 +409,78 @@ mod tests {
         assert!(!(span.start <= cursor_pos && cursor_pos <= span.end));
     }
 
+    fn diagnostic(start: u32, end: u32, severity: DiagnosticSeverity, message: &str) -> Diagnostic {
+        Diagnostic {
+            range: Range::new(Position::new(0, start), Position::new(0, end)),
+            severity: Some(severity),
+            message: message.to_string(),
+            ..Diagnostic::default()
+        }
+    }
+
+    // User expectation: errors are listed first, whatever order the server used
+
+    #[test]
+    fn errors_render_before_earlier_warnings() {
+        let diagnostics = [
+            diagnostic(0, 3, DiagnosticSeverity::WARNING, "unused"),
+            diagnostic(8, 9, DiagnosticSeverity::HINT, "shorter"),
+            diagnostic(4, 7, DiagnosticSeverity::ERROR, "unknown"),
+        ];
+
+        assert_eq!(
+            format_diagnostic_messages(&diagnostics, "let foo = 1", 0, false, true),
+            "╎   ╰─╯ unknown\n╰─╯ unused\n        ╰ shorter"
+        );
+    }
+
+    #[test]
+    fn unsorted_diagnostics_keep_the_server_order() {
+        let diagnostics = [
+            diagnostic(8, 9, DiagnosticSeverity::HINT, "shorter"),
+            diagnostic(0, 3, DiagnosticSeverity::WARNING, "unused"),
+            diagnostic(4, 7, DiagnosticSeverity::ERROR, "unknown"),
+        ];
+
+        assert_eq!(
+            format_diagnostic_messages(&diagnostics, "let foo = 1", 0, false, false),
+            "╎   ╎   ╰ shorter\n╰─╯ unused\n    ╰─╯ unknown"
+        );
+    }
+
+    // User expectation: the rule behind a diagnostic can be looked up
+
+    #[rstest]
+    #[case::string(NumberOrString::String("unused_variable".to_string()), "[unused_variable]")]
+    #[case::number(NumberOrString::Number(2304), "[2304]")]
+    fn code_follows_the_message(#[case] code: NumberOrString, #[case] expected: &str) {
+        let diagnostics = [Diagnostic {
+            code: Some(code),
+            ..diagnostic(4, 7, DiagnosticSeverity::WARNING, "unused")
+        }];
+
+        assert_eq!(
+            format_diagnostic_messages(&diagnostics, "let foo = 1", 0, false, true),
+            format!("    ╰─╯ unused {expected}")
+        );
+        assert!(
+            format_diagnostic_messages(&diagnostics, "let foo = 1", 0, true, true)
+                .contains(&Style::new().dimmed().paint(expected).to_string())
+        );
+    }
+
+    #[test]
+    fn copied_text_ends_in_the_code() {
+        let unused = diagnostic(4, 7, DiagnosticSeverity::WARNING, "unused");
+        assert_eq!(clipboard_text(&unused), "unused");
+
+        let with_code = Diagnostic {
+            code: Some(NumberOrString::String("unused_variable".to_string())),
+            ..unused
+        };
+        assert_eq!(clipboard_text(&with_code), "unused [unused_variable]");
+    }
+
     // User expectation: diagnostic aligns correctly after wide characters
 
     #[test]
@@ -347,4 +491,28 @@ mod tests {
         // CJK chars are 2 columns each: 日(2) + 本(2) + 語(2) + space(1) = 7 columns
         assert_eq!(span.start_column(code), 7);
     }
+
+    // User expectation: ranges counted in UTF-16 land on the same text
+
+    #[test]
+    fn diagnostic_covers_identifier_after_emoji() {
+        // 😀 is 4 bytes but 2 UTF-16 code units
+        let code = "echo 😀 | lenght";
+        let range = Range {
+            start: Position {
+                line: 0,
+                character: 10,
+            },
+            end: Position {
+                line: 0,
+                character: 16,
+            },
+        };
+        let span = range_to_span(code, &range);
+        assert_eq!(&code[span.start..span.end], "lenght");
+        assert_eq!(
+            crate::lsp::actions::offset_to_position(code, span.start),
+            range.start
+        );
+    }
 }
@@ -4,22 +4,24 @@
 //! Reedline engine, keeping the LSP-specific logic separate from the core engine.
 
 use lsp_types::Diagnostic;
+use nu_ansi_term::{Color, Style};
 use unicode_width::UnicodeWidthStr;
 
 use super::{
     diagnostic::{format_diagnostic_messages, range_to_span, Span},
+    popup::{prompt_width, PopupRenderer},
     LspDiagnosticsProvider,
 };
-use crate::{menu::DiagnosticFixMenu, Highlighter, Menu, MenuEvent, Prompt, ReedlineMenu};
+use crate::{
+    menu::{DiagnosticFixMenu, SymbolMenu},
+    Highlighter, Menu, MenuEvent, Prompt, ReedlineMenu, StyleModifier, StyledText,
+};
 
-/// Strip ANSI escape sequences from a string.
-///
-/// Prompts contain color codes like `\x1b[32m` which would incorrectly inflate
-/// width calculations. Uses the strip_ansi_escapes crate to handle all escape
-/// sequence types (SGR, OSC, etc).
-fn strip_ansi(s: &str) -> String {
-    String::from_utf8(strip_ansi_escapes::strip(s)).unwrap_or_else(|_| s.to_string())
-}
+/// Name of the menu with the fixes for the diagnostics at the cursor.
+pub(crate) const DIAGNOSTIC_FIX_MENU: &str = "diagnostic_fix_menu";
+
+/// Name of the menu with the symbols of the document and the workspace.
+pub(crate) const SYMBOL_MENU: &str = "symbol_menu";
 
 /// Format diagnostic messages for display below the prompt.
 ///
@@ -28,27 +30,79 @@ fn strip_ansi(s: &str) -> String {
 /// ╎ ╰────╯ Unnecessary '^' prefix on external command 'head'
 /// ╰ Use 'first N' to get the first N items
 /// ```
+///
+/// While a command fix runs, an `Applying <title>…` line follows them, and
+/// after a fix failed a `Could not apply <title>` line until the line changes.
+/// With [`LspConfig::current_line_only`](super::LspConfig::current_line_only),
+/// only diagnostics touching the line of `cursor` are shown.
 pub fn format_diagnostics_for_prompt(
     provider: &mut LspDiagnosticsProvider,
     buffer: &str,
+    cursor: usize,
     prompt: &dyn Prompt,
     prompt_edit_mode: crate::PromptEditMode,
+    terminal_width: usize,
     use_ansi_coloring: bool,
 ) -> String {
-    let diagnostics: Vec<Diagnostic> = provider.diagnostics().to_vec();
+    let mut diagnostics: Vec<Diagnostic> = if provider.is_enabled() {
+        provider.diagnostics().to_vec()
+    } else {
+        Vec::new()
+    };
+    if provider.shows_current_line_only() {
+        let line = buffer.get(..cursor).unwrap_or(buffer).matches('\n').count() as u32;
+        diagnostics.retain(|d| d.range.start.line <= line && line <= d.range.end.line);
+    }
+    let running_command = provider.running_command();
+    let failed_fix = provider.failed_fix();
 
-    if diagnostics.is_empty() {
+    if diagnostics.is_empty() && running_command.is_none() && failed_fix.is_none() {
         return String::new();
     }
 
-    // Calculate prompt width (last line of prompt + indicator)
-    // Strip ANSI escape sequences before measuring - they have no visual width
-    let prompt_left = prompt.render_prompt_left();
-    let prompt_indicator = prompt.render_prompt_indicator(prompt_edit_mode);
-    let last_prompt_line = prompt_left.lines().last().unwrap_or("");
-    let prompt_width = strip_ansi(last_prompt_line).width() + strip_ansi(&prompt_indicator).width();
+    let prompt_width = prompt_width(prompt, prompt_edit_mode);
+
+    let messages = format_diagnostic_messages(
+        &diagnostics,
+        buffer,
+        prompt_width,
+        use_ansi_coloring,
+        provider.sorts_diagnostics(),
+    );
+    let popup = PopupRenderer::new(prompt_width, terminal_width, use_ansi_coloring);
+    let progress = running_command.map(|title| popup.render(&[format_progress(&title)]).join("\n"));
+    let failure = failed_fix.map(|title| popup.render(&[format_failure(&title)]).join("\n"));
+    std::iter::once(messages)
+        .filter(|messages| !messages.is_empty())
+        .chain(progress)
+        .chain(failure)
+        .collect::<Vec<_>>()
+        .join("\n")
+}
 
-    format_diagnostic_messages(&diagnostics, buffer, prompt_width, use_ansi_coloring)
+/// The line shown while the command fix `title` runs.
+fn format_progress(title: &str) -> String {
+    Style::new()
+        .dimmed()
+        .paint(format!("Applying {title}…"))
+        .to_string()
+}
+
+/// The line shown after the fix `title` could not be applied.
+fn format_failure(title: &str) -> String {
+    Style::new()
+        .fg(Color::Red)
+        .paint(format!("Could not apply {title}"))
+        .to_string()
+}
+
+/// Highlight the other occurrences of the symbol under the cursor, as
+/// reported by the server, keeping the colors of the highlighter.
+pub fn highlight_occurrences(styled_text: &mut StyledText, provider: &LspDiagnosticsProvider) {
+    let modifier = StyleModifier::new().with_background(Color::DarkGray);
+    for span in provider.occurrences() {
+        styled_text.apply_style_over_range(span.start..span.end, modifier);
+    }
 }
 
 /// Create a diagnostic fix menu for code actions at the cursor position.
@@ -58,20 +112,19 @@ pub fn format_diagnostics_for_prompt(
 ///
 /// When a highlighter is provided, the fix menu pre-highlights replacement text
 /// at setup time, avoiding repeated highlighting work on each render pass.
+///
+/// At most `max_height` fixes are shown at once, fewer if the terminal is
+/// shorter.
 pub fn create_diagnostic_fix_menu(
     provider: &mut LspDiagnosticsProvider,
     cursor_pos: usize,
     content: &str,
     highlighter: Option<&dyn Highlighter>,
+    max_height: u16,
 ) -> Option<ReedlineMenu> {
-    // Find diagnostics at cursor position to determine the span for code actions
+    // The diagnostic at the cursor determines the span for code actions
     let diagnostic_span = provider
-        .diagnostics()
-        .iter()
-        .find(|d| {
-            let span = range_to_span(content, &d.range);
-            span.start <= cursor_pos && cursor_pos <= span.end
-        })
+        .diagnostic_at(content, cursor_pos)
         .map(|d| range_to_span(content, &d.range));
 
     let span = diagnostic_span.unwrap_or_else(|| {
@@ -80,7 +133,20 @@ pub fn create_diagnostic_fix_menu(
     });
 
     // Request code actions from the LSP server
-    let code_actions = provider.code_actions(content, span);
+    let mut code_actions = provider.code_actions(content, span);
+
+    // Offer conversions to other notations when the cursor is on a color literal
+    let color_at_cursor = provider
+        .colors()
+        .iter()
+        .find(|c| {
+            let span = range_to_span(content, &c.range);
+            span.start <= cursor_pos && cursor_pos <= span.end
+        })
+        .cloned();
+    if let Some(color) = color_at_cursor {
+        code_actions.extend(provider.color_presentations(content, &color));
+    }
 
     if code_actions.is_empty() {
         return None;
@@ -94,7 +160,9 @@ pub fn create_diagnostic_fix_menu(
     };
 
     // Create a new menu with fixes, positioned at the start of the diagnostic span
-    let mut fix_menu = DiagnosticFixMenu::default();
+    let mut fix_menu = DiagnosticFixMenu::default()
+        .with_max_height(max_height)
+        .with_document_uri(provider.document_uri());
     fix_menu.set_fixes(code_actions, content, anchor_col, highlighter);
     fix_menu.set_command_sender(provider.command_sender());
 
@@ -103,3 +171,24 @@ pub fn create_diagnostic_fix_menu(
 
     Some(menu)
 }
+
+/// Create a symbol menu listing the symbols of the document and the workspace.
+///
+/// Returns `None` if the server reported no symbols.
+pub fn create_symbol_menu(
+    provider: &mut LspDiagnosticsProvider,
+    content: &str,
+) -> Option<ReedlineMenu> {
+    let symbols = provider.symbols(content);
+    if symbols.is_empty() {
+        return None;
+    }
+
+    let mut symbol_menu = SymbolMenu::default();
+    symbol_menu.set_symbols(symbols);
+
+    let mut menu = ReedlineMenu::EngineCompleter(Box::new(symbol_menu));
+    menu.menu_event(MenuEvent::Activate(false));
+
+    Some(menu)
+}
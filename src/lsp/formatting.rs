@@ -0,0 +1,213 @@
+//! On-type formatting support for LSP integration.
+//!
+//! When a server-declared trigger character (e.g. `}`) is typed, requests
+//! `textDocument/onTypeFormatting` for that position and applies the returned
+//! edits to the buffer.
+
+use lsp_types::{
+    DocumentOnTypeFormattingOptions, DocumentOnTypeFormattingParams, FormattingOptions,
+    TextDocumentIdentifier, TextDocumentPositionParams, TextEdit,
+};
+use serde_json::Value;
+
+use super::{actions::offset_to_position, diagnostic::range_to_span};
+
+/// Indentation settings sent with every on-type formatting request.
+const TAB_SIZE: u32 = 4;
+
+/// Collect the trigger characters a server declared in its capabilities.
+pub(super) fn trigger_characters(options: Option<DocumentOnTypeFormattingOptions>) -> Vec<String> {
+    options
+        .map(|options| {
+            std::iter::once(options.first_trigger_character)
+                .chain(options.more_trigger_character.unwrap_or_default())
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+/// Request formatting edits after `ch` was typed, with the cursor at `offset`.
+pub(super) fn request_on_type_formatting<F>(
+    uri: &str,
+    content: &str,
+    offset: usize,
+    ch: char,
+    timeout_ms: u64,
+    request_fn: F,
+) -> Vec<TextEdit>
+where
+    F: FnOnce(&str, &DocumentOnTypeFormattingParams, u64) -> Option<Value>,
+{
+    let Some(uri) = uri.parse().ok() else {
+        return Vec::new();
+    };
+
+    let params = DocumentOnTypeFormattingParams {
+        text_document_position: TextDocumentPositionParams {
+            text_document: TextDocumentIdentifier { uri },
+            position: offset_to_position(content, offset),
+        },
+        ch: ch.to_string(),
+        options: FormattingOptions {
+            tab_size: TAB_SIZE,
+            insert_spaces: true,
+            ..Default::default()
+        },
+    };
+
+    request_fn("textDocument/onTypeFormatting", &params, timeout_ms)
+        .and_then(|v| serde_json::from_value::<Option<Vec<TextEdit>>>(v).ok())
+        .flatten()
+        .unwrap_or_default()
+}
+
+/// Apply formatting edits to `content`, keeping the cursor on the same text.
+///
+/// Returns the new content and the new cursor offset. A cursor inside a
+/// replaced range stays after as many non-whitespace characters of the
+/// replacement as there were before it, and after whitespace also in front
+/// of the next one. A server reflowing the whole buffer in one edit so keeps
+/// the cursor on the same text.
+pub(crate) fn apply_text_edits(
+    content: &str,
+    cursor: usize,
+    edits: &[TextEdit],
+) -> (String, usize) {
+    let mut spans: Vec<_> = edits
+        .iter()
+        .map(|edit| (range_to_span(content, &edit.range), edit.new_text.as_str()))
+        .collect();
+    // Apply from the end so earlier offsets stay valid
+    spans.sort_by_key(|(span, _)| std::cmp::Reverse(span.start));
+
+    let mut buffer = content.to_string();
+    let mut cursor = cursor.min(content.len());
+    for (span, new_text) in spans {
+        let start = span.start.min(span.end);
+        let end = span.end;
+        if end <= cursor {
+            cursor = cursor - (end - start) + new_text.len();
+        } else if start < cursor {
+            let before = &buffer[start..cursor];
+            let kept = before.chars().filter(|c| !c.is_whitespace()).count();
+            let after_whitespace = before
+                .chars()
+                .next_back()
+                .map_or(false, char::is_whitespace);
+            cursor = start + offset_after_non_whitespace(new_text, kept, after_whitespace);
+        }
+        buffer.replace_range(start..end, new_text);
+    }
+
+    (buffer, cursor)
+}
+
+/// The offset right after the first `count` non-whitespace characters of
+/// `text`, or in front of the next one with `skip_whitespace`
+///
+/// Is the end of `text` if it has fewer.
+fn offset_after_non_whitespace(text: &str, count: usize, skip_whitespace: bool) -> usize {
+    let mut non_whitespace = text.char_indices().filter(|(_, c)| !c.is_whitespace());
+    let after = match count.checked_sub(1) {
+        Some(last) => non_whitespace
+            .nth(last)
+            .map_or(text.len(), |(i, c)| i + c.len_utf8()),
+        None => 0,
+    };
+    if skip_whitespace {
+        non_whitespace.next().map_or(text.len(), |(i, _)| i)
+    } else {
+        after
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use lsp_types::{Position, Range};
+    use pretty_assertions::assert_eq;
+
+    fn edit(line: u32, start: u32, end: u32, new_text: &str) -> TextEdit {
+        TextEdit {
+            range: Range {
+                start: Position {
+                    line,
+                    character: start,
+                },
+                end: Position {
+                    line,
+                    character: end,
+                },
+            },
+            new_text: new_text.into(),
+        }
+    }
+
+    #[test]
+    fn edits_before_cursor_shift_it() {
+        let content = "if true {\n        }";
+
+        let (buffer, cursor) = apply_text_edits(content, content.len(), &[edit(1, 0, 8, "")]);
+
+        assert_eq!(buffer, "if true {\n}");
+        assert_eq!(cursor, buffer.len());
+    }
+
+    #[test]
+    fn edits_after_cursor_leave_it_in_place() {
+        let content = "a  =  1";
+
+        let (buffer, cursor) =
+            apply_text_edits(content, 1, &[edit(0, 1, 3, " "), edit(0, 4, 6, " ")]);
+
+        assert_eq!(buffer, "a = 1");
+        assert_eq!(cursor, 1);
+    }
+
+    #[test]
+    fn reflowing_the_whole_buffer_keeps_the_cursor_on_its_text() {
+        let content = "let x=[1,2,\n3]\nlet y =  x";
+        let formatted = "let x = [1, 2, 3]\nlet y = x";
+        let reflow = [TextEdit {
+            range: Range {
+                start: Position::new(0, 0),
+                end: Position::new(2, 10),
+            },
+            new_text: formatted.into(),
+        }];
+
+        // Before the 3, after the 2 and in whitespace that is collapsed
+        for (cursor, expected) in [(12, 15), (10, 13), (23, 26)] {
+            let (buffer, new_cursor) = apply_text_edits(content, cursor, &reflow);
+
+            assert_eq!(buffer, formatted);
+            assert_eq!(new_cursor, expected, "cursor at {cursor}");
+        }
+    }
+
+    #[test]
+    fn trigger_characters_include_first_and_more() {
+        let options = DocumentOnTypeFormattingOptions {
+            first_trigger_character: "}".into(),
+            more_trigger_character: Some(vec!["\n".into()]),
+        };
+
+        assert_eq!(trigger_characters(Some(options)), vec!["}", "\n"]);
+        assert!(trigger_characters(None).is_empty());
+    }
+
+    #[test]
+    fn edits_after_emoji_replace_the_right_text() {
+        // Columns count UTF-16 code units, 😀 takes two of them
+        let content = "echo 😀  |  str trim";
+
+        let (buffer, cursor) = apply_text_edits(
+            content,
+            content.len(),
+            &[edit(0, 7, 9, " "), edit(0, 10, 12, " ")],
+        );
+
+        assert_eq!(buffer, "echo 😀 | str trim");
+        assert_eq!(cursor, buffer.len());
+    }
+}
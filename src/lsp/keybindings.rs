@@ -0,0 +1,47 @@
+//! Default keybindings for LSP features.
+
+use crossterm::event::{KeyCode, KeyModifiers};
+
+use crate::{add_menu_keybindings, EditMode, Keybindings, ReedlineEvent, COMPLETION_MENU};
+
+/// Name of the completion menu installed by [`Reedline::with_lsp`](crate::Reedline::with_lsp).
+pub const LSP_COMPLETION_MENU: &str = COMPLETION_MENU;
+
+/// Add the default keybindings for LSP completion and diagnostic fixes.
+///
+/// - `Tab` opens the completion menu or moves to the next entry
+/// - `Shift+Tab` moves to the previous menu entry
+/// - `Esc` closes the active menu
+/// - `PageUp` and `PageDown` move a page through the active menu
+/// - `Ctrl+.` opens the diagnostic fix menu at the cursor
+/// - `Alt+s` opens the symbol menu
+pub fn add_lsp_keybindings(keybindings: &mut Keybindings) {
+    use KeyCode as KC;
+    use KeyModifiers as KM;
+
+    add_menu_keybindings(keybindings);
+    keybindings.add_binding(
+        KM::CONTROL,
+        KC::Char('.'),
+        ReedlineEvent::OpenDiagnosticFixMenu,
+    );
+    keybindings.add_binding(KM::ALT, KC::Char('s'), ReedlineEvent::OpenSymbolMenu);
+    keybindings.add_binding(KM::NONE, KC::PageUp, ReedlineEvent::MenuPagePrevious);
+    keybindings.add_binding(KM::NONE, KC::PageDown, ReedlineEvent::MenuPageNext);
+}
+
+/// Add the bindings of [`add_lsp_keybindings`] to every keybinding table of
+/// `edit_mode`, keeping the keys it binds already
+pub(crate) fn merge_lsp_keybindings(edit_mode: &mut dyn EditMode) {
+    let mut lsp = Keybindings::empty();
+    add_lsp_keybindings(&mut lsp);
+
+    for keybindings in edit_mode.keybindings_mut() {
+        for (combination, event) in lsp.get_keybindings() {
+            let (modifier, key_code) = (combination.modifier, combination.key_code);
+            if keybindings.find_binding(modifier, key_code).is_none() {
+                keybindings.add_binding(modifier, key_code, event.clone());
+            }
+        }
+    }
+}
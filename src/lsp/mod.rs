@@ -18,12 +18,33 @@
 
 mod actions;
 mod client;
+mod color;
+mod completion;
 mod diagnostic;
 mod engine_integration;
+mod formatting;
+mod keybindings;
+mod occurrences;
+mod popup;
+mod symbols;
 mod worker;
+mod workspace_edit;
 
 pub use client::{LspCommandSender, LspConfig, LspDiagnosticsProvider};
-pub use diagnostic::{CodeAction, Diagnostic, DiagnosticSeverity, Span, TextEdit};
+pub use completion::LspCompleter;
+pub use diagnostic::{
+    CodeAction, ColorInformation, Diagnostic, DiagnosticSeverity, Span, TextEdit,
+};
 // Internal utilities used by engine and menu modules
-pub(crate) use diagnostic::range_to_span;
-pub(crate) use engine_integration::{create_diagnostic_fix_menu, format_diagnostics_for_prompt};
+pub(crate) use color::{insert_color_swatches, offset_with_swatches, offset_without_swatches};
+pub(crate) use diagnostic::{clipboard_text, range_to_span};
+pub(crate) use engine_integration::{
+    create_diagnostic_fix_menu, create_symbol_menu, format_diagnostics_for_prompt,
+    highlight_occurrences, DIAGNOSTIC_FIX_MENU, SYMBOL_MENU,
+};
+pub(crate) use formatting::apply_text_edits;
+pub use keybindings::add_lsp_keybindings;
+pub(crate) use keybindings::merge_lsp_keybindings;
+pub(crate) use keybindings::LSP_COMPLETION_MENU;
+pub(crate) use popup::PopupRenderer;
+pub use symbols::{LspSymbol, SymbolKind, SymbolLocation};
@@ -0,0 +1,197 @@
+//! Occurrence highlighting for LSP integration.
+//!
+//! Asks the server for the other occurrences of the symbol under the cursor
+//! (`textDocument/documentHighlight`) once the cursor rested for a while, so
+//! moving it quickly doesn't flood the server with requests.
+
+use std::{
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use lsp_types::{
+    DocumentHighlight, DocumentHighlightParams, TextDocumentIdentifier, TextDocumentPositionParams,
+};
+use serde_json::Value;
+
+use super::{
+    actions::offset_to_position,
+    diagnostic::{range_to_span, Span},
+};
+
+/// Request the occurrences of the symbol at `offset` in the document at
+/// `uri`, whose text is `content`, as byte spans.
+pub(super) fn request_occurrences<F>(
+    uri: &str,
+    content: &str,
+    offset: usize,
+    timeout_ms: u64,
+    request_fn: F,
+) -> Vec<Span>
+where
+    F: FnOnce(&str, &DocumentHighlightParams, u64) -> Option<Value>,
+{
+    let Ok(uri) = uri.parse() else {
+        return Vec::new();
+    };
+    let params = DocumentHighlightParams {
+        text_document_position_params: TextDocumentPositionParams {
+            text_document: TextDocumentIdentifier { uri },
+            position: offset_to_position(content, offset),
+        },
+        work_done_progress_params: Default::default(),
+        partial_result_params: Default::default(),
+    };
+
+    request_fn("textDocument/documentHighlight", &params, timeout_ms)
+        .and_then(|response| serde_json::from_value::<Vec<DocumentHighlight>>(response).ok())
+        .unwrap_or_default()
+        .iter()
+        .map(|highlight| range_to_span(content, &highlight.range))
+        .collect()
+}
+
+/// Holds back a request keyed on the cursor position until the cursor
+/// stopped moving for `delay`.
+///
+/// Each position is requested at most once; moving the cursor or changing
+/// the content starts the wait over.
+#[derive(Debug)]
+pub(super) struct CursorDebounce {
+    delay: Duration,
+    /// The content and cursor position last seen, and since when
+    position: Option<(Arc<str>, usize, Instant)>,
+    /// Whether the request for `position` is due or was sent already
+    sent: bool,
+}
+
+impl CursorDebounce {
+    pub fn new(delay: Duration) -> Self {
+        Self {
+            delay,
+            position: None,
+            sent: false,
+        }
+    }
+
+    /// Note the cursor at `cursor` in `content` at time `now`.
+    ///
+    /// Returns `true` if the cursor moved or the content changed, which
+    /// starts the wait over.
+    pub fn move_to(&mut self, content: &Arc<str>, cursor: usize, now: Instant) -> bool {
+        let unchanged = self
+            .position
+            .as_ref()
+            .map_or(false, |(seen, seen_cursor, _)| {
+                *seen_cursor == cursor && **seen == **content
+            });
+        if !unchanged {
+            self.position = Some((Arc::clone(content), cursor, now));
+            self.sent = false;
+        }
+        !unchanged
+    }
+
+    /// Forget the position, e.g. when the buffer was cleared.
+    pub fn clear(&mut self) {
+        self.position = None;
+    }
+
+    /// How long until the request for the current position is due, `None`
+    /// if there is none waiting.
+    pub fn due_in(&self, now: Instant) -> Option<Duration> {
+        match &self.position {
+            Some((_, _, since)) if !self.sent => {
+                Some((*since + self.delay).saturating_duration_since(now))
+            }
+            _ => None,
+        }
+    }
+
+    /// The content and cursor position to send the request for, once the
+    /// cursor rested there for the delay.
+    pub fn take_due(&mut self, now: Instant) -> Option<(Arc<str>, usize)> {
+        if self.due_in(now)? > Duration::ZERO {
+            return None;
+        }
+        self.sent = true;
+        self.position
+            .as_ref()
+            .map(|(content, cursor, _)| (Arc::clone(content), *cursor))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+    use serde_json::json;
+
+    const URI: &str = "repl:/session/repl";
+    const DELAY: Duration = Duration::from_millis(150);
+
+    #[test]
+    fn occurrences_are_byte_spans() {
+        let content = "let x = 1; $x + $x";
+        let response = json!([
+            { "range": { "start": { "line": 0, "character": 4 }, "end": { "line": 0, "character": 5 } }, "kind": 3 },
+            { "range": { "start": { "line": 0, "character": 12 }, "end": { "line": 0, "character": 13 } } },
+        ]);
+
+        let occurrences = request_occurrences(URI, content, 12, 100, |method, params, _| {
+            assert_eq!(method, "textDocument/documentHighlight");
+            assert_eq!(
+                params.text_document_position_params.position,
+                lsp_types::Position::new(0, 12)
+            );
+            Some(response)
+        });
+
+        assert_eq!(occurrences, vec![Span::new(4, 5), Span::new(12, 13)]);
+    }
+
+    #[test]
+    fn unanswered_occurrences_are_empty() {
+        assert!(request_occurrences(URI, "ls", 1, 100, |_, _, _| None).is_empty());
+        assert!(request_occurrences(URI, "ls", 1, 100, |_, _, _| Some(Value::Null)).is_empty());
+    }
+
+    #[test]
+    fn requests_wait_until_the_cursor_rests() {
+        let content: Arc<str> = Arc::from("echo hello");
+        let start = Instant::now();
+        let mut debounce = CursorDebounce::new(DELAY);
+        assert_eq!(debounce.due_in(start), None);
+
+        // Arrow keys held down: every move starts the wait over
+        for (step, cursor) in (0..5).enumerate() {
+            let now = start + DELAY / 2 * step as u32;
+            assert!(debounce.move_to(&content, cursor, now));
+            assert_eq!(debounce.take_due(now), None);
+        }
+        let rested = start + DELAY / 2 * 4;
+        assert_eq!(debounce.due_in(rested), Some(DELAY));
+        assert!(!debounce.move_to(&content, 4, rested + DELAY / 2));
+        assert_eq!(debounce.take_due(rested + DELAY / 2), None);
+
+        assert_eq!(debounce.take_due(rested + DELAY), Some((content, 4)));
+        // Sent once per position
+        assert_eq!(debounce.due_in(rested + DELAY * 2), None);
+        assert_eq!(debounce.take_due(rested + DELAY * 2), None);
+    }
+
+    #[test]
+    fn changed_content_starts_the_wait_over() {
+        let start = Instant::now();
+        let mut debounce = CursorDebounce::new(DELAY);
+        debounce.move_to(&Arc::from("echo a"), 6, start);
+        assert!(debounce.take_due(start + DELAY).is_some());
+
+        let later = start + DELAY * 2;
+        assert!(debounce.move_to(&Arc::from("echo b"), 6, later));
+        assert_eq!(debounce.due_in(later), Some(DELAY));
+
+        debounce.clear();
+        assert_eq!(debounce.due_in(later), None);
+    }
+}
@@ -0,0 +1,141 @@
+//! Small blocks of text shown below the line being edited.
+//!
+//! Popups like the progress of a running fix or the fix menu are aligned to a
+//! column of the line, so their text lines up with the code it is about. The
+//! [`PopupRenderer`] does the width math they share: it measures text without
+//! its ANSI escapes, moves popups that don't fit left and wraps or cuts lines
+//! wider than the terminal.
+
+use unicode_width::UnicodeWidthStr;
+
+use crate::{
+    menu::menu_functions::{cut_with_ansi, wrap_with_ansi},
+    painting::{line_width, strip_ansi},
+    Prompt, PromptEditMode,
+};
+
+/// Width of the last line of the prompt and its indicator, the column the
+/// line being edited starts at
+pub(crate) fn prompt_width(prompt: &dyn Prompt, edit_mode: PromptEditMode) -> usize {
+    let prompt_left = prompt.render_prompt_left();
+    let last_prompt_line = prompt_left.lines().last().unwrap_or("");
+    strip_ansi(last_prompt_line).width()
+        + strip_ansi(&prompt.render_prompt_indicator(edit_mode)).width()
+}
+
+/// Lays out the lines of a popup starting at an anchor column
+pub(crate) struct PopupRenderer {
+    anchor_col: usize,
+    terminal_width: usize,
+    use_ansi_coloring: bool,
+    wrap: bool,
+}
+
+impl PopupRenderer {
+    /// Popup starting at `anchor_col` of a terminal `terminal_width` columns
+    /// wide, 0 if unknown
+    pub(crate) fn new(anchor_col: usize, terminal_width: usize, use_ansi_coloring: bool) -> Self {
+        Self {
+            anchor_col,
+            terminal_width,
+            use_ansi_coloring,
+            wrap: true,
+        }
+    }
+
+    /// Wrap lines wider than the terminal (the default), or cut them, for
+    /// popups whose lines have to stay one row each
+    #[must_use]
+    pub(crate) fn with_wrap(mut self, wrap: bool) -> Self {
+        self.wrap = wrap;
+        self
+    }
+
+    /// `lines` indented to the anchor column, moved left as far as needed to
+    /// fit the widest of them
+    ///
+    /// Without ANSI coloring, the escapes in `lines` are left out.
+    pub(crate) fn render<S: AsRef<str>>(&self, lines: &[S]) -> Vec<String> {
+        let lines: Vec<String> = lines
+            .iter()
+            .map(|line| {
+                if self.use_ansi_coloring {
+                    line.as_ref().to_string()
+                } else {
+                    strip_ansi(line.as_ref())
+                }
+            })
+            .collect();
+
+        if self.terminal_width == 0 {
+            let indent = " ".repeat(self.anchor_col);
+            return lines.iter().map(|line| format!("{indent}{line}")).collect();
+        }
+
+        let widest = lines.iter().map(|line| line_width(line)).max().unwrap_or(0);
+        let anchor_col = self
+            .anchor_col
+            .min(self.terminal_width.saturating_sub(widest));
+        let room = self.terminal_width - anchor_col;
+        let indent = " ".repeat(anchor_col);
+
+        lines
+            .iter()
+            .flat_map(|line| {
+                if line_width(line) <= room {
+                    vec![line.clone()]
+                } else if self.wrap {
+                    wrap_with_ansi(line, room)
+                } else {
+                    vec![cut_with_ansi(line, room)]
+                }
+            })
+            .map(|line| format!("{indent}{line}"))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use nu_ansi_term::Color;
+    use pretty_assertions::assert_eq;
+    use rstest::rstest;
+
+    #[rstest]
+    #[case::fits(4, 20, &["    fix the import"])]
+    #[case::moved_left(10, 20, &["      fix the import"])]
+    #[case::wrapped(4, 10, &["fix the", "import"])]
+    #[case::unknown_width(4, 0, &["    fix the import"])]
+    fn lines_start_at_the_anchor_if_they_fit(
+        #[case] anchor_col: usize,
+        #[case] terminal_width: usize,
+        #[case] expected: &[&str],
+    ) {
+        let popup = PopupRenderer::new(anchor_col, terminal_width, false);
+        assert_eq!(popup.render(&["fix the import"]), expected);
+    }
+
+    #[test]
+    fn lines_are_cut_without_wrapping() {
+        let popup = PopupRenderer::new(4, 10, false).with_wrap(false);
+        assert_eq!(
+            popup.render(&["fix the import", "ok"]),
+            ["fix the...", "ok"]
+        );
+    }
+
+    #[test]
+    fn escapes_are_measured_without_their_width() {
+        let line = Color::Red.paint("fix").to_string();
+
+        assert_eq!(
+            PopupRenderer::new(8, 10, true).render(&[&line]),
+            [format!("       {line}")]
+        );
+        assert_eq!(
+            PopupRenderer::new(8, 10, false).render(&[&line]),
+            ["       fix"]
+        );
+    }
+}
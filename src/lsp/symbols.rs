@@ -0,0 +1,257 @@
+//! Symbol support for LSP integration.
+//!
+//! Collects the symbols of the edited document (`textDocument/documentSymbol`)
+//! and of the whole workspace (`workspace/symbol`) for the symbol menu.
+
+use lsp_types::{
+    DocumentSymbol, DocumentSymbolParams, DocumentSymbolResponse, Location, OneOf,
+    SymbolInformation, TextDocumentIdentifier, WorkspaceSymbolParams, WorkspaceSymbolResponse,
+};
+use serde_json::Value;
+
+pub use lsp_types::SymbolKind;
+
+use super::diagnostic::{range_to_span, Span};
+
+/// A symbol reported by the LSP server.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LspSymbol {
+    /// Name of the symbol
+    pub name: String,
+    /// Kind of the symbol, e.g. function or variable
+    pub kind: SymbolKind,
+    /// Name of the symbol containing this one, if any
+    pub container: Option<String>,
+    /// Where the symbol is defined
+    pub location: SymbolLocation,
+}
+
+/// Where an [`LspSymbol`] is defined.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SymbolLocation {
+    /// The span of the symbol's name in the edited document
+    Document(Span),
+    /// Another document, or one the server gave no position in, by URI
+    External(String),
+}
+
+/// Request the symbols of the document at `uri`, whose text is `content`.
+///
+/// Returns `None` if the server didn't answer.
+pub(super) fn request_document_symbols<F>(
+    uri: &str,
+    content: &str,
+    timeout_ms: u64,
+    request_fn: F,
+) -> Option<Vec<LspSymbol>>
+where
+    F: FnOnce(&str, &DocumentSymbolParams, u64) -> Option<Value>,
+{
+    let params = DocumentSymbolParams {
+        text_document: TextDocumentIdentifier {
+            uri: uri.parse().ok()?,
+        },
+        work_done_progress_params: Default::default(),
+        partial_result_params: Default::default(),
+    };
+
+    let response = request_fn("textDocument/documentSymbol", &params, timeout_ms)?;
+    let symbols = match serde_json::from_value(response).ok()? {
+        DocumentSymbolResponse::Flat(symbols) => symbols
+            .into_iter()
+            .map(|symbol| from_information(symbol, uri, content))
+            .collect(),
+        DocumentSymbolResponse::Nested(symbols) => {
+            let mut flattened = Vec::new();
+            flatten_document_symbols(symbols, None, content, &mut flattened);
+            flattened
+        }
+    };
+    Some(symbols)
+}
+
+/// Request the symbols of the whole workspace.
+///
+/// The query is left empty, so the server reports all symbols and the menu
+/// filters them while typing. Symbols of the document at `uri` get its spans.
+pub(super) fn request_workspace_symbols<F>(
+    uri: &str,
+    content: &str,
+    timeout_ms: u64,
+    request_fn: F,
+) -> Vec<LspSymbol>
+where
+    F: FnOnce(&str, &WorkspaceSymbolParams, u64) -> Option<Value>,
+{
+    let params = WorkspaceSymbolParams {
+        query: String::new(),
+        work_done_progress_params: Default::default(),
+        partial_result_params: Default::default(),
+    };
+
+    let response = request_fn("workspace/symbol", &params, timeout_ms)
+        .and_then(|v| serde_json::from_value::<WorkspaceSymbolResponse>(v).ok());
+    match response {
+        None => Vec::new(),
+        Some(WorkspaceSymbolResponse::Flat(symbols)) => symbols
+            .into_iter()
+            .map(|symbol| from_information(symbol, uri, content))
+            .collect(),
+        Some(WorkspaceSymbolResponse::Nested(symbols)) => symbols
+            .into_iter()
+            .map(|symbol| LspSymbol {
+                name: symbol.name,
+                kind: symbol.kind,
+                container: symbol.container_name,
+                location: match symbol.location {
+                    OneOf::Left(location) => locate(&location, uri, content),
+                    OneOf::Right(location) => SymbolLocation::External(location.uri.to_string()),
+                },
+            })
+            .collect(),
+    }
+}
+
+/// Append `symbols` and their children to `flattened`, parents first.
+fn flatten_document_symbols(
+    symbols: Vec<DocumentSymbol>,
+    container: Option<&str>,
+    content: &str,
+    flattened: &mut Vec<LspSymbol>,
+) {
+    for symbol in symbols {
+        flattened.push(LspSymbol {
+            name: symbol.name.clone(),
+            kind: symbol.kind,
+            container: container.map(str::to_string),
+            location: SymbolLocation::Document(range_to_span(content, &symbol.selection_range)),
+        });
+        let children = symbol.children.unwrap_or_default();
+        flatten_document_symbols(children, Some(&symbol.name), content, flattened);
+    }
+}
+
+fn from_information(symbol: SymbolInformation, uri: &str, content: &str) -> LspSymbol {
+    LspSymbol {
+        location: locate(&symbol.location, uri, content),
+        name: symbol.name,
+        kind: symbol.kind,
+        container: symbol.container_name,
+    }
+}
+
+/// Where `location` is, relative to the document at `uri`.
+fn locate(location: &Location, uri: &str, content: &str) -> SymbolLocation {
+    if location.uri.as_str() == uri {
+        SymbolLocation::Document(range_to_span(content, &location.range))
+    } else {
+        SymbolLocation::External(location.uri.to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+    use serde_json::json;
+
+    const URI: &str = "repl:/session/repl";
+
+    fn range(line: u32, start: u32, end: u32) -> Value {
+        json!({
+            "start": { "line": line, "character": start },
+            "end": { "line": line, "character": end },
+        })
+    }
+
+    #[test]
+    fn nested_document_symbols_are_flattened_with_their_container() {
+        let content = "def greet [] {\n  let name = 1\n}";
+        let response = json!([{
+            "name": "greet",
+            "kind": 12,
+            "range": { "start": { "line": 0, "character": 0 }, "end": { "line": 2, "character": 1 } },
+            "selectionRange": range(0, 4, 9),
+            "children": [{
+                "name": "name",
+                "kind": 13,
+                "range": range(1, 2, 14),
+                "selectionRange": range(1, 6, 10),
+            }],
+        }]);
+
+        let symbols =
+            request_document_symbols(URI, content, 100, |_, _, _| Some(response)).unwrap();
+
+        assert_eq!(
+            symbols,
+            vec![
+                LspSymbol {
+                    name: "greet".into(),
+                    kind: SymbolKind::FUNCTION,
+                    container: None,
+                    location: SymbolLocation::Document(Span::new(4, 9)),
+                },
+                LspSymbol {
+                    name: "name".into(),
+                    kind: SymbolKind::VARIABLE,
+                    container: Some("greet".into()),
+                    location: SymbolLocation::Document(Span::new(21, 25)),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn workspace_symbols_of_other_documents_are_external() {
+        let content = "def greet [] {}";
+        let response = json!([
+            {
+                "name": "greet",
+                "kind": 12,
+                "location": { "uri": URI, "range": range(0, 4, 9) },
+            },
+            {
+                "name": "helper",
+                "kind": 12,
+                "containerName": "utils",
+                "location": { "uri": "file:///lib/utils.nu", "range": range(3, 4, 10) },
+            },
+        ]);
+
+        let locations: Vec<_> =
+            request_workspace_symbols(URI, content, 100, |_, _, _| Some(response))
+                .into_iter()
+                .map(|symbol| symbol.location)
+                .collect();
+
+        assert_eq!(
+            locations,
+            vec![
+                SymbolLocation::Document(Span::new(4, 9)),
+                SymbolLocation::External("file:///lib/utils.nu".into()),
+            ]
+        );
+    }
+
+    #[test]
+    fn workspace_symbols_without_range_are_external() {
+        let response = json!([{
+            "name": "greet",
+            "kind": 12,
+            "location": { "uri": URI },
+        }]);
+
+        let symbols = request_workspace_symbols(URI, "", 100, |_, _, _| Some(response));
+
+        assert_eq!(symbols[0].location, SymbolLocation::External(URI.into()));
+    }
+
+    #[test]
+    fn unanswered_document_symbols_are_none() {
+        assert_eq!(
+            request_document_symbols(URI, "ls", 100, |_, _, _| None),
+            None
+        );
+    }
+}
@@ -3,25 +3,39 @@
 //! Runs in a separate thread to avoid blocking the main editor thread.
 
 use std::{
-    io::{BufRead, BufReader, BufWriter, Write},
+    collections::HashMap,
+    io::{self, BufRead, BufReader, BufWriter, Write},
+    path::Path,
     process::{Child, ChildStdin, ChildStdout, Command, Stdio},
+    sync::Arc,
     thread,
     time::{Duration, Instant},
 };
 
 use crossbeam::channel::{Receiver, Sender};
 use lsp_types::{
-    Diagnostic, DidChangeTextDocumentParams, DidOpenTextDocumentParams, ExecuteCommandParams,
-    InitializeParams, InitializedParams, PublishDiagnosticsParams, TextDocumentContentChangeEvent,
-    TextDocumentItem, VersionedTextDocumentIdentifier,
+    ApplyWorkspaceEditParams, ClientCapabilities, CodeAction, CodeActionCapabilityResolveSupport,
+    CodeActionClientCapabilities, CodeActionKind, CodeActionProviderCapability, ColorInformation,
+    ColorProviderCapability, CompletionItem, CompletionParams, CompletionResponse, Diagnostic,
+    DidChangeTextDocumentParams, DidOpenTextDocumentParams, ExecuteCommandParams,
+    GeneralClientCapabilities, InitializeParams, InitializeResult, InitializedParams, OneOf,
+    PositionEncodingKind, PublishDiagnosticsParams, TextDocumentClientCapabilities,
+    TextDocumentContentChangeEvent, TextDocumentIdentifier, TextDocumentItem,
+    TextDocumentPositionParams, TextDocumentSyncCapability, TextDocumentSyncKind, TextEdit,
+    VersionedTextDocumentIdentifier, WorkspaceClientCapabilities, WorkspaceFolder,
 };
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
 
 use super::{
-    actions::request_code_actions,
-    client::{LspCommand, LspResponse},
+    actions::{offset_to_position, only_kinds, request_code_actions},
+    client::{LspCommand, LspResponse, PendingContent, ServerProcess},
+    color::{presentations_to_actions, request_color_presentations, request_document_colors},
     diagnostic::Span,
+    formatting::{apply_text_edits, request_on_type_formatting, trigger_characters},
+    occurrences::request_occurrences,
+    symbols::{request_document_symbols, request_workspace_symbols, LspSymbol, SymbolLocation},
+    workspace_edit::{applied, check_workspace_edit, failure, EditableDocument},
     LspConfig,
 };
 
@@ -29,152 +43,612 @@ use super::{
 pub(super) struct LspWorker {
     pub config: LspConfig,
     pub conn: Option<Connection>,
+    /// Server process spawned ahead of time, initialized on first use
+    pub spawned: Option<Child>,
+    /// Server process of the connection
+    pub process: ServerProcess,
+    /// Whether the server binary is missing or not executable, so starting it
+    /// is not tried again
+    pub unstartable: bool,
+    /// URI of the main document, which requests other than content updates refer to
     pub uri: String,
-    pub version: i32,
+    /// Documents opened on the server, by URI
+    pub documents: HashMap<String, Document>,
+    /// Newest content the provider wants synced
+    pub pending_content: PendingContent,
     pub command_rx: Receiver<LspCommand>,
     pub response_tx: Sender<LspResponse>,
     pub wake_tx: Sender<()>,
 }
 
+/// State of a document opened on the server
+#[derive(Default)]
+pub(super) struct Document {
+    pub version: i32,
+    /// Last content sent to the server via `didChange`
+    pub synced_content: Arc<str>,
+}
+
 pub(super) struct Connection {
-    #[allow(dead_code)]
-    pub child: Child,
     pub writer: BufWriter<ChildStdin>,
     pub reader: BufReader<ChildStdout>,
     pub next_id: i32,
+    /// Whether the server advertised `textDocument/documentColor` support
+    pub colors_supported: bool,
+    /// Whether the server advertised `completionItem/resolve` support
+    pub completion_resolve_supported: bool,
+    /// Whether the server advertised `codeAction/resolve` support
+    pub code_action_resolve_supported: bool,
+    /// Kinds of code actions the server declared it produces, `None` if it
+    /// didn't say
+    pub code_action_kinds: Option<Vec<CodeActionKind>>,
+    /// Whether the server advertised `textDocument/documentSymbol` support
+    pub document_symbols_supported: bool,
+    /// Whether the server advertised `workspace/symbol` support
+    pub workspace_symbols_supported: bool,
+    /// Whether the server advertised `textDocument/documentHighlight` support
+    pub occurrences_supported: bool,
+    /// How the server wants document changes sent
+    pub sync_kind: TextDocumentSyncKind,
+    /// Main document as last synced, which `workspace/applyEdit` requests may change
+    pub editable: EditableDocument,
+    /// Accepted `workspace/applyEdit` edits with the content they apply to,
+    /// not yet passed on to the main thread
+    pub applied_edits: Vec<(Arc<str>, Vec<TextEdit>)>,
+    /// Folders of the workspace, returned for `workspace/workspaceFolders`
+    pub workspace_folders: Vec<WorkspaceFolder>,
+}
+
+/// JSON-RPC error code for requests of a method the receiver doesn't implement
+const METHOD_NOT_FOUND: i64 = -32601;
+
+impl Connection {
+    /// Read the next message, answering the requests of the server on the way.
+    ///
+    /// Only responses and notifications are returned, so a server waiting for
+    /// the answer to a request never blocks.
+    fn read(&mut self, timeout: Duration) -> Option<Msg> {
+        loop {
+            let msg = read_msg(&mut self.reader, timeout)?;
+            if msg.id.is_none() || msg.method.is_none() {
+                return Some(msg);
+            }
+            if msg.method.as_deref() == Some("workspace/applyEdit") {
+                self.answer_apply_edit(msg);
+            } else {
+                self.answer_server_request(msg);
+            }
+        }
+    }
+
+    /// Answer a request other than `workspace/applyEdit` the server sent.
+    ///
+    /// Requests for settings, capability registration, progress reporting and
+    /// the like get the answer of a client without them, and all others a
+    /// "method not found" error.
+    fn answer_server_request(&mut self, msg: Msg) {
+        let (result, error) = match msg.method.as_deref().unwrap_or_default() {
+            // One setting per requested item, none of them set
+            "workspace/configuration" => {
+                let items = msg
+                    .params
+                    .as_ref()
+                    .and_then(|params| params["items"].as_array())
+                    .map_or(0, Vec::len);
+                (Some(Value::Array(vec![Value::Null; items])), None)
+            }
+            // `null` for no folders open
+            "workspace/workspaceFolders" if self.workspace_folders.is_empty() => {
+                (Some(Value::Null), None)
+            }
+            "workspace/workspaceFolders" => {
+                (serde_json::to_value(&self.workspace_folders).ok(), None)
+            }
+            "client/registerCapability"
+            | "client/unregisterCapability"
+            | "window/workDoneProgress/create"
+            | "window/showMessageRequest" => (Some(Value::Null), None),
+            "window/showDocument" => (Some(serde_json::json!({ "success": false })), None),
+            method if method.starts_with("workspace/") && method.ends_with("/refresh") => {
+                (Some(Value::Null), None)
+            }
+            method => (
+                None,
+                Some(serde_json::json!({
+                    "code": METHOD_NOT_FOUND,
+                    "message": format!("method not found: {method}"),
+                })),
+            ),
+        };
+        let reply = Msg {
+            jsonrpc: "2.0".into(),
+            id: msg.id,
+            method: None,
+            params: None,
+            result,
+            error,
+        };
+        let _ = write_msg(&mut self.writer, &reply);
+    }
+
+    /// Accept the edits of a `workspace/applyEdit` request if they apply to
+    /// the main document, and tell the server whether they did.
+    fn answer_apply_edit(&mut self, msg: Msg) {
+        let params = msg
+            .params
+            .and_then(|params| serde_json::from_value::<ApplyWorkspaceEditParams>(params).ok());
+        let response = match params {
+            None => failure("invalid workspace/applyEdit parameters".into(), None),
+            Some(params) => match check_workspace_edit(&self.editable, params.edit) {
+                Ok(edits) => {
+                    let content = Arc::clone(&self.editable.content);
+                    let (edited, _) = apply_text_edits(&content, 0, &edits);
+                    self.editable.content = edited.into();
+                    self.applied_edits.push((content, edits));
+                    applied()
+                }
+                Err(response) => response,
+            },
+        };
+
+        let reply = Msg {
+            jsonrpc: "2.0".into(),
+            id: msg.id,
+            method: None,
+            params: None,
+            result: serde_json::to_value(response).ok(),
+            error: None,
+        };
+        let _ = write_msg(&mut self.writer, &reply);
+    }
 }
 
 impl LspWorker {
     pub fn run(mut self) {
+        let idle_tick = Duration::from_millis(self.config.idle_poll_ms.max(1));
         loop {
             // Block waiting for commands (with timeout to allow graceful shutdown)
-            match self.command_rx.recv_timeout(Duration::from_millis(50)) {
+            match self.command_rx.recv_timeout(idle_tick) {
                 Ok(LspCommand::Shutdown) => {
                     self.shutdown();
                     return;
                 }
-                Ok(LspCommand::UpdateContent(content)) => {
-                    self.handle_update_content(&content);
-                }
+                Ok(LspCommand::UpdateContent) => self.handle_pending_content(),
                 Ok(LspCommand::RequestCodeActions { content, span }) => {
                     self.handle_code_actions_request(&content, span);
                 }
                 Ok(LspCommand::ExecuteCommand { command, arguments }) => {
                     self.handle_execute_command(&command, &arguments);
                 }
+                Ok(LspCommand::RequestColorPresentations { content, color }) => {
+                    self.handle_color_presentations_request(&content, &color);
+                }
+                Ok(LspCommand::RequestCompletion {
+                    content,
+                    offset,
+                    reply,
+                }) => {
+                    let _ = reply.try_send(self.handle_completion_request(&content, offset));
+                }
+                Ok(LspCommand::ResolveCompletion { item, reply }) => {
+                    if let Some(item) = self.handle_resolve_completion(*item) {
+                        let _ = reply.try_send(item);
+                    }
+                }
+                Ok(LspCommand::ResolveCodeAction { action, reply }) => {
+                    if let Some(action) = self.handle_resolve_code_action(*action) {
+                        let _ = reply.try_send(action);
+                    }
+                }
+                Ok(LspCommand::RequestOnTypeFormatting {
+                    content,
+                    offset,
+                    ch,
+                    reply,
+                }) => {
+                    let _ = reply.try_send(self.handle_on_type_formatting(&content, offset, ch));
+                }
+                Ok(LspCommand::RequestOccurrences {
+                    content,
+                    offset,
+                    reply,
+                }) => {
+                    let _ = reply.try_send(self.handle_occurrences_request(&content, offset));
+                }
+                Ok(LspCommand::RequestSymbols { content, reply }) => {
+                    let _ = reply.try_send(self.handle_symbols_request(&content));
+                }
                 Err(crossbeam::channel::RecvTimeoutError::Disconnected) => {
                     self.shutdown();
                     return;
                 }
                 Err(crossbeam::channel::RecvTimeoutError::Timeout) => {
-                    // No commands, continue loop
+                    // No commands; pick up content whose wake-up was dropped
+                    self.handle_pending_content();
                 }
             }
+            self.forward_applied_edits();
+        }
+    }
+
+    /// Pass edits accepted from `workspace/applyEdit` requests on to the main thread.
+    fn forward_applied_edits(&mut self) {
+        let Some(conn) = self
+            .conn
+            .as_mut()
+            .filter(|conn| !conn.applied_edits.is_empty())
+        else {
+            return;
+        };
+        for (content, edits) in conn.applied_edits.drain(..) {
+            let _ = self
+                .response_tx
+                .try_send(LspResponse::WorkspaceEdit { content, edits });
+        }
+        let _ = self.wake_tx.try_send(());
+    }
+
+    fn handle_pending_content(&mut self) {
+        for (uri, content) in self.pending_content.take() {
+            self.handle_update_content(&uri, &content);
         }
     }
 
-    fn handle_update_content(&mut self, content: &str) {
+    fn handle_update_content(&mut self, uri: &str, content: &Arc<str>) {
         if content.is_empty() {
-            self.send_diagnostics(Vec::new());
+            self.send_diagnostics(uri, Vec::new());
             return;
         }
 
-        if !self.ensure_init() {
+        if !self.ensure_init() || !self.sync_content(uri, content) {
             return;
         }
 
-        self.version += 1;
+        // A server not told about changes has nothing new to diagnose
+        let sync_kind = self.conn.as_ref().map(|conn| conn.sync_kind);
+        if sync_kind != Some(TextDocumentSyncKind::NONE) {
+            self.poll_for_diagnostics(uri);
+        }
+        if uri == self.uri {
+            self.refresh_colors();
+        }
+    }
+
+    /// Whether the server has seen `content` as the main document.
+    fn is_synced(&self, content: &Arc<str>) -> bool {
+        self.documents
+            .get(&self.uri)
+            .map_or(false, |document| document.synced_content == *content)
+    }
+
+    /// Send the content of the document at `uri` to the server, opening the
+    /// document first if needed.
+    ///
+    /// The change is sent the way the server asked for: the full text, only
+    /// the replaced range, or not at all for servers that don't sync.
+    ///
+    /// Returns `false` if the notification could not be sent.
+    fn sync_content(&mut self, uri: &str, content: &Arc<str>) -> bool {
         let Some(conn) = self.conn.as_mut() else {
-            return;
+            return false;
         };
-        let Some(uri) = self.uri.parse().ok() else {
-            return;
+        if !self.documents.contains_key(uri) {
+            if did_open(conn, uri).is_none() {
+                return false;
+            }
+            self.documents.insert(uri.to_string(), Document::default());
+        }
+        let (Some(document), Some(parsed_uri)) = (self.documents.get_mut(uri), uri.parse().ok())
+        else {
+            return false;
         };
 
-        let params = DidChangeTextDocumentParams {
-            text_document: VersionedTextDocumentIdentifier {
-                uri,
-                version: self.version,
-            },
-            content_changes: vec![TextDocumentContentChangeEvent {
+        let change = match conn.sync_kind {
+            TextDocumentSyncKind::NONE => None,
+            TextDocumentSyncKind::INCREMENTAL => {
+                Some(changed_range(&document.synced_content, content))
+            }
+            _ => Some(TextDocumentContentChangeEvent {
                 range: None,
                 range_length: None,
-                text: content.into(),
-            }],
+                text: content.to_string(),
+            }),
         };
-        let _ = notify(conn, "textDocument/didChange", &params);
-
-        self.poll_for_diagnostics();
+        if let Some(change) = change {
+            document.version += 1;
+            let params = DidChangeTextDocumentParams {
+                text_document: VersionedTextDocumentIdentifier {
+                    uri: parsed_uri,
+                    version: document.version,
+                },
+                content_changes: vec![change],
+            };
+            if notify(conn, "textDocument/didChange", &params).is_none() {
+                return false;
+            }
+        }
+        document.synced_content = Arc::clone(content);
+        if uri == self.uri {
+            conn.editable = EditableDocument {
+                uri: uri.to_string(),
+                version: document.version,
+                content: Arc::clone(content),
+            };
+        }
+        true
     }
 
-    fn send_diagnostics(&self, diagnostics: Vec<Diagnostic>) {
-        let _ = self
-            .response_tx
-            .try_send(LspResponse::Diagnostics(diagnostics));
+    fn send_diagnostics(&self, uri: &str, diagnostics: Vec<Diagnostic>) {
+        let _ = self.response_tx.try_send(LspResponse::Diagnostics {
+            uri: uri.to_string(),
+            diagnostics,
+        });
         let _ = self.wake_tx.try_send(());
     }
 
     fn handle_code_actions_request(&mut self, content: &str, span: Span) {
+        let requested = &self.config.code_action_kinds;
         let actions = self
             .conn
             .as_mut()
-            .map(|conn| {
-                request_code_actions(
+            .and_then(|conn| {
+                let only = only_kinds(requested, conn.code_action_kinds.as_deref())?;
+                Some(request_code_actions(
                     &self.uri,
                     content,
                     span,
+                    only,
                     self.config.timeout_ms,
                     |method, params, timeout| request(conn, method, params, timeout),
-                )
+                ))
             })
             .unwrap_or_default();
 
         let _ = self.response_tx.try_send(LspResponse::CodeActions(actions));
     }
 
+    /// Fetch color literals if the server supports them.
+    fn refresh_colors(&mut self) {
+        let Some(conn) = self.conn.as_mut().filter(|conn| conn.colors_supported) else {
+            return;
+        };
+        // A buffer can hold many color literals, so let the server stream them
+        let colors = request_document_colors(
+            &self.uri,
+            self.config.timeout_ms,
+            |method, params, timeout| request_with_partial_results(conn, method, params, timeout),
+        );
+        let _ = self.response_tx.try_send(LspResponse::Colors(colors));
+        let _ = self.wake_tx.try_send(());
+    }
+
+    fn handle_color_presentations_request(&mut self, content: &str, color: &ColorInformation) {
+        let actions = self
+            .conn
+            .as_mut()
+            .filter(|conn| conn.colors_supported)
+            .map(|conn| {
+                let presentations = request_color_presentations(
+                    &self.uri,
+                    color,
+                    self.config.timeout_ms,
+                    |method, params, timeout| request(conn, method, params, timeout),
+                );
+                presentations_to_actions(&self.uri, content, color, presentations)
+            })
+            .unwrap_or_default();
+
+        let _ = self.response_tx.try_send(LspResponse::CodeActions(actions));
+    }
+
+    fn handle_completion_request(
+        &mut self,
+        content: &Arc<str>,
+        offset: usize,
+    ) -> Vec<CompletionItem> {
+        if !self.ensure_init() {
+            return Vec::new();
+        }
+        // The server must see the same text the completion position refers to
+        let uri = self.uri.clone();
+        if !self.is_synced(content) && !self.sync_content(&uri, content) {
+            return Vec::new();
+        }
+        let (Some(conn), Some(uri)) = (self.conn.as_mut(), self.uri.parse().ok()) else {
+            return Vec::new();
+        };
+
+        let params = CompletionParams {
+            text_document_position: TextDocumentPositionParams {
+                text_document: TextDocumentIdentifier { uri },
+                position: offset_to_position(content, offset),
+            },
+            work_done_progress_params: Default::default(),
+            partial_result_params: Default::default(),
+            context: None,
+        };
+
+        request(
+            conn,
+            "textDocument/completion",
+            &params,
+            self.config.timeout_ms,
+        )
+        .and_then(|v| serde_json::from_value::<CompletionResponse>(v).ok())
+        .map(|response| match response {
+            CompletionResponse::Array(items) => items,
+            CompletionResponse::List(list) => list.items,
+        })
+        .unwrap_or_default()
+    }
+
+    fn handle_resolve_completion(&mut self, item: CompletionItem) -> Option<CompletionItem> {
+        if !self.ensure_init() {
+            return None;
+        }
+        let conn = self
+            .conn
+            .as_mut()
+            .filter(|conn| conn.completion_resolve_supported)?;
+        request(
+            conn,
+            "completionItem/resolve",
+            &item,
+            self.config.timeout_ms,
+        )
+        .and_then(|v| serde_json::from_value(v).ok())
+    }
+
+    fn handle_resolve_code_action(&mut self, action: CodeAction) -> Option<CodeAction> {
+        if !self.ensure_init() {
+            return None;
+        }
+        let conn = self
+            .conn
+            .as_mut()
+            .filter(|conn| conn.code_action_resolve_supported)?;
+        request(conn, "codeAction/resolve", &action, self.config.timeout_ms)
+            .and_then(|v| serde_json::from_value(v).ok())
+    }
+
+    fn handle_on_type_formatting(
+        &mut self,
+        content: &Arc<str>,
+        offset: usize,
+        ch: char,
+    ) -> Vec<TextEdit> {
+        if !self.ensure_init() {
+            return Vec::new();
+        }
+        // Edits are only valid for the text the server has seen
+        let uri = self.uri.clone();
+        if !self.is_synced(content) && !self.sync_content(&uri, content) {
+            return Vec::new();
+        }
+        let Some(conn) = self.conn.as_mut() else {
+            return Vec::new();
+        };
+
+        request_on_type_formatting(
+            &self.uri,
+            content,
+            offset,
+            ch,
+            self.config.timeout_ms,
+            |method, params, timeout| request(conn, method, params, timeout),
+        )
+    }
+
+    fn handle_occurrences_request(&mut self, content: &Arc<str>, offset: usize) -> Vec<Span> {
+        if !self.config.highlight_occurrences || !self.ensure_init() {
+            return Vec::new();
+        }
+        // Occurrence ranges refer to the text the server has seen
+        let uri = self.uri.clone();
+        if !self.is_synced(content) && !self.sync_content(&uri, content) {
+            return Vec::new();
+        }
+        let Some(conn) = self.conn.as_mut().filter(|conn| conn.occurrences_supported) else {
+            return Vec::new();
+        };
+
+        request_occurrences(
+            &uri,
+            content,
+            offset,
+            self.config.timeout_ms,
+            |method, params, timeout| request(conn, method, params, timeout),
+        )
+    }
+
+    fn handle_symbols_request(&mut self, content: &Arc<str>) -> Vec<LspSymbol> {
+        if !self.ensure_init() {
+            return Vec::new();
+        }
+        // Symbol ranges refer to the text the server has seen
+        let uri = self.uri.clone();
+        if !self.is_synced(content) && !self.sync_content(&uri, content) {
+            return Vec::new();
+        }
+        let Some(conn) = self.conn.as_mut() else {
+            return Vec::new();
+        };
+        let timeout_ms = self.config.timeout_ms;
+
+        // Both lists can be long, so let the server stream them
+        let document_symbols = if conn.document_symbols_supported {
+            request_document_symbols(&uri, content, timeout_ms, |method, params, timeout| {
+                request_with_partial_results(conn, method, params, timeout)
+            })
+        } else {
+            None
+        };
+        let has_document_symbols = document_symbols.is_some();
+        let mut symbols = document_symbols.unwrap_or_default();
+        if conn.workspace_symbols_supported {
+            let workspace_symbols =
+                request_workspace_symbols(&uri, content, timeout_ms, |method, params, timeout| {
+                    request_with_partial_results(conn, method, params, timeout)
+                });
+            // The document's own symbols are already there
+            symbols.extend(workspace_symbols.into_iter().filter(|symbol| {
+                !has_document_symbols || !matches!(symbol.location, SymbolLocation::Document(_))
+            }));
+        }
+        symbols
+    }
+
     fn handle_execute_command(&mut self, command: &str, arguments: &[Value]) {
-        let success = self
+        let result = self
             .conn
             .as_mut()
-            .and_then(|conn| {
+            .map_or(Err(RequestError::Failed), |conn| {
                 let params = ExecuteCommandParams {
                     command: command.to_string(),
                     arguments: arguments.to_vec(),
                     work_done_progress_params: Default::default(),
                 };
-                request(
+                answered_request(
                     conn,
                     "workspace/executeCommand",
                     &params,
-                    self.config.timeout_ms,
+                    self.config.command_timeout_ms,
                 )
-            })
-            .is_some();
+            });
 
-        let _ = self
-            .response_tx
-            .try_send(LspResponse::CommandExecuted(success));
+        // Blocking, as a dropped answer would leave the command running
+        let _ = self.response_tx.send(LspResponse::CommandExecuted(result));
+        let _ = self.wake_tx.try_send(());
     }
 
-    fn poll_for_diagnostics(&mut self) {
+    /// Wait for the diagnostics of the document at `uri`.
+    ///
+    /// Diagnostics published for other open documents in the meantime are
+    /// passed on too. Those for documents we didn't open, e.g. related files
+    /// the server analyzes, are discarded.
+    fn poll_for_diagnostics(&mut self, uri: &str) {
         let Some(conn) = &mut self.conn else { return };
+        let documents = &self.documents;
 
         let timeout = Duration::from_millis(self.config.timeout_ms);
         let start = Instant::now();
 
-        let diagnostics =
-            std::iter::from_fn(|| read_msg(&mut conn.reader, Duration::from_millis(5)))
-                .take_while(|_| start.elapsed() < timeout)
-                .filter(|msg| msg.method.as_deref() == Some("textDocument/publishDiagnostics"))
-                .filter_map(|msg| msg.params)
-                .filter_map(|params| {
-                    serde_json::from_value::<PublishDiagnosticsParams>(params).ok()
+        let published: Vec<_> = std::iter::from_fn(|| conn.read(Duration::from_millis(5)))
+            .take_while(|_| start.elapsed() < timeout)
+            .filter(|msg| msg.method.as_deref() == Some("textDocument/publishDiagnostics"))
+            .filter_map(|msg| msg.params)
+            .filter_map(|params| serde_json::from_value::<PublishDiagnosticsParams>(params).ok())
+            .filter(|params| documents.contains_key(params.uri.as_str()))
+            .scan(false, |found, params| {
+                (!*found).then(|| {
+                    *found = params.uri.as_str() == uri;
+                    params
                 })
-                .next()
-                .map(|p| p.diagnostics);
+            })
+            .collect();
 
-        if let Some(diagnostics) = diagnostics {
-            self.send_diagnostics(diagnostics);
+        for params in published {
+            self.send_diagnostics(params.uri.as_str(), params.diagnostics);
         }
     }
 
@@ -182,79 +656,264 @@ impl LspWorker {
         if self.conn.is_some() {
             return true;
         }
+        if self.unstartable {
+            return false;
+        }
         self.conn = self.try_init();
         self.conn.is_some()
     }
 
-    fn try_init(&self) -> Option<Connection> {
-        let mut parts = self.config.command.split_whitespace();
-        let bin = parts.next()?;
-        let args: Vec<&str> = parts.collect();
-
-        let mut child = Command::new(bin)
-            .args(&args)
-            .stdin(Stdio::piped())
-            .stdout(Stdio::piped())
-            .stderr(Stdio::null())
-            .spawn()
-            .ok()?;
+    fn try_init(&mut self) -> Option<Connection> {
+        let mut child = match self.spawned.take() {
+            Some(child) => child,
+            None => match spawn_server(&self.config.command, self.config.current_dir.as_deref()) {
+                Ok(child) => child,
+                Err(err) => {
+                    self.report_spawn_error(&err);
+                    return None;
+                }
+            },
+        };
 
         let mut conn = Connection {
             writer: BufWriter::new(child.stdin.take()?),
             reader: BufReader::new(child.stdout.take()?),
-            child,
             next_id: 1,
+            colors_supported: false,
+            completion_resolve_supported: false,
+            code_action_resolve_supported: false,
+            code_action_kinds: None,
+            document_symbols_supported: false,
+            workspace_symbols_supported: false,
+            occurrences_supported: false,
+            sync_kind: TextDocumentSyncKind::FULL,
+            editable: EditableDocument::default(),
+            applied_edits: Vec::new(),
+            workspace_folders: self.config.lsp_workspace_folders(),
         };
+        self.process.set(child);
 
+        let has_folders = !conn.workspace_folders.is_empty();
         let init_params = InitializeParams {
             process_id: Some(std::process::id()),
             client_info: Some(lsp_types::ClientInfo {
                 name: "reedline".into(),
                 version: Some(env!("CARGO_PKG_VERSION").into()),
             }),
+            capabilities: ClientCapabilities {
+                // Positions are converted counting UTF-16 code units
+                general: Some(GeneralClientCapabilities {
+                    position_encodings: Some(vec![PositionEncodingKind::UTF16]),
+                    ..Default::default()
+                }),
+                workspace: Some(WorkspaceClientCapabilities {
+                    workspace_folders: Some(has_folders),
+                    ..Default::default()
+                }),
+                text_document: Some(TextDocumentClientCapabilities {
+                    code_action: Some(CodeActionClientCapabilities {
+                        // Actions come back with `data` for `codeAction/resolve`
+                        data_support: Some(true),
+                        resolve_support: Some(CodeActionCapabilityResolveSupport {
+                            properties: vec!["edit".to_string()],
+                        }),
+                        ..Default::default()
+                    }),
+                    ..Default::default()
+                }),
+                ..Default::default()
+            },
+            workspace_folders: has_folders.then(|| conn.workspace_folders.clone()),
             ..Default::default()
         };
 
-        request(
+        let init_result = request(
             &mut conn,
             "initialize",
             &init_params,
             self.config.timeout_ms * 5,
         )?;
+        let capabilities = serde_json::from_value::<InitializeResult>(init_result)
+            .map(|result| result.capabilities)
+            .unwrap_or_default();
+        conn.colors_supported = colors_provided(&capabilities.color_provider);
+        conn.completion_resolve_supported = capabilities
+            .completion_provider
+            .as_ref()
+            .and_then(|provider| provider.resolve_provider)
+            .unwrap_or(false);
+        conn.code_action_resolve_supported = matches!(
+            capabilities.code_action_provider,
+            Some(CodeActionProviderCapability::Options(
+                lsp_types::CodeActionOptions {
+                    resolve_provider: Some(true),
+                    ..
+                }
+            ))
+        );
+        conn.code_action_kinds = match capabilities.code_action_provider {
+            Some(CodeActionProviderCapability::Options(options)) => options.code_action_kinds,
+            _ => None,
+        };
+        conn.document_symbols_supported = is_provided(&capabilities.document_symbol_provider);
+        conn.workspace_symbols_supported = is_provided(&capabilities.workspace_symbol_provider);
+        conn.occurrences_supported = is_provided(&capabilities.document_highlight_provider);
+        conn.sync_kind = sync_kind(capabilities.text_document_sync);
+        let triggers = trigger_characters(capabilities.document_on_type_formatting_provider);
+        if self.config.format_on_type && !triggers.is_empty() {
+            let _ = self
+                .response_tx
+                .try_send(LspResponse::OnTypeTriggers(triggers));
+        }
         notify(&mut conn, "initialized", &InitializedParams {})?;
-        notify(
-            &mut conn,
-            "textDocument/didOpen",
-            &DidOpenTextDocumentParams {
-                text_document: TextDocumentItem {
-                    uri: self.uri.parse().ok()?,
-                    language_id: "nushell".into(),
-                    version: 0,
-                    text: String::new(),
-                },
-            },
-        )?;
+        self.documents.clear();
+        let uri = self.uri.clone();
+        self.open_document(&mut conn, &uri)?;
 
         Some(conn)
     }
 
+    /// Open the (empty) document at `uri`.
+    ///
+    /// Opening resets the document version to 0, so the first `didChange`
+    /// afterwards is version 1 even if an earlier server saw higher versions.
+    fn open_document(&mut self, conn: &mut Connection, uri: &str) -> Option<()> {
+        did_open(conn, uri)?;
+        self.documents.insert(uri.to_string(), Document::default());
+        if uri == self.uri {
+            conn.editable = EditableDocument {
+                uri: uri.to_string(),
+                ..Default::default()
+            };
+        }
+        Some(())
+    }
+
+    /// Tell the provider, once, that the server binary is missing or not
+    /// executable. Other failures to start the server are retried silently.
+    fn report_spawn_error(&mut self, err: &io::Error) {
+        let reason = match err.kind() {
+            io::ErrorKind::NotFound => "No such file or directory",
+            io::ErrorKind::PermissionDenied => "Permission denied",
+            _ => return,
+        };
+        self.unstartable = true;
+        let bin = self.config.command.split_whitespace().next().unwrap_or("");
+        let _ = self
+            .response_tx
+            .try_send(LspResponse::Error(format!("{bin}: {reason}")));
+        let _ = self.wake_tx.try_send(());
+    }
+
     fn shutdown(&mut self) {
         if let Some(mut conn) = self.conn.take() {
             let _ = request(&mut conn, "shutdown", &(), 100);
             let _ = notify(&mut conn, "exit", &());
             thread::sleep(Duration::from_millis(20));
-            let _ = conn.child.kill();
+        }
+        self.process.kill();
+        if let Some(mut child) = self.spawned.take() {
+            let _ = child.kill();
+            let _ = child.wait();
         }
     }
 }
 
+/// Notify the server that the (empty) document at `uri` was opened.
+fn did_open(conn: &mut Connection, uri: &str) -> Option<()> {
+    notify(
+        conn,
+        "textDocument/didOpen",
+        &DidOpenTextDocumentParams {
+            text_document: TextDocumentItem {
+                uri: uri.parse().ok()?,
+                language_id: "nushell".into(),
+                version: 0,
+                text: String::new(),
+            },
+        },
+    )
+}
+
+/// The change turning `old` into `new`, as one replaced range of `old`.
+fn changed_range(old: &str, new: &str) -> TextDocumentContentChangeEvent {
+    let prefix = old
+        .char_indices()
+        .zip(new.chars())
+        .find(|((_, a), b)| a != b)
+        .map_or(old.len().min(new.len()), |((i, _), _)| i);
+    let suffix = old[prefix..]
+        .chars()
+        .rev()
+        .zip(new[prefix..].chars().rev())
+        .take_while(|(a, b)| a == b)
+        .map(|(c, _)| c.len_utf8())
+        .sum::<usize>();
+
+    TextDocumentContentChangeEvent {
+        range: Some(lsp_types::Range {
+            start: offset_to_position(old, prefix),
+            end: offset_to_position(old, old.len() - suffix),
+        }),
+        range_length: None,
+        text: new[prefix..new.len() - suffix].to_string(),
+    }
+}
+
+/// How a server wants document changes sent.
+///
+/// Servers that leave it out get the full text, as most expect changes even
+/// without saying so.
+fn sync_kind(capability: Option<TextDocumentSyncCapability>) -> TextDocumentSyncKind {
+    match capability {
+        Some(TextDocumentSyncCapability::Kind(kind)) => kind,
+        Some(TextDocumentSyncCapability::Options(options)) => {
+            options.change.unwrap_or(TextDocumentSyncKind::FULL)
+        }
+        None => TextDocumentSyncKind::FULL,
+    }
+}
+
+/// Whether a capability given as `true` or as options is provided.
+fn is_provided<T>(capability: &Option<OneOf<bool, T>>) -> bool {
+    !matches!(capability, None | Some(OneOf::Left(false)))
+}
+
+/// Whether `documentColor` is provided, given as `true` or as options.
+fn colors_provided(capability: &Option<ColorProviderCapability>) -> bool {
+    is_provided(&capability.as_ref().map(|capability| match capability {
+        ColorProviderCapability::Simple(provided) => OneOf::Left(*provided),
+        _ => OneOf::Right(()),
+    }))
+}
+
+/// Start the LSP server process with piped stdin/stdout, in `current_dir`
+/// if given.
+pub(super) fn spawn_server(command: &str, current_dir: Option<&Path>) -> io::Result<Child> {
+    let mut parts = command.split_whitespace();
+    let bin = parts
+        .next()
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "empty LSP server command"))?;
+
+    let mut command = Command::new(bin);
+    if let Some(dir) = current_dir {
+        command.current_dir(dir);
+    }
+    command
+        .args(parts)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .spawn()
+}
+
 // JSON-RPC helpers
 
 #[derive(Serialize, Deserialize)]
 pub(super) struct Msg {
     jsonrpc: String,
     #[serde(skip_serializing_if = "Option::is_none")]
-    pub id: Option<i32>,
+    pub id: Option<Value>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub method: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
@@ -265,35 +924,189 @@ pub(super) struct Msg {
     pub error: Option<Value>,
 }
 
+/// Why a request got no result
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(super) enum RequestError {
+    /// The server answered with an error, or the request couldn't be sent
+    Failed,
+    /// The server didn't answer in time, it may still be working on it
+    TimedOut,
+}
+
+/// Send a request and wait for its result.
+///
+/// Returns `None` if the server answered with an error or `null`, or didn't
+/// answer in time.
 pub(super) fn request<T: Serialize>(
     conn: &mut Connection,
     method: &str,
     params: &T,
     timeout_ms: u64,
 ) -> Option<Value> {
+    answered_request(conn, method, params, timeout_ms)
+        .ok()
+        .filter(|result| !result.is_null())
+}
+
+/// Send a request and wait for its result, keeping a `null` one, so
+/// requests without a result can tell success from failure.
+pub(super) fn answered_request<T: Serialize>(
+    conn: &mut Connection,
+    method: &str,
+    params: &T,
+    timeout_ms: u64,
+) -> Result<Value, RequestError> {
+    let params = serde_json::to_value(params).map_err(|_| RequestError::Failed)?;
+    send_request(conn, method, Some(params), timeout_ms, None)
+}
+
+/// Send a request that lets the server stream its result in chunks.
+///
+/// A `partialResultToken` is added to `params`, and `$/progress` notifications
+/// carrying that token are accumulated until the final response arrives.
+/// Servers that ignore the token answer normally. Use [`request`] for requests
+/// whose results are always small.
+pub(super) fn request_with_partial_results<T: Serialize>(
+    conn: &mut Connection,
+    method: &str,
+    params: &T,
+    timeout_ms: u64,
+) -> Option<Value> {
+    let token = format!("reedline-partial-{}", conn.next_id);
+    let params = with_partial_result_token(serde_json::to_value(params).ok()?, &token)?;
+    let mut partial = PartialResults::default();
+    let result = send_request(
+        conn,
+        method,
+        Some(params),
+        timeout_ms,
+        Some((&token, &mut partial)),
+    );
+    partial
+        .finish(result.ok())
+        .filter(|result| !result.is_null())
+}
+
+fn send_request(
+    conn: &mut Connection,
+    method: &str,
+    params: Option<Value>,
+    timeout_ms: u64,
+    mut partial: Option<(&str, &mut PartialResults)>,
+) -> Result<Value, RequestError> {
     let id = conn.next_id;
     conn.next_id += 1;
 
     let msg = Msg {
         jsonrpc: "2.0".into(),
-        id: Some(id),
+        id: Some(id.into()),
         method: Some(method.into()),
-        params: serde_json::to_value(params).ok(),
+        params,
         result: None,
         error: None,
     };
-    write_msg(&mut conn.writer, &msg).ok()?;
+    write_msg(&mut conn.writer, &msg).map_err(|_| RequestError::Failed)?;
 
     let timeout = Duration::from_millis(timeout_ms);
     let start = Instant::now();
     while start.elapsed() < timeout {
-        if let Some(resp) = read_msg(&mut conn.reader, Duration::from_millis(10)) {
-            if resp.id == Some(id) {
-                return resp.result;
+        if let Some(resp) = conn.read(Duration::from_millis(10)) {
+            if resp.id == Some(Value::from(id)) {
+                // A `null` result is read as a missing one
+                return match resp.error {
+                    Some(_) => Err(RequestError::Failed),
+                    None => Ok(resp.result.unwrap_or(Value::Null)),
+                };
+            }
+            if let Some((token, results)) = partial.as_mut() {
+                if let Some(chunk) = progress_value(resp, token) {
+                    results.push(chunk);
+                }
             }
         }
     }
-    None
+    Err(RequestError::TimedOut)
+}
+
+/// Add a `partialResultToken` to request params.
+///
+/// Returns `None` if the params are not a JSON object.
+fn with_partial_result_token(mut params: Value, token: &str) -> Option<Value> {
+    params
+        .as_object_mut()?
+        .insert("partialResultToken".into(), Value::from(token));
+    Some(params)
+}
+
+/// The payload of a `$/progress` notification for `token`, if `msg` is one.
+fn progress_value(msg: Msg, token: &str) -> Option<Value> {
+    if msg.id.is_some() || msg.method.as_deref() != Some("$/progress") {
+        return None;
+    }
+    let Value::Object(mut params) = msg.params? else {
+        return None;
+    };
+    if params.get("token").and_then(Value::as_str) != Some(token) {
+        return None;
+    }
+    params.remove("value")
+}
+
+/// Partial result chunks received for one request.
+#[derive(Default)]
+struct PartialResults {
+    value: Option<Value>,
+}
+
+impl PartialResults {
+    fn push(&mut self, chunk: Value) {
+        self.value = Some(match self.value.take() {
+            Some(acc) => merge_partial(acc, chunk),
+            None => chunk,
+        });
+    }
+
+    /// Assemble the final value from the chunks and the final response.
+    ///
+    /// Without chunks the final response is returned as is. After streaming,
+    /// servers send an empty final result, so it is merged into the chunks.
+    fn finish(self, result: Option<Value>) -> Option<Value> {
+        match (self.value, result) {
+            (None, result) => result,
+            (Some(acc), None | Some(Value::Null)) => Some(acc),
+            (Some(acc), Some(result)) => Some(merge_partial(acc, result)),
+        }
+    }
+}
+
+/// Merge a partial result chunk into the accumulated value.
+///
+/// Arrays are concatenated, as are array fields of objects (e.g. the `data`
+/// of semantic tokens). Any other field is replaced by the newer chunk.
+fn merge_partial(acc: Value, chunk: Value) -> Value {
+    match (acc, chunk) {
+        (Value::Array(mut acc), Value::Array(chunk)) => {
+            acc.extend(chunk);
+            Value::Array(acc)
+        }
+        (Value::Object(mut acc), Value::Object(chunk)) => {
+            for (key, value) in chunk {
+                let merged = match acc.remove(&key) {
+                    Some(Value::Array(mut existing)) => match value {
+                        Value::Array(more) => {
+                            existing.extend(more);
+                            Value::Array(existing)
+                        }
+                        other => other,
+                    },
+                    _ => value,
+                };
+                acc.insert(key, merged);
+            }
+            Value::Object(acc)
+        }
+        (_, chunk) => chunk,
+    }
 }
 
 pub(super) fn notify<T: Serialize>(conn: &mut Connection, method: &str, params: &T) -> Option<()> {
@@ -334,3 +1147,554 @@ fn read_msg<R: BufRead>(r: &mut R, timeout: Duration) -> Option<Msg> {
     }
     None
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crossbeam::channel::bounded;
+    use pretty_assertions::assert_eq;
+    use serde_json::json;
+
+    #[test]
+    fn declined_colors_are_not_requested() {
+        let colors = |capabilities: Value| {
+            let capabilities: lsp_types::ServerCapabilities =
+                serde_json::from_value(capabilities).unwrap();
+            colors_provided(&capabilities.color_provider)
+        };
+
+        assert!(!colors(json!({})));
+        assert!(!colors(json!({ "colorProvider": false })));
+        assert!(colors(json!({ "colorProvider": true })));
+        assert!(colors(json!({ "colorProvider": {} })));
+    }
+
+    fn progress(token: &str, value: Value) -> Msg {
+        Msg {
+            jsonrpc: "2.0".into(),
+            id: None,
+            method: Some("$/progress".into()),
+            params: Some(json!({ "token": token, "value": value })),
+            result: None,
+            error: None,
+        }
+    }
+
+    #[test]
+    fn partial_result_token_is_added_to_params() {
+        let params = json!({ "query": "foo" });
+
+        assert_eq!(
+            with_partial_result_token(params, "t1"),
+            Some(json!({ "query": "foo", "partialResultToken": "t1" }))
+        );
+        assert_eq!(with_partial_result_token(Value::Null, "t1"), None);
+    }
+
+    #[test]
+    fn progress_for_other_tokens_is_ignored() {
+        assert_eq!(
+            progress_value(progress("t1", json!([1])), "t1"),
+            Some(json!([1]))
+        );
+        assert_eq!(progress_value(progress("t2", json!([1])), "t1"), None);
+    }
+
+    #[test]
+    fn array_chunks_are_concatenated_with_empty_final_result() {
+        let mut partial = PartialResults::default();
+        partial.push(json!([{ "name": "a" }]));
+        partial.push(json!([{ "name": "b" }]));
+
+        assert_eq!(
+            partial.finish(Some(json!([]))),
+            Some(json!([{ "name": "a" }, { "name": "b" }]))
+        );
+    }
+
+    #[test]
+    fn object_chunks_concatenate_array_fields() {
+        let mut partial = PartialResults::default();
+        partial.push(json!({ "data": [0, 1, 3, 0, 0] }));
+        partial.push(json!({ "data": [1, 0, 2, 1, 0] }));
+
+        assert_eq!(
+            partial.finish(Some(json!({ "resultId": "7", "data": [] }))),
+            Some(json!({ "resultId": "7", "data": [0, 1, 3, 0, 0, 1, 0, 2, 1, 0] }))
+        );
+    }
+
+    #[test]
+    fn final_result_is_kept_without_chunks() {
+        let partial = PartialResults::default();
+
+        assert_eq!(partial.finish(Some(json!([1, 2]))), Some(json!([1, 2])));
+        assert_eq!(PartialResults::default().finish(None), None);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn server_starts_in_the_configured_directory() {
+        let dir = tempfile::tempdir().unwrap();
+        let output = spawn_server("pwd", Some(dir.path()))
+            .unwrap()
+            .wait_with_output()
+            .unwrap();
+
+        let started_in = String::from_utf8(output.stdout).unwrap();
+        assert_eq!(
+            Path::new(started_in.trim()).canonicalize().unwrap(),
+            dir.path().canonicalize().unwrap()
+        );
+    }
+
+    /// A connection to `cat`, which echoes every message back.
+    ///
+    /// `cat` exits once the connection is dropped and closes its input.
+    #[cfg(unix)]
+    #[allow(clippy::zombie_processes)]
+    fn echo_connection() -> Connection {
+        let mut child = spawn_server("cat", None).unwrap();
+        Connection {
+            writer: BufWriter::new(child.stdin.take().unwrap()),
+            reader: BufReader::new(child.stdout.take().unwrap()),
+            next_id: 1,
+            colors_supported: false,
+            completion_resolve_supported: false,
+            code_action_resolve_supported: false,
+            code_action_kinds: None,
+            document_symbols_supported: false,
+            workspace_symbols_supported: false,
+            occurrences_supported: false,
+            sync_kind: TextDocumentSyncKind::FULL,
+            editable: EditableDocument::default(),
+            applied_edits: Vec::new(),
+            workspace_folders: Vec::new(),
+        }
+    }
+
+    /// Queue `answer` on `conn`, for `cat` to echo back before the request
+    /// it answers.
+    #[cfg(unix)]
+    fn queue_answer(conn: &mut Connection, answer: &str) {
+        write!(
+            conn.writer,
+            "Content-Length: {}\r\n\r\n{}",
+            answer.len(),
+            answer
+        )
+        .unwrap();
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn null_results_are_told_apart_from_errors() {
+        let mut conn = echo_connection();
+
+        queue_answer(&mut conn, r#"{"jsonrpc":"2.0","id":1,"result":null}"#);
+        assert_eq!(
+            answered_request(&mut conn, "workspace/executeCommand", &json!({}), 500),
+            Ok(Value::Null)
+        );
+
+        let error = r#"{"jsonrpc":"2.0","id":2,"error":{"code":-32603,"message":"failed"}}"#;
+        queue_answer(&mut conn, error);
+        assert_eq!(
+            answered_request(&mut conn, "workspace/executeCommand", &json!({}), 500),
+            Err(RequestError::Failed)
+        );
+
+        queue_answer(&mut conn, r#"{"jsonrpc":"2.0","id":3,"result":null}"#);
+        assert_eq!(
+            request(&mut conn, "textDocument/hover", &json!({}), 500),
+            None
+        );
+
+        assert_eq!(
+            answered_request(&mut conn, "workspace/executeCommand", &json!({}), 0),
+            Err(RequestError::TimedOut)
+        );
+    }
+
+    #[cfg(unix)]
+    fn worker() -> LspWorker {
+        let (_, command_rx) = bounded(1);
+        let (response_tx, _) = bounded(1);
+        let (wake_tx, _) = bounded(1);
+        LspWorker {
+            config: LspConfig::new("cat"),
+            conn: None,
+            spawned: None,
+            process: ServerProcess::default(),
+            unstartable: false,
+            uri: "repl:/session/repl".into(),
+            documents: HashMap::new(),
+            pending_content: PendingContent::default(),
+            command_rx,
+            response_tx,
+            wake_tx,
+        }
+    }
+
+    #[cfg(unix)]
+    fn apply_edit_request(edits: Value) -> Msg {
+        Msg {
+            jsonrpc: "2.0".into(),
+            id: Some(json!("apply-1")),
+            method: Some("workspace/applyEdit".into()),
+            params: Some(json!({
+                "edit": { "changes": { "repl:/session/repl": edits } }
+            })),
+            result: None,
+            error: None,
+        }
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn overlapping_apply_edit_is_rejected() {
+        let mut conn = echo_connection();
+        conn.editable = EditableDocument {
+            uri: "repl:/session/repl".into(),
+            version: 1,
+            content: "mut x = 1".into(),
+        };
+
+        conn.answer_apply_edit(apply_edit_request(json!([
+            { "range": { "start": { "line": 0, "character": 0 }, "end": { "line": 0, "character": 5 } }, "newText": "let " },
+            { "range": { "start": { "line": 0, "character": 4 }, "end": { "line": 0, "character": 9 } }, "newText": "y" },
+        ])));
+
+        let reply = read_msg(&mut conn.reader, Duration::from_millis(500)).unwrap();
+        assert_eq!(reply.id, Some(json!("apply-1")));
+        assert_eq!(
+            reply.result,
+            Some(json!({ "applied": false, "failureReason": "edits overlap" }))
+        );
+        assert!(conn.applied_edits.is_empty());
+        assert_eq!(&*conn.editable.content, "mut x = 1");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn valid_apply_edit_is_accepted_and_queued() {
+        let mut conn = echo_connection();
+        conn.editable = EditableDocument {
+            uri: "repl:/session/repl".into(),
+            version: 1,
+            content: "mut x = 1".into(),
+        };
+
+        conn.answer_apply_edit(apply_edit_request(json!([
+            { "range": { "start": { "line": 0, "character": 0 }, "end": { "line": 0, "character": 3 } }, "newText": "let" },
+        ])));
+
+        let reply = read_msg(&mut conn.reader, Duration::from_millis(500)).unwrap();
+        assert_eq!(reply.result, Some(json!({ "applied": true })));
+        assert_eq!(conn.applied_edits.len(), 1);
+        assert_eq!(&*conn.applied_edits[0].0, "mut x = 1");
+        assert_eq!(&*conn.editable.content, "let x = 1");
+    }
+
+    #[cfg(unix)]
+    fn server_request(id: i64, method: &str, params: Value) -> Msg {
+        Msg {
+            jsonrpc: "2.0".into(),
+            id: Some(json!(id)),
+            method: Some(method.into()),
+            params: Some(params),
+            result: None,
+            error: None,
+        }
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn unknown_server_requests_are_answered() {
+        let mut conn = echo_connection();
+        let requests = [
+            server_request(1, "workspace/workspaceFolders", Value::Null),
+            server_request(2, "window/workDoneProgress/create", json!({ "token": "t" })),
+            server_request(
+                3,
+                "workspace/configuration",
+                json!({ "items": [{ "section": "a" }, { "section": "b" }] }),
+            ),
+            server_request(4, "workspace/semanticTokens/refresh", Value::Null),
+            server_request(5, "custom/probe", json!({})),
+        ];
+        for request in requests {
+            conn.answer_server_request(request);
+        }
+
+        let replies: Vec<_> =
+            std::iter::from_fn(|| read_msg(&mut conn.reader, Duration::from_millis(500)))
+                .take(5)
+                .map(|reply| {
+                    (
+                        reply.id,
+                        reply.result,
+                        reply.error.map(|e| e["code"].clone()),
+                    )
+                })
+                .collect();
+        assert_eq!(
+            replies,
+            vec![
+                (Some(json!(1)), None, None),
+                (Some(json!(2)), None, None),
+                (Some(json!(3)), Some(json!([null, null])), None),
+                (Some(json!(4)), None, None),
+                (Some(json!(5)), None, Some(json!(METHOD_NOT_FOUND))),
+            ]
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn workspace_folders_are_answered_with_the_configured_ones() {
+        let mut conn = echo_connection();
+        conn.workspace_folders = LspConfig::new("cat")
+            .with_workspace_folders(vec!["/work/app".into()])
+            .lsp_workspace_folders();
+
+        conn.answer_server_request(server_request(1, "workspace/workspaceFolders", Value::Null));
+
+        let reply = read_msg(&mut conn.reader, Duration::from_millis(500)).unwrap();
+        assert_eq!(
+            reply.result,
+            Some(json!([{ "uri": "file:///work/app/", "name": "app" }]))
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn reading_answers_server_requests_and_returns_the_rest() {
+        let mut conn = echo_connection();
+        let notification = Msg {
+            id: None,
+            ..server_request(0, "textDocument/publishDiagnostics", json!({}))
+        };
+        write_msg(
+            &mut conn.writer,
+            &server_request(7, "custom/probe", json!({})),
+        )
+        .unwrap();
+        write_msg(&mut conn.writer, &notification).unwrap();
+
+        // The request is answered, and `cat` echoes the answer back after
+        // the notification
+        let next = conn.read(Duration::from_millis(500)).unwrap();
+        assert_eq!(
+            next.method.as_deref(),
+            Some("textDocument/publishDiagnostics")
+        );
+        let reply = conn.read(Duration::from_millis(500)).unwrap();
+        assert_eq!(reply.id, Some(json!(7)));
+        assert_eq!(reply.method, None);
+        assert!(reply.error.is_some());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn commands_are_waited_for_longer_than_other_requests() {
+        let mut worker = worker();
+        let (response_tx, response_rx) = bounded(1);
+        worker.response_tx = response_tx;
+        worker.config = LspConfig::new("cat").with_command_timeout_ms(500);
+        worker.config.timeout_ms = 0;
+        let mut conn = echo_connection();
+        queue_answer(&mut conn, r#"{"jsonrpc":"2.0","id":1,"result":null}"#);
+        worker.conn = Some(conn);
+
+        worker.handle_execute_command("fix.all", &[]);
+
+        assert!(matches!(
+            response_rx.try_recv(),
+            Ok(LspResponse::CommandExecuted(Ok(Value::Null)))
+        ));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn a_missing_server_is_reported_once() {
+        let mut worker = worker();
+        let (response_tx, response_rx) = bounded(4);
+        worker.response_tx = response_tx;
+        worker.config = LspConfig::new("reedline-test-missing-server --lsp");
+
+        assert!(!worker.ensure_init());
+        assert!(!worker.ensure_init());
+
+        let errors: Vec<_> = response_rx
+            .try_iter()
+            .map(|response| match response {
+                LspResponse::Error(error) => error,
+                _ => panic!("expected an error"),
+            })
+            .collect();
+        assert_eq!(
+            errors,
+            ["reedline-test-missing-server: No such file or directory"]
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn diagnostics_for_foreign_documents_are_ignored() {
+        let mut worker = worker();
+        let (response_tx, response_rx) = bounded(4);
+        worker.response_tx = response_tx;
+        let uri = worker.uri.clone();
+        worker.documents.insert(uri.clone(), Document::default());
+        let mut conn = echo_connection();
+        // Reading stops at the message after ours, so it never blocks on `cat`
+        for published in ["file:///work/app/lib.nu", uri.as_str(), uri.as_str()] {
+            let notification = Msg {
+                id: None,
+                ..server_request(
+                    0,
+                    "textDocument/publishDiagnostics",
+                    json!({ "uri": published, "diagnostics": [{
+                        "range": { "start": { "line": 0, "character": 0 }, "end": { "line": 0, "character": 1 } },
+                        "message": published,
+                    }] }),
+                )
+            };
+            write_msg(&mut conn.writer, &notification).unwrap();
+        }
+        worker.conn = Some(conn);
+
+        worker.poll_for_diagnostics(&uri);
+
+        let received: Vec<_> = response_rx
+            .try_iter()
+            .map(|response| match response {
+                LspResponse::Diagnostics { uri, diagnostics } => {
+                    (uri, diagnostics[0].message.clone())
+                }
+                _ => panic!("expected diagnostics"),
+            })
+            .collect();
+        assert_eq!(received, [(uri.clone(), uri)]);
+    }
+
+    #[test]
+    fn changed_range_covers_only_the_edit() {
+        let change = changed_range("ls -a\necho é", "ls -al\necho è");
+        let range = change.range.unwrap();
+
+        assert_eq!(change.text, "l\necho è");
+        assert_eq!((range.start.line, range.start.character), (0, 5));
+        assert_eq!((range.end.line, range.end.character), (1, 6));
+
+        let change = changed_range("ls", "ls");
+        assert_eq!(change.text, "");
+        assert_eq!(change.range.unwrap().start, change.range.unwrap().end);
+    }
+
+    #[test]
+    fn omitted_sync_kind_sends_full_text() {
+        assert_eq!(sync_kind(None), TextDocumentSyncKind::FULL);
+        assert_eq!(
+            sync_kind(Some(TextDocumentSyncCapability::Kind(
+                TextDocumentSyncKind::NONE
+            ))),
+            TextDocumentSyncKind::NONE
+        );
+        assert_eq!(
+            sync_kind(Some(TextDocumentSyncCapability::Options(
+                lsp_types::TextDocumentSyncOptions {
+                    change: Some(TextDocumentSyncKind::INCREMENTAL),
+                    ..Default::default()
+                }
+            ))),
+            TextDocumentSyncKind::INCREMENTAL
+        );
+    }
+
+    /// The `contentChanges` of the `didChange` notifications sent when
+    /// syncing `contents` in turn to a server wanting `sync_kind`
+    #[cfg(unix)]
+    fn sent_changes(sync_kind: TextDocumentSyncKind, contents: &[&str]) -> Vec<Value> {
+        let mut worker = worker();
+        let uri = worker.uri.clone();
+        let mut conn = echo_connection();
+        conn.sync_kind = sync_kind;
+        worker.open_document(&mut conn, &uri).unwrap();
+        worker.conn = Some(conn);
+
+        for content in contents {
+            assert!(worker.sync_content(&uri, &(*content).into()));
+            assert!(worker.is_synced(&(*content).into()));
+        }
+
+        // Reading stops at the marker, so it never blocks on `cat`
+        let conn = worker.conn.as_mut().unwrap();
+        notify(conn, "$/marker", &()).unwrap();
+        std::iter::from_fn(|| read_msg(&mut conn.reader, Duration::from_millis(500)))
+            .take_while(|msg| msg.method.as_deref() != Some("$/marker"))
+            .filter(|msg| msg.method.as_deref() == Some("textDocument/didChange"))
+            .map(|msg| msg.params.unwrap()["contentChanges"].clone())
+            .collect()
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn changes_are_sent_the_way_the_server_syncs() {
+        let contents = ["ls", "ls -a"];
+
+        assert_eq!(
+            sent_changes(TextDocumentSyncKind::FULL, &contents),
+            [json!([{ "text": "ls" }]), json!([{ "text": "ls -a" }])]
+        );
+        assert_eq!(
+            sent_changes(TextDocumentSyncKind::INCREMENTAL, &contents),
+            [
+                json!([{
+                    "range": { "start": { "line": 0, "character": 0 }, "end": { "line": 0, "character": 0 } },
+                    "text": "ls",
+                }]),
+                json!([{
+                    "range": { "start": { "line": 0, "character": 2 }, "end": { "line": 0, "character": 2 } },
+                    "text": " -a",
+                }]),
+            ]
+        );
+        assert!(sent_changes(TextDocumentSyncKind::NONE, &contents).is_empty());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn reopening_restarts_versions_at_one() {
+        let mut worker = worker();
+        let uri = worker.uri.clone();
+        worker.documents.insert(
+            uri.clone(),
+            Document {
+                version: 7,
+                synced_content: "old".into(),
+            },
+        );
+        let mut conn = echo_connection();
+
+        worker.open_document(&mut conn, &uri).unwrap();
+        worker.conn = Some(conn);
+        assert_eq!(worker.documents[&uri].version, 0);
+        assert!(worker.documents[&uri].synced_content.is_empty());
+
+        assert!(worker.sync_content(&uri, &"ls".into()));
+        assert!(worker.sync_content(&uri, &"ls -a".into()));
+
+        let conn = worker.conn.as_mut().unwrap();
+        let versions: Vec<_> =
+            std::iter::from_fn(|| read_msg(&mut conn.reader, Duration::from_millis(500)))
+                .take(3)
+                .map(|msg| {
+                    let params = msg.params.unwrap();
+                    params["textDocument"]["version"].as_i64().unwrap()
+                })
+                .collect();
+        assert_eq!(versions, vec![0, 1, 2]);
+    }
+}
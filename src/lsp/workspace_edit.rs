@@ -0,0 +1,261 @@
+//! Server-initiated edits (`workspace/applyEdit`).
+//!
+//! Edits are checked against the content the server last saw before they are
+//! accepted, so the reply only claims success for edits that can be applied.
+
+use std::sync::Arc;
+
+use lsp_types::{
+    ApplyWorkspaceEditResponse, DocumentChangeOperation, DocumentChanges, OneOf, Position,
+    TextEdit, WorkspaceEdit,
+};
+
+/// The document `workspace/applyEdit` requests may change.
+#[derive(Clone, Default)]
+pub(super) struct EditableDocument {
+    pub uri: String,
+    pub version: i32,
+    pub content: Arc<str>,
+}
+
+/// Check the edits of `edit` against `document`.
+///
+/// Returns the edits to apply, or the response rejecting them with the reason.
+pub(super) fn check_workspace_edit(
+    document: &EditableDocument,
+    edit: WorkspaceEdit,
+) -> Result<Vec<TextEdit>, ApplyWorkspaceEditResponse> {
+    let mut edits = Vec::new();
+
+    for (uri, changes) in edit.changes.into_iter().flatten() {
+        check_uri(document, uri.as_str(), None)?;
+        edits.extend(changes);
+    }
+
+    let document_edits = match edit.document_changes {
+        None => Vec::new(),
+        Some(DocumentChanges::Edits(document_edits)) => document_edits,
+        Some(DocumentChanges::Operations(operations)) => operations
+            .into_iter()
+            .enumerate()
+            .map(|(index, operation)| match operation {
+                DocumentChangeOperation::Edit(document_edit) => Ok(document_edit),
+                DocumentChangeOperation::Op(_) => Err(failure(
+                    "creating, renaming or deleting files is not supported".into(),
+                    Some(index),
+                )),
+            })
+            .collect::<Result<_, _>>()?,
+    };
+    for (index, document_edit) in document_edits.into_iter().enumerate() {
+        let text_document = document_edit.text_document;
+        check_uri(document, text_document.uri.as_str(), Some(index))?;
+        if let Some(version) = text_document.version.filter(|v| *v != document.version) {
+            return Err(failure(
+                format!(
+                    "edit is for version {version} of {}, but it is at version {}",
+                    document.uri, document.version
+                ),
+                Some(index),
+            ));
+        }
+        edits.extend(document_edit.edits.into_iter().map(|edit| match edit {
+            OneOf::Left(edit) => edit,
+            OneOf::Right(annotated) => annotated.text_edit,
+        }));
+    }
+
+    check_edits(&document.content, &edits).map_err(|reason| failure(reason, None))?;
+    Ok(edits)
+}
+
+/// Response accepting the edits.
+pub(super) fn applied() -> ApplyWorkspaceEditResponse {
+    ApplyWorkspaceEditResponse {
+        applied: true,
+        failure_reason: None,
+        failed_change: None,
+    }
+}
+
+/// Response rejecting the edits because of `reason`.
+pub(super) fn failure(reason: String, failed_change: Option<usize>) -> ApplyWorkspaceEditResponse {
+    ApplyWorkspaceEditResponse {
+        applied: false,
+        failure_reason: Some(reason),
+        failed_change: failed_change.map(|index| index as u32),
+    }
+}
+
+fn check_uri(
+    document: &EditableDocument,
+    uri: &str,
+    failed_change: Option<usize>,
+) -> Result<(), ApplyWorkspaceEditResponse> {
+    if uri == document.uri {
+        Ok(())
+    } else {
+        Err(failure(
+            format!("{uri} is not open for editing, only {}", document.uri),
+            failed_change,
+        ))
+    }
+}
+
+/// Check that all edits lie within `content` and none overlap.
+fn check_edits(content: &str, edits: &[TextEdit]) -> Result<(), String> {
+    let mut spans = edits
+        .iter()
+        .map(|edit| {
+            let (start, end) = (&edit.range.start, &edit.range.end);
+            match (offset(content, start), offset(content, end)) {
+                (Some(start_offset), Some(end_offset)) if start_offset <= end_offset => {
+                    Ok((start_offset, end_offset))
+                }
+                (Some(_), Some(_)) => Err(format!(
+                    "edit range {} ends before it starts",
+                    describe(start, end)
+                )),
+                _ => Err(format!(
+                    "edit range {} is outside the document",
+                    describe(start, end)
+                )),
+            }
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+
+    spans.sort_unstable();
+    match spans.windows(2).find(|pair| pair[0].1 > pair[1].0) {
+        Some(_) => Err("edits overlap".into()),
+        None => Ok(()),
+    }
+}
+
+/// Byte offset of `position`, like the offsets edits are applied at.
+///
+/// `None` if the line doesn't exist; characters past the end of a line
+/// mean its end.
+fn offset(content: &str, position: &Position) -> Option<usize> {
+    let mut line_start = 0;
+    for (index, line) in content.split('\n').enumerate() {
+        if index == position.line as usize {
+            return Some(line_start + line.len().min(position.character as usize));
+        }
+        line_start += line.len() + 1;
+    }
+    None
+}
+
+fn describe(start: &Position, end: &Position) -> String {
+    format!(
+        "{}:{}-{}:{}",
+        start.line, start.character, end.line, end.character
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use lsp_types::{OptionalVersionedTextDocumentIdentifier, Range, TextDocumentEdit};
+    use pretty_assertions::assert_eq;
+    use std::collections::HashMap;
+
+    const URI: &str = "repl:/session/repl";
+
+    fn document(content: &str) -> EditableDocument {
+        EditableDocument {
+            uri: URI.into(),
+            version: 3,
+            content: content.into(),
+        }
+    }
+
+    fn edit(line: u32, start: u32, end: u32, new_text: &str) -> TextEdit {
+        TextEdit {
+            range: Range {
+                start: Position {
+                    line,
+                    character: start,
+                },
+                end: Position {
+                    line,
+                    character: end,
+                },
+            },
+            new_text: new_text.into(),
+        }
+    }
+
+    fn changes(uri: &str, edits: Vec<TextEdit>) -> WorkspaceEdit {
+        WorkspaceEdit {
+            changes: Some(HashMap::from([(uri.parse().unwrap(), edits)])),
+            ..Default::default()
+        }
+    }
+
+    fn failure_reason(result: Result<Vec<TextEdit>, ApplyWorkspaceEditResponse>) -> String {
+        let response = result.unwrap_err();
+        assert!(!response.applied);
+        response.failure_reason.unwrap()
+    }
+
+    #[test]
+    fn accepts_adjacent_edits() {
+        let edits = vec![edit(0, 0, 3, "let"), edit(0, 3, 3, " "), edit(0, 3, 4, "")];
+
+        let result = check_workspace_edit(&document("mut x = 1"), changes(URI, edits.clone()));
+
+        assert_eq!(result.unwrap(), edits);
+    }
+
+    #[test]
+    fn rejects_overlapping_edits() {
+        let edits = vec![edit(0, 4, 9, "y"), edit(0, 0, 5, "let ")];
+
+        let result = check_workspace_edit(&document("mut x = 1"), changes(URI, edits));
+
+        assert_eq!(failure_reason(result), "edits overlap");
+    }
+
+    #[test]
+    fn rejects_edits_outside_the_document() {
+        let result = check_workspace_edit(
+            &document("ls\nls -a"),
+            changes(URI, vec![edit(2, 0, 1, "x")]),
+        );
+
+        assert_eq!(
+            failure_reason(result),
+            "edit range 2:0-2:1 is outside the document"
+        );
+    }
+
+    #[test]
+    fn rejects_other_documents_and_outdated_versions() {
+        let result = check_workspace_edit(
+            &document("ls"),
+            changes("file:///tmp/other.nu", vec![edit(0, 0, 0, "x")]),
+        );
+        assert_eq!(
+            failure_reason(result),
+            "file:///tmp/other.nu is not open for editing, only repl:/session/repl"
+        );
+
+        let outdated = WorkspaceEdit {
+            document_changes: Some(DocumentChanges::Edits(vec![TextDocumentEdit {
+                text_document: OptionalVersionedTextDocumentIdentifier {
+                    uri: URI.parse().unwrap(),
+                    version: Some(2),
+                },
+                edits: vec![OneOf::Left(edit(0, 0, 0, "x"))],
+            }])),
+            ..Default::default()
+        };
+        let response = check_workspace_edit(&document("ls"), outdated).unwrap_err();
+        assert_eq!(response.failed_change, Some(0));
+        assert_eq!(
+            response.failure_reason.as_deref(),
+            Some("edit is for version 2 of repl:/session/repl, but it is at version 3")
+        );
+    }
+}
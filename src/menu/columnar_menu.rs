@@ -1,14 +1,18 @@
-use super::{Menu, MenuBuilder, MenuEvent, MenuSettings};
+use super::{Menu, MenuBuilder, MenuEvent, MenuSettings, ScrollIndicator};
 use crate::{
     core_editor::Editor,
     menu_functions::{
-        can_partially_complete, completer_input, floor_char_boundary, get_match_indices,
-        replace_in_buffer, style_suggestion, truncate_with_ansi,
+        can_partially_complete, completer_input, compose_styles, ellipsize_matched,
+        ellipsize_with_ansi, get_match_indices, group_header, group_values, kind_column_width,
+        kind_marker, next_index, previous_index, replace_in_buffer, resolve_description,
+        scroll_footer, scrollbar_track, shortest_base_string, style_suggestion, truncate_with_ansi,
+        with_scrollbar_glyph, ArrivingValues, ValueGroup,
     },
     painting::Painter,
-    Completer, Suggestion,
+    Completer, CompletionContext, Suggestion,
 };
 use nu_ansi_term::ansi::RESET;
+use std::ops::Range;
 use unicode_width::UnicodeWidthStr;
 
 /// The traversal direction of the menu
@@ -20,6 +24,17 @@ pub enum TraversalDirection {
     Vertical,
 }
 
+/// Where the `…` goes in a value too wide for its column
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum EllipsisPosition {
+    /// At the end, keeping the start of the value
+    #[default]
+    End,
+    /// In the middle, keeping its start and end, like the file name at the
+    /// end of a path
+    Middle,
+}
+
 /// Default values used as reference for the menu. These values are set during
 /// the initial declaration of the menu and are always kept as reference for the
 /// changeable [`ColumnDetails`]
@@ -32,6 +47,10 @@ struct DefaultColumnDetails {
     pub col_padding: usize,
     /// Traversal direction
     pub traversal_dir: TraversalDirection,
+    /// Widest a column gets, values and descriptions are shortened to fit
+    pub max_col_width: Option<usize>,
+    /// Where shortened values are cut
+    pub ellipsis: EllipsisPosition,
 }
 
 impl Default for DefaultColumnDetails {
@@ -41,6 +60,8 @@ impl Default for DefaultColumnDetails {
             col_width: None,
             col_padding: 2,
             traversal_dir: TraversalDirection::Horizontal,
+            max_col_width: None,
+            ellipsis: EllipsisPosition::default(),
         }
     }
 }
@@ -55,6 +76,10 @@ struct ColumnDetails {
     pub col_width: usize,
     /// The shortest of the strings, which the suggestions are based on
     pub shortest_base_string: String,
+    /// Screen width the columns were laid out for
+    pub screen_width: usize,
+    /// Whether the last column of the screen is kept for a scrollbar
+    pub scrollbar: bool,
 }
 
 /// Menu to present suggestions in a columnar fashion
@@ -74,8 +99,13 @@ pub struct ColumnarMenu {
     working_details: ColumnDetails,
     /// Menu cached values
     values: Vec<Suggestion>,
-    /// Cached display width of each suggestion in `values`
+    /// Groups of the values, each laid out in columns of its own below its
+    /// header
+    groups: Vec<ValueGroup>,
+    /// Cached display width of each suggestion in `values`, including its kind
     display_widths: Vec<usize>,
+    /// Width of the column showing the suggestion kinds, 0 if none has one
+    kind_width: usize,
     /// column position of the cursor. Starts from 0
     col_pos: u16,
     /// row position in the menu. Starts from 0
@@ -89,6 +119,16 @@ pub struct ColumnarMenu {
     longest_suggestion: usize,
     /// String collected after the menu is activated
     input: Option<String>,
+    /// Whether the values were completed since the menu was activated
+    refining: bool,
+    /// Values still arriving from a completer running in the background
+    arriving: Option<ArrivingValues>,
+    /// Index of the selected value when more values arrived, selected again
+    /// once they are laid out
+    arrived_selection: Option<usize>,
+    /// Value selected before the line was edited, selected again if the
+    /// updated values still list it
+    kept_selection: Option<String>,
 }
 
 impl Default for ColumnarMenu {
@@ -100,13 +140,19 @@ impl Default for ColumnarMenu {
             min_rows: 3,
             working_details: ColumnDetails::default(),
             values: Vec::new(),
+            groups: Vec::new(),
             display_widths: Vec::new(),
+            kind_width: 0,
             col_pos: 0,
             row_pos: 0,
             skip_rows: 0,
             event: None,
             longest_suggestion: 0,
             input: None,
+            refining: false,
+            arriving: None,
+            arrived_selection: None,
+            kept_selection: None,
         }
     }
 }
@@ -147,159 +193,164 @@ impl ColumnarMenu {
         self.default_details.traversal_dir = direction;
         self
     }
+
+    /// Menu builder with the widest a column gets, padding included
+    ///
+    /// Longer values and descriptions are shortened with a `…`, so that
+    /// more columns fit.
+    #[must_use]
+    pub fn with_max_column_width(mut self, max_col_width: Option<usize>) -> Self {
+        self.default_details.max_col_width = max_col_width;
+        self
+    }
+
+    /// Menu builder with where the `…` goes in values too wide for their
+    /// column
+    #[must_use]
+    pub fn with_ellipsis_position(mut self, ellipsis: EllipsisPosition) -> Self {
+        self.default_details.ellipsis = ellipsis;
+        self
+    }
 }
 
 // Menu functionality
 impl ColumnarMenu {
     /// Move menu cursor to the next element
     fn move_next(&mut self) {
-        let new_index = self.index() + 1;
-
-        let new_index = if new_index >= self.get_values().len() {
-            0
-        } else {
-            new_index
-        };
-
+        let new_index = next_index(self.index(), self.get_values().len(), self.settings.wrap);
         (self.row_pos, self.col_pos) = self.position_from_index(new_index);
     }
 
     /// Move menu cursor to the previous element
     fn move_previous(&mut self) {
-        let new_index = match self.index().checked_sub(1) {
-            Some(index) => index,
-            None => self.values.len().saturating_sub(1),
-        };
-
+        let new_index = previous_index(self.index(), self.values.len(), self.settings.wrap);
         (self.row_pos, self.col_pos) = self.position_from_index(new_index);
     }
 
     /// Move menu cursor up
     fn move_up(&mut self) {
-        self.row_pos = match self.row_pos.checked_sub(1) {
-            Some(index) => index,
-            None => self.get_last_row_at_col(self.col_pos),
+        let rows = self.rows_at_col(self.col_pos);
+        if let Some(current) = rows.iter().position(|&row| row == self.row_pos) {
+            self.row_pos = rows[previous_index(current, rows.len(), self.settings.wrap)];
         }
     }
 
     /// Move menu cursor down
     fn move_down(&mut self) {
-        let new_row = self.row_pos + 1;
-        self.row_pos = if new_row > self.get_last_row_at_col(self.col_pos) {
-            0
-        } else {
-            new_row
+        let rows = self.rows_at_col(self.col_pos);
+        if let Some(current) = rows.iter().position(|&row| row == self.row_pos) {
+            self.row_pos = rows[next_index(current, rows.len(), self.settings.wrap)];
         }
     }
 
     /// Move menu cursor left
     fn move_left(&mut self) {
-        self.col_pos = if let Some(col) = self.col_pos.checked_sub(1) {
-            col
-        } else {
-            self.get_last_col_at_row(self.row_pos)
-        }
+        let cols = self.cols_at_row(self.row_pos) as usize;
+        self.col_pos = previous_index(self.col_pos as usize, cols, self.settings.wrap) as u16;
     }
 
     /// Move menu cursor right
     fn move_right(&mut self) {
-        let new_col = self.col_pos + 1;
-        self.col_pos = if new_col > self.get_last_col_at_row(self.row_pos) {
-            0
-        } else {
-            new_col
-        }
+        let cols = self.cols_at_row(self.row_pos) as usize;
+        self.col_pos = next_index(self.col_pos as usize, cols, self.settings.wrap) as u16;
     }
 
-    /// Calculates row and column positions from an index
-    fn position_from_index(&self, index: usize) -> (u16, u16) {
+    /// Rows taken by the values of `group`, without its header
+    fn group_rows(&self, group: &ValueGroup) -> u16 {
+        let (values, cols) = (group.len as u16, self.get_cols());
+        values / cols + u16::from(values % cols != 0)
+    }
+
+    /// Columns used to display the values of `group`
+    fn group_used_cols(&self, group: &ValueGroup) -> u16 {
         match self.default_details.traversal_dir {
             TraversalDirection::Vertical => {
-                let row = index % self.get_rows() as usize;
-                let col = index / self.get_rows() as usize;
-                (row as u16, col as u16)
-            }
-            TraversalDirection::Horizontal => {
-                let row = index / self.get_used_cols() as usize;
-                let col = index % self.get_used_cols() as usize;
-                (row as u16, col as u16)
+                let (values, rows) = (group.len as u16, self.group_rows(group).max(1));
+                values / rows + u16::from(values % rows != 0)
             }
+            TraversalDirection::Horizontal => self.get_cols().min(group.len as u16),
         }
     }
 
-    /// Calculates the last row containing a value for the specified column
-    fn get_last_row_at_col(&self, col_pos: u16) -> u16 {
-        let num_values = self.get_values().len() as u16;
-        match self.default_details.traversal_dir {
-            TraversalDirection::Vertical => {
-                if col_pos >= self.get_used_cols() - 1 {
-                    // Last column, might not be full
-                    let mod_val = num_values % self.get_rows();
-                    if mod_val == 0 {
-                        // Full column
-                        self.get_rows().saturating_sub(1)
-                    } else {
-                        // Column with last row empty
-                        mod_val.saturating_sub(1)
-                    }
-                } else {
-                    // Full column
-                    self.get_rows().saturating_sub(1)
-                }
-            }
-            TraversalDirection::Horizontal => {
-                let mod_val = num_values % self.get_used_cols();
-                if mod_val > 0 && col_pos >= mod_val {
-                    // Column with last row empty
-                    self.get_rows().saturating_sub(2)
-                } else {
-                    // Full column
-                    self.get_rows().saturating_sub(1)
-                }
-            }
+    /// The groups with the row of their header, if they have one, and the row
+    /// of their first values
+    fn laid_out_groups(&self) -> impl Iterator<Item = (&ValueGroup, Option<u16>, u16)> {
+        let mut next_row = 0;
+        self.groups.iter().map(move |group| {
+            let header_row = group.header.as_ref().map(|_| next_row);
+            let first_row = next_row + u16::from(header_row.is_some());
+            next_row = first_row + self.group_rows(group);
+            (group, header_row, first_row)
+        })
+    }
+
+    /// Name of the group whose header is on `row`
+    fn header_at(&self, row: u16) -> Option<&str> {
+        self.laid_out_groups()
+            .find(|(_, header_row, _)| *header_row == Some(row))
+            .and_then(|(group, _, _)| group.header.as_deref())
+    }
+
+    /// Index of the value shown on `row` in column `col`, if there is one
+    fn index_at(&self, row: u16, col: u16) -> Option<usize> {
+        let (group, _, first_row) = self.laid_out_groups().find(|(group, _, first_row)| {
+            (*first_row..first_row + self.group_rows(group)).contains(&row)
+        })?;
+        let row = row - first_row;
+        let used_cols = self.group_used_cols(group);
+        if col >= used_cols {
+            return None;
         }
+        let index = match self.default_details.traversal_dir {
+            TraversalDirection::Vertical => col * self.group_rows(group) + row,
+            TraversalDirection::Horizontal => row * used_cols + col,
+        } as usize;
+        (index < group.len).then_some(group.start + index)
     }
 
-    /// Calculates the last column containing a value for the specified row
-    fn get_last_col_at_row(&self, row_pos: u16) -> u16 {
-        let num_values = self.get_values().len() as u16;
-        match self.default_details.traversal_dir {
+    /// Calculates row and column positions from an index
+    fn position_from_index(&self, index: usize) -> (u16, u16) {
+        let Some((group, _, first_row)) = self
+            .laid_out_groups()
+            .find(|(group, _, _)| index < group.start + group.len)
+        else {
+            return (0, 0);
+        };
+        let index = index.saturating_sub(group.start) as u16;
+        let (row, col) = match self.default_details.traversal_dir {
             TraversalDirection::Vertical => {
-                let mod_val = num_values % self.get_rows();
-                if mod_val > 0 && row_pos >= mod_val {
-                    // Row with last column empty
-                    self.get_used_cols().saturating_sub(2)
-                } else {
-                    // Full row
-                    self.get_used_cols().saturating_sub(1)
-                }
+                let rows = self.group_rows(group);
+                (index % rows, index / rows)
             }
             TraversalDirection::Horizontal => {
-                if row_pos >= self.get_rows() - 1 {
-                    // Last row, might not be full
-                    let mod_val = num_values % self.get_used_cols();
-                    if mod_val == 0 {
-                        // Full row
-                        self.get_used_cols().saturating_sub(1)
-                    } else {
-                        // Row with some columns empty
-                        mod_val.saturating_sub(1)
-                    }
-                } else {
-                    // Full row
-                    self.get_used_cols().saturating_sub(1)
-                }
+                let cols = self.group_used_cols(group);
+                (index / cols, index % cols)
             }
-        }
+        };
+        (first_row + row, col)
+    }
+
+    /// The rows with a value in column `col`
+    fn rows_at_col(&self, col: u16) -> Vec<u16> {
+        (0..self.get_rows())
+            .filter(|&row| self.index_at(row, col).is_some())
+            .collect()
+    }
+
+    /// The number of columns with a value on `row`
+    fn cols_at_row(&self, row: u16) -> u16 {
+        (0..self.get_cols())
+            .take_while(|&col| self.index_at(row, col).is_some())
+            .count() as u16
     }
 
     /// Menu index based on column and row position
+    ///
+    /// A position without a value, e.g. before the values are laid out, is
+    /// past the last value.
     fn index(&self) -> usize {
-        let index = match self.default_details.traversal_dir {
-            TraversalDirection::Vertical => self.col_pos * self.get_rows() + self.row_pos,
-            TraversalDirection::Horizontal => self.row_pos * self.get_used_cols() + self.col_pos,
-        };
-        index.into()
+        self.index_at(self.row_pos, self.col_pos)
+            .unwrap_or(self.values.len())
     }
 
     /// Get selected value from the menu
@@ -307,54 +358,150 @@ impl ColumnarMenu {
         self.get_values().get(self.index()).cloned()
     }
 
-    /// Calculates how many rows the menu will use
+    /// Calculates how many rows the menu will use, including the group headers
     fn get_rows(&self) -> u16 {
-        let values = self.get_values().len() as u16;
-
-        if values == 0 {
+        if self.get_values().is_empty() {
             // When the values are empty the "NO RECORDS FOUND" message is shown, taking 1 line
             return 1;
         }
 
-        let rows = values / self.get_cols();
-        if values % self.get_cols() != 0 {
-            rows + 1
-        } else {
-            rows
-        }
+        self.laid_out_groups()
+            .last()
+            .map_or(0, |(group, _, first_row)| {
+                first_row + self.group_rows(group)
+            })
     }
 
-    /// Calculates how many columns will be used to display values
-    fn get_used_cols(&self) -> u16 {
-        let values = self.get_values().len() as u16;
+    /// Returns working details col width
+    fn get_width(&self) -> usize {
+        self.working_details.col_width
+    }
 
-        if values == 0 {
-            // When the values are empty the "NO RECORDS FOUND" message is shown, taking 1 column
-            return 1;
-        }
+    /// Reset menu position
+    fn reset_position(&mut self) {
+        (self.row_pos, self.col_pos) = self.position_from_index(0);
+    }
 
-        match self.default_details.traversal_dir {
-            TraversalDirection::Vertical => {
-                let cols = values / self.get_rows();
-                if values % self.get_rows() != 0 {
-                    cols + 1
-                } else {
-                    cols
-                }
+    /// Select the value at `index` in the current layout
+    fn set_index(&mut self, index: usize) {
+        let index = index.min(self.values.len().saturating_sub(1));
+        (self.row_pos, self.col_pos) = self.position_from_index(index);
+    }
+
+    /// Group the values and update their widths and the text in `base_ranges`
+    /// of `buffer` they are based on, returning where the value at index
+    /// `selected` went
+    fn update_value_details(
+        &mut self,
+        buffer: &str,
+        base_ranges: &[Range<usize>],
+        selected: usize,
+    ) -> usize {
+        let (groups, selected) = group_values(&mut self.values, selected);
+        self.groups = groups;
+        self.kind_width = kind_column_width(&self.values, self.settings.kind_icons);
+        let value_width = self.max_value_width().unwrap_or(usize::MAX);
+        self.display_widths = self
+            .values
+            .iter()
+            .map(|sugg| self.kind_width + sugg.display_value().width().min(value_width))
+            .collect();
+        self.working_details.shortest_base_string = shortest_base_string(buffer, base_ranges);
+        selected
+    }
+
+    /// Widest a value is shown in a column of the max width, without the
+    /// kind in front of it
+    fn max_value_width(&self) -> Option<usize> {
+        let details = &self.default_details;
+        details.max_col_width.map(|max_col_width| {
+            max_col_width
+                .saturating_sub(details.col_padding + self.kind_width)
+                .max(1)
+        })
+    }
+
+    /// Width the columns are laid out in, without the column of a scrollbar
+    /// and the border
+    fn layout_width(&self) -> usize {
+        self.working_details
+            .screen_width
+            .saturating_sub(usize::from(self.working_details.scrollbar))
+            .saturating_sub(self.settings.border_width())
+    }
+
+    /// Lay the values out in columns fitting in `width`
+    fn lay_out(&mut self, width: usize) {
+        // If there is at least one suggestion that contains a description, then the layout
+        // is changed to one column to fit the description
+        let exist_description = self
+            .get_values()
+            .iter()
+            .any(|suggestion| suggestion.description.is_some());
+
+        self.longest_suggestion = *self.display_widths.iter().max().unwrap_or(&0);
+        let max_col_width = self.default_details.max_col_width.unwrap_or(usize::MAX);
+        if exist_description {
+            self.working_details.columns = 1;
+            self.working_details.col_width = width.min(max_col_width);
+        } else {
+            // If no default width is found, then the total screen width is used to estimate
+            // the column width based on the default number of columns
+            let default_width = if let Some(col_width) = self.default_details.col_width {
+                col_width
+            } else {
+                width / self.default_details.columns as usize
+            };
+
+            // Adjusting the working width of the column based the max line width found
+            // in the menu values
+            self.working_details.col_width = default_width
+                .max(self.longest_suggestion + self.default_details.col_padding)
+                .min(max_col_width)
+                .min(width);
+
+            // The working columns is adjusted based on possible number of columns
+            // that could be fitted in the screen with the calculated column width
+            let possible_cols = (width / self.working_details.col_width.max(1)) as u16;
+            if possible_cols > self.default_details.columns {
+                self.working_details.columns = self.default_details.columns.max(1);
+            } else {
+                self.working_details.columns = possible_cols;
             }
-            TraversalDirection::Horizontal => self.get_cols().min(values),
         }
     }
 
-    /// Returns working details col width
-    fn get_width(&self) -> usize {
-        self.working_details.col_width
+    /// Rows shown when `available_lines` are left, keeping one for the
+    /// footer if not all of them fit and two for the border
+    fn shown_rows(&self, available_lines: u16) -> u16 {
+        let available_lines = available_lines
+            .saturating_sub(self.settings.border_rows())
+            .max(1);
+        if self.settings.scroll_indicator == ScrollIndicator::Footer
+            && self.get_rows() > available_lines
+        {
+            available_lines.saturating_sub(1).max(1)
+        } else {
+            available_lines
+        }
     }
 
-    /// Reset menu position
-    fn reset_position(&mut self) {
-        self.col_pos = 0;
-        self.row_pos = 0;
+    /// The header above the values of group `name`
+    fn header_string(&self, name: &str, use_ansi_coloring: bool) -> String {
+        let header = group_header(name);
+        let header = match self.layout_width() {
+            0 => header.into(),
+            width => truncate_with_ansi(&header, width),
+        };
+        if use_ansi_coloring {
+            self.settings
+                .color
+                .group_header_style
+                .paint(header)
+                .to_string()
+        } else {
+            header.into_owned()
+        }
     }
 
     fn no_records_msg(&self, use_ansi_coloring: bool) -> String {
@@ -385,6 +532,7 @@ impl ColumnarMenu {
     ) -> String {
         let selected = index == self.index();
         let display_value = suggestion.display_value();
+        let kind = kind_marker(suggestion, self.kind_width, self.settings.kind_icons);
         let empty_space = self.get_width().saturating_sub(self.display_widths[index]);
 
         if use_ansi_coloring {
@@ -405,24 +553,42 @@ impl ColumnarMenu {
             let padding = left_text_size.saturating_sub(self.display_widths[index]);
 
             let text_style = &suggestion.style.unwrap_or(self.settings.color.text_style);
+            let selected_style = suggestion
+                .style
+                .map_or(self.settings.color.selected_text_style, |style| {
+                    compose_styles(&self.settings.color.selected_text_style, &style)
+                });
             let match_style = if selected {
                 &self.settings.color.selected_match_style
             } else {
                 &self.settings.color.match_style
             };
-            let value_trunc = truncate_with_ansi(display_value, left_text_size);
-            let styled_value = style_suggestion(
-                &value_trunc,
+            let value_width = left_text_size
+                .saturating_sub(self.kind_width)
+                .min(self.max_value_width().unwrap_or(usize::MAX));
+            let (value_trunc, match_indices) = ellipsize_matched(
+                display_value,
                 &match_indices,
-                text_style,
-                match_style,
-                selected.then_some(&self.settings.color.selected_text_style),
+                value_width,
+                self.default_details.ellipsis,
+            );
+            let styled_value = format!(
+                "{}{}",
+                self.settings.color.description_style.paint(kind),
+                style_suggestion(
+                    &value_trunc,
+                    &match_indices,
+                    text_style,
+                    match_style,
+                    selected.then_some(&selected_style),
+                )
             );
 
             match &suggestion.description {
                 Some(desc) if description_size > 3 => {
-                    let desc = desc.replace('\n', "");
-                    let desc_trunc = truncate_with_ansi(desc.as_str(), description_size);
+                    let desc = desc.replace('\n', " ");
+                    let desc_trunc =
+                        ellipsize_with_ansi(&desc, description_size, EllipsisPosition::End);
                     if selected {
                         format!(
                             "{}{}{}{}{}{}{}",
@@ -458,6 +624,12 @@ impl ColumnarMenu {
         } else {
             // If no ansi coloring is found, then the selection word is the line in uppercase
             let marker = if index == self.index() { ">" } else { "" };
+            let display_value = ellipsize_with_ansi(
+                display_value,
+                self.max_value_width().unwrap_or(usize::MAX),
+                self.default_details.ellipsis,
+            );
+            let display_value = format!("{kind}{display_value}");
 
             let line = if let Some(description) = &suggestion.description {
                 format!(
@@ -510,6 +682,11 @@ impl Menu for ColumnarMenu {
         true
     }
 
+    /// Completing one argument after another keeps the menu open
+    fn can_continue(&self) -> bool {
+        true
+    }
+
     /// The columnar menu can try to find the common string and replace it
     /// in the given line buffer
     fn can_partially_complete(
@@ -538,10 +715,17 @@ impl Menu for ColumnarMenu {
     /// Selects what type of event happened with the menu
     fn menu_event(&mut self, event: MenuEvent) {
         match &event {
-            MenuEvent::Activate(_) => self.active = true,
+            MenuEvent::Activate(_) => {
+                self.active = true;
+                self.refining = false;
+            }
             MenuEvent::Deactivate => {
                 self.active = false;
                 self.input = None;
+                self.arriving = None;
+            }
+            MenuEvent::Edit(_) if self.settings.keep_selection => {
+                self.kept_selection = self.get_value().map(|suggestion| suggestion.value);
             }
             _ => {}
         }
@@ -562,24 +746,14 @@ impl Menu for ColumnarMenu {
             self.settings.only_buffer_difference,
         );
 
-        let (values, base_ranges) = completer.complete_with_base_ranges(&input, pos);
+        let ctx = CompletionContext::new(&input, pos, self.refining);
+        let (values, base_ranges, arriving) =
+            ArrivingValues::start(completer, &ctx, editor.get_buffer());
+        self.arriving = arriving;
+        self.refining = true;
 
         self.values = values;
-        self.display_widths = self
-            .values
-            .iter()
-            .map(|sugg| sugg.display_value().width())
-            .collect();
-        self.working_details.shortest_base_string = base_ranges
-            .iter()
-            .map(|range| {
-                let end = floor_char_boundary(editor.get_buffer(), range.end);
-                let start = floor_char_boundary(editor.get_buffer(), range.start).min(end);
-                editor.get_buffer()[start..end].to_string()
-            })
-            .min_by_key(|s| s.width())
-            .unwrap_or_default();
-
+        self.update_value_details(editor.get_buffer(), &base_ranges, 0);
         self.reset_position();
     }
 
@@ -591,6 +765,7 @@ impl Menu for ColumnarMenu {
         completer: &mut dyn Completer,
         painter: &Painter,
     ) {
+        let screen_width = painter.screen_width() as usize;
         if let Some(event) = self.event.take() {
             match event {
                 MenuEvent::Activate(updated) => {
@@ -607,6 +782,12 @@ impl Menu for ColumnarMenu {
                     if !updated {
                         self.update_values(editor, completer);
                     }
+                    // Selected once the values are laid out
+                    self.arrived_selection = self.kept_selection.take().and_then(|value| {
+                        self.values
+                            .iter()
+                            .position(|suggestion| suggestion.value == value)
+                    });
                 }
                 MenuEvent::NextElement => self.move_next(),
                 MenuEvent::PreviousElement => self.move_previous(),
@@ -617,66 +798,64 @@ impl Menu for ColumnarMenu {
                 MenuEvent::PreviousPage | MenuEvent::NextPage => {
                     // The columnar menu doest have the concept of pages, yet
                 }
+                // Laying the values out again loses the position
+                MenuEvent::Refresh => self.update_values(editor, completer),
+                MenuEvent::Select(_)
+                | MenuEvent::Accept
+                | MenuEvent::Notice(_)
+                | MenuEvent::ScrollDescriptionUp
+                | MenuEvent::ScrollDescriptionDown => {}
             }
-
-            // The working value for the menu are updated only after executing the menu events,
-            // so they have the latest suggestions
-            //
-            // If there is at least one suggestion that contains a description, then the layout
-            // is changed to one column to fit the description
-            let exist_description = self
-                .get_values()
-                .iter()
-                .any(|suggestion| suggestion.description.is_some());
-
-            let screen_width = painter.screen_width() as usize;
-            self.longest_suggestion = *self.display_widths.iter().max().unwrap_or(&0);
-            if exist_description {
-                self.working_details.columns = 1;
-                self.working_details.col_width = screen_width;
-            } else {
-                // If no default width is found, then the total screen width is used to estimate
-                // the column width based on the default number of columns
-                let default_width = if let Some(col_width) = self.default_details.col_width {
-                    col_width
-                } else {
-                    screen_width / self.default_details.columns as usize
-                };
-
-                // Adjusting the working width of the column based the max line width found
-                // in the menu values
-                self.working_details.col_width = default_width
-                    .max(self.longest_suggestion + self.default_details.col_padding)
-                    .min(screen_width);
-
-                // The working columns is adjusted based on possible number of columns
-                // that could be fitted in the screen with the calculated column width
-                let possible_cols = painter.screen_width() / self.working_details.col_width as u16;
-                if possible_cols > self.default_details.columns {
-                    self.working_details.columns = self.default_details.columns.max(1);
-                } else {
-                    self.working_details.columns = possible_cols;
-                }
+            if self.is_active() {
+                let index = self.index();
+                resolve_description(self.values.get_mut(index), completer);
             }
+        } else if self.working_details.screen_width == screen_width
+            && self.arrived_selection.is_none()
+        {
+            // Nothing changed since the last layout, a resize or values
+            // arriving lay the values out again
+            return;
+        }
+        self.working_details.screen_width = screen_width;
 
-            let mut available_lines = painter.remaining_lines_real();
-            // Handle the case where a prompt uses the entire screen.
-            // Drawing the menu has priority over the drawing the prompt.
-            if available_lines == 0 {
-                available_lines = painter.remaining_lines().min(self.min_rows());
-            }
+        let mut available_lines = painter.remaining_lines_real();
+        // Handle the case where a prompt uses the entire screen.
+        // Drawing the menu has priority over the drawing the prompt.
+        if available_lines == 0 {
+            available_lines = painter.remaining_lines().min(self.min_rows());
+        }
 
-            self.skip_rows = if self.row_pos < self.skip_rows {
-                // Selection is above the visible area, scroll up
-                self.row_pos
-            } else if self.row_pos >= self.skip_rows + available_lines {
-                // Selection is below the visible area, scroll down
-                self.row_pos - available_lines + 1
-            } else {
-                // Selection is within the visible area
-                self.skip_rows
-            };
+        // The working value for the menu are updated only after executing the menu events,
+        // so they have the latest suggestions
+        self.working_details.scrollbar = false;
+        self.lay_out(self.layout_width());
+        if self.settings.scroll_indicator == ScrollIndicator::Scrollbar
+            && self.get_rows() > self.shown_rows(available_lines)
+        {
+            self.working_details.scrollbar = true;
+            self.lay_out(self.layout_width());
+        }
+        if let Some(index) = self.arrived_selection.take() {
+            self.set_index(index);
         }
+        let available_lines = self.shown_rows(available_lines);
+
+        // The header of the first values of a group is shown with them
+        let top_row = match self.row_pos.checked_sub(1) {
+            Some(above) if self.header_at(above).is_some() => above,
+            _ => self.row_pos,
+        };
+        self.skip_rows = if top_row < self.skip_rows {
+            // Selection is above the visible area, scroll up
+            top_row
+        } else if self.row_pos >= self.skip_rows + available_lines {
+            // Selection is below the visible area, scroll down
+            self.row_pos - available_lines + 1
+        } else {
+            // Selection is within the visible area
+            self.skip_rows
+        };
     }
 
     /// The buffer gets replaced in the Span location
@@ -684,6 +863,36 @@ impl Menu for ColumnarMenu {
         replace_in_buffer(self.get_value(), editor);
     }
 
+    fn selected_value(&self) -> Option<Suggestion> {
+        self.get_value()
+    }
+
+    fn is_completing(&self) -> bool {
+        self.arriving.is_some()
+    }
+
+    /// Arriving values are added after the ones shown, the selected value
+    /// stays selected
+    fn poll_values(&mut self) -> bool {
+        let Some(arriving) = self.arriving.as_mut() else {
+            return false;
+        };
+        let values = arriving.take();
+        let buffer = arriving.buffer().to_string();
+        let base_ranges = arriving.base_ranges().to_vec();
+        if arriving.is_finished() {
+            self.arriving = None;
+        }
+        if values.is_empty() {
+            return false;
+        }
+
+        let selected = self.index();
+        self.values.extend(values);
+        self.arrived_selection = Some(self.update_value_details(&buffer, &base_ranges, selected));
+        true
+    }
+
     /// Minimum rows that should be displayed by the menu
     fn min_rows(&self) -> u16 {
         self.get_rows().min(self.min_rows)
@@ -695,7 +904,7 @@ impl Menu for ColumnarMenu {
     }
 
     fn menu_required_lines(&self, _terminal_columns: u16) -> u16 {
-        self.get_rows()
+        self.get_rows() + self.settings.border_rows()
     }
 
     fn menu_string(&self, available_lines: u16, use_ansi_coloring: bool) -> String {
@@ -705,58 +914,67 @@ impl Menu for ColumnarMenu {
             // It seems that crossterm prefers to have a complete string ready to be printed
             // rather than looping through the values and printing multiple things
             // This reduces the flickering when printing the menu
-            match self.default_details.traversal_dir {
-                TraversalDirection::Vertical => {
-                    let num_rows: usize = self.get_rows().into();
-                    let rows_to_draw = num_rows.min(available_lines.into());
-                    let mut menu_string = String::new();
-                    for line in 0..rows_to_draw {
-                        let skip_value = self.skip_rows as usize + line;
-                        let row_string: String = self
-                            .get_values()
-                            .iter()
-                            .enumerate()
-                            .skip(skip_value)
-                            .step_by(num_rows)
-                            .take(self.get_cols().into())
-                            .map(|(index, suggestion)| {
-                                self.create_string(suggestion, index, use_ansi_coloring)
-                            })
-                            .collect();
-                        menu_string.push_str(&row_string);
-                        menu_string.push_str("\r\n");
+            let rows = self.get_rows();
+            let shown_rows = self.shown_rows(available_lines);
+            let last_row = rows.min(self.skip_rows.saturating_add(shown_rows));
+            let mut lines = Vec::new();
+            let mut line_break_after = true;
+            for row in self.skip_rows..last_row {
+                if let Some(name) = self.header_at(row) {
+                    lines.push(self.header_string(name, use_ansi_coloring));
+                    continue;
+                }
+                let mut line = String::new();
+                let mut cols = 0;
+                for index in (0..self.get_cols()).map_while(|col| self.index_at(row, col)) {
+                    let suggestion = &self.values[index];
+                    line.push_str(&self.create_string(suggestion, index, use_ansi_coloring));
+                    cols += 1;
+                }
+                lines.push(line);
+                // The horizontal layout ends without a line break after a
+                // last row that isn't full
+                line_break_after = self.default_details.traversal_dir
+                    == TraversalDirection::Vertical
+                    || cols == self.get_cols()
+                    || row + 1 < rows;
+            }
+
+            let shown = self.skip_rows as usize..last_row as usize;
+            match self.settings.scroll_indicator {
+                ScrollIndicator::None => {}
+                ScrollIndicator::Scrollbar => {
+                    if let Some(track) = scrollbar_track(rows as usize, shown, lines.len()) {
+                        for (line, glyph) in lines.iter_mut().zip(track) {
+                            *line = with_scrollbar_glyph(line, self.layout_width(), glyph);
+                        }
                     }
-                    menu_string
                 }
-                TraversalDirection::Horizontal => {
-                    let available_values = (available_lines * self.get_cols()) as usize;
-                    let skip_values = (self.skip_rows * self.get_used_cols()) as usize;
-
-                    self.get_values()
-                        .iter()
-                        .skip(skip_values)
-                        .take(available_values)
-                        .enumerate()
-                        .map(|(index, suggestion)| {
-                            // Correcting the enumerate index based on the number of skipped values
-                            let index = index + skip_values;
-                            let column = index % self.get_cols() as usize;
-
-                            let end_of_line =
-                                if column == self.get_cols().saturating_sub(1) as usize {
-                                    "\r\n"
-                                } else {
-                                    ""
-                                };
-                            format!(
-                                "{}{}",
-                                self.create_string(suggestion, index, use_ansi_coloring),
-                                end_of_line
-                            )
-                        })
-                        .collect()
+                ScrollIndicator::Footer => {
+                    if shown.len() < rows as usize {
+                        let footer = scroll_footer(self.index(), self.values.len());
+                        lines.push(if use_ansi_coloring {
+                            self.settings.color.text_style.paint(footer).to_string()
+                        } else {
+                            footer
+                        });
+                        line_break_after = false;
+                    }
                 }
             }
+
+            if let Some(border) = &self.settings.border {
+                let content_width =
+                    self.layout_width() + usize::from(self.working_details.scrollbar);
+                lines = border.frame(lines, content_width, use_ansi_coloring);
+                line_break_after = false;
+            }
+
+            let mut menu_string = lines.join("\r\n");
+            if line_break_after {
+                menu_string.push_str("\r\n");
+            }
+            menu_string
         }
     }
 }
@@ -765,7 +983,9 @@ impl Menu for ColumnarMenu {
 mod tests {
     use std::io::BufWriter;
 
-    use crate::{Span, UndoBehavior};
+    use crate::{DefaultCompleter, MenuBorder, Span, SuggestionKind, UndoBehavior};
+    use nu_ansi_term::{Color, Style};
+    use rstest::rstest;
 
     use super::*;
 
@@ -1075,4 +1295,782 @@ mod tests {
             assert!(menu.row_pos == 0 && menu.col_pos == 1);
         }
     }
+
+    #[test]
+    fn test_menu_selection_stops_at_the_ends_without_wrap() {
+        let vs: Vec<String> = (0..10).map(|v| v.to_string()).collect();
+        let vs: Vec<_> = vs.iter().map(|v| v.as_ref()).collect();
+        let mut completer = FakeCompleter::new(&vs);
+        let mut menu = ColumnarMenu::default()
+            .with_traversal_direction(TraversalDirection::Horizontal)
+            .with_name("testmenu")
+            .with_wrap(false);
+        menu.working_details.columns = 4;
+        let mut editor = Editor::default();
+
+        editor.set_buffer("a".to_string(), UndoBehavior::CreateUndoPoint);
+        menu.update_values(&mut editor, &mut completer);
+        menu.move_previous();
+        assert!(menu.index() == 0);
+        menu.move_up();
+        menu.move_left();
+        assert!(menu.row_pos == 0 && menu.col_pos == 0);
+        for _ in 0..vs.len() {
+            menu.move_next();
+        }
+        assert!(menu.index() == vs.len() - 1);
+        assert!(menu.row_pos == 2 && menu.col_pos == 1);
+        menu.move_down();
+        menu.move_right();
+        assert!(menu.row_pos == 2 && menu.col_pos == 1);
+    }
+
+    struct KindCompleter(Vec<(&'static str, Option<SuggestionKind>)>);
+
+    impl Completer for KindCompleter {
+        fn complete(&mut self, _line: &str, pos: usize) -> Vec<Suggestion> {
+            self.0
+                .iter()
+                .map(|(name, kind)| Suggestion {
+                    kind: *kind,
+                    ..fake_suggestion(name, pos)
+                })
+                .collect()
+        }
+    }
+
+    #[rstest]
+    #[case::icons(true, [">ƒ ls", "  lib.rs", "α limit"])]
+    #[case::labels(false, [">fn  ls", "    lib.rs", "var limit"])]
+    fn kinds_are_shown_in_front_of_the_values(#[case] icons: bool, #[case] expected: [&str; 3]) {
+        let mut completer = KindCompleter(vec![
+            ("ls", Some(SuggestionKind::Function)),
+            ("lib.rs", None),
+            ("limit", Some(SuggestionKind::Variable)),
+        ]);
+        let mut menu = ColumnarMenu::default()
+            .with_name("testmenu")
+            .with_kind_icons(icons);
+        menu.working_details.columns = 1;
+        let mut editor = Editor::default();
+        editor.set_buffer("l".to_string(), UndoBehavior::CreateUndoPoint);
+
+        menu.update_values(&mut editor, &mut completer);
+
+        let lines: Vec<String> = menu
+            .menu_string(5, false)
+            .lines()
+            .map(|line| line.trim_end().to_lowercase())
+            .collect();
+        assert_eq!(lines, expected);
+    }
+
+    /// Describes suggestions only when asked, recording what it was asked for
+    struct LazyCompleter {
+        completer: FakeCompleter,
+        resolved: Vec<String>,
+    }
+
+    impl Completer for LazyCompleter {
+        fn complete(&mut self, line: &str, pos: usize) -> Vec<Suggestion> {
+            self.completer.complete(line, pos)
+        }
+
+        fn resolve_description(&mut self, suggestion: &Suggestion) -> Option<String> {
+            self.resolved.push(suggestion.value.clone());
+            Some(format!("about {}", suggestion.value))
+        }
+    }
+
+    #[test]
+    fn selected_suggestion_description_is_resolved_once() {
+        let mut completer = LazyCompleter {
+            completer: FakeCompleter::new(&["ls", "less", "lsof"]),
+            resolved: Vec::new(),
+        };
+        let mut menu = ColumnarMenu::default().with_name("testmenu");
+        let mut editor = Editor::default();
+        editor.set_buffer("l".to_string(), UndoBehavior::CreateUndoPoint);
+        let mut painter = Painter::new(BufWriter::new(std::io::stderr()));
+        painter.handle_resize(80, 10);
+
+        menu.menu_event(MenuEvent::Activate(false));
+        menu.update_working_details(&mut editor, &mut completer, &painter);
+        for event in [MenuEvent::NextElement, MenuEvent::PreviousElement] {
+            menu.menu_event(event);
+            menu.update_working_details(&mut editor, &mut completer, &painter);
+        }
+
+        assert_eq!(completer.resolved, ["ls", "less"]);
+        let descriptions: Vec<_> = menu
+            .get_values()
+            .iter()
+            .map(|suggestion| suggestion.description.as_deref())
+            .collect();
+        assert_eq!(descriptions, [Some("about ls"), Some("about less"), None]);
+    }
+
+    /// Replaces the first word, based on the whole line
+    struct WholeLineCompleter;
+
+    impl Completer for WholeLineCompleter {
+        fn complete(&mut self, _line: &str, _pos: usize) -> Vec<Suggestion> {
+            vec![fake_suggestion("git status", 3)]
+        }
+
+        fn complete_streaming(
+            &mut self,
+            ctx: &CompletionContext,
+            _token: crate::CancellationToken,
+        ) -> crate::CompletionHandle {
+            crate::CompletionHandle::ready(self.complete(ctx.line, ctx.pos)).with_base_ranges(vec![
+                Range {
+                    start: 0,
+                    end: ctx.line.len(),
+                },
+            ])
+        }
+    }
+
+    #[test]
+    fn menus_use_the_base_ranges_of_the_completer() {
+        let mut menu = ColumnarMenu::default().with_name("testmenu");
+        let mut editor = Editor::default();
+        editor.set_buffer("git st".to_string(), UndoBehavior::CreateUndoPoint);
+
+        menu.menu_event(MenuEvent::Activate(false));
+        menu.update_values(&mut editor, &mut WholeLineCompleter);
+
+        assert_eq!(menu.working_details.shortest_base_string, "git st");
+    }
+
+    /// Replaces the first word, with base ranges covering the whole line
+    struct WholeLineRangesCompleter;
+
+    impl Completer for WholeLineRangesCompleter {
+        fn complete(&mut self, _line: &str, _pos: usize) -> Vec<Suggestion> {
+            unreachable!("completes with base ranges")
+        }
+
+        fn complete_with_base_ranges(
+            &mut self,
+            line: &str,
+            _pos: usize,
+        ) -> (Vec<Suggestion>, Vec<Range<usize>>) {
+            (
+                vec![fake_suggestion("git status", 3)],
+                vec![Range {
+                    start: 0,
+                    end: line.len(),
+                }],
+            )
+        }
+    }
+
+    #[rstest]
+    #[case::alone(Box::new(WholeLineRangesCompleter))]
+    #[case::chained(Box::new(crate::ChainedCompleter::new(vec![Box::new(
+        WholeLineRangesCompleter
+    )])))]
+    fn menus_use_the_base_ranges_of_complete_with_base_ranges(
+        #[case] mut completer: Box<dyn Completer>,
+    ) {
+        let mut menu = ColumnarMenu::default().with_name("testmenu");
+        let mut editor = Editor::default();
+        editor.set_buffer("git st".to_string(), UndoBehavior::CreateUndoPoint);
+
+        menu.menu_event(MenuEvent::Activate(false));
+        menu.update_values(&mut editor, completer.as_mut());
+
+        assert_eq!(menu.working_details.shortest_base_string, "git st");
+    }
+
+    /// Records the word, quote and refinement of each context it completes with
+    #[derive(Default)]
+    struct ContextCompleter {
+        contexts: Vec<(String, Option<char>, bool)>,
+    }
+
+    impl Completer for ContextCompleter {
+        fn complete(&mut self, _line: &str, _pos: usize) -> Vec<Suggestion> {
+            unreachable!("menus complete with the context")
+        }
+
+        fn complete_with_context(&mut self, ctx: &CompletionContext) -> Vec<Suggestion> {
+            self.contexts
+                .push((ctx.word.to_string(), ctx.quote, ctx.refinement));
+            vec![fake_suggestion("ls", ctx.pos)]
+        }
+    }
+
+    #[test]
+    fn menus_complete_with_the_context_of_the_cursor() {
+        let mut completer = ContextCompleter::default();
+        let mut menu = ColumnarMenu::default().with_name("testmenu");
+        let mut editor = Editor::default();
+        editor.set_buffer("cd \"My Doc".to_string(), UndoBehavior::CreateUndoPoint);
+
+        menu.menu_event(MenuEvent::Activate(false));
+        menu.update_values(&mut editor, &mut completer);
+        editor.set_buffer(
+            "cd \"My Documents\" ".to_string(),
+            UndoBehavior::CreateUndoPoint,
+        );
+        menu.menu_event(MenuEvent::Edit(false));
+        menu.update_values(&mut editor, &mut completer);
+        editor.set_buffer("ls".to_string(), UndoBehavior::CreateUndoPoint);
+        menu.menu_event(MenuEvent::Deactivate);
+        menu.menu_event(MenuEvent::Activate(false));
+        menu.update_values(&mut editor, &mut completer);
+
+        assert_eq!(
+            completer.contexts,
+            [
+                ("\"My Doc".to_string(), Some('"'), false),
+                (String::new(), None, true),
+                ("ls".to_string(), None, false),
+            ]
+        );
+    }
+
+    /// Suggests the word followed by each of the `early` suffixes right away
+    /// and by the `late` one after `delay`, unless cancelled by then
+    struct SlowCompleter {
+        early: Vec<&'static str>,
+        late: &'static str,
+        delay: std::time::Duration,
+        /// The word of each completion and whether it ended up cancelled
+        cancelled: std::sync::Arc<std::sync::Mutex<Vec<(String, bool)>>>,
+    }
+
+    impl SlowCompleter {
+        fn new(delay_ms: u64) -> Self {
+            SlowCompleter {
+                early: vec!["1"],
+                late: "2",
+                delay: std::time::Duration::from_millis(delay_ms),
+                cancelled: std::sync::Arc::default(),
+            }
+        }
+    }
+
+    impl Completer for SlowCompleter {
+        fn complete(&mut self, _line: &str, _pos: usize) -> Vec<Suggestion> {
+            unreachable!("menus complete in the background")
+        }
+
+        fn complete_streaming(
+            &mut self,
+            ctx: &CompletionContext,
+            token: crate::CancellationToken,
+        ) -> crate::CompletionHandle {
+            let (sink, handle) = crate::CompletionHandle::streaming();
+            let (word, pos, delay, late) = (ctx.word.to_string(), ctx.pos, self.delay, self.late);
+            let early: Vec<_> = self
+                .early
+                .iter()
+                .map(|suffix| fake_suggestion(&format!("{word}{suffix}"), pos))
+                .collect();
+            let cancelled = self.cancelled.clone();
+            std::thread::spawn(move || {
+                sink.send(early);
+                std::thread::sleep(delay);
+                cancelled
+                    .lock()
+                    .unwrap()
+                    .push((word.clone(), token.is_cancelled()));
+                if !token.is_cancelled() {
+                    sink.send(vec![fake_suggestion(&format!("{word}{late}"), pos)]);
+                }
+            });
+            handle
+        }
+    }
+
+    /// Poll the values of `menu` until all arrived
+    fn poll_until_complete(menu: &mut ColumnarMenu) {
+        let start = std::time::Instant::now();
+        while menu.is_completing() {
+            assert!(start.elapsed().as_secs() < 5, "completion never finished");
+            menu.poll_values();
+            std::thread::sleep(std::time::Duration::from_millis(5));
+        }
+    }
+
+    fn values(menu: &ColumnarMenu) -> Vec<&str> {
+        menu.get_values().iter().map(|s| s.value.as_str()).collect()
+    }
+
+    #[test]
+    fn slow_completions_arrive_without_blocking_the_menu() {
+        let mut completer = SlowCompleter::new(200);
+        let mut menu = ColumnarMenu::default().with_name("testmenu");
+        let mut editor = Editor::default();
+        editor.set_buffer("ca".to_string(), UndoBehavior::CreateUndoPoint);
+
+        let start = std::time::Instant::now();
+        menu.menu_event(MenuEvent::Activate(false));
+        menu.update_values(&mut editor, &mut completer);
+        assert!(start.elapsed() < completer.delay);
+        assert!(menu.is_completing());
+
+        poll_until_complete(&mut menu);
+        assert_eq!(values(&menu), ["ca1", "ca2"]);
+        assert!(!menu.poll_values());
+    }
+
+    #[test]
+    fn stale_completions_are_discarded() {
+        let mut completer = SlowCompleter::new(100);
+        let mut menu = ColumnarMenu::default().with_name("testmenu");
+        let mut editor = Editor::default();
+        editor.set_buffer("ca".to_string(), UndoBehavior::CreateUndoPoint);
+        menu.menu_event(MenuEvent::Activate(false));
+        menu.update_values(&mut editor, &mut completer);
+
+        // Typing on before the completion finished
+        editor.set_buffer("car".to_string(), UndoBehavior::CreateUndoPoint);
+        menu.menu_event(MenuEvent::Edit(false));
+        menu.update_values(&mut editor, &mut completer);
+        poll_until_complete(&mut menu);
+        assert_eq!(values(&menu), ["car1", "car2"]);
+
+        // The first completion sees its cancellation, however the threads run
+        let mut cancelled = completer.cancelled.lock().unwrap().clone();
+        let start = std::time::Instant::now();
+        while cancelled.len() < 2 {
+            assert!(start.elapsed().as_secs() < 5, "completion never finished");
+            std::thread::sleep(std::time::Duration::from_millis(5));
+            cancelled = completer.cancelled.lock().unwrap().clone();
+        }
+        cancelled.sort();
+        assert_eq!(
+            cancelled,
+            [("ca".to_string(), true), ("car".to_string(), false)]
+        );
+    }
+
+    #[test]
+    fn selection_stays_on_its_value_as_values_arrive() {
+        let mut completer = SlowCompleter {
+            early: vec!["1", "2", "3"],
+            late: "4 taking up a whole line of the menu on its own",
+            ..SlowCompleter::new(50)
+        };
+        let mut menu = ColumnarMenu::default()
+            .with_name("testmenu")
+            .with_columns(2);
+        let mut editor = Editor::default();
+        editor.set_buffer("ca".to_string(), UndoBehavior::CreateUndoPoint);
+        let mut painter = Painter::new(BufWriter::new(std::io::stderr()));
+        painter.handle_resize(80, 10);
+
+        menu.menu_event(MenuEvent::Activate(false));
+        menu.update_working_details(&mut editor, &mut completer, &painter);
+        let start = std::time::Instant::now();
+        while menu.get_values().len() < 3 {
+            assert!(start.elapsed().as_secs() < 5, "early values never arrived");
+            menu.poll_values();
+            std::thread::sleep(std::time::Duration::from_millis(5));
+        }
+        menu.update_working_details(&mut editor, &mut completer, &painter);
+        for _ in 0..2 {
+            menu.menu_event(MenuEvent::NextElement);
+            menu.update_working_details(&mut editor, &mut completer, &painter);
+        }
+        assert_eq!(menu.selected_value().unwrap().value, "ca3");
+
+        // The long value leaves room for one column only
+        poll_until_complete(&mut menu);
+        menu.update_working_details(&mut editor, &mut completer, &painter);
+        assert_eq!(menu.get_values().len(), 4);
+        assert_eq!(menu.working_details.columns, 1);
+        assert_eq!(menu.selected_value().unwrap().value, "ca3");
+    }
+
+    struct StyleCompleter(Vec<(&'static str, Option<Style>)>);
+
+    impl Completer for StyleCompleter {
+        fn complete(&mut self, _line: &str, pos: usize) -> Vec<Suggestion> {
+            self.0
+                .iter()
+                .map(|(name, style)| Suggestion {
+                    style: *style,
+                    ..fake_suggestion(name, pos)
+                })
+                .collect()
+        }
+    }
+
+    #[test]
+    fn values_are_painted_in_their_own_style() {
+        let blue = Color::Blue.normal();
+        let mut completer = StyleCompleter(vec![
+            ("lib/", Some(blue)),
+            ("log/", Some(blue)),
+            ("lsd", None),
+        ]);
+        let mut menu = ColumnarMenu::default().with_name("testmenu");
+        menu.working_details.columns = 1;
+        let mut editor = Editor::default();
+        editor.set_buffer("l".to_string(), UndoBehavior::CreateUndoPoint);
+
+        menu.update_values(&mut editor, &mut completer);
+
+        let rows: Vec<String> = (0..3)
+            .map(|index| menu.create_string(&menu.get_values()[index], index, true))
+            .collect();
+        let colors = &menu.settings.color;
+        let selected_blue = compose_styles(&colors.selected_text_style, &blue);
+        assert!(rows[0].contains(&selected_blue.prefix().to_string()));
+        assert!(rows[1].contains(&blue.prefix().to_string()));
+        assert!(rows[2].contains(&colors.text_style.prefix().to_string()));
+        // Styles take no room
+        let widths: Vec<usize> = rows
+            .iter()
+            .map(|row| {
+                String::from_utf8(strip_ansi_escapes::strip(row))
+                    .unwrap()
+                    .width()
+            })
+            .collect();
+        assert_eq!(widths, [widths[2]; 3]);
+    }
+
+    #[test]
+    fn rows_with_and_without_a_style_of_their_own() {
+        let mut completer = StyleCompleter(vec![
+            ("lib/", Some(Color::Blue.normal())),
+            ("lsd", None),
+            ("old", Some(Color::DarkGray.dimmed())),
+        ]);
+        let mut menu = ColumnarMenu::default().with_columns(1);
+        let mut editor = Editor::default();
+        editor.set_buffer("l".to_string(), UndoBehavior::CreateUndoPoint);
+        let mut painter = Painter::new(BufWriter::new(std::io::stderr()));
+        painter.handle_resize(16, 10);
+        menu.menu_event(MenuEvent::Activate(false));
+
+        let mut rendered = Vec::new();
+        for selected in 0..3 {
+            menu.update_working_details(&mut editor, &mut completer, &painter);
+            rendered.push(format!("selected: {selected}"));
+            rendered.extend(
+                menu.menu_string(10, true)
+                    .split("\r\n")
+                    .map(|line| line.trim_end().replace('\u{1b}', "\\e")),
+            );
+            menu.menu_event(MenuEvent::MoveDown);
+        }
+
+        insta::assert_snapshot!(rendered.join("\n"));
+    }
+
+    /// Completes files, aliases and a value without a group, mixed up
+    struct GroupedCompleter;
+
+    impl Completer for GroupedCompleter {
+        fn complete(&mut self, _line: &str, pos: usize) -> Vec<Suggestion> {
+            [
+                ("a.rs", Some("files")),
+                ("ll", Some("aliases")),
+                ("b.rs", Some("files")),
+                ("x", None),
+                ("c.rs", Some("files")),
+                ("d.rs", Some("files")),
+                ("la", Some("aliases")),
+                ("e.rs", Some("files")),
+            ]
+            .into_iter()
+            .map(|(name, group)| Suggestion {
+                group: group.map(str::to_string),
+                ..fake_suggestion(name, pos)
+            })
+            .collect()
+        }
+    }
+
+    fn grouped_menu(direction: TraversalDirection, terminal_size: (u16, u16)) -> ColumnarMenu {
+        let mut menu = ColumnarMenu::default()
+            .with_name("testmenu")
+            .with_traversal_direction(direction);
+        let mut editor = Editor::default();
+        setup_menu(&mut menu, &mut editor, &mut GroupedCompleter, terminal_size);
+        menu
+    }
+
+    fn menu_lines(menu: &ColumnarMenu, available_lines: u16) -> Vec<String> {
+        menu.menu_string(available_lines, false)
+            .split("\r\n")
+            .map(|line| line.trim_end().to_string())
+            .collect()
+    }
+
+    #[rstest]
+    #[case::horizontal(
+        TraversalDirection::Horizontal,
+        &[
+            "── files ──",
+            ">A.RS     b.rs      c.rs      d.rs",
+            "e.rs",
+            "── aliases ──",
+            "ll        la",
+            "x",
+        ]
+    )]
+    #[case::vertical(
+        TraversalDirection::Vertical,
+        &[
+            "── files ──",
+            ">A.RS     c.rs      e.rs",
+            "b.rs      d.rs",
+            "── aliases ──",
+            "ll        la",
+            "x",
+            "",
+        ]
+    )]
+    fn groups_are_laid_out_below_their_headers(
+        #[case] direction: TraversalDirection,
+        #[case] expected: &[&str],
+    ) {
+        let menu = grouped_menu(direction, (40, 20));
+
+        assert_eq!(menu.menu_required_lines(40), 6);
+        assert_eq!(menu_lines(&menu, 10), expected);
+    }
+
+    #[test]
+    fn navigation_skips_the_group_headers() {
+        let mut menu = grouped_menu(TraversalDirection::Horizontal, (40, 20));
+        let selected = |menu: &ColumnarMenu| menu.get_value().unwrap().value;
+
+        menu.move_down();
+        assert_eq!(selected(&menu), "e.rs");
+        menu.move_down();
+        assert_eq!(selected(&menu), "ll");
+        menu.move_right();
+        assert_eq!(selected(&menu), "la");
+        menu.move_down();
+        assert_eq!(selected(&menu), "b.rs");
+        menu.move_previous();
+        assert_eq!(selected(&menu), "a.rs");
+        menu.move_previous();
+        assert_eq!(selected(&menu), "x");
+        menu.move_previous();
+        assert_eq!(selected(&menu), "la");
+    }
+
+    #[test]
+    fn scrolling_up_to_a_group_shows_its_header() {
+        let mut painter = Painter::new(BufWriter::new(std::io::stderr()));
+        painter.handle_resize(40, 3);
+        let mut menu = grouped_menu(TraversalDirection::Horizontal, (40, 3));
+        let mut editor = Editor::default();
+
+        menu.menu_event(MenuEvent::PreviousElement);
+        menu.update_working_details(&mut editor, &mut GroupedCompleter, &painter);
+        assert_eq!(
+            menu_lines(&menu, 3),
+            ["── aliases ──", "ll        la", ">X"]
+        );
+
+        for _ in 0..2 {
+            menu.menu_event(MenuEvent::PreviousElement);
+            menu.update_working_details(&mut editor, &mut GroupedCompleter, &painter);
+        }
+        assert_eq!(menu_lines(&menu, 3), ["── aliases ──", ">LL       la", "x"]);
+
+        menu.menu_event(MenuEvent::PreviousElement);
+        menu.update_working_details(&mut editor, &mut GroupedCompleter, &painter);
+        assert_eq!(
+            menu_lines(&menu, 3),
+            [">E.RS", "── aliases ──", "ll        la", ""]
+        );
+    }
+
+    /// Twenty values in rows of two on a screen 16 columns wide and `height`
+    /// rows high
+    fn scrolled_menu(
+        indicator: ScrollIndicator,
+        height: u16,
+    ) -> (ColumnarMenu, FakeCompleter, Painter) {
+        let mut menu = ColumnarMenu::default()
+            .with_columns(2)
+            .with_scroll_indicator(indicator);
+        let values: Vec<String> = (0..20).map(|i| format!("v{i}")).collect();
+        let values: Vec<&str> = values.iter().map(String::as_str).collect();
+        let mut completer = FakeCompleter::new(&values);
+        let mut painter = Painter::new(BufWriter::new(std::io::stderr()));
+        painter.handle_resize(16, height);
+        menu.menu_event(MenuEvent::Activate(false));
+        menu.update_working_details(&mut Editor::default(), &mut completer, &painter);
+        (menu, completer, painter)
+    }
+
+    #[test]
+    fn scrollbar_thumb_follows_the_rows_shown() {
+        let (mut menu, mut completer, painter) = scrolled_menu(ScrollIndicator::Scrollbar, 3);
+        let mut lines_after_moving_down = |times| {
+            for _ in 0..times {
+                menu.menu_event(MenuEvent::MoveDown);
+                menu.update_working_details(&mut Editor::default(), &mut completer, &painter);
+            }
+            menu_lines(&menu, 3)
+        };
+
+        assert_eq!(
+            lines_after_moving_down(0),
+            [
+                ">V0    v1      ┃",
+                "v2     v3      │",
+                "v4     v5      │",
+                ""
+            ]
+        );
+        assert_eq!(
+            lines_after_moving_down(5),
+            [
+                "v6     v7      │",
+                "v8     v9      ┃",
+                ">V10   v11     │",
+                ""
+            ]
+        );
+        assert_eq!(
+            lines_after_moving_down(4),
+            [
+                "v14    v15     │",
+                "v16    v17     │",
+                ">V18   v19     ┃",
+                ""
+            ]
+        );
+    }
+
+    #[test]
+    fn footer_shows_the_position_of_the_selection() {
+        let (menu, _, _) = scrolled_menu(ScrollIndicator::Footer, 3);
+
+        assert_eq!(menu_lines(&menu, 3), [">V0     v1", "v2      v3", "[1/20]"]);
+    }
+
+    #[rstest]
+    #[case::scrollbar(ScrollIndicator::Scrollbar)]
+    #[case::footer(ScrollIndicator::Footer)]
+    fn menus_that_fit_show_no_scroll_indicator(#[case] indicator: ScrollIndicator) {
+        let (menu, _, _) = scrolled_menu(ScrollIndicator::None, 20);
+        let (with_indicator, _, _) = scrolled_menu(indicator, 20);
+
+        assert_eq!(
+            menu.menu_string(20, true),
+            with_indicator.menu_string(20, true)
+        );
+    }
+
+    #[rstest]
+    #[case::kept(true, ["cart", "cargo"])]
+    #[case::reset(false, ["cargo", "cargo"])]
+    fn typing_keeps_the_selected_value_selected(
+        #[case] keep_selection: bool,
+        #[case] selected: [&str; 2],
+    ) {
+        let mut completer = DefaultCompleter::new_with_wordlen(
+            vec!["cargo".into(), "cart".into(), "cat".into()],
+            2,
+        );
+        let mut menu = ColumnarMenu::default().with_keep_selection(keep_selection);
+        let mut editor = Editor::default();
+        editor.set_buffer("c".to_string(), UndoBehavior::CreateUndoPoint);
+        setup_menu(&mut menu, &mut editor, &mut completer, (80, 10));
+        let mut painter = Painter::new(BufWriter::new(std::io::stderr()));
+        painter.handle_resize(80, 10);
+        menu.menu_event(MenuEvent::NextElement);
+        menu.update_working_details(&mut editor, &mut completer, &painter);
+
+        let mut selected_after_typing = |buffer: &str| {
+            editor.set_buffer(buffer.to_string(), UndoBehavior::CreateUndoPoint);
+            menu.menu_event(MenuEvent::Edit(false));
+            menu.update_working_details(&mut editor, &mut completer, &painter);
+            menu.get_value().unwrap().value
+        };
+
+        assert_eq!(
+            [selected_after_typing("car"), selected_after_typing("carg")],
+            selected
+        );
+    }
+
+    /// Paths in a menu whose columns are at most 24 wide, cut in the middle
+    fn path_menu(terminal_width: u16) -> ColumnarMenu {
+        let mut completer = FakeCompleter::new(&[
+            "~/projects/reedline/src/menu/columnar_menu.rs",
+            "~/projects/reedline/src/menu/ide_menu.rs",
+            "~/projects/reedline/src/menu/list_menu.rs",
+            "~/projects/reedline/Cargo.toml",
+            "README.md",
+        ]);
+        let mut menu = ColumnarMenu::default()
+            .with_max_column_width(Some(24))
+            .with_ellipsis_position(EllipsisPosition::Middle);
+        setup_menu(
+            &mut menu,
+            &mut Editor::default(),
+            &mut completer,
+            (terminal_width, 10),
+        );
+        menu
+    }
+
+    #[rstest]
+    #[case::wide(
+        80,
+        &[
+            ">~/PROJECTS/…AR_MENU.RS ~/projects/…de_menu.rs  ~/projects/…st_menu.rs",
+            "~/projects/…Cargo.toml  README.md",
+        ]
+    )]
+    #[case::narrow(
+        40,
+        &[
+            ">~/PROJECTS/…AR_MENU.RS",
+            "~/projects/…de_menu.rs",
+            "~/projects/…st_menu.rs",
+            "~/projects/…Cargo.toml",
+            "README.md",
+            "",
+        ]
+    )]
+    fn long_paths_are_cut_in_the_middle(#[case] terminal_width: u16, #[case] expected: &[&str]) {
+        assert_eq!(menu_lines(&path_menu(terminal_width), 10), expected);
+    }
+
+    #[rstest]
+    #[case::unicode(
+        true,
+        &[
+            "╭────────────────────────╮",
+            "│ >ONE   two    three    │",
+            "│ four   five   six      │",
+            "╰────────────────────────╯",
+        ]
+    )]
+    #[case::ascii(
+        false,
+        &[
+            "+------------------------+",
+            "| >ONE   two    three    |",
+            "| four   five   six      |",
+            "+------------------------+",
+        ]
+    )]
+    fn bordered_menu(#[case] use_unicode: bool, #[case] expected: &[&str]) {
+        let mut completer = FakeCompleter::new(&["one", "two", "three", "four", "five", "six"]);
+        let mut menu = ColumnarMenu::default()
+            .with_menu_border(MenuBorder::default().with_use_unicode(use_unicode));
+        setup_menu(&mut menu, &mut Editor::default(), &mut completer, (26, 10));
+
+        assert_eq!(menu.menu_required_lines(26), 4);
+        assert_eq!(menu_lines(&menu, 10), expected);
+    }
 }
@@ -1,8 +1,9 @@
 use {
     super::MenuSettings,
     crate::{
-        menu_functions::{completer_input, replace_in_buffer},
-        Completer, Editor, Menu, MenuBuilder, MenuEvent, Painter, Suggestion,
+        menu_functions::{completer_input, next_index, previous_index, replace_in_buffer},
+        painting::line_width,
+        Completer, CompletionContext, Editor, Menu, MenuBuilder, MenuEvent, Painter, Suggestion,
     },
     nu_ansi_term::ansi::RESET,
 };
@@ -45,6 +46,8 @@ struct WorkingDetails {
     pub col_width: usize,
     /// Number of rows for description
     pub description_rows: usize,
+    /// Screen width the menu was laid out for
+    pub screen_width: u16,
 }
 
 /// Completion menu definition
@@ -71,6 +74,8 @@ pub struct DescriptionMenu {
     event: Option<MenuEvent>,
     /// String collected after the menu is activated
     input: Option<String>,
+    /// Whether the values were completed since the menu was activated
+    refining: bool,
     /// Examples to select
     examples: Vec<String>,
     /// Example index
@@ -97,6 +102,7 @@ impl Default for DescriptionMenu {
             row_pos: 0,
             event: None,
             input: None,
+            refining: false,
             examples: Vec::new(),
             example_index: None,
             show_examples: true,
@@ -149,6 +155,9 @@ impl DescriptionMenu {
 impl DescriptionMenu {
     /// Move menu cursor to the next element
     fn move_next(&mut self) {
+        if !self.settings.wrap && self.index() + 1 >= self.get_values().len() {
+            return;
+        }
         let mut new_col = self.col_pos + 1;
         let mut new_row = self.row_pos;
 
@@ -173,6 +182,9 @@ impl DescriptionMenu {
 
     /// Move menu cursor to the previous element
     fn move_previous(&mut self) {
+        if !self.settings.wrap && self.index() == 0 {
+            return;
+        }
         let new_col = self.col_pos.checked_sub(1);
 
         let (new_col, new_row) = match new_col {
@@ -346,12 +358,19 @@ impl DescriptionMenu {
             .join("\r\n");
 
         if use_ansi_coloring && !description.is_empty() {
-            format!(
-                "{}{}{}",
-                self.settings.color.description_style.prefix(),
-                description,
-                RESET,
-            )
+            // Each line is styled on its own, for a border to frame it
+            description
+                .split("\r\n")
+                .map(|line| {
+                    format!(
+                        "{}{}{}",
+                        self.settings.color.description_style.prefix(),
+                        line,
+                        RESET,
+                    )
+                })
+                .collect::<Vec<_>>()
+                .join("\r\n")
         } else {
             description
         }
@@ -389,7 +408,7 @@ impl DescriptionMenu {
             "".into()
         } else if use_ansi_coloring {
             format!(
-                "{}\r\n\r\nExamples:\r\n{}{}",
+                "\r\n\r\n{}Examples:{}\r\n{}",
                 self.settings.color.description_style.prefix(),
                 RESET,
                 examples,
@@ -398,6 +417,58 @@ impl DescriptionMenu {
             format!("\r\n\r\nExamples:\r\n{examples}",)
         }
     }
+
+    /// The values with the description and examples of the selected one
+    fn values_string(&self, use_ansi_coloring: bool) -> String {
+        if self.get_values().is_empty() {
+            self.no_records_msg(use_ansi_coloring)
+        } else {
+            // The skip values represent the number of lines that should be skipped
+            // while printing the menu
+            let available_lines = self.default_details.selection_rows;
+            let skip_values = if self.row_pos >= available_lines {
+                let skip_lines = self.row_pos.saturating_sub(available_lines) + 1;
+                (skip_lines * self.get_cols()) as usize
+            } else {
+                0
+            };
+
+            // It seems that crossterm prefers to have a complete string ready to be printed
+            // rather than looping through the values and printing multiple things
+            // This reduces the flickering when printing the menu
+            let available_values = (available_lines * self.get_cols()) as usize;
+            let selection_values: String = self
+                .get_values()
+                .iter()
+                .skip(skip_values)
+                .take(available_values)
+                .enumerate()
+                .map(|(index, suggestion)| {
+                    // Correcting the enumerate index based on the number of skipped values
+                    let index = index + skip_values;
+                    let column = index as u16 % self.get_cols();
+                    let empty_space = self
+                        .get_width()
+                        .saturating_sub(suggestion.display_value().len());
+
+                    self.create_entry_string(
+                        suggestion,
+                        index,
+                        column,
+                        empty_space,
+                        use_ansi_coloring,
+                    )
+                })
+                .collect();
+
+            format!(
+                "{}{}{}",
+                selection_values,
+                self.create_description_string(use_ansi_coloring),
+                self.create_example_string(use_ansi_coloring)
+            )
+        }
+    }
 }
 
 impl Menu for DescriptionMenu {
@@ -429,7 +500,10 @@ impl Menu for DescriptionMenu {
     /// Selects what type of event happened with the menu
     fn menu_event(&mut self, event: MenuEvent) {
         match &event {
-            MenuEvent::Activate(_) => self.active = true,
+            MenuEvent::Activate(_) => {
+                self.active = true;
+                self.refining = false;
+            }
             MenuEvent::Deactivate => {
                 self.active = false;
                 self.input = None;
@@ -453,7 +527,9 @@ impl Menu for DescriptionMenu {
             self.input.as_deref(),
             self.settings.only_buffer_difference,
         );
-        self.values = completer.complete(&input, pos);
+        self.values =
+            completer.complete_with_context(&CompletionContext::new(&input, pos, self.refining));
+        self.refining = true;
 
         self.reset_position();
     }
@@ -466,7 +542,14 @@ impl Menu for DescriptionMenu {
         completer: &mut dyn Completer,
         painter: &Painter,
     ) {
-        if let Some(event) = self.event.take() {
+        // Events and a resize lay the menu out again
+        let resized = self.working_details.screen_width != painter.screen_width();
+        self.working_details.screen_width = painter.screen_width();
+        let event = self.event.take();
+        if event.is_none() && !resized {
+            return;
+        }
+        if let Some(event) = event {
             match event {
                 MenuEvent::Activate(_) => {
                     self.reset_position();
@@ -490,23 +573,22 @@ impl Menu for DescriptionMenu {
                 }
                 MenuEvent::MoveUp => {
                     if let Some(example_index) = self.example_index {
-                        if let Some(index) = example_index.checked_sub(1) {
-                            self.example_index = Some(index);
-                        } else {
-                            self.example_index = Some(self.examples.len().saturating_sub(1));
-                        }
+                        self.example_index = Some(previous_index(
+                            example_index,
+                            self.examples.len(),
+                            self.settings.wrap,
+                        ));
                     } else if !self.examples.is_empty() {
                         self.example_index = Some(0);
                     }
                 }
                 MenuEvent::MoveDown => {
                     if let Some(example_index) = self.example_index {
-                        let index = example_index + 1;
-                        if index < self.examples.len() {
-                            self.example_index = Some(index);
-                        } else {
-                            self.example_index = Some(0);
-                        }
+                        self.example_index = Some(next_index(
+                            example_index,
+                            self.examples.len(),
+                            self.settings.wrap,
+                        ));
                     } else if !self.examples.is_empty() {
                         self.example_index = Some(0);
                     }
@@ -530,56 +612,64 @@ impl Menu for DescriptionMenu {
                         self.skipped_rows = allowed_skips;
                     }
                 }
-                MenuEvent::PreviousPage | MenuEvent::NextPage => {}
+                MenuEvent::PreviousPage
+                | MenuEvent::NextPage
+                | MenuEvent::Select(_)
+                | MenuEvent::Accept
+                | MenuEvent::Notice(_)
+                | MenuEvent::Refresh
+                | MenuEvent::ScrollDescriptionUp
+                | MenuEvent::ScrollDescriptionDown => {}
             }
+        }
 
-            let max_width = self
-                .get_values()
-                .iter()
-                .map(|suggestion| {
-                    suggestion.display_value().len() + self.default_details.col_padding
-                })
-                .max()
-                .unwrap_or(0);
-
-            // If no default width is found, then the total screen width is used to estimate
-            // the column width based on the default number of columns
-            let default_width = if let Some(col_width) = self.default_details.col_width {
-                col_width
-            } else {
-                let col_width = painter.screen_width() / self.default_details.columns;
-                col_width as usize
-            };
+        let max_width = self
+            .get_values()
+            .iter()
+            .map(|suggestion| suggestion.display_value().len() + self.default_details.col_padding)
+            .max()
+            .unwrap_or(0);
+
+        let screen_width = painter
+            .screen_width()
+            .saturating_sub(self.settings.border_width() as u16);
+
+        // If no default width is found, then the total screen width is used to estimate
+        // the column width based on the default number of columns
+        let default_width = if let Some(col_width) = self.default_details.col_width {
+            col_width
+        } else {
+            let col_width = screen_width / self.default_details.columns;
+            col_width as usize
+        };
 
-            // Adjusting the working width of the column based the max line width found
-            // in the menu values
-            if max_width > default_width {
-                self.working_details.col_width = max_width;
-            } else {
-                self.working_details.col_width = default_width;
-            };
+        // Adjusting the working width of the column based the max line width found
+        // in the menu values
+        if max_width > default_width {
+            self.working_details.col_width = max_width;
+        } else {
+            self.working_details.col_width = default_width;
+        };
 
-            // The working columns is adjusted based on possible number of columns
-            // that could be fitted in the screen with the calculated column width
-            let possible_cols = painter.screen_width() / self.working_details.col_width as u16;
-            if possible_cols > self.default_details.columns {
-                self.working_details.columns = self.default_details.columns.max(1);
-            } else {
-                self.working_details.columns = possible_cols;
-            }
+        // The working columns is adjusted based on possible number of columns
+        // that could be fitted in the screen with the calculated column width
+        let possible_cols = screen_width / (self.working_details.col_width as u16).max(1);
+        if possible_cols > self.default_details.columns {
+            self.working_details.columns = self.default_details.columns.max(1);
+        } else {
+            self.working_details.columns = possible_cols;
+        }
 
-            // Updating the working rows to display the description
-            if self.menu_required_lines(painter.screen_width()) <= painter.remaining_lines() {
-                self.working_details.description_rows = self.default_details.description_rows;
-                self.show_examples = true;
-            } else {
-                self.working_details.description_rows = painter
-                    .remaining_lines()
-                    .saturating_sub(self.default_details.selection_rows + 1)
-                    as usize;
+        // Updating the working rows to display the description
+        if self.menu_required_lines(painter.screen_width()) <= painter.remaining_lines() {
+            self.working_details.description_rows = self.default_details.description_rows;
+            self.show_examples = true;
+        } else {
+            self.working_details.description_rows = painter.remaining_lines().saturating_sub(
+                self.default_details.selection_rows + 1 + self.settings.border_rows(),
+            ) as usize;
 
-                self.show_examples = false;
-            }
+            self.show_examples = false;
         }
     }
 
@@ -617,56 +707,100 @@ impl Menu for DescriptionMenu {
             + self.default_details.description_rows as u16
             + example_lines as u16
             + 3
+            + self.settings.border_rows()
     }
 
     fn menu_string(&self, _available_lines: u16, use_ansi_coloring: bool) -> String {
-        if self.get_values().is_empty() {
-            self.no_records_msg(use_ansi_coloring)
-        } else {
-            // The skip values represent the number of lines that should be skipped
-            // while printing the menu
-            let available_lines = self.default_details.selection_rows;
-            let skip_values = if self.row_pos >= available_lines {
-                let skip_lines = self.row_pos.saturating_sub(available_lines) + 1;
-                (skip_lines * self.get_cols()) as usize
-            } else {
-                0
-            };
+        let menu = self.values_string(use_ansi_coloring);
+        match &self.settings.border {
+            Some(border) => {
+                let lines: Vec<String> = menu
+                    .strip_suffix("\r\n")
+                    .unwrap_or(&menu)
+                    .split("\r\n")
+                    .map(String::from)
+                    .collect();
+                let available_width = (self.working_details.screen_width as usize)
+                    .saturating_sub(self.settings.border_width());
+                let content_width = lines
+                    .iter()
+                    .map(|line| line_width(line))
+                    .max()
+                    .unwrap_or(0)
+                    .min(available_width);
+                border
+                    .frame(lines, content_width, use_ansi_coloring)
+                    .join("\r\n")
+            }
+            None => menu,
+        }
+    }
+}
 
-            // It seems that crossterm prefers to have a complete string ready to be printed
-            // rather than looping through the values and printing multiple things
-            // This reduces the flickering when printing the menu
-            let available_values = (available_lines * self.get_cols()) as usize;
-            let selection_values: String = self
-                .get_values()
-                .iter()
-                .skip(skip_values)
-                .take(available_values)
-                .enumerate()
-                .map(|(index, suggestion)| {
-                    // Correcting the enumerate index based on the number of skipped values
-                    let index = index + skip_values;
-                    let column = index as u16 % self.get_cols();
-                    let empty_space = self
-                        .get_width()
-                        .saturating_sub(suggestion.display_value().len());
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{MenuBorder, Span, UndoBehavior};
+    use pretty_assertions::assert_eq;
+
+    struct Described;
+
+    impl Completer for Described {
+        fn complete(&mut self, _line: &str, pos: usize) -> Vec<Suggestion> {
+            vec![Suggestion {
+                value: "ls".into(),
+                description: Some("List the files\nof a directory".into()),
+                span: Span::new(0, pos),
+                ..Default::default()
+            }]
+        }
+    }
 
-                    self.create_entry_string(
-                        suggestion,
-                        index,
-                        column,
-                        empty_space,
-                        use_ansi_coloring,
-                    )
-                })
-                .collect();
+    #[test]
+    fn bordered_menus_frame_the_values_and_description() {
+        let mut menu = DescriptionMenu::default()
+            .with_columns(1)
+            .with_menu_border(MenuBorder::default().with_use_unicode(false));
+        let mut editor = Editor::default();
+        editor.set_buffer("l".to_string(), UndoBehavior::CreateUndoPoint);
+        let mut painter = Painter::new(std::io::BufWriter::new(std::io::stderr()));
+        painter.handle_resize(24, 20);
+
+        menu.menu_event(MenuEvent::Activate(false));
+        menu.update_working_details(&mut editor, &mut Described, &painter);
+
+        assert_eq!(
+            menu.menu_string(20, false)
+                .split("\r\n")
+                .collect::<Vec<_>>(),
+            [
+                "+----------------------+",
+                "| >LS                  |",
+                "| List the files       |",
+                "| of a directory       |",
+                "+----------------------+",
+            ]
+        );
+        assert_eq!(
+            menu.menu_required_lines(24),
+            DescriptionMenu::default().menu_required_lines(24) + 2
+        );
+    }
 
-            format!(
-                "{}{}{}",
-                selection_values,
-                self.create_description_string(use_ansi_coloring),
-                self.create_example_string(use_ansi_coloring)
-            )
-        }
+    #[test]
+    fn resizing_lays_the_columns_out_again() {
+        let mut menu = DescriptionMenu::default();
+        let mut editor = Editor::default();
+        editor.set_buffer("l".to_string(), UndoBehavior::CreateUndoPoint);
+        let mut painter = Painter::new(std::io::BufWriter::new(std::io::stderr()));
+        painter.handle_resize(80, 20);
+        menu.menu_event(MenuEvent::Activate(false));
+        menu.update_working_details(&mut editor, &mut Described, &painter);
+        let col_width = menu.working_details.col_width;
+
+        painter.handle_resize(40, 20);
+        menu.update_working_details(&mut editor, &mut Described, &painter);
+
+        assert_eq!(menu.working_details.col_width, col_width / 2);
     }
 }
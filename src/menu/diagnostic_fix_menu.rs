@@ -4,37 +4,42 @@
 //! with a simple inline format: replacement text followed by title in parentheses.
 //! The menu is positioned below the text being replaced, aligned with the anchor column.
 
-use itertools::Itertools;
-use lsp_types::{CodeAction, TextEdit};
-use nu_ansi_term::{ansi::RESET, Style};
+use lsp_types::{
+    CodeAction, DocumentChangeOperation, DocumentChanges, OneOf, ResourceOp, TextDocumentEdit,
+    TextEdit,
+};
+use nu_ansi_term::Style;
 use serde_json::Value;
 use unicode_width::UnicodeWidthStr;
 
-use super::{Menu, MenuBuilder, MenuEvent, MenuSettings};
+use super::{
+    menu_functions::{next_index, previous_index},
+    Menu, MenuBuilder, MenuEvent, MenuSettings,
+};
 use crate::Highlighter;
 use crate::{
     core_editor::Editor,
-    lsp::{range_to_span, LspCommandSender, Span},
-    painting::Painter,
+    lsp::{range_to_span, LspCommandSender, PopupRenderer, Span, DIAGNOSTIC_FIX_MENU},
+    painting::{line_width, Painter, StyledText},
     Completer, Suggestion, UndoBehavior,
 };
 
 // Necessary because of indicator text of two characters `> ` to the left of selected menu item
 const LEFT_PADDING: u16 = 2;
 
-/// A single text edit with span, replacement, and original text.
+/// A single text edit with span, replacement, and the text it replaces.
 #[derive(Debug, Clone)]
 pub struct TextEditInfo {
     /// Byte span in the buffer
     pub span: Span,
     /// Replacement text (empty for deletions) - raw text for buffer operations
     pub replacement: String,
-    /// Pre-highlighted ANSI string for display (may include syntax highlighting)
-    pub replacement_styled: String,
-    /// Original text at this span - raw text
+    /// Text the edit replaces
     pub original: String,
-    /// Pre-highlighted ANSI string with strikethrough for deletions
-    pub original_styled: String,
+    /// Pre-highlighted replacement for display (may include syntax highlighting)
+    pub replacement_styled: StyledText,
+    /// Pre-highlighted original text with strikethrough for deletions
+    pub original_styled: StyledText,
 }
 
 /// The action to perform for a fix.
@@ -47,6 +52,11 @@ pub enum FixAction {
         command: String,
         arguments: Vec<Value>,
     },
+    /// Action whose edits the server only sends once asked with
+    /// `codeAction/resolve`, when the fix is accepted
+    Resolve,
+    /// Action that can't be applied to the line, kept to say why
+    Unsupported,
 }
 
 /// Pre-computed fix with byte offsets for buffer manipulation.
@@ -56,6 +66,19 @@ struct FixInfo {
     title: String,
     /// The action to perform
     action: FixAction,
+    /// Data the server attached to the action, sent back when resolving it
+    data: Option<Value>,
+}
+
+impl FixInfo {
+    /// The code action to resolve, as far as the server needs it back
+    fn code_action(&self) -> CodeAction {
+        CodeAction {
+            title: self.title.clone(),
+            data: self.data.clone(),
+            ..Default::default()
+        }
+    }
 }
 
 /// Working details calculated during layout
@@ -65,6 +88,10 @@ struct WorkingDetails {
     space_left: u16,
     /// Cursor column from set_cursor_pos (includes prompt width)
     cursor_col: u16,
+    /// Fixes shown at once, the max height clamped to the terminal
+    visible_items: u16,
+    /// Width of the terminal
+    screen_width: u16,
 }
 
 /// Menu for displaying and applying diagnostic fixes.
@@ -79,6 +106,9 @@ pub struct DiagnosticFixMenu {
     fixes: Vec<FixInfo>,
     /// Selected index
     selected: usize,
+    /// Index of the fix the server marked as preferred, selected when the
+    /// menu opens, the first fix if there is none
+    preferred: usize,
     /// Number of values to skip for scrolling
     skip_values: usize,
     /// Working details calculated during layout
@@ -89,20 +119,27 @@ pub struct DiagnosticFixMenu {
     anchor_col: u16,
     /// Command sender for executing LSP commands
     command_sender: Option<LspCommandSender>,
+    /// URI of the document the fixes are for
+    document_uri: Option<String>,
+    /// Content the fixes were computed for
+    content: String,
 }
 
 impl Default for DiagnosticFixMenu {
     fn default() -> Self {
         Self {
-            settings: MenuSettings::default().with_name("diagnostic_fix_menu"),
+            settings: MenuSettings::default().with_name(DIAGNOSTIC_FIX_MENU),
             active: false,
             fixes: Vec::new(),
             selected: 0,
+            preferred: 0,
             skip_values: 0,
             working_details: WorkingDetails::default(),
             max_height: 10,
             anchor_col: 0,
             command_sender: None,
+            document_uri: None,
+            content: String::new(),
         }
     }
 }
@@ -114,6 +151,24 @@ impl MenuBuilder for DiagnosticFixMenu {
 }
 
 impl DiagnosticFixMenu {
+    /// Menu builder with the maximum number of fixes shown at once
+    #[must_use]
+    pub fn with_max_height(mut self, max_height: u16) -> Self {
+        self.max_height = max_height.max(1);
+        self
+    }
+
+    /// Only apply the edits to the document at `uri`, leaving out those to
+    /// other files.
+    ///
+    /// Without it, only workspace edits changing a single document are
+    /// applied.
+    #[must_use]
+    pub fn with_document_uri(mut self, uri: impl Into<String>) -> Self {
+        self.document_uri = Some(uri.into());
+        self
+    }
+
     /// Update the available fixes from LSP code actions.
     ///
     /// Converts LSP ranges to byte offsets using the provided content.
@@ -121,6 +176,13 @@ impl DiagnosticFixMenu {
     ///
     /// When a highlighter is provided, replacement and original text are pre-highlighted
     /// at setup time, avoiding repeated highlighting work on each render pass.
+    ///
+    /// Actions that create, rename or delete files can't be applied to the line.
+    /// They are listed with the reason in their title, and accepting them does
+    /// nothing.
+    ///
+    /// The first action the server marks as `isPreferred` is selected when the
+    /// menu opens, so accepting it takes a single keypress.
     pub fn set_fixes(
         &mut self,
         actions: Vec<CodeAction>,
@@ -128,74 +190,85 @@ impl DiagnosticFixMenu {
         anchor_col: u16,
         highlighter: Option<&dyn Highlighter>,
     ) {
-        self.fixes = actions
+        let fixes: Vec<(bool, FixInfo)> = actions
             .into_iter()
             .filter_map(|action| {
-                // Try edit-based action first
-                if let Some(edits) = extract_text_edits(&action) {
-                    let edits: Vec<TextEditInfo> = edits
-                        .into_iter()
-                        .map(|edit| {
-                            let span = range_to_span(content, &edit.range);
-                            let original =
-                                content.get(span.start..span.end).unwrap_or("").to_string();
-                            let replacement = edit.new_text;
-
-                            // Pre-highlight the replacement text
-                            let replacement_styled = if let Some(h) = highlighter {
-                                h.highlight(&replacement, replacement.len()).render_simple()
-                            } else {
-                                replacement.clone()
-                            };
-
-                            // Pre-highlight the original text with strikethrough for deletions
-                            let original_styled = if let Some(h) = highlighter {
-                                let mut styled = h.highlight(&original, original.len());
-                                styled.transform_style_range(0, original.len(), |s| {
-                                    s.strikethrough()
-                                });
-                                styled.render_simple()
-                            } else {
-                                let style = Style::new().strikethrough();
-                                format!("{}{}{}", style.prefix(), original, style.suffix())
-                            };
-
-                            TextEditInfo {
-                                span,
-                                replacement,
-                                replacement_styled,
-                                original,
-                                original_styled,
-                            }
-                        })
-                        .collect();
-
-                    if !edits.is_empty() {
-                        return Some(FixInfo {
-                            title: action.title,
-                            action: FixAction::TextEdits(edits),
-                        });
-                    }
-                }
-
-                // Fall back to command-based action
-                if let Some(cmd) = action.command {
-                    return Some(FixInfo {
-                        title: action.title,
-                        action: FixAction::Command {
-                            command: cmd.command,
-                            arguments: cmd.arguments.unwrap_or_default(),
-                        },
-                    });
-                }
-
-                None
+                let is_preferred = action.is_preferred == Some(true);
+                self.fix_info(action, content, highlighter)
+                    .map(|fix| (is_preferred, fix))
             })
             .collect();
+        self.preferred = fixes
+            .iter()
+            .position(|(is_preferred, fix)| {
+                *is_preferred && !matches!(fix.action, FixAction::Unsupported)
+            })
+            .unwrap_or(0);
+        self.fixes = fixes.into_iter().map(|(_, fix)| fix).collect();
 
-        self.selected = 0;
+        self.selected = self.preferred;
         self.skip_values = 0;
         self.anchor_col = anchor_col;
+        self.content = content.to_string();
+    }
+
+    /// The fix to list for `action`, `None` if it does nothing
+    fn fix_info(
+        &self,
+        action: CodeAction,
+        content: &str,
+        highlighter: Option<&dyn Highlighter>,
+    ) -> Option<FixInfo> {
+        if let Some(operation) = resource_operation(&action) {
+            return Some(FixInfo {
+                title: format!(
+                    "{} (not supported here: {})",
+                    action.title,
+                    operation_description(operation)
+                ),
+                action: FixAction::Unsupported,
+                data: action.data,
+            });
+        }
+
+        // Try edit-based action first
+        if let Some(edits) = extract_text_edits(&action, self.document_uri.as_deref()) {
+            let edits: Vec<TextEditInfo> = edits
+                .into_iter()
+                .map(|edit| text_edit_info(content, edit, highlighter))
+                .collect();
+
+            if !edits.is_empty() {
+                return Some(FixInfo {
+                    title: action.title,
+                    action: FixAction::TextEdits(edits),
+                    data: action.data,
+                });
+            }
+        }
+
+        // Fall back to command-based action
+        if let Some(cmd) = action.command {
+            return Some(FixInfo {
+                title: action.title,
+                action: FixAction::Command {
+                    command: cmd.command,
+                    arguments: cmd.arguments.unwrap_or_default(),
+                },
+                data: action.data,
+            });
+        }
+
+        // Edits left out for `codeAction/resolve`
+        if action.data.is_some() && action.edit.is_none() {
+            return Some(FixInfo {
+                title: action.title,
+                action: FixAction::Resolve,
+                data: action.data,
+            });
+        }
+
+        None
     }
 
     /// Check if there are any fixes available.
@@ -208,88 +281,109 @@ impl DiagnosticFixMenu {
         self.command_sender = Some(sender);
     }
 
+    /// Show that `fix` could not be applied below the prompt.
+    fn report_failure(&self, fix: &FixInfo) {
+        if let Some(sender) = &self.command_sender {
+            sender.report_failure(fix.title.clone());
+        }
+    }
+
     /// Get the currently selected fix.
     fn get_selected_fix(&self) -> Option<&FixInfo> {
         self.fixes.get(self.selected)
     }
 
     /// Format a single fix line using pre-computed styled text.
-    fn format_fix_line(&self, fix: &FixInfo, index: usize, use_ansi_coloring: bool) -> String {
-        let is_selected = index == self.selected;
-        let indicator = if is_selected { "> " } else { "  " };
-
-        let title_style = if use_ansi_coloring {
-            Style::new().italic()
-        } else {
-            Style::new()
-        };
+    fn fix_line(&self, fix: &FixInfo, index: usize) -> StyledText {
+        let indicator = if index == self.selected { "> " } else { "  " };
+        let title_style = Style::new().italic();
 
+        let mut line = StyledText::new();
+        line.push((Style::new(), indicator.to_string()));
         match &fix.action {
-            FixAction::TextEdits(edits) => {
-                // "Fix all" type actions: multiple edits, show title only
-                if edits.len() > 1 {
-                    return format!("{indicator}{}{}{RESET}", title_style.prefix(), fix.title,);
-                }
-
-                let first_edit = edits.first();
-                let replacement_text = first_edit.map_or("", |e| e.replacement.as_str());
-
-                if replacement_text.is_empty() {
-                    // Deletion: show original text with strikethrough (pre-computed)
-                    let styled_original = if use_ansi_coloring {
-                        first_edit.map_or(String::new(), |e| e.original_styled.clone())
-                    } else {
-                        first_edit.map_or(String::new(), |e| e.original.clone())
-                    };
-
-                    format!(
-                        "{indicator}{styled_original} {}({}){RESET}",
-                        title_style.prefix(),
-                        fix.title,
-                    )
+            FixAction::TextEdits(edits) if edits.len() == 1 => {
+                // Deletions show the original text struck through, replacements
+                // the new text, both pre-highlighted
+                let edit = &edits[0];
+                line.append(if edit.replacement.is_empty() {
+                    edit.original_styled.clone()
                 } else {
-                    // Replacement: show new text (pre-computed with syntax highlighting)
-                    let styled_replacement = if use_ansi_coloring {
-                        first_edit.map_or(String::new(), |e| e.replacement_styled.clone())
-                    } else {
-                        first_edit.map_or(String::new(), |e| e.replacement.clone())
-                    };
-
-                    format!(
-                        "{indicator}{styled_replacement} {}({}){RESET}",
-                        title_style.prefix(),
-                        fix.title,
-                    )
-                }
+                    edit.replacement_styled.clone()
+                });
+                line.push((Style::new(), " ".to_string()));
+                line.push((title_style, format!("({})", fix.title)));
+            }
+            // "Fix all" type actions with several edits and command-only
+            // actions: show the title only
+            FixAction::TextEdits(_) | FixAction::Command { .. } | FixAction::Resolve => {
+                line.push((title_style, fix.title.clone()));
             }
-            FixAction::Command { .. } => {
-                // Command-only: show title without parentheses
-                format!("{indicator}{}{}{RESET}", title_style.prefix(), fix.title,)
+            FixAction::Unsupported => {
+                line.push((title_style.dimmed(), fix.title.clone()));
             }
         }
+        line
     }
 
-    /// Move selection forward, wrapping around
+    /// Move selection forward, wrapping around unless disabled
     fn select_next(&mut self) {
         if self.fixes.is_empty() {
             return;
         }
-        self.selected = (self.selected + 1) % self.fixes.len();
+        self.selected = next_index(self.selected, self.fixes.len(), self.settings.wrap);
         self.adjust_scroll_forward();
     }
 
-    /// Move selection backward, wrapping around
+    /// Move selection backward, wrapping around unless disabled
     fn select_previous(&mut self) {
         if self.fixes.is_empty() {
             return;
         }
-        self.selected = self.selected.checked_sub(1).unwrap_or(self.fixes.len() - 1);
+        self.selected = previous_index(self.selected, self.fixes.len(), self.settings.wrap);
+        self.adjust_scroll_forward();
+    }
+
+    /// Move selection and view a page forward, stopping at the last fix
+    fn select_next_page(&mut self) {
+        if self.fixes.is_empty() {
+            return;
+        }
+        let page = self.page_size();
+        self.selected = (self.selected + page).min(self.fixes.len() - 1);
+        self.skip_values = (self.skip_values + page).min(self.fixes.len().saturating_sub(page));
+        self.adjust_scroll_forward();
+    }
+
+    /// Move selection and view a page backward, stopping at the first fix
+    fn select_previous_page(&mut self) {
+        let page = self.page_size();
+        self.selected = self.selected.saturating_sub(page);
+        self.skip_values = self.skip_values.saturating_sub(page);
         self.adjust_scroll_backward();
     }
 
+    /// Select the fix at `index`, scrolling it into view
+    fn select(&mut self, index: usize) {
+        if index < self.fixes.len() {
+            self.selected = index;
+            self.adjust_scroll_forward();
+        }
+    }
+
+    /// Number of fixes shown at once.
+    ///
+    /// Before the first layout the terminal height is unknown, so this is
+    /// the max height.
+    fn page_size(&self) -> usize {
+        match self.working_details.visible_items {
+            0 => self.max_height as usize,
+            visible_items => visible_items as usize,
+        }
+    }
+
     /// Adjust scroll position when moving forward
     fn adjust_scroll_forward(&mut self) {
-        let visible_items = self.max_height as usize;
+        let visible_items = self.page_size();
         if self.selected >= self.skip_values + visible_items {
             self.skip_values = self.selected.saturating_sub(visible_items - 1);
         } else if self.selected < self.skip_values {
@@ -305,16 +399,161 @@ impl DiagnosticFixMenu {
     }
 }
 
-/// Extract text edits from a code action's workspace edit.
-fn extract_text_edits(action: &CodeAction) -> Option<Vec<TextEdit>> {
-    action
-        .edit
-        .as_ref()?
-        .changes
-        .as_ref()?
-        .values()
-        .next()
-        .cloned()
+/// Byte span and pre-highlighted text of an edit to `content`.
+///
+/// When a highlighter is provided, replacement and original text are
+/// highlighted, the original struck through for deletions.
+pub(crate) fn text_edit_info(
+    content: &str,
+    edit: TextEdit,
+    highlighter: Option<&dyn Highlighter>,
+) -> TextEditInfo {
+    let span = range_to_span(content, &edit.range);
+    let original = content.get(span.start..span.end).unwrap_or("").to_string();
+    let replacement = edit.new_text;
+
+    // Pre-highlight the replacement text
+    let replacement_styled = if let Some(h) = highlighter {
+        h.highlight(&replacement, replacement.len())
+    } else {
+        let mut styled = StyledText::new();
+        styled.push((Style::new(), replacement.clone()));
+        styled
+    };
+
+    // Pre-highlight the original text with strikethrough for deletions
+    let original_styled = if let Some(h) = highlighter {
+        let mut styled = h.highlight(&original, original.len());
+        styled.transform_style_range(0, original.len(), |s| s.strikethrough());
+        styled
+    } else {
+        let mut styled = StyledText::new();
+        styled.push((Style::new().strikethrough(), original.clone()));
+        styled
+    };
+
+    TextEditInfo {
+        span,
+        replacement,
+        original,
+        replacement_styled,
+        original_styled,
+    }
+}
+
+/// The first file operation in a code action's workspace edit, if any.
+pub(crate) fn resource_operation(action: &CodeAction) -> Option<&ResourceOp> {
+    match action.edit.as_ref()?.document_changes.as_ref()? {
+        DocumentChanges::Operations(operations) => {
+            operations.iter().find_map(|operation| match operation {
+                DocumentChangeOperation::Op(operation) => Some(operation),
+                DocumentChangeOperation::Edit(_) => None,
+            })
+        }
+        DocumentChanges::Edits(_) => None,
+    }
+}
+
+/// What a file operation does, to explain why its action isn't applied.
+fn operation_description(operation: &ResourceOp) -> &'static str {
+    match operation {
+        ResourceOp::Create(_) => "creates a file",
+        ResourceOp::Rename(_) => "renames a file",
+        ResourceOp::Delete(_) => "deletes a file",
+    }
+}
+
+/// Extract the text edits to the document at `uri` from a code action's
+/// workspace edit.
+///
+/// Like the spec asks, `documentChanges` are used over `changes` when a
+/// server sends both. Without a `uri`, only the edits of a workspace edit
+/// changing a single document are taken.
+pub(crate) fn extract_text_edits(action: &CodeAction, uri: Option<&str>) -> Option<Vec<TextEdit>> {
+    let edit = action.edit.as_ref()?;
+    let mut documents: Vec<(&str, Vec<TextEdit>)> = match &edit.document_changes {
+        Some(document_changes) => {
+            let mut documents: Vec<(&str, Vec<TextEdit>)> = Vec::new();
+            for document_edit in text_document_edits(document_changes) {
+                let changed = document_edit.text_document.uri.as_str();
+                let edits = document_edit.edits.iter().map(|edit| match edit {
+                    OneOf::Left(edit) => edit.clone(),
+                    OneOf::Right(annotated) => annotated.text_edit.clone(),
+                });
+                match documents.iter_mut().find(|(seen, _)| *seen == changed) {
+                    Some((_, document)) => document.extend(edits),
+                    None => documents.push((changed, edits.collect())),
+                }
+            }
+            documents
+        }
+        None => edit
+            .changes
+            .as_ref()?
+            .iter()
+            .map(|(changed, edits)| (changed.as_str(), edits.clone()))
+            .collect(),
+    };
+    match uri {
+        Some(uri) => documents
+            .into_iter()
+            .find(|(changed, _)| *changed == uri)
+            .map(|(_, edits)| edits),
+        None if documents.len() == 1 => documents.pop().map(|(_, edits)| edits),
+        None => None,
+    }
+}
+
+/// The text document edits of `document_changes`, leaving out file operations.
+fn text_document_edits(document_changes: &DocumentChanges) -> Vec<&TextDocumentEdit> {
+    match document_changes {
+        DocumentChanges::Edits(edits) => edits.iter().collect(),
+        DocumentChanges::Operations(operations) => operations
+            .iter()
+            .filter_map(|operation| match operation {
+                DocumentChangeOperation::Edit(edit) => Some(edit),
+                DocumentChangeOperation::Op(_) => None,
+            })
+            .collect(),
+    }
+}
+
+/// Apply `edits` to the buffer, leaving the cursor after the first one.
+///
+/// Returns `false` and leaves the buffer alone if a span doesn't fit the
+/// buffer, e.g. because it changed since the edits were computed.
+fn apply_edits(editor: &mut Editor, edits: &[TextEditInfo]) -> bool {
+    // Sort edits by start position descending to apply from end to start
+    let mut edits = edits.to_vec();
+    edits.sort_by_key(|e| std::cmp::Reverse(e.span.start));
+
+    let mut line_buffer = editor.line_buffer().clone();
+
+    let new_buffer =
+        edits
+            .iter()
+            .try_fold(line_buffer.get_buffer().to_string(), |mut buf, edit| {
+                let Span { start, end } = edit.span;
+                if start > end || !buf.is_char_boundary(start) || !buf.is_char_boundary(end) {
+                    return None;
+                }
+                buf.replace_range(start..end, &edit.replacement);
+                Some(buf)
+            });
+    let Some(new_buffer) = new_buffer else {
+        return false;
+    };
+
+    // Place cursor at end of first edit
+    let cursor_pos = edits
+        .last() // After sorting descending, last is first original edit
+        .map(|edit| edit.span.start + edit.replacement.len())
+        .unwrap_or_else(|| line_buffer.insertion_point());
+
+    line_buffer.set_buffer(new_buffer);
+    line_buffer.set_insertion_point(cursor_pos.min(line_buffer.get_buffer().len()));
+    editor.set_line_buffer(line_buffer, UndoBehavior::CreateUndoPoint);
+    true
 }
 
 impl Menu for DiagnosticFixMenu {
@@ -343,14 +582,18 @@ impl Menu for DiagnosticFixMenu {
         match event {
             MenuEvent::Activate(_) => {
                 self.active = true;
-                self.selected = 0;
+                self.selected = self.preferred;
                 self.skip_values = 0;
+                self.adjust_scroll_forward();
             }
             MenuEvent::Deactivate => self.active = false,
             // Handle both NextElement (Tab) and MoveDown (arrow key)
             MenuEvent::NextElement | MenuEvent::MoveDown => self.select_next(),
             // Handle both PreviousElement (Shift+Tab) and MoveUp (arrow key)
             MenuEvent::PreviousElement | MenuEvent::MoveUp => self.select_previous(),
+            MenuEvent::NextPage => self.select_next_page(),
+            MenuEvent::PreviousPage => self.select_previous_page(),
+            MenuEvent::Select(index) => self.select(index),
             _ => {}
         }
     }
@@ -363,8 +606,20 @@ impl Menu for DiagnosticFixMenu {
         &mut self,
         editor: &mut Editor,
         _completer: &mut dyn Completer,
-        _painter: &Painter,
+        painter: &Painter,
     ) {
+        // Keep the line being edited on screen
+        self.working_details.visible_items = self
+            .max_height
+            .min(
+                painter
+                    .screen_height()
+                    .saturating_sub(1 + self.settings.border_rows()),
+            )
+            .max(1);
+        self.adjust_scroll_forward();
+        self.working_details.screen_width = painter.screen_width();
+
         // Calculate menu position: prompt_width + anchor_col
         // cursor_col = prompt_width + text_before_cursor_width (mod terminal width)
         // So: prompt_width = cursor_col - text_before_cursor_width
@@ -389,39 +644,43 @@ impl Menu for DiagnosticFixMenu {
 
         match &fix.action {
             FixAction::TextEdits(edits) => {
-                // Sort edits by start position descending to apply from end to start
-                let mut edits = edits.clone();
-                edits.sort_by_key(|e| std::cmp::Reverse(e.span.start));
-
-                let mut line_buffer = editor.line_buffer().clone();
-
-                // Apply all edits using fold
-                let new_buffer =
-                    edits
-                        .iter()
-                        .fold(line_buffer.get_buffer().to_string(), |mut buf, edit| {
-                            let start = edit.span.start.min(buf.len());
-                            let end = edit.span.end.min(buf.len());
-                            buf.replace_range(start..end, &edit.replacement);
-                            buf
-                        });
-
-                // Place cursor at end of first edit
-                let cursor_pos = edits
-                    .last() // After sorting descending, last is first original edit
-                    .map(|edit| edit.span.start + edit.replacement.len())
-                    .unwrap_or_else(|| line_buffer.insertion_point());
-
-                line_buffer.set_buffer(new_buffer);
-                line_buffer.set_insertion_point(cursor_pos.min(line_buffer.get_buffer().len()));
-                editor.set_line_buffer(line_buffer, UndoBehavior::CreateUndoPoint);
+                // Spans computed for other content would land on the wrong text
+                if editor.get_buffer() != self.content || !apply_edits(editor, edits) {
+                    self.report_failure(fix);
+                }
+            }
+            FixAction::Resolve => {
+                let resolved = self
+                    .command_sender
+                    .as_ref()
+                    .and_then(|sender| sender.resolve_code_action(fix.code_action()));
+                let applied = resolved
+                    .as_ref()
+                    .and_then(|action| extract_text_edits(action, self.document_uri.as_deref()))
+                    .map_or(false, |edits| {
+                        let content = editor.get_buffer().to_string();
+                        let edits: Vec<TextEditInfo> = edits
+                            .into_iter()
+                            .map(|edit| text_edit_info(&content, edit, None))
+                            .collect();
+                        apply_edits(editor, &edits)
+                    });
+                if !applied {
+                    self.report_failure(fix);
+                }
             }
             FixAction::Command { command, arguments } => {
-                // Execute the command via the LSP provider
+                // Execute the command via the LSP provider, showing progress until it answers
                 if let Some(sender) = &self.command_sender {
-                    sender.execute_command(command.clone(), arguments.clone());
+                    sender.execute_titled_command(
+                        fix.title.clone(),
+                        command.clone(),
+                        arguments.clone(),
+                    );
                 }
             }
+            // The title already says why it isn't applied
+            FixAction::Unsupported => {}
         }
     }
 
@@ -435,7 +694,7 @@ impl Menu for DiagnosticFixMenu {
     }
 
     fn menu_required_lines(&self, _terminal_columns: u16) -> u16 {
-        (self.fixes.len() as u16).min(self.max_height)
+        (self.fixes.len()).min(self.page_size()) as u16 + self.settings.border_rows()
     }
 
     fn menu_string(&self, available_lines: u16, use_ansi_coloring: bool) -> String {
@@ -453,24 +712,418 @@ impl Menu for DiagnosticFixMenu {
             return String::from("No fixes available");
         }
 
-        let visible_count = (available_lines.min(self.max_height)) as usize;
-        let left_padding = " ".repeat(self.working_details.space_left as usize);
+        let available_lines = available_lines.saturating_sub(self.settings.border_rows());
+        let visible_count = (available_lines as usize).max(1).min(self.page_size());
 
-        self.fixes
+        let mut lines: Vec<String> = self
+            .fixes
             .iter()
             .enumerate()
             .skip(self.skip_values)
             .take(visible_count)
-            .map(|(idx, fix)| {
-                format!(
-                    "{left_padding}{}",
-                    self.format_fix_line(fix, idx, use_ansi_coloring)
-                )
-            })
-            .join("\r\n")
+            .map(|(idx, fix)| self.fix_line(fix, idx).render(use_ansi_coloring))
+            .collect();
+        if let Some(border) = &self.settings.border {
+            let content_width = lines.iter().map(|line| line_width(line)).max();
+            lines = border.frame(lines, content_width.unwrap_or(0), use_ansi_coloring);
+        }
+
+        // Each fix stays on a row of its own, for the mouse to find it
+        PopupRenderer::new(
+            self.working_details.space_left as usize,
+            self.working_details.screen_width as usize,
+            use_ansi_coloring,
+        )
+        .with_wrap(false)
+        .render(&lines)
+        .join("\r\n")
     }
 
     fn set_cursor_pos(&mut self, pos: (u16, u16)) {
         self.working_details.cursor_col = pos.0;
     }
+
+    /// Clicking a fix selects it, clicking the selected fix applies it
+    fn handle_mouse(&self, row: u16, _column: u16) -> Option<MenuEvent> {
+        // The top of the border is above the first fix
+        let row = row.checked_sub(self.settings.border_rows() / 2)?;
+        if row as usize >= self.page_size() {
+            return None;
+        }
+        let index = self.skip_values + row as usize;
+        if index >= self.fixes.len() {
+            None
+        } else if index == self.selected {
+            Some(MenuEvent::Accept)
+        } else {
+            Some(MenuEvent::Select(index))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lsp::{LspConfig, LspDiagnosticsProvider};
+    use crate::{DefaultCompleter, MenuBorder};
+    use nu_ansi_term::Color;
+    use pretty_assertions::assert_eq;
+    use std::io::BufWriter;
+
+    fn action(value: serde_json::Value) -> CodeAction {
+        serde_json::from_value(value).unwrap()
+    }
+
+    #[test]
+    fn actions_with_file_operations_are_marked_unsupported() {
+        let rename = action(serde_json::json!({
+            "title": "Rename to ls",
+            "edit": { "changes": { "repl:/session/repl": [{
+                "range": {
+                    "start": { "line": 0, "character": 0 },
+                    "end": { "line": 0, "character": 2 },
+                },
+                "newText": "ls",
+            }]}},
+        }));
+        let extract = action(serde_json::json!({
+            "title": "Extract to module",
+            "edit": { "documentChanges": [
+                { "kind": "create", "uri": "file:///tmp/module.nu" },
+                {
+                    "textDocument": { "uri": "repl:/session/repl", "version": null },
+                    "edits": [{
+                        "range": {
+                            "start": { "line": 0, "character": 0 },
+                            "end": { "line": 0, "character": 2 },
+                        },
+                        "newText": "use module.nu",
+                    }],
+                },
+            ]},
+        }));
+        let mut menu = DiagnosticFixMenu::default();
+        menu.set_fixes(vec![rename, extract], "sl", 0, None);
+
+        let titles: Vec<_> = menu.fixes.iter().map(|fix| fix.title.as_str()).collect();
+        assert_eq!(
+            titles,
+            [
+                "Rename to ls",
+                "Extract to module (not supported here: creates a file)"
+            ]
+        );
+        assert!(matches!(menu.fixes[1].action, FixAction::Unsupported));
+
+        let mut editor = Editor::default();
+        editor.set_buffer("sl".to_string(), UndoBehavior::CreateUndoPoint);
+        menu.select(1);
+        menu.replace_in_buffer(&mut editor);
+        assert_eq!(editor.get_buffer(), "sl");
+    }
+
+    #[test]
+    fn actions_to_resolve_keep_their_data() {
+        let lazy = action(serde_json::json!({
+            "title": "Remove unused variable",
+            "data": { "id": 7, "file": "repl:/session/repl" },
+        }));
+        let without_data = action(serde_json::json!({ "title": "Nothing to do" }));
+        let mut menu = DiagnosticFixMenu::default();
+        menu.set_fixes(vec![lazy, without_data], "let x = 1", 0, None);
+
+        assert_eq!(menu.fixes.len(), 1);
+        assert!(matches!(menu.fixes[0].action, FixAction::Resolve));
+        let sent_back = menu.fixes[0].code_action();
+        assert_eq!(sent_back.title, "Remove unused variable");
+        assert_eq!(
+            sent_back.data,
+            Some(serde_json::json!({ "id": 7, "file": "repl:/session/repl" }))
+        );
+
+        // Without a server to resolve it, accepting leaves the line alone
+        let mut editor = Editor::default();
+        editor.set_buffer("let x = 1".to_string(), UndoBehavior::CreateUndoPoint);
+        menu.replace_in_buffer(&mut editor);
+        assert_eq!(editor.get_buffer(), "let x = 1");
+    }
+
+    #[test]
+    fn fixes_after_emoji_keep_the_buffer_intact() {
+        let rename = action(serde_json::json!({
+            "title": "Rename to length",
+            "edit": { "changes": { "repl:/session/repl": [{
+                // 😀 is 4 bytes but 2 UTF-16 code units
+                "range": {
+                    "start": { "line": 0, "character": 10 },
+                    "end": { "line": 0, "character": 16 },
+                },
+                "newText": "length",
+            }]}},
+        }));
+        let content = "echo 😀 | lenght";
+        let mut menu = DiagnosticFixMenu::default();
+        menu.set_fixes(vec![rename], content, 0, None);
+
+        let mut editor = Editor::default();
+        editor.set_buffer(content.to_string(), UndoBehavior::CreateUndoPoint);
+        menu.replace_in_buffer(&mut editor);
+
+        assert_eq!(editor.get_buffer(), "echo 😀 | length");
+    }
+
+    #[test]
+    fn only_the_edits_to_our_document_are_applied() {
+        let edit = |new_text: &str| {
+            serde_json::json!([{
+                "range": {
+                    "start": { "line": 0, "character": 0 },
+                    "end": { "line": 0, "character": 2 },
+                },
+                "newText": new_text,
+            }])
+        };
+        let rename = action(serde_json::json!({
+            "title": "Rename everywhere",
+            "edit": { "changes": {
+                "file:///tmp/aaa.nu": edit("other"),
+                "repl:/session/repl": edit("ls"),
+                "file:///tmp/zzz.nu": edit("other"),
+            }},
+        }));
+        let elsewhere = action(serde_json::json!({
+            "title": "Fix another file",
+            "edit": { "changes": { "file:///tmp/aaa.nu": edit("other") }},
+        }));
+
+        let mut menu = DiagnosticFixMenu::default().with_document_uri("repl:/session/repl");
+        menu.set_fixes(vec![rename.clone(), elsewhere.clone()], "sl", 0, None);
+        assert_eq!(menu.fixes.len(), 1);
+        let mut editor = Editor::default();
+        editor.set_buffer("sl".to_string(), UndoBehavior::CreateUndoPoint);
+        menu.replace_in_buffer(&mut editor);
+        assert_eq!(editor.get_buffer(), "ls");
+
+        // Without knowing our document, edits to several files are left out
+        let mut menu = DiagnosticFixMenu::default();
+        menu.set_fixes(vec![rename, elsewhere], "sl", 0, None);
+        let titles: Vec<_> = menu.fixes.iter().map(|fix| fix.title.as_str()).collect();
+        assert_eq!(titles, ["Fix another file"]);
+    }
+
+    #[test]
+    fn edits_in_document_changes_are_applied() {
+        let edit = |new_text: &str| {
+            serde_json::json!({
+                "range": {
+                    "start": { "line": 0, "character": 0 },
+                    "end": { "line": 0, "character": 2 },
+                },
+                "newText": new_text,
+            })
+        };
+        let document = |uri: &str, edits: serde_json::Value| serde_json::json!({ "textDocument": { "uri": uri, "version": null }, "edits": edits });
+        let versioned = action(serde_json::json!({
+            "title": "Rename everywhere",
+            "edit": {
+                "changes": { "repl:/session/repl": [edit("stale")] },
+                "documentChanges": [
+                    document("file:///tmp/aaa.nu", serde_json::json!([edit("other")])),
+                    document("repl:/session/repl", serde_json::json!([edit("ls")])),
+                ],
+            },
+        }));
+        let mut annotated_edit = edit("ls");
+        annotated_edit["annotationId"] = "rename".into();
+        let annotated = action(serde_json::json!({
+            "title": "Rename with annotation",
+            "edit": {
+                "documentChanges": [document("repl:/session/repl", serde_json::json!([annotated_edit]))],
+                "changeAnnotations": { "rename": { "label": "Rename" } },
+            },
+        }));
+        let with_file_operation = action(serde_json::json!({
+            "title": "Extract to module",
+            "edit": { "documentChanges": [
+                { "kind": "create", "uri": "file:///tmp/module.nu" },
+                document("repl:/session/repl", serde_json::json!([edit("ls")])),
+            ]},
+        }));
+        assert_eq!(
+            extract_text_edits(&with_file_operation, Some("repl:/session/repl"))
+                .map(|edits| edits.len()),
+            Some(1)
+        );
+
+        for fix in [versioned, annotated] {
+            let mut menu = DiagnosticFixMenu::default().with_document_uri("repl:/session/repl");
+            menu.set_fixes(vec![fix], "sl", 0, None);
+            let mut editor = Editor::default();
+            editor.set_buffer("sl".to_string(), UndoBehavior::CreateUndoPoint);
+            menu.replace_in_buffer(&mut editor);
+            assert_eq!(editor.get_buffer(), "ls");
+        }
+    }
+
+    #[test]
+    fn the_preferred_fix_is_selected_when_the_menu_opens() {
+        let replace = |title: &str, new_text: &str, is_preferred: bool| {
+            action(serde_json::json!({
+                "title": title,
+                "isPreferred": is_preferred,
+                "edit": { "changes": { "repl:/session/repl": [{
+                    "range": {
+                        "start": { "line": 0, "character": 0 },
+                        "end": { "line": 0, "character": 2 },
+                    },
+                    "newText": new_text,
+                }]}},
+            }))
+        };
+        let create_file = action(serde_json::json!({
+            "title": "Create the module",
+            "isPreferred": true,
+            "edit": { "documentChanges": [{ "kind": "create", "uri": "file:///tmp/a.nu" }] },
+        }));
+        let mut menu = DiagnosticFixMenu::default().with_max_height(2);
+        menu.set_fixes(
+            vec![
+                create_file,
+                replace("Replace with lsof", "lsof", false),
+                replace("Replace with ssh", "ssh", false),
+                replace("Replace with ls", "ls", true),
+                replace("Replace with sl", "sl", true),
+            ],
+            "sl",
+            0,
+            None,
+        );
+        menu.menu_event(MenuEvent::Activate(false));
+        assert_eq!((menu.selected, menu.skip_values), (3, 2));
+
+        let mut editor = Editor::default();
+        editor.set_buffer("sl".to_string(), UndoBehavior::CreateUndoPoint);
+        menu.replace_in_buffer(&mut editor);
+        assert_eq!(editor.get_buffer(), "ls");
+
+        // Without a preferred fix, the first one is selected
+        menu.set_fixes(vec![replace("Replace with ls", "ls", false)], "sl", 0, None);
+        menu.menu_event(MenuEvent::Activate(false));
+        assert_eq!((menu.selected, menu.skip_values), (0, 0));
+    }
+
+    /// A menu with `count` fixes that are resolved on accept
+    fn menu_with_fixes(menu: DiagnosticFixMenu, count: usize) -> DiagnosticFixMenu {
+        let mut menu = menu;
+        let actions = (0..count)
+            .map(|i| action(serde_json::json!({ "title": format!("Fix {i}"), "data": i })))
+            .collect();
+        menu.set_fixes(actions, "ls", 0, None);
+        menu.menu_event(MenuEvent::Activate(false));
+        menu
+    }
+
+    #[test]
+    fn pages_jump_by_max_height() {
+        let mut menu = menu_with_fixes(DiagnosticFixMenu::default().with_max_height(3), 8);
+        let position = |menu: &DiagnosticFixMenu| (menu.selected, menu.skip_values);
+
+        assert_eq!(menu.menu_required_lines(80), 3);
+        menu.menu_event(MenuEvent::MoveDown);
+        menu.menu_event(MenuEvent::NextPage);
+        assert_eq!(position(&menu), (4, 3));
+        menu.menu_event(MenuEvent::NextPage);
+        assert_eq!(position(&menu), (7, 5));
+        menu.menu_event(MenuEvent::PreviousPage);
+        assert_eq!(position(&menu), (4, 2));
+    }
+
+    #[test]
+    fn short_terminals_show_fewer_fixes_than_the_max_height() {
+        let mut menu = menu_with_fixes(DiagnosticFixMenu::default().with_max_height(10), 8);
+        menu.menu_event(MenuEvent::Select(6));
+        let mut painter = Painter::new(BufWriter::new(std::io::stderr()));
+        painter.handle_resize(80, 4);
+        menu.update_working_details(
+            &mut Editor::default(),
+            &mut DefaultCompleter::default(),
+            &painter,
+        );
+
+        assert_eq!(menu.menu_required_lines(80), 3);
+        assert_eq!(menu.skip_values, 4);
+        assert!(menu.handle_mouse(3, 0).is_none());
+        assert!(matches!(menu.handle_mouse(2, 0), Some(MenuEvent::Accept)));
+    }
+
+    #[test]
+    fn bordered_fixes_leave_room_for_the_border() {
+        let border = MenuBorder::default().with_style(Style::new().fg(Color::Blue));
+        let menu = menu_with_fixes(
+            DiagnosticFixMenu::default()
+                .with_max_height(2)
+                .with_menu_border(border),
+            3,
+        );
+
+        assert_eq!(menu.menu_required_lines(80), 4);
+        let lines: Vec<String> = menu
+            .menu_string(10, false)
+            .split("\r\n")
+            .map(String::from)
+            .collect();
+        assert_eq!(
+            lines,
+            ["╭─────────╮", "│ > Fix 0 │", "│   Fix 1 │", "╰─────────╯",]
+        );
+        assert!(menu
+            .menu_string(10, true)
+            .starts_with(&Color::Blue.prefix().to_string()));
+        assert!(menu.handle_mouse(0, 0).is_none());
+        assert!(matches!(menu.handle_mouse(1, 0), Some(MenuEvent::Accept)));
+    }
+
+    #[test]
+    fn fixes_for_a_changed_line_are_reported_as_failed() {
+        let rename = action(serde_json::json!({
+            "title": "Rename to ls",
+            "edit": { "changes": { "repl:/session/repl": [{
+                "range": {
+                    "start": { "line": 0, "character": 5 },
+                    "end": { "line": 0, "character": 7 },
+                },
+                "newText": "ls",
+            }]}},
+        }));
+        let provider = LspDiagnosticsProvider::new(LspConfig::new("reedline-test-missing-server"));
+        let mut menu = DiagnosticFixMenu::default();
+        menu.set_command_sender(provider.command_sender());
+        menu.set_fixes(vec![rename], "echo sl", 0, None);
+
+        let mut editor = Editor::default();
+        editor.set_buffer("echo".to_string(), UndoBehavior::CreateUndoPoint);
+        menu.replace_in_buffer(&mut editor);
+
+        assert_eq!(editor.get_buffer(), "echo");
+        assert_eq!(provider.failed_fix().as_deref(), Some("Rename to ls"));
+    }
+
+    #[test]
+    fn edits_that_dont_fit_the_buffer_are_not_applied() {
+        let mut editor = Editor::default();
+        editor.set_buffer("echo 😀".to_string(), UndoBehavior::CreateUndoPoint);
+
+        // Inside the emoji, reversed and past the end
+        for (start, end) in [(6, 8), (7, 5), (5, 12)] {
+            let edit = TextEditInfo {
+                span: Span::new(start, end),
+                replacement: "x".to_string(),
+                original: String::new(),
+                replacement_styled: StyledText::new(),
+                original_styled: StyledText::new(),
+            };
+            assert!(!apply_edits(&mut editor, &[edit]));
+            assert_eq!(editor.get_buffer(), "echo 😀");
+        }
+    }
 }
@@ -1,19 +1,21 @@
-use super::{Menu, MenuBuilder, MenuEvent, MenuSettings};
+use super::{Menu, MenuBorder, MenuBuilder, MenuEvent, MenuSettings, ScrollIndicator};
 use crate::{
     core_editor::Editor,
     menu_functions::{
-        can_partially_complete, completer_input, floor_char_boundary, get_match_indices,
-        replace_in_buffer, style_suggestion, truncate_with_ansi,
+        can_partially_complete, completer_input, compose_styles, get_match_indices, group_header,
+        group_values, kind_column_width, kind_marker, next_index, previous_index,
+        replace_in_buffer, resolve_description, scroll_footer, scrollbar_track,
+        shortest_base_string, style_suggestion, truncate_with_ansi, wrap_with_ansi, ArrivingValues,
+        ValueGroup,
     },
-    painting::Painter,
-    Completer, Suggestion,
+    painting::{line_width, strip_ansi, Painter},
+    Completer, CompletionContext, Suggestion,
 };
 use itertools::{
     EitherOrBoth::{Both, Left, Right},
     Itertools,
 };
 use nu_ansi_term::ansi::RESET;
-use unicode_segmentation::UnicodeSegmentation;
 use unicode_width::UnicodeWidthStr;
 
 /// The direction of the description box
@@ -27,29 +29,6 @@ pub enum DescriptionMode {
     PreferRight,
 }
 
-/// Symbols used for the border of the menu
-struct BorderSymbols {
-    pub top_left: char,
-    pub top_right: char,
-    pub bottom_left: char,
-    pub bottom_right: char,
-    pub horizontal: char,
-    pub vertical: char,
-}
-
-impl Default for BorderSymbols {
-    fn default() -> Self {
-        Self {
-            top_left: '╭',
-            top_right: '╮',
-            bottom_left: '╰',
-            bottom_right: '╯',
-            horizontal: '─',
-            vertical: '│',
-        }
-    }
-}
-
 /// Default values used as reference for the menu. These values are set during
 /// the initial declaration of the menu and are always kept as reference for the
 /// changeable [`IdeMenuDetails`] values.
@@ -63,8 +42,9 @@ struct DefaultIdeMenuDetails {
     pub max_completion_height: u16,
     /// Padding to the left and right of the suggestions
     pub padding: u16,
-    /// Whether the menu has a border or not
-    pub border: Option<BorderSymbols>,
+    /// Whether the menu has a border or not, taking the place of the one
+    /// in the menu settings
+    pub border: Option<MenuBorder>,
     /// Horizontal offset from the cursor.
     /// 0 means the top left corner of the menu is below the cursor
     pub cursor_offset: i16,
@@ -130,6 +110,10 @@ struct IdeMenuDetails {
     pub description_offset: u16,
     /// The shortest of the strings, which the suggestions are based on
     pub shortest_base_string: String,
+    /// Whether the completion box has a scrollbar at its right edge
+    pub scrollbar: bool,
+    /// Screen width the menu was laid out for
+    pub screen_width: u16,
 }
 
 /// Menu to present suggestions like similar to Ide completion menus
@@ -145,17 +129,32 @@ pub struct IdeMenu {
     working_details: IdeMenuDetails,
     /// Menu cached values
     values: Vec<Suggestion>,
+    /// Groups of the values, each listed below its header
+    groups: Vec<ValueGroup>,
     /// Selected value. Starts at 0
     selected: u16,
-    /// Number of values that are skipped when printing,
-    /// depending on selected value and terminal height
-    skip_values: u16,
+    /// Number of lines of values and group headers that are skipped when
+    /// printing, depending on selected value and terminal height
+    skip_lines: u16,
+    /// Number of lines of the description that are skipped when printing
+    description_scroll: u16,
     /// Event sent to the menu
     event: Option<MenuEvent>,
-    /// Longest suggestion found in the values
+    /// Longest suggestion found in the values, including the kind column
     longest_suggestion: usize,
+    /// Width of the column showing the kinds of the values
+    kind_width: usize,
     /// String collected after the menu is activated
     input: Option<String>,
+    /// Whether the values were completed since the menu was activated
+    refining: bool,
+    /// Values still arriving from a completer running in the background
+    arriving: Option<ArrivingValues>,
+    /// Whether values arrived since the last layout
+    values_arrived: bool,
+    /// Value selected before the line was edited, selected again if the
+    /// updated values still list it
+    kept_selection: Option<String>,
 }
 
 impl Default for IdeMenu {
@@ -166,11 +165,18 @@ impl Default for IdeMenu {
             default_details: DefaultIdeMenuDetails::default(),
             working_details: IdeMenuDetails::default(),
             values: Vec::new(),
+            groups: Vec::new(),
             selected: 0,
-            skip_values: 0,
+            skip_lines: 0,
+            description_scroll: 0,
             event: None,
             longest_suggestion: 0,
+            kind_width: 0,
             input: None,
+            refining: false,
+            arriving: None,
+            values_arrived: false,
+            kept_selection: None,
         }
     }
 }
@@ -215,7 +221,7 @@ impl IdeMenu {
     /// Menu builder with the default border
     #[must_use]
     pub fn with_default_border(mut self) -> Self {
-        self.default_details.border = Some(BorderSymbols::default());
+        self.default_details.border = Some(MenuBorder::default().with_padding(0));
         self
     }
 
@@ -230,14 +236,12 @@ impl IdeMenu {
         horizontal: char,
         vertical: char,
     ) -> Self {
-        self.default_details.border = Some(BorderSymbols {
-            top_right,
-            top_left,
-            bottom_right,
-            bottom_left,
-            horizontal,
-            vertical,
-        });
+        self.default_details.border = Some(
+            MenuBorder::default()
+                .with_corners(top_left, top_right, bottom_left, bottom_right)
+                .with_edges(horizontal, vertical)
+                .with_padding(0),
+        );
         self
     }
 
@@ -263,6 +267,9 @@ impl IdeMenu {
     }
 
     /// Menu builder with new max description width
+    ///
+    /// Descriptions are wrapped at this width, keeping their paragraphs and
+    /// styles.
     #[must_use]
     pub fn with_max_description_width(mut self, max_description_width: u16) -> Self {
         self.default_details.max_description_width = max_description_width;
@@ -270,6 +277,13 @@ impl IdeMenu {
     }
 
     /// Menu builder with new max description height
+    ///
+    /// A description with more lines shows a scroll indicator on its last
+    /// row, and is scrolled with [`ReedlineEvent::MenuDescriptionUp`] and
+    /// [`ReedlineEvent::MenuDescriptionDown`] (`Alt+Up` and `Alt+Down`).
+    ///
+    /// [`ReedlineEvent::MenuDescriptionUp`]: crate::ReedlineEvent::MenuDescriptionUp
+    /// [`ReedlineEvent::MenuDescriptionDown`]: crate::ReedlineEvent::MenuDescriptionDown
     #[must_use]
     pub fn with_max_description_height(mut self, max_description_height: u16) -> Self {
         self.default_details.max_description_height = max_description_height;
@@ -294,19 +308,13 @@ impl IdeMenu {
 // Menu functionality
 impl IdeMenu {
     fn move_next(&mut self) {
-        if self.selected < (self.values.len() as u16).saturating_sub(1) {
-            self.selected += 1;
-        } else {
-            self.selected = 0;
-        }
+        self.selected = next_index(self.index(), self.values.len(), self.settings.wrap) as u16;
+        self.description_scroll = 0;
     }
 
     fn move_previous(&mut self) {
-        if self.selected > 0 {
-            self.selected -= 1;
-        } else {
-            self.selected = self.values.len().saturating_sub(1) as u16;
-        }
+        self.selected = previous_index(self.index(), self.values.len(), self.settings.wrap) as u16;
+        self.description_scroll = 0;
     }
 
     fn index(&self) -> usize {
@@ -317,22 +325,49 @@ impl IdeMenu {
         self.values.get(self.index()).cloned()
     }
 
+    /// The line the value at `index` is listed on, counting the group headers
+    fn line_of(&self, index: usize) -> u16 {
+        let headers = self
+            .groups
+            .iter()
+            .filter(|group| group.header.is_some() && group.start <= index)
+            .count();
+        (index + headers) as u16
+    }
+
+    /// The lines of the menu, each the name of a group or the index of a value
+    fn lines(&self) -> impl Iterator<Item = Result<usize, &str>> {
+        self.groups.iter().flat_map(|group| {
+            let values = (group.start..group.start + group.len).map(Ok);
+            group.header.as_deref().map(Err).into_iter().chain(values)
+        })
+    }
+
     /// Calculates how many rows the Menu will try to use (if available)
     fn get_rows(&self) -> u16 {
-        let mut values = self.get_values().len() as u16;
+        self.value_rows().max(self.description_rows())
+    }
+
+    /// Rows of the values and group headers, including the border
+    fn value_rows(&self) -> u16 {
+        let mut values = self.line_of(self.get_values().len());
 
         if values == 0 {
             // When the values are empty the no_records_msg is shown, taking 1 line
             return 1;
         }
 
-        if self.default_details.border.is_some() {
+        if self.border().is_some() {
             // top and bottom border take 1 line each
             values += 2;
         }
 
-        let description_height = self
-            .get_value()
+        values
+    }
+
+    /// Rows of the description of the selected value, including the border
+    fn description_rows(&self) -> u16 {
+        self.get_value()
             .and_then(|value| value.description)
             .map(|description| {
                 self.description_dims(
@@ -344,13 +379,45 @@ impl IdeMenu {
                 .1
             })
             .unwrap_or(0)
-            .min(self.default_details.max_description_height);
-
-        values.max(description_height)
+            .min(self.default_details.max_description_height)
     }
 
     fn reset_position(&mut self) {
         self.selected = 0;
+        self.description_scroll = 0;
+    }
+
+    /// Lines of values and group headers shown when `available_lines` are
+    /// left, keeping one for the footer if not all of them fit
+    fn shown_lines(&self, available_lines: u16) -> u16 {
+        let border_width = if self.border().is_some() { 2 } else { 0 };
+        let room = available_lines.saturating_sub(border_width);
+        if self.settings.scroll_indicator == ScrollIndicator::Footer
+            && self.line_of(self.values.len()) > room
+        {
+            room.saturating_sub(1).max(1)
+        } else {
+            room
+        }
+    }
+
+    /// The border of its own, or else the one of the menu settings
+    fn border(&self) -> Option<&MenuBorder> {
+        self.default_details
+            .border
+            .as_ref()
+            .or(self.settings.border.as_ref())
+    }
+
+    /// Padding to the left and right of the suggestions, including the one
+    /// of the border
+    fn padding(&self) -> u16 {
+        self.default_details.padding + self.border().map_or(0, MenuBorder::padding)
+    }
+
+    /// Width of the scrollbar, which replaces the right border if there is one
+    fn scrollbar_width(&self) -> u16 {
+        u16::from(self.working_details.scrollbar && self.border().is_none())
     }
 
     fn no_records_msg(&self, use_ansi_coloring: bool) -> String {
@@ -379,72 +446,86 @@ impl IdeMenu {
             return Vec::new();
         }
 
-        let border_width = if self.default_details.border.is_some() {
-            2
-        } else {
-            0
-        };
+        let border_width = if self.border().is_some() { 2 } else { 0 };
 
         let content_width = available_width.saturating_sub(border_width);
         let content_height = available_height.saturating_sub(border_width);
 
-        let mut description_lines = split_string(&description, content_width as usize);
-
-        if description_lines.len() > content_height as usize {
-            description_lines.truncate(content_height as usize);
-            truncate_string_list(&mut description_lines, "...");
+        let mut description_lines = wrap_with_ansi(&description, content_width as usize);
+        if !use_ansi_coloring {
+            for line in &mut description_lines {
+                *line = strip_ansi(line);
+            }
         }
 
         let content_width = description_lines
             .iter()
-            .map(|s| s.width())
+            .map(|s| line_width(s))
             .max()
             .unwrap_or_default()
             .max(min_width.saturating_sub(border_width) as usize);
 
-        // let needs_padding = description_lines.len() > 1
+        // A description too long for the box shows the lines it is scrolled
+        // to, and where they are in it on the last row
+        let total_lines = description_lines.len();
+        let shown_lines = shown_description_lines(total_lines, content_height);
+        if shown_lines < total_lines {
+            let first = (self.description_scroll as usize).min(total_lines - shown_lines);
+            let last = first + shown_lines;
+            description_lines.truncate(last);
+            description_lines.drain(..first);
+            if content_height > 1 {
+                let arrows = match (first > 0, last < total_lines) {
+                    (true, true) => "↑↓",
+                    (true, false) => "↑",
+                    (false, _) => "↓",
+                };
+                let indicator = format!("{arrows} {}-{last}/{total_lines}", first + 1);
+                description_lines.push(truncate_with_ansi(&indicator, content_width.max(3)).into());
+            }
+        }
+
+        // Styles of the description end in resets, after which the
+        // description style has to be set again
+        let restyle = |line: &str| {
+            line.replace(
+                RESET,
+                &format!("{RESET}{}", self.settings.color.description_style.prefix()),
+            )
+        };
 
-        if let Some(border) = &self.default_details.border {
-            let horizontal_border = border.horizontal.to_string().repeat(content_width);
+        if let Some(border) = self.border() {
+            let vertical_border = border.vertical(use_ansi_coloring);
 
             for line in &mut description_lines {
-                let padding = " ".repeat(content_width.saturating_sub(line.width()));
+                let padding = " ".repeat(content_width.saturating_sub(line_width(line)));
 
                 if use_ansi_coloring {
                     *line = format!(
                         "{}{}{}{}{}{}",
-                        border.vertical,
+                        vertical_border,
                         self.settings.color.description_style.prefix(),
-                        line,
+                        restyle(line),
                         padding,
                         RESET,
-                        border.vertical
+                        vertical_border
                     );
                 } else {
-                    *line = format!("{}{}{}{}", border.vertical, line, padding, border.vertical);
+                    *line = format!("{vertical_border}{line}{padding}{vertical_border}");
                 }
             }
 
-            description_lines.insert(
-                0,
-                format!(
-                    "{}{}{}",
-                    border.top_left, horizontal_border, border.top_right
-                ),
-            );
-            description_lines.push(format!(
-                "{}{}{}",
-                border.bottom_left, horizontal_border, border.bottom_right
-            ));
+            description_lines.insert(0, border.top(content_width, use_ansi_coloring));
+            description_lines.push(border.bottom(content_width, use_ansi_coloring));
         } else {
             for line in &mut description_lines {
-                let padding = " ".repeat(content_width.saturating_sub(line.width()));
+                let padding = " ".repeat(content_width.saturating_sub(line_width(line)));
 
                 if use_ansi_coloring {
                     *line = format!(
                         "{}{}{}{}",
                         self.settings.color.description_style.prefix(),
-                        line,
+                        restyle(line),
                         padding,
                         RESET
                     );
@@ -457,6 +538,21 @@ impl IdeMenu {
         description_lines
     }
 
+    /// How many lines the description of the selected value can be scrolled
+    fn max_description_scroll(&self, description: &str) -> u16 {
+        let border_width = if self.border().is_some() { 2 } else { 0 };
+        let content_width = self
+            .working_details
+            .description_width
+            .saturating_sub(border_width);
+        let content_height = self
+            .default_details
+            .max_description_height
+            .saturating_sub(border_width);
+        let total_lines = wrap_with_ansi(description, content_width as usize).len();
+        (total_lines - shown_description_lines(total_lines, content_height)) as u16
+    }
+
     /// Returns width and height of the description, including the border
     fn description_dims(
         &self,
@@ -475,6 +571,42 @@ impl IdeMenu {
         (width, height)
     }
 
+    /// The header line above the values of group `name`
+    fn create_header_string(&self, name: &str, use_ansi_coloring: bool, padding: usize) -> String {
+        let border_width = if self.border().is_some() { 2 } else { 0 };
+
+        let vertical_border = self
+            .border()
+            .map(|border| border.vertical(use_ansi_coloring))
+            .unwrap_or_default();
+
+        let border_width = border_width + self.scrollbar_width() as usize;
+        let header = group_header(name);
+        let max_string_width =
+            (self.working_details.completion_width as usize).saturating_sub(border_width + padding);
+        let header = truncate_with_ansi(&header, max_string_width);
+        let padding_right = (self.working_details.completion_width as usize)
+            .saturating_sub(header.width() + border_width + padding);
+        let header = if use_ansi_coloring {
+            self.settings
+                .color
+                .group_header_style
+                .paint(header)
+                .to_string()
+        } else {
+            header.into_owned()
+        };
+
+        format!(
+            "{}{}{}{}{}",
+            vertical_border,
+            " ".repeat(padding),
+            header,
+            " ".repeat(padding_right),
+            vertical_border,
+        )
+    }
+
     fn create_value_string(
         &self,
         suggestion: &Suggestion,
@@ -482,26 +614,22 @@ impl IdeMenu {
         use_ansi_coloring: bool,
         padding: usize,
     ) -> String {
-        let border_width = if self.default_details.border.is_some() {
-            2
-        } else {
-            0
-        };
+        let border_width = if self.border().is_some() { 2 } else { 0 };
 
         let vertical_border = self
-            .default_details
-            .border
-            .as_ref()
-            .map(|border| border.vertical)
+            .border()
+            .map(|border| border.vertical(use_ansi_coloring))
             .unwrap_or_default();
 
+        let border_width = border_width + self.scrollbar_width() as usize;
         let display_value = suggestion.display_value();
+        let kind = kind_marker(suggestion, self.kind_width, self.settings.kind_icons);
 
         let padding_right = (self.working_details.completion_width as usize)
-            .saturating_sub(display_value.width() + border_width + padding);
+            .saturating_sub(self.kind_width + display_value.width() + border_width + padding);
 
-        let max_string_width =
-            (self.working_details.completion_width as usize).saturating_sub(border_width + padding);
+        let max_string_width = (self.working_details.completion_width as usize)
+            .saturating_sub(self.kind_width + border_width + padding);
 
         let string = truncate_with_ansi(display_value, max_string_width);
 
@@ -519,10 +647,15 @@ impl IdeMenu {
             let suggestion_style = suggestion.style.unwrap_or(self.settings.color.text_style);
 
             let styled_string = if index == self.index() {
+                let selected_style = suggestion
+                    .style
+                    .map_or(self.settings.color.selected_text_style, |style| {
+                        compose_styles(&self.settings.color.selected_text_style, &style)
+                    });
                 style_suggestion(
                     &string,
                     &match_indices,
-                    &self.settings.color.selected_text_style,
+                    &selected_style,
                     &self.settings.color.selected_match_style,
                     None,
                 )
@@ -537,10 +670,11 @@ impl IdeMenu {
             };
 
             format!(
-                "{}{}{}{}{}{}{}",
+                "{}{}{}{}{}{}{}{}",
                 vertical_border,
                 suggestion_style.prefix(),
                 " ".repeat(padding),
+                self.settings.color.description_style.paint(kind),
                 styled_string,
                 " ".repeat(padding_right),
                 RESET,
@@ -550,10 +684,11 @@ impl IdeMenu {
             let marker = if index == self.index() { ">" } else { "" };
 
             format!(
-                "{}{}{}{}{}{}",
+                "{}{}{}{}{}{}{}",
                 vertical_border,
                 " ".repeat(padding),
                 marker,
+                kind,
                 string,
                 " ".repeat(padding_right),
                 vertical_border,
@@ -578,6 +713,11 @@ impl Menu for IdeMenu {
         true
     }
 
+    /// Completing one argument after another keeps the menu open
+    fn can_continue(&self) -> bool {
+        true
+    }
+
     fn can_partially_complete(
         &mut self,
         values_updated: bool,
@@ -604,10 +744,17 @@ impl Menu for IdeMenu {
     /// Selects what type of event happened with the menu
     fn menu_event(&mut self, event: MenuEvent) {
         match &event {
-            MenuEvent::Activate(_) => self.active = true,
+            MenuEvent::Activate(_) => {
+                self.active = true;
+                self.refining = false;
+            }
             MenuEvent::Deactivate => {
                 self.active = false;
                 self.input = None;
+                self.arriving = None;
+            }
+            MenuEvent::Edit(_) if self.settings.keep_selection => {
+                self.kept_selection = self.get_value().map(|suggestion| suggestion.value);
             }
             _ => {}
         }
@@ -627,18 +774,16 @@ impl Menu for IdeMenu {
             self.input.as_deref(),
             self.settings.only_buffer_difference,
         );
-        let (values, base_ranges) = completer.complete_with_base_ranges(&input, pos);
+        let ctx = CompletionContext::new(&input, pos, self.refining);
+        let (values, base_ranges, arriving) =
+            ArrivingValues::start(completer, &ctx, editor.get_buffer());
+        self.arriving = arriving;
+        self.refining = true;
 
         self.values = values;
-        self.working_details.shortest_base_string = base_ranges
-            .iter()
-            .map(|range| {
-                let end = floor_char_boundary(editor.get_buffer(), range.end);
-                let start = floor_char_boundary(editor.get_buffer(), range.start).min(end);
-                editor.get_buffer()[start..end].to_string()
-            })
-            .min_by_key(|s| s.width())
-            .unwrap_or_default();
+        self.groups = group_values(&mut self.values, 0).0;
+        self.working_details.shortest_base_string =
+            shortest_base_string(editor.get_buffer(), &base_ranges);
 
         self.reset_position();
     }
@@ -651,45 +796,81 @@ impl Menu for IdeMenu {
         completer: &mut dyn Completer,
         painter: &Painter,
     ) {
-        if let Some(event) = self.event.take() {
-            match event {
-                MenuEvent::Activate(updated) => {
-                    self.reset_position();
-
-                    if !updated {
-                        self.update_values(editor, completer);
+        let event = self.event.take();
+        // Arriving values and a resize lay the menu out again, keeping the
+        // selection
+        let values_arrived = std::mem::take(&mut self.values_arrived);
+        let resized = self.working_details.screen_width != painter.screen_width();
+        if event.is_some() || values_arrived || resized {
+            if let Some(event) = event {
+                match event {
+                    MenuEvent::Activate(updated) => {
+                        self.reset_position();
+
+                        if !updated {
+                            self.update_values(editor, completer);
+                        }
                     }
-                }
-                MenuEvent::Deactivate => {}
-                MenuEvent::Edit(updated) => {
-                    self.reset_position();
+                    MenuEvent::Deactivate => {}
+                    MenuEvent::Edit(updated) => {
+                        self.reset_position();
 
-                    if !updated {
+                        if !updated {
+                            self.update_values(editor, completer);
+                        }
+                        let kept = self.kept_selection.take().and_then(|value| {
+                            self.values
+                                .iter()
+                                .position(|suggestion| suggestion.value == value)
+                        });
+                        if let Some(index) = kept {
+                            self.selected = index as u16;
+                        }
+                    }
+                    MenuEvent::NextElement | MenuEvent::MoveDown => self.move_next(),
+                    MenuEvent::PreviousElement | MenuEvent::MoveUp => self.move_previous(),
+                    MenuEvent::ScrollDescriptionUp => {
+                        self.description_scroll = self.description_scroll.saturating_sub(1);
+                    }
+                    MenuEvent::ScrollDescriptionDown => {
+                        self.description_scroll = self.description_scroll.saturating_add(1);
+                    }
+                    MenuEvent::MoveLeft
+                    | MenuEvent::MoveRight
+                    | MenuEvent::PreviousPage
+                    | MenuEvent::NextPage
+                    | MenuEvent::Select(_)
+                    | MenuEvent::Accept
+                    | MenuEvent::Notice(_) => {}
+                    MenuEvent::Refresh => {
+                        let selected = self.selected;
                         self.update_values(editor, completer);
+                        self.selected = selected.min(self.values.len().saturating_sub(1) as u16);
                     }
                 }
-                MenuEvent::NextElement | MenuEvent::MoveDown => self.move_next(),
-                MenuEvent::PreviousElement | MenuEvent::MoveUp => self.move_previous(),
-                MenuEvent::MoveLeft
-                | MenuEvent::MoveRight
-                | MenuEvent::PreviousPage
-                | MenuEvent::NextPage => {}
+                if self.is_active() {
+                    let index = self.index();
+                    resolve_description(self.values.get_mut(index), completer);
+                }
             }
 
+            let headers = self
+                .groups
+                .iter()
+                .filter_map(|group| group.header.as_deref());
+            self.kind_width = kind_column_width(&self.values, self.settings.kind_icons);
             self.longest_suggestion = self
                 .get_values()
                 .iter()
-                .map(|s| s.display_value().width())
+                .map(|s| self.kind_width + s.display_value().width())
+                .chain(headers.map(|name| group_header(name).width()))
                 .max()
                 .unwrap_or_default();
 
             let terminal_width = painter.screen_width();
+            self.working_details.screen_width = terminal_width;
 
-            let total_border_width = if self.default_details.border.is_some() {
-                2
-            } else {
-                0
-            };
+            let total_border_width = if self.border().is_some() { 2 } else { 0 };
 
             let description = self.get_value().and_then(|v| {
                 let v = v.description?;
@@ -710,12 +891,18 @@ impl Menu for IdeMenu {
                 available_lines = painter.remaining_lines().min(self.min_rows());
             }
 
+            self.working_details.scrollbar = self.settings.scroll_indicator
+                == ScrollIndicator::Scrollbar
+                && self.line_of(self.values.len())
+                    > available_lines.saturating_sub(total_border_width);
+
             let completion_width = ((self.longest_suggestion.min(u16::MAX as usize) as u16)
-                + 2 * self.default_details.padding
-                + total_border_width)
-                .min(self.default_details.max_completion_width)
-                .max(self.default_details.min_completion_width)
-                .max(3 + total_border_width); // Big enough to show "..."
+                + 2 * self.padding()
+                + total_border_width
+                + self.scrollbar_width())
+            .min(self.default_details.max_completion_width)
+            .max(self.default_details.min_completion_width)
+            .max(3 + total_border_width); // Big enough to show "..."
             self.working_details.completion_width = completion_width;
 
             // Columns at which completion box begins
@@ -768,12 +955,15 @@ impl Menu for IdeMenu {
                 };
                 self.working_details.description_width = self
                     .description_dims(
-                        description,
+                        description.clone(),
                         desc_space.min(self.default_details.max_description_width),
                         available_lines,
                         self.default_details.min_description_width,
                     )
                     .0;
+                self.description_scroll = self
+                    .description_scroll
+                    .min(self.max_description_scroll(&description));
 
                 let max_offset = terminal_width
                     .saturating_sub(completion_width + self.working_details.description_width);
@@ -798,17 +988,28 @@ impl Menu for IdeMenu {
                 + self.working_details.description_offset
                 + self.working_details.description_width;
 
-            let visible_items = available_lines.saturating_sub(total_border_width);
+            let visible_items = self.shown_lines(available_lines);
 
-            self.skip_values = if self.selected <= self.skip_values {
+            let selected_line = self.line_of(self.index());
+            // The header of the first value of a group is shown with it
+            let top_line = if self
+                .groups
+                .iter()
+                .any(|group| group.header.is_some() && group.start == self.index())
+            {
+                selected_line.saturating_sub(1)
+            } else {
+                selected_line
+            };
+            self.skip_lines = if top_line <= self.skip_lines {
                 // Selection is above the visible area
-                self.selected
-            } else if self.selected >= self.skip_values + visible_items {
+                top_line
+            } else if selected_line >= self.skip_lines + visible_items {
                 // Selection is below the visible area
-                self.selected.saturating_sub(visible_items) + 1
+                selected_line.saturating_sub(visible_items) + 1
             } else {
                 // Selection is within the visible area
-                self.skip_values
+                self.skip_lines
             }
         }
     }
@@ -818,6 +1019,40 @@ impl Menu for IdeMenu {
         replace_in_buffer(self.get_value(), editor);
     }
 
+    fn selected_value(&self) -> Option<Suggestion> {
+        self.get_value()
+    }
+
+    fn is_completing(&self) -> bool {
+        self.arriving.is_some()
+    }
+
+    /// Arriving values are added after the ones shown, the selected value
+    /// stays selected
+    fn poll_values(&mut self) -> bool {
+        let Some(arriving) = self.arriving.as_mut() else {
+            return false;
+        };
+        let values = arriving.take();
+        let buffer = arriving.buffer().to_string();
+        let base_ranges = arriving.base_ranges().to_vec();
+        if arriving.is_finished() {
+            self.arriving = None;
+        }
+        if values.is_empty() {
+            return false;
+        }
+
+        self.values.extend(values);
+        let selected = self.index();
+        let (groups, selected) = group_values(&mut self.values, selected);
+        self.groups = groups;
+        self.selected = selected as u16;
+        self.working_details.shortest_base_string = shortest_base_string(&buffer, &base_ranges);
+        self.values_arrived = true;
+        true
+    }
+
     /// Minimum rows that should be displayed by the menu
     fn min_rows(&self) -> u16 {
         self.get_rows()
@@ -828,74 +1063,86 @@ impl Menu for IdeMenu {
     }
 
     fn menu_required_lines(&self, _terminal_columns: u16) -> u16 {
-        self.get_rows()
+        self.value_rows()
             .min(self.default_details.max_completion_height)
+            .max(self.description_rows())
     }
 
     fn menu_string(&self, available_lines: u16, use_ansi_coloring: bool) -> String {
         if self.get_values().is_empty() {
             self.no_records_msg(use_ansi_coloring)
         } else {
-            let border_width = if self.default_details.border.is_some() {
-                2
-            } else {
-                0
-            };
+            let border_width = if self.border().is_some() { 2 } else { 0 };
 
+            let description_height =
+                available_lines.min(self.default_details.max_description_height);
             let available_lines = available_lines.min(self.default_details.max_completion_height);
-            let skip_values = self.skip_values as usize;
+            let skip_lines = self.skip_lines as usize;
 
-            let available_values = available_lines.saturating_sub(border_width) as usize;
+            let available_values = self.shown_lines(available_lines) as usize;
 
             let max_padding = self.working_details.completion_width.saturating_sub(
-                self.longest_suggestion.min(u16::MAX as usize) as u16 + border_width,
+                self.longest_suggestion.min(u16::MAX as usize) as u16
+                    + border_width
+                    + self.scrollbar_width(),
             ) / 2;
 
-            let corrected_padding = self.default_details.padding.min(max_padding) as usize;
+            let corrected_padding = self.padding().min(max_padding) as usize;
 
             let mut strings = self
-                .get_values()
-                .iter()
-                .skip(skip_values)
+                .lines()
+                .skip(skip_lines)
                 .take(available_values)
-                .enumerate()
-                .map(|(index, suggestion)| {
-                    // Correcting the enumerate index based on the number of skipped values
-
-                    let index = index + skip_values;
-                    self.create_value_string(
-                        suggestion,
+                .map(|line| match line {
+                    Ok(index) => self.create_value_string(
+                        &self.values[index],
                         index,
                         use_ansi_coloring,
                         corrected_padding,
-                    )
+                    ),
+                    Err(name) => {
+                        self.create_header_string(name, use_ansi_coloring, corrected_padding)
+                    }
                 })
                 .collect::<Vec<String>>();
 
+            let total_lines = self.line_of(self.values.len()) as usize;
+            let shown = skip_lines..skip_lines + strings.len();
+            let footer = match self.settings.scroll_indicator {
+                ScrollIndicator::None => None,
+                ScrollIndicator::Scrollbar => {
+                    // The glyphs take the place of the right border
+                    let right_border = self
+                        .border()
+                        .map(|border| border.vertical(use_ansi_coloring))
+                        .unwrap_or_default();
+                    let track = scrollbar_track(total_lines, shown, strings.len())
+                        .filter(|_| self.working_details.scrollbar);
+                    for (line, glyph) in strings.iter_mut().zip(track.into_iter().flatten()) {
+                        line.truncate(line.len() - right_border.len());
+                        line.push(glyph);
+                    }
+                    None
+                }
+                ScrollIndicator::Footer => (shown.len() < total_lines).then(|| {
+                    let footer = scroll_footer(self.index(), self.values.len());
+                    if use_ansi_coloring {
+                        self.settings.color.text_style.paint(footer).to_string()
+                    } else {
+                        footer
+                    }
+                }),
+            };
+
             // Add top and bottom border
-            if let Some(border) = &self.default_details.border {
+            if let Some(border) = self.border() {
                 let inner_width = self.working_details.completion_width.saturating_sub(2) as usize;
 
-                strings.insert(
-                    0,
-                    format!(
-                        "{}{}{}",
-                        border.top_left,
-                        border.horizontal.to_string().repeat(inner_width),
-                        border.top_right,
-                    ),
-                );
-
-                strings.push(format!(
-                    "{}{}{}",
-                    border.bottom_left,
-                    border.horizontal.to_string().repeat(inner_width),
-                    border.bottom_right,
-                ));
+                strings.insert(0, border.top(inner_width, use_ansi_coloring));
+                strings.push(border.bottom(inner_width, use_ansi_coloring));
             }
+            strings.extend(footer);
 
-            let description_height =
-                available_lines.min(self.default_details.max_description_height);
             let description_lines = self
                 .get_value()
                 .and_then(|value| value.clone().description)
@@ -988,316 +1235,26 @@ impl Menu for IdeMenu {
     }
 }
 
-/// Split the input into strings that are at most `max_length` (in columns, not in chars) long
-/// The split is done at whitespace if possible
-fn split_string(input_str: &str, max_length: usize) -> Vec<String> {
-    let whitespace_split = input_str.split_whitespace();
-    let mut words = Vec::new();
-
-    for word in whitespace_split {
-        let word_len_cols = word.width();
-
-        if word_len_cols > max_length {
-            let mut width = 0;
-            let mut substring = String::new();
-            for grapheme in word.graphemes(true) {
-                let grapheme_width = grapheme.width();
-                // Some unicode characters can have a width of multiple rows
-                if grapheme_width > max_length {
-                    continue;
-                }
-                if width + grapheme_width > max_length {
-                    words.push(substring);
-                    substring = String::from(grapheme);
-                    width = grapheme_width;
-                } else {
-                    substring.push_str(grapheme);
-                    width += grapheme_width;
-                }
-            }
-            if !substring.is_empty() {
-                words.push(substring);
-            }
-        } else {
-            words.push(word.to_string());
-        }
-    }
-
-    let mut result = Vec::new();
-    let mut string = String::new();
-
-    for word in words {
-        if string.width() + word.width() > max_length {
-            result.push(string.trim_end().to_string());
-            string = word;
-            string.push(' ');
-        } else {
-            string.push_str(&word);
-            string.push(' ');
-        }
-    }
-
-    if !string.trim_end().is_empty() {
-        result.push(string.trim_end().to_string());
-    }
-
-    result
-}
-
-/// Truncate a list of strings using the provided truncation characters
-fn truncate_string_list(list: &mut [String], truncation_chars: &str) {
-    let truncation_chars: Vec<char> = truncation_chars.chars().rev().collect();
-    let truncation_len = truncation_chars.len();
-    let mut to_replace = truncation_len;
-
-    'outer: for line in list.iter_mut().rev() {
-        let chars = UnicodeSegmentation::graphemes(line.as_str(), true).collect::<Vec<&str>>();
-        let mut new_line = String::new();
-        for grapheme in chars.into_iter().rev() {
-            if to_replace > 0 {
-                new_line.insert(0, truncation_chars[truncation_len - to_replace]);
-                to_replace -= 1;
-            } else {
-                new_line.insert_str(0, grapheme);
-            }
-        }
-        *line = new_line;
-        if to_replace == 0 {
-            break 'outer;
-        }
+/// Lines of a description `total_lines` long shown in a box with room for
+/// `height` of them, keeping a row for the scroll indicator if they don't fit
+fn shown_description_lines(total_lines: usize, height: u16) -> usize {
+    let height = height as usize;
+    if total_lines <= height {
+        total_lines
+    } else {
+        height.saturating_sub(1).max(1).min(height)
     }
 }
 
 #[cfg(test)]
 mod tests {
-    use crate::{Span, UndoBehavior};
+    use crate::{DefaultCompleter, Span, SuggestionKind, UndoBehavior};
 
     use super::*;
+    use nu_ansi_term::{Color, Style};
     use pretty_assertions::assert_eq;
     use rstest::rstest;
 
-    #[rstest]
-    #[case(
-        "",
-        10,
-        vec![]
-    )]
-    #[case(
-        "description",
-        15,
-        vec![
-            "description".into(),
-        ]
-    )]
-    #[case(
-        "this is a description",
-        10,
-        vec![
-            "this is a".into(),
-            "descriptio".into(),
-            "n".into(),
-        ]
-    )]
-    #[case(
-        "this is another description",
-        2,
-        vec![
-            "th".into(),
-            "is".into(),
-            "is".into(),
-            "an".into(),
-            "ot".into(),
-            "he".into(),
-            "r".into(),
-            "de".into(),
-            "sc".into(),
-            "ri".into(),
-            "pt".into(),
-            "io".into(),
-            "n".into(),
-        ]
-    )]
-    #[case(
-        "this is a description",
-        10,
-        vec![
-            "this is a".into(),
-            "descriptio".into(),
-            "n".into(),
-        ]
-    )]
-    #[case(
-        "this is a description",
-        10,
-        vec![
-            "this is a".into(),
-            "descriptio".into(),
-            "n".into(),
-        ]
-    )]
-    #[case(
-        "this is a description",
-        12,
-        vec![
-            "this is a".into(),
-            "description".into(),
-        ]
-    )]
-    #[case(
-        "test",
-        1,
-        vec![
-            "t".into(),
-            "e".into(),
-            "s".into(),
-            "t".into(),
-        ]
-    )]
-    #[case(
-        "😊a😊 😊bc de😊fg",
-        2,
-        vec![
-            "😊".into(),
-            "a".into(),
-            "😊".into(),
-            "😊".into(),
-            "bc".into(),
-            "de".into(),
-            "😊".into(),
-            "fg".into(),
-        ]
-    )]
-    #[case(
-        "😊",
-        1,
-        vec![],
-    )]
-    #[case(
-        "t😊e😊s😊t",
-        1,
-        vec![
-            "t".into(),
-            "e".into(),
-            "s".into(),
-            "t".into(),
-        ]
-    )]
-
-    fn test_split_string(
-        #[case] input: &str,
-        #[case] max_width: usize,
-        #[case] expected: Vec<String>,
-    ) {
-        let result = split_string(input, max_width);
-
-        assert_eq!(result, expected)
-    }
-
-    #[rstest]
-    #[case(
-        &mut vec![
-            "this is a description".into(),
-            "that will be truncate".into(),
-            "d".into(),
-        ],
-        "...",
-        vec![
-            "this is a description".into(),
-            "that will be trunca..".into(),
-            ".".into(),
-        ]
-    )]
-    #[case(
-        &mut vec![
-            "this is a description".into(),
-            "that will be truncate".into(),
-            "d".into(),
-        ],
-        "....",
-        vec![
-            "this is a description".into(),
-            "that will be trunc...".into(),
-            ".".into(),
-        ]
-    )]
-    #[case(
-        &mut vec![
-            "😊a😊 😊bc de😊fg".into(),
-            "😊a😊 😊bc de😊fg".into(),
-            "😊a😊 😊bc de😊fg".into(),
-        ],
-        "...",
-        vec![
-            "😊a😊 😊bc de😊fg".into(),
-            "😊a😊 😊bc de😊fg".into(),
-            "😊a😊 😊bc de...".into(),
-        ]
-    )]
-    #[case(
-        &mut vec![
-            "t".into(),
-            "e".into(),
-            "s".into(),
-            "t".into(),
-        ],
-        "..",
-        vec![
-            "t".into(),
-            "e".into(),
-            ".".into(),
-            ".".into(),
-        ]
-    )]
-    #[case(
-        &mut vec![
-            "😊".into(),
-            "😊".into(),
-            "s".into(),
-            "t".into(),
-        ],
-        "..😊",
-        vec![
-            "😊".into(),
-            ".".into(),
-            ".".into(),
-            "😊".into(),
-        ]
-    )]
-    #[case(
-        &mut vec![
-            "".into(),
-        ],
-        "test",
-        vec![
-            "".into()
-        ],
-    )]
-    #[case(
-        &mut vec![
-            "t".into(),
-            "e".into(),
-            "s".into(),
-            "t".into()
-        ],
-        "",
-        vec![
-            "t".into(),
-            "e".into(),
-            "s".into(),
-            "t".into()
-        ],
-    )]
-
-    fn test_truncate_list_string(
-        #[case] input: &mut Vec<String>,
-        #[case] truncation_chars: &str,
-        #[case] expected: Vec<String>,
-    ) {
-        truncate_string_list(input, truncation_chars);
-
-        assert_eq!(*input, expected)
-    }
-
     macro_rules! partial_completion_tests {
         (name: $test_group_name:ident, completions: $completions:expr, test_cases: $($name:ident: $value:expr,)*) => {
             mod $test_group_name {
@@ -1427,6 +1384,8 @@ mod tests {
             space_right: 50,
             description_offset: 50,
             shortest_base_string: String::new(),
+            scrollbar: false,
+            screen_width: 50,
         };
         let mut editor = Editor::default();
         // backtick at the end of the line
@@ -1455,6 +1414,8 @@ mod tests {
             space_right: 50,
             description_offset: 50,
             shortest_base_string: String::new(),
+            scrollbar: false,
+            screen_width: 50,
         };
         let mut editor = Editor::default();
 
@@ -1488,4 +1449,404 @@ mod tests {
         menu.update_values(&mut editor, &mut completer);
         assert!(menu.menu_string(10, true).contains("验"));
     }
+
+    #[test]
+    fn values_are_painted_in_their_own_style() {
+        let mut menu = IdeMenu::default().with_name("testmenu");
+        menu.working_details.completion_width = 20;
+        let blue = Color::Blue.normal();
+        let styled = Suggestion {
+            value: "lib/".into(),
+            style: Some(blue),
+            ..Default::default()
+        };
+        let plain = Suggestion {
+            value: "log/".into(),
+            ..Default::default()
+        };
+
+        let row =
+            |suggestion: &Suggestion, index| menu.create_value_string(suggestion, index, true, 0);
+        let colors = &menu.settings.color;
+        let selected_blue = compose_styles(&colors.selected_text_style, &blue);
+        assert!(row(&styled, 0).contains(&selected_blue.prefix().to_string()));
+        assert!(row(&styled, 1).contains(&blue.prefix().to_string()));
+        assert!(row(&plain, 0).contains(&colors.selected_text_style.prefix().to_string()));
+        // Styles take no room
+        let width = |row: String| {
+            String::from_utf8(strip_ansi_escapes::strip(row))
+                .unwrap()
+                .width()
+        };
+        assert_eq!(width(row(&styled, 1)), width(row(&plain, 1)));
+    }
+
+    #[test]
+    fn rows_with_and_without_a_style_of_their_own() {
+        let mut menu = IdeMenu::default().with_name("testmenu");
+        menu.working_details.completion_width = 10;
+        let suggestion = |value: &str, style: Option<Style>| Suggestion {
+            value: value.into(),
+            style,
+            ..Default::default()
+        };
+        let suggestions = [
+            suggestion("lib/", Some(Color::Blue.normal())),
+            suggestion("lsd", None),
+            suggestion("old", Some(Color::DarkGray.dimmed())),
+        ];
+
+        let mut rendered = Vec::new();
+        for selected in 0..3 {
+            menu.selected = selected;
+            rendered.push(format!("selected: {selected}"));
+            rendered.extend(suggestions.iter().enumerate().map(|(index, suggestion)| {
+                menu.create_value_string(suggestion, index, true, 0)
+                    .replace('\u{1b}', "\\e")
+            }));
+        }
+
+        insta::assert_snapshot!(rendered.join("\n"));
+    }
+
+    /// Completes files, aliases and a value without a group, mixed up
+    struct GroupedCompleter;
+
+    impl Completer for GroupedCompleter {
+        fn complete(&mut self, _line: &str, pos: usize) -> Vec<Suggestion> {
+            [
+                ("a.rs", Some("files")),
+                ("ll", Some("aliases")),
+                ("x", None),
+                ("b.rs", Some("files")),
+            ]
+            .into_iter()
+            .map(|(name, group)| Suggestion {
+                group: group.map(str::to_string),
+                ..fake_suggestion(name, pos)
+            })
+            .collect()
+        }
+    }
+
+    /// Send `event` to `menu` and its lines when `height` lines are left
+    fn lines_after(menu: &mut IdeMenu, event: MenuEvent, height: u16) -> Vec<String> {
+        lines_on_screen(menu, &mut GroupedCompleter, event, (40, height))
+    }
+
+    /// Send `event` to `menu` and its lines on a screen of `size`
+    fn lines_on_screen(
+        menu: &mut IdeMenu,
+        completer: &mut dyn Completer,
+        event: MenuEvent,
+        (width, height): (u16, u16),
+    ) -> Vec<String> {
+        let mut painter = Painter::new(std::io::BufWriter::new(std::io::stderr()));
+        painter.handle_resize(width, height);
+        menu.menu_event(event);
+        menu.update_working_details(&mut Editor::default(), completer, &painter);
+        menu.menu_string(height, false)
+            .split("\r\n")
+            .map(|line| line.trim_end().to_string())
+            .collect()
+    }
+
+    struct KindCompleter;
+
+    impl Completer for KindCompleter {
+        fn complete(&mut self, _line: &str, pos: usize) -> Vec<Suggestion> {
+            [("ls", Some(SuggestionKind::Function)), ("lib.rs", None)]
+                .into_iter()
+                .map(|(name, kind)| Suggestion {
+                    kind,
+                    ..fake_suggestion(name, pos)
+                })
+                .collect()
+        }
+    }
+
+    #[rstest]
+    #[case::icons(true, [">ƒ ls", "  lib.rs"])]
+    #[case::labels(false, [">fn ls", "   lib.rs"])]
+    fn kinds_are_shown_in_front_of_the_values(#[case] icons: bool, #[case] expected: [&str; 2]) {
+        let mut menu = IdeMenu::default()
+            .with_name("testmenu")
+            .with_kind_icons(icons);
+
+        let lines = lines_on_screen(
+            &mut menu,
+            &mut KindCompleter,
+            MenuEvent::Activate(false),
+            (40, 10),
+        );
+
+        assert_eq!(lines, expected);
+    }
+
+    #[test]
+    fn groups_are_listed_below_their_headers() {
+        let mut menu = IdeMenu::default().with_name("testmenu");
+
+        let lines = lines_after(&mut menu, MenuEvent::Activate(false), 10);
+
+        assert_eq!(
+            lines,
+            ["── files ──", ">a.rs", "b.rs", "── aliases ──", "ll", "x",]
+        );
+        assert_eq!(menu.menu_required_lines(40), 6);
+    }
+
+    #[test]
+    fn scrolling_up_to_a_group_shows_its_header() {
+        let mut menu = IdeMenu::default().with_name("testmenu");
+        lines_after(&mut menu, MenuEvent::Activate(false), 3);
+
+        let lines = lines_after(&mut menu, MenuEvent::NextElement, 3);
+        assert_eq!(lines, ["── files ──", "a.rs", ">b.rs"]);
+
+        let lines = lines_after(&mut menu, MenuEvent::NextElement, 3);
+        assert_eq!(lines, ["b.rs", "── aliases ──", ">ll"]);
+
+        let lines = lines_after(&mut menu, MenuEvent::PreviousElement, 3);
+        assert_eq!(lines, [">b.rs", "── aliases ──", "ll"]);
+
+        let lines = lines_after(&mut menu, MenuEvent::PreviousElement, 3);
+        assert_eq!(lines, ["── files ──", ">a.rs", "b.rs"]);
+    }
+
+    /// The lines of a menu of twenty values showing `indicator`, after
+    /// moving the selection down `moves` times on a screen `height` rows high
+    fn scrolled_lines(indicator: ScrollIndicator, moves: usize, height: u16) -> Vec<String> {
+        let mut menu = IdeMenu::default()
+            .with_name("testmenu")
+            .with_scroll_indicator(indicator);
+        let values: Vec<String> = (0..20).map(|i| format!("v{i}")).collect();
+        let values: Vec<&str> = values.iter().map(String::as_str).collect();
+        let mut completer = FakeCompleter::new(&values);
+        let events = std::iter::once(MenuEvent::Activate(false))
+            .chain(std::iter::repeat(MenuEvent::NextElement).take(moves));
+        let mut lines = Vec::new();
+        for event in events {
+            lines = lines_on_screen(&mut menu, &mut completer, event, (20, height));
+        }
+        lines
+    }
+
+    #[test]
+    fn scrollbar_thumb_follows_the_values_shown() {
+        let lines = |moves| scrolled_lines(ScrollIndicator::Scrollbar, moves, 4);
+
+        assert_eq!(lines(0), [">v0 ┃", "v1 │", "v2 │", "v3 │"]);
+        assert_eq!(lines(9), ["v6 │", "v7 ┃", "v8 │", ">v9 │"]);
+        assert_eq!(lines(19), ["v16│", "v17│", "v18│", ">v19┃"]);
+    }
+
+    #[test]
+    fn footer_shows_the_position_of_the_selection() {
+        assert_eq!(
+            scrolled_lines(ScrollIndicator::Footer, 6, 4),
+            ["v4", "v5", ">v6", "[7/20]"]
+        );
+    }
+
+    #[rstest]
+    #[case::scrollbar(ScrollIndicator::Scrollbar)]
+    #[case::footer(ScrollIndicator::Footer)]
+    fn menus_that_fit_show_no_scroll_indicator(#[case] indicator: ScrollIndicator) {
+        assert_eq!(
+            scrolled_lines(indicator, 3, 30),
+            scrolled_lines(ScrollIndicator::None, 3, 30)
+        );
+    }
+
+    /// Completes two commands, the first with a description of paragraphs
+    struct DescribedCompleter;
+
+    impl Completer for DescribedCompleter {
+        fn complete(&mut self, _line: &str, pos: usize) -> Vec<Suggestion> {
+            let description = "List the entries of a directory.\n\n\
+                - \x1b[1m-l\x1b[0m long listing with sizes and dates\n\
+                - \x1b[1m-a\x1b[0m hidden entries too";
+            vec![
+                Suggestion {
+                    description: Some(description.to_string()),
+                    ..fake_suggestion("ls", pos)
+                },
+                Suggestion {
+                    description: Some("Change the directory".to_string()),
+                    ..fake_suggestion("cd", pos)
+                },
+            ]
+        }
+    }
+
+    #[test]
+    fn description_wraps_at_the_panel_width() {
+        let mut menu = IdeMenu::default()
+            .with_name("testmenu")
+            .with_default_border();
+
+        let wide = lines_on_screen(
+            &mut menu,
+            &mut DescribedCompleter,
+            MenuEvent::Activate(false),
+            (80, 10),
+        );
+        assert_eq!(
+            wide,
+            [
+                "╭───╮ ╭──────────────────────────────────────╮",
+                "│>ls │ │List the entries of a directory.      │",
+                "│cd │ │                                      │",
+                "╰───╯ │- -l long listing with sizes and dates│",
+                "      │- -a hidden entries too               │",
+                "      ╰──────────────────────────────────────╯",
+            ]
+        );
+
+        let narrow = lines_on_screen(
+            &mut menu,
+            &mut DescribedCompleter,
+            MenuEvent::Activate(false),
+            (30, 20),
+        );
+        assert_eq!(
+            narrow,
+            [
+                "╭───╮ ╭──────────────────────╮",
+                "│>ls │ │List the entries of a │",
+                "│cd │ │directory.            │",
+                "╰───╯ │                      │",
+                "      │- -l long listing with│",
+                "      │  sizes and dates     │",
+                "      │- -a hidden entries   │",
+                "      │  too                 │",
+                "      ╰──────────────────────╯",
+            ]
+        );
+        // The painter makes room for the whole description
+        assert_eq!(menu.menu_required_lines(30), 9);
+    }
+
+    #[test]
+    fn description_scrolls_without_changing_the_selection() {
+        let mut menu = IdeMenu::default()
+            .with_name("testmenu")
+            .with_default_border()
+            .with_max_description_height(6);
+        let mut lines =
+            |event| lines_on_screen(&mut menu, &mut DescribedCompleter, event, (30, 20));
+
+        let top = [
+            "╭───╮ ╭──────────────────────╮",
+            "│>ls │ │List the entries of a │",
+            "│cd │ │directory.            │",
+            "╰───╯ │                      │",
+            "      │↓ 1-3/7               │",
+            "      ╰──────────────────────╯",
+        ];
+        assert_eq!(lines(MenuEvent::Activate(false)), top);
+        assert_eq!(
+            lines(MenuEvent::ScrollDescriptionDown),
+            [
+                "╭───╮ ╭──────────────────────╮",
+                "│>ls │ │directory.            │",
+                "│cd │ │                      │",
+                "╰───╯ │- -l long listing with│",
+                "      │↑↓ 2-4/7              │",
+                "      ╰──────────────────────╯",
+            ]
+        );
+        for _ in 0..10 {
+            lines(MenuEvent::ScrollDescriptionDown);
+        }
+        assert_eq!(
+            lines(MenuEvent::ScrollDescriptionDown),
+            [
+                "╭───╮ ╭──────────────────────╮",
+                "│>ls │ │  sizes and dates     │",
+                "│cd │ │- -a hidden entries   │",
+                "╰───╯ │  too                 │",
+                "      │↑ 5-7/7               │",
+                "      ╰──────────────────────╯",
+            ]
+        );
+        assert_eq!(
+            lines(MenuEvent::ScrollDescriptionUp),
+            [
+                "╭───╮ ╭──────────────────────╮",
+                "│>ls │ │- -l long listing with│",
+                "│cd │ │  sizes and dates     │",
+                "╰───╯ │- -a hidden entries   │",
+                "      │↑↓ 4-6/7              │",
+                "      ╰──────────────────────╯",
+            ]
+        );
+        // Another selection shows its description from the top
+        lines(MenuEvent::NextElement);
+        assert_eq!(lines(MenuEvent::PreviousElement), top);
+    }
+
+    #[test]
+    fn styles_in_descriptions_survive_wrapping() {
+        let menu = IdeMenu::default();
+        let bold = Style::new().bold();
+        let description = bold.paint("bold words").to_string();
+
+        let lines = menu.create_description(description, true, 5, 5, 0);
+
+        assert_eq!(lines.len(), 2);
+        for line in lines {
+            assert!(line.contains(&bold.prefix().to_string()));
+            assert_eq!(line_width(&line), 5);
+        }
+    }
+
+    #[test]
+    fn resizing_lays_the_menu_out_again() {
+        let mut completer = DefaultCompleter::new_with_wordlen(vec!["cargo".into()], 2);
+        let mut menu = IdeMenu::default();
+        let mut editor = Editor::default();
+        editor.set_buffer("c".to_string(), UndoBehavior::CreateUndoPoint);
+        let mut painter = Painter::new(std::io::BufWriter::new(std::io::stderr()));
+        painter.handle_resize(80, 10);
+        menu.menu_event(MenuEvent::Activate(false));
+        menu.update_working_details(&mut editor, &mut completer, &painter);
+        let space_right = menu.working_details.space_right;
+
+        painter.handle_resize(40, 10);
+        menu.update_working_details(&mut editor, &mut completer, &painter);
+
+        assert_eq!(menu.working_details.space_right, space_right - 40);
+    }
+
+    #[test]
+    fn typing_keeps_the_selected_value_selected() {
+        let mut completer = DefaultCompleter::new_with_wordlen(
+            vec!["cargo".into(), "cart".into(), "cat".into()],
+            2,
+        );
+        let mut menu = IdeMenu::default();
+        let mut editor = Editor::default();
+        editor.set_buffer("c".to_string(), UndoBehavior::CreateUndoPoint);
+        let mut painter = Painter::new(std::io::BufWriter::new(std::io::stderr()));
+        painter.handle_resize(80, 10);
+        for event in [MenuEvent::Activate(false), MenuEvent::NextElement] {
+            menu.menu_event(event);
+            menu.update_working_details(&mut editor, &mut completer, &painter);
+        }
+
+        let mut selected_after_typing = |buffer: &str| {
+            editor.set_buffer(buffer.to_string(), UndoBehavior::CreateUndoPoint);
+            menu.menu_event(MenuEvent::Edit(false));
+            menu.update_working_details(&mut editor, &mut completer, &painter);
+            menu.get_value().unwrap().value
+        };
+
+        // The selection falls back to the first value once its own is gone
+        assert_eq!(
+            [selected_after_typing("car"), selected_after_typing("carg")],
+            ["cart", "cargo"]
+        );
+    }
 }
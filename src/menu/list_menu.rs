@@ -1,18 +1,31 @@
 use {
-    super::{menu_functions::parse_selection_char, Menu, MenuBuilder, MenuEvent, MenuSettings},
+    super::{
+        menu_functions::parse_selection_char, Menu, MenuBuilder, MenuEvent, MenuSettings,
+        ScrollIndicator,
+    },
     crate::{
         core_editor::Editor,
-        menu_functions::{completer_input, replace_in_buffer},
-        painting::{estimate_single_line_wraps, Painter},
-        Completer, Suggestion,
+        enums::EditCommand,
+        history::best_match,
+        menu_functions::{
+            completer_input, compose_styles, kind_column_width, kind_marker, replace_in_buffer,
+            scroll_footer, scrollbar_track, style_suggestion, truncate_with_ansi,
+            with_scrollbar_glyph,
+        },
+        painting::{estimate_single_line_wraps, line_width, Painter},
+        Completer, MatchingMode, Suggestion,
     },
-    nu_ansi_term::ansi::RESET,
-    std::{fmt::Write, iter::Sum},
+    chrono::{DateTime, FixedOffset, Local, TimeZone, Utc},
+    nu_ansi_term::{ansi::RESET, Style},
+    std::{borrow::Cow, fmt::Write, iter::Sum},
     unicode_width::UnicodeWidthStr,
 };
 
 const SELECTION_CHAR: char = '!';
 
+/// Terminals narrower than this show no age column
+const MIN_COLUMNS_FOR_AGE: u16 = 40;
+
 struct Page {
     size: usize,
     full: bool,
@@ -67,6 +80,29 @@ pub struct ListMenu {
     event: Option<MenuEvent>,
     /// String collected after the menu is activated
     input: Option<String>,
+    /// Message shown instead of the status bar until the next event
+    notice: Option<String>,
+    /// Whether to show how long ago each value was recorded
+    show_age: bool,
+    /// Screen width the pages were laid out for
+    columns: u16,
+    /// Current time the ages are computed from
+    clock: fn() -> DateTime<Utc>,
+    /// Time zone the dates of older values are shown in, `None` for the
+    /// local one
+    time_zone: Option<FixedOffset>,
+    /// How long ago each value was recorded, computed when the values are
+    /// updated, or `None` when no value has a timestamp
+    ages: Option<Vec<Option<String>>>,
+    /// How keys typed into the menu filter its values, if they do
+    type_to_filter: Option<MatchingMode>,
+    /// Text typed into the menu to filter its values
+    filter: String,
+    /// Every value of the query while a filter is typed
+    unfiltered: Option<Vec<Suggestion>>,
+    /// Value selected before the line was edited, selected again if the
+    /// updated values still list it
+    kept_selection: Option<String>,
 }
 
 impl Default for ListMenu {
@@ -87,6 +123,16 @@ impl Default for ListMenu {
             pages: Vec::new(),
             event: None,
             input: None,
+            notice: None,
+            show_age: false,
+            columns: 0,
+            clock: Utc::now,
+            time_zone: None,
+            ages: None,
+            type_to_filter: None,
+            filter: String::new(),
+            unfiltered: None,
+            kept_selection: None,
         }
     }
 }
@@ -113,6 +159,29 @@ impl ListMenu {
         self.max_lines = max_lines;
         self
     }
+
+    /// Menu builder showing how long ago each entry was recorded, e.g. `3h`,
+    /// dimmed at the right end of its first line
+    ///
+    /// The first lines are cut short to make room. The column is left out
+    /// when no entry has a timestamp, such as with a history that doesn't
+    /// record them, and on terminals narrower than 40 columns.
+    #[must_use]
+    pub fn with_age_column(mut self, show_age: bool) -> Self {
+        self.show_age = show_age;
+        self
+    }
+
+    /// Menu builder letting keys typed while the menu is open filter its
+    /// values, matched in `matching` mode, instead of editing the line
+    ///
+    /// The filter is shown in the status row. Backspace edits it and Esc
+    /// clears it, closing the menu only when it is empty.
+    #[must_use]
+    pub fn with_type_to_filter(mut self, matching: Option<MatchingMode>) -> Self {
+        self.type_to_filter = matching;
+        self
+    }
 }
 
 // Menu functionality
@@ -128,14 +197,88 @@ impl ListMenu {
     }
 
     /// The number of rows an entry from the menu can take considering wrapping
+    ///
+    /// With the age column, the first line is cut to fit next to it instead
+    /// of wrapping.
     fn number_of_lines(&self, entry: &str, terminal_columns: u16) -> u16 {
+        let entry = if self.ages().is_some() {
+            // An empty first line stands for the cut one
+            &entry[entry.find('\n').unwrap_or(entry.len())..]
+        } else {
+            entry
+        };
         number_of_lines(entry, self.max_lines as usize, terminal_columns)
     }
 
+    /// How long ago each value was recorded, or `None` without an age column
+    fn ages(&self) -> Option<&[Option<String>]> {
+        if !self.show_age || self.columns < MIN_COLUMNS_FOR_AGE {
+            return None;
+        }
+        self.ages.as_deref()
+    }
+
+    /// Computes the ages of the current values
+    fn update_ages(&mut self) {
+        self.ages = None;
+        if !self.show_age {
+            return;
+        }
+        let now = (self.clock)();
+        let ages: Vec<_> = self
+            .get_values()
+            .iter()
+            .map(|suggestion| {
+                suggestion.timestamp.map(|timestamp| match self.time_zone {
+                    Some(time_zone) => format_age(timestamp, now, &time_zone),
+                    None => format_age(timestamp, now, &Local),
+                })
+            })
+            .collect();
+        self.ages = ages.iter().any(Option::is_some).then_some(ages);
+    }
+
+    /// Whether `value` matches the typed filter
+    fn matches_filter(&self, value: &str) -> bool {
+        match self.type_to_filter {
+            None => true,
+            Some(MatchingMode::Prefix) => value.starts_with(&self.filter),
+            Some(MatchingMode::Substring) => value.contains(&self.filter),
+            Some(MatchingMode::Fuzzy) => {
+                let needle: Vec<char> = self.filter.chars().collect();
+                let haystack: Vec<char> = value.chars().collect();
+                best_match(&needle, &haystack).is_some()
+            }
+        }
+    }
+
+    /// Forget the typed filter, returning whether there was one
+    fn reset_filter(&mut self) -> bool {
+        self.unfiltered = None;
+        !std::mem::take(&mut self.filter).is_empty()
+    }
+
     fn total_values(&self) -> usize {
         self.query_size.unwrap_or(self.values.len())
     }
 
+    /// Whether more values than fit on the page are shown with a scrollbar
+    fn has_scrollbar(&self) -> bool {
+        self.settings.scroll_indicator == ScrollIndicator::Scrollbar
+            && self.columns > 0
+            && self
+                .pages
+                .get(self.page)
+                .map_or(false, |page| page.size < self.total_values())
+    }
+
+    /// Column the lines end at, keeping the last one free, one more for a
+    /// scrollbar and the ones of the border
+    fn right_edge(&self) -> usize {
+        (self.columns as usize)
+            .saturating_sub(1 + usize::from(self.has_scrollbar()) + self.settings.border_width())
+    }
+
     fn values_until_current_page(&self) -> usize {
         self.pages.iter().take(self.page + 1).sum::<Page>().size
     }
@@ -165,36 +308,114 @@ impl ListMenu {
     }
 
     fn printable_entries(&self, painter: &Painter) -> usize {
+        self.printable_entries_of(self.get_values(), painter)
+    }
+
+    /// How many of `values` fit on a page, from the first one
+    fn printable_entries_of(&self, values: &[Suggestion], painter: &Painter) -> usize {
         // The number 2 comes from the prompt line and the banner printed at the bottom
         // of the menu
-        let available_lines = painter.screen_height().saturating_sub(2);
-        let (printable_entries, _) =
-            self.get_values()
+        let available_lines = painter
+            .screen_height()
+            .saturating_sub(2 + self.settings.border_rows());
+        let screen_width = painter
+            .screen_width()
+            .saturating_sub(self.settings.border_width() as u16);
+        let (printable_entries, _) = values.iter().fold(
+            (0, Some(0)),
+            |(lines, total_lines), suggestion| match total_lines {
+                None => (lines, None),
+                Some(total_lines) => {
+                    let new_total_lines = total_lines
+                        + self.number_of_lines(
+                            suggestion.display_value(),
+                            //  to account for the index and the indicator e.g. 0: XXXX
+                            screen_width.saturating_sub(
+                                self.indicator().width() as u16 + count_digits(lines),
+                            ),
+                        );
+
+                    if new_total_lines < available_lines {
+                        (lines + 1, Some(new_total_lines))
+                    } else {
+                        (lines, None)
+                    }
+                }
+            },
+        );
+
+        printable_entries
+    }
+
+    /// Select `value` on the page it is on, if the values still list it
+    ///
+    /// Every value is only completed when the first page doesn't list it.
+    fn select_kept_value(
+        &mut self,
+        value: &str,
+        editor: &Editor,
+        completer: &mut dyn Completer,
+        painter: &Painter,
+    ) {
+        let first_page = self.pages.first().map_or(0, |page| page.size);
+        if let Some(row) = self
+            .values
+            .iter()
+            .take(first_page)
+            .position(|suggestion| suggestion.value == value)
+        {
+            self.row_position = row as u16;
+            return;
+        }
+
+        let values = match &self.unfiltered {
+            Some(unfiltered) => unfiltered
                 .iter()
-                .fold(
-                    (0, Some(0)),
-                    |(lines, total_lines), suggestion| match total_lines {
-                        None => (lines, None),
-                        Some(total_lines) => {
-                            let new_total_lines = total_lines
-                                + self.number_of_lines(
-                                    suggestion.display_value(),
-                                    //  to account for the index and the indicator e.g. 0: XXXX
-                                    painter.screen_width().saturating_sub(
-                                        self.indicator().width() as u16 + count_digits(lines),
-                                    ),
-                                );
-
-                            if new_total_lines < available_lines {
-                                (lines + 1, Some(new_total_lines))
-                            } else {
-                                (lines, None)
-                            }
-                        }
-                    },
+                .filter(|suggestion| self.matches_filter(&suggestion.value))
+                .cloned()
+                .collect(),
+            None => {
+                let (input, pos) = completer_input(
+                    editor.get_buffer(),
+                    editor.insertion_point(),
+                    self.input.as_deref(),
+                    self.settings.only_buffer_difference,
                 );
+                completer.complete(&input, pos)
+            }
+        };
+        let index = match values
+            .iter()
+            .position(|suggestion| suggestion.value == value)
+        {
+            Some(index) => index,
+            None => return,
+        };
 
-        printable_entries
+        // Lay out the pages up to the one with the value, as paging to it would
+        let mut pages = Vec::new();
+        let mut skip = 0;
+        loop {
+            let rest = &values[skip..];
+            let size = self.printable_entries_of(rest, painter).max(1);
+            pages.push(Page {
+                size,
+                full: size < rest.len(),
+            });
+            if index < skip + size {
+                break;
+            }
+            skip += size;
+        }
+        self.page = pages.len() - 1;
+        self.row_position = (index - skip) as u16;
+        self.values = values[skip..]
+            .iter()
+            .take(pages[self.page].size)
+            .cloned()
+            .collect();
+        self.pages = pages;
+        self.update_ages();
     }
 
     fn no_page_msg(&self, use_ansi_coloring: bool) -> String {
@@ -221,14 +442,29 @@ impl ListMenu {
         };
 
         let full_page = if page.full { "[FULL]" } else { "" };
-        let status_bar = format!(
-            "Page {}: records {} - {}  total: {}  {}",
-            self.page + 1,
-            value_before,
-            values_until,
-            self.total_values(),
-            full_page,
-        );
+        let position = match self.settings.scroll_indicator {
+            ScrollIndicator::Footer if page.size < self.total_values() => format!(
+                "{}  ",
+                scroll_footer(value_before + self.index(), self.total_values())
+            ),
+            _ => String::new(),
+        };
+        let filter = if self.filter.is_empty() {
+            String::new()
+        } else {
+            format!("Filter: {}  ", self.filter)
+        };
+        let status_bar = match &self.notice {
+            Some(notice) => notice.clone(),
+            None => format!(
+                "{filter}{position}Page {}: records {} - {}  total: {}  {}",
+                self.page + 1,
+                value_before,
+                values_until,
+                self.total_values(),
+                full_page,
+            ),
+        };
 
         if use_ansi_coloring {
             format!(
@@ -248,42 +484,134 @@ impl ListMenu {
     }
 
     /// Text style for menu
-    fn text_style(&self, index: usize) -> String {
-        if index == self.index() {
-            self.settings.color.selected_text_style.prefix().to_string()
+    /// Style of the value of `suggestion`, its own style if it has one
+    fn text_style(&self, suggestion: &Suggestion, index: usize) -> Style {
+        let colors = &self.settings.color;
+        match (index == self.index(), suggestion.style) {
+            (true, Some(style)) => compose_styles(&colors.selected_text_style, &style),
+            (true, None) => colors.selected_text_style,
+            (false, style) => style.unwrap_or(colors.text_style),
+        }
+    }
+
+    /// The value of a suggestion with its matched text emphasized, limited to
+    /// `max_lines` lines
+    fn format_value(
+        &self,
+        suggestion: &Suggestion,
+        index: usize,
+        use_ansi_coloring: bool,
+    ) -> String {
+        let value = suggestion.display_value();
+        // Emphasize before cutting the lines, the match indices refer to the whole value
+        let value = match &suggestion.match_indices {
+            Some(match_indices) if use_ansi_coloring && !match_indices.is_empty() => {
+                let colors = &self.settings.color;
+                let selected = index == self.index();
+                let match_style = if selected {
+                    &colors.selected_match_style
+                } else {
+                    &colors.match_style
+                };
+                let selected_style = self.text_style(suggestion, index);
+                Cow::Owned(style_suggestion(
+                    value,
+                    match_indices,
+                    &suggestion.style.unwrap_or(colors.text_style),
+                    match_style,
+                    selected.then_some(&selected_style),
+                ))
+            }
+            _ => Cow::Borrowed(value),
+        };
+
+        if value.lines().count() > self.max_lines as usize {
+            let lines = value.lines().take(self.max_lines as usize).fold(
+                String::new(),
+                |mut out_string, string| {
+                    let _ = write!(out_string, "{}\r\n{}", string, self.multiline_marker);
+                    out_string
+                },
+            );
+
+            lines + "..."
+        } else {
+            value.replace('\n', &format!("\r\n{}", self.multiline_marker))
+        }
+    }
+
+    /// The first line of `line` cut to leave room for an age column of
+    /// `age_width` at the right end of the terminal, followed by `age`
+    ///
+    /// `prefix_width` is the width of what comes before `line` on the row.
+    fn with_age(
+        &self,
+        line: &str,
+        prefix_width: usize,
+        age: Option<&str>,
+        age_width: usize,
+        use_ansi_coloring: bool,
+    ) -> String {
+        let (first_line, rest) = line.split_at(line.find("\r\n").unwrap_or(line.len()));
+        let right_edge = self.right_edge();
+        let text_width = right_edge
+            .saturating_sub(prefix_width + age_width + 1)
+            .max(3);
+        let first_line = truncate_with_ansi(first_line, text_width);
+
+        let age = age.unwrap_or_default();
+        let padding = " ".repeat(
+            right_edge
+                .saturating_sub(prefix_width + line_width(&first_line) + age.width())
+                .max(1),
+        );
+        if use_ansi_coloring {
+            format!(
+                "{first_line}{RESET}{padding}{}{}{rest}",
+                Style::new().dimmed().paint(age),
+                self.settings.color.text_style.prefix(),
+            )
         } else {
-            self.settings.color.text_style.prefix().to_string()
+            format!("{first_line}{padding}{age}{rest}")
         }
     }
 
     /// Creates default string that represents one line from a menu
+    ///
+    /// `kind_width` is the width of the column showing the kinds of the values.
     fn create_string(
         &self,
         line: &str,
-        description: Option<&str>,
+        suggestion: &Suggestion,
         index: usize,
         row_number: &str,
+        kind_width: usize,
         use_ansi_coloring: bool,
     ) -> String {
-        let description = description.map_or("".to_string(), |desc| {
-            if use_ansi_coloring {
-                format!(
-                    "{}({}) {}",
-                    self.settings.color.description_style.prefix(),
-                    desc,
-                    RESET
-                )
-            } else {
-                format!("({desc}) ")
-            }
-        });
+        let kind = kind_marker(suggestion, kind_width, self.settings.kind_icons);
+        let description = suggestion
+            .description
+            .as_deref()
+            .map_or("".to_string(), |desc| {
+                if use_ansi_coloring {
+                    format!(
+                        "{}({}) {}",
+                        self.settings.color.description_style.prefix(),
+                        desc,
+                        RESET
+                    )
+                } else {
+                    format!("({desc}) ")
+                }
+            });
 
         if use_ansi_coloring {
             format!(
-                "{}{}{}{}{}{}",
+                "{}{}{}{}{}{}{}",
                 row_number,
+                self.settings.color.description_style.paint(kind),
                 description,
-                self.text_style(index),
+                self.text_style(suggestion, index).prefix(),
                 &line,
                 RESET,
                 Self::end_of_line(),
@@ -292,9 +620,15 @@ impl ListMenu {
             // If no ansi coloring is found, then the selection word is
             // the line in uppercase
             let line_str = if index == self.index() {
-                format!("{}{}>{}", row_number, description, line.to_uppercase())
+                format!(
+                    "{}{}{}>{}",
+                    row_number,
+                    kind,
+                    description,
+                    line.to_uppercase()
+                )
             } else {
-                format!("{row_number}{description}{line}")
+                format!("{row_number}{kind}{description}{line}")
             };
 
             // Final string with formatting
@@ -332,11 +666,20 @@ impl Menu for ListMenu {
 
     /// Selects what type of event happened with the menu
     fn menu_event(&mut self, event: MenuEvent) {
+        self.notice = None;
         match &event {
             MenuEvent::Activate(_) => self.active = true,
             MenuEvent::Deactivate => {
                 self.active = false;
                 self.input = None;
+                self.reset_filter();
+            }
+            MenuEvent::Notice(notice) => {
+                self.notice = Some(notice.clone());
+                return;
+            }
+            MenuEvent::Edit(_) if self.settings.keep_selection => {
+                self.kept_selection = self.get_value().map(|suggestion| suggestion.value);
             }
             _ => {}
         }
@@ -359,6 +702,10 @@ impl Menu for ListMenu {
 
         let parsed = parse_selection_char(&input, SELECTION_CHAR);
         self.update_row_pos(parsed.index);
+        // A typed row selector takes precedence over the kept selection
+        if parsed.index.is_some() {
+            self.kept_selection = None;
+        }
 
         // If there are no row selector and the menu has an Edit event, this clears
         // the position together with the pages vector
@@ -366,48 +713,49 @@ impl Menu for ListMenu {
             self.reset_position();
         }
 
-        self.values = if parsed.remainder.is_empty() {
+        // Counting every match can take a while in a large history, so it is
+        // only done again when the text searched for changes
+        if self.filter.is_empty() && self.query_size.is_none()
+            || matches!(
+                self.event,
+                Some(MenuEvent::Activate(_) | MenuEvent::Edit(_))
+            )
+        {
             self.query_size = Some(completer.total_completions(parsed.remainder, pos));
+        }
 
-            let skip = self.pages.iter().take(self.page).sum::<Page>().size;
-            let take = self
-                .pages
-                .get(self.page)
-                .map(|page| page.size)
-                .unwrap_or(self.page_size);
+        let skip = self.pages.iter().take(self.page).sum::<Page>().size;
+        let take = self
+            .pages
+            .get(self.page)
+            .map(|page| page.size)
+            .unwrap_or(self.page_size);
 
-            completer.partial_complete(&input, pos, skip, take)
+        if self.filter.is_empty() {
+            self.values = completer.partial_complete(&input, pos, skip, take);
         } else {
-            self.query_size = None;
-            completer.complete(&input, pos)
+            // The filter is matched here, against every value of the query
+            let unfiltered = match self.unfiltered.take() {
+                Some(unfiltered) => unfiltered,
+                None => completer.complete(&input, pos),
+            };
+            let filtered: Vec<_> = unfiltered
+                .iter()
+                .filter(|suggestion| self.matches_filter(&suggestion.value))
+                .cloned()
+                .collect();
+            self.unfiltered = Some(unfiltered);
+            self.query_size = Some(filtered.len());
+            self.values = filtered.into_iter().skip(skip).take(take).collect();
         }
+        self.update_ages();
     }
 
     /// Gets values from cached values that will be displayed in the menu
+    ///
+    /// Only the current page is pulled from the completer.
     fn get_values(&self) -> &[Suggestion] {
-        if self.query_size.is_some() {
-            // When there is a size value it means that only a chunk of the
-            // chronological data from the database was collected
-            &self.values
-        } else {
-            // If no record then it means that the values hold the result
-            // from the query to the database. This slice can be used to get the
-            // data that will be shown in the menu
-            if self.values.is_empty() {
-                return &self.values;
-            }
-
-            let start = self.pages.iter().take(self.page).sum::<Page>().size;
-
-            let end: usize = if self.page >= self.pages.len() {
-                self.page_size + start
-            } else {
-                self.pages.iter().take(self.page + 1).sum::<Page>().size
-            };
-
-            let end = end.min(self.total_values());
-            &self.values[start..end]
-        }
+        &self.values
     }
 
     /// The buffer gets cleared with the actual value
@@ -421,6 +769,21 @@ impl Menu for ListMenu {
         completer: &mut dyn Completer,
         painter: &Painter,
     ) {
+        let resized = self.columns != painter.screen_width();
+        self.columns = painter.screen_width();
+        if resized && self.event.is_none() && self.is_active() {
+            // Lay the pages out again for the new width, keeping the selection
+            let selected = self.get_value().map(|suggestion| suggestion.value);
+            self.reset_position();
+            self.update_values(editor, completer);
+            self.pages.push(Page {
+                size: self.printable_entries(painter),
+                full: false,
+            });
+            if let Some(value) = selected {
+                self.select_kept_value(&value, editor, completer, painter);
+            }
+        }
         if let Some(event) = self.event.clone() {
             match event {
                 MenuEvent::Activate(_) => {
@@ -436,10 +799,11 @@ impl Menu for ListMenu {
                 MenuEvent::Deactivate => {}
                 MenuEvent::Edit(_) => {
                     self.update_values(editor, completer);
-                    self.pages.push(Page {
-                        size: self.printable_entries(painter),
-                        full: false,
-                    });
+                    let size = self.printable_entries(painter);
+                    self.pages.push(Page { size, full: false });
+                    if let Some(value) = self.kept_selection.take() {
+                        self.select_kept_value(&value, editor, completer, painter);
+                    }
                 }
                 MenuEvent::NextElement | MenuEvent::MoveDown | MenuEvent::MoveRight => {
                     let new_pos = self.row_position + 1;
@@ -456,7 +820,7 @@ impl Menu for ListMenu {
                 MenuEvent::PreviousElement | MenuEvent::MoveUp | MenuEvent::MoveLeft => {
                     if let Some(new_pos) = self.row_position.checked_sub(1) {
                         self.row_position = new_pos;
-                    } else {
+                    } else if self.page > 0 || self.settings.wrap {
                         let page = if let Some(page) = self.page.checked_sub(1) {
                             self.pages.get(page)
                         } else {
@@ -490,7 +854,7 @@ impl Menu for ListMenu {
 
                         self.update_values(editor, completer);
                         self.set_actual_page_size(self.printable_entries(painter));
-                    } else {
+                    } else if self.settings.wrap {
                         self.row_position = 0;
                         self.page = 0;
                         self.update_values(editor, completer);
@@ -499,10 +863,33 @@ impl Menu for ListMenu {
                 MenuEvent::PreviousPage => {
                     match self.page.checked_sub(1) {
                         Some(page_num) => self.page = page_num,
-                        None => self.page = self.pages.len().saturating_sub(1),
+                        None if self.settings.wrap => {
+                            self.page = self.pages.len().saturating_sub(1)
+                        }
+                        None => {}
                     }
                     self.update_values(editor, completer);
                 }
+                MenuEvent::Refresh => {
+                    // Count the values again
+                    self.query_size = None;
+                    self.unfiltered = None;
+                    self.update_values(editor, completer);
+                    // The last value of a page went, select the one before it
+                    if self.values.is_empty() && self.page > 0 {
+                        self.page -= 1;
+                        self.row_position = u16::MAX;
+                        self.update_values(editor, completer);
+                    }
+                    self.row_position = self
+                        .row_position
+                        .min(self.values.len().saturating_sub(1) as u16);
+                }
+                MenuEvent::Select(_)
+                | MenuEvent::Accept
+                | MenuEvent::Notice(_)
+                | MenuEvent::ScrollDescriptionUp
+                | MenuEvent::ScrollDescriptionDown => {}
             }
 
             self.event = None;
@@ -512,8 +899,9 @@ impl Menu for ListMenu {
     /// Calculates the real required lines for the menu considering how many lines
     /// wrap the terminal and if an entry is larger than the remaining lines
     fn menu_required_lines(&self, terminal_columns: u16) -> u16 {
+        let terminal_columns = terminal_columns.saturating_sub(self.settings.border_width() as u16);
         let mut entry_index = 0;
-        self.get_values().iter().fold(0, |total_lines, suggestion| {
+        let entry_lines = self.get_values().iter().fold(0, |total_lines, suggestion| {
             //  to account for the the index and the indicator e.g. 0: XXXX
             let ret = total_lines
                 + self.number_of_lines(
@@ -524,7 +912,9 @@ impl Menu for ListMenu {
                 );
             entry_index += 1;
             ret
-        }) + 1
+        });
+        // The banner takes one more line
+        entry_lines + 1 + self.settings.border_rows()
     }
 
     /// Creates the menu representation as a string which will be painted by the painter
@@ -532,54 +922,119 @@ impl Menu for ListMenu {
         let values_before_page = self.pages.iter().take(self.page).sum::<Page>().size;
         match self.pages.get(self.page) {
             Some(page) => {
-                let lines_string = self
+                let ages = self.ages();
+                let age_width = ages
+                    .into_iter()
+                    .flatten()
+                    .flatten()
+                    .map(|age| age.width())
+                    .max()
+                    .unwrap_or(0);
+                let shown = &self.values[..page.size.min(self.values.len())];
+                let kind_width = kind_column_width(shown, self.settings.kind_icons);
+                let mut lines = self
                     .get_values()
                     .iter()
                     .take(page.size)
                     .enumerate()
                     .map(|(index, suggestion)| {
-                        // Final string with colors
-                        let line = suggestion.display_value();
-                        let line = if line.lines().count() > self.max_lines as usize {
-                            let lines = line.lines().take(self.max_lines as usize).fold(
-                                String::new(),
-                                |mut out_string, string| {
-                                    let _ = write!(
-                                        out_string,
-                                        "{}\r\n{}",
-                                        string, self.multiline_marker
-                                    );
-                                    out_string
-                                },
-                            );
-
-                            lines + "..."
-                        } else {
-                            line.replace('\n', &format!("\r\n{}", self.multiline_marker))
-                        };
-
+                        let mut line = self.format_value(suggestion, index, use_ansi_coloring);
                         let row_number = format!("{}: ", index + values_before_page);
+                        if let Some(ages) = ages {
+                            // The description is shown as `(description) `, and
+                            // the selected value follows a `>` without colors
+                            let description_width = suggestion
+                                .description
+                                .as_ref()
+                                .map_or(0, |description| description.width() + 3);
+                            let marker_width =
+                                usize::from(!use_ansi_coloring && index == self.index());
+                            line = self.with_age(
+                                &line,
+                                row_number.width() + kind_width + description_width + marker_width,
+                                ages[index].as_deref(),
+                                age_width,
+                                use_ansi_coloring,
+                            );
+                        }
 
                         self.create_string(
                             &line,
-                            suggestion.description.as_deref(),
+                            suggestion,
                             index,
                             &row_number,
+                            kind_width,
                             use_ansi_coloring,
                         )
                     })
-                    .collect::<String>();
+                    .collect::<Vec<String>>();
 
-                format!(
+                if self.has_scrollbar() {
+                    let shown = values_before_page..values_before_page + lines.len();
+                    let track = scrollbar_track(self.total_values(), shown, lines.len());
+                    for (line, glyph) in lines.iter_mut().zip(track.into_iter().flatten()) {
+                        *line = with_scrollbar_glyph(line, self.right_edge(), glyph);
+                    }
+                }
+
+                let menu_string = format!(
                     "{}{}",
-                    lines_string,
+                    lines.concat(),
                     self.banner_message(page, use_ansi_coloring)
-                )
+                );
+                match &self.settings.border {
+                    Some(border) => border
+                        .frame(
+                            menu_string.split("\r\n").map(String::from).collect(),
+                            self.right_edge() + usize::from(self.has_scrollbar()),
+                            use_ansi_coloring,
+                        )
+                        .join("\r\n"),
+                    None => menu_string,
+                }
             }
             None => self.no_page_msg(use_ansi_coloring),
         }
     }
 
+    fn selected_value(&self) -> Option<Suggestion> {
+        self.get_value()
+    }
+
+    /// With type to filter, printable keys go to the filter and Backspace
+    /// edits it
+    fn edit_filter(&mut self, commands: &[EditCommand]) -> bool {
+        if self.type_to_filter.is_none() || !self.active {
+            return false;
+        }
+        let typed: Option<String> = commands
+            .iter()
+            .map(|command| match command {
+                EditCommand::InsertChar(c) if !c.is_control() => Some(*c),
+                _ => None,
+            })
+            .collect();
+        match (typed, commands) {
+            (Some(typed), _) if !typed.is_empty() => self.filter.push_str(&typed),
+            (_, [EditCommand::Backspace]) if !self.filter.is_empty() => {
+                self.filter.pop();
+            }
+            _ => return false,
+        }
+        self.notice = None;
+        self.event = Some(MenuEvent::Edit(false));
+        true
+    }
+
+    fn clear_filter(&mut self) -> bool {
+        let cleared = self.reset_filter();
+        if cleared {
+            self.notice = None;
+            self.event = Some(MenuEvent::Edit(false));
+        }
+        cleared
+    }
+
     /// Minimum rows that should be displayed by the menu
     fn min_rows(&self) -> u16 {
         self.max_lines + 1
@@ -609,6 +1064,35 @@ fn number_of_lines(entry: &str, max_lines: usize, terminal_columns: u16) -> u16
     lines
 }
 
+/// How long ago `timestamp` was, e.g. `2m`, `3h`, `yesterday` or, beyond a
+/// week, the date in `time_zone`
+fn format_age<Tz: TimeZone>(timestamp: DateTime<Utc>, now: DateTime<Utc>, time_zone: &Tz) -> String
+where
+    Tz::Offset: std::fmt::Display,
+{
+    const MINUTE: i64 = 60;
+    const HOUR: i64 = 60 * MINUTE;
+    const DAY: i64 = 24 * HOUR;
+
+    let seconds = now.signed_duration_since(timestamp).num_seconds();
+    if seconds < MINUTE {
+        "now".to_string()
+    } else if seconds < HOUR {
+        format!("{}m", seconds / MINUTE)
+    } else if seconds < DAY {
+        format!("{}h", seconds / HOUR)
+    } else if seconds < 2 * DAY {
+        "yesterday".to_string()
+    } else if seconds < 7 * DAY {
+        format!("{}d", seconds / DAY)
+    } else {
+        timestamp
+            .with_timezone(time_zone)
+            .format("%Y-%m-%d")
+            .to_string()
+    }
+}
+
 fn count_digits(mut n: usize) -> u16 {
     // count the digits in the number
     if n == 0 {
@@ -625,6 +1109,183 @@ fn count_digits(mut n: usize) -> u16 {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::{DefaultCompleter, MenuBorder, Span, SuggestionKind, UndoBehavior};
+    use nu_ansi_term::{Color, Style};
+    use rstest::rstest;
+
+    const EMPHASIS: &str = "\u{1b}[1m";
+
+    fn suggestion(value: &str, match_indices: Vec<usize>) -> Suggestion {
+        Suggestion {
+            value: value.to_string(),
+            match_indices: Some(match_indices),
+            ..Default::default()
+        }
+    }
+
+    /// A menu whose only style is the bold emphasis of matches
+    fn plain_menu() -> ListMenu {
+        ListMenu::default()
+            .with_text_style(Style::new())
+            .with_selected_text_style(Style::new())
+            .with_match_text_style(Style::new().bold())
+            .with_max_entry_lines(2)
+    }
+
+    // `{R}` stands for a reset, `{E}` for the emphasis of a match
+    #[rstest]
+    #[case("git status", vec![0, 1, 2], "{R}{R}{E}git{R} status")]
+    #[case("git status", vec![4, 5, 6, 7], "{R}git {R}{E}stat{R}us")]
+    #[case("git status", vec![8, 9], "{R}git stat{R}{E}us{R}")]
+    #[case("ls; ls -l", vec![0, 1, 4, 5], "{R}{R}{E}ls{R}; {R}{E}ls{R} -l")]
+    #[case("echo ä; ls", vec![8, 9], "{R}echo ä; {R}{E}ls{R}")]
+    #[case("git status", vec![], "git status")]
+    fn format_value_emphasizes_matches(
+        #[case] value: &str,
+        #[case] match_indices: Vec<usize>,
+        #[case] expected: &str,
+    ) {
+        let menu = plain_menu();
+
+        let line = menu.format_value(&suggestion(value, match_indices), 1, true);
+
+        let expected = expected.replace("{R}", RESET).replace("{E}", EMPHASIS);
+        assert_eq!(line, expected);
+    }
+
+    #[test]
+    fn format_value_emphasis_survives_cutting_lines() {
+        let menu = plain_menu();
+        let value = "ls a\nls b\nls c";
+
+        let line = menu.format_value(&suggestion(value, vec![0, 1, 5, 6, 10, 11]), 1, true);
+
+        assert_eq!(
+            line,
+            format!(
+                "{RESET}{RESET}{EMPHASIS}ls{RESET} a\r\n:::\
+                 {RESET}{EMPHASIS}ls{RESET} b\r\n:::..."
+            )
+        );
+    }
+
+    #[test]
+    fn format_value_without_ansi_coloring_is_plain() {
+        let menu = plain_menu();
+
+        let line = menu.format_value(&suggestion("git status", vec![0, 1, 2]), 1, false);
+
+        assert_eq!(line, "git status");
+    }
+
+    fn now() -> DateTime<Utc> {
+        "2026-03-01T12:00:00Z".parse().unwrap()
+    }
+
+    fn seconds_ago(seconds: i64) -> DateTime<Utc> {
+        now() - chrono::Duration::try_seconds(seconds).unwrap()
+    }
+
+    /// A menu of `columns` showing history entries recorded before [`now`]
+    fn aged_menu(columns: u16, timestamps: bool) -> ListMenu {
+        let entry = |value: &str, description: Option<&str>, age: i64| Suggestion {
+            value: value.to_string(),
+            description: description.map(str::to_string),
+            timestamp: timestamps.then(|| seconds_ago(age)),
+            ..Default::default()
+        };
+        let mut menu = ListMenu::default().with_age_column(true);
+        menu.values = vec![
+            entry("git status", None, 2 * 60 + 5),
+            entry(
+                "cargo test --workspace --all-features -- --nocapture",
+                Some("✓ 1.2s"),
+                3 * 3600,
+            ),
+            entry("ls\nls -a", None, 30 * 3600),
+            entry("make", Some("✗"), 55 * 86_400),
+        ];
+        menu.pages = vec![Page {
+            size: 4,
+            full: false,
+        }];
+        menu.row_position = 1;
+        menu.columns = columns;
+        menu.clock = now;
+        menu.time_zone = Some(FixedOffset::east_opt(0).unwrap());
+        menu.update_ages();
+        menu
+    }
+
+    #[test]
+    fn format_age_test() {
+        let ago = |seconds| format_age(seconds_ago(seconds), now(), &Utc);
+        assert_eq!(ago(-5), "now");
+        assert_eq!(ago(59), "now");
+        assert_eq!(ago(60), "1m");
+        assert_eq!(ago(3599), "59m");
+        assert_eq!(ago(3 * 3600 + 59), "3h");
+        assert_eq!(ago(36 * 3600), "yesterday");
+        assert_eq!(ago(6 * 86_400), "6d");
+        assert_eq!(ago(10 * 86_400), "2026-02-19");
+
+        // Dates are those of the given time zone
+        let auckland = FixedOffset::east_opt(13 * 3600).unwrap();
+        assert_eq!(
+            format_age(seconds_ago(10 * 86_400), now(), &auckland),
+            "2026-02-20"
+        );
+    }
+
+    #[rstest]
+    #[case::wide(
+        60,
+        "0: git status                                            2m\r\n\
+         1: (✓ 1.2s) >CARGO TEST --WORKSPACE --ALL-FEA...         3H\r\n\
+         2: ls                                             yesterday\r\n:::ls -a\r\n\
+         3: (✗) make                                      2026-01-05\r\n"
+    )]
+    #[case::narrow(
+        40,
+        "0: git status                        2m\r\n\
+         1: (✓ 1.2s) >CARGO TEST -...         3H\r\n\
+         2: ls                         yesterday\r\n:::ls -a\r\n\
+         3: (✗) make                  2026-01-05\r\n"
+    )]
+    fn age_column_is_right_aligned(#[case] columns: u16, #[case] expected: &str) {
+        let menu = aged_menu(columns, true);
+
+        let lines = menu.menu_string(10, false);
+
+        assert_eq!(lines.split_once("Page").unwrap().0, expected);
+    }
+
+    #[test]
+    fn age_column_is_left_out_without_timestamps_or_room() {
+        let plain = |columns, timestamps| {
+            aged_menu(columns, timestamps)
+                .with_age_column(false)
+                .menu_string(10, false)
+        };
+
+        assert_eq!(
+            aged_menu(60, false).menu_string(10, false),
+            plain(60, false)
+        );
+        assert_eq!(aged_menu(39, true).menu_string(10, false), plain(39, true));
+    }
+
+    #[test]
+    fn entries_cut_for_the_age_column_take_one_line() {
+        let long = "cargo test --workspace --all-features -- --nocapture";
+        let with_ages = aged_menu(40, true);
+        let without_ages = aged_menu(40, false);
+
+        assert_eq!(with_ages.number_of_lines(long, 30), 1);
+        assert_eq!(without_ages.number_of_lines(long, 30), 2);
+        assert_eq!(with_ages.number_of_lines("ls\nls -a", 30), 2);
+        assert_eq!(with_ages.menu_required_lines(40), 6);
+    }
 
     #[test]
     fn number_of_lines_test() {
@@ -659,4 +1320,317 @@ mod tests {
         // There is an extra line showing ...
         assert_eq!(res, 4);
     }
+
+    #[test]
+    fn values_are_painted_in_their_own_style() {
+        let menu = ListMenu::default();
+        let blue = Color::Blue.normal();
+        let styled = Suggestion {
+            value: "ls lib/".into(),
+            style: Some(blue),
+            ..Default::default()
+        };
+        let plain = Suggestion {
+            value: "ls log/".into(),
+            ..Default::default()
+        };
+
+        let row = |suggestion: &Suggestion, index| {
+            menu.create_string(&suggestion.value, suggestion, index, "0: ", 0, true)
+        };
+        let colors = &menu.settings.color;
+        let selected_blue = compose_styles(&colors.selected_text_style, &blue);
+        assert!(row(&styled, 0).contains(&selected_blue.prefix().to_string()));
+        assert!(row(&styled, 1).contains(&blue.prefix().to_string()));
+        assert!(row(&plain, 0).contains(&colors.selected_text_style.prefix().to_string()));
+        assert!(row(&plain, 1).contains(&colors.text_style.prefix().to_string()));
+    }
+
+    #[test]
+    fn rows_with_and_without_a_style_of_their_own() {
+        let mut menu = ListMenu::default();
+        let suggestion = |value: &str, style: Option<Style>| Suggestion {
+            value: value.into(),
+            style,
+            ..Default::default()
+        };
+        let suggestions = [
+            suggestion("ls lib/", Some(Color::Blue.normal())),
+            suggestion("ls lsd", None),
+            suggestion("ls old", Some(Color::DarkGray.dimmed())),
+        ];
+
+        let mut rendered = Vec::new();
+        for selected in 0..3 {
+            menu.row_position = selected;
+            rendered.push(format!("selected: {selected}"));
+            rendered.extend(suggestions.iter().enumerate().map(|(index, suggestion)| {
+                menu.create_string(&suggestion.value, suggestion, index, "0: ", 0, true)
+                    .trim_end()
+                    .replace('\u{1b}', "\\e")
+            }));
+        }
+
+        insta::assert_snapshot!(rendered.join("\n"));
+    }
+
+    #[rstest]
+    #[case::kept(true, ["cart", "cargo"])]
+    #[case::reset(false, ["cargo", "cargo"])]
+    fn typing_keeps_the_selected_value_selected(
+        #[case] keep_selection: bool,
+        #[case] selected: [&str; 2],
+    ) {
+        let mut completer = DefaultCompleter::new_with_wordlen(
+            vec!["cargo".into(), "cart".into(), "cat".into()],
+            2,
+        );
+        let mut menu = ListMenu::default()
+            .with_only_buffer_difference(false)
+            .with_keep_selection(keep_selection);
+        let mut editor = Editor::default();
+        editor.set_buffer("c".to_string(), UndoBehavior::CreateUndoPoint);
+        let mut painter = Painter::new(std::io::BufWriter::new(std::io::stderr()));
+        painter.handle_resize(80, 10);
+        for event in [MenuEvent::Activate(false), MenuEvent::NextElement] {
+            menu.menu_event(event);
+            menu.update_working_details(&mut editor, &mut completer, &painter);
+        }
+
+        let mut selected_after_typing = |buffer: &str| {
+            editor.set_buffer(buffer.to_string(), UndoBehavior::CreateUndoPoint);
+            menu.menu_event(MenuEvent::Edit(false));
+            menu.update_working_details(&mut editor, &mut completer, &painter);
+            menu.get_value().unwrap().value
+        };
+
+        assert_eq!(
+            [selected_after_typing("car"), selected_after_typing("carg")],
+            selected
+        );
+    }
+
+    #[test]
+    fn resizing_lays_the_pages_out_again() {
+        let words: Vec<String> = ["a", "b", "c"]
+            .iter()
+            .map(|letter| format!("c{}", letter.repeat(29)))
+            .collect();
+        let mut completer = DefaultCompleter::new_with_wordlen(words.clone(), 2);
+        let mut menu = ListMenu::default().with_only_buffer_difference(false);
+        let mut editor = Editor::default();
+        editor.set_buffer("c".to_string(), UndoBehavior::CreateUndoPoint);
+        let mut painter = Painter::new(std::io::BufWriter::new(std::io::stderr()));
+        painter.handle_resize(80, 6);
+        for event in [MenuEvent::Activate(false), MenuEvent::NextElement] {
+            menu.menu_event(event);
+            menu.update_working_details(&mut editor, &mut completer, &painter);
+        }
+        assert_eq!(menu.pages[0].size, 3);
+
+        // Wrapped in two lines, only one value fits on a page
+        painter.handle_resize(20, 6);
+        menu.update_working_details(&mut editor, &mut completer, &painter);
+
+        assert_eq!(menu.pages[0].size, 1);
+        assert_eq!(menu.get_value().unwrap().value, words[1]);
+    }
+
+    #[test]
+    fn kept_selections_on_later_pages_stay_selected() {
+        let mut completer = DefaultCompleter::new_with_wordlen(
+            ["cabal", "cache", "cargo", "cart", "cat"]
+                .iter()
+                .map(|word| word.to_string())
+                .collect(),
+            2,
+        );
+        let mut menu = ListMenu::default()
+            .with_only_buffer_difference(false)
+            .with_keep_selection(true);
+        let mut editor = Editor::default();
+        editor.set_buffer("c".to_string(), UndoBehavior::CreateUndoPoint);
+        let mut painter = Painter::new(std::io::BufWriter::new(std::io::stderr()));
+        // Two values fit on a page
+        painter.handle_resize(80, 5);
+        let mut send = |menu: &mut ListMenu, editor: &mut Editor, event| {
+            menu.menu_event(event);
+            menu.update_working_details(editor, &mut completer, &painter);
+        };
+        send(&mut menu, &mut editor, MenuEvent::Activate(false));
+        while menu.get_value().unwrap().value != "cat" {
+            send(&mut menu, &mut editor, MenuEvent::NextElement);
+        }
+        let page = menu.page;
+        assert!(page > 0);
+
+        editor.set_buffer("ca".to_string(), UndoBehavior::CreateUndoPoint);
+        send(&mut menu, &mut editor, MenuEvent::Edit(false));
+        assert_eq!(
+            (menu.page, menu.get_value().unwrap().value),
+            (page, "cat".into())
+        );
+
+        send(&mut menu, &mut editor, MenuEvent::PreviousElement);
+        assert_eq!(menu.get_value().unwrap().value, "cart");
+    }
+
+    struct KindCompleter;
+
+    impl Completer for KindCompleter {
+        fn complete(&mut self, _line: &str, pos: usize) -> Vec<Suggestion> {
+            [("ls", Some(SuggestionKind::Function)), ("lib.rs", None)]
+                .into_iter()
+                .map(|(value, kind)| Suggestion {
+                    value: value.to_string(),
+                    span: Span::new(0, pos),
+                    kind,
+                    ..Default::default()
+                })
+                .collect()
+        }
+    }
+
+    #[rstest]
+    #[case::icons(true, ["0: ƒ >LS", "1:   lib.rs"])]
+    #[case::labels(false, ["0: fn >LS", "1:    lib.rs"])]
+    fn kinds_are_shown_in_front_of_the_values(#[case] icons: bool, #[case] expected: [&str; 2]) {
+        let mut menu = ListMenu::default()
+            .with_only_buffer_difference(false)
+            .with_kind_icons(icons);
+        let mut editor = Editor::default();
+        editor.set_buffer("l".to_string(), UndoBehavior::CreateUndoPoint);
+        let mut painter = Painter::new(std::io::BufWriter::new(std::io::stderr()));
+        painter.handle_resize(50, 10);
+        menu.menu_event(MenuEvent::Activate(false));
+        menu.update_working_details(&mut editor, &mut KindCompleter, &painter);
+
+        let menu_string = menu.menu_string(10, false);
+        let lines: Vec<_> = menu_string.split("\r\n").take(2).collect();
+        assert_eq!(lines, expected);
+    }
+
+    /// The lines of a menu of twenty values showing `indicator`, after
+    /// sending it `events` on a screen `height` rows high
+    fn scrolled_lines(
+        indicator: ScrollIndicator,
+        events: &[MenuEvent],
+        height: u16,
+    ) -> Vec<String> {
+        let words = (0..20).map(|i| format!("c{i:02}")).collect();
+        let mut completer = DefaultCompleter::new_with_wordlen(words, 2);
+        let mut menu = ListMenu::default()
+            .with_only_buffer_difference(false)
+            .with_page_size(20)
+            .with_scroll_indicator(indicator);
+        let mut editor = Editor::default();
+        editor.set_buffer("c".to_string(), UndoBehavior::CreateUndoPoint);
+        let mut painter = Painter::new(std::io::BufWriter::new(std::io::stderr()));
+        painter.handle_resize(30, height);
+        for event in std::iter::once(&MenuEvent::Activate(false)).chain(events) {
+            menu.menu_event(event.clone());
+            menu.update_working_details(&mut editor, &mut completer, &painter);
+        }
+        menu.menu_string(height, false)
+            .split("\r\n")
+            .map(str::to_string)
+            .collect()
+    }
+
+    #[test]
+    fn scrollbar_thumb_follows_the_page_shown() {
+        let lines = |pages| {
+            let events = vec![MenuEvent::NextPage; pages];
+            scrolled_lines(ScrollIndicator::Scrollbar, &events, 7)
+        };
+
+        assert_eq!(
+            lines(0),
+            [
+                "0: >C00                     ┃",
+                "1: c01                      │",
+                "2: c02                      │",
+                "3: c03                      │",
+                "Page 1: records 0 - 3  total: 20  ",
+            ]
+        );
+        assert_eq!(
+            lines(2),
+            [
+                "4: >C04                     │",
+                "5: c05                      ┃",
+                "6: c06                      │",
+                "7: c07                      │",
+                "Page 2: records 4 - 7  total: 20  [FULL]",
+            ]
+        );
+        assert_eq!(
+            lines(5),
+            [
+                "16: >C16                    │",
+                "17: c17                     │",
+                "18: c18                     │",
+                "19: c19                     ┃",
+                "Page 5: records 16 - 19  total: 20  [FULL]",
+            ]
+        );
+    }
+
+    #[test]
+    fn footer_shows_the_position_of_the_selection() {
+        let events = [
+            MenuEvent::NextPage,
+            MenuEvent::NextPage,
+            MenuEvent::NextElement,
+        ];
+        let lines = scrolled_lines(ScrollIndicator::Footer, &events, 7);
+        assert_eq!(
+            lines,
+            [
+                "4: c04",
+                "5: >C05",
+                "6: c06",
+                "7: c07",
+                "[6/20]  Page 2: records 4 - 7  total: 20  [FULL]",
+            ]
+        );
+    }
+
+    #[rstest]
+    #[case::scrollbar(ScrollIndicator::Scrollbar)]
+    #[case::footer(ScrollIndicator::Footer)]
+    fn menus_that_fit_show_no_scroll_indicator(#[case] indicator: ScrollIndicator) {
+        assert_eq!(
+            scrolled_lines(indicator, &[MenuEvent::NextElement], 30),
+            scrolled_lines(ScrollIndicator::None, &[MenuEvent::NextElement], 30)
+        );
+    }
+
+    #[test]
+    fn bordered_menu() {
+        let mut completer = DefaultCompleter::new_with_wordlen(
+            vec!["cargo".into(), "cart".into(), "cat".into()],
+            2,
+        );
+        let mut menu = ListMenu::default()
+            .with_only_buffer_difference(false)
+            .with_menu_border(MenuBorder::default().with_use_unicode(false));
+        let mut editor = Editor::default();
+        editor.set_buffer("c".to_string(), UndoBehavior::CreateUndoPoint);
+        let mut painter = Painter::new(std::io::BufWriter::new(std::io::stderr()));
+        painter.handle_resize(50, 10);
+        menu.menu_event(MenuEvent::Activate(false));
+        menu.update_working_details(&mut editor, &mut completer, &painter);
+
+        assert_eq!(menu.menu_required_lines(50), 6);
+        assert_eq!(
+            menu.menu_string(10, false),
+            "+-----------------------------------------------+\r\n\
+             | 0: >CARGO                                     |\r\n\
+             | 1: cart                                       |\r\n\
+             | 2: cat                                        |\r\n\
+             | Page 1: records 0 - 2  total: 3               |\r\n\
+             +-----------------------------------------------+"
+        );
+    }
 }
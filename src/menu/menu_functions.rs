@@ -1,5 +1,5 @@
 //! Collection of common functions that can be used to create menus
-use std::borrow::Cow;
+use std::{borrow::Cow, ops::Range};
 use unicase::UniCase;
 
 use itertools::{
@@ -10,7 +10,11 @@ use nu_ansi_term::{ansi::RESET, Style};
 use unicode_segmentation::UnicodeSegmentation;
 use unicode_width::UnicodeWidthStr;
 
-use crate::{Editor, Suggestion, UndoBehavior};
+use crate::{
+    completion::quote_in_line, painting::line_width, CancellationToken, Completer,
+    CompletionContext, CompletionHandle, Editor, EllipsisPosition, Suggestion, SuggestionKind,
+    UndoBehavior,
+};
 
 /// Index result obtained from parsing a string with an index marker
 /// For example, the next string:
@@ -313,17 +317,218 @@ pub fn floor_char_boundary(s: &str, index: usize) -> usize {
     }
 }
 
+/// The index after `index` among `len` values, wrapping around to the first
+/// one or stopping at the last one
+pub(crate) fn next_index(index: usize, len: usize, wrap: bool) -> usize {
+    if index + 1 < len {
+        index + 1
+    } else if wrap {
+        0
+    } else {
+        len.saturating_sub(1)
+    }
+}
+
+/// The index before `index` among `len` values, wrapping around to the last
+/// one or stopping at the first one
+pub(crate) fn previous_index(index: usize, len: usize, wrap: bool) -> usize {
+    match index.checked_sub(1) {
+        Some(index) => index,
+        None if wrap => len.saturating_sub(1),
+        None => 0,
+    }
+}
+
+/// Values of a menu listed together under a header, see
+/// [`Suggestion::group`]
+pub(crate) struct ValueGroup {
+    /// Name shown in the header, `None` for the values without a group
+    pub header: Option<String>,
+    /// Index of the first value of the group
+    pub start: usize,
+    /// Number of values in the group
+    pub len: usize,
+}
+
+/// Order `values` group by group, the groups in the order they first appear
+/// and the values without a group last
+///
+/// Returns the groups and where the value at index `tracked` went. Values
+/// keep their order within a group.
+pub(crate) fn group_values(
+    values: &mut Vec<Suggestion>,
+    tracked: usize,
+) -> (Vec<ValueGroup>, usize) {
+    let mut names: Vec<Option<String>> = Vec::new();
+    for value in values.iter() {
+        if value.group.is_some() && !names.contains(&value.group) {
+            names.push(value.group.clone());
+        }
+    }
+    if names.is_empty() {
+        let groups = (!values.is_empty()).then_some(ValueGroup {
+            header: None,
+            start: 0,
+            len: values.len(),
+        });
+        return (groups.into_iter().collect(), tracked);
+    }
+    names.push(None);
+
+    let mut order: Vec<usize> = (0..values.len()).collect();
+    order.sort_by_key(|&index| names.iter().position(|name| *name == values[index].group));
+    let tracked = order
+        .iter()
+        .position(|&index| index == tracked)
+        .unwrap_or(tracked);
+    let mut unordered = std::mem::take(values);
+    *values = order
+        .iter()
+        .map(|&index| std::mem::take(&mut unordered[index]))
+        .collect();
+
+    let mut groups: Vec<ValueGroup> = Vec::with_capacity(names.len());
+    for name in names {
+        let start = groups.last().map_or(0, |group| group.start + group.len);
+        let len = values[start..]
+            .iter()
+            .take_while(|value| value.group == name)
+            .count();
+        if len > 0 {
+            groups.push(ValueGroup {
+                header: name,
+                start,
+                len,
+            });
+        }
+    }
+    (groups, tracked)
+}
+
+/// The header line shown above the values of group `name`
+pub(crate) fn group_header(name: &str) -> String {
+    format!("── {name} ──")
+}
+
+/// Width of the column showing the kinds of `suggestions`, including a space
+/// after them, or 0 if none has a kind
+pub(crate) fn kind_column_width(suggestions: &[Suggestion], icons: bool) -> usize {
+    suggestions
+        .iter()
+        .filter_map(|suggestion| suggestion.kind)
+        .map(|kind| kind_text(kind, icons).width() + 1)
+        .max()
+        .unwrap_or(0)
+}
+
+/// The kind of `suggestion` padded to `width` columns, blank if it has none
+pub(crate) fn kind_marker(suggestion: &Suggestion, width: usize, icons: bool) -> String {
+    let text = suggestion.kind.map_or("", |kind| kind_text(kind, icons));
+    format!("{text}{}", " ".repeat(width.saturating_sub(text.width())))
+}
+
+fn kind_text(kind: SuggestionKind, icons: bool) -> &'static str {
+    if icons {
+        kind.icon()
+    } else {
+        kind.label()
+    }
+}
+
+/// Let `completer` fill in the description of the selected `suggestion` if
+/// it has none
+pub(crate) fn resolve_description(
+    suggestion: Option<&mut Suggestion>,
+    completer: &mut dyn Completer,
+) {
+    if let Some(suggestion) = suggestion.filter(|suggestion| suggestion.description.is_none()) {
+        suggestion.description = completer.resolve_description(suggestion);
+    }
+}
+
+/// The shortest text of `buffer` in one of the `base_ranges` of the values
+pub(crate) fn shortest_base_string(buffer: &str, base_ranges: &[Range<usize>]) -> String {
+    base_ranges
+        .iter()
+        .map(|range| {
+            let end = floor_char_boundary(buffer, range.end);
+            let start = floor_char_boundary(buffer, range.start).min(end);
+            buffer[start..end].to_string()
+        })
+        .min_by_key(|s| s.width())
+        .unwrap_or_default()
+}
+
+/// Values of a menu still arriving from a completer running in the background
+///
+/// Dropping it cancels the completion, discarding the values still to come.
+pub(crate) struct ArrivingValues {
+    handle: CompletionHandle,
+    token: CancellationToken,
+    /// The buffer the values complete
+    buffer: String,
+}
+
+impl ArrivingValues {
+    /// Complete `ctx` for the text in `buffer`, returning the values available
+    /// right away with the ranges of the strings they are based on, and the
+    /// values still to come, if any
+    pub fn start(
+        completer: &mut dyn Completer,
+        ctx: &CompletionContext,
+        buffer: &str,
+    ) -> (Vec<Suggestion>, Vec<Range<usize>>, Option<Self>) {
+        let token = CancellationToken::new();
+        let mut handle = completer.complete_streaming(ctx, token.clone());
+        let values = handle.take_arrived();
+        let base_ranges = handle.base_ranges().to_vec();
+        let arriving = (!handle.is_finished()).then(|| ArrivingValues {
+            handle,
+            token,
+            buffer: buffer.to_string(),
+        });
+        (values, base_ranges, arriving)
+    }
+
+    /// Values arrived since the last call
+    pub fn take(&mut self) -> Vec<Suggestion> {
+        self.handle.take_arrived()
+    }
+
+    /// Whether all values arrived
+    pub fn is_finished(&self) -> bool {
+        self.handle.is_finished()
+    }
+
+    pub fn buffer(&self) -> &str {
+        &self.buffer
+    }
+
+    /// The ranges of the strings all values arrived so far are based on
+    pub fn base_ranges(&self) -> &[Range<usize>] {
+        self.handle.base_ranges()
+    }
+}
+
+impl Drop for ArrivingValues {
+    fn drop(&mut self) {
+        self.token.cancel();
+    }
+}
+
 /// Helper to accept a completion suggestion and edit the buffer
 pub fn replace_in_buffer(value: Option<Suggestion>, editor: &mut Editor) {
     if let Some(Suggestion {
         mut value,
         span,
         append_whitespace,
+        quoting,
         ..
     }) = value
     {
         let end = floor_char_boundary(editor.get_buffer(), span.end);
         let start = floor_char_boundary(editor.get_buffer(), span.start).min(end);
+        value = quote_in_line(editor.get_buffer(), start, end, &value, quoting);
         if append_whitespace {
             value.push(' ');
         }
@@ -338,39 +543,70 @@ pub fn replace_in_buffer(value: Option<Suggestion>, editor: &mut Editor) {
     }
 }
 
+/// The start the suggestions have in common, for completers matching
+/// regardless of case
+///
+/// Where the suggestions agree, their casing is kept. Where they differ only
+/// in case, the casing of `typed` is kept and the common start ends past it.
+fn common_prefix(values: &[Suggestion], typed: &str) -> String {
+    let Some((first, rest)) = values.split_first() else {
+        return String::new();
+    };
+    let mut typed = typed.chars();
+    let mut others: Vec<_> = rest.iter().map(|s| s.value.chars()).collect();
+    let mut prefix = String::new();
+    for c in first.value.chars() {
+        let typed_char = typed.next();
+        let Some(next): Option<Vec<char>> = others.iter_mut().map(Iterator::next).collect() else {
+            break;
+        };
+        if next.iter().all(|&other| other == c) {
+            prefix.push(c);
+            continue;
+        }
+        let same_letter = |a: char, b: char| a.to_lowercase().eq(b.to_lowercase());
+        match typed_char {
+            Some(t) if same_letter(c, t) && next.iter().all(|&other| same_letter(other, t)) => {
+                prefix.push(t);
+            }
+            _ => break,
+        }
+    }
+    prefix
+}
+
 /// Helper for `Menu::can_partially_complete`
 pub fn can_partially_complete(values: &[Suggestion], editor: &mut Editor) -> bool {
-    if let Some((Suggestion { value, span, .. }, index)) = find_common_string(values) {
-        let matching = &value[0..index];
-        let end = floor_char_boundary(editor.get_buffer(), span.end);
-        let start = floor_char_boundary(editor.get_buffer(), span.start).min(end);
+    let Some(Suggestion { span, .. }) = values.first() else {
+        return false;
+    };
+    let end = floor_char_boundary(editor.get_buffer(), span.end);
+    let start = floor_char_boundary(editor.get_buffer(), span.start).min(end);
+
+    // make sure that the partial completion does not overwrite user entered input
+    let entered_input = &editor.get_buffer()[start..end];
+    let matching = common_prefix(values, entered_input);
+    let extends_input = UniCase::new(matching.as_str())
+        .to_folded_case()
+        .contains(&UniCase::new(entered_input).to_folded_case())
+        && matching != entered_input;
+
+    if !matching.is_empty() && extends_input {
+        let mut line_buffer = editor.line_buffer().clone();
+        line_buffer.replace_range(start..end, &matching);
 
-        // make sure that the partial completion does not overwrite user entered input
-        let entered_input = &editor.get_buffer()[start..end];
-        let extends_input = UniCase::new(matching)
-            .to_folded_case()
-            .contains(&UniCase::new(entered_input).to_folded_case())
-            && matching != entered_input;
-
-        if !matching.is_empty() && extends_input {
-            let mut line_buffer = editor.line_buffer().clone();
-            line_buffer.replace_range(start..end, matching);
-
-            let offset = if matching.len() < (end - start) {
-                line_buffer
-                    .insertion_point()
-                    .saturating_sub((end - start) - matching.len())
-            } else {
-                line_buffer.insertion_point() + matching.len() - (end - start)
-            };
+        let offset = if matching.len() < (end - start) {
+            line_buffer
+                .insertion_point()
+                .saturating_sub((end - start) - matching.len())
+        } else {
+            line_buffer.insertion_point() + matching.len() - (end - start)
+        };
 
-            line_buffer.set_insertion_point(offset);
-            editor.set_line_buffer(line_buffer, UndoBehavior::CreateUndoPoint);
+        line_buffer.set_insertion_point(offset);
+        editor.set_line_buffer(line_buffer, UndoBehavior::CreateUndoPoint);
 
-            true
-        } else {
-            false
-        }
+        true
     } else {
         false
     }
@@ -592,6 +828,24 @@ pub fn style_suggestion(
     res
 }
 
+/// `selected` with the colors and attributes of `style` on top, so a selected
+/// suggestion with a style of its own keeps its color while standing out
+pub(crate) fn compose_styles(selected: &Style, style: &Style) -> Style {
+    Style {
+        foreground: style.foreground.or(selected.foreground),
+        background: style.background.or(selected.background),
+        is_bold: selected.is_bold || style.is_bold,
+        is_dimmed: selected.is_dimmed || style.is_dimmed,
+        is_italic: selected.is_italic || style.is_italic,
+        is_underline: selected.is_underline || style.is_underline,
+        is_blink: selected.is_blink || style.is_blink,
+        is_reverse: selected.is_reverse || style.is_reverse,
+        is_hidden: selected.is_hidden || style.is_hidden,
+        is_strikethrough: selected.is_strikethrough || style.is_strikethrough,
+        prefix_with_reset: selected.prefix_with_reset || style.prefix_with_reset,
+    }
+}
+
 /// If `match_indices` is given, then returns that. Otherwise, tries to find `typed_text`
 /// inside `value`, then returns the indices for that substring.
 pub fn get_match_indices<'a>(
@@ -681,10 +935,324 @@ pub(crate) fn truncate_with_ansi(s: &str, max_width: usize) -> Cow<'_, str> {
     }
 }
 
+/// `line` cut to `max_width` like [`truncate_with_ansi`], with a style left
+/// open by the cut ended so it doesn't reach what follows
+pub(crate) fn cut_with_ansi(line: &str, max_width: usize) -> String {
+    let cut = truncate_with_ansi(line, max_width.max(3));
+    if cut.contains('\x1b') {
+        format!("{cut}{RESET}")
+    } else {
+        cut.into_owned()
+    }
+}
+
+/// Glyph of the part of a scrollbar marking the values shown
+const SCROLLBAR_THUMB: char = '┃';
+/// Glyph of the rest of a scrollbar
+const SCROLLBAR_TRACK: char = '│';
+
+/// The glyphs of a scrollbar `height` rows high, from the top, for a menu
+/// showing the `visible` ones of `total` rows or values
+///
+/// Is `None` when all of them are shown. The thumb is at least one row
+/// long, and at the top or bottom end only when the first or last of them
+/// are shown.
+pub(crate) fn scrollbar_track(
+    total: usize,
+    visible: std::ops::Range<usize>,
+    height: usize,
+) -> Option<Vec<char>> {
+    let visible = visible.start.min(total)..visible.end.min(total);
+    if visible.len() == total || height == 0 {
+        return None;
+    }
+    let thumb_len = ((height * visible.len() + total / 2) / total).clamp(1, height);
+    let max_start = total - visible.len();
+    let free_rows = height - thumb_len;
+    let thumb_start = match visible.start {
+        0 => 0,
+        start if start == max_start => free_rows,
+        // Away from the ends the thumb isn't at them either
+        start => ((free_rows * start + max_start / 2) / max_start).clamp(
+            usize::from(free_rows > 1),
+            free_rows.saturating_sub(usize::from(free_rows > 1)),
+        ),
+    };
+    let thumb = thumb_start..thumb_start + thumb_len;
+    Some(
+        (0..height)
+            .map(|row| {
+                if thumb.contains(&row) {
+                    SCROLLBAR_THUMB
+                } else {
+                    SCROLLBAR_TRACK
+                }
+            })
+            .collect(),
+    )
+}
+
+/// The position of the value at `selected` among `total` values, like
+/// `[12/87]`, for the footer of a menu not showing all of them
+pub(crate) fn scroll_footer(selected: usize, total: usize) -> String {
+    format!("[{}/{total}]", selected + 1)
+}
+
+/// The first line of `line` cut or padded to `width` columns, followed by a
+/// scrollbar `glyph`
+pub(crate) fn with_scrollbar_glyph(line: &str, width: usize, glyph: char) -> String {
+    let (first_line, rest) = line.split_at(line.find("\r\n").unwrap_or(line.len()));
+    let first_line = if line_width(first_line) > width {
+        truncate_with_ansi(first_line, width.max(3))
+    } else {
+        Cow::Borrowed(first_line)
+    };
+    let padding = " ".repeat(width.saturating_sub(line_width(&first_line)));
+    // A style left open by the cut doesn't reach the glyph
+    let reset = if first_line.contains('\x1b') {
+        RESET
+    } else {
+        ""
+    };
+    format!("{first_line}{reset}{padding}{glyph}{rest}")
+}
+
+/// A grapheme with the SGR arguments it is styled with
+type StyledGrapheme<'a> = (Option<&'a str>, &'a str);
+
+/// The graphemes of a string with ANSI escapes, each with its style
+fn styled_graphemes(s: &str) -> Vec<StyledGrapheme<'_>> {
+    parse_ansi(s)
+        .into_iter()
+        .flat_map(|AnsiSegment { escape, text }| text.graphemes(true).map(move |g| (escape, g)))
+        .collect()
+}
+
+/// Shorten a string with ANSI escapes to at most `max_width` columns, putting
+/// a `…` where graphemes were left out
+///
+/// The escapes don't count towards the width and are never split. The `…`
+/// takes the style of the first grapheme it replaces, and is kept even if
+/// `max_width` is 0.
+pub(crate) fn ellipsize_with_ansi(
+    s: &str,
+    max_width: usize,
+    position: EllipsisPosition,
+) -> Cow<'_, str> {
+    ellipsize_matched(s, &[], max_width, position).0
+}
+
+/// Shorten a suggestion like [`ellipsize_with_ansi`], moving the
+/// `match_indices` of its graphemes to where they are after shortening
+///
+/// Matches left out are dropped, the `…` is never one.
+pub(crate) fn ellipsize_matched<'a>(
+    s: &'a str,
+    match_indices: &[usize],
+    max_width: usize,
+    position: EllipsisPosition,
+) -> (Cow<'a, str>, Vec<usize>) {
+    let graphemes = styled_graphemes(s);
+    if graphemes.iter().map(|(_, g)| g.width()).sum::<usize>() <= max_width {
+        return (Cow::Borrowed(s), match_indices.to_vec());
+    }
+
+    // Number of graphemes from `graphemes` that fit in `max_width`
+    let fitting = |graphemes: &mut dyn Iterator<Item = &StyledGrapheme>, max_width: usize| {
+        graphemes
+            .scan(0, |used, (_, g)| {
+                *used += g.width();
+                (*used <= max_width).then_some(())
+            })
+            .count()
+    };
+    let room = max_width.saturating_sub(1);
+    let (head_room, tail_room) = match position {
+        EllipsisPosition::End => (room, 0),
+        EllipsisPosition::Middle => (room - room / 2, room / 2),
+    };
+    let head = fitting(&mut graphemes.iter(), head_room);
+    let tail = fitting(&mut graphemes[head..].iter().rev(), tail_room);
+    let tail_start = graphemes.len() - tail;
+
+    let mut shortened = graphemes[..head].to_vec();
+    shortened.push((graphemes[head].0, "…"));
+    shortened.extend_from_slice(&graphemes[tail_start..]);
+    let match_indices = match_indices
+        .iter()
+        .filter_map(|&index| match index {
+            index if index < head => Some(index),
+            index if index >= tail_start => Some(index - tail_start + head + 1),
+            _ => None,
+        })
+        .collect();
+    (Cow::Owned(render_styled(&shortened)), match_indices)
+}
+
+/// Wrap a string with ANSI escapes into lines at most `max_width` columns wide
+///
+/// Lines break at whitespace, and inside words longer than a line. Each line
+/// of `s` starts a paragraph, blank lines between them are kept. The lines
+/// continuing a paragraph are indented like its first line, those of a list
+/// item (`- `, `* `, `1. `) hang below the text after the marker. A style
+/// spanning a line break is ended before it and started again after it.
+pub(crate) fn wrap_with_ansi(s: &str, max_width: usize) -> Vec<String> {
+    if max_width == 0 {
+        return Vec::new();
+    }
+    let graphemes = styled_graphemes(s);
+
+    let mut lines = Vec::new();
+    for paragraph in graphemes.split(|(_, g)| *g == "\n" || *g == "\r\n") {
+        wrap_paragraph(paragraph, max_width, &mut lines);
+    }
+
+    let first = lines.iter().position(|line| !line.is_empty());
+    let last = lines.iter().rposition(|line| !line.is_empty());
+    match (first, last) {
+        (Some(first), Some(last)) => lines[first..=last]
+            .iter()
+            .map(|line| render_styled(line))
+            .collect(),
+        _ => Vec::new(),
+    }
+}
+
+/// Wrap the graphemes of one paragraph into `lines`
+fn wrap_paragraph<'a>(
+    paragraph: &[StyledGrapheme<'a>],
+    max_width: usize,
+    lines: &mut Vec<Vec<StyledGrapheme<'a>>>,
+) {
+    let is_space = |(_, g): &StyledGrapheme| g.chars().all(char::is_whitespace);
+    let indent = paragraph.iter().take_while(|g| is_space(g)).count();
+
+    // Each word with the space in front of it, keeping the style of the space
+    let mut words = Vec::new();
+    let mut space = (None, " ");
+    let mut word_start = None;
+    for (i, grapheme) in paragraph.iter().enumerate().skip(indent) {
+        if is_space(grapheme) {
+            if let Some(start) = word_start.take() {
+                words.push((space, &paragraph[start..i]));
+            }
+            space = (grapheme.0, " ");
+        } else if word_start.is_none() {
+            word_start = Some(i);
+        }
+    }
+    if let Some(start) = word_start {
+        words.push((space, &paragraph[start..]));
+    }
+
+    let marker = match words.first() {
+        Some((_, word)) if words.len() > 1 && is_list_marker(word) => word.len() + 1,
+        _ => 0,
+    };
+    // Indentation never takes more than half of the line
+    let indent = indent.min(max_width / 2);
+    let hanging = if indent + marker <= max_width / 2 {
+        indent + marker
+    } else {
+        indent
+    };
+
+    let mut line = vec![(None, " "); indent];
+    let mut width = indent;
+    let mut is_empty = true;
+    for (space, word) in words {
+        for (i, chunk) in split_word(word, max_width - hanging)
+            .into_iter()
+            .enumerate()
+        {
+            let chunk_width: usize = chunk.iter().map(|(_, g)| g.width()).sum();
+            // The rest of a long word is always on a line of its own
+            if !is_empty && (i > 0 || width + 1 + chunk_width > max_width) {
+                lines.push(std::mem::replace(&mut line, vec![(None, " "); hanging]));
+                width = hanging;
+                is_empty = true;
+            }
+            if !is_empty {
+                line.push(space);
+                width += 1;
+            }
+            line.extend(chunk);
+            width += chunk_width;
+            is_empty = false;
+        }
+    }
+    if is_empty {
+        line.clear();
+    }
+    lines.push(line);
+}
+
+/// Whether a word is the marker of a list item, like `-`, `*` or `1.`
+fn is_list_marker(word: &[StyledGrapheme]) -> bool {
+    match word {
+        [(_, "-" | "*" | "•")] => true,
+        [digits @ .., (_, "." | ")")] => {
+            !digits.is_empty()
+                && digits
+                    .iter()
+                    .all(|(_, g)| g.chars().all(|c| c.is_ascii_digit()))
+        }
+        _ => false,
+    }
+}
+
+/// Split a word into chunks at most `max_width` columns wide, leaving out
+/// graphemes wider than that
+fn split_word<'a>(word: &[StyledGrapheme<'a>], max_width: usize) -> Vec<Vec<StyledGrapheme<'a>>> {
+    let mut chunks = Vec::new();
+    let mut chunk = Vec::new();
+    let mut width = 0;
+    for &grapheme in word {
+        let grapheme_width = grapheme.1.width();
+        // Some unicode characters can have a width of multiple rows
+        if grapheme_width > max_width {
+            continue;
+        }
+        if width + grapheme_width > max_width {
+            chunks.push(std::mem::take(&mut chunk));
+            width = 0;
+        }
+        chunk.push(grapheme);
+        width += grapheme_width;
+    }
+    if !chunk.is_empty() {
+        chunks.push(chunk);
+    }
+    chunks
+}
+
+/// The graphemes with their styles, ending in a reset if any is styled
+fn render_styled(line: &[StyledGrapheme]) -> String {
+    let mut rendered = String::new();
+    let mut style = None;
+    for &(escape, grapheme) in line {
+        if escape != style {
+            if style.is_some() {
+                rendered.push_str(RESET);
+            }
+            if let Some(escape) = escape {
+                rendered.push_str(ANSI_SGR_START);
+                rendered.push_str(escape);
+            }
+            style = escape;
+        }
+        rendered.push_str(grapheme);
+    }
+    if style.is_some() {
+        rendered.push_str(RESET);
+    }
+    rendered
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::{EditCommand, LineBuffer, Span};
+    use crate::{EditCommand, LineBuffer, QuotingStyle, Span};
     use nu_ansi_term::Color;
     use rstest::rstest;
 
@@ -947,6 +1515,30 @@ mod tests {
         assert!(len == expected);
     }
 
+    #[rstest]
+    #[case::agreeing_casing_is_kept(vec!["Cargo.toml", "Cargo.lock"], "cargo", "Cargo.")]
+    #[case::typed_casing_where_ambiguous(vec!["Cargo.toml", "cargo-clippy"], "ca", "cargo")]
+    #[case::typed_casing_then_agreeing_letters(vec!["Makefile", "makepkg"], "m", "make")]
+    #[case::ends_at_ambiguous_casing_past_typed(vec!["fooBar", "foobaz"], "f", "foo")]
+    #[case::stops_before_ambiguous_untyped(vec!["README", "readme.txt"], "", "")]
+    #[case::single(vec!["clean"], "c", "clean")]
+    #[case::none(vec![], "c", "")]
+    fn common_prefix_ignores_case_where_typed(
+        #[case] values: Vec<&str>,
+        #[case] typed: &str,
+        #[case] expected: &str,
+    ) {
+        let values: Vec<_> = values
+            .into_iter()
+            .map(|s| Suggestion {
+                value: s.into(),
+                ..Default::default()
+            })
+            .collect();
+
+        assert_eq!(common_prefix(&values, typed), expected);
+    }
+
     #[rstest]
     #[case("foobar", 6, None, false, "foobar", 6)]
     #[case("foo\r\nbar", 5, None, false, "foo\r\n", 5)]
@@ -1003,6 +1595,194 @@ mod tests {
         assert_eq!(orig_insertion_point, editor.insertion_point());
     }
 
+    #[rstest]
+    #[case(
+        "",
+        10,
+        vec![]
+    )]
+    #[case(
+        "description",
+        15,
+        vec![
+            "description".into(),
+        ]
+    )]
+    #[case(
+        "this is a description",
+        10,
+        vec![
+            "this is a".into(),
+            "descriptio".into(),
+            "n".into(),
+        ]
+    )]
+    #[case(
+        "this is another description",
+        2,
+        vec![
+            "th".into(),
+            "is".into(),
+            "is".into(),
+            "an".into(),
+            "ot".into(),
+            "he".into(),
+            "r".into(),
+            "de".into(),
+            "sc".into(),
+            "ri".into(),
+            "pt".into(),
+            "io".into(),
+            "n".into(),
+        ]
+    )]
+    #[case(
+        "this is a description",
+        10,
+        vec![
+            "this is a".into(),
+            "descriptio".into(),
+            "n".into(),
+        ]
+    )]
+    #[case(
+        "this is a description",
+        10,
+        vec![
+            "this is a".into(),
+            "descriptio".into(),
+            "n".into(),
+        ]
+    )]
+    #[case(
+        "this is a description",
+        12,
+        vec![
+            "this is a".into(),
+            "description".into(),
+        ]
+    )]
+    #[case(
+        "test",
+        1,
+        vec![
+            "t".into(),
+            "e".into(),
+            "s".into(),
+            "t".into(),
+        ]
+    )]
+    #[case(
+        "😊a😊 😊bc de😊fg",
+        2,
+        vec![
+            "😊".into(),
+            "a".into(),
+            "😊".into(),
+            "😊".into(),
+            "bc".into(),
+            "de".into(),
+            "😊".into(),
+            "fg".into(),
+        ]
+    )]
+    #[case(
+        "😊",
+        1,
+        vec![],
+    )]
+    #[case(
+        "t😊e😊s😊t",
+        1,
+        vec![
+            "t".into(),
+            "e".into(),
+            "s".into(),
+            "t".into(),
+        ]
+    )]
+    #[case(
+        "first line\n\nsecond paragraph here",
+        10,
+        vec![
+            "first line".into(),
+            "".into(),
+            "second".into(),
+            "paragraph".into(),
+            "here".into(),
+        ]
+    )]
+    #[case(
+        "- a list item that wraps",
+        12,
+        vec![
+            "- a list".into(),
+            "  item that".into(),
+            "  wraps".into(),
+        ]
+    )]
+    #[case(
+        "  indented text wraps",
+        10,
+        vec![
+            "  indented".into(),
+            "  text".into(),
+            "  wraps".into(),
+        ]
+    )]
+    #[case(
+        "\x1b[1mbold text\x1b[0m plain",
+        5,
+        vec![
+            "\x1b[1mbold\x1b[0m".into(),
+            "\x1b[1mtext\x1b[0m".into(),
+            "plain".into(),
+        ]
+    )]
+    #[case(
+        "\x1b[1mbold text\x1b[0m plain",
+        9,
+        vec![
+            "\x1b[1mbold text\x1b[0m".into(),
+            "plain".into(),
+        ]
+    )]
+
+    fn test_wrap_with_ansi(
+        #[case] input: &str,
+        #[case] max_width: usize,
+        #[case] expected: Vec<String>,
+    ) {
+        let result = wrap_with_ansi(input, max_width);
+
+        assert_eq!(result, expected)
+    }
+
+    #[rstest]
+    #[case::quoted("cat My", 4, "cat 'My Documents' ")]
+    #[case::closing_the_open_quote("cat 'My", 5, "cat 'My Documents' ")]
+    #[case::keeping_the_quote_in_the_span("cat \"My", 4, "cat \"My Documents\" ")]
+    fn replace_in_buffer_quotes_the_value(
+        #[case] buffer: &str,
+        #[case] start: usize,
+        #[case] expected: &str,
+    ) {
+        let mut editor = Editor::default();
+        editor.set_buffer(buffer.to_string(), UndoBehavior::CreateUndoPoint);
+        replace_in_buffer(
+            Some(Suggestion {
+                value: "My Documents".to_string(),
+                span: Span::new(start, buffer.len()),
+                append_whitespace: true,
+                quoting: QuotingStyle::SingleQuotes,
+                ..Default::default()
+            }),
+            &mut editor,
+        );
+        assert_eq!(editor.get_buffer(), expected);
+        assert_eq!(editor.insertion_point(), expected.len());
+    }
+
     #[rstest]
     #[case::plain("Foo", vec![AnsiSegment { escape: None, text: "Foo" }])]
     #[case::unterminated("\x1b[", vec![AnsiSegment { escape: None, text: "\x1b[" }])]
@@ -1162,4 +1942,128 @@ mod tests {
     ) {
         assert_eq!(expected, truncate_with_ansi(value, max_width));
     }
+
+    #[rstest]
+    #[case::fits("src/lib.rs", 10, EllipsisPosition::Middle, "src/lib.rs")]
+    #[case::end("src/menu/mod.rs", 8, EllipsisPosition::End, "src/men…")]
+    #[case::middle("src/menu/mod.rs", 8, EllipsisPosition::Middle, "src/….rs")]
+    #[case::middle_keeps_more_of_the_start(
+        "src/menu/mod.rs",
+        9,
+        EllipsisPosition::Middle,
+        "src/…d.rs"
+    )]
+    // Ｈ has width 2 and doesn't fit in the column left before the `…`
+    #[case::wide_grapheme("aＨＨＨb", 5, EllipsisPosition::Middle, "a…b")]
+    #[case::nothing_left("foobar", 0, EllipsisPosition::End, "…")]
+    #[case::styles_are_kept(
+        "\x1b[1mfoo\x1b[0m/bar/\x1b[2mbaz\x1b[0m",
+        7,
+        EllipsisPosition::Middle,
+        "\x1b[1mfoo\x1b[0m…\x1b[2mbaz\x1b[0m"
+    )]
+    fn test_ellipsize_with_ansi(
+        #[case] value: &str,
+        #[case] max_width: usize,
+        #[case] position: EllipsisPosition,
+        #[case] expected: &str,
+    ) {
+        assert_eq!(expected, ellipsize_with_ansi(value, max_width, position));
+    }
+
+    #[test]
+    fn matches_move_with_their_graphemes_when_shortened() {
+        let (value, matches) = ellipsize_matched(
+            "src/menu/mod.rs",
+            &[0, 5, 9, 14],
+            8,
+            EllipsisPosition::Middle,
+        );
+
+        assert_eq!(value, "src/….rs");
+        assert_eq!(matches, [0, 7]);
+    }
+
+    #[rstest]
+    #[case::middle(1, 3, true, 2, 0)]
+    #[case::last_wraps(2, 3, true, 0, 1)]
+    #[case::first_wraps(0, 3, true, 1, 2)]
+    #[case::last_clamps(2, 3, false, 2, 1)]
+    #[case::first_clamps(0, 3, false, 1, 0)]
+    #[case::empty(0, 0, true, 0, 0)]
+    fn test_next_and_previous_index(
+        #[case] index: usize,
+        #[case] len: usize,
+        #[case] wrap: bool,
+        #[case] next: usize,
+        #[case] previous: usize,
+    ) {
+        assert_eq!(next, next_index(index, len, wrap));
+        assert_eq!(previous, previous_index(index, len, wrap));
+    }
+
+    #[test]
+    fn composed_styles_keep_the_color_of_the_suggestion() {
+        let selected = Color::Green.bold().reverse();
+
+        assert_eq!(
+            compose_styles(&selected, &Color::Blue.dimmed()),
+            Color::Blue.bold().dimmed().reverse()
+        );
+        assert_eq!(compose_styles(&selected, &Style::new()), selected);
+    }
+
+    #[test]
+    fn values_are_grouped_in_the_order_groups_appear() {
+        let mut values: Vec<Suggestion> = [
+            ("x", None),
+            ("a.rs", Some("files")),
+            ("ll", Some("aliases")),
+            ("b.rs", Some("files")),
+            ("y", None),
+        ]
+        .into_iter()
+        .map(|(value, group)| Suggestion {
+            value: value.into(),
+            group: group.map(Into::into),
+            ..Default::default()
+        })
+        .collect();
+
+        let (groups, tracked) = group_values(&mut values, 3);
+
+        let values: Vec<_> = values.iter().map(|value| value.value.as_str()).collect();
+        assert_eq!(values, ["a.rs", "b.rs", "ll", "x", "y"]);
+        let groups: Vec<_> = groups
+            .iter()
+            .map(|group| (group.header.as_deref(), group.start, group.len))
+            .collect();
+        assert_eq!(
+            groups,
+            [(Some("files"), 0, 2), (Some("aliases"), 2, 1), (None, 3, 2)]
+        );
+        assert_eq!(tracked, 1);
+    }
+
+    #[rstest]
+    #[case::top(0..5, Some("┃││││"))]
+    #[case::middle(8..13, Some("││┃││"))]
+    #[case::next_to_the_top(1..6, Some("│┃│││"))]
+    #[case::bottom(15..20, Some("││││┃"))]
+    #[case::everything(0..20, None)]
+    fn scrollbar_thumb_marks_the_rows_shown(
+        #[case] shown: std::ops::Range<usize>,
+        #[case] expected: Option<&str>,
+    ) {
+        let track = scrollbar_track(20, shown, 5).map(|track| track.into_iter().collect());
+
+        assert_eq!(track, expected.map(String::from));
+    }
+
+    #[test]
+    fn scrollbar_thumb_grows_with_the_rows_shown() {
+        let track: String = scrollbar_track(10, 0..6, 6).unwrap().into_iter().collect();
+
+        assert_eq!(track, "┃┃┃┃││");
+    }
 }
@@ -5,22 +5,31 @@ mod diagnostic_fix_menu;
 mod ide_menu;
 mod list_menu;
 pub mod menu_functions;
+#[cfg(feature = "lsp_diagnostics")]
+mod symbol_menu;
 
 use crate::core_editor::Editor;
+use crate::enums::EditCommand;
 use crate::History;
 use crate::{
-    completion::history::HistoryCompleter, highlighter::Highlighter, painting::Painter, Completer,
-    Suggestion,
+    completion::history::HistoryCompleter,
+    highlighter::Highlighter,
+    painting::{line_width, Painter},
+    Completer, Suggestion, SuggestionPostProcessor,
 };
 pub use columnar_menu::ColumnarMenu;
-pub use columnar_menu::TraversalDirection;
+pub use columnar_menu::{EllipsisPosition, TraversalDirection};
 pub use description_menu::DescriptionMenu;
 #[cfg(feature = "lsp_diagnostics")]
-pub use diagnostic_fix_menu::DiagnosticFixMenu;
+pub(crate) use diagnostic_fix_menu::{extract_text_edits, resource_operation, text_edit_info};
+#[cfg(feature = "lsp_diagnostics")]
+pub use diagnostic_fix_menu::{DiagnosticFixMenu, TextEditInfo};
 pub use ide_menu::DescriptionMode;
 pub use ide_menu::IdeMenu;
 pub use list_menu::ListMenu;
 use nu_ansi_term::{Color, Style};
+#[cfg(feature = "lsp_diagnostics")]
+pub use symbol_menu::SymbolMenu;
 
 /// Struct to store the menu style
 pub struct MenuTextStyle {
@@ -36,6 +45,9 @@ pub struct MenuTextStyle {
     /// Text style of the parts of the suggestions that match the
     /// typed text
     pub match_style: Style,
+    /// Text style of the headers above groups of suggestions, see
+    /// [`Suggestion::group`]
+    pub group_header_style: Style,
 }
 
 impl Default for MenuTextStyle {
@@ -46,6 +58,7 @@ impl Default for MenuTextStyle {
             description_style: Color::Yellow.normal(),
             selected_match_style: Color::Green.bold().reverse().underline(),
             match_style: Style::default().underline(),
+            group_header_style: Color::Cyan.bold(),
         }
     }
 }
@@ -77,6 +90,24 @@ pub enum MenuEvent {
     NextPage,
     /// Move to previous page
     PreviousPage,
+    /// Scrolling the description of the selected element up, keeping the
+    /// selection
+    ScrollDescriptionUp,
+    /// Scrolling the description of the selected element down, keeping the
+    /// selection
+    ScrollDescriptionDown,
+    /// Selecting the element at the given index
+    Select(usize),
+    /// Applying the selected element and closing the menu, like Enter.
+    /// Produced by [`Menu::handle_mouse`] and handled by the engine
+    Accept,
+    /// Show a short message, e.g. asking to confirm an action, in place of
+    /// the menu's status line until the next event. Menus without a status
+    /// line ignore it
+    Notice(String),
+    /// Get the values again after the source changed, keeping the selection
+    /// as close to where it was as possible
+    Refresh,
 }
 
 /// Trait that defines how a menu will be printed by the painter
@@ -109,6 +140,14 @@ pub trait Menu: Send {
     /// active even with one element
     fn can_quick_complete(&self) -> bool;
 
+    /// Whether the menu stays open after
+    /// [`ReedlineEvent::MenuAcceptAndContinue`](crate::ReedlineEvent::MenuAcceptAndContinue)
+    /// applied the selected value, to pick the next one. Menus applying a
+    /// single choice close instead
+    fn can_continue(&self) -> bool {
+        false
+    }
+
     /// The completion menu can try to find the common string and replace it
     /// in the given line buffer
     fn can_partially_complete(
@@ -168,6 +207,39 @@ pub trait Menu: Send {
     fn set_cursor_pos(&mut self, _pos: (u16, u16)) {
         // empty implementation to make it optional
     }
+    /// Maps a click at `row` and `column`, counted from the first line of the
+    /// menu, to the event it triggers. Only called while the host captures the mouse
+    fn handle_mouse(&self, _row: u16, _column: u16) -> Option<MenuEvent> {
+        None
+    }
+    /// The selected value, if the menu has one
+    fn selected_value(&self) -> Option<Suggestion> {
+        None
+    }
+    /// Whether values are still arriving from a completer running in the
+    /// background, see [`Completer::complete_streaming`]
+    fn is_completing(&self) -> bool {
+        false
+    }
+    /// Take the values that arrived from a completer running in the
+    /// background, returning whether there were any
+    ///
+    /// Called by the engine between key presses while the menu
+    /// [`is_completing`](Menu::is_completing).
+    fn poll_values(&mut self) -> bool {
+        false
+    }
+    /// Take `commands` typed while the menu is active to filter its values
+    /// instead of editing the line, returning whether it took them
+    fn edit_filter(&mut self, _commands: &[EditCommand]) -> bool {
+        false
+    }
+    /// Clear the filter typed into the menu, returning whether there was one
+    ///
+    /// Esc only closes the menu when there was none.
+    fn clear_filter(&mut self) -> bool {
+        false
+    }
 }
 
 /// Struct to store configuration for a menu.
@@ -181,6 +253,18 @@ pub struct MenuSettings {
     /// Calls the completer using only the line buffer difference difference
     /// after the menu was activated
     only_buffer_difference: bool,
+    /// Moving past the last element selects the first one and vice versa,
+    /// instead of stopping at the ends
+    wrap: bool,
+    /// Show suggestion kinds as icons instead of ASCII labels
+    kind_icons: bool,
+    /// How the menu shows that it has more values than fit on the screen
+    scroll_indicator: ScrollIndicator,
+    /// Editing the line while the menu is open keeps the selected value
+    /// selected, if it is still listed
+    keep_selection: bool,
+    /// Box drawn around the values of the menu
+    border: Option<MenuBorder>,
 }
 
 impl Default for MenuSettings {
@@ -190,6 +274,11 @@ impl Default for MenuSettings {
             color: MenuTextStyle::default(),
             marker: "| ".to_string(),
             only_buffer_difference: false,
+            wrap: true,
+            kind_icons: true,
+            scroll_indicator: ScrollIndicator::default(),
+            keep_selection: true,
+            border: None,
         }
     }
 }
@@ -222,6 +311,241 @@ impl MenuSettings {
         self.only_buffer_difference = only_buffer_difference;
         self
     }
+
+    /// MenuSettings builder with wrap
+    #[must_use]
+    pub fn with_wrap(mut self, wrap: bool) -> Self {
+        self.wrap = wrap;
+        self
+    }
+
+    /// MenuSettings builder with kind_icons
+    #[must_use]
+    pub fn with_kind_icons(mut self, kind_icons: bool) -> Self {
+        self.kind_icons = kind_icons;
+        self
+    }
+
+    /// MenuSettings builder with scroll_indicator
+    #[must_use]
+    pub fn with_scroll_indicator(mut self, scroll_indicator: ScrollIndicator) -> Self {
+        self.scroll_indicator = scroll_indicator;
+        self
+    }
+
+    /// MenuSettings builder with keep_selection
+    #[must_use]
+    pub fn with_keep_selection(mut self, keep_selection: bool) -> Self {
+        self.keep_selection = keep_selection;
+        self
+    }
+
+    /// MenuSettings builder with border
+    #[must_use]
+    pub fn with_border(mut self, border: MenuBorder) -> Self {
+        self.border = Some(border);
+        self
+    }
+
+    /// Columns taken by the border and its padding
+    pub(crate) fn border_width(&self) -> usize {
+        self.border.as_ref().map_or(0, MenuBorder::width)
+    }
+
+    /// Rows taken by the top and bottom of the border
+    pub(crate) fn border_rows(&self) -> u16 {
+        if self.border.is_some() {
+            2
+        } else {
+            0
+        }
+    }
+}
+
+/// How a menu shows that it has more values than fit on the screen
+///
+/// The columnar, IDE and list menus show it while some of their values are
+/// out of sight, and nothing when all of them fit.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum ScrollIndicator {
+    /// Show nothing
+    #[default]
+    None,
+    /// A scrollbar along the right edge of the menu, its thumb marking the
+    /// part that is shown
+    Scrollbar,
+    /// The position of the selected value among all of them, like
+    /// `[12/87]`, below the menu
+    Footer,
+}
+
+/// Box drawn around the values of a menu
+///
+/// The default border has rounded corners and leaves one column of padding
+/// on either side of the values. Without unicode, the corners become `+`
+/// and the edges `-` and `|`.
+#[derive(Debug, Clone)]
+pub struct MenuBorder {
+    top_left: char,
+    top_right: char,
+    bottom_left: char,
+    bottom_right: char,
+    horizontal: char,
+    vertical: char,
+    style: Style,
+    padding: u16,
+    use_unicode: bool,
+}
+
+impl Default for MenuBorder {
+    fn default() -> Self {
+        Self {
+            top_left: '╭',
+            top_right: '╮',
+            bottom_left: '╰',
+            bottom_right: '╯',
+            horizontal: '─',
+            vertical: '│',
+            style: Style::new(),
+            padding: 1,
+            use_unicode: true,
+        }
+    }
+}
+
+impl MenuBorder {
+    /// MenuBorder builder with the corner characters
+    #[must_use]
+    pub fn with_corners(
+        mut self,
+        top_left: char,
+        top_right: char,
+        bottom_left: char,
+        bottom_right: char,
+    ) -> Self {
+        self.top_left = top_left;
+        self.top_right = top_right;
+        self.bottom_left = bottom_left;
+        self.bottom_right = bottom_right;
+        self
+    }
+
+    /// MenuBorder builder with the characters of the horizontal and vertical
+    /// edges
+    #[must_use]
+    pub fn with_edges(mut self, horizontal: char, vertical: char) -> Self {
+        self.horizontal = horizontal;
+        self.vertical = vertical;
+        self
+    }
+
+    /// MenuBorder builder with style
+    #[must_use]
+    pub fn with_style(mut self, style: Style) -> Self {
+        self.style = style;
+        self
+    }
+
+    /// MenuBorder builder with the columns of padding between the border and
+    /// the values
+    #[must_use]
+    pub fn with_padding(mut self, padding: u16) -> Self {
+        self.padding = padding;
+        self
+    }
+
+    /// MenuBorder builder with use_unicode. Without it, characters that
+    /// aren't ASCII are replaced by `+`, `-` and `|`
+    #[must_use]
+    pub fn with_use_unicode(mut self, use_unicode: bool) -> Self {
+        self.use_unicode = use_unicode;
+        self
+    }
+
+    /// Columns of padding between the border and the values
+    pub(crate) fn padding(&self) -> u16 {
+        self.padding
+    }
+
+    /// Columns taken by both vertical edges and the padding
+    pub(crate) fn width(&self) -> usize {
+        2 + 2 * self.padding as usize
+    }
+
+    fn glyph(&self, glyph: char, ascii: char) -> char {
+        if self.use_unicode || glyph.is_ascii() {
+            glyph
+        } else {
+            ascii
+        }
+    }
+
+    fn paint(&self, border: String, use_ansi_coloring: bool) -> String {
+        if use_ansi_coloring {
+            self.style.paint(border).to_string()
+        } else {
+            border
+        }
+    }
+
+    /// The vertical edge, without padding
+    pub(crate) fn vertical(&self, use_ansi_coloring: bool) -> String {
+        self.paint(
+            self.glyph(self.vertical, '|').to_string(),
+            use_ansi_coloring,
+        )
+    }
+
+    fn horizontal_line(&self, left: char, right: char, inner_width: usize) -> String {
+        let mut line = String::with_capacity(inner_width + 2);
+        line.push(self.glyph(left, '+'));
+        line.extend(std::iter::repeat(self.glyph(self.horizontal, '-')).take(inner_width));
+        line.push(self.glyph(right, '+'));
+        line
+    }
+
+    /// The top edge around `inner_width` columns
+    pub(crate) fn top(&self, inner_width: usize, use_ansi_coloring: bool) -> String {
+        self.paint(
+            self.horizontal_line(self.top_left, self.top_right, inner_width),
+            use_ansi_coloring,
+        )
+    }
+
+    /// The bottom edge around `inner_width` columns
+    pub(crate) fn bottom(&self, inner_width: usize, use_ansi_coloring: bool) -> String {
+        self.paint(
+            self.horizontal_line(self.bottom_left, self.bottom_right, inner_width),
+            use_ansi_coloring,
+        )
+    }
+
+    /// `lines` inside the border, filled with spaces to `content_width` or
+    /// cut to it
+    pub(crate) fn frame(
+        &self,
+        lines: Vec<String>,
+        content_width: usize,
+        use_ansi_coloring: bool,
+    ) -> Vec<String> {
+        let vertical = self.vertical(use_ansi_coloring);
+        let padding = " ".repeat(self.padding as usize);
+        let inner_width = content_width + 2 * self.padding as usize;
+
+        let mut framed = Vec::with_capacity(lines.len() + 2);
+        framed.push(self.top(inner_width, use_ansi_coloring));
+        framed.extend(lines.into_iter().map(|line| {
+            let line = if line_width(&line) > content_width {
+                menu_functions::cut_with_ansi(&line, content_width)
+            } else {
+                line
+            };
+            let fill = " ".repeat(content_width.saturating_sub(line_width(&line)));
+            format!("{vertical}{padding}{line}{fill}{padding}{vertical}")
+        }));
+        framed.push(self.bottom(inner_width, use_ansi_coloring));
+        framed
+    }
 }
 
 /// Common builder for all menus
@@ -258,6 +582,13 @@ pub trait MenuBuilder: Menu + Sized {
         self
     }
 
+    /// Menu builder with new value for the style of group headers
+    #[must_use]
+    fn with_group_header_style(mut self, color: Style) -> Self {
+        self.settings_mut().color.group_header_style = color;
+        self
+    }
+
     /// Menu builder with new value for match style
     /// This is the style of the part of the input text, the suggestions
     /// are based on
@@ -289,6 +620,46 @@ pub trait MenuBuilder: Menu + Sized {
         self.settings_mut().only_buffer_difference = only_buffer_difference;
         self
     }
+
+    /// Menu builder choosing whether moving past the last element selects the
+    /// first one and vice versa (the default), or stops at the ends
+    #[must_use]
+    fn with_wrap(mut self, wrap: bool) -> Self {
+        self.settings_mut().wrap = wrap;
+        self
+    }
+
+    /// Menu builder choosing whether the [`SuggestionKind`](crate::SuggestionKind)
+    /// of suggestions is shown as an icon (the default) or as an ASCII label
+    #[must_use]
+    fn with_kind_icons(mut self, kind_icons: bool) -> Self {
+        self.settings_mut().kind_icons = kind_icons;
+        self
+    }
+
+    /// Menu builder with new value for how the menu shows that it has more
+    /// values than fit on the screen
+    #[must_use]
+    fn with_scroll_indicator(mut self, scroll_indicator: ScrollIndicator) -> Self {
+        self.settings_mut().scroll_indicator = scroll_indicator;
+        self
+    }
+
+    /// Menu builder choosing whether editing the line while the menu is open
+    /// keeps the selected value selected if it is still listed (the default),
+    /// or selects the first value
+    #[must_use]
+    fn with_keep_selection(mut self, keep_selection: bool) -> Self {
+        self.settings_mut().keep_selection = keep_selection;
+        self
+    }
+
+    /// Menu builder with a border drawn around the values
+    #[must_use]
+    fn with_menu_border(mut self, border: MenuBorder) -> Self {
+        self.settings_mut().border = Some(border);
+        self
+    }
 }
 
 /// Allowed menus in Reedline
@@ -329,39 +700,51 @@ impl ReedlineMenu {
         editor: &mut Editor,
         completer: &mut dyn Completer,
         history: &dyn History,
+        post_processor: &SuggestionPostProcessor,
     ) -> bool {
         match self {
-            Self::EngineCompleter(menu) => {
-                menu.can_partially_complete(values_updated, editor, completer)
-            }
+            Self::EngineCompleter(menu) => menu.can_partially_complete(
+                values_updated,
+                editor,
+                &mut post_processor.wrap(completer),
+            ),
             Self::HistoryMenu(menu) => {
                 let mut history_completer = HistoryCompleter::new(history);
-                menu.can_partially_complete(values_updated, editor, &mut history_completer)
+                let mut completer = post_processor.wrap(&mut history_completer);
+                menu.can_partially_complete(values_updated, editor, &mut completer)
             }
             Self::WithCompleter {
                 menu,
                 completer: own_completer,
-            } => menu.can_partially_complete(values_updated, editor, own_completer.as_mut()),
+            } => {
+                let mut completer = post_processor.wrap(own_completer.as_mut());
+                menu.can_partially_complete(values_updated, editor, &mut completer)
+            }
         }
     }
 
+    /// Update the values of the menu, sorted and deduplicated by
+    /// `post_processor`
     pub(crate) fn update_values(
         &mut self,
         editor: &mut Editor,
         completer: &mut dyn Completer,
         history: &dyn History,
+        post_processor: &SuggestionPostProcessor,
     ) {
         match self {
-            Self::EngineCompleter(menu) => menu.update_values(editor, completer),
+            Self::EngineCompleter(menu) => {
+                menu.update_values(editor, &mut post_processor.wrap(completer));
+            }
             Self::HistoryMenu(menu) => {
                 let mut history_completer = HistoryCompleter::new(history);
-                menu.update_values(editor, &mut history_completer);
+                menu.update_values(editor, &mut post_processor.wrap(&mut history_completer));
             }
             Self::WithCompleter {
                 menu,
                 completer: own_completer,
             } => {
-                menu.update_values(editor, own_completer.as_mut());
+                menu.update_values(editor, &mut post_processor.wrap(own_completer.as_mut()));
             }
         }
     }
@@ -371,21 +754,25 @@ impl ReedlineMenu {
         editor: &mut Editor,
         completer: &mut dyn Completer,
         history: &dyn History,
+        post_processor: &SuggestionPostProcessor,
         painter: &Painter,
     ) {
         match self {
             Self::EngineCompleter(menu) => {
-                menu.update_working_details(editor, completer, painter);
+                let mut completer = post_processor.wrap(completer);
+                menu.update_working_details(editor, &mut completer, painter);
             }
             Self::HistoryMenu(menu) => {
                 let mut history_completer = HistoryCompleter::new(history);
-                menu.update_working_details(editor, &mut history_completer, painter);
+                let mut completer = post_processor.wrap(&mut history_completer);
+                menu.update_working_details(editor, &mut completer, painter);
             }
             Self::WithCompleter {
                 menu,
                 completer: own_completer,
             } => {
-                menu.update_working_details(editor, own_completer.as_mut(), painter);
+                let mut completer = post_processor.wrap(own_completer.as_mut());
+                menu.update_working_details(editor, &mut completer, painter);
             }
         }
     }
@@ -416,6 +803,14 @@ impl Menu for ReedlineMenu {
         self.as_ref().can_quick_complete()
     }
 
+    /// History menus replace the whole line, there is nothing to continue
+    fn can_continue(&self) -> bool {
+        match self {
+            Self::HistoryMenu(_) => false,
+            Self::EngineCompleter(menu) | Self::WithCompleter { menu, .. } => menu.can_continue(),
+        }
+    }
+
     fn can_partially_complete(
         &mut self,
         values_updated: bool,
@@ -500,4 +895,28 @@ impl Menu for ReedlineMenu {
     fn set_cursor_pos(&mut self, pos: (u16, u16)) {
         self.as_mut().set_cursor_pos(pos);
     }
+
+    fn handle_mouse(&self, row: u16, column: u16) -> Option<MenuEvent> {
+        self.as_ref().handle_mouse(row, column)
+    }
+
+    fn selected_value(&self) -> Option<Suggestion> {
+        self.as_ref().selected_value()
+    }
+
+    fn is_completing(&self) -> bool {
+        self.as_ref().is_completing()
+    }
+
+    fn poll_values(&mut self) -> bool {
+        self.as_mut().poll_values()
+    }
+
+    fn edit_filter(&mut self, commands: &[EditCommand]) -> bool {
+        self.as_mut().edit_filter(commands)
+    }
+
+    fn clear_filter(&mut self) -> bool {
+        self.as_mut().clear_filter()
+    }
 }
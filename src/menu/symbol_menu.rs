@@ -0,0 +1,508 @@
+//! Menu for jumping to the symbols reported by the LSP server.
+//!
+//! Typing while the menu is open narrows the symbols by fuzzy matching their
+//! names. Selecting a symbol of the edited document removes the typed text
+//! again and moves the cursor to the symbol; symbols of other documents are
+//! shown dimmed and can't be jumped to.
+
+use itertools::Itertools;
+use nu_ansi_term::{ansi::RESET, Style};
+
+use super::{
+    menu_functions::{completer_input, next_index, previous_index},
+    Menu, MenuBuilder, MenuEvent, MenuSettings,
+};
+use crate::{
+    core_editor::Editor,
+    history::fuzzy_match_indices,
+    lsp::{LspSymbol, SymbolLocation, SYMBOL_MENU},
+    painting::{line_width, Painter},
+    Completer, LineBuffer, Suggestion, UndoBehavior,
+};
+
+/// Symbol matching the typed text.
+struct SymbolMatch {
+    /// Index into the menu's symbols
+    index: usize,
+    /// Byte offsets of the matched characters in the symbol's name
+    indices: Vec<usize>,
+}
+
+/// Menu listing LSP symbols, filtered by the text typed while it is open.
+///
+/// Shows symbols as lines: `> name kind (container)`
+pub struct SymbolMenu {
+    /// Menu settings (name, color, etc.)
+    settings: MenuSettings,
+    /// Whether the menu is active
+    active: bool,
+    /// All symbols, in the order the server reported them
+    symbols: Vec<LspSymbol>,
+    /// Symbols matching the typed text, best match first
+    matches: Vec<SymbolMatch>,
+    /// Text typed since the menu opened
+    query: String,
+    /// Selected index into the matches
+    selected: usize,
+    /// Number of values to skip for scrolling
+    skip_values: usize,
+    /// Max height of the menu
+    max_height: u16,
+    /// Buffer and cursor position before the first character was typed
+    origin: Option<(String, usize)>,
+}
+
+impl Default for SymbolMenu {
+    fn default() -> Self {
+        Self {
+            settings: MenuSettings::default().with_name(SYMBOL_MENU),
+            active: false,
+            symbols: Vec::new(),
+            matches: Vec::new(),
+            query: String::new(),
+            selected: 0,
+            skip_values: 0,
+            max_height: 10,
+            origin: None,
+        }
+    }
+}
+
+impl MenuBuilder for SymbolMenu {
+    fn settings_mut(&mut self) -> &mut MenuSettings {
+        &mut self.settings
+    }
+}
+
+impl SymbolMenu {
+    /// Menu builder with the maximum number of symbols shown at once
+    #[must_use]
+    pub fn with_max_height(mut self, max_height: u16) -> Self {
+        self.max_height = max_height.max(1);
+        self
+    }
+
+    /// Replace the listed symbols, clearing the typed text.
+    pub fn set_symbols(&mut self, symbols: Vec<LspSymbol>) {
+        self.symbols = symbols;
+        self.query.clear();
+        self.filter();
+    }
+
+    /// Check if there are any symbols.
+    pub fn has_symbols(&self) -> bool {
+        !self.symbols.is_empty()
+    }
+
+    /// Keep the symbols matching the query, most compact match first.
+    ///
+    /// Equally good matches keep the server's order.
+    fn filter(&mut self) {
+        self.matches = self
+            .symbols
+            .iter()
+            .enumerate()
+            .filter_map(|(index, symbol)| {
+                let indices = fuzzy_match_indices(&self.query, &symbol.name)?;
+                Some(SymbolMatch { index, indices })
+            })
+            .sorted_by_key(|symbol_match| match_spread(&symbol_match.indices))
+            .collect();
+        self.selected = 0;
+        self.skip_values = 0;
+    }
+
+    /// Filter by the text typed into `editor` since the menu opened.
+    fn update_query(&mut self, editor: &Editor) {
+        let origin = self
+            .origin
+            .get_or_insert_with(|| (editor.get_buffer().to_string(), editor.insertion_point()));
+        let (query, _) = completer_input(
+            editor.get_buffer(),
+            editor.insertion_point(),
+            Some(&origin.0),
+            true,
+        );
+        if query != self.query {
+            self.query = query;
+            self.filter();
+        }
+    }
+
+    /// Get the currently selected symbol.
+    fn get_selected_symbol(&self) -> Option<&LspSymbol> {
+        let symbol_match = self.matches.get(self.selected)?;
+        self.symbols.get(symbol_match.index)
+    }
+
+    /// Format a single symbol line.
+    fn format_symbol_line(
+        &self,
+        symbol_match: &SymbolMatch,
+        index: usize,
+        use_ansi_coloring: bool,
+    ) -> String {
+        let symbol = &self.symbols[symbol_match.index];
+        let indicator = if index == self.selected { "> " } else { "  " };
+
+        let mut detail = format!("{:?}", symbol.kind);
+        if let Some(container) = &symbol.container {
+            detail.push_str(&format!(" in {container}"));
+        }
+        if let SymbolLocation::External(uri) = &symbol.location {
+            detail.push_str(&format!(" ({uri})"));
+        }
+        if !use_ansi_coloring {
+            return format!("{indicator}{} {detail}", symbol.name);
+        }
+
+        let (name_style, detail_style, match_style) = match &symbol.location {
+            SymbolLocation::Document(_) => (
+                Style::new(),
+                Style::new().dimmed(),
+                self.settings.color.match_style,
+            ),
+            SymbolLocation::External(_) => (
+                Style::new().dimmed(),
+                Style::new().dimmed().italic(),
+                self.settings.color.match_style.dimmed(),
+            ),
+        };
+
+        let name: String = symbol
+            .name
+            .char_indices()
+            .map(|(offset, c)| {
+                let style = if symbol_match.indices.contains(&offset) {
+                    match_style
+                } else {
+                    name_style
+                };
+                format!("{}{c}{RESET}", style.prefix())
+            })
+            .collect();
+
+        format!("{indicator}{name} {}{detail}{RESET}", detail_style.prefix())
+    }
+
+    /// Move selection forward, wrapping around unless disabled
+    fn select_next(&mut self) {
+        if self.matches.is_empty() {
+            return;
+        }
+        self.selected = next_index(self.selected, self.matches.len(), self.settings.wrap);
+        self.adjust_scroll();
+    }
+
+    /// Move selection backward, wrapping around unless disabled
+    fn select_previous(&mut self) {
+        if self.matches.is_empty() {
+            return;
+        }
+        self.selected = previous_index(self.selected, self.matches.len(), self.settings.wrap);
+        self.adjust_scroll();
+    }
+
+    /// Move selection and view a page forward, stopping at the last symbol
+    fn select_next_page(&mut self) {
+        if self.matches.is_empty() {
+            return;
+        }
+        let page = self.max_height as usize;
+        self.selected = (self.selected + page).min(self.matches.len() - 1);
+        self.skip_values = (self.skip_values + page).min(self.matches.len().saturating_sub(page));
+        self.adjust_scroll();
+    }
+
+    /// Move selection and view a page backward, stopping at the first symbol
+    fn select_previous_page(&mut self) {
+        let page = self.max_height as usize;
+        self.selected = self.selected.saturating_sub(page);
+        self.skip_values = self.skip_values.saturating_sub(page);
+        self.adjust_scroll();
+    }
+
+    /// Keep the selected symbol visible
+    fn adjust_scroll(&mut self) {
+        let visible_items = self.max_height as usize;
+        if self.selected >= self.skip_values + visible_items {
+            self.skip_values = self.selected + 1 - visible_items;
+        } else if self.selected < self.skip_values {
+            self.skip_values = self.selected;
+        }
+    }
+}
+
+/// Distance in bytes from the first to the last matched character
+fn match_spread(indices: &[usize]) -> usize {
+    match (indices.first(), indices.last()) {
+        (Some(first), Some(last)) => last - first,
+        _ => 0,
+    }
+}
+
+impl Menu for SymbolMenu {
+    fn settings(&self) -> &MenuSettings {
+        &self.settings
+    }
+
+    fn is_active(&self) -> bool {
+        self.active
+    }
+
+    /// The menu stays open while typing narrows it to a single symbol
+    fn can_quick_complete(&self) -> bool {
+        false
+    }
+
+    fn can_partially_complete(
+        &mut self,
+        _values_updated: bool,
+        _editor: &mut Editor,
+        _completer: &mut dyn Completer,
+    ) -> bool {
+        false
+    }
+
+    fn menu_event(&mut self, event: MenuEvent) {
+        match event {
+            MenuEvent::Activate(_) => {
+                self.active = true;
+                self.origin = None;
+                self.selected = 0;
+                self.skip_values = 0;
+            }
+            MenuEvent::Deactivate => {
+                self.active = false;
+                self.origin = None;
+            }
+            MenuEvent::NextElement | MenuEvent::MoveDown => self.select_next(),
+            MenuEvent::PreviousElement | MenuEvent::MoveUp => self.select_previous(),
+            MenuEvent::NextPage => self.select_next_page(),
+            MenuEvent::PreviousPage => self.select_previous_page(),
+            _ => {}
+        }
+    }
+
+    fn update_values(&mut self, editor: &mut Editor, _completer: &mut dyn Completer) {
+        // Symbols are set via set_symbols(), only the filter follows the buffer
+        self.update_query(editor);
+    }
+
+    fn update_working_details(
+        &mut self,
+        editor: &mut Editor,
+        _completer: &mut dyn Completer,
+        _painter: &Painter,
+    ) {
+        self.update_query(editor);
+    }
+
+    fn replace_in_buffer(&self, editor: &mut Editor) {
+        let (buffer, cursor) = self
+            .origin
+            .clone()
+            .unwrap_or_else(|| (editor.get_buffer().to_string(), editor.insertion_point()));
+        let position = match self.get_selected_symbol().map(|symbol| &symbol.location) {
+            Some(SymbolLocation::Document(span)) => span.start.min(buffer.len()),
+            // Other documents can't be jumped to, so only the typed text goes
+            Some(SymbolLocation::External(_)) | None => cursor,
+        };
+
+        let mut line_buffer = LineBuffer::from(buffer.as_str());
+        line_buffer.set_insertion_point(position);
+        editor.set_line_buffer(line_buffer, UndoBehavior::CreateUndoPoint);
+    }
+
+    fn min_rows(&self) -> u16 {
+        (self.matches.len() as u16).clamp(1, self.max_height)
+    }
+
+    fn get_values(&self) -> &[Suggestion] {
+        // Return empty - we don't use Suggestion directly
+        &[]
+    }
+
+    fn menu_required_lines(&self, _terminal_columns: u16) -> u16 {
+        self.min_rows() + self.settings.border_rows()
+    }
+
+    fn menu_string(&self, available_lines: u16, use_ansi_coloring: bool) -> String {
+        let mut lines = if self.matches.is_empty() {
+            vec![String::from("No matching symbols")]
+        } else {
+            let available_lines = available_lines.saturating_sub(self.settings.border_rows());
+            let visible_count = available_lines.max(1).min(self.max_height) as usize;
+            self.matches
+                .iter()
+                .enumerate()
+                .skip(self.skip_values)
+                .take(visible_count)
+                .map(|(index, symbol_match)| {
+                    self.format_symbol_line(symbol_match, index, use_ansi_coloring)
+                })
+                .collect()
+        };
+        if let Some(border) = &self.settings.border {
+            let content_width = lines.iter().map(|line| line_width(line)).max();
+            lines = border.frame(lines, content_width.unwrap_or(0), use_ansi_coloring);
+        }
+        lines.join("\r\n")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        lsp::{Span, SymbolKind},
+        DefaultCompleter, EditCommand,
+    };
+    use pretty_assertions::assert_eq;
+
+    fn symbol(name: &str, location: SymbolLocation) -> LspSymbol {
+        LspSymbol {
+            name: name.into(),
+            kind: SymbolKind::FUNCTION,
+            container: None,
+            location,
+        }
+    }
+
+    fn menu() -> SymbolMenu {
+        let mut menu = SymbolMenu::default();
+        menu.set_symbols(vec![
+            symbol("greet", SymbolLocation::Document(Span::new(4, 9))),
+            symbol("get-name", SymbolLocation::Document(Span::new(21, 29))),
+            symbol(
+                "grep-lines",
+                SymbolLocation::External("file:///lib.nu".into()),
+            ),
+        ]);
+        menu.menu_event(MenuEvent::Activate(false));
+        menu
+    }
+
+    fn editor(buffer: &str) -> Editor {
+        let mut editor = Editor::default();
+        editor.set_buffer(buffer.into(), UndoBehavior::CreateUndoPoint);
+        editor
+    }
+
+    fn type_text(menu: &mut SymbolMenu, editor: &mut Editor, text: &str) {
+        let mut completer = DefaultCompleter::default();
+        menu.update_values(editor, &mut completer);
+        editor.run_edit_command(&EditCommand::InsertString(text.into()));
+        menu.update_values(editor, &mut completer);
+    }
+
+    fn listed(menu: &SymbolMenu) -> Vec<&str> {
+        menu.matches
+            .iter()
+            .map(|m| menu.symbols[m.index].name.as_str())
+            .collect()
+    }
+
+    const BUFFER: &str = "def greet [] {}; def get-name [] {}";
+
+    #[test]
+    fn typing_narrows_symbols_by_fuzzy_match() {
+        let mut menu = menu();
+        let mut editor = editor(BUFFER);
+        assert_eq!(listed(&menu), vec!["greet", "get-name", "grep-lines"]);
+
+        type_text(&mut menu, &mut editor, "gre");
+        assert_eq!(listed(&menu), vec!["greet", "grep-lines"]);
+
+        type_text(&mut menu, &mut editor, "l");
+        assert_eq!(listed(&menu), vec!["grep-lines"]);
+    }
+
+    #[test]
+    fn selecting_removes_typed_text_and_jumps_to_symbol() {
+        let mut menu = menu();
+        let mut editor = editor(BUFFER);
+
+        type_text(&mut menu, &mut editor, "gn");
+        assert_eq!(listed(&menu), vec!["get-name", "grep-lines"]);
+        menu.replace_in_buffer(&mut editor);
+
+        assert_eq!(editor.get_buffer(), BUFFER);
+        assert_eq!(editor.insertion_point(), 21);
+    }
+
+    #[test]
+    fn external_symbols_keep_the_cursor() {
+        let mut menu = menu();
+        let mut editor = editor(BUFFER);
+
+        type_text(&mut menu, &mut editor, "lines");
+        menu.replace_in_buffer(&mut editor);
+
+        assert_eq!(editor.get_buffer(), BUFFER);
+        assert_eq!(editor.insertion_point(), BUFFER.len());
+    }
+
+    #[test]
+    fn pages_jump_by_max_height_and_stop_at_the_ends() {
+        let mut menu = SymbolMenu::default().with_max_height(3);
+        menu.set_symbols(
+            (0..8)
+                .map(|i| symbol(&format!("f{i}"), SymbolLocation::Document(Span::new(i, i))))
+                .collect(),
+        );
+        menu.menu_event(MenuEvent::Activate(false));
+        let position = |menu: &SymbolMenu| (menu.selected, menu.skip_values);
+
+        menu.menu_event(MenuEvent::MoveDown);
+        menu.menu_event(MenuEvent::NextPage);
+        assert_eq!(position(&menu), (4, 3));
+        menu.menu_event(MenuEvent::NextPage);
+        assert_eq!(position(&menu), (7, 5));
+        menu.menu_event(MenuEvent::NextPage);
+        assert_eq!(position(&menu), (7, 5));
+
+        menu.menu_event(MenuEvent::PreviousPage);
+        assert_eq!(position(&menu), (4, 2));
+        menu.menu_event(MenuEvent::PreviousPage);
+        assert_eq!(position(&menu), (1, 0));
+        menu.menu_event(MenuEvent::PreviousPage);
+        assert_eq!(position(&menu), (0, 0));
+    }
+
+    #[test]
+    fn symbol_lines_show_kind_container_and_location() {
+        let mut menu = SymbolMenu::default();
+        menu.set_symbols(vec![
+            LspSymbol {
+                container: Some("greet".into()),
+                kind: SymbolKind::VARIABLE,
+                ..symbol("name", SymbolLocation::Document(Span::new(0, 4)))
+            },
+            symbol("helper", SymbolLocation::External("file:///lib.nu".into())),
+        ]);
+
+        assert_eq!(
+            menu.menu_string(5, false),
+            "> name Variable in greet\r\n  helper Function (file:///lib.nu)"
+        );
+    }
+
+    #[test]
+    fn bordered_symbols_leave_room_for_the_border() {
+        let mut menu = SymbolMenu::default()
+            .with_menu_border(crate::MenuBorder::default().with_use_unicode(false));
+        menu.set_symbols(vec![
+            symbol("greet", SymbolLocation::Document(Span::new(4, 9))),
+            symbol("get-name", SymbolLocation::Document(Span::new(21, 29))),
+        ]);
+
+        assert_eq!(menu.menu_required_lines(80), 4);
+        assert_eq!(
+            menu.menu_string(3, false),
+            "+------------------+\r\n\
+             | > greet Function |\r\n\
+             +------------------+"
+        );
+    }
+}
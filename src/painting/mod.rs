@@ -5,5 +5,5 @@ mod utils;
 
 pub use painter::{Painter, PainterSuspendedState, RenderSnapshot};
 pub(crate) use prompt_lines::PromptLines;
-pub use styled_text::StyledText;
-pub(crate) use utils::estimate_single_line_wraps;
+pub use styled_text::{MergePolicy, StyleModifier, StyledText};
+pub(crate) use utils::{estimate_single_line_wraps, line_width, strip_ansi};
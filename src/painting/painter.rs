@@ -143,6 +143,17 @@ fn select_prompt_row(
     PromptRowSelector::MakeNewPrompt { new_row }
 }
 
+/// Prompt start row after a resize, given the row the cursor ended up on and the
+/// number of rows the prompt and buffer before the cursor take at the new width.
+///
+/// When the content before the cursor no longer fits on screen, the prompt starts
+/// at the top row and the rest is scrolled off.
+fn prompt_start_after_resize(cursor_row: u16, lines_before_cursor: u16, screen_height: u16) -> u16 {
+    cursor_row
+        .min(screen_height.saturating_sub(1))
+        .saturating_sub(lines_before_cursor.saturating_sub(1))
+}
+
 /// Layout values computed once per paint cycle, shared between rendering and snapshot creation.
 pub(crate) struct PromptLayout {
     /// Total rows scrolled off the top (before prompt adjustment).
@@ -386,6 +397,7 @@ impl Painter {
     ///
     /// Note. The `ScrollUp` operation in `crossterm` deletes lines from the top of
     /// the screen.
+    #[allow(clippy::too_many_arguments)]
     pub(crate) fn repaint_buffer(
         &mut self,
         prompt: &dyn Prompt,
@@ -411,12 +423,7 @@ impl Painter {
         let lines_before_cursor = lines.required_lines(screen_width, true, None);
 
         // Calibrate prompt start position for multi-line prompt/content before cursor. Check issue #841/#848/#930
-        if self.just_resized {
-            self.prompt_start_row = self
-                .prompt_start_row
-                .saturating_sub(lines_before_cursor - 1);
-            self.just_resized = false;
-        }
+        self.anchor_after_resize(lines_before_cursor);
 
         // Lines and distance parameters
         let remaining_lines = self.remaining_lines();
@@ -901,8 +908,6 @@ impl Painter {
 
     /// Updates prompt origin and offset to handle a screen resize event
     pub(crate) fn handle_resize(&mut self, width: u16, height: u16) {
-        self.terminal_size = (width, height);
-
         // `cursor::position() is blocking and can timeout.
         // The question is whether we can afford it. If not, perhaps we should use it in some scenarios but not others
         // The problem is trying to calculate this internally doesn't seem to be reliable because terminals might
@@ -915,11 +920,40 @@ impl Painter {
         // I assume this is a bug with the position() call but haven't figured that
         // out yet.
         #[cfg(not(test))]
-        {
-            if let Ok(position) = cursor::position() {
-                self.prompt_start_row = position.1;
-                self.just_resized = true;
-            }
+        let cursor_row = cursor::position().ok().map(|(_, row)| row);
+        #[cfg(test)]
+        let cursor_row = None;
+
+        self.resize(width, height, cursor_row);
+    }
+
+    /// Apply a new screen size given the cursor row reported after the resize.
+    ///
+    /// The terminal may have re-wrapped the previous frame, so the prompt origin
+    /// is recomputed from the cursor row on the next repaint, which clears and
+    /// redraws everything from there with the new width. Without a cursor row the
+    /// old origin is kept, clamped to the new screen.
+    fn resize(&mut self, width: u16, height: u16, cursor_row: Option<u16>) {
+        // A zero sized terminal would break the wrapping arithmetic
+        self.terminal_size = (width.max(1), height.max(1));
+
+        if let Some(row) = cursor_row {
+            self.prompt_start_row = row;
+            self.just_resized = true;
+        }
+        self.prompt_start_row = self.prompt_start_row.min(self.screen_height() - 1);
+    }
+
+    /// Move the prompt origin up from the cursor row reported after a resize,
+    /// so the `lines_before_cursor` rows rewrapped at the new width end on it
+    fn anchor_after_resize(&mut self, lines_before_cursor: u16) {
+        if self.just_resized {
+            self.prompt_start_row = prompt_start_after_resize(
+                self.prompt_start_row,
+                lines_before_cursor,
+                self.screen_height(),
+            );
+            self.just_resized = false;
         }
     }
 
@@ -939,6 +973,17 @@ impl Painter {
         self.stdout.flush()
     }
 
+    /// Sets the clipboard of the terminal to `text` with OSC 52
+    #[cfg(feature = "lsp_diagnostics")]
+    pub(crate) fn copy_to_clipboard(&mut self, text: &str) -> Result<()> {
+        self.stdout
+            .queue(Print(crate::terminal_extensions::osc52::copy_sequence(
+                text,
+            )))?;
+
+        self.stdout.flush()
+    }
+
     /// Clear the screen by printing enough whitespace to start the prompt or
     /// other output back at the first line of the terminal.
     pub(crate) fn clear_screen(&mut self) -> Result<()> {
@@ -1239,6 +1284,30 @@ mod tests {
         assert_eq!(painter.screen_to_buffer_offset(&snapshot, 1, 1), Some(4));
     }
 
+    #[test]
+    fn test_wide_character_mapping() {
+        let mut snapshot = base_snapshot();
+        snapshot.before_cursor = "日本語 ok".to_string();
+
+        let painter = Painter::new(W::new(std::io::stderr()));
+        // Both columns of a wide character map to its start
+        assert_eq!(painter.screen_to_buffer_offset(&snapshot, 4, 0), Some(3));
+        assert_eq!(painter.screen_to_buffer_offset(&snapshot, 5, 0), Some(3));
+        assert_eq!(painter.screen_to_buffer_offset(&snapshot, 9, 0), Some(10));
+    }
+
+    #[test]
+    fn test_wrapped_wide_character_mapping() {
+        let mut snapshot = base_snapshot();
+        snapshot.screen_width = 6;
+        snapshot.before_cursor = "日本語x".to_string();
+
+        let painter = Painter::new(W::new(std::io::stderr()));
+        // "> 日本" fills the first row, "語x" wraps onto the second
+        assert_eq!(painter.screen_to_buffer_offset(&snapshot, 0, 1), Some(6));
+        assert_eq!(painter.screen_to_buffer_offset(&snapshot, 2, 1), Some(9));
+    }
+
     #[test]
     fn test_multiline_mapping() {
         let mut snapshot = base_snapshot();
@@ -1433,4 +1502,83 @@ mod tests {
             ]
         );
     }
+
+    /// Resize the painter with the cursor reported on `row` and anchor the
+    /// prompt the way the next repaint does
+    fn resize_and_anchor(painter: &mut Painter, lines: &PromptLines, size: (u16, u16), row: u16) {
+        painter.resize(size.0, size.1, Some(row));
+        assert!(painter.just_resized);
+        painter.anchor_after_resize(lines.required_lines(painter.screen_width(), true, None));
+        assert!(!painter.just_resized);
+    }
+
+    #[test]
+    fn test_resize_reflows_wrapped_buffer() {
+        let mut painter = make_painter(20, 10, false);
+        // 2 prompt columns + 48 buffer columns
+        let before = "x".repeat(48);
+        let lines = make_lines("> ", "", "", &before, "");
+
+        // 50 columns take 3 rows at width 20
+        resize_and_anchor(&mut painter, &lines, (20, 10), 9);
+        assert_eq!(painter.prompt_start_row, 7);
+
+        // Shrinking to 10 columns needs 5 rows
+        resize_and_anchor(&mut painter, &lines, (10, 10), 9);
+        assert_eq!(painter.prompt_start_row, 5);
+
+        // Growing to 80 columns reclaims the wrapped rows
+        resize_and_anchor(&mut painter, &lines, (80, 10), 5);
+        assert_eq!(painter.prompt_start_row, 5);
+    }
+
+    #[test]
+    fn test_resize_narrower_than_prompt() {
+        let mut painter = make_painter(20, 10, false);
+        let lines = make_lines("a-very-long-prompt> ", "", "", "ls", "");
+
+        resize_and_anchor(&mut painter, &lines, (4, 10), 8);
+
+        // 22 columns wrap into 6 rows of 4, 5 of which belong to the prompt
+        assert_eq!(painter.prompt_start_row, 3);
+        assert_eq!(lines.prompt_lines_with_wrap(painter.screen_width()) + 1, 5);
+    }
+
+    #[test]
+    fn test_resize_to_tiny_height_keeps_prompt_on_screen() {
+        let mut painter = make_painter(20, 10, false);
+        let lines = make_lines("> ", "", "", "line1\nline2\nline3", "");
+
+        resize_and_anchor(&mut painter, &lines, (20, 2), 1);
+        assert_eq!(painter.prompt_start_row, 0);
+
+        resize_and_anchor(&mut painter, &lines, (20, 1), 5);
+        assert_eq!(painter.prompt_start_row, 0);
+    }
+
+    #[test]
+    fn test_resize_to_zero_size_is_clamped() {
+        let mut painter = make_painter(20, 10, false);
+        painter.prompt_start_row = 8;
+
+        painter.resize(0, 0, None);
+
+        assert_eq!((painter.screen_width(), painter.screen_height()), (1, 1));
+        assert_eq!(painter.prompt_start_row, 0);
+        let lines = make_lines("> ", "", "", "hello", "");
+        assert_eq!(lines.required_lines(painter.screen_width(), false, None), 7);
+    }
+
+    #[test]
+    fn test_resize_without_cursor_position_keeps_origin() {
+        let mut painter = make_painter(80, 24, false);
+        painter.prompt_start_row = 12;
+
+        painter.resize(60, 20, None);
+        assert_eq!(painter.prompt_start_row, 12);
+        assert!(!painter.just_resized);
+
+        painter.resize(60, 5, None);
+        assert_eq!(painter.prompt_start_row, 4);
+    }
 }
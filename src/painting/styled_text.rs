@@ -1,12 +1,112 @@
-use nu_ansi_term::Style;
+use std::ops::Range;
+
+use nu_ansi_term::{Color, Style};
+use unicode_width::UnicodeWidthStr;
 
 use crate::terminal_extensions::semantic_prompt::{PromptKind, SemanticPromptMarkers};
 use crate::Prompt;
 
 use super::utils::strip_ansi;
 
+/// Changes to the parts of a [`Style`] that are set, leaving the others as
+/// they are
+///
+/// Used to layer styles over highlighted text, like an underline over the
+/// colors of the highlighter, with [`StyledText::apply_style_over_range`].
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct StyleModifier {
+    foreground: Option<Color>,
+    background: Option<Color>,
+    underline: Option<bool>,
+    attributes: Style,
+}
+
+impl StyleModifier {
+    /// A modifier leaving styles as they are
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// A builder that sets the foreground color
+    #[must_use]
+    pub fn with_foreground(mut self, color: Color) -> Self {
+        self.foreground = Some(color);
+        self
+    }
+
+    /// A builder that sets the background color
+    #[must_use]
+    pub fn with_background(mut self, color: Color) -> Self {
+        self.background = Some(color);
+        self
+    }
+
+    /// A builder that turns the underline on or off
+    #[must_use]
+    pub fn with_underline(mut self, underline: bool) -> Self {
+        self.underline = Some(underline);
+        self
+    }
+
+    /// A builder that adds the attributes of `attributes`, like bold or
+    /// italic, to the ones of the style
+    ///
+    /// The colors of `attributes` are ignored.
+    #[must_use]
+    pub fn with_attributes(mut self, attributes: Style) -> Self {
+        self.attributes = attributes;
+        self
+    }
+
+    /// `style` with the changes of the modifier
+    pub fn apply(&self, style: Style) -> Style {
+        let attributes = self.attributes;
+        Style {
+            foreground: self.foreground.or(style.foreground),
+            background: self.background.or(style.background),
+            is_underline: self
+                .underline
+                .unwrap_or(style.is_underline || attributes.is_underline),
+            is_bold: style.is_bold || attributes.is_bold,
+            is_dimmed: style.is_dimmed || attributes.is_dimmed,
+            is_italic: style.is_italic || attributes.is_italic,
+            is_blink: style.is_blink || attributes.is_blink,
+            is_reverse: style.is_reverse || attributes.is_reverse,
+            is_hidden: style.is_hidden || attributes.is_hidden,
+            is_strikethrough: style.is_strikethrough || attributes.is_strikethrough,
+            ..style
+        }
+    }
+}
+
+/// Sets the colors, the underline and the attributes set in the style
+impl From<Style> for StyleModifier {
+    fn from(style: Style) -> Self {
+        StyleModifier {
+            foreground: style.foreground,
+            background: style.background,
+            underline: style.is_underline.then_some(true),
+            attributes: style,
+        }
+    }
+}
+
+/// How [`StyledText::merge`] combines the styles of two texts
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum MergePolicy {
+    /// The styles of the other text replace the existing ones
+    Replace,
+    /// The parts set in the styles of the other text are layered over the
+    /// existing styles
+    #[default]
+    Overlay,
+    /// The parts set in the existing styles are layered over the styles of
+    /// the other text, which only fill in what is missing
+    Underlay,
+}
+
 /// A representation of a buffer with styling, used for doing syntax highlighting
-#[derive(Clone)]
+#[derive(Debug, Clone)]
 pub struct StyledText {
     /// The component, styled parts of the text
     pub buffer: Vec<(Style, String)>,
@@ -101,6 +201,106 @@ impl StyledText {
         }
     }
 
+    /// Split the parts at the ends of `range`, returning the indices of the
+    /// parts it covers
+    ///
+    /// `range` is a byte range of the text, limited to its length. An end
+    /// inside a multi-byte character is moved out to cover all of it.
+    pub fn split_range(&mut self, range: Range<usize>) -> Range<usize> {
+        let len = self
+            .buffer
+            .iter()
+            .map(|(_, text)| text.len())
+            .sum::<usize>();
+        let start = range.start.min(len);
+        let end = range.end.clamp(start, len);
+
+        let start = self.split_at(start, false);
+        let end = self.split_at(end, true);
+        start..end
+    }
+
+    /// Split the part containing the byte `offset`, moved `forward` or
+    /// backward to the next character boundary, returning the index of the
+    /// part starting there
+    fn split_at(&mut self, offset: usize, forward: bool) -> usize {
+        let mut part_start = 0;
+        for (index, (style, text)) in self.buffer.iter_mut().enumerate() {
+            let part_end = part_start + text.len();
+            if offset < part_end {
+                let mut local = offset - part_start;
+                while !text.is_char_boundary(local) {
+                    if forward {
+                        local += 1;
+                    } else {
+                        local -= 1;
+                    }
+                }
+                if local == 0 {
+                    return index;
+                }
+                if local == text.len() {
+                    return index + 1;
+                }
+                let rest = text.split_off(local);
+                let style = *style;
+                self.buffer.insert(index + 1, (style, rest));
+                return index + 1;
+            }
+            part_start = part_end;
+        }
+        self.buffer.len()
+    }
+
+    /// Change the styles of the byte `range` with `modifier`, keeping what it
+    /// doesn't set
+    ///
+    /// See [`Self::split_range`] for how the range is limited to the text.
+    pub fn apply_style_over_range(&mut self, range: Range<usize>, modifier: StyleModifier) {
+        for index in self.split_range(range) {
+            let part = &mut self.buffer[index];
+            part.0 = modifier.apply(part.0);
+        }
+    }
+
+    /// Combine the styles of `other` with the styles at the same byte offsets
+    /// of this text, as set by `policy`
+    ///
+    /// The text of `other` is only used for its length: both are expected to
+    /// style the same text, like the output of two highlighters for one
+    /// buffer. Parts of `other` past the end of this text are ignored.
+    pub fn merge(&mut self, other: &StyledText, policy: MergePolicy) {
+        let mut offset = 0;
+        for (style, text) in &other.buffer {
+            let range = offset..offset + text.len();
+            offset = range.end;
+            for index in self.split_range(range) {
+                let part = &mut self.buffer[index];
+                part.0 = match policy {
+                    MergePolicy::Replace => *style,
+                    MergePolicy::Overlay => StyleModifier::from(*style).apply(part.0),
+                    MergePolicy::Underlay => StyleModifier::from(part.0).apply(*style),
+                };
+            }
+        }
+    }
+
+    /// The parts of the text with the adjacent ones of the same style joined
+    /// and the empty ones left out, to paint each style once
+    fn runs(&self) -> Vec<(Style, String)> {
+        let mut runs: Vec<(Style, String)> = Vec::with_capacity(self.buffer.len());
+        for (style, text) in &self.buffer {
+            if text.is_empty() {
+                continue;
+            }
+            match runs.last_mut() {
+                Some((last_style, last_text)) if last_style == style => last_text.push_str(text),
+                _ => runs.push((*style, text.clone())),
+            }
+        }
+        runs
+    }
+
     /// Render the styled string. We use the insertion point to render around so that
     /// we can properly write out the styled string to the screen and find the correct
     /// place to put the cursor. This assumes a logic that prints the first part of the
@@ -122,7 +322,7 @@ impl StyledText {
         let multiline_prompt = prompt.render_prompt_multiline_indicator();
         let prompt_style = Style::new().fg(prompt.get_prompt_multiline_color());
 
-        for pair in &self.buffer {
+        for pair in &self.runs() {
             if current_idx >= insertion_point {
                 right_string.push_str(&render_as_string(
                     pair,
@@ -168,7 +368,7 @@ impl StyledText {
 
     /// Apply the ANSI style formatting to the full string.
     pub fn render_simple(&self) -> String {
-        self.buffer
+        self.runs()
             .iter()
             .map(|(style, text)| style.paint(text).to_string())
             .collect()
@@ -178,6 +378,42 @@ impl StyledText {
     pub fn raw_string(&self) -> String {
         self.buffer.iter().map(|(_, str)| str.as_str()).collect()
     }
+
+    /// Render with the ANSI style formatting, or as the unformatted text
+    pub fn render(&self, use_ansi_coloring: bool) -> String {
+        if use_ansi_coloring {
+            self.render_simple()
+        } else {
+            self.raw_string()
+        }
+    }
+
+    /// Width of the text in terminal columns
+    pub fn width(&self) -> usize {
+        self.buffer.iter().map(|(_, text)| text.width()).sum()
+    }
+
+    /// Add the styled parts of `other` at the end
+    pub fn append(&mut self, other: StyledText) {
+        self.buffer.extend(other.buffer);
+    }
+
+    /// Indent the text by `columns` unstyled spaces
+    pub fn pad_left(&mut self, columns: usize) {
+        if columns > 0 {
+            self.buffer.insert(0, (Style::new(), " ".repeat(columns)));
+        }
+    }
+
+    /// Fill the text up with unstyled spaces to be `width` columns wide
+    ///
+    /// Text that is already wider is left as is.
+    pub fn pad_right_to(&mut self, width: usize) {
+        let missing = width.saturating_sub(self.width());
+        if missing > 0 {
+            self.push((Style::new(), " ".repeat(missing)));
+        }
+    }
 }
 
 fn render_as_string(
@@ -214,8 +450,10 @@ fn render_as_string(
 #[cfg(test)]
 mod test {
     use nu_ansi_term::{Color, Style};
+    use pretty_assertions::assert_eq;
+    use rstest::rstest;
 
-    use crate::StyledText;
+    use crate::{MergePolicy, StyleModifier, StyledText};
 
     fn get_styled_text_template() -> (super::StyledText, Style, Style) {
         let before_style = Style::new().on(Color::Black);
@@ -312,6 +550,208 @@ mod test {
         assert_eq!(styled_text.buffer[4], (before_style, "n".into()));
     }
 
+    fn parts(styled_text: &StyledText) -> Vec<(Style, &str)> {
+        styled_text
+            .buffer
+            .iter()
+            .map(|(style, text)| (*style, text.as_str()))
+            .collect()
+    }
+
+    #[rstest]
+    #[case::inside_a_part(1..2, &["a", "a", "a", "bbb", "ccc"], 1..2)]
+    #[case::whole_part(3..6, &["aaa", "bbb", "ccc"], 1..2)]
+    #[case::over_a_boundary(2..4, &["aa", "a", "b", "bb", "ccc"], 1..3)]
+    #[case::over_several_parts(1..8, &["a", "aa", "bbb", "cc", "c"], 1..4)]
+    #[case::everything(0..9, &["aaa", "bbb", "ccc"], 0..3)]
+    #[case::empty(4..4, &["aaa", "b", "bb", "ccc"], 2..2)]
+    #[case::past_the_end(7..20, &["aaa", "bbb", "c", "cc"], 3..4)]
+    fn range_is_split_into_parts(
+        #[case] range: std::ops::Range<usize>,
+        #[case] expected_parts: &[&str],
+        #[case] expected_indices: std::ops::Range<usize>,
+    ) {
+        let (mut styled_text, before_style, _) = get_styled_text_template();
+
+        assert_eq!(styled_text.split_range(range), expected_indices);
+        let expected: Vec<(Style, &str)> = expected_parts
+            .iter()
+            .map(|&text| (before_style, text))
+            .collect();
+        assert_eq!(parts(&styled_text), expected);
+    }
+
+    #[rstest]
+    #[case::start_inside_a_character(2..4, "验")]
+    #[case::end_inside_a_character(0..2, "a验")]
+    #[case::both_inside_one_character(2..3, "验")]
+    #[case::both_inside_characters(2..5, "验证")]
+    fn range_ends_inside_characters_cover_them(
+        #[case] range: std::ops::Range<usize>,
+        #[case] expected: &str,
+    ) {
+        let mut styled_text = StyledText::new();
+        styled_text.push((Style::new(), "a验证b".into()));
+
+        styled_text.apply_style_over_range(range, StyleModifier::new().with_underline(true));
+
+        let underlined: String = styled_text
+            .buffer
+            .iter()
+            .filter(|(style, _)| style.is_underline)
+            .map(|(_, text)| text.as_str())
+            .collect();
+        assert_eq!(underlined, expected);
+        assert_eq!(styled_text.raw_string(), "a验证b");
+    }
+
+    #[test]
+    fn modifier_keeps_what_it_does_not_set() {
+        let style = Style::new().fg(Color::Red).on(Color::Black).italic();
+
+        assert_eq!(StyleModifier::new().apply(style), style);
+        assert_eq!(
+            StyleModifier::new()
+                .with_underline(true)
+                .with_attributes(Style::new().bold())
+                .apply(style),
+            style.underline().bold()
+        );
+        assert_eq!(
+            StyleModifier::new()
+                .with_foreground(Color::Green)
+                .apply(style),
+            Style::new().fg(Color::Green).on(Color::Black).italic()
+        );
+        assert_eq!(
+            StyleModifier::new()
+                .with_background(Color::Blue)
+                .with_underline(false)
+                .apply(style.underline()),
+            Style::new().fg(Color::Red).on(Color::Blue).italic()
+        );
+        assert_eq!(
+            StyleModifier::from(Style::new().underline()).apply(style),
+            style.underline()
+        );
+    }
+
+    #[test]
+    fn overlapping_modifiers_stack() {
+        let red = Style::new().fg(Color::Red);
+        let mut styled_text = StyledText::new();
+        styled_text.push((red, "let x".into()));
+        styled_text.push((Style::new(), " = 1".into()));
+
+        styled_text.apply_style_over_range(0..7, StyleModifier::new().with_underline(true));
+        styled_text.apply_style_over_range(4..9, StyleModifier::new().with_background(Color::Blue));
+
+        assert_eq!(
+            parts(&styled_text),
+            [
+                (red.underline(), "let "),
+                (red.underline().on(Color::Blue), "x"),
+                (Style::new().underline().on(Color::Blue), " ="),
+                (Style::new().on(Color::Blue), " 1"),
+            ]
+        );
+    }
+
+    #[rstest]
+    #[case::replace(
+        MergePolicy::Replace,
+        Style::new().fg(Color::Red).underline(),
+        Style::new()
+    )]
+    #[case::overlay(
+        MergePolicy::Overlay,
+        Style::new().fg(Color::Red).underline(),
+        Style::new().fg(Color::Green)
+    )]
+    #[case::underlay(
+        MergePolicy::Underlay,
+        Style::new().fg(Color::Green).underline(),
+        Style::new().fg(Color::Green)
+    )]
+    fn merged_styles_follow_the_policy(
+        #[case] policy: MergePolicy,
+        #[case] expected_underlined: Style,
+        #[case] expected_rest: Style,
+    ) {
+        let mut highlighted = StyledText::new();
+        highlighted.push((Style::new().fg(Color::Green), "ls -la".into()));
+        let mut diagnostics = StyledText::new();
+        diagnostics.push((Style::new(), "ls ".into()));
+        diagnostics.push((Style::new().fg(Color::Red).underline(), "-l".into()));
+        diagnostics.push((Style::new(), "a and more".into()));
+
+        let mut merged = highlighted.clone();
+        merged.merge(&diagnostics, policy);
+
+        assert_eq!(
+            parts(&merged),
+            [
+                (expected_rest, "ls "),
+                (expected_underlined, "-l"),
+                (expected_rest, "a"),
+            ]
+        );
+        assert_eq!(merged.raw_string(), highlighted.raw_string());
+    }
+
+    #[test]
+    fn adjacent_parts_of_one_style_are_painted_once() {
+        let red = Style::new().fg(Color::Red);
+        let mut styled_text = StyledText::new();
+        styled_text.push((red, "cargo".into()));
+        styled_text.push((red, String::new()));
+        styled_text.push((red, " run".into()));
+        styled_text.push((Style::new(), " --".into()));
+        styled_text.apply_style_over_range(1..3, StyleModifier::new());
+
+        assert_eq!(
+            styled_text.render_simple(),
+            format!("{} --", red.paint("cargo run"))
+        );
+
+        let prompt = crate::DefaultPrompt::default();
+        let (before, after) = styled_text.render_around_insertion_point(7, &prompt, true, None);
+        assert_eq!(before, red.paint("cargo r").to_string());
+        assert_eq!(after, format!("{} --", red.paint("un")));
+    }
+
+    #[test]
+    fn padding_aligns_by_display_width() {
+        let mut styled_text = StyledText::new();
+        styled_text.push((Style::new().bold(), "验证".into()));
+        styled_text.push((Style::new(), "ab".into()));
+        assert_eq!(styled_text.width(), 6);
+
+        styled_text.pad_left(2);
+        styled_text.pad_right_to(10);
+        assert_eq!(styled_text.raw_string(), "  验证ab  ");
+        assert_eq!(styled_text.width(), 10);
+
+        styled_text.pad_right_to(4);
+        assert_eq!(styled_text.width(), 10);
+    }
+
+    #[test]
+    fn render_strips_the_styles_without_ansi_coloring() {
+        let style = Style::new().fg(Color::Red);
+        let mut styled_text = StyledText::new();
+        styled_text.push((style, "err".into()));
+        let mut note = StyledText::new();
+        note.push((Style::new(), ": oops".into()));
+        styled_text.append(note);
+
+        assert_eq!(styled_text.render(false), "err: oops");
+        assert_eq!(
+            styled_text.render(true),
+            format!("{}: oops", style.paint("err"))
+        );
+    }
+
     #[test]
     fn test_render_multiline_without_semantic_markers() {
         let style = Style::new();
@@ -1,4 +1,5 @@
 use {
+    crate::HistoryNavigationScope,
     crossterm::style::Color,
     serde::{Deserialize, Serialize},
     std::{
@@ -30,6 +31,13 @@ pub struct PromptHistorySearch {
 
     /// The search term used during the search
     pub term: String,
+
+    /// The commands the search covers
+    pub scope: HistoryNavigationScope,
+
+    /// The position of the shown match among the matches, counting from 1,
+    /// and their number, if known
+    pub position: Option<(usize, usize)>,
 }
 
 impl PromptHistorySearch {
@@ -38,8 +46,25 @@ impl PromptHistorySearch {
         PromptHistorySearch {
             status,
             term: search_term,
+            scope: HistoryNavigationScope::Global,
+            position: None,
         }
     }
+
+    /// A builder that sets the commands the search covers
+    #[must_use]
+    pub const fn with_scope(mut self, scope: HistoryNavigationScope) -> Self {
+        self.scope = scope;
+        self
+    }
+
+    /// A builder that sets the position of the shown match among `total`
+    /// matches, counting from 1
+    #[must_use]
+    pub const fn with_position(mut self, index: usize, total: usize) -> Self {
+        self.position = Some((index, total));
+        self
+    }
 }
 
 /// Modes that the prompt can be in
@@ -91,6 +116,12 @@ pub trait Prompt: Send {
     fn render_prompt_right(&self) -> Cow<'_, str>;
     /// Render the prompt indicator (Last part of the prompt that changes based on the editor mode)
     fn render_prompt_indicator(&self, prompt_mode: PromptEditMode) -> Cow<'_, str>;
+    /// Render the prompt indicator while overwrite mode is on
+    ///
+    /// Defaults to the regular indicator for `prompt_mode`.
+    fn render_prompt_overwrite_indicator(&self, prompt_mode: PromptEditMode) -> Cow<'_, str> {
+        self.render_prompt_indicator(prompt_mode)
+    }
     /// Indicator to show before explicit new lines
     fn render_prompt_multiline_indicator(&self) -> Cow<'_, str>;
     /// Render the prompt indicator for `Ctrl-R` history search
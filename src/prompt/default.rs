@@ -1,4 +1,7 @@
-use crate::{Prompt, PromptEditMode, PromptHistorySearch, PromptHistorySearchStatus, PromptViMode};
+use crate::{
+    HistoryNavigationScope, Prompt, PromptEditMode, PromptHistorySearch, PromptHistorySearchStatus,
+    PromptViMode,
+};
 
 use {
     chrono::Local,
@@ -10,6 +13,7 @@ pub static DEFAULT_PROMPT_INDICATOR: &str = "〉";
 pub static DEFAULT_VI_INSERT_PROMPT_INDICATOR: &str = ": ";
 pub static DEFAULT_VI_NORMAL_PROMPT_INDICATOR: &str = "〉";
 pub static DEFAULT_MULTILINE_INDICATOR: &str = "::: ";
+pub static DEFAULT_OVERWRITE_PROMPT_INDICATOR: &str = "[ovr]〉";
 
 /// Simple [`Prompt`] displaying a configurable left and a right prompt.
 /// For more fine-tuned configuration, implement the [`Prompt`] trait.
@@ -70,6 +74,10 @@ impl Prompt for DefaultPrompt {
         }
     }
 
+    fn render_prompt_overwrite_indicator(&self, _edit_mode: PromptEditMode) -> Cow<'_, str> {
+        DEFAULT_OVERWRITE_PROMPT_INDICATOR.into()
+    }
+
     fn render_prompt_multiline_indicator(&self) -> Cow<'_, str> {
         Cow::Borrowed(DEFAULT_MULTILINE_INDICATOR)
     }
@@ -82,11 +90,21 @@ impl Prompt for DefaultPrompt {
             PromptHistorySearchStatus::Passing => "",
             PromptHistorySearchStatus::Failing => "failing ",
         };
+        let scope = match history_search.scope {
+            HistoryNavigationScope::Global => "",
+            HistoryNavigationScope::Session => " in session",
+            HistoryNavigationScope::Directory { exact: true } => " in directory",
+            HistoryNavigationScope::Directory { exact: false } => " in directory tree",
+        };
+        let position = history_search
+            .position
+            .map(|(index, total)| format!(" [{index}/{total}]"))
+            .unwrap_or_default();
         // NOTE: magic strings, given there is logic on how these compose I am not sure if it
         // is worth extracting in to static constant
         Cow::Owned(format!(
-            "({}reverse-search: {}) ",
-            prefix, history_search.term
+            "({}reverse-search{}{}: {}) ",
+            prefix, scope, position, history_search.term
         ))
     }
 }
@@ -13,6 +13,9 @@ impl BracketedPasteGuard {
     pub fn set(&mut self, enable: bool) {
         self.enabled = enable;
     }
+    pub fn is_active(&self) -> bool {
+        self.active
+    }
     pub fn enter(&mut self) {
         if self.enabled && !self.active {
             let _ = execute!(std::io::stdout(), event::EnableBracketedPaste);
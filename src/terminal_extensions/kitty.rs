@@ -31,6 +31,9 @@ impl KittyProtocolGuard {
 
         self.enabled = enable && self.support_kitty_protocol.unwrap_or(false);
     }
+    pub fn is_active(&self) -> bool {
+        self.active
+    }
     pub fn enter(&mut self) {
         if self.enabled && !self.active {
             let _ = execute!(
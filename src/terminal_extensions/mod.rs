@@ -1,6 +1,9 @@
 pub(crate) mod bracketed_paste;
 pub(crate) mod kitty;
+pub(crate) mod mouse_capture;
+pub(crate) mod osc52;
 pub mod semantic_prompt;
+pub(crate) mod suspend;
 
 /// Return if the terminal supports the kitty keyboard enhancement protocol
 ///
@@ -0,0 +1,43 @@
+use crossterm::{event, execute};
+
+/// Helper managing proper setup and teardown of terminal mouse capture
+///
+/// While capture is active the terminal reports clicks, drags and scrolling
+/// to the application instead of handling native text selection.
+#[derive(Default)]
+pub(crate) struct MouseCaptureGuard {
+    enabled: bool,
+    active: bool,
+}
+
+impl MouseCaptureGuard {
+    pub fn set(&mut self, enable: bool) {
+        self.enabled = enable;
+    }
+    pub fn enabled(&self) -> bool {
+        self.enabled
+    }
+    pub fn is_active(&self) -> bool {
+        self.active
+    }
+    pub fn enter(&mut self) {
+        if self.enabled && !self.active {
+            let _ = execute!(std::io::stdout(), event::EnableMouseCapture);
+            self.active = true;
+        }
+    }
+    pub fn exit(&mut self) {
+        if self.active {
+            let _ = execute!(std::io::stdout(), event::DisableMouseCapture);
+            self.active = false;
+        }
+    }
+}
+
+impl Drop for MouseCaptureGuard {
+    fn drop(&mut self) {
+        if self.active {
+            let _ = execute!(std::io::stdout(), event::DisableMouseCapture);
+        }
+    }
+}
@@ -0,0 +1,57 @@
+//! Copying text to the clipboard of the terminal with OSC 52.
+//!
+//! The terminal, not the machine reedline runs on, receives the text, so
+//! copying works over SSH too. Terminals may ignore the sequence or ask the
+//! user before allowing it.
+
+const BASE64_ALPHABET: &[u8; 64] =
+    b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+/// The sequence setting the clipboard of the terminal to `text`
+#[cfg_attr(not(feature = "lsp_diagnostics"), allow(dead_code))]
+pub(crate) fn copy_sequence(text: &str) -> String {
+    format!("\x1b]52;c;{}\x1b\\", base64(text.as_bytes()))
+}
+
+fn base64(bytes: &[u8]) -> String {
+    let mut encoded = String::with_capacity((bytes.len() + 2) / 3 * 4);
+    for chunk in bytes.chunks(3) {
+        let group = chunk.iter().enumerate().fold(0u32, |group, (i, &byte)| {
+            group | u32::from(byte) << (16 - 8 * i)
+        });
+        for i in 0..4 {
+            if i <= chunk.len() {
+                let index = (group >> (18 - 6 * i)) & 0x3f;
+                encoded.push(BASE64_ALPHABET[index as usize] as char);
+            } else {
+                encoded.push('=');
+            }
+        }
+    }
+    encoded
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+    use rstest::rstest;
+
+    #[rstest]
+    #[case::empty("", "")]
+    #[case::one_padding("ab", "YWI=")]
+    #[case::two_paddings("a", "YQ==")]
+    #[case::no_padding("abc", "YWJj")]
+    #[case::unicode("naïve", "bmHDr3Zl")]
+    fn text_is_base64_encoded(#[case] text: &str, #[case] expected: &str) {
+        assert_eq!(base64(text.as_bytes()), expected);
+    }
+
+    #[test]
+    fn sequence_is_terminated_with_st() {
+        assert_eq!(
+            copy_sequence("unused variable"),
+            "\x1b]52;c;dW51c2VkIHZhcmlhYmxl\x1b\\"
+        );
+    }
+}
@@ -0,0 +1,24 @@
+//! Job control support for suspending the line editor (Ctrl+Z)
+
+/// Terminal modes that were active before suspending, restored on resume
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct TerminalModes {
+    pub raw_mode: bool,
+    pub bracketed_paste: bool,
+    pub kitty_protocol: bool,
+    pub mouse_capture: bool,
+}
+
+/// Stop the foreground process group with `SIGTSTP`.
+///
+/// When the signal targets the calling process it is delivered before `kill`
+/// returns, so this only returns once the job has been resumed with `SIGCONT`.
+#[cfg(unix)]
+pub(crate) fn stop_process_group() -> std::io::Result<()> {
+    // SAFETY: `kill` has no memory safety preconditions
+    if unsafe { libc::kill(0, libc::SIGTSTP) } == 0 {
+        Ok(())
+    } else {
+        Err(std::io::Error::last_os_error())
+    }
+}
@@ -1,53 +1,117 @@
-use crate::{ValidationResult, Validator};
+use crate::{
+    highlighter::{scan_brackets, Bracket},
+    ValidationResult, Validator,
+};
 
 /// A default validator which checks for mismatched quotes and brackets
+///
+/// Unclosed quotes and brackets make the input incomplete, a closing bracket
+/// of another kind than the innermost open one makes it invalid. Closing
+/// brackets without any open bracket, like in `echo :)`, are ignored.
 pub struct DefaultValidator;
 
 impl Validator for DefaultValidator {
     fn validate(&self, line: &str) -> ValidationResult {
-        if line.split('"').count() % 2 == 0 || incomplete_brackets(line) {
-            ValidationResult::Incomplete
-        } else {
-            ValidationResult::Complete
+        if line.split('"').count() % 2 == 0 {
+            return ValidationResult::Incomplete;
+        }
+        match unbalanced_bracket(line) {
+            Some(Unbalanced::Unclosed) => ValidationResult::Incomplete,
+            Some(Unbalanced::Unexpected { found, open }) => ValidationResult::Invalid {
+                message: format!("Unexpected `{found}`, `{open}` is not closed yet"),
+            },
+            None => ValidationResult::Complete,
         }
     }
 }
 
-fn incomplete_brackets(line: &str) -> bool {
-    let mut balance: Vec<char> = Vec::new();
-
-    for c in line.chars() {
-        if c == '{' {
-            balance.push('}');
-        } else if c == '[' {
-            balance.push(']');
-        } else if c == '(' {
-            balance.push(')');
-        } else if ['}', ']', ')'].contains(&c) {
-            if let Some(last) = balance.last() {
-                if last == &c {
-                    balance.pop();
+/// The first problem with the brackets of a line
+#[derive(Debug, PartialEq, Eq)]
+enum Unbalanced {
+    /// A closing bracket that doesn't close the innermost open bracket
+    Unexpected { found: char, open: char },
+    /// Brackets left open at the end of the line
+    Unclosed,
+}
+
+/// Check the brackets of `line` as the highlighter sees them, outside of
+/// quotes and not escaped with `\`
+///
+/// Closing brackets while none is open are left for the command to handle.
+fn unbalanced_bracket(line: &str) -> Option<Unbalanced> {
+    let bracket_at = |offset: usize| line[offset..].chars().next().unwrap_or_default();
+    let mut open: Vec<usize> = Vec::new();
+    let mut unbalanced = None;
+
+    scan_brackets(line, |bracket| {
+        if unbalanced.is_some() {
+            return;
+        }
+        match bracket {
+            Bracket::Open { offset, .. } => open.push(offset),
+            Bracket::Close {
+                opener: Some(_), ..
+            } => {
+                open.pop();
+            }
+            Bracket::Close {
+                offset,
+                opener: None,
+                ..
+            } => {
+                if let Some(&opener) = open.last() {
+                    unbalanced = Some(Unbalanced::Unexpected {
+                        found: bracket_at(offset),
+                        open: bracket_at(opener),
+                    });
                 }
             }
+            Bracket::Unclosed { .. } => unbalanced = Some(Unbalanced::Unclosed),
         }
-    }
+    });
 
-    !balance.is_empty()
+    unbalanced
 }
 
 #[cfg(test)]
 mod test {
     use super::*;
+    use pretty_assertions::assert_eq;
     use rstest::rstest;
 
     #[rstest]
-    #[case("(([[]]))", false)]
-    #[case("(([[]]", true)]
-    #[case("{[}]", true)]
-    #[case("{[]}{()}", false)]
-    fn test_incomplete_brackets(#[case] input: &str, #[case] expected: bool) {
-        let result = incomplete_brackets(input);
+    #[case("(([[]]))", None)]
+    #[case("(([[]]", Some(Unbalanced::Unclosed))]
+    #[case("{[}]", Some(Unbalanced::Unexpected { found: '}', open: '[' }))]
+    #[case("{[]}{()}", None)]
+    #[case("echo :)", None)]
+    #[case("git commit -m fix)", None)]
+    #[case("echo :) (", Some(Unbalanced::Unclosed))]
+    #[case("(echo :] )", Some(Unbalanced::Unexpected { found: ']', open: '(' }))]
+    #[case("echo \":)\" \"(\"", None)]
+    #[case("echo ':)' '('", None)]
+    #[case("echo `:)`", None)]
+    #[case("echo 'a\\' )", None)]
+    #[case("[echo 'a\\' )", Some(Unbalanced::Unexpected { found: ')', open: '[' }))]
+    #[case("echo \\) \\[", None)]
+    #[case("echo \"\\\"(\"", None)]
+    fn test_unbalanced_bracket(#[case] input: &str, #[case] expected: Option<Unbalanced>) {
+        let result = unbalanced_bracket(input);
 
         assert_eq!(result, expected);
     }
+
+    #[rstest]
+    #[case("ls", ValidationResult::Complete)]
+    #[case("echo \"a", ValidationResult::Incomplete)]
+    #[case("if (true) {", ValidationResult::Incomplete)]
+    #[case(
+        "[1, 2)",
+        ValidationResult::Invalid { message: "Unexpected `)`, `[` is not closed yet".to_string() }
+    )]
+    #[case("ls }", ValidationResult::Complete)]
+    #[case("echo hi :)", ValidationResult::Complete)]
+    fn unexpected_brackets_are_reported(#[case] input: &str, #[case] expected: ValidationResult) {
+        assert_eq!(DefaultValidator.validate(input), expected);
+    }
 }
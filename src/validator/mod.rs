@@ -8,7 +8,7 @@ pub trait Validator: Send {
     fn validate(&self, line: &str) -> ValidationResult;
 }
 
-#[derive(Clone, Copy)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 /// Whether or not the validation shows the input was complete
 pub enum ValidationResult {
     /// An incomplete input which may need to span multiple lines to be complete
@@ -16,4 +16,14 @@ pub enum ValidationResult {
 
     /// An input that is complete as-is
     Complete,
+
+    /// An input that can't be submitted and won't become complete on more
+    /// lines, e.g. with a bracket closing the wrong opener
+    ///
+    /// Enter keeps the line as it is, without a newline, and shows the
+    /// message below the prompt until the next edit.
+    Invalid {
+        /// Why the input can't be submitted
+        message: String,
+    },
 }